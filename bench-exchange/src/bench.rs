@@ -7,6 +7,7 @@ use rand::{thread_rng, Rng};
 use rayon::prelude::*;
 use morgan::createKeys::GenKeys;
 use morgan_client::perf_utils::{sample_txs, SampleStats};
+use morgan_client::retry_policy::RetryPolicy;
 use morgan_tokenbot::drone::request_airdrop_transaction;
 use morgan_exchange_api::exchange_instruction;
 use morgan_exchange_api::exchange_state::*;
@@ -14,6 +15,7 @@ use morgan_exchange_api::id;
 use morgan_metricbot::datapoint_info;
 use morgan_interface::client::Client;
 use morgan_interface::client::SyncClient;
+use morgan_interface::message::Message;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil};
 use morgan_interface::system_instruction;
@@ -22,6 +24,7 @@ use morgan_interface::transaction::Transaction;
 use morgan_helper::logHelper::*;
 use std::cmp;
 use std::collections::VecDeque;
+use std::io;
 use std::mem;
 use std::net::SocketAddr;
 use std::process::exit;
@@ -246,6 +249,8 @@ where
 
     create_token_accounts(client, &swapper_signers, &profit_pubkeys);
 
+    let registry = create_pair_registry(client, &identity, &[TokenPair::AB]);
+
     // Collect the max transaction rate and total tx count seen (single node only)
     let sample_stats = Arc::new(RwLock::new(Vec::new()));
     let sample_period = 1; // in seconds
@@ -346,6 +351,7 @@ where
                     &shared_txs,
                     &trader_signers,
                     &src_pubkeys,
+                    &registry,
                     transfer_delay,
                     batch_size,
                     chunk_size,
@@ -603,6 +609,7 @@ fn swapper<T>(
                             &swap.0.pubkey,
                             &swap.1.pubkey,
                             &profit,
+                            &swap.0.info.registry,
                         )],
                         blockhash,
                     )
@@ -688,6 +695,7 @@ fn trader<T>(
     shared_txs: &SharedTransactions,
     signers: &[Arc<Keypair>],
     srcs: &[Pubkey],
+    registry: &Pubkey,
     transfer_delay: u64,
     batch_size: usize,
     chunk_size: usize,
@@ -734,6 +742,10 @@ fn trader<T>(
                 tokens,
                 price,
                 tokens_settled: 0,
+                registry: *registry,
+                displayed_tokens: None,
+                self_trade_prevention: None,
+                tick_height: 0,
             };
             trade_infos.push(TradeInfo {
                 trade_account: trade.pubkey(),
@@ -759,7 +771,7 @@ fn trader<T>(
                         vec![
                             system_instruction::create_account(owner, trade, 1, space, &id()),
                             exchange_instruction::trade_request(
-                                owner, trade, *direction, pair, tokens, price, src,
+                                owner, trade, *direction, pair, tokens, price, src, registry,
                             ),
                         ],
                         blockhash,
@@ -1013,6 +1025,30 @@ pub fn fund_keys(client: &Client, source: &Keypair, dests: &[Arc<Keypair>], difs
     }
 }
 
+/// Create a pair registry administered by `admin` with each of `pairs` listed at a tick
+/// and minimum order size of 1
+pub fn create_pair_registry(client: &Client, admin: &Keypair, pairs: &[TokenPair]) -> Pubkey {
+    let registry = Keypair::new().pubkey();
+    let space = mem::size_of::<ExchangeState>() as u64;
+    let mut instructions = vec![
+        system_instruction::create_account(&admin.pubkey(), &registry, 1, space, &id()),
+        exchange_instruction::pair_registry_request(&admin.pubkey(), &registry),
+    ];
+    for pair in pairs {
+        instructions.push(exchange_instruction::list_pair(
+            &admin.pubkey(),
+            &registry,
+            *pair,
+            1,
+            1,
+        ));
+    }
+    client
+        .send_message(&[admin], Message::new(instructions))
+        .expect("Failed to create pair registry");
+    registry
+}
+
 pub fn create_token_accounts(client: &Client, signers: &[Arc<Keypair>], accounts: &[Pubkey]) {
     let mut notfunded: Vec<(&Arc<Keypair>, &Pubkey)> = signers.iter().zip(accounts).collect();
 
@@ -1250,46 +1286,48 @@ pub fn airdrop_difs(client: &Client, drone_addr: &SocketAddr, id: &Keypair, amou
         )
     );
 
-    let mut tries = 0;
-    loop {
+    let policy = RetryPolicy {
+        base_delay: Duration::from_millis(500),
+        max_delay: Duration::from_secs(2),
+        max_attempts: 50,
+        deadline: Duration::from_secs(120),
+        ..RetryPolicy::default()
+    };
+    let result = policy.retry(|| {
         let (blockhash, _fee_calculator) = client
             .get_recent_blockhash()
             .expect("Failed to get blockhash");
-        match request_airdrop_transaction(&drone_addr, &id.pubkey(), amount_to_drop, blockhash) {
-            Ok(transaction) => {
-                let signature = client.async_send_transaction(transaction).unwrap();
+        let transaction =
+            request_airdrop_transaction(&drone_addr, &id.pubkey(), amount_to_drop, blockhash)?;
+        let signature = client.async_send_transaction(transaction).unwrap();
 
-                for _ in 0..30 {
-                    if let Ok(Some(_)) = client.get_signature_status(&signature) {
-                        break;
-                    }
-                    sleep(Duration::from_millis(100));
-                }
-                if client.get_balance(&id.pubkey()).unwrap_or(0) >= amount {
-                    break;
-                }
-            }
-            Err(err) => {
-                panic!(
-                    "Error requesting airdrop: {:?} to addr: {:?} amount: {}",
-                    err, drone_addr, amount
-                );
+        for _ in 0..30 {
+            if let Ok(Some(_)) = client.get_signature_status(&signature) {
+                break;
             }
-        };
-        debug!("  Retry...");
-        tries += 1;
-        if tries > 50 {
-            // error!("{}", Error(format!("Too many retries, give up").to_string()));
-            println!(
-                "{}",
-                Error(
-                    format!("Too many retries, give up").to_string(),
-                    module_path!().to_string()
-                )
-            );
-            exit(1);
+            sleep(Duration::from_millis(100));
+        }
+        let balance = client.get_balance(&id.pubkey()).unwrap_or(0);
+        if balance >= amount {
+            Ok(())
+        } else {
+            debug!("  Retry...");
+            Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("airdrop not confirmed, balance {}", balance),
+            ))
         }
-        sleep(Duration::from_secs(2));
+    });
+    if let Err(err) = result {
+        // error!("{}", Error(format!("Too many retries, give up").to_string()));
+        println!(
+            "{}",
+            Error(
+                format!("Airdrop to {} {}, give up", id.pubkey(), err),
+                module_path!().to_string()
+            )
+        );
+        exit(1);
     }
 }
 
@@ -1,5 +1,6 @@
 use clap::{crate_description, crate_name, crate_version, value_t, App, Arg, ArgMatches};
 use morgan::createKeys::GenKeys;
+use morgan_client::paced_sender::PacingConfig;
 use morgan_tokenbot::drone::DRONE_PORT;
 use morgan_interface::signature::{read_keypair, Keypair, KeypairUtil};
 use std::net::SocketAddr;
@@ -18,6 +19,7 @@ pub struct Config {
     pub batch_size: usize,
     pub chunk_size: usize,
     pub account_groups: usize,
+    pub pacing: PacingConfig,
 }
 
 impl Default for Config {
@@ -34,6 +36,7 @@ impl Default for Config {
             batch_size: 100,
             chunk_size: 100,
             account_groups: 100,
+            pacing: PacingConfig::default(),
         }
     }
 }
@@ -141,6 +144,23 @@ pub fn build_args<'a, 'b>() -> App<'a, 'b> {
                 .default_value("10")
                 .help("Number of account groups to cycle for each batch"),
         )
+        .arg(
+            Arg::with_name("packets-per-second")
+                .long("packets-per-second")
+                .value_name("<pps>")
+                .takes_value(true)
+                .required(false)
+                .default_value("0")
+                .help("Most transactions sent to each node per second, 0 is unlimited"),
+        )
+        .arg(
+            Arg::with_name("burst")
+                .long("burst")
+                .value_name("<burst>")
+                .takes_value(true)
+                .required(false)
+                .help("Transactions sent back to back to a node before packets-per-second applies"),
+        )
 }
 
 pub fn extract_args<'a>(matches: &ArgMatches<'a>) -> Config {
@@ -183,6 +203,12 @@ pub fn extract_args<'a>(matches: &ArgMatches<'a>) -> Config {
         value_t!(matches.value_of("chunk-size"), usize).expect("Failed to parse chunk-size");
     args.account_groups = value_t!(matches.value_of("account-groups"), usize)
         .expect("Failed to parse account-groups");
+    args.pacing.packets_per_second = value_t!(matches.value_of("packets-per-second"), u64)
+        .expect("Failed to parse packets-per-second");
+    if matches.is_present("burst") {
+        args.pacing.burst =
+            value_t!(matches.value_of("burst"), u64).expect("Failed to parse burst");
+    }
 
     args
 }
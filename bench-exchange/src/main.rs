@@ -32,6 +32,7 @@ fn main() {
         batch_size,
         chunk_size,
         account_groups,
+        pacing,
         ..
     } = cli_config;
 
@@ -50,7 +51,10 @@ fn main() {
             panic!("Failed to discover nodes");
         });
 
-    let clients = get_clients(&nodes);
+    let mut clients = get_clients(&nodes);
+    for client in &mut clients {
+        client.set_pacing(pacing);
+    }
 
     // info!("{}",
     //         Info(format!("{} nodes found", clients.len()).to_string()));
@@ -1,11 +1,10 @@
 use clap::{crate_description, crate_name, crate_version, App, Arg};
 use morgan::packet::{Packet, Packets, BLOB_SIZE, PACKET_DATA_SIZE};
 use morgan::result::Result;
-use morgan::streamer::{receiver, PacketReceiver};
+use morgan::streamer::{queue_channel, receiver, PacketReceiver, MAX_QUEUED_PACKET_BATCHES};
 use std::cmp::max;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::channel;
 use std::sync::Arc;
 use std::thread::sleep;
 use std::thread::{spawn, JoinHandle};
@@ -81,7 +80,7 @@ fn main() -> Result<()> {
         addr = read.local_addr().unwrap();
         port = addr.port();
 
-        let (s_reader, r_reader) = channel();
+        let (s_reader, r_reader) = queue_channel("bench-streamer", MAX_QUEUED_PACKET_BATCHES);
         read_channels.push(r_reader);
         read_threads.push(receiver(Arc::new(read), &exit, s_reader));
     }
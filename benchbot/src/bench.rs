@@ -4,7 +4,8 @@ use log::*;
 use rayon::prelude::*;
 use morgan::createKeys::GenKeys;
 use morgan_client::perf_utils::{sample_txs, SampleStats};
-use morgan_tokenbot::drone::request_airdrop_transaction;
+use morgan_client::retry_policy::RetryPolicy;
+use morgan_tokenbot::drone::request_airdrop_transaction_with_retry;
 use morgan_metricbot::datapoint_info;
 use morgan_interface::client::Client;
 use morgan_interface::hash::Hash;
@@ -459,7 +460,13 @@ pub fn airdrop_difs<T: Client>(
         );
 
         let (blockhash, _fee_calculator) = client.get_recent_blockhash().unwrap();
-        match request_airdrop_transaction(&drone_addr, &id.pubkey(), airdrop_amount, blockhash) {
+        match request_airdrop_transaction_with_retry(
+            &drone_addr,
+            &id.pubkey(),
+            airdrop_amount,
+            blockhash,
+            &RetryPolicy::default(),
+        ) {
             Ok(transaction) => {
                 let signature = client.async_send_transaction(transaction).unwrap();
                 client
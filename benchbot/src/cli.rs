@@ -3,6 +3,7 @@ use std::process::exit;
 use std::time::Duration;
 
 use clap::{crate_description, crate_name, crate_version, App, Arg, ArgMatches};
+use morgan_client::paced_sender::PacingConfig;
 use morgan_tokenbot::drone::DRONE_PORT;
 use morgan_interface::signature::{read_keypair, Keypair, KeypairUtil};
 
@@ -17,6 +18,7 @@ pub struct Config {
     pub tx_count: usize,
     pub thread_batch_sleep_ms: usize,
     pub sustained: bool,
+    pub pacing: PacingConfig,
 }
 
 impl Default for Config {
@@ -31,6 +33,7 @@ impl Default for Config {
             tx_count: 500_000,
             thread_batch_sleep_ms: 0,
             sustained: false,
+            pacing: PacingConfig::default(),
         }
     }
 }
@@ -106,6 +109,20 @@ pub fn build_args<'a, 'b>() -> App<'a, 'b> {
                 .takes_value(true)
                 .help("Per-thread-per-iteration sleep in ms"),
         )
+        .arg(
+            Arg::with_name("packets-per-second")
+                .long("packets-per-second")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Most transactions sent to each node per second; default is as fast as possible"),
+        )
+        .arg(
+            Arg::with_name("burst")
+                .long("burst")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Transactions sent back to back to a node before --packets-per-second applies"),
+        )
 }
 
 /// Parses a clap `ArgMatches` structure into a `Config`
@@ -163,5 +180,16 @@ pub fn extract_args<'a>(matches: &ArgMatches<'a>) -> Config {
 
     args.sustained = matches.is_present("sustained");
 
+    if let Some(n) = matches.value_of("packets-per-second") {
+        args.pacing.packets_per_second = n
+            .to_string()
+            .parse()
+            .expect("can't parse packets-per-second");
+    }
+
+    if let Some(n) = matches.value_of("burst") {
+        args.pacing.burst = n.to_string().parse().expect("can't parse burst");
+    }
+
     args
 }
@@ -22,6 +22,7 @@ fn main() {
         tx_count,
         thread_batch_sleep_ms,
         sustained,
+        pacing,
     } = cli_config;
 
     println!("Connecting to the cluster");
@@ -38,7 +39,10 @@ fn main() {
         exit(1);
     }
 
-    let clients = get_clients(&nodes);
+    let mut clients = get_clients(&nodes);
+    for client in &mut clients {
+        client.set_pacing(pacing);
+    }
 
     let (keypairs, keypair_balance) = generate_and_fund_keypairs(
         &clients[0],
@@ -1,7 +1,9 @@
 pub mod client_error;
 mod generic_rpc_client_request;
 pub mod mock_rpc_client_request;
+pub mod paced_sender;
 pub mod perf_utils;
+pub mod retry_policy;
 pub mod rpc_client;
 pub mod rpc_client_request;
 pub mod rpc_request;
@@ -1,7 +1,7 @@
 use crate::client_error::ClientError;
 use crate::generic_rpc_client_request::GenericRpcClientRequest;
 use crate::rpc_request::RpcRequest;
-use serde_json::{Number, Value};
+use serde_json::{json, Number, Value};
 use morgan_interface::fee_calculator::FeeCalculator;
 use morgan_interface::transaction::{self, TransactionError};
 
@@ -41,7 +41,7 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                     Value::Null
                 }
             }
-            RpcRequest::GetBalance => {
+            RpcRequest::GetBalance | RpcRequest::GetReputation => {
                 let n = if self.url == "airdrop" { 0 } else { 50 };
                 Value::Number(Number::from(n))
             }
@@ -49,6 +49,11 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                 Value::String(PUBKEY.to_string()),
                 serde_json::to_value(FeeCalculator::default()).unwrap(),
             ]),
+            RpcRequest::GetFees => json!({
+                "blockhash": PUBKEY,
+                "feeCalculator": FeeCalculator::default(),
+                "lastValidSlot": 42,
+            }),
             RpcRequest::GetSignatureStatus => {
                 let response: Option<transaction::Result<()>> = if self.url == "account_in_use" {
                     Some(Err(TransactionError::AccountInUse))
@@ -60,7 +65,9 @@ impl GenericRpcClientRequest for MockRpcClientRequest {
                 serde_json::to_value(response).unwrap()
             }
             RpcRequest::GetTransactionCount => Value::Number(Number::from(1234)),
-            RpcRequest::SendTransaction => Value::String(SIGNATURE.to_string()),
+            RpcRequest::RequestReputation | RpcRequest::SendTransaction => {
+                Value::String(SIGNATURE.to_string())
+            }
             _ => Value::Null,
         };
         Ok(val)
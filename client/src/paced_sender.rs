@@ -0,0 +1,186 @@
+//! The `paced_sender` module sends UDP packets to a TPU no faster than a configured rate.
+//! Blasting datagrams as fast as the socket takes them mostly overflows the receiving
+//! socket's buffer and gets them dropped; pacing trades a little latency for packets that
+//! arrive.  Credit for a configurable burst of packets builds up while the sender is idle.
+
+use morgan_interface::timing::duration_as_ns;
+use std::cmp;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// Packets that may go out back to back, unless configured otherwise
+pub const DEFAULT_BURST: u64 = 64;
+
+const NS_PER_SECOND: u64 = 1_000_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PacingConfig {
+    /// Sustained packets per second, 0 sends as fast as the socket allows
+    pub packets_per_second: u64,
+    /// Packets that may go out back to back after the sender was idle
+    pub burst: u64,
+}
+
+impl Default for PacingConfig {
+    fn default() -> Self {
+        Self {
+            packets_per_second: 0,
+            burst: DEFAULT_BURST,
+        }
+    }
+}
+
+impl PacingConfig {
+    pub fn new(packets_per_second: u64, burst: u64) -> Self {
+        Self {
+            packets_per_second,
+            burst,
+        }
+    }
+
+    pub fn is_paced(&self) -> bool {
+        self.packets_per_second > 0
+    }
+
+    fn interval_ns(&self) -> i64 {
+        (NS_PER_SECOND / cmp::max(self.packets_per_second, 1)) as i64
+    }
+
+    fn max_credit_ns(&self) -> i64 {
+        self.interval_ns()
+            .saturating_mul(cmp::max(self.burst, 1) as i64)
+    }
+}
+
+/// Send credit, in nanoseconds of packet intervals; negative once packets were let through
+/// ahead of the credit and their senders have to wait it off
+struct Pace {
+    credit_ns: i64,
+    last: Instant,
+}
+
+impl Pace {
+    fn new(config: &PacingConfig, now: Instant) -> Self {
+        Self {
+            credit_ns: config.max_credit_ns(),
+            last: now,
+        }
+    }
+
+    /// Spend the credit of one packet sent at `now`, returning how long to wait before
+    /// sending it
+    fn delay(&mut self, config: &PacingConfig, now: Instant) -> Duration {
+        if now > self.last {
+            let elapsed_ns = duration_as_ns(&now.duration_since(self.last)) as i64;
+            self.credit_ns = cmp::min(
+                self.credit_ns.saturating_add(elapsed_ns),
+                config.max_credit_ns(),
+            );
+            self.last = now;
+        }
+        self.credit_ns -= config.interval_ns();
+        if self.credit_ns < 0 {
+            Duration::from_nanos(-self.credit_ns as u64)
+        } else {
+            Duration::from_millis(0)
+        }
+    }
+}
+
+/// A UDP socket whose sends are paced by a `PacingConfig`, shared by the threads sending
+/// through it
+pub struct PacedSender {
+    socket: UdpSocket,
+    config: PacingConfig,
+    pace: Mutex<Pace>,
+}
+
+impl PacedSender {
+    pub fn new(socket: UdpSocket, config: PacingConfig) -> Self {
+        Self {
+            socket,
+            config,
+            pace: Mutex::new(Pace::new(&config, Instant::now())),
+        }
+    }
+
+    pub fn config(&self) -> &PacingConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: PacingConfig) {
+        self.pace = Mutex::new(Pace::new(&config, Instant::now()));
+        self.config = config;
+    }
+
+    /// Send `buf` to `addr` once the pace allows another packet
+    pub fn send_to(&self, buf: &[u8], addr: &SocketAddr) -> io::Result<usize> {
+        if self.config.is_paced() {
+            let delay = self
+                .pace
+                .lock()
+                .unwrap()
+                .delay(&self.config, Instant::now());
+            if delay > Duration::from_millis(0) {
+                sleep(delay);
+            }
+        }
+        self.socket.send_to(buf, addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pace_burst_then_rate() {
+        let config = PacingConfig::new(1_000, 3);
+        let start = Instant::now();
+        let mut pace = Pace::new(&config, start);
+
+        // the burst goes out right away, then every packet waits its interval
+        for _ in 0..3 {
+            assert_eq!(pace.delay(&config, start), Duration::from_millis(0));
+        }
+        assert_eq!(pace.delay(&config, start), Duration::from_millis(1));
+        assert_eq!(pace.delay(&config, start), Duration::from_millis(2));
+
+        // waiting off the debt and one more interval earns one packet
+        let now = start + Duration::from_millis(3);
+        assert_eq!(pace.delay(&config, now), Duration::from_millis(0));
+        assert_eq!(pace.delay(&config, now), Duration::from_millis(1));
+
+        // idling never earns more than the burst
+        let now = now + Duration::from_secs(10);
+        for _ in 0..3 {
+            assert_eq!(pace.delay(&config, now), Duration::from_millis(0));
+        }
+        assert_eq!(pace.delay(&config, now), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_paced_sender_send_to() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = receiver.local_addr().unwrap();
+        let sender = PacedSender::new(
+            UdpSocket::bind("127.0.0.1:0").unwrap(),
+            PacingConfig::new(100, 1),
+        );
+
+        let start = Instant::now();
+        for i in 0..3u8 {
+            sender.send_to(&[i], &addr).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        let mut buf = [0u8; 1];
+        for i in 0..3u8 {
+            receiver.recv_from(&mut buf).unwrap();
+            assert_eq!(buf[0], i);
+        }
+    }
+}
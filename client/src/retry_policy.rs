@@ -0,0 +1,228 @@
+//! The `retry_policy` module holds the backoff schedule client helpers use when a request to
+//! the cluster fails: the delay between attempts grows exponentially with random jitter, and
+//! retrying stops after a number of attempts or once the total deadline would be exceeded.
+
+use morgan_interface::timing::duration_as_ns;
+use rand::{thread_rng, Rng};
+use std::fmt;
+use std::io;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Delay after the first failed attempt, doubled after every further failure
+    pub base_delay: Duration,
+    /// Longest delay between two attempts
+    pub max_delay: Duration,
+    /// Fraction of each delay that is randomized, from 0.0 (none) to 1.0
+    pub jitter: f64,
+    /// Attempts made before giving up, including the first one
+    pub max_attempts: usize,
+    /// Total time budget for all the attempts and the delays between them
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(4),
+            jitter: 0.25,
+            max_attempts: 5,
+            deadline: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A failed attempt of a retried operation
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryAttempt {
+    /// Time since the first attempt started
+    pub elapsed: Duration,
+    pub error: String,
+}
+
+/// The error of the last attempt, and the history of all the failed attempts
+#[derive(Debug)]
+pub struct RetryError<E> {
+    pub last_error: E,
+    pub attempts: Vec<RetryAttempt>,
+}
+
+impl<E> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed after {} attempts:", self.attempts.len())?;
+        for (i, attempt) in self.attempts.iter().enumerate() {
+            write!(
+                f,
+                " [{} at {}ms: {}]",
+                i + 1,
+                attempt.elapsed.as_millis(),
+                attempt.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: fmt::Debug> std::error::Error for RetryError<E> {}
+
+impl<E> From<RetryError<E>> for io::Error {
+    fn from(err: RetryError<E>) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, err.to_string())
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(base_delay: Duration, max_attempts: usize, deadline: Duration) -> Self {
+        Self {
+            base_delay,
+            max_attempts,
+            deadline,
+            ..Self::default()
+        }
+    }
+
+    /// The delay to wait after `attempt` failed, counting attempts from 0
+    pub fn delay(&self, attempt: usize) -> Duration {
+        let backoff = 2u32.saturating_pow(attempt as u32);
+        let delay = self
+            .base_delay
+            .checked_mul(backoff)
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        let jitter = self.jitter.max(0.0).min(1.0);
+        if jitter == 0.0 {
+            return delay;
+        }
+        let scale = 1.0 - jitter + thread_rng().gen_range(0.0, 2.0 * jitter);
+        Duration::from_nanos((duration_as_ns(&delay) as f64 * scale) as u64)
+    }
+
+    /// Call `f` until it succeeds or the policy gives up
+    pub fn retry<T, E, F>(&self, f: F) -> Result<T, RetryError<E>>
+    where
+        E: fmt::Debug,
+        F: FnMut() -> Result<T, E>,
+    {
+        self.retry_if(|_| true, f)
+    }
+
+    /// Call `f` until it succeeds, fails with an error `is_retryable` rejects, or the policy
+    /// gives up
+    pub fn retry_if<T, E, R, F>(&self, is_retryable: R, mut f: F) -> Result<T, RetryError<E>>
+    where
+        E: fmt::Debug,
+        R: Fn(&E) -> bool,
+        F: FnMut() -> Result<T, E>,
+    {
+        let start = Instant::now();
+        let mut attempts = vec![];
+        loop {
+            let err = match f() {
+                Ok(result) => return Ok(result),
+                Err(err) => err,
+            };
+            attempts.push(RetryAttempt {
+                elapsed: start.elapsed(),
+                error: format!("{:?}", err),
+            });
+            let delay = self.delay(attempts.len() - 1);
+            if !is_retryable(&err)
+                || attempts.len() >= self.max_attempts
+                || start.elapsed() + delay >= self.deadline
+            {
+                return Err(RetryError {
+                    last_error: err,
+                    attempts,
+                });
+            }
+            sleep(delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        };
+        let delays: Vec<_> = (0..5).map(|attempt| policy.delay(attempt)).collect();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 500, 500]
+                .iter()
+                .map(|ms| Duration::from_millis(*ms))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(policy.delay(usize::max_value()), policy.max_delay);
+
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(300));
+        }
+    }
+
+    #[test]
+    fn test_retry_policy_retry() {
+        let policy = RetryPolicy::new(Duration::from_millis(0), 3, Duration::from_secs(10));
+        let mut calls = 0;
+        let result: Result<_, RetryError<&str>> = policy.retry(|| {
+            calls += 1;
+            if calls < 3 {
+                Err("not yet")
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+
+        let mut calls = 0;
+        let err = policy
+            .retry(|| -> Result<(), _> {
+                calls += 1;
+                Err(calls)
+            })
+            .unwrap_err();
+        assert_eq!(err.last_error, 3);
+        assert_eq!(
+            err.attempts.iter().map(|a| &a.error).collect::<Vec<_>>(),
+            vec!["1", "2", "3"]
+        );
+        assert!(err
+            .to_string()
+            .starts_with("failed after 3 attempts: [1 at "));
+
+        // errors that can't be retried fail right away
+        let err = policy
+            .retry_if(|err| *err != "fatal", || -> Result<(), _> { Err("fatal") })
+            .unwrap_err();
+        assert_eq!(err.attempts.len(), 1);
+    }
+
+    #[test]
+    fn test_retry_policy_deadline() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(50),
+            jitter: 0.0,
+            max_attempts: 100,
+            deadline: Duration::from_millis(100),
+            ..RetryPolicy::default()
+        };
+        let start = Instant::now();
+        let err = policy.retry(|| -> Result<(), _> { Err(()) }).unwrap_err();
+        assert_eq!(err.attempts.len(), 2);
+        assert!(start.elapsed() < policy.deadline);
+    }
+}
@@ -5,6 +5,7 @@ use crate::rpc_client_request::RpcClientRequest;
 use crate::rpc_request::RpcRequest;
 use bincode::serialize;
 use log::*;
+use serde_derive::Deserialize;
 use serde_json::{json, Value};
 use morgan_interface::account::Account;
 use morgan_interface::fee_calculator::FeeCalculator;
@@ -51,7 +52,21 @@ impl RpcClient {
 
     pub fn send_transaction(&self, transaction: &Transaction) -> Result<String, ClientError> {
         let serialized = serialize(transaction).unwrap();
-        let params = json!([serialized]);
+        self.send_transaction_params(json!([serialized]))
+    }
+
+    /// Send `transaction` tagged with an opaque `trace_id`, which the validator reports in its
+    /// metrics and blockstream as the transaction makes progress. The id never goes on-chain.
+    pub fn send_transaction_with_trace_id(
+        &self,
+        transaction: &Transaction,
+        trace_id: &str,
+    ) -> Result<String, ClientError> {
+        let serialized = serialize(transaction).unwrap();
+        self.send_transaction_params(json!([serialized, trace_id]))
+    }
+
+    fn send_transaction_params(&self, params: Value) -> Result<String, ClientError> {
         let signature = self
             .client
             .send(&RpcRequest::SendTransaction, Some(params), 5)?;
@@ -225,6 +240,19 @@ impl RpcClient {
         Ok(res)
     }
 
+    pub fn retry_get_reputation(
+        &self,
+        pubkey: &Pubkey,
+        retries: usize,
+    ) -> Result<Option<u64>, Box<dyn error::Error>> {
+        let params = json!([format!("{}", pubkey)]);
+        let res = self
+            .client
+            .send(&RpcRequest::GetReputation, Some(params), retries)?
+            .as_u64();
+        Ok(res)
+    }
+
     pub fn get_account(&self, pubkey: &Pubkey) -> io::Result<Account> {
         let params = json!([format!("{}", pubkey)]);
         let response = self
@@ -257,6 +285,33 @@ impl RpcClient {
         self.get_account(pubkey).map(|account| account.difs)
     }
 
+    /// Request the reputation of the user holding `pubkey`.
+    pub fn get_reputation(&self, pubkey: &Pubkey) -> io::Result<u64> {
+        self.get_account(pubkey).map(|account| account.reputations)
+    }
+
+    /// Ask the node's drone to airdrop `reputations` to `pubkey`, returning the
+    /// signature of the airdrop transaction.
+    pub fn request_reputation(&self, pubkey: &Pubkey, reputations: u64) -> io::Result<String> {
+        let params = json!([format!("{}", pubkey), reputations]);
+        let response = self
+            .client
+            .send(&RpcRequest::RequestReputation, Some(params), 0)
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("RequestReputation request failure: {:?}", err),
+                )
+            })?;
+
+        serde_json::from_value(response).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("RequestReputation parse failure: {}", err),
+            )
+        })
+    }
+
     /// Request the transaction count.  If the response packet is dropped by the network,
     /// this method will try again 5 times.
     pub fn get_transaction_count(&self) -> io::Result<u64> {
@@ -306,6 +361,42 @@ impl RpcClient {
         Ok((blockhash, fee_calculator))
     }
 
+    /// A recent blockhash, the fees to expect when using it, and the last slot it's accepted in
+    pub fn get_fees(&self) -> io::Result<(Hash, FeeCalculator, u64)> {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Fees {
+            blockhash: String,
+            fee_calculator: FeeCalculator,
+            last_valid_slot: u64,
+        }
+
+        let response = self
+            .client
+            .send(&RpcRequest::GetFees, None, 0)
+            .map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("GetFees request failure: {:?}", err),
+                )
+            })?;
+
+        let fees = serde_json::from_value::<Fees>(response).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("GetFees parse failure: {:?}", err),
+            )
+        })?;
+
+        let blockhash = fees.blockhash.parse().map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                format!("GetFees parse failure: {:?}", err),
+            )
+        })?;
+        Ok((blockhash, fees.fee_calculator, fees.last_valid_slot))
+    }
+
     pub fn get_new_blockhash(&self, blockhash: &Hash) -> io::Result<(Hash, FeeCalculator)> {
         let mut num_retries = 10;
         while num_retries > 0 {
@@ -377,6 +468,64 @@ impl RpcClient {
         None
     }
 
+    pub fn poll_reputation_with_timeout(
+        &self,
+        pubkey: &Pubkey,
+        polling_frequency: &Duration,
+        timeout: &Duration,
+    ) -> io::Result<u64> {
+        let now = Instant::now();
+        loop {
+            match self.get_reputation(&pubkey) {
+                Ok(reputation) => {
+                    return Ok(reputation);
+                }
+                Err(e) => {
+                    sleep(*polling_frequency);
+                    if now.elapsed() > *timeout {
+                        return Err(e);
+                    }
+                }
+            };
+        }
+    }
+
+    pub fn poll_get_reputation(&self, pubkey: &Pubkey) -> io::Result<u64> {
+        self.poll_reputation_with_timeout(
+            pubkey,
+            &Duration::from_millis(100),
+            &Duration::from_secs(1),
+        )
+    }
+
+    pub fn wait_for_reputation(
+        &self,
+        pubkey: &Pubkey,
+        expected_reputation: Option<u64>,
+    ) -> Option<u64> {
+        const LAST: usize = 30;
+        for run in 0..LAST {
+            let reputation_result = self.poll_get_reputation(pubkey);
+            if expected_reputation.is_none() {
+                return reputation_result.ok();
+            }
+            trace!(
+                "retry_get_reputation[{}] {:?} {:?}",
+                run,
+                reputation_result,
+                expected_reputation
+            );
+            if let (Some(expected_reputation), Ok(reputation_result)) =
+                (expected_reputation, reputation_result)
+            {
+                if expected_reputation == reputation_result {
+                    return Some(reputation_result);
+                }
+            }
+        }
+        None
+    }
+
     /// Poll the server to confirm a transaction.
     pub fn poll_for_signature(&self, signature: &Signature) -> io::Result<()> {
         let now = Instant::now();
@@ -666,6 +815,47 @@ mod tests {
         let signature = rpc_client.send_transaction(&tx);
         assert!(signature.is_err());
     }
+
+    #[test]
+    fn test_send_transaction_with_trace_id() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+
+        let key = Keypair::new();
+        let to = Pubkey::new_rand();
+        let blockhash = Hash::default();
+        let tx = system_transaction::create_user_account(&key, &to, 50, blockhash);
+
+        let signature = rpc_client.send_transaction_with_trace_id(&tx, "dapp-1");
+        assert_eq!(signature.unwrap(), SIGNATURE.to_string());
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+
+        let signature = rpc_client.send_transaction_with_trace_id(&tx, "dapp-1");
+        assert!(signature.is_err());
+    }
+    #[test]
+    fn test_retry_get_reputation() {
+        let pubkey = Pubkey::new_rand();
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let reputation = rpc_client.retry_get_reputation(&pubkey, 0);
+        assert_eq!(reputation.unwrap(), Some(50));
+
+        let rpc_client = RpcClient::new_mock("airdrop".to_string());
+        let reputation = rpc_client.retry_get_reputation(&pubkey, 0);
+        assert_eq!(reputation.unwrap(), Some(0));
+    }
+
+    #[test]
+    fn test_request_reputation() {
+        let pubkey = Pubkey::new_rand();
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+        let signature = rpc_client.request_reputation(&pubkey, 42);
+        assert_eq!(signature.unwrap(), SIGNATURE.to_string());
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+        assert!(rpc_client.request_reputation(&pubkey, 42).is_err());
+    }
+
     #[test]
     fn test_get_recent_blockhash() {
         let rpc_client = RpcClient::new_mock("succeeds".to_string());
@@ -680,6 +870,21 @@ mod tests {
         assert!(rpc_client.get_recent_blockhash().is_err());
     }
 
+    #[test]
+    fn test_get_fees() {
+        let rpc_client = RpcClient::new_mock("succeeds".to_string());
+
+        let expected_blockhash: Hash = PUBKEY.parse().unwrap();
+
+        let (blockhash, _fee_calculator, last_valid_slot) = rpc_client.get_fees().expect("fees ok");
+        assert_eq!(blockhash, expected_blockhash);
+        assert_eq!(last_valid_slot, 42);
+
+        let rpc_client = RpcClient::new_mock("fails".to_string());
+
+        assert!(rpc_client.get_fees().is_err());
+    }
+
     #[test]
     fn test_get_signature_status() {
         let rpc_client = RpcClient::new_mock("succeeds".to_string());
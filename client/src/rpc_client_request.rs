@@ -1,11 +1,11 @@
 use crate::client_error::ClientError;
 use crate::generic_rpc_client_request::GenericRpcClientRequest;
+use crate::retry_policy::RetryPolicy;
 use crate::rpc_request::{RpcError, RpcRequest};
 use log::*;
 use reqwest;
 use reqwest::header::CONTENT_TYPE;
 use morgan_interface::timing::{DEFAULT_NUM_TICKS_PER_SECOND, DEFAULT_TICKS_PER_SLOT};
-use std::thread::sleep;
 use std::time::Duration;
 use ansi_term::Color::{Green};
 use morgan_helper::logHelper::*;
@@ -38,60 +38,41 @@ impl GenericRpcClientRequest for RpcClientRequest {
         &self,
         request: &RpcRequest,
         params: Option<serde_json::Value>,
-        mut retries: usize,
+        retries: usize,
     ) -> Result<serde_json::Value, ClientError> {
         // Concurrent requests are not supported so reuse the same request id for all requests
         let request_id = 1;
 
         let request_json = request.build_request_json(request_id, params);
 
-        loop {
-            match self
-                .client
+        // Retry requests that didn't reach the server, starting at approximately half a slot
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(
+                500 * DEFAULT_TICKS_PER_SLOT / DEFAULT_NUM_TICKS_PER_SECOND,
+            ),
+            max_attempts: retries + 1,
+            ..RetryPolicy::default()
+        };
+        let response = policy.retry(|| {
+            self.client
                 .post(&self.url)
                 .header(CONTENT_TYPE, "application/json")
                 .body(request_json.to_string())
                 .send()
-            {
-                Ok(mut response) => {
-                    let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
-                    if json["error"].is_object() {
-                        Err(RpcError::RpcRequestError(format!(
-                            "RPC Error response: {}",
-                            serde_json::to_string(&json["error"]).unwrap()
-                        )))?
-                    }
-                    return Ok(json["result"].clone());
-                }
-                Err(e) => {
-                    // info!(
-                    //     "{}",
-                    //     Info(format!(
-                    //         "make_rpc_request({:?}) failed, {} retries left: {:?}",
-                    //         request, retries, e).to_string()
-                    //     )
-                    // );
-                    let info:String = format!(
-                        "make_rpc_request({:?}) failed, {} retries left: {:?}",
-                        request, retries, e).to_string();
-                    println!("{}",
-                        printLn(
-                            info,
-                            module_path!().to_string()
-                        )
-                    );
+        });
+        let mut response = response.map_err(|err| {
+            let info = format!("make_rpc_request({:?}) {}", request, err);
+            println!("{}", printLn(info, module_path!().to_string()));
+            err.last_error
+        })?;
 
-                    if retries == 0 {
-                        Err(e)?;
-                    }
-                    retries -= 1;
-
-                    // Sleep for approximately half a slot
-                    sleep(Duration::from_millis(
-                        500 * DEFAULT_TICKS_PER_SLOT / DEFAULT_NUM_TICKS_PER_SECOND,
-                    ));
-                }
-            }
+        let json: serde_json::Value = serde_json::from_str(&response.text()?)?;
+        if json["error"].is_object() {
+            Err(RpcError::RpcRequestError(format!(
+                "RPC Error response: {}",
+                serde_json::to_string(&json["error"]).unwrap()
+            )))?
         }
+        Ok(json["result"].clone())
     }
 }
@@ -9,10 +9,15 @@ pub enum RpcRequest {
     GetAccountInfo,
     GetBalance,
     GetClusterNodes,
+    GetFees,
     GetNumBlocksSinceSignatureConfirmation,
+    GetPerformanceSamples,
     GetRecentBlockhash,
+    GetReputation,
+    GetSignaturesForAddress,
     GetSignatureStatus,
     GetSlotLeader,
+    GetSlotProof,
     GetEpochVoteAccounts,
     GetStorageBlockhash,
     GetStorageSlot,
@@ -20,7 +25,9 @@ pub enum RpcRequest {
     GetTransactionCount,
     RegisterNode,
     RequestAirdrop,
+    RequestReputation,
     SendTransaction,
+    SendTransactionBatch,
     SignVote,
 }
 
@@ -34,12 +41,17 @@ impl RpcRequest {
             RpcRequest::GetAccountInfo => "getAccountInfo",
             RpcRequest::GetBalance => "getDif",
             RpcRequest::GetClusterNodes => "getClusterNodes",
+            RpcRequest::GetFees => "getFees",
             RpcRequest::GetNumBlocksSinceSignatureConfirmation => {
                 "getNumBlocksSinceSignatureConfirmation"
             }
+            RpcRequest::GetPerformanceSamples => "getPerformanceSamples",
             RpcRequest::GetRecentBlockhash => "getLatestBlockhash",
+            RpcRequest::GetReputation => "getReputation",
+            RpcRequest::GetSignaturesForAddress => "getSignaturesForAddress",
             RpcRequest::GetSignatureStatus => "getSignatureState",
             RpcRequest::GetSlotLeader => "getRoundLeader",
+            RpcRequest::GetSlotProof => "getSlotProof",
             RpcRequest::GetEpochVoteAccounts => "getEpochVoteAccounts",
             RpcRequest::GetStorageBlockhash => "getStorageBlockhash",
             RpcRequest::GetStorageSlot => "getStorageSlot",
@@ -47,7 +59,9 @@ impl RpcRequest {
             RpcRequest::GetTransactionCount => "getTxnCnt",
             RpcRequest::RegisterNode => "registerNode",
             RpcRequest::RequestAirdrop => "requestDif",
+            RpcRequest::RequestReputation => "requestReputation",
             RpcRequest::SendTransaction => "sendTxn",
+            RpcRequest::SendTransactionBatch => "sendTxnBatch",
             RpcRequest::SignVote => "signVote",
         };
         let mut request = json!({
@@ -97,9 +111,13 @@ mod tests {
         assert_eq!(request["params"], addr,);
 
         let test_request = RpcRequest::GetBalance;
-        let request = test_request.build_request_json(1, Some(addr));
+        let request = test_request.build_request_json(1, Some(addr.clone()));
         assert_eq!(request["method"], "getDif");
 
+        let test_request = RpcRequest::GetReputation;
+        let request = test_request.build_request_json(1, Some(addr));
+        assert_eq!(request["method"], "getReputation");
+
         let test_request = RpcRequest::GetRecentBlockhash;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "getLatestBlockhash");
@@ -112,8 +130,16 @@ mod tests {
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "requestDif");
 
+        let test_request = RpcRequest::RequestReputation;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "requestReputation");
+
         let test_request = RpcRequest::SendTransaction;
         let request = test_request.build_request_json(1, None);
         assert_eq!(request["method"], "sendTxn");
+
+        let test_request = RpcRequest::SendTransactionBatch;
+        let request = test_request.build_request_json(1, None);
+        assert_eq!(request["method"], "sendTxnBatch");
     }
 }
@@ -3,6 +3,8 @@
 //! messages to the network directly. The binary encoding of its messages are
 //! unstable and may change in future releases.
 
+use crate::paced_sender::{PacedSender, PacingConfig};
+use crate::retry_policy::{RetryError, RetryPolicy};
 use crate::rpc_client::RpcClient;
 use bincode::{serialize_into, serialized_size};
 use log::*;
@@ -16,7 +18,7 @@ use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil, Signature};
 use morgan_interface::system_instruction;
 use morgan_interface::transaction::{self, Transaction};
-use morgan_interface::transport::Result as TransportResult;
+use morgan_interface::transport::{Result as TransportResult, TransportError};
 use std::io;
 use std::net::{SocketAddr, UdpSocket};
 use std::time::Duration;
@@ -25,7 +27,7 @@ use morgan_helper::logHelper::*;
 /// An object for querying and sending transactions to the network.
 pub struct ThinClient {
     transactions_addr: SocketAddr,
-    transactions_socket: UdpSocket,
+    transactions_sender: PacedSender,
     rpc_client: RpcClient,
 }
 
@@ -62,10 +64,16 @@ impl ThinClient {
         Self {
             rpc_client,
             transactions_addr,
-            transactions_socket,
+            transactions_sender: PacedSender::new(transactions_socket, PacingConfig::default()),
         }
     }
 
+    /// Pace the transactions sent to the Tpu, they go out as fast as the socket allows until
+    /// this is called
+    pub fn set_pacing(&mut self, pacing: PacingConfig) {
+        self.transactions_sender.set_config(pacing);
+    }
+
     /// Retry a sending a signed Transaction to the server for processing.
     pub fn retry_transfer_until_confirmed(
         &self,
@@ -95,34 +103,36 @@ impl ThinClient {
         tries: usize,
         min_confirmed_blocks: usize,
     ) -> io::Result<Signature> {
-        for x in 0..tries {
-            let mut buf = vec![0; serialized_size(&transaction).unwrap() as usize];
-            let mut wr = std::io::Cursor::new(&mut buf[..]);
-            serialize_into(&mut wr, &transaction)
-                .expect("serialize Transaction in pub fn transfer_signed");
-            self.transactions_socket
-                .send_to(&buf[..], &self.transactions_addr)?;
-            if self
-                .poll_for_signature_confirmation(&transaction.signatures[0], min_confirmed_blocks)
-                .is_ok()
-            {
-                return Ok(transaction.signatures[0]);
-            }
-            // info!("{}", Info(format!("{} tries failed transfer to {}", x, self.transactions_addr).to_string()));
-            let info:String = format!("{} tries failed transfer to {}", x, self.transactions_addr).to_string();
-            println!("{}",
-                printLn(
-                    info,
-                    module_path!().to_string()
+        let policy = RetryPolicy {
+            max_attempts: tries,
+            ..RetryPolicy::default()
+        };
+        let mut attempt = 0;
+        policy
+            .retry(|| {
+                if attempt > 0 {
+                    let (blockhash, _fee_calculator) = self.rpc_client.get_recent_blockhash()?;
+                    transaction.sign(keypairs, blockhash);
+                }
+                attempt += 1;
+                let mut buf = vec![0; serialized_size(&transaction).unwrap() as usize];
+                let mut wr = std::io::Cursor::new(&mut buf[..]);
+                serialize_into(&mut wr, &transaction)
+                    .expect("serialize Transaction in pub fn transfer_signed");
+                self.transactions_sender
+                    .send_to(&buf[..], &self.transactions_addr)?;
+                self.poll_for_signature_confirmation(
+                    &transaction.signatures[0],
+                    min_confirmed_blocks,
+                )?;
+                Ok(transaction.signatures[0])
+            })
+            .map_err(|err: RetryError<TransportError>| {
+                io::Error::new(
+                    io::ErrorKind::Other,
+                    format!("retry_transfer to {} {}", self.transactions_addr, err),
                 )
-            );
-            let (blockhash, _fee_calculator) = self.rpc_client.get_recent_blockhash()?;
-            transaction.sign(keypairs, blockhash);
-        }
-        Err(io::Error::new(
-            io::ErrorKind::Other,
-            format!("retry_transfer failed in {} retries", tries),
-        ))
+            })
     }
 
     pub fn poll_balance_with_timeout(
@@ -143,6 +153,19 @@ impl ThinClient {
         self.rpc_client.wait_for_balance(pubkey, expected_balance)
     }
 
+    pub fn poll_get_reputation(&self, pubkey: &Pubkey) -> io::Result<u64> {
+        self.rpc_client.poll_get_reputation(pubkey)
+    }
+
+    pub fn wait_for_reputation(
+        &self,
+        pubkey: &Pubkey,
+        expected_reputation: Option<u64>,
+    ) -> Option<u64> {
+        self.rpc_client
+            .wait_for_reputation(pubkey, expected_reputation)
+    }
+
     /// Check a signature in the bank. This method blocks
     /// until the server sends a response.
     pub fn check_signature(&self, signature: &Signature) -> bool {
@@ -195,6 +218,17 @@ impl SyncClient for ThinClient {
         self.send_instruction(keypair, transfer_instruction)
     }
 
+    fn transfer_reputations(
+        &self,
+        reputations: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+    ) -> TransportResult<Signature> {
+        let transfer_instruction =
+            system_instruction::transfer_reputations(&keypair.pubkey(), pubkey, reputations);
+        self.send_instruction(keypair, transfer_instruction)
+    }
+
     fn get_account_data(&self, pubkey: &Pubkey) -> TransportResult<Option<Vec<u8>>> {
         Ok(self.rpc_client.get_account_data(pubkey).ok())
     }
@@ -204,6 +238,11 @@ impl SyncClient for ThinClient {
         Ok(balance)
     }
 
+    fn get_reputation(&self, pubkey: &Pubkey) -> TransportResult<u64> {
+        let reputation = self.rpc_client.get_reputation(pubkey)?;
+        Ok(reputation)
+    }
+
     fn get_signature_status(
         &self,
         signature: &Signature,
@@ -256,7 +295,7 @@ impl AsyncClient for ThinClient {
         serialize_into(&mut wr, &transaction)
             .expect("serialize Transaction in pub fn transfer_signed");
         assert!(buf.len() < PACKET_DATA_SIZE);
-        self.transactions_socket
+        self.transactions_sender
             .send_to(&buf[..], &self.transactions_addr)?;
         Ok(transaction.signatures[0])
     }
@@ -289,6 +328,17 @@ impl AsyncClient for ThinClient {
             system_instruction::transfer(&keypair.pubkey(), pubkey, difs);
         self.async_send_instruction(keypair, transfer_instruction, recent_blockhash)
     }
+    fn async_transfer_reputations(
+        &self,
+        reputations: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+        recent_blockhash: Hash,
+    ) -> io::Result<Signature> {
+        let transfer_instruction =
+            system_instruction::transfer_reputations(&keypair.pubkey(), pubkey, reputations);
+        self.async_send_instruction(keypair, transfer_instruction, recent_blockhash)
+    }
 }
 
 pub fn create_client((rpc, tpu): (SocketAddr, SocketAddr), range: (u16, u16)) -> ThinClient {
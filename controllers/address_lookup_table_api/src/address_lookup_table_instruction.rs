@@ -0,0 +1,311 @@
+use crate::address_lookup_table_state::{AddressLookupTable, AddressLookupTableState, MAX_ADDRESSES};
+use crate::id;
+use bincode::deserialize;
+use log::*;
+use serde_derive::{Deserialize, Serialize};
+use morgan_interface::account::KeyedAccount;
+use morgan_interface::account_utils::State;
+use morgan_interface::instruction::{AccountMeta, Instruction, InstructionError};
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::system_instruction;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum AddressLookupTableInstruction {
+    /// Initialize an empty lookup table
+    ///
+    /// Expects 2 Accounts:
+    ///    0 - Uninitialized lookup table account
+    ///    1 - Authority of the new table
+    InitializeLookupTable,
+
+    /// Append addresses to a lookup table
+    ///
+    /// Expects 2 Accounts:
+    ///    0 - Lookup table account
+    ///    1 - Authority of the table, signed
+    ExtendLookupTable(Vec<Pubkey>),
+
+    /// Clear the table authority so the table can never be extended again
+    ///
+    /// Expects 2 Accounts:
+    ///    0 - Lookup table account
+    ///    1 - Authority of the table, signed
+    FreezeLookupTable,
+}
+
+pub fn create_lookup_table(
+    from_pubkey: &Pubkey,
+    table_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    difs: u64,
+) -> Vec<Instruction> {
+    vec![
+        system_instruction::create_account(
+            from_pubkey,
+            table_pubkey,
+            difs,
+            AddressLookupTableState::max_space(),
+            &id(),
+        ),
+        Instruction::new(
+            id(),
+            &AddressLookupTableInstruction::InitializeLookupTable,
+            vec![
+                AccountMeta::new(*table_pubkey, false),
+                AccountMeta::new_credit_only(*authority_pubkey, false),
+            ],
+        ),
+    ]
+}
+
+pub fn extend_lookup_table(
+    table_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    addresses: Vec<Pubkey>,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*table_pubkey, false),
+        AccountMeta::new_credit_only(*authority_pubkey, true),
+    ];
+    Instruction::new(
+        id(),
+        &AddressLookupTableInstruction::ExtendLookupTable(addresses),
+        account_metas,
+    )
+}
+
+pub fn freeze_lookup_table(table_pubkey: &Pubkey, authority_pubkey: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*table_pubkey, false),
+        AccountMeta::new_credit_only(*authority_pubkey, true),
+    ];
+    Instruction::new(
+        id(),
+        &AddressLookupTableInstruction::FreezeLookupTable,
+        account_metas,
+    )
+}
+
+/// Return the table held by `table_account` if `authority_account` signed and may modify it
+fn authorized_table(
+    table_account: &KeyedAccount,
+    authority_account: &KeyedAccount,
+) -> Result<AddressLookupTable, InstructionError> {
+    let table = match table_account.state()? {
+        AddressLookupTableState::Initialized(table) => table,
+        AddressLookupTableState::Uninitialized => {
+            return Err(InstructionError::UninitializedAccount);
+        }
+    };
+    match (table.authority, authority_account.signer_key()) {
+        (Some(authority), Some(signer)) if authority == *signer => Ok(table),
+        (Some(_), Some(_)) | (None, _) => Err(InstructionError::InvalidArgument),
+        (Some(_), None) => Err(InstructionError::MissingRequiredSignature),
+    }
+}
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    keyed_accounts: &mut [KeyedAccount],
+    data: &[u8],
+    _tick_height: u64,
+) -> Result<(), InstructionError> {
+    morgan_logger::setup();
+
+    trace!("process_instruction: {:?}", data);
+    trace!("keyed_accounts: {:?}", keyed_accounts);
+
+    if keyed_accounts.len() != 2 {
+        return Err(InstructionError::InvalidInstructionData);
+    }
+    let (table_account, authority_account) = keyed_accounts.split_at_mut(1);
+    let table_account = &mut table_account[0];
+    let authority_account = &authority_account[0];
+
+    match deserialize(data).map_err(|_| InstructionError::InvalidInstructionData)? {
+        AddressLookupTableInstruction::InitializeLookupTable => {
+            if let AddressLookupTableState::Uninitialized = table_account.state()? {
+                table_account.set_state(&AddressLookupTableState::Initialized(AddressLookupTable {
+                    authority: Some(*authority_account.unsigned_key()),
+                    addresses: vec![],
+                }))
+            } else {
+                Err(InstructionError::AccountAlreadyInitialized)
+            }
+        }
+        AddressLookupTableInstruction::ExtendLookupTable(addresses) => {
+            let mut table = authorized_table(table_account, authority_account)?;
+            if addresses.is_empty() || table.addresses.len() + addresses.len() > MAX_ADDRESSES {
+                return Err(InstructionError::InvalidInstructionData);
+            }
+            table.addresses.extend(addresses);
+            table_account.set_state(&AddressLookupTableState::Initialized(table))
+        }
+        AddressLookupTableInstruction::FreezeLookupTable => {
+            let mut table = authorized_table(table_account, authority_account)?;
+            table.authority = None;
+            table_account.set_state(&AddressLookupTableState::Initialized(table))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morgan_interface::account::Account;
+
+    fn process_instruction(
+        instruction: &Instruction,
+        table_account: &mut Account,
+    ) -> Result<(), InstructionError> {
+        let mut authority_account = Account::default();
+        let mut keyed_accounts = vec![
+            KeyedAccount::new(
+                &instruction.accounts[0].pubkey,
+                instruction.accounts[0].is_signer,
+                table_account,
+            ),
+            KeyedAccount::new(
+                &instruction.accounts[1].pubkey,
+                instruction.accounts[1].is_signer,
+                &mut authority_account,
+            ),
+        ];
+        super::process_instruction(&id(), &mut keyed_accounts, &instruction.data, 0)
+    }
+
+    fn create_table_account(authority_pubkey: &Pubkey) -> Account {
+        let mut table_account =
+            Account::new(1, 0, AddressLookupTableState::max_space() as usize, &id());
+        let instructions = create_lookup_table(
+            &Pubkey::new_rand(),
+            &Pubkey::new_rand(),
+            authority_pubkey,
+            1,
+        );
+        process_instruction(&instructions[1], &mut table_account).unwrap();
+        table_account
+    }
+
+    #[test]
+    fn test_initialize_lookup_table() {
+        let authority_pubkey = Pubkey::new_rand();
+        let mut table_account = create_table_account(&authority_pubkey);
+        assert_eq!(
+            AddressLookupTableState::table_from(&table_account),
+            Some(AddressLookupTable {
+                authority: Some(authority_pubkey),
+                addresses: vec![],
+            })
+        );
+
+        let instructions = create_lookup_table(
+            &Pubkey::new_rand(),
+            &Pubkey::new_rand(),
+            &authority_pubkey,
+            1,
+        );
+        assert_eq!(
+            process_instruction(&instructions[1], &mut table_account),
+            Err(InstructionError::AccountAlreadyInitialized)
+        );
+    }
+
+    #[test]
+    fn test_extend_lookup_table() {
+        let authority_pubkey = Pubkey::new_rand();
+        let mut table_account = create_table_account(&authority_pubkey);
+        let table_pubkey = Pubkey::new_rand();
+        let address0 = Pubkey::new_rand();
+        let address1 = Pubkey::new_rand();
+
+        process_instruction(
+            &extend_lookup_table(&table_pubkey, &authority_pubkey, vec![address0]),
+            &mut table_account,
+        )
+        .unwrap();
+        process_instruction(
+            &extend_lookup_table(&table_pubkey, &authority_pubkey, vec![address1]),
+            &mut table_account,
+        )
+        .unwrap();
+        assert_eq!(
+            AddressLookupTableState::table_from(&table_account)
+                .unwrap()
+                .addresses,
+            vec![address0, address1]
+        );
+
+        assert_eq!(
+            process_instruction(
+                &extend_lookup_table(&table_pubkey, &Pubkey::new_rand(), vec![address0]),
+                &mut table_account,
+            ),
+            Err(InstructionError::InvalidArgument)
+        );
+        assert_eq!(
+            process_instruction(
+                &extend_lookup_table(&table_pubkey, &authority_pubkey, vec![]),
+                &mut table_account,
+            ),
+            Err(InstructionError::InvalidInstructionData)
+        );
+        assert_eq!(
+            process_instruction(
+                &extend_lookup_table(
+                    &table_pubkey,
+                    &authority_pubkey,
+                    vec![address0; MAX_ADDRESSES - 1]
+                ),
+                &mut table_account,
+            ),
+            Err(InstructionError::InvalidInstructionData)
+        );
+
+        let mut unsigned = extend_lookup_table(&table_pubkey, &authority_pubkey, vec![address0]);
+        unsigned.accounts[1].is_signer = false;
+        assert_eq!(
+            process_instruction(&unsigned, &mut table_account),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn test_freeze_lookup_table() {
+        let authority_pubkey = Pubkey::new_rand();
+        let mut table_account = create_table_account(&authority_pubkey);
+        let table_pubkey = Pubkey::new_rand();
+
+        process_instruction(
+            &freeze_lookup_table(&table_pubkey, &authority_pubkey),
+            &mut table_account,
+        )
+        .unwrap();
+        assert_eq!(
+            AddressLookupTableState::table_from(&table_account)
+                .unwrap()
+                .authority,
+            None
+        );
+        assert_eq!(
+            process_instruction(
+                &extend_lookup_table(&table_pubkey, &authority_pubkey, vec![Pubkey::new_rand()]),
+                &mut table_account,
+            ),
+            Err(InstructionError::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_uninitialized_lookup_table() {
+        let mut table_account =
+            Account::new(1, 0, AddressLookupTableState::max_space() as usize, &id());
+        assert_eq!(
+            process_instruction(
+                &freeze_lookup_table(&Pubkey::new_rand(), &Pubkey::new_rand()),
+                &mut table_account,
+            ),
+            Err(InstructionError::UninitializedAccount)
+        );
+    }
+}
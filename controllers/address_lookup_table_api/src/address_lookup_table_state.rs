@@ -0,0 +1,74 @@
+//! Address lookup table state
+//! * an append-only list of addresses that messages load accounts from by index
+//! * extended by its authority until frozen
+
+use bincode::serialized_size;
+use serde_derive::{Deserialize, Serialize};
+use morgan_interface::account::Account;
+use morgan_interface::account_utils::State;
+use morgan_interface::pubkey::Pubkey;
+
+/// Messages index table entries with a u8
+pub const MAX_ADDRESSES: usize = 256;
+
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct AddressLookupTable {
+    /// The key allowed to extend or freeze the table, `None` once frozen
+    pub authority: Option<Pubkey>,
+
+    /// Entries are never removed or reordered, so an index resolves to the same
+    /// address for the life of the table
+    pub addresses: Vec<Pubkey>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub enum AddressLookupTableState {
+    Uninitialized,
+    Initialized(AddressLookupTable),
+}
+
+impl Default for AddressLookupTableState {
+    fn default() -> Self {
+        AddressLookupTableState::Uninitialized
+    }
+}
+
+impl AddressLookupTableState {
+    /// Space needed by a table holding `MAX_ADDRESSES` addresses
+    pub fn max_space() -> u64 {
+        serialized_size(&AddressLookupTableState::Initialized(AddressLookupTable {
+            authority: Some(Pubkey::default()),
+            addresses: vec![Pubkey::default(); MAX_ADDRESSES],
+        }))
+        .unwrap()
+    }
+
+    // utility function, used by Accounts to resolve message lookups
+    pub fn table_from(account: &Account) -> Option<AddressLookupTable> {
+        match account.state() {
+            Ok(AddressLookupTableState::Initialized(table)) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_from_account() {
+        let space = AddressLookupTableState::max_space() as usize;
+        let mut account = Account::new(1, 0, space, &Pubkey::default());
+        assert_eq!(AddressLookupTableState::table_from(&account), None);
+
+        let table = AddressLookupTable {
+            authority: None,
+            addresses: vec![Pubkey::new_rand(); MAX_ADDRESSES],
+        };
+        account
+            .set_state(&AddressLookupTableState::Initialized(table.clone()))
+            .unwrap();
+        assert_eq!(AddressLookupTableState::table_from(&account), Some(table));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod address_lookup_table_instruction;
+pub mod address_lookup_table_state;
+
+const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: [u8; 32] = [
+    2, 119, 166, 175, 151, 51, 155, 122, 200, 141, 24, 146, 201, 4, 70, 245, 0, 2, 48, 146, 102,
+    246, 46, 83, 193, 24, 36, 73, 130, 0, 0, 0,
+];
+
+morgan_interface::morgan_program_id!(ADDRESS_LOOKUP_TABLE_PROGRAM_ID);
@@ -0,0 +1,12 @@
+#[macro_export]
+macro_rules! morgan_address_lookup_table_controller {
+    () => {
+        (
+            "morgan_address_lookup_table_controller".to_string(),
+            morgan_address_lookup_table_api::id(),
+        )
+    };
+}
+
+use morgan_address_lookup_table_api::address_lookup_table_instruction::process_instruction;
+morgan_interface::morgan_entrypoint!(process_instruction);
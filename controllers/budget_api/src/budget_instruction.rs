@@ -19,8 +19,9 @@ pub struct Contract {
 /// An instruction to progress the smart contract.
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum BudgetInstruction {
-    /// Declare and instantiate `BudgetExpr`.
-    InitializeAccount(BudgetExpr),
+    /// Declare and instantiate `BudgetExpr`, refundable to the payer until it
+    /// completes. Anyone may cancel it from the optional expiry tick height on.
+    InitializeAccount(BudgetExpr, Option<u64>),
 
     /// Tell a payment plan acknowledge the given `DateTime` has past.
     ApplyTimestamp(DateTime<Utc>),
@@ -28,15 +29,29 @@ pub enum BudgetInstruction {
     /// Tell the budget that the `InitializeAccount` with `Signature` has been
     /// signed by the containing transaction's `Pubkey`.
     ApplySignature,
+
+    /// Refund the escrowed difs to the payer. Must be signed by the payer
+    /// unless the contract's expiry tick height has been reached.
+    Cancel,
 }
 
-fn initialize_account(contract: &Pubkey, expr: BudgetExpr) -> Instruction {
+fn initialize_account(
+    from: &Pubkey,
+    contract: &Pubkey,
+    expr: BudgetExpr,
+    expiry_tick_height: Option<u64>,
+) -> Instruction {
     let mut keys = vec![];
     if let BudgetExpr::Pay(payment) = &expr {
         keys.push(AccountMeta::new(payment.to, false));
     }
     keys.push(AccountMeta::new(*contract, false));
-    Instruction::new(id(), &BudgetInstruction::InitializeAccount(expr), keys)
+    keys.push(AccountMeta::new(*from, false));
+    Instruction::new(
+        id(),
+        &BudgetInstruction::InitializeAccount(expr, expiry_tick_height),
+        keys,
+    )
 }
 
 pub fn create_account(
@@ -44,14 +59,38 @@ pub fn create_account(
     contract: &Pubkey,
     difs: u64,
     expr: BudgetExpr,
+) -> Vec<Instruction> {
+    create_account_with_expiry_tick_height(from, contract, difs, expr, None)
+}
+
+/// Like `create_account`, but anyone may refund the payer from `expiry_slot` on.
+/// `ticks_per_slot` must be the cluster's, from its genesis block or a bank.
+pub fn create_account_with_expiry(
+    from: &Pubkey,
+    contract: &Pubkey,
+    difs: u64,
+    expr: BudgetExpr,
+    expiry_slot: u64,
+    ticks_per_slot: u64,
+) -> Vec<Instruction> {
+    let expiry_tick_height = expiry_slot * ticks_per_slot;
+    create_account_with_expiry_tick_height(from, contract, difs, expr, Some(expiry_tick_height))
+}
+
+fn create_account_with_expiry_tick_height(
+    from: &Pubkey,
+    contract: &Pubkey,
+    difs: u64,
+    expr: BudgetExpr,
+    expiry_tick_height: Option<u64>,
 ) -> Vec<Instruction> {
     if !expr.verify(difs) {
         panic!("invalid budget expression");
     }
-    let space = serialized_size(&BudgetState::new(expr.clone())).unwrap();
+    let space = serialized_size(&BudgetState::new(expr.clone(), from, expiry_tick_height)).unwrap();
     vec![
         system_instruction::create_account(&from, contract, difs, space, &id()),
-        initialize_account(contract, expr),
+        initialize_account(from, contract, expr, expiry_tick_height),
     ]
 }
 
@@ -116,6 +155,18 @@ pub fn apply_signature(from: &Pubkey, contract: &Pubkey, to: &Pubkey) -> Instruc
     Instruction::new(id(), &BudgetInstruction::ApplySignature, account_metas)
 }
 
+/// Refund the difs escrowed in `contract` to `payer`, signed by `from`
+pub fn cancel(from: &Pubkey, contract: &Pubkey, payer: &Pubkey) -> Instruction {
+    let mut account_metas = vec![
+        AccountMeta::new(*from, true),
+        AccountMeta::new(*contract, false),
+    ];
+    if from != payer {
+        account_metas.push(AccountMeta::new(*payer, false));
+    }
+    Instruction::new(id(), &BudgetInstruction::Cancel, account_metas)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2,11 +2,11 @@
 use crate::budget_expr::Witness;
 use crate::budget_instruction::BudgetInstruction;
 use crate::budget_state::{BudgetError, BudgetState};
-use bincode::deserialize;
 use chrono::prelude::{DateTime, Utc};
 use log::*;
 use morgan_interface::account::KeyedAccount;
 use morgan_interface::instruction::InstructionError;
+use morgan_interface::instruction_processor_utils::limited_deserialize;
 use morgan_interface::pubkey::Pubkey;
 use morgan_helper::logHelper::*;
 
@@ -71,13 +71,42 @@ fn apply_timestamp(
     Ok(())
 }
 
+/// Refund the escrowed difs to the payer. Only the payer may cancel before
+/// the expiry tick height, anyone may after it.
+fn apply_cancel(
+    budget_state: &mut BudgetState,
+    keyed_accounts: &mut [KeyedAccount],
+    tick_height: u64,
+) -> Result<(), BudgetError> {
+    let signer_is_payer = keyed_accounts[0].signer_key() == Some(&budget_state.payer);
+    let expired = budget_state
+        .expiry_tick_height
+        .map_or(false, |expiry| tick_height >= expiry);
+    if !signer_is_payer && !expired {
+        trace!("cancel not allowed");
+        return Err(BudgetError::CancelNotAllowed);
+    }
+    let refund_index = if signer_is_payer { 0 } else { 2 };
+    if keyed_accounts.len() <= refund_index
+        || keyed_accounts[refund_index].unsigned_key() != &budget_state.payer
+    {
+        trace!("destination missing");
+        return Err(BudgetError::DestinationMissing);
+    }
+    let difs = keyed_accounts[1].account.difs;
+    budget_state.pending_budget = None;
+    keyed_accounts[1].account.difs = 0;
+    keyed_accounts[refund_index].account.difs += difs;
+    Ok(())
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
-    _tick_height: u64,
+    tick_height: u64,
 ) -> Result<(), InstructionError> {
-    let instruction = deserialize(data).map_err(|err| {
+    let instruction = limited_deserialize(data).map_err(|err| {
         // info!("{}", Info(format!("Invalid transaction data: {:?} {:?}", data, err).to_string()));
         let info:String = format!("Invalid transaction data: {:?} {:?}", data, err).to_string();
         println!("{}",
@@ -92,7 +121,7 @@ pub fn process_instruction(
     trace!("process_instruction: {:?}", instruction);
 
     match instruction {
-        BudgetInstruction::InitializeAccount(expr) => {
+        BudgetInstruction::InitializeAccount(expr, expiry_tick_height) => {
             let expr = expr.clone();
             if let Some(payment) = expr.final_payment() {
                 keyed_accounts[1].account.difs = 0;
@@ -104,9 +133,12 @@ pub fn process_instruction(
                 trace!("contract already exists");
                 return Err(InstructionError::AccountAlreadyInitialized);
             }
-            let mut budget_state = BudgetState::default();
-            budget_state.pending_budget = Some(expr);
-            budget_state.initialized = true;
+            if keyed_accounts.len() < 2 {
+                trace!("payer missing");
+                return Err(InstructionError::InvalidArgument);
+            }
+            let budget_state =
+                BudgetState::new(expr, keyed_accounts[1].unsigned_key(), expiry_tick_height);
             budget_state.serialize(&mut keyed_accounts[0].account.data)
         }
         BudgetInstruction::ApplyTimestamp(dt) => {
@@ -145,22 +177,42 @@ pub fn process_instruction(
             trace!("apply signature committed");
             budget_state.serialize(&mut keyed_accounts[1].account.data)
         }
+        BudgetInstruction::Cancel => {
+            let mut budget_state = BudgetState::deserialize(&keyed_accounts[1].account.data)?;
+            if !budget_state.is_pending() {
+                return Ok(()); // Nothing to do here.
+            }
+            if !budget_state.initialized {
+                trace!("contract is uninitialized");
+                return Err(InstructionError::UninitializedAccount);
+            }
+            if keyed_accounts[0].signer_key().is_none() {
+                return Err(InstructionError::MissingRequiredSignature);
+            }
+            trace!("apply cancel");
+            apply_cancel(&mut budget_state, keyed_accounts, tick_height)
+                .map_err(|e| InstructionError::CustomError(e as u32))?;
+            trace!("apply cancel committed");
+            budget_state.serialize(&mut keyed_accounts[1].account.data)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::budget_expr::BudgetExpr;
     use crate::budget_instruction;
     use crate::id;
     use morgan_runtime::bank::Bank;
     use morgan_runtime::bank_client::BankClient;
     use morgan_interface::client::SyncClient;
     use morgan_interface::genesis_block::create_genesis_block;
+    use morgan_interface::hash::Hash;
     use morgan_interface::instruction::InstructionError;
     use morgan_interface::message::Message;
     use morgan_interface::signature::{Keypair, KeypairUtil};
-    use morgan_interface::transaction::TransactionError;
+    use morgan_interface::transaction::{Transaction, TransactionError};
 
     fn create_bank(difs: u64) -> (Bank, Keypair) {
         let (genesis_block, mint_keypair) = create_genesis_block(difs);
@@ -409,4 +461,188 @@ mod tests {
         assert_eq!(bank_client.get_account_data(&budget_pubkey).unwrap(), None);
         assert_eq!(bank_client.get_account_data(&bob_pubkey).unwrap(), None);
     }
+
+    #[test]
+    fn test_cancel_refund_to_payer() {
+        let (bank, alice_keypair) = create_bank(3);
+        let bank_client = BankClient::new(bank);
+        let alice_pubkey = alice_keypair.pubkey();
+        let budget_pubkey = Pubkey::new_rand();
+        let bob_pubkey = Pubkey::new_rand();
+        let dt = Utc::now();
+
+        // not cancelable through a witness signature, only through Cancel
+        let instructions = budget_instruction::on_date(
+            &alice_pubkey,
+            &bob_pubkey,
+            &budget_pubkey,
+            dt,
+            &alice_pubkey,
+            None,
+            1,
+        );
+        let message = Message::new(instructions);
+        bank_client
+            .send_message(&[&alice_keypair], message)
+            .unwrap();
+        let contract_account = bank_client
+            .get_account_data(&budget_pubkey)
+            .unwrap()
+            .unwrap();
+        let budget_state = BudgetState::deserialize(&contract_account).unwrap();
+        assert_eq!(budget_state.payer, alice_pubkey);
+        assert_eq!(budget_state.expiry_tick_height, None);
+
+        // Attack! mallory can't cancel a contract she didn't pay for
+        let mallory_keypair = Keypair::new();
+        let mallory_pubkey = mallory_keypair.pubkey();
+        bank_client
+            .transfer(1, &alice_keypair, &mallory_pubkey)
+            .unwrap();
+        let instruction =
+            budget_instruction::cancel(&mallory_pubkey, &budget_pubkey, &alice_pubkey);
+        assert_eq!(
+            bank_client
+                .send_instruction(&mallory_keypair, instruction)
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::CustomError(BudgetError::CancelNotAllowed as u32)
+            )
+        );
+        assert_eq!(bank_client.get_balance(&budget_pubkey).unwrap(), 1);
+
+        let instruction = budget_instruction::cancel(&alice_pubkey, &budget_pubkey, &alice_pubkey);
+        bank_client
+            .send_instruction(&alice_keypair, instruction)
+            .unwrap();
+        assert_eq!(bank_client.get_balance(&alice_pubkey).unwrap(), 2);
+        assert_eq!(bank_client.get_account_data(&budget_pubkey).unwrap(), None);
+
+        // the timestamp no longer pays out
+        let instruction =
+            budget_instruction::apply_timestamp(&alice_pubkey, &budget_pubkey, &bob_pubkey, dt);
+        assert!(bank_client
+            .send_instruction(&alice_keypair, instruction)
+            .is_err());
+        assert_eq!(bank_client.get_balance(&bob_pubkey).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_cancel_after_expiry() {
+        let (bank, alice_keypair) = create_bank(4);
+        let ticks_per_slot = bank.ticks_per_slot();
+        let bank_client = BankClient::new(bank);
+        let alice_pubkey = alice_keypair.pubkey();
+        let bob_keypair = Keypair::new();
+        let bob_pubkey = bob_keypair.pubkey();
+        bank_client
+            .transfer(1, &alice_keypair, &bob_pubkey)
+            .unwrap();
+
+        let witness = Pubkey::new_rand();
+        let not_expired_pubkey = Pubkey::new_rand();
+        let expr = BudgetExpr::new_authorized_payment(&witness, 1, &bob_pubkey);
+        let instructions = budget_instruction::create_account_with_expiry(
+            &alice_pubkey,
+            &not_expired_pubkey,
+            1,
+            expr.clone(),
+            1,
+            ticks_per_slot,
+        );
+        bank_client
+            .send_message(&[&alice_keypair], Message::new(instructions))
+            .unwrap();
+        let instruction =
+            budget_instruction::cancel(&bob_pubkey, &not_expired_pubkey, &alice_pubkey);
+        assert_eq!(
+            bank_client
+                .send_instruction(&bob_keypair, instruction)
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::CustomError(BudgetError::CancelNotAllowed as u32)
+            )
+        );
+
+        // expired at slot 0, anyone may refund alice but not themselves
+        let expired_pubkey = Pubkey::new_rand();
+        let instructions = budget_instruction::create_account_with_expiry(
+            &alice_pubkey,
+            &expired_pubkey,
+            1,
+            expr,
+            0,
+            ticks_per_slot,
+        );
+        bank_client
+            .send_message(&[&alice_keypair], Message::new(instructions))
+            .unwrap();
+        assert_eq!(bank_client.get_balance(&alice_pubkey).unwrap(), 1);
+
+        let instruction = budget_instruction::cancel(&bob_pubkey, &expired_pubkey, &bob_pubkey);
+        assert_eq!(
+            bank_client
+                .send_instruction(&bob_keypair, instruction)
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::CustomError(BudgetError::DestinationMissing as u32)
+            )
+        );
+        let instruction = budget_instruction::cancel(&bob_pubkey, &expired_pubkey, &alice_pubkey);
+        bank_client
+            .send_instruction(&bob_keypair, instruction)
+            .unwrap();
+        assert_eq!(bank_client.get_balance(&alice_pubkey).unwrap(), 2);
+        assert_eq!(bank_client.get_balance(&bob_pubkey).unwrap(), 1);
+        assert_eq!(bank_client.get_account_data(&expired_pubkey).unwrap(), None);
+    }
+
+    #[test]
+    fn test_cancel_expiry_uses_cluster_ticks_per_slot() {
+        let (mut genesis_block, alice_keypair) = create_genesis_block(4);
+        genesis_block.ticks_per_slot = 2;
+        let mut bank = Bank::new(&genesis_block);
+        bank.add_instruction_processor(id(), process_instruction);
+        let alice_pubkey = alice_keypair.pubkey();
+        let bob_keypair = Keypair::new();
+        let bob_pubkey = bob_keypair.pubkey();
+        bank.transfer(1, &alice_keypair, &bob_pubkey).unwrap();
+
+        let contract_pubkey = Pubkey::new_rand();
+        let expr = BudgetExpr::new_authorized_payment(&Pubkey::new_rand(), 1, &bob_pubkey);
+        let instructions = budget_instruction::create_account_with_expiry(
+            &alice_pubkey,
+            &contract_pubkey,
+            1,
+            expr,
+            1,
+            genesis_block.ticks_per_slot,
+        );
+        let tx = Transaction::new_signed_instructions(
+            &[&alice_keypair],
+            instructions,
+            bank.last_blockhash(),
+        );
+        bank.process_transaction(&tx).unwrap();
+
+        // slot 1 starts after 2 ticks, it would take 8 at the default ticks per slot
+        for _ in 0..genesis_block.ticks_per_slot {
+            bank.register_tick(&Hash::default());
+        }
+        let instruction = budget_instruction::cancel(&bob_pubkey, &contract_pubkey, &alice_pubkey);
+        let tx = Transaction::new_signed_instructions(
+            &[&bob_keypair],
+            vec![instruction],
+            bank.last_blockhash(),
+        );
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&alice_pubkey), 3);
+        assert_eq!(bank.get_balance(&contract_pubkey), 0);
+    }
 }
@@ -5,10 +5,12 @@ use num_derive::FromPrimitive;
 use serde_derive::{Deserialize, Serialize};
 use morgan_interface::instruction::InstructionError;
 use morgan_interface::instruction_processor_utils::DecodeError;
+use morgan_interface::pubkey::Pubkey;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromPrimitive)]
 pub enum BudgetError {
     DestinationMissing,
+    CancelNotAllowed,
 }
 
 impl<T> DecodeError<T> for BudgetError {
@@ -28,13 +30,19 @@ impl std::error::Error for BudgetError {}
 pub struct BudgetState {
     pub initialized: bool,
     pub pending_budget: Option<BudgetExpr>,
+    /// Who funded the contract and gets the difs back on `Cancel`
+    pub payer: Pubkey,
+    /// The tick height from which anyone may cancel the contract on the payer's behalf
+    pub expiry_tick_height: Option<u64>,
 }
 
 impl BudgetState {
-    pub fn new(budget_expr: BudgetExpr) -> Self {
+    pub fn new(budget_expr: BudgetExpr, payer: &Pubkey, expiry_tick_height: Option<u64>) -> Self {
         Self {
             initialized: true,
             pending_budget: Some(budget_expr),
+            payer: *payer,
+            expiry_tick_height,
         }
     }
 
@@ -1,3 +1,4 @@
+use crate::config_schema::{ConfigHeader, CONFIG_HEADER_LEN};
 use crate::id;
 use crate::ConfigState;
 use morgan_interface::instruction::{AccountMeta, Instruction};
@@ -10,17 +11,35 @@ pub fn create_account<T: ConfigState>(
     config_account_pubkey: &Pubkey,
     difs: u64,
 ) -> Instruction {
+    let header_space = if T::schema().is_some() {
+        CONFIG_HEADER_LEN
+    } else {
+        0
+    };
     system_instruction::create_account(
         from_account_pubkey,
         config_account_pubkey,
         difs,
-        T::max_space(),
+        header_space + T::max_space(),
         &id(),
     )
 }
 
-/// Store new data in a configuration account
+/// Store new data in a configuration account, behind a header if `T` has a schema
 pub fn store<T: ConfigState>(config_account_pubkey: &Pubkey, data: &T) -> Instruction {
     let account_metas = vec![AccountMeta::new(*config_account_pubkey, true)];
-    Instruction::new(id(), data, account_metas)
+    match T::schema() {
+        Some((schema_id, version)) => {
+            let payload = bincode::serialize(data).unwrap();
+            let header = ConfigHeader::new(schema_id, version, payload.len() as u32);
+            let mut data = bincode::serialize(&header).unwrap();
+            data.extend_from_slice(&payload);
+            Instruction {
+                program_ids_index: id(),
+                accounts: account_metas,
+                data,
+            }
+        }
+        None => Instruction::new(id(), data, account_metas),
+    }
 }
@@ -1,5 +1,8 @@
 //! Config program
 
+use crate::config_schema::{
+    find_schema, ConfigError, ConfigHeader, ConfigSchema, CONFIG_HEADER_LEN, CONFIG_SCHEMAS,
+};
 use log::*;
 use morgan_interface::account::KeyedAccount;
 use morgan_interface::instruction::InstructionError;
@@ -11,6 +14,38 @@ pub fn process_instruction(
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
     _tick_height: u64,
+) -> Result<(), InstructionError> {
+    process_store(keyed_accounts, data, CONFIG_SCHEMAS)
+}
+
+/// Check a typed payload against its registered schema.  Once an account holds a typed
+/// payload, only payloads of the same schema may replace it, so consumers always find the
+/// layout they expect.
+fn check_schema(
+    schemas: &[ConfigSchema],
+    current_data: &[u8],
+    data: &[u8],
+) -> Result<(), ConfigError> {
+    let current_header = ConfigHeader::from_data(current_data);
+    let header = match ConfigHeader::from_data(data) {
+        Some(header) => header,
+        None if current_header.is_some() => return Err(ConfigError::SchemaMismatch),
+        None => return Ok(()),
+    };
+    let schema = find_schema(schemas, header.schema_id).ok_or(ConfigError::UnknownSchema)?;
+    schema.check(&header, &data[CONFIG_HEADER_LEN as usize..])?;
+    match current_header {
+        Some(current_header) if current_header.schema_id != header.schema_id => {
+            Err(ConfigError::SchemaMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+fn process_store(
+    keyed_accounts: &mut [KeyedAccount],
+    data: &[u8],
+    schemas: &[ConfigSchema],
 ) -> Result<(), InstructionError> {
     if keyed_accounts[0].signer_key().is_none() {
         // error!("{}", Error(format!("account[0].signer_key().is_none()").to_string()));
@@ -36,6 +71,17 @@ pub fn process_instruction(
         Err(InstructionError::InvalidInstructionData)?;
     }
 
+    if let Err(err) = check_schema(schemas, &keyed_accounts[0].account.data, data) {
+        println!(
+            "{}",
+            Error(
+                format!("config payload rejected: {:?}", err).to_string(),
+                module_path!().to_string()
+            )
+        );
+        Err(InstructionError::CustomError(err as u32))?;
+    }
+
     keyed_accounts[0].account.data[0..data.len()].copy_from_slice(data);
     Ok(())
 }
@@ -43,6 +89,7 @@ pub fn process_instruction(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config_schema::{config_payload, ConfigSchemaId, UPDATE_MANIFEST_SCHEMA_ID};
     use crate::{config_instruction, id, ConfigState};
     use bincode::{deserialize, serialized_size};
     use serde_derive::{Deserialize, Serialize};
@@ -73,6 +120,34 @@ mod tests {
         }
     }
 
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+    struct MyTypedConfig {
+        pub item: u64,
+    }
+
+    impl ConfigState for MyTypedConfig {
+        fn max_space() -> u64 {
+            serialized_size(&Self::default()).unwrap()
+        }
+        fn schema() -> Option<(ConfigSchemaId, u32)> {
+            Some((UPDATE_MANIFEST_SCHEMA_ID, 1))
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+    struct MyUnregisteredConfig {
+        pub item: u64,
+    }
+
+    impl ConfigState for MyUnregisteredConfig {
+        fn max_space() -> u64 {
+            serialized_size(&Self::default()).unwrap()
+        }
+        fn schema() -> Option<(ConfigSchemaId, u32)> {
+            Some((UPDATE_MANIFEST_SCHEMA_ID + 1, 1))
+        }
+    }
+
     fn create_bank(difs: u64) -> (Bank, Keypair) {
         let (genesis_block, mint_keypair) = create_genesis_block(difs);
         let mut bank = Bank::new(&genesis_block);
@@ -81,6 +156,13 @@ mod tests {
     }
 
     fn create_config_account(bank: Bank, mint_keypair: &Keypair) -> (BankClient, Keypair) {
+        create_config_account_for::<MyConfig>(bank, mint_keypair)
+    }
+
+    fn create_config_account_for<T: ConfigState>(
+        bank: Bank,
+        mint_keypair: &Keypair,
+    ) -> (BankClient, Keypair) {
         let config_keypair = Keypair::new();
         let config_pubkey = config_keypair.pubkey();
 
@@ -88,11 +170,7 @@ mod tests {
         bank_client
             .send_instruction(
                 mint_keypair,
-                config_instruction::create_account::<MyConfig>(
-                    &mint_keypair.pubkey(),
-                    &config_pubkey,
-                    1,
-                ),
+                config_instruction::create_account::<T>(&mint_keypair.pubkey(), &config_pubkey, 1),
             )
             .expect("new_account");
 
@@ -178,4 +256,135 @@ mod tests {
             .send_message(&[&system_keypair], message)
             .unwrap_err();
     }
+
+    fn store_config<T: ConfigState>(
+        bank_client: &BankClient,
+        mint_keypair: &Keypair,
+        config_keypair: &Keypair,
+        config: &T,
+    ) -> bool {
+        let instruction = config_instruction::store(&config_keypair.pubkey(), config);
+        let message = Message::new_with_payer(vec![instruction], Some(&mint_keypair.pubkey()));
+        bank_client
+            .send_message(&[mint_keypair, config_keypair], message)
+            .is_ok()
+    }
+
+    #[test]
+    fn test_process_store_typed_ok() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (bank_client, config_keypair) =
+            create_config_account_for::<MyTypedConfig>(bank, &mint_keypair);
+
+        let my_config = MyTypedConfig { item: 42 };
+        assert!(store_config(
+            &bank_client,
+            &mint_keypair,
+            &config_keypair,
+            &my_config
+        ));
+
+        let config_account_data = bank_client
+            .get_account_data(&config_keypair.pubkey())
+            .unwrap()
+            .unwrap();
+        let header = ConfigHeader::from_data(&config_account_data).unwrap();
+        assert_eq!(header.schema_id, UPDATE_MANIFEST_SCHEMA_ID);
+        assert_eq!(
+            my_config,
+            deserialize(config_payload(&config_account_data)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_process_store_typed_fail() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (bank_client, config_keypair) =
+            create_config_account_for::<MyTypedConfig>(bank, &mint_keypair);
+
+        // Unregistered schemas are refused
+        assert!(!store_config(
+            &bank_client,
+            &mint_keypair,
+            &config_keypair,
+            &MyUnregisteredConfig { item: 42 }
+        ));
+
+        // and once typed, an account can't go back to an untyped payload
+        assert!(store_config(
+            &bank_client,
+            &mint_keypair,
+            &config_keypair,
+            &MyTypedConfig { item: 42 }
+        ));
+        assert!(!store_config(
+            &bank_client,
+            &mint_keypair,
+            &config_keypair,
+            &MyConfig::new(43)
+        ));
+        let config_account_data = bank_client
+            .get_account_data(&config_keypair.pubkey())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            MyTypedConfig { item: 42 },
+            deserialize(config_payload(&config_account_data)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_check_schema() {
+        let schemas = [ConfigSchema {
+            id: 7,
+            min_version: 1,
+            max_version: 2,
+            min_len: 1,
+            max_len: 8,
+        }];
+        let typed = |schema_id, version, payload: &[u8]| {
+            let header = ConfigHeader::new(schema_id, version, payload.len() as u32);
+            let mut data = bincode::serialize(&header).unwrap();
+            data.extend_from_slice(payload);
+            data
+        };
+        let untyped = vec![0u8; 24];
+
+        assert_eq!(check_schema(&schemas, &untyped, &[1, 2, 3]), Ok(()));
+        assert_eq!(check_schema(&schemas, &untyped, &typed(7, 1, &[1])), Ok(()));
+        assert_eq!(
+            check_schema(&schemas, &typed(7, 1, &[1]), &typed(7, 2, &[1, 2])),
+            Ok(())
+        );
+        assert_eq!(
+            check_schema(&schemas, &untyped, &typed(8, 1, &[1])),
+            Err(ConfigError::UnknownSchema)
+        );
+        assert_eq!(
+            check_schema(&schemas, &untyped, &typed(7, 3, &[1])),
+            Err(ConfigError::UnsupportedVersion)
+        );
+        assert_eq!(
+            check_schema(&schemas, &untyped, &typed(7, 1, &[0; 9])),
+            Err(ConfigError::InvalidLength)
+        );
+        assert_eq!(
+            check_schema(&schemas, &typed(7, 1, &[1]), &[1, 2, 3]),
+            Err(ConfigError::SchemaMismatch)
+        );
+
+        let schemas = [
+            schemas[0],
+            ConfigSchema {
+                id: 8,
+                ..schemas[0]
+            },
+        ];
+        assert_eq!(
+            check_schema(&schemas, &typed(7, 1, &[1]), &typed(8, 1, &[1])),
+            Err(ConfigError::SchemaMismatch)
+        );
+    }
 }
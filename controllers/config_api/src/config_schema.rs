@@ -0,0 +1,179 @@
+//! Optional schemas for configuration payloads.  A payload stored under a schema is prefixed
+//! with a `ConfigHeader` naming the schema and version it follows, and the config program
+//! refuses payloads that don't satisfy the registered bounds of their schema.
+
+use bincode::deserialize;
+use num_derive::FromPrimitive;
+use serde_derive::{Deserialize, Serialize};
+use morgan_interface::instruction_processor_utils::DecodeError;
+
+pub type ConfigSchemaId = u32;
+
+/// Marks account data that starts with a `ConfigHeader`, the bytes "MCFG"
+pub const CONFIG_HEADER_MAGIC: u32 = 0x4746_434d;
+
+/// Serialized size of a `ConfigHeader`
+pub const CONFIG_HEADER_LEN: u64 = 16;
+
+/// Schema of the update manifest published for `morgan-install`
+pub const UPDATE_MANIFEST_SCHEMA_ID: ConfigSchemaId = 1;
+
+/// Schemas the config program enforces, a payload naming any other schema is refused
+pub const CONFIG_SCHEMAS: &[ConfigSchema] = &[ConfigSchema {
+    id: UPDATE_MANIFEST_SCHEMA_ID,
+    min_version: 1,
+    max_version: 1,
+    min_len: 1,
+    max_len: 256,
+}];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromPrimitive)]
+pub enum ConfigError {
+    UnknownSchema,
+    UnsupportedVersion,
+    InvalidLength,
+    /// A payload with a different schema, or none at all, was stored over a typed payload
+    SchemaMismatch,
+}
+
+impl<T> DecodeError<T> for ConfigError {
+    fn type_of(&self) -> &'static str {
+        "ConfigError"
+    }
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "error")
+    }
+}
+impl std::error::Error for ConfigError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct ConfigHeader {
+    pub magic: u32,
+    pub schema_id: ConfigSchemaId,
+    pub version: u32,
+    pub len: u32, // Bytes of payload following the header
+}
+
+impl ConfigHeader {
+    pub fn new(schema_id: ConfigSchemaId, version: u32, len: u32) -> Self {
+        Self {
+            magic: CONFIG_HEADER_MAGIC,
+            schema_id,
+            version,
+            len,
+        }
+    }
+
+    /// The header `data` starts with, None if `data` holds an untyped payload
+    pub fn from_data(data: &[u8]) -> Option<Self> {
+        if (data.len() as u64) < CONFIG_HEADER_LEN {
+            return None;
+        }
+        let header: Self = deserialize(&data[..CONFIG_HEADER_LEN as usize]).ok()?;
+        if header.magic == CONFIG_HEADER_MAGIC {
+            Some(header)
+        } else {
+            None
+        }
+    }
+}
+
+/// The payload held in configuration account `data`, without the header of a typed payload
+pub fn config_payload(data: &[u8]) -> &[u8] {
+    match ConfigHeader::from_data(data) {
+        Some(header) => {
+            let start = CONFIG_HEADER_LEN as usize;
+            let end = (start + header.len as usize).min(data.len());
+            &data[start..end]
+        }
+        None => data,
+    }
+}
+
+/// Bounds a typed payload must stay within
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConfigSchema {
+    pub id: ConfigSchemaId,
+    pub min_version: u32,
+    pub max_version: u32,
+    pub min_len: u64,
+    pub max_len: u64,
+}
+
+impl ConfigSchema {
+    pub fn check(&self, header: &ConfigHeader, payload: &[u8]) -> Result<(), ConfigError> {
+        if header.version < self.min_version || header.version > self.max_version {
+            return Err(ConfigError::UnsupportedVersion);
+        }
+        let len = payload.len() as u64;
+        if len != u64::from(header.len) || len < self.min_len || len > self.max_len {
+            return Err(ConfigError::InvalidLength);
+        }
+        Ok(())
+    }
+}
+
+pub fn find_schema(schemas: &[ConfigSchema], id: ConfigSchemaId) -> Option<&ConfigSchema> {
+    schemas.iter().find(|schema| schema.id == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::serialized_size;
+
+    #[test]
+    fn test_config_header() {
+        let header = ConfigHeader::new(UPDATE_MANIFEST_SCHEMA_ID, 1, 3);
+        assert_eq!(serialized_size(&header).unwrap(), CONFIG_HEADER_LEN);
+
+        let mut data = bincode::serialize(&header).unwrap();
+        data.extend_from_slice(&[1, 2, 3, 0, 0]);
+        assert_eq!(ConfigHeader::from_data(&data), Some(header));
+        assert_eq!(config_payload(&data), &[1, 2, 3]);
+
+        let untyped = vec![0; 20];
+        assert_eq!(ConfigHeader::from_data(&untyped), None);
+        assert_eq!(config_payload(&untyped), &untyped[..]);
+        assert_eq!(ConfigHeader::from_data(&data[..8]), None);
+    }
+
+    #[test]
+    fn test_config_schema_check() {
+        let schema = ConfigSchema {
+            id: 7,
+            min_version: 2,
+            max_version: 3,
+            min_len: 1,
+            max_len: 4,
+        };
+        let payload = [0u8; 4];
+        assert_eq!(schema.check(&ConfigHeader::new(7, 2, 4), &payload), Ok(()));
+        assert_eq!(
+            schema.check(&ConfigHeader::new(7, 1, 4), &payload),
+            Err(ConfigError::UnsupportedVersion)
+        );
+        assert_eq!(
+            schema.check(&ConfigHeader::new(7, 4, 4), &payload),
+            Err(ConfigError::UnsupportedVersion)
+        );
+        assert_eq!(
+            schema.check(&ConfigHeader::new(7, 3, 0), &[]),
+            Err(ConfigError::InvalidLength)
+        );
+        assert_eq!(
+            schema.check(&ConfigHeader::new(7, 3, 5), &[0u8; 5]),
+            Err(ConfigError::InvalidLength)
+        );
+        assert_eq!(
+            schema.check(&ConfigHeader::new(7, 3, 3), &payload),
+            Err(ConfigError::InvalidLength)
+        );
+
+        assert_eq!(find_schema(&[schema], 7), Some(&schema));
+        assert_eq!(find_schema(CONFIG_SCHEMAS, 7), None);
+    }
+}
@@ -2,6 +2,9 @@ use serde::Serialize;
 
 pub mod config_instruction;
 pub mod config_processor;
+pub mod config_schema;
+
+use crate::config_schema::ConfigSchemaId;
 
 const CONFIG_PROGRAM_ID: [u8; 32] = [
     3, 6, 74, 163, 0, 47, 116, 220, 200, 110, 67, 49, 15, 12, 5, 42, 248, 197, 218, 39, 246, 16,
@@ -13,4 +16,9 @@ morgan_interface::morgan_program_id!(CONFIG_PROGRAM_ID);
 pub trait ConfigState: Serialize {
     /// Maximum space that the serialized representation will require
     fn max_space() -> u64;
+
+    /// The registered schema and version to store the payload under, None stores it untyped
+    fn schema() -> Option<(ConfigSchemaId, u32)> {
+        None
+    }
 }
@@ -20,6 +20,12 @@ pub struct TradeRequestInfo {
     /// The price ratio the primary price over the secondary price.  The primary price is fixed
     /// and equal to the variable `SCALER`.
     pub price: u64,
+
+    /// Most tokens a single swap may fill, `None` displays the whole order
+    pub displayed_tokens: Option<u64>,
+
+    /// How swaps against another order of the same owner are prevented, `None` allows them
+    pub self_trade_prevention: Option<SelfTradePrevention>,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -39,6 +45,7 @@ pub enum ExchangeInstruction {
     /// key 0 - Signer
     /// key 1 - Account in which to record the trade order
     /// key 2 - Token account to source tokens from
+    /// key 3 - Pair registry the trade must satisfy
     TradeRequest(TradeRequestInfo),
 
     /// Trade cancellation
@@ -48,10 +55,48 @@ pub enum ExchangeInstruction {
 
     /// Trade swap request
     /// key 0 - Signer
-    /// key 2 - 'To' trade order
-    /// key 3 - `From` trade order
-    /// key 6 - Token account in which to deposit the brokers profit from the swap.
+    /// key 1 - 'To' trade order
+    /// key 2 - `From` trade order
+    /// key 3 - Token account in which to deposit the brokers profit from the swap.
+    /// key 4 - Pair registry both trade orders were placed under
+    /// key 5 - Fee vault of the pair, only needed if the registry charges fees on the pair
+    /// If both trade orders have the same owner and either asks for self-trade prevention,
+    /// one of them is cancelled instead.
     SwapRequest,
+
+    /// New pair registry with no pairs listed
+    /// key 0 - Signer, becomes the registry admin
+    /// key 1 - New pair registry account
+    PairRegistryRequest,
+
+    /// List a token pair, or replace the rules of an already listed one
+    /// key 0 - Signer, must be the registry admin
+    /// key 1 - Pair registry
+    ListPair(TokenPair, PairListing),
+
+    /// Stop accepting new trade orders for a token pair
+    /// key 0 - Signer, must be the registry admin
+    /// key 1 - Pair registry
+    DelistPair(TokenPair),
+
+    /// Set the fees charged on swaps of a listed token pair
+    /// key 0 - Signer, must be the registry admin
+    /// key 1 - Pair registry
+    SetPairFees(TokenPair, PairFees),
+
+    /// Move the fees collected from swaps of a token pair to the pair's fee collector
+    /// key 0 - Signer, must be the registry admin
+    /// key 1 - Pair registry
+    /// key 2 - Fee collector token account
+    /// key 3.. - Fee vaults of the pair to empty
+    WithdrawFees(TokenPair),
+
+    /// New fee vault collecting the fees of swaps of a token pair.  Swaps using different vaults
+    /// of the same pair don't contend for one account.
+    /// key 0 - Signer, must be the registry admin
+    /// key 1 - Pair registry
+    /// key 2 - New fee vault account
+    FeeVaultRequest(TokenPair),
 }
 
 pub fn account_request(owner: &Pubkey, new: &Pubkey) -> Instruction {
@@ -81,6 +126,7 @@ pub fn transfer_request(
     )
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn trade_request(
     owner: &Pubkey,
     trade: &Pubkey,
@@ -89,20 +135,41 @@ pub fn trade_request(
     tokens: u64,
     price: u64,
     src_account: &Pubkey,
+    registry: &Pubkey,
+) -> Instruction {
+    trade_request_with_info(
+        owner,
+        trade,
+        TradeRequestInfo {
+            direction,
+            pair,
+            tokens,
+            price,
+            displayed_tokens: None,
+            self_trade_prevention: None,
+        },
+        src_account,
+        registry,
+    )
+}
+
+/// Trade request that may hide part of its size or prevent self-trades
+pub fn trade_request_with_info(
+    owner: &Pubkey,
+    trade: &Pubkey,
+    info: TradeRequestInfo,
+    src_account: &Pubkey,
+    registry: &Pubkey,
 ) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*owner, true),
         AccountMeta::new(*trade, false),
         AccountMeta::new(*src_account, false),
+        AccountMeta::new_credit_only(*registry, false),
     ];
     Instruction::new(
         id(),
-        &ExchangeInstruction::TradeRequest(TradeRequestInfo {
-            direction,
-            pair,
-            tokens,
-            price,
-        }),
+        &ExchangeInstruction::TradeRequest(info),
         account_metas,
     )
 }
@@ -120,12 +187,142 @@ pub fn swap_request(
     to_trade: &Pubkey,
     from_trade: &Pubkey,
     profit_account: &Pubkey,
+    registry: &Pubkey,
 ) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*owner, true),
         AccountMeta::new(*to_trade, false),
         AccountMeta::new(*from_trade, false),
         AccountMeta::new(*profit_account, false),
+        AccountMeta::new_credit_only(*registry, false),
     ];
     Instruction::new(id(), &ExchangeInstruction::SwapRequest, account_metas)
 }
+
+/// Swap request on a pair the registry charges fees on, the fees go to `fee_vault`
+pub fn swap_request_with_fee_vault(
+    owner: &Pubkey,
+    to_trade: &Pubkey,
+    from_trade: &Pubkey,
+    profit_account: &Pubkey,
+    registry: &Pubkey,
+    fee_vault: &Pubkey,
+) -> Instruction {
+    let mut instruction = swap_request(owner, to_trade, from_trade, profit_account, registry);
+    instruction
+        .accounts
+        .push(AccountMeta::new(*fee_vault, false));
+    instruction
+}
+
+pub fn pair_registry_request(admin: &Pubkey, new: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*new, false),
+    ];
+    Instruction::new(
+        id(),
+        &ExchangeInstruction::PairRegistryRequest,
+        account_metas,
+    )
+}
+
+pub fn list_pair(
+    admin: &Pubkey,
+    registry: &Pubkey,
+    pair: TokenPair,
+    tick_size: u64,
+    min_order_size: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*registry, false),
+    ];
+    Instruction::new(
+        id(),
+        &ExchangeInstruction::ListPair(
+            pair,
+            PairListing {
+                tick_size,
+                min_order_size,
+            },
+        ),
+        account_metas,
+    )
+}
+
+pub fn delist_pair(admin: &Pubkey, registry: &Pubkey, pair: TokenPair) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*registry, false),
+    ];
+    Instruction::new(id(), &ExchangeInstruction::DelistPair(pair), account_metas)
+}
+
+pub fn set_pair_fees(
+    admin: &Pubkey,
+    registry: &Pubkey,
+    pair: TokenPair,
+    maker_rebate_bps: u64,
+    taker_fee_bps: u64,
+    fee_collector: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new(*registry, false),
+    ];
+    Instruction::new(
+        id(),
+        &ExchangeInstruction::SetPairFees(
+            pair,
+            PairFees {
+                maker_rebate_bps,
+                taker_fee_bps,
+                fee_collector: *fee_collector,
+            },
+        ),
+        account_metas,
+    )
+}
+
+pub fn withdraw_fees(
+    admin: &Pubkey,
+    registry: &Pubkey,
+    pair: TokenPair,
+    fee_collector: &Pubkey,
+    fee_vaults: &[Pubkey],
+) -> Instruction {
+    let mut account_metas = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_credit_only(*registry, false),
+        AccountMeta::new(*fee_collector, false),
+    ];
+    account_metas.extend(
+        fee_vaults
+            .iter()
+            .map(|fee_vault| AccountMeta::new(*fee_vault, false)),
+    );
+    Instruction::new(
+        id(),
+        &ExchangeInstruction::WithdrawFees(pair),
+        account_metas,
+    )
+}
+
+pub fn fee_vault_request(
+    admin: &Pubkey,
+    registry: &Pubkey,
+    pair: TokenPair,
+    new: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*admin, true),
+        AccountMeta::new_credit_only(*registry, false),
+        AccountMeta::new(*new, false),
+    ];
+    Instruction::new(
+        id(),
+        &ExchangeInstruction::FeeVaultRequest(pair),
+        account_metas,
+    )
+}
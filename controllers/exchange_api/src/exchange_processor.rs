@@ -2,11 +2,12 @@
 
 use crate::exchange_instruction::*;
 use crate::exchange_state::*;
-use crate::faucet_id;
+use crate::{faucet_id, id};
 use log::*;
 use morgan_metricbot::inc_new_counter_info;
 use morgan_interface::account::KeyedAccount;
 use morgan_interface::instruction::InstructionError;
+use morgan_interface::instruction_processor_utils::limited_deserialize;
 use morgan_interface::pubkey::Pubkey;
 use std::cmp;
 use morgan_helper::logHelper::*;
@@ -78,6 +79,141 @@ impl ExchangeProcessor {
         }
     }
 
+    fn deserialize_registry(
+        keyed_account: &KeyedAccount,
+    ) -> Result<PairRegistryInfo, InstructionError> {
+        if keyed_account.account.owner != id() {
+            println!(
+                "{}",
+                Error(
+                    "Pair registry is not owned by the exchange".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidAccountData);
+        }
+        let state: ExchangeState =
+            bincode::deserialize(&keyed_account.account.data).map_err(Self::map_to_invalid_arg)?;
+        if let ExchangeState::PairRegistry(registry) = state {
+            Ok(registry)
+        } else {
+            println!(
+                "{}",
+                Error(
+                    "Not a valid pair registry".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            Err(InstructionError::InvalidAccountData)
+        }
+    }
+
+    /// Return the fee vault held by `keyed_account` if it collects the fees of `pair` for `registry`
+    fn deserialize_fee_vault(
+        keyed_account: &KeyedAccount,
+        registry: &Pubkey,
+        pair: TokenPair,
+    ) -> Result<FeeVaultInfo, InstructionError> {
+        if keyed_account.account.owner != id() {
+            println!(
+                "{}",
+                Error(
+                    "Fee vault is not owned by the exchange".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidAccountData);
+        }
+        let state: ExchangeState =
+            bincode::deserialize(&keyed_account.account.data).map_err(Self::map_to_invalid_arg)?;
+        match state {
+            ExchangeState::FeeVault(fee_vault)
+                if fee_vault.registry == *registry && fee_vault.pair == pair =>
+            {
+                Ok(fee_vault)
+            }
+            _ => {
+                println!(
+                    "{}",
+                    Error(
+                        format!("Not a fee vault of token pair {:?}", pair),
+                        module_path!().to_string()
+                    )
+                );
+                Err(InstructionError::InvalidAccountData)
+            }
+        }
+    }
+
+    /// Return the registry held by `registry_account` if `admin_account` signed and administers it
+    fn authorized_registry(
+        admin_account: &KeyedAccount,
+        registry_account: &KeyedAccount,
+    ) -> Result<PairRegistryInfo, InstructionError> {
+        let registry = Self::deserialize_registry(registry_account)?;
+        match admin_account.signer_key() {
+            Some(signer) if *signer == registry.admin => Ok(registry),
+            Some(_) => {
+                println!(
+                    "{}",
+                    Error(
+                        "Signer is not the pair registry admin".to_string(),
+                        module_path!().to_string()
+                    )
+                );
+                Err(InstructionError::GenericError)
+            }
+            None => Err(InstructionError::MissingRequiredSignature),
+        }
+    }
+
+    /// Check a trade request against the rules its pair is listed with
+    fn check_listing(
+        registry: &PairRegistryInfo,
+        info: &TradeRequestInfo,
+    ) -> Result<(), InstructionError> {
+        let listing = match registry.listing(info.pair) {
+            Some(listing) => listing,
+            None => {
+                println!(
+                    "{}",
+                    Error(
+                        format!("Token pair {:?} is not listed", info.pair),
+                        module_path!().to_string()
+                    )
+                );
+                return Err(InstructionError::InvalidArgument);
+            }
+        };
+        if info.price % listing.tick_size != 0 {
+            println!(
+                "{}",
+                Error(
+                    format!(
+                        "Price {} is not a multiple of the tick size {}",
+                        info.price, listing.tick_size
+                    ),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+        if info.tokens < listing.min_order_size {
+            println!(
+                "{}",
+                Error(
+                    format!(
+                        "Order of {} tokens is below the minimum order size {}",
+                        info.tokens, listing.min_order_size
+                    ),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+        Ok(())
+    }
+
     fn serialize(state: &ExchangeState, data: &mut [u8]) -> Result<(), InstructionError> {
         let writer = std::io::BufWriter::new(data);
         match bincode::serialize_into(writer, state) {
@@ -96,26 +232,63 @@ impl ExchangeProcessor {
         }
     }
 
-    fn trade_to_token_account(trade: &TradeOrderInfo) -> TokenAccountInfo {
-        // Turn trade order into token account
-
-        let token = match trade.direction {
+    /// Token a trade order receives when it is swapped
+    fn settled_token(trade: &TradeOrderInfo) -> Token {
+        match trade.direction {
             Direction::To => trade.pair.secondary(),
             Direction::From => trade.pair.primary(),
-        };
+        }
+    }
+
+    fn trade_to_token_account(trade: &TradeOrderInfo) -> TokenAccountInfo {
+        // Turn trade order into token account
 
         let mut account = TokenAccountInfo::default().owner(&trade.owner);
-        account.tokens[token] = trade.tokens_settled;
+        account.tokens[Self::settled_token(trade)] = trade.tokens_settled;
         account
     }
 
+    /// Charge the taker fee on what the newest of two swapped orders received, and pay the maker
+    /// rebate on what the other one received as far as the fees in the fee vault cover it
+    fn apply_fees(
+        fees: &PairFees,
+        collected_fees: &mut Tokens,
+        to_trade: &mut TradeOrderInfo,
+        from_trade: &mut TradeOrderInfo,
+        to_received: u64,
+        from_received: u64,
+    ) {
+        let (taker, taker_received, maker, maker_received) =
+            if Self::to_trade_is_newest(to_trade, from_trade) {
+                (to_trade, to_received, from_trade, from_received)
+            } else {
+                (from_trade, from_received, to_trade, to_received)
+            };
+
+        let taker_fee = fees.taker_fee(taker_received);
+        taker.tokens_settled -= taker_fee;
+        collected_fees[Self::settled_token(taker)] += taker_fee;
+
+        let maker_token = Self::settled_token(maker);
+        let maker_rebate = cmp::min(
+            fees.maker_rebate(maker_received),
+            collected_fees[maker_token],
+        );
+        collected_fees[maker_token] -= maker_rebate;
+        maker.tokens_settled += maker_rebate;
+    }
+
     fn calculate_swap(
         scaler: u64,
         to_trade: &mut TradeOrderInfo,
         from_trade: &mut TradeOrderInfo,
         profit_account: &mut TokenAccountInfo,
     ) -> Result<(), InstructionError> {
-        if to_trade.tokens == 0 || from_trade.tokens == 0 {
+        // Iceberg orders only offer their displayed tokens to a swap
+        let to_tokens = to_trade.visible_tokens();
+        let from_tokens = from_trade.visible_tokens();
+
+        if to_tokens == 0 || from_tokens == 0 {
             // error!("{}", Error(format!("Inactive Trade, balance is zero").to_string()));
             println!(
                 "{}",
@@ -140,16 +313,16 @@ impl ExchangeProcessor {
 
         // Calc swap
 
-        trace!("tt {} ft {}", to_trade.tokens, from_trade.tokens);
+        trace!("tt {} ft {}", to_tokens, from_tokens);
         trace!("tp {} fp {}", to_trade.price, from_trade.price);
 
-        let max_to_secondary = to_trade.tokens * to_trade.price / scaler;
-        let max_to_primary = from_trade.tokens * scaler / from_trade.price;
+        let max_to_secondary = to_tokens * to_trade.price / scaler;
+        let max_to_primary = from_tokens * scaler / from_trade.price;
 
         trace!("mtp {} mts {}", max_to_primary, max_to_secondary);
 
-        let max_primary = cmp::min(max_to_primary, to_trade.tokens);
-        let max_secondary = cmp::min(max_to_secondary, from_trade.tokens);
+        let max_primary = cmp::min(max_to_primary, to_tokens);
+        let max_secondary = cmp::min(max_to_secondary, from_tokens);
 
         trace!("mp {} ms {}", max_primary, max_secondary);
 
@@ -193,7 +366,7 @@ impl ExchangeProcessor {
 
         // Update tokens
 
-        if to_trade.tokens < primary_cost {
+        if to_tokens < primary_cost {
             // error!("{}", Error(format!("Not enough tokens in to account").to_string()));
             println!(
                 "{}",
@@ -204,7 +377,7 @@ impl ExchangeProcessor {
             );
             Err(InstructionError::InvalidArgument)?
         }
-        if from_trade.tokens < secondary_cost {
+        if from_tokens < secondary_cost {
             // error!("{}", Error(format!("Not enough tokens in from account").to_string()));
             println!(
                 "{}",
@@ -389,12 +562,14 @@ impl ExchangeProcessor {
     fn do_trade_request(
         keyed_accounts: &mut [KeyedAccount],
         info: &TradeRequestInfo,
+        tick_height: u64,
     ) -> Result<(), InstructionError> {
         const OWNER_INDEX: usize = 0;
         const TRADE_INDEX: usize = 1;
         const ACCOUNT_INDEX: usize = 2;
+        const REGISTRY_INDEX: usize = 3;
 
-        if keyed_accounts.len() < 3 {
+        if keyed_accounts.len() < 4 {
             // error!("{}", Error(format!("Not enough accounts").to_string()));
             println!(
                 "{}",
@@ -408,6 +583,20 @@ impl ExchangeProcessor {
 
         Self::is_account_unallocated(&keyed_accounts[TRADE_INDEX].account.data)?;
 
+        let registry = Self::deserialize_registry(&keyed_accounts[REGISTRY_INDEX])?;
+        Self::check_listing(&registry, info)?;
+
+        if info.displayed_tokens == Some(0) {
+            println!(
+                "{}",
+                Error(
+                    "Displayed tokens must be non-zero".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+
         let mut account = Self::deserialize_account(&keyed_accounts[ACCOUNT_INDEX].account.data)?;
 
         if &account.owner != keyed_accounts[OWNER_INDEX].unsigned_key() {
@@ -454,6 +643,10 @@ impl ExchangeProcessor {
                 tokens: info.tokens,
                 price: info.price,
                 tokens_settled: 0,
+                registry: *keyed_accounts[REGISTRY_INDEX].unsigned_key(),
+                displayed_tokens: info.displayed_tokens,
+                self_trade_prevention: info.self_trade_prevention,
+                tick_height,
             }),
             &mut keyed_accounts[TRADE_INDEX].account.data,
         )?;
@@ -493,6 +686,14 @@ impl ExchangeProcessor {
             Err(InstructionError::GenericError)?
         }
 
+        // Turn trade order into a token account
+        Self::serialize(
+            &ExchangeState::Account(Self::cancelled_trade_to_token_account(&trade)),
+            &mut keyed_accounts[TRADE_INDEX].account.data,
+        )
+    }
+
+    fn cancelled_trade_to_token_account(trade: &TradeOrderInfo) -> TokenAccountInfo {
         let token = match trade.direction {
             Direction::To => trade.pair.primary(),
             Direction::From => trade.pair.secondary(),
@@ -501,20 +702,47 @@ impl ExchangeProcessor {
         let mut account = TokenAccountInfo::default().owner(&trade.owner);
         account.tokens[token] = trade.tokens;
         account.tokens[token] += trade.tokens_settled;
+        account
+    }
 
-        // Turn trade order into a token account
-        Self::serialize(
-            &ExchangeState::Account(account),
-            &mut keyed_accounts[TRADE_INDEX].account.data,
-        )
+    /// Whether `to_trade` was placed after `from_trade`, of two orders placed at the same tick
+    /// the `From` one is newest
+    fn to_trade_is_newest(to_trade: &TradeOrderInfo, from_trade: &TradeOrderInfo) -> bool {
+        to_trade.tick_height > from_trade.tick_height
+    }
+
+    /// Direction of the order to cancel instead of swapping `to_trade` with `from_trade`, if
+    /// they belong to the same owner and either asks for self-trade prevention.  The newest
+    /// order's mode wins
+    fn self_trade_cancellation(
+        to_trade: &TradeOrderInfo,
+        from_trade: &TradeOrderInfo,
+    ) -> Option<Direction> {
+        if to_trade.owner != from_trade.owner {
+            return None;
+        }
+        let (newest, oldest) = if Self::to_trade_is_newest(to_trade, from_trade) {
+            (to_trade, from_trade)
+        } else {
+            (from_trade, to_trade)
+        };
+        match newest
+            .self_trade_prevention
+            .or(oldest.self_trade_prevention)?
+        {
+            SelfTradePrevention::CancelNewest => Some(newest.direction),
+            SelfTradePrevention::CancelOldest => Some(oldest.direction),
+        }
     }
 
     fn do_swap_request(keyed_accounts: &mut [KeyedAccount]) -> Result<(), InstructionError> {
         const TO_TRADE_INDEX: usize = 1;
         const FROM_TRADE_INDEX: usize = 2;
         const PROFIT_ACCOUNT_INDEX: usize = 3;
+        const REGISTRY_INDEX: usize = 4;
+        const FEE_VAULT_INDEX: usize = 5;
 
-        if keyed_accounts.len() < 4 {
+        if keyed_accounts.len() < 5 {
             // error!("{}", Error(format!("Not enough accounts").to_string()));
             println!(
                 "{}",
@@ -565,6 +793,27 @@ impl ExchangeProcessor {
             );
             Err(InstructionError::InvalidArgument)?
         }
+        if to_trade.registry != from_trade.registry {
+            println!(
+                "{}",
+                Error(
+                    "Trades were placed under different pair registries".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+        if *keyed_accounts[REGISTRY_INDEX].unsigned_key() != to_trade.registry {
+            println!(
+                "{}",
+                Error(
+                    "Pair registry is not the one the trades were placed under".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+        let registry = Self::deserialize_registry(&keyed_accounts[REGISTRY_INDEX])?;
         if to_trade.direction == from_trade.direction {
             // error!("{}", Error(format!("Matching trade directions").to_string()));
             println!(
@@ -577,6 +826,27 @@ impl ExchangeProcessor {
             Err(InstructionError::InvalidArgument)?
         }
 
+        if let Some(direction) = Self::self_trade_cancellation(&to_trade, &from_trade) {
+            println!(
+                "{}",
+                printLn(
+                    format!("Self-trade prevented, cancelling the {:?} trade", direction),
+                    module_path!().to_string()
+                )
+            );
+            inc_new_counter_info!("exchange_processor-self_trades_prevented", 1, 1000, 1000);
+            let (trade, index) = match direction {
+                Direction::To => (to_trade, TO_TRADE_INDEX),
+                Direction::From => (from_trade, FROM_TRADE_INDEX),
+            };
+            return Self::serialize(
+                &ExchangeState::Account(Self::cancelled_trade_to_token_account(&trade)),
+                &mut keyed_accounts[index].account.data,
+            );
+        }
+
+        let to_settled = to_trade.tokens_settled;
+        let from_settled = from_trade.tokens_settled;
         if let Err(e) =
             Self::calculate_swap(SCALER, &mut to_trade, &mut from_trade, &mut profit_account)
         {
@@ -599,6 +869,41 @@ impl ExchangeProcessor {
 
         inc_new_counter_info!("exchange_processor-swaps", 1, 1000, 1000);
 
+        if let Some(fees) = registry.fees(to_trade.pair) {
+            if keyed_accounts.len() <= FEE_VAULT_INDEX {
+                println!(
+                    "{}",
+                    Error(
+                        format!(
+                            "Token pair {:?} charges fees, missing a fee vault",
+                            to_trade.pair
+                        ),
+                        module_path!().to_string()
+                    )
+                );
+                return Err(InstructionError::InvalidArgument);
+            }
+            let mut fee_vault = Self::deserialize_fee_vault(
+                &keyed_accounts[FEE_VAULT_INDEX],
+                &to_trade.registry,
+                to_trade.pair,
+            )?;
+            let to_received = to_trade.tokens_settled - to_settled;
+            let from_received = from_trade.tokens_settled - from_settled;
+            Self::apply_fees(
+                &fees,
+                &mut fee_vault.collected_fees,
+                &mut to_trade,
+                &mut from_trade,
+                to_received,
+                from_received,
+            );
+            Self::serialize(
+                &ExchangeState::FeeVault(fee_vault),
+                &mut keyed_accounts[FEE_VAULT_INDEX].account.data,
+            )?;
+        }
+
         if to_trade.tokens == 0 {
             // Turn into token account
             Self::serialize(
@@ -630,57 +935,287 @@ impl ExchangeProcessor {
             &mut keyed_accounts[PROFIT_ACCOUNT_INDEX].account.data,
         )
     }
-}
 
-pub fn process_instruction(
-    _program_id: &Pubkey,
-    keyed_accounts: &mut [KeyedAccount],
-    data: &[u8],
-    _tick_height: u64,
-) -> Result<(), InstructionError> {
-    morgan_logger::setup();
+    fn do_pair_registry_request(
+        keyed_accounts: &mut [KeyedAccount],
+    ) -> Result<(), InstructionError> {
+        const ADMIN_INDEX: usize = 0;
+        const NEW_REGISTRY_INDEX: usize = 1;
 
-    let command = bincode::deserialize::<ExchangeInstruction>(data).map_err(|err| {
-        // info!("{}", Info(format!("Invalid transaction data: {:?} {:?}", data, err).to_string()));
-        let info:String = format!("Invalid transaction data: {:?} {:?}", data, err).to_string();
-        println!("{}",
-            printLn(
-                info,
-                module_path!().to_string()
-            )
-        );
-        InstructionError::InvalidInstructionData
-    })?;
+        if keyed_accounts.len() < 2 {
+            println!(
+                "{}",
+                Error(
+                    "Not enough accounts".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
 
-    trace!("{:?}", command);
+        let admin = match keyed_accounts[ADMIN_INDEX].signer_key() {
+            Some(admin) => *admin,
+            None => return Err(InstructionError::MissingRequiredSignature),
+        };
+        Self::is_account_unallocated(&keyed_accounts[NEW_REGISTRY_INDEX].account.data)?;
+        Self::serialize(
+            &ExchangeState::PairRegistry(PairRegistryInfo::new(&admin)),
+            &mut keyed_accounts[NEW_REGISTRY_INDEX].account.data,
+        )
+    }
 
-    match command {
-        ExchangeInstruction::AccountRequest => {
-            ExchangeProcessor::do_account_request(keyed_accounts)
+    fn do_pair_listing(
+        keyed_accounts: &mut [KeyedAccount],
+        pair: TokenPair,
+        listing: Option<PairListing>,
+    ) -> Result<(), InstructionError> {
+        const ADMIN_INDEX: usize = 0;
+        const REGISTRY_INDEX: usize = 1;
+
+        if keyed_accounts.len() < 2 {
+            println!(
+                "{}",
+                Error(
+                    "Not enough accounts".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
         }
-        ExchangeInstruction::TransferRequest(token, tokens) => {
-            ExchangeProcessor::do_transfer_request(keyed_accounts, token, tokens)
+
+        let mut registry = Self::authorized_registry(
+            &keyed_accounts[ADMIN_INDEX],
+            &keyed_accounts[REGISTRY_INDEX],
+        )?;
+        match listing {
+            Some(listing) => {
+                if listing.tick_size == 0 {
+                    println!(
+                        "{}",
+                        Error(
+                            "Tick size must be non-zero".to_string(),
+                            module_path!().to_string()
+                        )
+                    );
+                    return Err(InstructionError::InvalidArgument);
+                }
+                registry.list(pair, listing);
+            }
+            None => registry.delist(pair),
         }
-        ExchangeInstruction::TradeRequest(info) => {
-            ExchangeProcessor::do_trade_request(keyed_accounts, &info)
+        Self::serialize(
+            &ExchangeState::PairRegistry(registry),
+            &mut keyed_accounts[REGISTRY_INDEX].account.data,
+        )
+    }
+
+    fn do_set_pair_fees(
+        keyed_accounts: &mut [KeyedAccount],
+        pair: TokenPair,
+        fees: PairFees,
+    ) -> Result<(), InstructionError> {
+        const ADMIN_INDEX: usize = 0;
+        const REGISTRY_INDEX: usize = 1;
+
+        if keyed_accounts.len() < 2 {
+            println!(
+                "{}",
+                Error(
+                    "Not enough accounts".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
         }
-        ExchangeInstruction::TradeCancellation => {
-            ExchangeProcessor::do_trade_cancellation(keyed_accounts)
+
+        let mut registry = Self::authorized_registry(
+            &keyed_accounts[ADMIN_INDEX],
+            &keyed_accounts[REGISTRY_INDEX],
+        )?;
+        if registry.listing(pair).is_none() {
+            println!(
+                "{}",
+                Error(
+                    format!("Token pair {:?} is not listed", pair),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
         }
-        ExchangeInstruction::SwapRequest => ExchangeProcessor::do_swap_request(keyed_accounts),
+        if fees.maker_rebate_bps > FEE_BPS_SCALE || fees.taker_fee_bps > FEE_BPS_SCALE {
+            println!(
+                "{}",
+                Error(
+                    format!("Fees may not exceed {} basis points", FEE_BPS_SCALE),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+        registry.set_fees(pair, fees);
+        Self::serialize(
+            &ExchangeState::PairRegistry(registry),
+            &mut keyed_accounts[REGISTRY_INDEX].account.data,
+        )
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use crate::{exchange_instruction, id};
-    use morgan_runtime::bank::Bank;
+    fn do_withdraw_fees(
+        keyed_accounts: &mut [KeyedAccount],
+        pair: TokenPair,
+    ) -> Result<(), InstructionError> {
+        const ADMIN_INDEX: usize = 0;
+        const REGISTRY_INDEX: usize = 1;
+        const FEE_COLLECTOR_INDEX: usize = 2;
+        const FIRST_FEE_VAULT_INDEX: usize = 3;
+
+        if keyed_accounts.len() < 3 {
+            println!(
+                "{}",
+                Error(
+                    "Not enough accounts".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+
+        let registry = Self::authorized_registry(
+            &keyed_accounts[ADMIN_INDEX],
+            &keyed_accounts[REGISTRY_INDEX],
+        )?;
+        let fee_collector_key = keyed_accounts[FEE_COLLECTOR_INDEX].unsigned_key();
+        if registry.fees(pair).map(|fees| fees.fee_collector) != Some(*fee_collector_key) {
+            println!(
+                "{}",
+                Error(
+                    format!("Not the fee collector of token pair {:?}", pair),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+        let mut fee_collector =
+            Self::deserialize_account(&keyed_accounts[FEE_COLLECTOR_INDEX].account.data)?;
+
+        let registry_key = *keyed_accounts[REGISTRY_INDEX].unsigned_key();
+        for keyed_account in keyed_accounts[FIRST_FEE_VAULT_INDEX..].iter_mut() {
+            let mut fee_vault = Self::deserialize_fee_vault(keyed_account, &registry_key, pair)?;
+            for token in &[Token::A, Token::B, Token::C, Token::D] {
+                fee_collector.tokens[*token] += fee_vault.collected_fees[*token];
+            }
+            fee_vault.collected_fees = Tokens::default();
+            Self::serialize(
+                &ExchangeState::FeeVault(fee_vault),
+                &mut keyed_account.account.data,
+            )?;
+        }
+
+        Self::serialize(
+            &ExchangeState::Account(fee_collector),
+            &mut keyed_accounts[FEE_COLLECTOR_INDEX].account.data,
+        )
+    }
+
+    fn do_fee_vault_request(
+        keyed_accounts: &mut [KeyedAccount],
+        pair: TokenPair,
+    ) -> Result<(), InstructionError> {
+        const ADMIN_INDEX: usize = 0;
+        const REGISTRY_INDEX: usize = 1;
+        const NEW_FEE_VAULT_INDEX: usize = 2;
+
+        if keyed_accounts.len() < 3 {
+            println!(
+                "{}",
+                Error(
+                    "Not enough accounts".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            return Err(InstructionError::InvalidArgument);
+        }
+
+        Self::authorized_registry(
+            &keyed_accounts[ADMIN_INDEX],
+            &keyed_accounts[REGISTRY_INDEX],
+        )?;
+        Self::is_account_unallocated(&keyed_accounts[NEW_FEE_VAULT_INDEX].account.data)?;
+        let registry_key = *keyed_accounts[REGISTRY_INDEX].unsigned_key();
+        Self::serialize(
+            &ExchangeState::FeeVault(FeeVaultInfo::new(&registry_key, pair)),
+            &mut keyed_accounts[NEW_FEE_VAULT_INDEX].account.data,
+        )
+    }
+}
+
+pub fn process_instruction(
+    _program_id: &Pubkey,
+    keyed_accounts: &mut [KeyedAccount],
+    data: &[u8],
+    tick_height: u64,
+) -> Result<(), InstructionError> {
+    morgan_logger::setup();
+
+    let command = limited_deserialize::<ExchangeInstruction>(data).map_err(|err| {
+        // info!("{}", Info(format!("Invalid transaction data: {:?} {:?}", data, err).to_string()));
+        let info:String = format!("Invalid transaction data: {:?} {:?}", data, err).to_string();
+        println!("{}",
+            printLn(
+                info,
+                module_path!().to_string()
+            )
+        );
+        InstructionError::InvalidInstructionData
+    })?;
+
+    trace!("{:?}", command);
+
+    match command {
+        ExchangeInstruction::AccountRequest => {
+            ExchangeProcessor::do_account_request(keyed_accounts)
+        }
+        ExchangeInstruction::TransferRequest(token, tokens) => {
+            ExchangeProcessor::do_transfer_request(keyed_accounts, token, tokens)
+        }
+        ExchangeInstruction::TradeRequest(info) => {
+            ExchangeProcessor::do_trade_request(keyed_accounts, &info, tick_height)
+        }
+        ExchangeInstruction::TradeCancellation => {
+            ExchangeProcessor::do_trade_cancellation(keyed_accounts)
+        }
+        ExchangeInstruction::SwapRequest => ExchangeProcessor::do_swap_request(keyed_accounts),
+        ExchangeInstruction::PairRegistryRequest => {
+            ExchangeProcessor::do_pair_registry_request(keyed_accounts)
+        }
+        ExchangeInstruction::ListPair(pair, listing) => {
+            ExchangeProcessor::do_pair_listing(keyed_accounts, pair, Some(listing))
+        }
+        ExchangeInstruction::DelistPair(pair) => {
+            ExchangeProcessor::do_pair_listing(keyed_accounts, pair, None)
+        }
+        ExchangeInstruction::SetPairFees(pair, fees) => {
+            ExchangeProcessor::do_set_pair_fees(keyed_accounts, pair, fees)
+        }
+        ExchangeInstruction::WithdrawFees(pair) => {
+            ExchangeProcessor::do_withdraw_fees(keyed_accounts, pair)
+        }
+        ExchangeInstruction::FeeVaultRequest(pair) => {
+            ExchangeProcessor::do_fee_vault_request(keyed_accounts, pair)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{exchange_instruction, id};
+    use morgan_runtime::bank::Bank;
     use morgan_runtime::bank_client::BankClient;
     use morgan_interface::client::SyncClient;
     use morgan_interface::genesis_block::create_genesis_block;
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_instruction;
+    use morgan_interface::transport::TransportError;
     use std::mem;
 
     fn try_calc(
@@ -760,6 +1295,173 @@ mod test {
         try_calc(1000,   50,  100,   50,  101,  0,45,  5,   49, Tokens::new(   1, 0, 0, 0)).unwrap();
     }
 
+    #[test]
+    fn test_calculate_swap_iceberg() {
+        let mut to_trade = TradeOrderInfo::default()
+            .tokens(10)
+            .price(1000)
+            .displayed_tokens(2);
+        let mut from_trade = TradeOrderInfo::default()
+            .direction(Direction::From)
+            .tokens(10)
+            .price(1000);
+        let mut profit_account = TokenAccountInfo::default();
+
+        // every swap only fills the displayed part of the iceberg order
+        for filled in 1..=5 {
+            ExchangeProcessor::calculate_swap(
+                1000,
+                &mut to_trade,
+                &mut from_trade,
+                &mut profit_account,
+            )
+            .unwrap();
+            assert_eq!(to_trade.tokens, 10 - filled * 2);
+            assert_eq!(to_trade.tokens_settled, filled * 2);
+            assert_eq!(from_trade.tokens, 10 - filled * 2);
+        }
+        assert_eq!(to_trade.visible_tokens(), 0);
+    }
+
+    #[test]
+    fn test_self_trade_cancellation() {
+        let owner = Pubkey::new_rand();
+        let to_trade = TradeOrderInfo {
+            owner,
+            ..TradeOrderInfo::default()
+        };
+        let from_trade = TradeOrderInfo {
+            owner,
+            direction: Direction::From,
+            tick_height: 1,
+            ..TradeOrderInfo::default()
+        };
+        let cancellation = |to_trade: &TradeOrderInfo, from_trade: &TradeOrderInfo| {
+            ExchangeProcessor::self_trade_cancellation(to_trade, from_trade)
+        };
+
+        // self-trades are allowed unless an order asks otherwise
+        assert_eq!(cancellation(&to_trade, &from_trade), None);
+
+        let stp_from_trade = from_trade.self_trade_prevention(SelfTradePrevention::CancelNewest);
+        assert_eq!(
+            cancellation(&to_trade, &stp_from_trade),
+            Some(Direction::From)
+        );
+        let stp_from_trade = from_trade.self_trade_prevention(SelfTradePrevention::CancelOldest);
+        assert_eq!(
+            cancellation(&to_trade, &stp_from_trade),
+            Some(Direction::To)
+        );
+
+        // the newest order's mode wins
+        let stp_to_trade = to_trade.self_trade_prevention(SelfTradePrevention::CancelNewest);
+        assert_eq!(
+            cancellation(&stp_to_trade, &stp_from_trade),
+            Some(Direction::To)
+        );
+        assert_eq!(
+            cancellation(&stp_to_trade, &from_trade),
+            Some(Direction::From)
+        );
+
+        // of two orders placed at the same tick the From one is newest
+        let from_trade = TradeOrderInfo {
+            tick_height: 0,
+            ..from_trade
+        };
+        assert_eq!(
+            cancellation(&stp_to_trade, &from_trade),
+            Some(Direction::From)
+        );
+
+        // orders of different owners always swap
+        let from_trade = TradeOrderInfo {
+            owner: Pubkey::new_rand(),
+            ..stp_from_trade
+        };
+        assert_eq!(cancellation(&stp_to_trade, &from_trade), None);
+    }
+
+    #[test]
+    fn test_apply_fees() {
+        let fees = PairFees {
+            maker_rebate_bps: 10,
+            taker_fee_bps: 30,
+            fee_collector: Pubkey::new_rand(),
+        };
+        let to_trade = TradeOrderInfo {
+            tokens_settled: 1000,
+            tick_height: 1,
+            ..TradeOrderInfo::default()
+        };
+        let from_trade = TradeOrderInfo {
+            tokens_settled: 2000,
+            ..TradeOrderInfo::default().direction(Direction::From)
+        };
+
+        // the To order is newest and pays the taker fee in the secondary token, the rebate on
+        // the primary token is only paid out of fees already collected
+        let mut collected_fees = Tokens::new(5, 0, 0, 0);
+        let (mut taker, mut maker) = (to_trade, from_trade);
+        ExchangeProcessor::apply_fees(
+            &fees,
+            &mut collected_fees,
+            &mut taker,
+            &mut maker,
+            1000,
+            2000,
+        );
+        assert_eq!(taker.tokens_settled, 997);
+        assert_eq!(maker.tokens_settled, 2002);
+        assert_eq!(collected_fees, Tokens::new(3, 3, 0, 0));
+
+        // no rebate without collected fees to pay it from
+        let mut collected_fees = Tokens::default();
+        let (mut taker, mut maker) = (to_trade, from_trade);
+        ExchangeProcessor::apply_fees(
+            &fees,
+            &mut collected_fees,
+            &mut taker,
+            &mut maker,
+            1000,
+            2000,
+        );
+        assert_eq!(taker.tokens_settled, 997);
+        assert_eq!(maker.tokens_settled, 2000);
+        assert_eq!(collected_fees, Tokens::new(0, 3, 0, 0));
+
+        // of two orders placed at the same tick the From one is the taker
+        let to_trade = TradeOrderInfo {
+            tick_height: 0,
+            ..to_trade
+        };
+        let mut collected_fees = Tokens::new(0, 5, 0, 0);
+        let (mut maker, mut taker) = (to_trade, from_trade);
+        ExchangeProcessor::apply_fees(
+            &fees,
+            &mut collected_fees,
+            &mut maker,
+            &mut taker,
+            1000,
+            2000,
+        );
+        assert_eq!(taker.tokens_settled, 1994);
+        assert_eq!(maker.tokens_settled, 1001);
+        assert_eq!(collected_fees, Tokens::new(6, 4, 0, 0));
+    }
+
+    #[test]
+    fn test_pair_fees_large_trade() {
+        let fees = PairFees {
+            maker_rebate_bps: FEE_BPS_SCALE,
+            taker_fee_bps: 30,
+            fee_collector: Pubkey::new_rand(),
+        };
+        assert_eq!(fees.taker_fee(std::u64::MAX), 55_340_232_221_128_654);
+        assert_eq!(fees.maker_rebate(std::u64::MAX), std::u64::MAX);
+    }
+
     fn create_bank(difs: u64) -> (Bank, Keypair) {
         let (genesis_block, mint_keypair) = create_genesis_block(difs);
         let mut bank = Bank::new(&genesis_block);
@@ -814,28 +1516,106 @@ mod test {
             .expect(&format!("{}:{}", line!(), file!()));
     }
 
+    fn create_registry(client: &BankClient, admin: &Keypair, pairs: &[TokenPair]) -> Pubkey {
+        let new = create_account(&client, &admin);
+        let instruction = exchange_instruction::pair_registry_request(&admin.pubkey(), &new);
+        client
+            .send_instruction(admin, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+        for pair in pairs {
+            let instruction = exchange_instruction::list_pair(&admin.pubkey(), &new, *pair, 1, 1);
+            client
+                .send_instruction(admin, instruction)
+                .expect(&format!("{}:{}", line!(), file!()));
+        }
+        new
+    }
+
+    fn create_fee_vault(
+        client: &BankClient,
+        admin: &Keypair,
+        registry: &Pubkey,
+        pair: TokenPair,
+    ) -> Pubkey {
+        let new = create_account(&client, &admin);
+        let instruction =
+            exchange_instruction::fee_vault_request(&admin.pubkey(), registry, pair, &new);
+        client
+            .send_instruction(admin, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+        new
+    }
+
+    fn trade_request(
+        client: &BankClient,
+        owner: &Keypair,
+        registry: &Pubkey,
+        direction: Direction,
+        pair: TokenPair,
+        tokens: u64,
+        price: u64,
+    ) -> Result<(), TransportError> {
+        let trade = create_account(&client, &owner);
+        let src = create_token_account(&client, &owner);
+        let instruction = exchange_instruction::trade_request(
+            &owner.pubkey(),
+            &trade,
+            direction,
+            pair,
+            tokens,
+            price,
+            &src,
+            registry,
+        );
+        client.send_instruction(owner, instruction).map(|_| ())
+    }
+
     fn trade(
         client: &BankClient,
         owner: &Keypair,
+        registry: &Pubkey,
         direction: Direction,
         pair: TokenPair,
         from_token: Token,
         src_tokens: u64,
         trade_tokens: u64,
         price: u64,
+    ) -> (Pubkey, Pubkey) {
+        trade_with_info(
+            client,
+            owner,
+            registry,
+            from_token,
+            src_tokens,
+            TradeRequestInfo {
+                direction,
+                pair,
+                tokens: trade_tokens,
+                price,
+                displayed_tokens: None,
+                self_trade_prevention: None,
+            },
+        )
+    }
+
+    fn trade_with_info(
+        client: &BankClient,
+        owner: &Keypair,
+        registry: &Pubkey,
+        from_token: Token,
+        src_tokens: u64,
+        info: TradeRequestInfo,
     ) -> (Pubkey, Pubkey) {
         let trade = create_account(&client, &owner);
         let src = create_token_account(&client, &owner);
         transfer(&client, &owner, &src, from_token, src_tokens);
 
-        let instruction = exchange_instruction::trade_request(
+        let instruction = exchange_instruction::trade_request_with_info(
             &owner.pubkey(),
             &trade,
-            direction,
-            pair,
-            trade_tokens,
-            price,
+            info,
             &src,
+            registry,
         );
         client
             .send_instruction(owner, instruction)
@@ -912,9 +1692,11 @@ mod test {
         let (bank, mint_keypair) = create_bank(10_000);
         let (client, owner) = create_client(bank, mint_keypair);
 
+        let registry = create_registry(&client, &owner, &[TokenPair::AB]);
         let (trade, src) = trade(
             &client,
             &owner,
+            &registry,
             Direction::To,
             TokenPair::AB,
             Token::A,
@@ -935,7 +1717,11 @@ mod test {
                 pair: TokenPair::AB,
                 tokens: 2,
                 price: 1000,
-                tokens_settled: 0
+                tokens_settled: 0,
+                registry,
+                displayed_tokens: None,
+                self_trade_prevention: None,
+                tick_height: 0,
             },
             ExchangeProcessor::deserialize_trade(&trade_account_data).unwrap()
         );
@@ -954,9 +1740,11 @@ mod test {
         let (client, owner) = create_client(bank, mint_keypair);
 
         let profit = create_token_account(&client, &owner);
+        let registry = create_registry(&client, &owner, &[TokenPair::AB]);
         let (to_trade, _) = trade(
             &client,
             &owner,
+            &registry,
             Direction::To,
             TokenPair::AB,
             Token::A,
@@ -967,6 +1755,7 @@ mod test {
         let (from_trade, _) = trade(
             &client,
             &owner,
+            &registry,
             Direction::From,
             TokenPair::AB,
             Token::B,
@@ -975,8 +1764,13 @@ mod test {
             3000,
         );
 
-        let instruction =
-            exchange_instruction::swap_request(&owner.pubkey(), &to_trade, &from_trade, &profit);
+        let instruction = exchange_instruction::swap_request(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+        );
         client
             .send_instruction(&owner, instruction)
             .expect(&format!("{}:{}", line!(), file!()));
@@ -995,6 +1789,10 @@ mod test {
                 tokens: 1,
                 price: 2000,
                 tokens_settled: 2,
+                registry,
+                displayed_tokens: None,
+                self_trade_prevention: None,
+                tick_height: 0,
             },
             ExchangeProcessor::deserialize_trade(&to_trade_account_data).unwrap()
         );
@@ -1021,9 +1819,11 @@ mod test {
         let (client, owner) = create_client(bank, mint_keypair);
 
         let profit = create_token_account(&client, &owner);
+        let registry = create_registry(&client, &owner, &[TokenPair::AB]);
         let (to_trade, _) = trade(
             &client,
             &owner,
+            &registry,
             Direction::To,
             TokenPair::AB,
             Token::A,
@@ -1034,6 +1834,7 @@ mod test {
         let (from_trade, _) = trade(
             &client,
             &owner,
+            &registry,
             Direction::From,
             TokenPair::AB,
             Token::B,
@@ -1042,8 +1843,13 @@ mod test {
             3000,
         );
 
-        let instruction =
-            exchange_instruction::swap_request(&owner.pubkey(), &to_trade, &from_trade, &profit);
+        let instruction = exchange_instruction::swap_request(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+        );
         client
             .send_instruction(&owner, instruction)
             .expect(&format!("{}:{}", line!(), file!()));
@@ -1073,4 +1879,498 @@ mod test {
             ExchangeProcessor::deserialize_account(&new_account_data).unwrap()
         );
     }
+
+    #[test]
+    fn test_exchange_pair_registry_listing() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, admin) = create_client(bank, mint_keypair);
+
+        let registry = create_registry(&client, &admin, &[]);
+        let registry_data = client.get_account_data(&registry).unwrap().unwrap();
+        assert_eq!(
+            bincode::deserialize::<ExchangeState>(&registry_data).unwrap(),
+            ExchangeState::PairRegistry(PairRegistryInfo::new(&admin.pubkey()))
+        );
+
+        let instruction =
+            exchange_instruction::list_pair(&admin.pubkey(), &registry, TokenPair::AB, 10, 5);
+        client
+            .send_instruction(&admin, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+        let registry_data = client.get_account_data(&registry).unwrap().unwrap();
+        let state: ExchangeState = bincode::deserialize(&registry_data).unwrap();
+        let mut expected = PairRegistryInfo::new(&admin.pubkey());
+        expected.list(
+            TokenPair::AB,
+            PairListing {
+                tick_size: 10,
+                min_order_size: 5,
+            },
+        );
+        assert_eq!(state, ExchangeState::PairRegistry(expected));
+
+        // a zero tick size would make every price invalid
+        let instruction =
+            exchange_instruction::list_pair(&admin.pubkey(), &registry, TokenPair::AC, 0, 5);
+        client
+            .send_instruction(&admin, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+
+        // only the admin may change listings
+        let stranger = Keypair::new();
+        client.transfer(2, &admin, &stranger.pubkey()).unwrap();
+        let instruction =
+            exchange_instruction::list_pair(&stranger.pubkey(), &registry, TokenPair::AC, 1, 1);
+        client
+            .send_instruction(&stranger, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        let instruction =
+            exchange_instruction::delist_pair(&stranger.pubkey(), &registry, TokenPair::AB);
+        client
+            .send_instruction(&stranger, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+
+        let instruction =
+            exchange_instruction::delist_pair(&admin.pubkey(), &registry, TokenPair::AB);
+        client
+            .send_instruction(&admin, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+        let registry_data = client.get_account_data(&registry).unwrap().unwrap();
+        assert_eq!(
+            bincode::deserialize::<ExchangeState>(&registry_data).unwrap(),
+            ExchangeState::PairRegistry(PairRegistryInfo::new(&admin.pubkey()))
+        );
+    }
+
+    #[test]
+    fn test_exchange_trade_request_enforces_listing() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+
+        let registry = create_registry(&client, &owner, &[]);
+        let instruction =
+            exchange_instruction::list_pair(&owner.pubkey(), &registry, TokenPair::AB, 10, 5);
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        trade_request(
+            &client,
+            &owner,
+            &registry,
+            Direction::To,
+            TokenPair::AB,
+            5,
+            1000,
+        )
+        .unwrap();
+        // unlisted pair
+        trade_request(
+            &client,
+            &owner,
+            &registry,
+            Direction::To,
+            TokenPair::AC,
+            5,
+            1000,
+        )
+        .unwrap_err();
+        // off tick
+        trade_request(
+            &client,
+            &owner,
+            &registry,
+            Direction::To,
+            TokenPair::AB,
+            5,
+            1005,
+        )
+        .unwrap_err();
+        // below the minimum order size
+        trade_request(
+            &client,
+            &owner,
+            &registry,
+            Direction::To,
+            TokenPair::AB,
+            4,
+            1000,
+        )
+        .unwrap_err();
+        // not a registry
+        let src = create_token_account(&client, &owner);
+        trade_request(&client, &owner, &src, Direction::To, TokenPair::AB, 5, 1000).unwrap_err();
+    }
+
+    #[test]
+    fn test_exchange_swap_request_mismatched_registries() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+
+        let profit = create_token_account(&client, &owner);
+        let to_registry = create_registry(&client, &owner, &[TokenPair::AB]);
+        let from_registry = create_registry(&client, &owner, &[TokenPair::AB]);
+        let (to_trade, _) = trade(
+            &client,
+            &owner,
+            &to_registry,
+            Direction::To,
+            TokenPair::AB,
+            Token::A,
+            2,
+            2,
+            2000,
+        );
+        let (from_trade, _) = trade(
+            &client,
+            &owner,
+            &from_registry,
+            Direction::From,
+            TokenPair::AB,
+            Token::B,
+            3,
+            3,
+            3000,
+        );
+
+        let instruction = exchange_instruction::swap_request(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &to_registry,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
+
+    #[test]
+    fn test_exchange_iceberg_swap_request() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+
+        let profit = create_token_account(&client, &owner);
+        let registry = create_registry(&client, &owner, &[TokenPair::AB]);
+        let (to_trade, _) = trade_with_info(
+            &client,
+            &owner,
+            &registry,
+            Token::A,
+            4,
+            TradeRequestInfo {
+                direction: Direction::To,
+                pair: TokenPair::AB,
+                tokens: 4,
+                price: 1000,
+                displayed_tokens: Some(2),
+                self_trade_prevention: None,
+            },
+        );
+        let (from_trade, _) = trade(
+            &client,
+            &owner,
+            &registry,
+            Direction::From,
+            TokenPair::AB,
+            Token::B,
+            4,
+            4,
+            1000,
+        );
+
+        let instruction = exchange_instruction::swap_request(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        // only the displayed part of the iceberg order was filled
+        let to_trade_account_data = client.get_account_data(&to_trade).unwrap().unwrap();
+        let to_trade_info = ExchangeProcessor::deserialize_trade(&to_trade_account_data).unwrap();
+        assert_eq!(to_trade_info.tokens, 2);
+        assert_eq!(to_trade_info.tokens_settled, 2);
+        let from_trade_account_data = client.get_account_data(&from_trade).unwrap().unwrap();
+        let from_trade_info =
+            ExchangeProcessor::deserialize_trade(&from_trade_account_data).unwrap();
+        assert_eq!(from_trade_info.tokens, 2);
+        assert_eq!(from_trade_info.tokens_settled, 2);
+
+        // an order can't display nothing
+        let trade = create_account(&client, &owner);
+        let src = create_token_account(&client, &owner);
+        let instruction = exchange_instruction::trade_request_with_info(
+            &owner.pubkey(),
+            &trade,
+            TradeRequestInfo {
+                direction: Direction::To,
+                pair: TokenPair::AB,
+                tokens: 4,
+                price: 1000,
+                displayed_tokens: Some(0),
+                self_trade_prevention: None,
+            },
+            &src,
+            &registry,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+    }
+
+    #[test]
+    fn test_exchange_self_trade_prevention() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+
+        let profit = create_token_account(&client, &owner);
+        let registry = create_registry(&client, &owner, &[TokenPair::AB]);
+        let (to_trade, _) = trade_with_info(
+            &client,
+            &owner,
+            &registry,
+            Token::A,
+            2,
+            TradeRequestInfo {
+                direction: Direction::To,
+                pair: TokenPair::AB,
+                tokens: 2,
+                price: 2000,
+                displayed_tokens: None,
+                self_trade_prevention: Some(SelfTradePrevention::CancelNewest),
+            },
+        );
+        let (from_trade, _) = trade(
+            &client,
+            &owner,
+            &registry,
+            Direction::From,
+            TokenPair::AB,
+            Token::B,
+            3,
+            3,
+            3000,
+        );
+        let to_trade_account_data = client.get_account_data(&to_trade).unwrap().unwrap();
+
+        let instruction = exchange_instruction::swap_request(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        // the newest order was cancelled and the other one left untouched
+        assert_eq!(
+            client.get_account_data(&to_trade).unwrap().unwrap(),
+            to_trade_account_data
+        );
+        let from_trade_account_data = client.get_account_data(&from_trade).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(0, 3, 0, 0),
+            ExchangeProcessor::deserialize_account(&from_trade_account_data).unwrap()
+        );
+        let profit_account_data = client.get_account_data(&profit).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(100_000, 100_000, 100_000, 100_000),
+            ExchangeProcessor::deserialize_account(&profit_account_data).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_exchange_pair_fees() {
+        morgan_logger::setup();
+        let (bank, mint_keypair) = create_bank(10_000);
+        let (client, owner) = create_client(bank, mint_keypair);
+
+        let profit = create_token_account(&client, &owner);
+        let fee_collector = create_token_account(&client, &owner);
+        let registry = create_registry(&client, &owner, &[TokenPair::AB]);
+
+        // fees can only be set on a listed pair
+        let instruction = exchange_instruction::set_pair_fees(
+            &owner.pubkey(),
+            &registry,
+            TokenPair::CD,
+            10,
+            30,
+            &fee_collector,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        let instruction = exchange_instruction::set_pair_fees(
+            &owner.pubkey(),
+            &registry,
+            TokenPair::AB,
+            10,
+            30,
+            &fee_collector,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        let (to_trade, _) = trade(
+            &client,
+            &owner,
+            &registry,
+            Direction::To,
+            TokenPair::AB,
+            Token::A,
+            2000,
+            2000,
+            1000,
+        );
+        let (from_trade, _) = trade(
+            &client,
+            &owner,
+            &registry,
+            Direction::From,
+            TokenPair::AB,
+            Token::B,
+            1000,
+            1000,
+            1000,
+        );
+
+        // a pair with fees only swaps with a fee vault of that pair
+        let instruction = exchange_instruction::swap_request(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        let other_fee_vault = create_fee_vault(&client, &owner, &registry, TokenPair::CD);
+        let instruction = exchange_instruction::swap_request_with_fee_vault(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+            &other_fee_vault,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        let fee_vault = create_fee_vault(&client, &owner, &registry, TokenPair::AB);
+        let instruction = exchange_instruction::swap_request_with_fee_vault(
+            &owner.pubkey(),
+            &to_trade,
+            &from_trade,
+            &profit,
+            &registry,
+            &fee_vault,
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        // the From order is the taker, nothing was collected yet to pay the maker's rebate
+        let to_trade_account_data = client.get_account_data(&to_trade).unwrap().unwrap();
+        let to_trade_info = ExchangeProcessor::deserialize_trade(&to_trade_account_data).unwrap();
+        assert_eq!(to_trade_info.tokens_settled, 1000);
+        let from_trade_account_data = client.get_account_data(&from_trade).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(997, 0, 0, 0),
+            ExchangeProcessor::deserialize_account(&from_trade_account_data).unwrap()
+        );
+        let registry_data = client.get_account_data(&registry).unwrap().unwrap();
+        let mut expected = PairRegistryInfo::new(&owner.pubkey());
+        expected.list(
+            TokenPair::AB,
+            PairListing {
+                tick_size: 1,
+                min_order_size: 1,
+            },
+        );
+        expected.set_fees(
+            TokenPair::AB,
+            PairFees {
+                maker_rebate_bps: 10,
+                taker_fee_bps: 30,
+                fee_collector,
+            },
+        );
+        assert_eq!(
+            bincode::deserialize::<ExchangeState>(&registry_data).unwrap(),
+            ExchangeState::PairRegistry(expected)
+        );
+        let mut expected_fee_vault = FeeVaultInfo::new(&registry, TokenPair::AB);
+        expected_fee_vault.collected_fees = Tokens::new(3, 0, 0, 0);
+        let fee_vault_data = client.get_account_data(&fee_vault).unwrap().unwrap();
+        assert_eq!(
+            bincode::deserialize::<ExchangeState>(&fee_vault_data).unwrap(),
+            ExchangeState::FeeVault(expected_fee_vault)
+        );
+
+        // fees are only withdrawn to the pair's fee collector, from vaults of the pair
+        let instruction = exchange_instruction::withdraw_fees(
+            &owner.pubkey(),
+            &registry,
+            TokenPair::AB,
+            &profit,
+            &[fee_vault],
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        let instruction = exchange_instruction::withdraw_fees(
+            &owner.pubkey(),
+            &registry,
+            TokenPair::AB,
+            &fee_collector,
+            &[fee_vault, other_fee_vault],
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect_err(&format!("{}:{}", line!(), file!()));
+        let instruction = exchange_instruction::withdraw_fees(
+            &owner.pubkey(),
+            &registry,
+            TokenPair::AB,
+            &fee_collector,
+            &[fee_vault],
+        );
+        client
+            .send_instruction(&owner, instruction)
+            .expect(&format!("{}:{}", line!(), file!()));
+
+        let fee_collector_data = client.get_account_data(&fee_collector).unwrap().unwrap();
+        assert_eq!(
+            TokenAccountInfo::default()
+                .owner(&owner.pubkey())
+                .tokens(100_003, 100_000, 100_000, 100_000),
+            ExchangeProcessor::deserialize_account(&fee_collector_data).unwrap()
+        );
+        let fee_vault_data = client.get_account_data(&fee_vault).unwrap().unwrap();
+        assert_eq!(
+            bincode::deserialize::<ExchangeState>(&fee_vault_data).unwrap(),
+            ExchangeState::FeeVault(FeeVaultInfo::new(&registry, TokenPair::AB))
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use serde_derive::{Deserialize, Serialize};
 use morgan_interface::pubkey::Pubkey;
-use std::{error, fmt};
+use std::{cmp, error, fmt};
 
 /// Fixed-point scaler, 10 = one base 10 digit to the right of the decimal, 100 = 2, ...
 /// Used by both price and amount in their fixed point representation
@@ -104,6 +104,113 @@ impl TokenPair {
             TokenPair::AD | TokenPair::BD | TokenPair::CD => Token::D,
         }
     }
+    fn index(self) -> usize {
+        match self {
+            TokenPair::AB => 0,
+            TokenPair::AC => 1,
+            TokenPair::AD => 2,
+            TokenPair::BC => 3,
+            TokenPair::BD => 4,
+            TokenPair::CD => 5,
+        }
+    }
+}
+
+/// Number of `TokenPair` variants
+pub const NUM_TOKEN_PAIRS: usize = 6;
+
+/// Trading rules the pair registry admin sets for a listed token pair
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PairListing {
+    /// Order prices must be a multiple of this
+    pub tick_size: u64,
+    /// Smallest number of tokens an order may offer
+    pub min_order_size: u64,
+}
+
+/// Fees are expressed in basis points, this many make up the whole amount
+pub const FEE_BPS_SCALE: u64 = 10_000;
+
+/// Fees the pair registry admin charges on swaps of a listed token pair.  Both are basis points
+/// of the tokens an order receives from the swap.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PairFees {
+    /// Paid to the order that was on the book first, out of the fees in the swap's fee vault
+    pub maker_rebate_bps: u64,
+    /// Charged to the order that was placed last
+    pub taker_fee_bps: u64,
+    /// Token account the collected fees are withdrawn to
+    pub fee_collector: Pubkey,
+}
+impl PairFees {
+    /// Taker fee on `tokens` received
+    pub fn taker_fee(&self, tokens: u64) -> u64 {
+        Self::bps_of(tokens, self.taker_fee_bps)
+    }
+    /// Maker rebate on `tokens` received
+    pub fn maker_rebate(&self, tokens: u64) -> u64 {
+        Self::bps_of(tokens, self.maker_rebate_bps)
+    }
+    // Multiplied as u128 so large trades don't overflow, the result never exceeds `tokens`
+    // since the bps are capped at `FEE_BPS_SCALE`
+    fn bps_of(tokens: u64, bps: u64) -> u64 {
+        (u128::from(tokens) * u128::from(bps) / u128::from(FEE_BPS_SCALE)) as u64
+    }
+}
+
+/// Pair registry accounts are populated with this structure
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PairRegistryInfo {
+    /// Key allowed to list and delist pairs
+    pub admin: Pubkey,
+    /// Listing of each token pair, indexed by `TokenPair`, `None` if not tradeable
+    listings: [Option<PairListing>; NUM_TOKEN_PAIRS],
+    /// Fees of each token pair, indexed by `TokenPair`, `None` if swaps are free
+    fees: [Option<PairFees>; NUM_TOKEN_PAIRS],
+}
+impl PairRegistryInfo {
+    pub fn new(admin: &Pubkey) -> Self {
+        Self {
+            admin: *admin,
+            ..Self::default()
+        }
+    }
+    pub fn listing(&self, pair: TokenPair) -> Option<PairListing> {
+        self.listings[pair.index()]
+    }
+    pub fn list(&mut self, pair: TokenPair, listing: PairListing) {
+        self.listings[pair.index()] = Some(listing);
+    }
+    pub fn delist(&mut self, pair: TokenPair) {
+        self.listings[pair.index()] = None;
+    }
+    pub fn fees(&self, pair: TokenPair) -> Option<PairFees> {
+        self.fees[pair.index()]
+    }
+    pub fn set_fees(&mut self, pair: TokenPair, fees: PairFees) {
+        self.fees[pair.index()] = Some(fees);
+    }
+}
+
+/// Fee vault accounts are populated with this structure.  Swaps deposit their fees into a vault
+/// rather than the pair registry so they only ever read the registry.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FeeVaultInfo {
+    /// Pair registry the vault collects fees for
+    pub registry: Pubkey,
+    /// Token pair the vault collects fees for
+    pub pair: TokenPair,
+    /// Fees collected and not yet withdrawn
+    pub collected_fees: Tokens,
+}
+impl FeeVaultInfo {
+    pub fn new(registry: &Pubkey, pair: TokenPair) -> Self {
+        Self {
+            registry: *registry,
+            pair,
+            collected_fees: Tokens::default(),
+        }
+    }
 }
 
 /// Token accounts are populated with this structure
@@ -148,6 +255,15 @@ impl fmt::Display for Direction {
     }
 }
 
+/// What a swap between two trade orders of the same owner does instead of trading them
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum SelfTradePrevention {
+    /// Cancel the order placed last, the older one stays on the book
+    CancelNewest,
+    /// Cancel the order placed first, the newer one stays on the book
+    CancelOldest,
+}
+
 /// Trade accounts are populated with this structure
 #[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct TradeOrderInfo {
@@ -166,6 +282,15 @@ pub struct TradeOrderInfo {
     /// Number of tokens that have been settled so far.  These nay be transferred to another
     /// token account by the owner.
     pub tokens_settled: u64,
+    /// Pair registry the order was placed under, only orders from the same registry are swapped
+    pub registry: Pubkey,
+    /// Most tokens a single swap may fill; the rest stays hidden until the displayed part is
+    /// filled.  `None` displays the whole order
+    pub displayed_tokens: Option<u64>,
+    /// How a swap against another order of the same owner is prevented, `None` allows it
+    pub self_trade_prevention: Option<SelfTradePrevention>,
+    /// Tick height the order was placed at, tells which of two self-trading orders is newest
+    pub tick_height: u64,
 }
 impl Default for TradeOrderInfo {
     fn default() -> Self {
@@ -176,6 +301,10 @@ impl Default for TradeOrderInfo {
             tokens: 0,
             price: 0,
             tokens_settled: 0,
+            registry: Pubkey::default(),
+            displayed_tokens: None,
+            self_trade_prevention: None,
+            tick_height: 0,
         }
     }
 }
@@ -196,6 +325,21 @@ impl TradeOrderInfo {
         self.price = price;
         self
     }
+    pub fn displayed_tokens(mut self, displayed_tokens: u64) -> Self {
+        self.displayed_tokens = Some(displayed_tokens);
+        self
+    }
+    pub fn self_trade_prevention(mut self, self_trade_prevention: SelfTradePrevention) -> Self {
+        self.self_trade_prevention = Some(self_trade_prevention);
+        self
+    }
+    /// Tokens the next swap may fill
+    pub fn visible_tokens(&self) -> u64 {
+        match self.displayed_tokens {
+            Some(displayed_tokens) => cmp::min(self.tokens, displayed_tokens),
+            None => self.tokens,
+        }
+    }
 }
 
 pub fn check_trade(direction: Direction, tokens: u64, price: u64) -> Result<(), ExchangeError> {
@@ -230,6 +374,10 @@ pub enum ExchangeState {
     // Trade order account
     Trade(TradeOrderInfo),
     Invalid,
+    // Pair registry account
+    PairRegistry(PairRegistryInfo),
+    // Fee vault account
+    FeeVault(FeeVaultInfo),
 }
 impl Default for ExchangeState {
     fn default() -> Self {
@@ -6,6 +6,7 @@ use serde_derive::{Deserialize, Serialize};
 use morgan_interface::account::KeyedAccount;
 use morgan_interface::instruction::{AccountMeta, Instruction, InstructionError};
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::syscall::epoch_ticks;
 use morgan_interface::system_instruction;
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -17,13 +18,6 @@ pub enum StakeInstruction {
     ///    1 - Delegate StakeAccount to be initialized
     InitializeDelegate,
 
-    // Initialize the stake account as a MiningPool account
-    ///
-    /// Expects 2 Accounts:
-    ///    0 - payer (TODO unused/remove)
-    ///    1 - MiningPool StakeAccount to be initialized
-    InitializeMiningPool,
-
     /// `Delegate` or `Assign` a stake account to a particular node
     ///
     /// Expects 3 Accounts:
@@ -32,14 +26,23 @@ pub enum StakeInstruction {
     ///    2 - VoteAccount to which this Stake will be delegated
     DelegateStake,
 
-    /// Redeem credits in the stake account
+    /// Deactivate the delegated stake; it can be withdrawn in full once it has cooled
+    /// down for as many ticks as the epoch it's withdrawn in
+    ///
+    /// Expects 2 Accounts:
+    ///    0 - payer (TODO unused/remove)
+    ///    1 - Delegate StakeAccount to be deactivated
+    DeactivateStake,
+
+    /// Withdraw difs from a stake account; while the stake is active only the
+    /// difs in excess of the delegated stake can be withdrawn
     ///
     /// Expects 4 Accounts:
     ///    0 - payer (TODO unused/remove)
-    ///    1 - MiningPool Stake Account to redeem credits from
-    ///    2 - Delegate StakeAccount to be updated
-    ///    3 - VoteAccount to which the Stake is delegated
-    RedeemVoteCredits,
+    ///    1 - StakeAccount to withdraw from
+    ///    2 - EpochTicks syscall account, for the length of the cooldown
+    ///    3 - Account to receive the withdrawn difs
+    Withdraw(u64),
 }
 
 pub fn create_delegate_account(
@@ -66,63 +69,47 @@ pub fn create_delegate_account(
     ]
 }
 
-pub fn create_mining_pool_account(
-    from_pubkey: &Pubkey,
-    staker_pubkey: &Pubkey,
-    difs: u64,
-) -> Vec<Instruction> {
-    vec![
-        system_instruction::create_account(
-            from_pubkey,
-            staker_pubkey,
-            difs,
-            std::mem::size_of::<StakeState>() as u64,
-            &id(),
-        ),
-        Instruction::new(
-            id(),
-            &StakeInstruction::InitializeMiningPool,
-            vec![
-                AccountMeta::new(*from_pubkey, true),
-                AccountMeta::new(*staker_pubkey, false),
-            ],
-        ),
-    ]
-}
-
-pub fn redeem_vote_credits(
+pub fn delegate_stake(
     from_pubkey: &Pubkey,
-    mining_pool_pubkey: &Pubkey,
     stake_pubkey: &Pubkey,
     vote_pubkey: &Pubkey,
 ) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*from_pubkey, true),
-        AccountMeta::new(*mining_pool_pubkey, false),
-        AccountMeta::new(*stake_pubkey, false),
+        AccountMeta::new(*stake_pubkey, true),
         AccountMeta::new(*vote_pubkey, false),
     ];
-    Instruction::new(id(), &StakeInstruction::RedeemVoteCredits, account_metas)
+    Instruction::new(id(), &StakeInstruction::DelegateStake, account_metas)
+}
+
+pub fn deactivate_stake(from_pubkey: &Pubkey, stake_pubkey: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*from_pubkey, true),
+        AccountMeta::new(*stake_pubkey, true),
+    ];
+    Instruction::new(id(), &StakeInstruction::DeactivateStake, account_metas)
 }
 
-pub fn delegate_stake(
+pub fn withdraw(
     from_pubkey: &Pubkey,
     stake_pubkey: &Pubkey,
-    vote_pubkey: &Pubkey,
+    to_pubkey: &Pubkey,
+    difs: u64,
 ) -> Instruction {
     let account_metas = vec![
         AccountMeta::new(*from_pubkey, true),
         AccountMeta::new(*stake_pubkey, true),
-        AccountMeta::new(*vote_pubkey, false),
+        AccountMeta::new(epoch_ticks::id(), false),
+        AccountMeta::new(*to_pubkey, false),
     ];
-    Instruction::new(id(), &StakeInstruction::DelegateStake, account_metas)
+    Instruction::new(id(), &StakeInstruction::Withdraw(difs), account_metas)
 }
 
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
-    _tick_height: u64,
+    tick_height: u64,
 ) -> Result<(), InstructionError> {
     morgan_logger::setup();
 
@@ -140,12 +127,6 @@ pub fn process_instruction(
 
     // TODO: data-driven unpack and dispatch of KeyedAccounts
     match deserialize(data).map_err(|_| InstructionError::InvalidInstructionData)? {
-        StakeInstruction::InitializeMiningPool => {
-            if !rest.is_empty() {
-                Err(InstructionError::InvalidInstructionData)?;
-            }
-            me.initialize_mining_pool()
-        }
         StakeInstruction::InitializeDelegate => {
             if !rest.is_empty() {
                 Err(InstructionError::InvalidInstructionData)?;
@@ -159,15 +140,18 @@ pub fn process_instruction(
             let vote = &rest[0];
             me.delegate_stake(vote)
         }
-        StakeInstruction::RedeemVoteCredits => {
+        StakeInstruction::DeactivateStake => {
+            if !rest.is_empty() {
+                return Err(InstructionError::InvalidInstructionData);
+            }
+            me.deactivate_stake(tick_height)
+        }
+        StakeInstruction::Withdraw(difs) => {
             if rest.len() != 2 {
-                Err(InstructionError::InvalidInstructionData)?;
+                return Err(InstructionError::InvalidInstructionData);
             }
-            let (stake, vote) = rest.split_at_mut(1);
-            let stake = &mut stake[0];
-            let vote = &mut vote[0];
-
-            me.redeem_vote_credits(stake, vote)
+            let (epoch_ticks, to) = rest.split_at_mut(1);
+            me.withdraw(difs, &mut to[0], &epoch_ticks[0], tick_height)
         }
     }
 }
@@ -202,8 +186,7 @@ mod tests {
     #[test]
     fn test_stake_process_instruction() {
         assert_eq!(
-            process_instruction(&redeem_vote_credits(
-                &Pubkey::default(),
+            process_instruction(&delegate_stake(
                 &Pubkey::default(),
                 &Pubkey::default(),
                 &Pubkey::default()
@@ -211,10 +194,15 @@ mod tests {
             Err(InstructionError::InvalidAccountData),
         );
         assert_eq!(
-            process_instruction(&delegate_stake(
+            process_instruction(&deactivate_stake(&Pubkey::default(), &Pubkey::default())),
+            Err(InstructionError::InvalidAccountData),
+        );
+        assert_eq!(
+            process_instruction(&withdraw(
                 &Pubkey::default(),
                 &Pubkey::default(),
-                &Pubkey::default()
+                &Pubkey::default(),
+                1
             )),
             Err(InstructionError::InvalidAccountData),
         );
@@ -253,20 +241,6 @@ mod tests {
             Err(InstructionError::InvalidInstructionData),
         );
 
-        assert_eq!(
-            super::process_instruction(
-                &Pubkey::default(),
-                &mut [
-                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
-                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
-                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
-                ],
-                &serialize(&StakeInstruction::RedeemVoteCredits).unwrap(),
-                0,
-            ),
-            Err(InstructionError::InvalidInstructionData),
-        );
-
         // gets the check in delegate_stake
         assert_eq!(
             super::process_instruction(
@@ -281,22 +255,6 @@ mod tests {
             ),
             Err(InstructionError::InvalidAccountData),
         );
-
-        // gets the check in redeem_vote_credits
-        assert_eq!(
-            super::process_instruction(
-                &Pubkey::default(),
-                &mut [
-                    KeyedAccount::new(&Pubkey::default(), true, &mut Account::default()), // from
-                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
-                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
-                    KeyedAccount::new(&Pubkey::default(), false, &mut Account::default()),
-                ],
-                &serialize(&StakeInstruction::RedeemVoteCredits).unwrap(),
-                0,
-            ),
-            Err(InstructionError::InvalidAccountData),
-        );
     }
 
 }
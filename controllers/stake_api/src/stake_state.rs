@@ -1,7 +1,7 @@
 //! Stake state
 //! * delegate stakes to vote accounts
-//! * keep track of rewards
-//! * own mining pools
+//! * keep track of rewards, which the bank mints at every epoch boundary
+//! * withdraw difs that aren't delegated, or all of them once deactivated
 
 use crate::id;
 use serde_derive::{Deserialize, Serialize};
@@ -9,16 +9,27 @@ use morgan_interface::account::{Account, KeyedAccount};
 use morgan_interface::account_utils::State;
 use morgan_interface::instruction::InstructionError;
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::syscall::epoch_ticks::{self, EpochTicks};
 use morgan_vote_api::vote_state::VoteState;
 
+/// Smallest stake that may be delegated; anything less is dust that isn't
+/// worth a slot in the stakes cache
+pub const MIN_DELEGATION: u64 = 3;
+
+/// `deactivated` value of a stake that is still active
+pub const STAKE_ACTIVE: u64 = std::u64::MAX;
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub enum StakeState {
     Uninitialized,
     Delegate {
         voter_pubkey: Pubkey,
         credits_observed: u64,
+        /// difs delegated to voter_pubkey, 0 until delegated
+        stake: u64,
+        /// tick height at which the stake was deactivated, or STAKE_ACTIVE
+        deactivated: u64,
     },
-    MiningPool,
 }
 
 impl Default for StakeState {
@@ -26,19 +37,6 @@ impl Default for StakeState {
         StakeState::Uninitialized
     }
 }
-//  TODO: trusted values of network parameters come from where?
-const TICKS_PER_SECOND: f64 = 10f64;
-const TICKS_PER_SLOT: f64 = 8f64;
-
-// credits/yr or slots/yr  is        seconds/year        *   ticks/second   * slots/tick
-const CREDITS_PER_YEAR: f64 = (365f64 * 24f64 * 3600f64) * TICKS_PER_SECOND / TICKS_PER_SLOT;
-
-// TODO: 20% is a niiice rate...  TODO: make this a member of MiningPool?
-const STAKE_REWARD_TARGET_RATE: f64 = 0.20;
-
-#[cfg(test)]
-const STAKE_GETS_PAID_EVERY_VOTE: u64 = 200_000_000; // if numbers above (TICKS_YEAR) move, fix this
-
 impl StakeState {
     // utility function, used by Stakes, tests
     pub fn from(account: &Account) -> Option<StakeState> {
@@ -50,26 +48,55 @@ impl StakeState {
         Self::from(account).and_then(|state: Self| state.voter_pubkey())
     }
 
+    /// The voter this stake counts towards; `None` unless the account holds an
+    /// active delegation of at least MIN_DELEGATION
     pub fn voter_pubkey(&self) -> Option<Pubkey> {
         match self {
-            StakeState::Delegate { voter_pubkey, .. } => Some(*voter_pubkey),
+            StakeState::Delegate {
+                voter_pubkey,
+                stake,
+                deactivated,
+                ..
+            } if *stake >= MIN_DELEGATION && *deactivated == STAKE_ACTIVE => Some(*voter_pubkey),
             _ => None,
         }
     }
 
+    /// difs of an account holding `difs` that may be withdrawn at `tick_height`, where a
+    /// deactivated stake cools down for `cooldown_ticks`, the length of the current epoch
+    pub fn withdrawable(&self, difs: u64, tick_height: u64, cooldown_ticks: u64) -> Option<u64> {
+        match self {
+            StakeState::Uninitialized => Some(difs),
+            StakeState::Delegate {
+                stake, deactivated, ..
+            } => {
+                if *deactivated != STAKE_ACTIVE
+                    && tick_height >= deactivated.saturating_add(cooldown_ticks)
+                {
+                    Some(difs)
+                } else {
+                    Some(difs.saturating_sub(*stake))
+                }
+            }
+        }
+    }
+
+    /// Rewards earned by `stake` for the credits since `credits_observed`, paid at `rate`
+    /// a year to a voter that earns `credits_per_year` credits voting on every slot, as
+    /// (voter's commission, staker's share)
     pub fn calculate_rewards(
         credits_observed: u64,
         stake: u64,
+        rate: f64,
+        credits_per_year: f64,
         vote_state: &VoteState,
     ) -> Option<(u64, u64)> {
         if credits_observed >= vote_state.credits() {
             return None;
         }
 
-        let total_rewards = stake as f64
-            * STAKE_REWARD_TARGET_RATE
-            * (vote_state.credits() - credits_observed) as f64
-            / CREDITS_PER_YEAR;
+        let total_rewards = stake as f64 * rate * (vote_state.credits() - credits_observed) as f64
+            / credits_per_year;
 
         // don't bother trying to collect fractional difs
         if total_rewards < 1f64 {
@@ -88,29 +115,26 @@ impl StakeState {
 }
 
 pub trait StakeAccount {
-    fn initialize_mining_pool(&mut self) -> Result<(), InstructionError>;
     fn initialize_delegate(&mut self) -> Result<(), InstructionError>;
     fn delegate_stake(&mut self, vote_account: &KeyedAccount) -> Result<(), InstructionError>;
-    fn redeem_vote_credits(
+    fn deactivate_stake(&mut self, tick_height: u64) -> Result<(), InstructionError>;
+    fn withdraw(
         &mut self,
-        stake_account: &mut KeyedAccount,
-        vote_account: &mut KeyedAccount,
+        difs: u64,
+        to_account: &mut KeyedAccount,
+        epoch_ticks_account: &KeyedAccount,
+        tick_height: u64,
     ) -> Result<(), InstructionError>;
 }
 
 impl<'a> StakeAccount for KeyedAccount<'a> {
-    fn initialize_mining_pool(&mut self) -> Result<(), InstructionError> {
-        if let StakeState::Uninitialized = self.state()? {
-            self.set_state(&StakeState::MiningPool)
-        } else {
-            Err(InstructionError::InvalidAccountData)
-        }
-    }
     fn initialize_delegate(&mut self) -> Result<(), InstructionError> {
         if let StakeState::Uninitialized = self.state()? {
             self.set_state(&StakeState::Delegate {
                 voter_pubkey: Pubkey::default(),
                 credits_observed: 0,
+                stake: 0,
+                deactivated: STAKE_ACTIVE,
             })
         } else {
             Err(InstructionError::InvalidAccountData)
@@ -122,62 +146,115 @@ impl<'a> StakeAccount for KeyedAccount<'a> {
         }
 
         if let StakeState::Delegate { .. } = self.state()? {
+            if self.account.difs < MIN_DELEGATION {
+                return Err(InstructionError::InvalidArgument);
+            }
             let vote_state: VoteState = vote_account.state()?;
             self.set_state(&StakeState::Delegate {
                 voter_pubkey: *vote_account.unsigned_key(),
                 credits_observed: vote_state.credits(),
+                stake: self.account.difs,
+                deactivated: STAKE_ACTIVE,
             })
         } else {
             Err(InstructionError::InvalidAccountData)
         }
     }
 
-    fn redeem_vote_credits(
-        &mut self,
-        stake_account: &mut KeyedAccount,
-        vote_account: &mut KeyedAccount,
-    ) -> Result<(), InstructionError> {
-        if let (
-            StakeState::MiningPool,
+    fn deactivate_stake(&mut self, tick_height: u64) -> Result<(), InstructionError> {
+        if self.signer_key().is_none() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
+
+        match self.state()? {
             StakeState::Delegate {
                 voter_pubkey,
                 credits_observed,
-            },
-        ) = (self.state()?, stake_account.state()?)
-        {
-            let vote_state: VoteState = vote_account.state()?;
-
-            if voter_pubkey != *vote_account.unsigned_key() {
-                return Err(InstructionError::InvalidArgument);
-            }
+                stake,
+                deactivated: STAKE_ACTIVE,
+            } if stake > 0 => self.set_state(&StakeState::Delegate {
+                voter_pubkey,
+                credits_observed,
+                stake,
+                deactivated: tick_height,
+            }),
+            _ => Err(InstructionError::InvalidAccountData),
+        }
+    }
 
-            if credits_observed > vote_state.credits() {
-                return Err(InstructionError::InvalidAccountData);
-            }
+    fn withdraw(
+        &mut self,
+        difs: u64,
+        to_account: &mut KeyedAccount,
+        epoch_ticks_account: &KeyedAccount,
+        tick_height: u64,
+    ) -> Result<(), InstructionError> {
+        if self.signer_key().is_none() {
+            return Err(InstructionError::MissingRequiredSignature);
+        }
 
-            if let Some((stakers_reward, voters_reward)) = StakeState::calculate_rewards(
-                credits_observed,
-                stake_account.account.difs,
-                &vote_state,
-            ) {
-                if self.account.difs < (stakers_reward + voters_reward) {
-                    return Err(InstructionError::UnbalancedInstruction);
-                }
-                self.account.difs -= stakers_reward + voters_reward;
-                stake_account.account.difs += stakers_reward;
-                vote_account.account.difs += voters_reward;
-
-                stake_account.set_state(&StakeState::Delegate {
-                    voter_pubkey,
-                    credits_observed: vote_state.credits(),
-                })
-            } else {
-                // not worth collecting
-                Err(InstructionError::CustomError(1))
-            }
-        } else {
-            Err(InstructionError::InvalidAccountData)
+        if !epoch_ticks::check_id(epoch_ticks_account.unsigned_key()) {
+            return Err(InstructionError::InvalidArgument);
+        }
+        let epoch_ticks: EpochTicks = epoch_ticks_account.state()?;
+
+        let stake_state: StakeState = self.state()?;
+        let withdrawable = stake_state
+            .withdrawable(self.account.difs, tick_height, epoch_ticks.ticks)
+            .ok_or(InstructionError::InvalidAccountData)?;
+        if difs > withdrawable {
+            return Err(InstructionError::new_result_with_negative_difs());
         }
+        self.account.difs -= difs;
+        to_account.account.difs += difs;
+        Ok(())
+    }
+}
+
+/// Pay `stake_account` and `vote_account` the rewards of the stake for the credits the
+/// voter earned since the stake was last paid, minting them at `rate` a year, see
+/// `calculate_rewards`.  Returns what was paid, as (voter's commission, staker's share),
+/// or `None` if the stake isn't active and delegated to `vote_account`'s voter or its
+/// rewards are still too small to pay
+pub fn redeem_rewards(
+    stake_account: &mut Account,
+    vote_pubkey: &Pubkey,
+    vote_account: &mut Account,
+    rate: f64,
+    credits_per_year: f64,
+) -> Option<(u64, u64)> {
+    let stake_state = StakeState::from(stake_account)?;
+    if stake_state.voter_pubkey() != Some(*vote_pubkey) {
+        return None;
+    }
+    if let StakeState::Delegate {
+        voter_pubkey,
+        credits_observed,
+        stake,
+        deactivated,
+    } = stake_state
+    {
+        let vote_state: VoteState = vote_account.state().ok()?;
+        let (voters_reward, stakers_reward) = StakeState::calculate_rewards(
+            credits_observed,
+            stake,
+            rate,
+            credits_per_year,
+            &vote_state,
+        )?;
+        stake_account
+            .set_state(&StakeState::Delegate {
+                voter_pubkey,
+                credits_observed: vote_state.credits(),
+                stake,
+                deactivated,
+            })
+            .ok()?;
+        stake_account.difs += stakers_reward;
+        vote_account.difs += voters_reward;
+        Some((voters_reward, stakers_reward))
+    } else {
+        None
     }
 }
 
@@ -193,6 +270,8 @@ pub fn create_delegate_stake_account(
         .set_state(&StakeState::Delegate {
             voter_pubkey: *voter_pubkey,
             credits_observed: vote_state.credits(),
+            stake: difs,
+            deactivated: STAKE_ACTIVE,
         })
         .expect("set_state");
 
@@ -208,6 +287,11 @@ mod tests {
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_vote_api::vote_state;
 
+    // credits/yr or slots/yr  is        seconds/year        *   ticks/second   * slots/tick
+    const CREDITS_PER_YEAR: f64 = (365f64 * 24f64 * 3600f64) * 10f64 / 8f64;
+
+    const STAKE_GETS_PAID_EVERY_VOTE: u64 = 200_000_000; // if CREDITS_PER_YEAR moves, fix this
+
     #[test]
     fn test_stake_delegate_stake() {
         let vote_keypair = Keypair::new();
@@ -223,7 +307,8 @@ mod tests {
         vote_keyed_account.set_state(&vote_state).unwrap();
 
         let stake_pubkey = Pubkey::default();
-        let mut stake_account = Account::new(0, 0, std::mem::size_of::<StakeState>(), &id());
+        let mut stake_account =
+            Account::new(MIN_DELEGATION, 0, std::mem::size_of::<StakeState>(), &id());
 
         let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, false, &mut stake_account);
 
@@ -246,7 +331,7 @@ mod tests {
         // verify that create_delegate_stake_account() matches the
         //   resulting account from delegate_stake()
         assert_eq!(
-            create_delegate_stake_account(&vote_pubkey, &vote_state, 0),
+            create_delegate_stake_account(&vote_pubkey, &vote_state, MIN_DELEGATION),
             *stake_keyed_account.account,
         );
 
@@ -255,18 +340,30 @@ mod tests {
             stake_state,
             StakeState::Delegate {
                 voter_pubkey: vote_keypair.pubkey(),
-                credits_observed: vote_state.credits()
+                credits_observed: vote_state.credits(),
+                stake: MIN_DELEGATION,
+                deactivated: STAKE_ACTIVE,
             }
         );
 
-        let stake_state = StakeState::MiningPool;
-        stake_keyed_account.set_state(&stake_state).unwrap();
+        // dust can't be delegated
+        stake_keyed_account.account.difs = MIN_DELEGATION - 1;
+        assert_eq!(
+            stake_keyed_account.delegate_stake(&vote_keyed_account),
+            Err(InstructionError::InvalidArgument)
+        );
+        stake_keyed_account.account.difs = MIN_DELEGATION;
+
+        stake_keyed_account
+            .set_state(&StakeState::Uninitialized)
+            .unwrap();
         assert!(stake_keyed_account
             .delegate_stake(&vote_keyed_account)
             .is_err());
     }
     #[test]
     fn test_stake_state_calculate_rewards() {
+        const RATE: f64 = 0.2;
         let mut vote_state = VoteState::default();
         let mut vote_i = 0;
 
@@ -276,17 +373,32 @@ mod tests {
             vote_i += 1;
         }
         // this guy can't collect now, not enough stake to get paid on 1 credit
-        assert_eq!(None, StakeState::calculate_rewards(0, 100, &vote_state));
+        assert_eq!(
+            None,
+            StakeState::calculate_rewards(0, 100, RATE, CREDITS_PER_YEAR, &vote_state)
+        );
         // this guy can
         assert_eq!(
             Some((0, 1)),
-            StakeState::calculate_rewards(0, STAKE_GETS_PAID_EVERY_VOTE, &vote_state)
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                RATE,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
         );
         // but, there's not enough to split
         vote_state.commission = std::u32::MAX / 2;
         assert_eq!(
             None,
-            StakeState::calculate_rewards(0, STAKE_GETS_PAID_EVERY_VOTE, &vote_state)
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                RATE,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
         );
 
         // put more credit in the vote_state
@@ -297,154 +409,261 @@ mod tests {
         vote_state.commission = 0;
         assert_eq!(
             Some((0, 10)),
-            StakeState::calculate_rewards(0, STAKE_GETS_PAID_EVERY_VOTE, &vote_state)
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                RATE,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
         );
         vote_state.commission = std::u32::MAX;
         assert_eq!(
             Some((10, 0)),
-            StakeState::calculate_rewards(0, STAKE_GETS_PAID_EVERY_VOTE, &vote_state)
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                RATE,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
         );
         vote_state.commission = std::u32::MAX / 2;
         assert_eq!(
             Some((5, 5)),
-            StakeState::calculate_rewards(0, STAKE_GETS_PAID_EVERY_VOTE, &vote_state)
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                RATE,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
         );
         // not even enough stake to get paid on 10 credits...
-        assert_eq!(None, StakeState::calculate_rewards(0, 100, &vote_state));
+        assert_eq!(
+            None,
+            StakeState::calculate_rewards(0, 100, RATE, CREDITS_PER_YEAR, &vote_state)
+        );
+
+        // the rate scales the rewards
+        vote_state.commission = 0;
+        assert_eq!(
+            Some((0, 20)),
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                RATE * 2.0,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
+        );
+        assert_eq!(
+            None,
+            StakeState::calculate_rewards(
+                0,
+                STAKE_GETS_PAID_EVERY_VOTE,
+                0.0,
+                CREDITS_PER_YEAR,
+                &vote_state
+            )
+        );
     }
 
     #[test]
-    fn test_stake_redeem_vote_credits() {
-        let vote_keypair = Keypair::new();
+    fn test_stake_redeem_rewards() {
+        let vote_pubkey = Pubkey::new_rand();
         let mut vote_state = VoteState::default();
         for i in 0..1000 {
             vote_state.process_slot_vote_unchecked(i);
         }
-
-        let vote_pubkey = vote_keypair.pubkey();
         let mut vote_account =
             vote_state::create_account(&vote_pubkey, &Pubkey::new_rand(), 0, 100);
-        let mut vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
-        vote_keyed_account.set_state(&vote_state).unwrap();
-
-        let pubkey = Pubkey::default();
-        let mut stake_account = Account::new(
-            STAKE_GETS_PAID_EVERY_VOTE,
-            0,
-            std::mem::size_of::<StakeState>(),
-            &id(),
-        );
-        let mut stake_keyed_account = KeyedAccount::new(&pubkey, true, &mut stake_account);
-        stake_keyed_account.initialize_delegate().unwrap();
-
-        // delegate the stake
-        assert!(stake_keyed_account
-            .delegate_stake(&vote_keyed_account)
-            .is_ok());
+        vote_account.set_state(&vote_state).unwrap();
 
-        let mut mining_pool_account = Account::new(0, 0, std::mem::size_of::<StakeState>(), &id());
-        let mut mining_pool_keyed_account =
-            KeyedAccount::new(&pubkey, true, &mut mining_pool_account);
+        let mut stake_account =
+            create_delegate_stake_account(&vote_pubkey, &vote_state, STAKE_GETS_PAID_EVERY_VOTE);
 
-        // not a mining pool yet...
+        // no movement in vote account, so nothing to redeem
         assert_eq!(
-            mining_pool_keyed_account
-                .redeem_vote_credits(&mut stake_keyed_account, &mut vote_keyed_account),
-            Err(InstructionError::InvalidAccountData)
+            redeem_rewards(
+                &mut stake_account,
+                &vote_pubkey,
+                &mut vote_account,
+                0.2,
+                CREDITS_PER_YEAR
+            ),
+            None
         );
 
-        mining_pool_keyed_account
-            .set_state(&StakeState::MiningPool)
-            .unwrap();
+        // move the vote account forward
+        vote_state.process_slot_vote_unchecked(1000);
+        vote_account.set_state(&vote_state).unwrap();
 
-        // no movement in vote account, so no redemption needed
+        // wrong voter
         assert_eq!(
-            mining_pool_keyed_account
-                .redeem_vote_credits(&mut stake_keyed_account, &mut vote_keyed_account),
-            Err(InstructionError::CustomError(1))
+            redeem_rewards(
+                &mut stake_account,
+                &Pubkey::new_rand(),
+                &mut vote_account,
+                0.2,
+                CREDITS_PER_YEAR
+            ),
+            None
         );
 
-        // move the vote account forward
-        vote_state.process_slot_vote_unchecked(1000);
-        vote_keyed_account.set_state(&vote_state).unwrap();
-
-        // now, no difs in the pool!
         assert_eq!(
-            mining_pool_keyed_account
-                .redeem_vote_credits(&mut stake_keyed_account, &mut vote_keyed_account),
-            Err(InstructionError::UnbalancedInstruction)
+            redeem_rewards(
+                &mut stake_account,
+                &vote_pubkey,
+                &mut vote_account,
+                0.2,
+                CREDITS_PER_YEAR
+            ),
+            Some((0, 1))
+        );
+        // the difs are minted, the stake stays what was delegated
+        assert_eq!(vote_account.difs, 100);
+        assert_eq!(stake_account.difs, STAKE_GETS_PAID_EVERY_VOTE + 1);
+        assert_eq!(
+            StakeState::from(&stake_account),
+            Some(StakeState::Delegate {
+                voter_pubkey: vote_pubkey,
+                credits_observed: vote_state.credits(),
+                stake: STAKE_GETS_PAID_EVERY_VOTE,
+                deactivated: STAKE_ACTIVE,
+            })
         );
 
-        // add a dif to pool
-        mining_pool_keyed_account.account.difs = 2;
-        assert!(mining_pool_keyed_account
-            .redeem_vote_credits(&mut stake_keyed_account, &mut vote_keyed_account)
-            .is_ok()); // yay
+        // the credits were paid for
+        assert_eq!(
+            redeem_rewards(
+                &mut stake_account,
+                &vote_pubkey,
+                &mut vote_account,
+                0.2,
+                CREDITS_PER_YEAR
+            ),
+            None
+        );
 
-        // difs only shifted around, none made or lost
+        // voter credits lower than the stake's credits observed
+        let vote_state = VoteState::default();
+        vote_account.set_state(&vote_state).unwrap();
         assert_eq!(
-            2 + 100 + STAKE_GETS_PAID_EVERY_VOTE,
-            mining_pool_account.difs + vote_account.difs + stake_account.difs
+            redeem_rewards(
+                &mut stake_account,
+                &vote_pubkey,
+                &mut vote_account,
+                0.2,
+                CREDITS_PER_YEAR
+            ),
+            None
         );
     }
 
     #[test]
-    fn test_stake_redeem_vote_credits_vote_errors() {
-        let vote_keypair = Keypair::new();
-        let mut vote_state = VoteState::default();
-        for i in 0..1000 {
-            vote_state.process_slot_vote_unchecked(i);
-        }
-
-        let vote_pubkey = vote_keypair.pubkey();
+    fn test_stake_deactivate_and_withdraw() {
+        let vote_pubkey = Pubkey::new_rand();
         let mut vote_account =
             vote_state::create_account(&vote_pubkey, &Pubkey::new_rand(), 0, 100);
-        let mut vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
-        vote_keyed_account.set_state(&vote_state).unwrap();
+        let vote_keyed_account = KeyedAccount::new(&vote_pubkey, false, &mut vote_account);
+
+        let stake_pubkey = Pubkey::new_rand();
+        let mut stake_account = Account::new(10, 0, std::mem::size_of::<StakeState>(), &id());
+        let mut to_account = Account::default();
+        let to_pubkey = Pubkey::new_rand();
+        let mut to_keyed_account = KeyedAccount::new(&to_pubkey, false, &mut to_account);
+        let cooldown_ticks = 64;
+        let mut epoch_ticks_account = epoch_ticks::create_account(
+            1,
+            &EpochTicks {
+                epoch: 0,
+                ticks: cooldown_ticks,
+            },
+        );
+        let epoch_ticks_pubkey = epoch_ticks::id();
+        let epoch_ticks_keyed_account =
+            KeyedAccount::new(&epoch_ticks_pubkey, false, &mut epoch_ticks_account);
 
-        let pubkey = Pubkey::default();
-        let mut stake_account = Account::new(0, 0, std::mem::size_of::<StakeState>(), &id());
-        let mut stake_keyed_account = KeyedAccount::new(&pubkey, true, &mut stake_account);
-        stake_keyed_account.initialize_delegate().unwrap();
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, false, &mut stake_account);
+        assert_eq!(
+            stake_keyed_account.withdraw(1, &mut to_keyed_account, &epoch_ticks_keyed_account, 0),
+            Err(InstructionError::MissingRequiredSignature)
+        );
+        assert_eq!(
+            stake_keyed_account.deactivate_stake(0),
+            Err(InstructionError::MissingRequiredSignature)
+        );
 
-        // delegate the stake
-        assert!(stake_keyed_account
+        let mut stake_keyed_account = KeyedAccount::new(&stake_pubkey, true, &mut stake_account);
+        stake_keyed_account.initialize_delegate().unwrap();
+        // nothing delegated yet, can't deactivate
+        assert_eq!(
+            stake_keyed_account.deactivate_stake(0),
+            Err(InstructionError::InvalidAccountData)
+        );
+        stake_keyed_account
             .delegate_stake(&vote_keyed_account)
-            .is_ok());
+            .unwrap();
 
-        let mut mining_pool_account = Account::new(0, 0, std::mem::size_of::<StakeState>(), &id());
-        let mut mining_pool_keyed_account =
-            KeyedAccount::new(&pubkey, true, &mut mining_pool_account);
-        mining_pool_keyed_account
-            .set_state(&StakeState::MiningPool)
+        // only the difs deposited after delegation are withdrawable
+        stake_keyed_account.account.difs += 5;
+        assert_eq!(
+            stake_keyed_account.withdraw(6, &mut to_keyed_account, &epoch_ticks_keyed_account, 0),
+            Err(InstructionError::new_result_with_negative_difs())
+        );
+        stake_keyed_account
+            .withdraw(5, &mut to_keyed_account, &epoch_ticks_keyed_account, 0)
             .unwrap();
+        assert_eq!(stake_keyed_account.account.difs, 10);
+        assert_eq!(to_keyed_account.account.difs, 5);
 
-        let mut vote_state = VoteState::default();
-        for i in 0..100 {
-            // go back in time, previous state had 1000 votes
-            vote_state.process_slot_vote_unchecked(i);
-        }
-        vote_keyed_account.set_state(&vote_state).unwrap();
-        // voter credits lower than stake_delegate credits...  TODO: is this an error?
+        // deactivated stake stops counting towards the voter right away...
+        stake_keyed_account.deactivate_stake(100).unwrap();
         assert_eq!(
-            mining_pool_keyed_account
-                .redeem_vote_credits(&mut stake_keyed_account, &mut vote_keyed_account),
+            StakeState::voter_pubkey_from(&stake_keyed_account.account),
+            None
+        );
+        assert_eq!(
+            stake_keyed_account.deactivate_stake(100),
             Err(InstructionError::InvalidAccountData)
         );
 
-        let vote1_keypair = Keypair::new();
-        let vote1_pubkey = vote1_keypair.pubkey();
-        let mut vote1_account =
-            vote_state::create_account(&vote1_pubkey, &Pubkey::new_rand(), 0, 100);
-        let mut vote1_keyed_account = KeyedAccount::new(&vote1_pubkey, false, &mut vote1_account);
-        vote1_keyed_account.set_state(&vote_state).unwrap();
+        // ...but is locked up until the cooldown, an epoch's worth of ticks, has passed
+        let cooldown_end = 100 + cooldown_ticks;
+        assert_eq!(
+            stake_keyed_account.withdraw(
+                1,
+                &mut to_keyed_account,
+                &epoch_ticks_keyed_account,
+                cooldown_end - 1
+            ),
+            Err(InstructionError::new_result_with_negative_difs())
+        );
 
-        // wrong voter_pubkey...
+        // the epoch's length is only taken from the epoch ticks account
+        let mut not_epoch_ticks_account = epoch_ticks_keyed_account.account.clone();
+        let not_epoch_ticks_keyed_account =
+            KeyedAccount::new(&to_pubkey, false, &mut not_epoch_ticks_account);
         assert_eq!(
-            mining_pool_keyed_account
-                .redeem_vote_credits(&mut stake_keyed_account, &mut vote1_keyed_account),
+            stake_keyed_account.withdraw(
+                10,
+                &mut to_keyed_account,
+                &not_epoch_ticks_keyed_account,
+                cooldown_end
+            ),
             Err(InstructionError::InvalidArgument)
         );
-    }
 
+        stake_keyed_account
+            .withdraw(
+                10,
+                &mut to_keyed_account,
+                &epoch_ticks_keyed_account,
+                cooldown_end,
+            )
+            .unwrap();
+        assert_eq!(stake_keyed_account.account.difs, 0);
+        assert_eq!(to_keyed_account.account.difs, 15);
+    }
 }
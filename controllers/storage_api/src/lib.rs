@@ -4,10 +4,18 @@ pub mod storage_processor;
 
 pub const SLOTS_PER_SEGMENT: u64 = 16;
 
+/// Proofs for a segment are accepted for this many segments after the segment completes
+pub const PROOF_WINDOW_SEGMENTS: usize = 8;
+
 pub fn get_segment_from_slot(slot: u64) -> usize {
     (slot / SLOTS_PER_SEGMENT) as usize
 }
 
+/// True if proofs for `segment` may still be submitted during `current_segment`
+pub fn is_proof_window_open(segment: usize, current_segment: usize) -> bool {
+    segment < current_segment && current_segment <= segment + PROOF_WINDOW_SEGMENTS
+}
+
 const STORAGE_PROGRAM_ID: [u8; 32] = [
     6, 162, 25, 123, 127, 68, 233, 59, 131, 151, 21, 152, 162, 120, 90, 37, 154, 88, 86, 5, 156,
     221, 182, 201, 142, 103, 151, 112, 0, 0, 0, 0,
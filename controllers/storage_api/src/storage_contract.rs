@@ -1,4 +1,4 @@
-use crate::get_segment_from_slot;
+use crate::{get_segment_from_slot, is_proof_window_open};
 use log::*;
 use serde_derive::{Deserialize, Serialize};
 use morgan_interface::account::Account;
@@ -139,8 +139,12 @@ impl<'a> StorageAccount<'a> {
             let segment_index = get_segment_from_slot(slot);
             let current_segment = get_segment_from_slot(current_slot);
 
-            if segment_index >= current_segment {
-                // attempt to submit proof for unconfirmed segment
+            if !is_proof_window_open(segment_index, current_segment) {
+                // attempt to submit proof for unconfirmed segment, or after the window closed
+                debug!(
+                    "proof for segment {} rejected in segment {}",
+                    segment_index, current_segment
+                );
                 return Err(InstructionError::InvalidArgument);
             }
 
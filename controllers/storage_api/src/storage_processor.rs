@@ -99,7 +99,7 @@ mod tests {
         TOTAL_REPLICATOR_REWARDS, TOTAL_VALIDATOR_REWARDS,
     };
     use crate::storage_instruction;
-    use crate::SLOTS_PER_SEGMENT;
+    use crate::{PROOF_WINDOW_SEGMENTS, SLOTS_PER_SEGMENT};
     use crate::{get_segment_from_slot, id};
     use assert_matches::assert_matches;
     use bincode::deserialize;
@@ -253,6 +253,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_submit_mining_late_proof() {
+        morgan_logger::setup();
+        let pubkey = Pubkey::new_rand();
+        let mut accounts = [Account::default(), Account::default()];
+        accounts[0].data.resize(STORAGE_ACCOUNT_SPACE as usize, 0);
+        {
+            let mut storage_account = StorageAccount::new(&mut accounts[0]);
+            storage_account.initialize_replicator_storage().unwrap();
+        }
+
+        let ix =
+            storage_instruction::mining_proof(&pubkey, Hash::default(), 0, Signature::default());
+        // the window for segment 0 closes once the tick height moves past its last segment
+        let ticks_till_window_closed = TICKS_IN_SEGMENT * (PROOF_WINDOW_SEGMENTS as u64 + 1);
+        assert_eq!(
+            test_instruction(&ix, &mut accounts, ticks_till_window_closed),
+            Err(InstructionError::InvalidArgument)
+        );
+        assert_matches!(
+            test_instruction(&ix, &mut accounts, ticks_till_window_closed - 1),
+            Ok(_)
+        );
+    }
+
     #[test]
     fn test_validate_mining() {
         morgan_logger::setup();
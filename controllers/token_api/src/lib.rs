@@ -1,6 +1,11 @@
 pub mod token_processor;
 mod token_state;
 
+pub use token_state::{
+    transfer_memo, TokenError, TokenInstruction, TransferApproval, MAX_MEMO_LEN,
+};
+
+use morgan_interface::message::Message;
 use morgan_interface::pubkey::Pubkey;
 
 const TOKEN_PROGRAM_ID: [u8; 32] = [
@@ -11,3 +16,17 @@ const TOKEN_PROGRAM_ID: [u8; 32] = [
 pub fn id() -> Pubkey {
     Pubkey::new(&TOKEN_PROGRAM_ID)
 }
+
+/// The memo of the first token transfer in `message` that carries one
+pub fn message_memo(message: &Message) -> Option<Vec<u8>> {
+    message
+        .instructions
+        .iter()
+        .filter(|instruction| {
+            message
+                .account_keys
+                .get(instruction.program_ids_index as usize)
+                == Some(&id())
+        })
+        .find_map(|instruction| transfer_memo(&instruction.data))
+}
@@ -6,11 +6,16 @@ use morgan_interface::instruction_processor_utils::DecodeError;
 use morgan_interface::pubkey::Pubkey;
 use morgan_helper::logHelper::*;
 
+/// Longest memo a transfer may carry
+pub const MAX_MEMO_LEN: usize = 64;
+
 #[derive(Serialize, Debug, PartialEq, FromPrimitive)]
 pub enum TokenError {
     InvalidArgument,
     InsufficentFunds,
     NotOwner,
+    MemoTooLong,
+    TransferNotApproved,
 }
 
 impl<T> DecodeError<T> for TokenError {
@@ -67,15 +72,47 @@ pub struct TokenAccountInfo {
     /// If `delegate` is Option<_>, `amount` represents the remaining allowance
     /// of tokens that may be transferred from the `source` account.
     delegate: Option<TokenAccountDelegateInfo>,
+
+    /// Program that must approve every transfer out of or into this account, from the token's
+    /// `NewTokenWithTransferHook`
+    transfer_hook: Option<Pubkey>,
+
+    /// Transfers out of this account so far, an approval is for the next one only
+    transfers: u64,
+}
+
+/// What a transfer hook program writes to an account it owns to let one transfer of its token
+/// through. The transfer carries that account after its own, and the approval is spent once the
+/// source account's `transfers` move past it
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TransferApproval {
+    pub source: Pubkey,
+    pub dest: Pubkey,
+    pub amount: u64,
+    /// The source account's `transfers` when the transfer is made
+    pub transfer: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
-enum TokenInstruction {
+pub enum TokenInstruction {
     NewToken(TokenInfo),
     NewTokenAccount,
     Transfer(u64),
     Approve(u64),
     SetOwner,
+    /// A `Transfer` carrying a memo of at most MAX_MEMO_LEN bytes, such as the deposit
+    /// reference an exchange attributes it by
+    TransferWithMemo(u64, Vec<u8>),
+    /// A `NewToken` whose transfers must all carry a `TransferApproval` from the given program
+    NewTokenWithTransferHook(TokenInfo, Pubkey),
+}
+
+/// The memo of the token program instruction `data`, if it is a transfer carrying one
+pub fn transfer_memo(data: &[u8]) -> Option<Vec<u8>> {
+    match bincode::deserialize(data) {
+        Ok(TokenInstruction::TransferWithMemo(_, memo)) => Some(memo),
+        _ => None,
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -84,6 +121,7 @@ pub enum TokenState {
     Token(TokenInfo),
     Account(TokenAccountInfo),
     Invalid,
+    TokenWithTransferHook(TokenInfo, Pubkey),
 }
 impl Default for TokenState {
     fn default() -> TokenState {
@@ -118,6 +156,11 @@ impl TokenState {
             2 => Ok(TokenState::Account(
                 bincode::deserialize(&input[1..]).map_err(Self::map_to_invalid_args)?,
             )),
+            3 => {
+                let (token_info, transfer_hook) =
+                    bincode::deserialize(&input[1..]).map_err(Self::map_to_invalid_args)?;
+                Ok(TokenState::TokenWithTransferHook(token_info, transfer_hook))
+            }
             _ => Err(TokenError::InvalidArgument),
         }
     }
@@ -146,7 +189,46 @@ impl TokenState {
                 let writer = std::io::BufWriter::new(&mut output[1..]);
                 bincode::serialize_into(writer, &account_info).map_err(Self::map_to_invalid_args)
             }
+            TokenState::TokenWithTransferHook(token_info, transfer_hook) => {
+                output[0] = 3;
+                let writer = std::io::BufWriter::new(&mut output[1..]);
+                bincode::serialize_into(writer, &(token_info, transfer_hook))
+                    .map_err(Self::map_to_invalid_args)
+            }
+        }
+    }
+
+    /// The program that approves the transfers of the token, if this is a token that has one
+    fn transfer_hook(&self) -> Option<Pubkey> {
+        if let TokenState::TokenWithTransferHook(_, transfer_hook) = self {
+            Some(*transfer_hook)
+        } else {
+            None
+        }
+    }
+
+    /// Only the hook program can write an account it owns, so the hook ran and let `approval`
+    /// through if one of the accounts past the transfer's own holds it
+    fn check_transfer_hook(
+        info: &[KeyedAccount],
+        transfer_hook: &Pubkey,
+        approval: &TransferApproval,
+    ) -> Result<()> {
+        if !info[3..].iter().any(|keyed_account| {
+            keyed_account.account.owner == *transfer_hook
+                && bincode::deserialize::<TransferApproval>(&keyed_account.account.data).ok()
+                    == Some(approval.clone())
+        }) {
+            println!(
+                "{}",
+                Error(
+                    format!("transfer not approved by hook {}", transfer_hook).to_string(),
+                    module_path!().to_string()
+                )
+            );
+            Err(TokenError::TransferNotApproved)?;
         }
+        Ok(())
     }
 
     #[allow(dead_code)]
@@ -181,6 +263,7 @@ impl TokenState {
     pub fn process_newtoken(
         info: &mut [KeyedAccount],
         token_info: TokenInfo,
+        transfer_hook: Option<Pubkey>,
         input_accounts: &[TokenState],
         output_accounts: &mut Vec<(usize, TokenState)>,
     ) -> Result<()> {
@@ -223,6 +306,7 @@ impl TokenState {
 
             let mut output_dest_account = dest_account.clone();
             output_dest_account.amount = token_info.supply;
+            output_dest_account.transfer_hook = transfer_hook;
             output_accounts.push((1, TokenState::Account(output_dest_account)));
         } else {
             // error!("{}", Error(format!("account 1 invalid").to_string()));
@@ -247,7 +331,11 @@ impl TokenState {
             );
             Err(TokenError::InvalidArgument)?;
         }
-        output_accounts.push((0, TokenState::Token(token_info)));
+        let token = match transfer_hook {
+            Some(transfer_hook) => TokenState::TokenWithTransferHook(token_info, transfer_hook),
+            None => TokenState::Token(token_info),
+        };
+        output_accounts.push((0, token));
         Ok(())
     }
 
@@ -287,6 +375,8 @@ impl TokenState {
             owner: *info[1].unsigned_key(),
             amount: 0,
             delegate: None,
+            transfer_hook: input_accounts[2].transfer_hook(),
+            transfers: 0,
         };
         if input_accounts.len() >= 4 {
             token_account_info.delegate = Some(TokenAccountDelegateInfo {
@@ -359,12 +449,22 @@ impl TokenState {
                 Err(TokenError::InsufficentFunds)?;
             }
 
+            // accounts created before their token pick up its hook from the tokens they receive
+            let mut transfer_hook = source_account.transfer_hook.or(dest_account.transfer_hook);
+            let approval = TransferApproval {
+                source: *info[1].unsigned_key(),
+                dest: *info[2].unsigned_key(),
+                amount,
+                transfer: source_account.transfers,
+            };
+
             let mut output_source_account = source_account.clone();
             output_source_account.amount -= amount;
+            output_source_account.transfers += 1;
             output_accounts.push((1, TokenState::Account(output_source_account)));
 
             if let Some(ref delegate_info) = source_account.delegate {
-                if input_accounts.len() != 4 {
+                if input_accounts.len() < 4 {
                     // error!("{}", Error(format!("Expected 4 accounts").to_string()));
                     println!(
                         "{}",
@@ -404,6 +504,7 @@ impl TokenState {
                     if source_account.amount < amount {
                         Err(TokenError::InsufficentFunds)?;
                     }
+                    transfer_hook = transfer_hook.or(source_account.transfer_hook);
 
                     let mut output_source_account = source_account.clone();
                     output_source_account.amount -= amount;
@@ -421,8 +522,13 @@ impl TokenState {
                 }
             }
 
+            if let Some(transfer_hook) = transfer_hook {
+                Self::check_transfer_hook(info, &transfer_hook, &approval)?;
+            }
+
             let mut output_dest_account = dest_account.clone();
             output_dest_account.amount += amount;
+            output_dest_account.transfer_hook = transfer_hook;
             output_accounts.push((2, TokenState::Account(output_dest_account)));
         } else {
             // error!("{}", Error(format!("account 1 and/or 2 are invalid accounts").to_string()));
@@ -526,6 +632,7 @@ impl TokenState {
                         source: delegate_info.source,
                         original_amount: amount,
                     });
+                    output_delegate_account.transfer_hook = source_account.transfer_hook;
                     output_accounts.push((2, TokenState::Account(output_delegate_account)));
                 }
             }
@@ -644,8 +751,21 @@ impl TokenState {
         let mut output_accounts: Vec<(_, _)> = vec![];
 
         match command {
-            TokenInstruction::NewToken(token_info) => {
-                Self::process_newtoken(info, token_info, &input_accounts, &mut output_accounts)?
+            TokenInstruction::NewToken(token_info) => Self::process_newtoken(
+                info,
+                token_info,
+                None,
+                &input_accounts,
+                &mut output_accounts,
+            )?,
+            TokenInstruction::NewTokenWithTransferHook(token_info, transfer_hook) => {
+                Self::process_newtoken(
+                    info,
+                    token_info,
+                    Some(transfer_hook),
+                    &input_accounts,
+                    &mut output_accounts,
+                )?
             }
             TokenInstruction::NewTokenAccount => {
                 Self::process_newaccount(info, &input_accounts, &mut output_accounts)?
@@ -655,6 +775,13 @@ impl TokenState {
                 Self::process_transfer(info, amount, &input_accounts, &mut output_accounts)?
             }
 
+            TokenInstruction::TransferWithMemo(amount, memo) => {
+                if memo.len() > MAX_MEMO_LEN {
+                    Err(TokenError::MemoTooLong)?;
+                }
+                Self::process_transfer(info, amount, &input_accounts, &mut output_accounts)?
+            }
+
             TokenInstruction::Approve(amount) => {
                 Self::process_approve(info, amount, &input_accounts, &mut output_accounts)?
             }
@@ -681,6 +808,7 @@ impl TokenState {
 #[cfg(test)]
 mod test {
     use super::*;
+    use morgan_interface::account::Account;
     #[test]
     pub fn serde() {
         assert_eq!(TokenState::deserialize(&[0]), Ok(TokenState::default()));
@@ -692,6 +820,8 @@ mod test {
             owner: Pubkey::new(&[2; 32]),
             amount: 123,
             delegate: None,
+            transfer_hook: Some(Pubkey::new(&[3; 32])),
+            transfers: 4,
         });
         account.serialize(&mut data).unwrap();
         assert_eq!(TokenState::deserialize(&data), Ok(account));
@@ -704,6 +834,18 @@ mod test {
         });
         account.serialize(&mut data).unwrap();
         assert_eq!(TokenState::deserialize(&data), Ok(account));
+
+        let account = TokenState::TokenWithTransferHook(
+            TokenInfo {
+                supply: 12345,
+                decimals: 2,
+                name: "A test token".to_string(),
+                symbol: "TEST".to_string(),
+            },
+            Pubkey::new(&[3; 32]),
+        );
+        account.serialize(&mut data).unwrap();
+        assert_eq!(TokenState::deserialize(&data), Ok(account));
     }
 
     #[test]
@@ -724,6 +866,169 @@ mod test {
         assert!(TokenState::deserialize(&[1, 2]).is_err());
         assert!(TokenState::deserialize(&[2, 2]).is_err());
         assert!(TokenState::deserialize(&[3]).is_err());
+        assert!(TokenState::deserialize(&[4]).is_err());
+    }
+
+    #[test]
+    fn test_transfer_memo() {
+        let data = bincode::serialize(&TokenInstruction::TransferWithMemo(1, vec![7; 3])).unwrap();
+        assert_eq!(transfer_memo(&data), Some(vec![7; 3]));
+        let data = bincode::serialize(&TokenInstruction::Transfer(1)).unwrap();
+        assert_eq!(transfer_memo(&data), None);
+        assert_eq!(transfer_memo(&[]), None);
+    }
+
+    fn process(
+        instruction: &TokenInstruction,
+        keys: &[Pubkey],
+        accounts: &mut [Account],
+    ) -> Result<()> {
+        let mut info: Vec<_> = keys
+            .iter()
+            .zip(accounts.iter_mut())
+            .enumerate()
+            .map(|(i, (key, account))| KeyedAccount::new(key, i == 0, account))
+            .collect();
+        TokenState::process(
+            &crate::id(),
+            &mut info,
+            &bincode::serialize(instruction).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_transfer_with_memo_and_hook() {
+        let token_info = TokenInfo {
+            supply: 100,
+            decimals: 0,
+            name: "A test token".to_string(),
+            symbol: "TEST".to_string(),
+        };
+        let [token, owner, source, dest, hook, approval] = [
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+        ];
+        // what the hook program writes to let a transfer through
+        let approval_account = |owner: &Pubkey, amount, transfer| {
+            let approval = TransferApproval {
+                source,
+                dest,
+                amount,
+                transfer,
+            };
+            let mut account = Account::new(1, 0, 0, owner);
+            account.data = bincode::serialize(&approval).unwrap();
+            account
+        };
+
+        // the token's accounts, then the token with its supply in `source`
+        let mut accounts = [
+            Account::new(0, 0, 256, &crate::id()),
+            Account::default(),
+            Account::new(0, 0, 256, &crate::id()),
+        ];
+        process(
+            &TokenInstruction::NewTokenAccount,
+            &[source, owner, token],
+            &mut accounts,
+        )
+        .unwrap();
+        let [source_account, _, token_account] = accounts;
+        let mut accounts = [
+            Account::new(0, 0, 256, &crate::id()),
+            Account::default(),
+            token_account.clone(),
+        ];
+        process(
+            &TokenInstruction::NewTokenAccount,
+            &[dest, owner, token],
+            &mut accounts,
+        )
+        .unwrap();
+        let [dest_account, _, _] = accounts;
+        let mut accounts = [token_account, source_account];
+        process(
+            &TokenInstruction::NewTokenWithTransferHook(token_info, hook),
+            &[token, source],
+            &mut accounts,
+        )
+        .unwrap();
+        let [token_account, source_account] = accounts;
+        assert_eq!(
+            TokenState::deserialize(&token_account.data)
+                .unwrap()
+                .transfer_hook(),
+            Some(hook)
+        );
+
+        // transfers must carry the hook's approval, written by the hook and for this transfer
+        let transfer = |approval_account: Option<Account>, accounts: &mut [Account; 3]| {
+            let [_, source_account, dest_account] = accounts;
+            let mut keys = vec![owner, source, dest];
+            let mut transfer_accounts = vec![
+                Account::default(),
+                source_account.clone(),
+                dest_account.clone(),
+            ];
+            if let Some(approval_account) = approval_account {
+                keys.push(approval);
+                transfer_accounts.push(approval_account);
+            }
+            process(
+                &TokenInstruction::TransferWithMemo(10, b"deposit 42".to_vec()),
+                &keys,
+                &mut transfer_accounts,
+            )?;
+            *source_account = transfer_accounts[1].clone();
+            *dest_account = transfer_accounts[2].clone();
+            Ok(())
+        };
+        let mut accounts = [Account::default(), source_account, dest_account];
+        for approval_account in vec![
+            None,
+            Some(approval_account(&Pubkey::new_rand(), 10, 0)),
+            Some(approval_account(&hook, 11, 0)),
+            Some(approval_account(&hook, 10, 1)),
+        ] {
+            assert_eq!(
+                transfer(approval_account, &mut accounts),
+                Err(TokenError::TransferNotApproved)
+            );
+        }
+        let mut memo_accounts = accounts.clone().to_vec();
+        memo_accounts.push(approval_account(&hook, 10, 0));
+        assert_eq!(
+            process(
+                &TokenInstruction::TransferWithMemo(10, vec![0; MAX_MEMO_LEN + 1]),
+                &[owner, source, dest, approval],
+                &mut memo_accounts,
+            ),
+            Err(TokenError::MemoTooLong)
+        );
+        transfer(Some(approval_account(&hook, 10, 0)), &mut accounts).unwrap();
+        assert_eq!(
+            TokenState::deserialize(&accounts[1].data).unwrap().amount(),
+            Ok(90)
+        );
+        assert_eq!(
+            TokenState::deserialize(&accounts[2].data).unwrap().amount(),
+            Ok(10)
+        );
+
+        // an approval lets one transfer through
+        assert_eq!(
+            transfer(Some(approval_account(&hook, 10, 0)), &mut accounts),
+            Err(TokenError::TransferNotApproved)
+        );
+        transfer(Some(approval_account(&hook, 10, 1)), &mut accounts).unwrap();
+        assert_eq!(
+            TokenState::deserialize(&accounts[2].data).unwrap().amount(),
+            Ok(20)
+        );
     }
 
     // Note: business logic tests are located in the @morgan/web3.js test suite
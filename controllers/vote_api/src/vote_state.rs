@@ -3,11 +3,13 @@
 use crate::id;
 use bincode::{deserialize, serialize_into, serialized_size, ErrorKind};
 use log::*;
+use num_derive::FromPrimitive;
 use serde_derive::{Deserialize, Serialize};
 use morgan_interface::account::{Account, KeyedAccount};
 use morgan_interface::account_utils::State;
-use morgan_interface::hash::Hash;
+use morgan_interface::hash::{hashv, Hash};
 use morgan_interface::instruction::InstructionError;
+use morgan_interface::instruction_processor_utils::DecodeError;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::syscall::slot_hashes;
 use std::collections::VecDeque;
@@ -17,17 +19,56 @@ use morgan_helper::logHelper::*;
 pub const MAX_LOCKOUT_HISTORY: usize = 31;
 pub const INITIAL_LOCKOUT: usize = 2;
 
+/// Leads the hash each vote commits to, so a signed vote can't pass for a signature over
+/// anything but a slot and its bank hash
+pub const VOTE_DOMAIN_SEPARATOR: &[u8] = b"morgan-vote-v1";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, FromPrimitive)]
+pub enum VoteError {
+    /// The vote's domain hash doesn't cover its slot and bank hash
+    InvalidDomain,
+    /// The voted slot has a different bank hash, or none, on the fork the vote landed on
+    WrongFork,
+}
+
+impl<T> DecodeError<T> for VoteError {
+    fn type_of(&self) -> &'static str {
+        "VoteError"
+    }
+}
+
+impl std::fmt::Display for VoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "error")
+    }
+}
+impl std::error::Error for VoteError {}
+
 #[derive(Serialize, Default, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Vote {
     /// A vote for height slot
     pub slot: u64,
     // signature of the bank's state at given slot
     pub hash: Hash,
+    /// Commits to `slot` and `hash` under `VOTE_DOMAIN_SEPARATOR`
+    pub domain_hash: Hash,
 }
 
 impl Vote {
     pub fn new(slot: u64, hash: Hash) -> Self {
-        Self { slot, hash }
+        Self {
+            slot,
+            hash,
+            domain_hash: Self::domain_hash(slot, &hash),
+        }
+    }
+
+    pub fn domain_hash(slot: u64, hash: &Hash) -> Hash {
+        hashv(&[VOTE_DOMAIN_SEPARATOR, &slot.to_le_bytes(), hash.as_ref()])
+    }
+
+    pub fn has_valid_domain(&self) -> bool {
+        self.domain_hash == Self::domain_hash(self.slot, &self.hash)
     }
 }
 
@@ -127,64 +168,85 @@ impl VoteState {
         }
     }
 
-    pub fn process_votes(&mut self, votes: &[Vote], slot_hashes: &[(u64, Hash)]) {
-        votes.iter().for_each(|v| self.process_vote(v, slot_hashes));
+    pub fn process_votes(
+        &mut self,
+        votes: &[Vote],
+        slot_hashes: &[(u64, Hash)],
+    ) -> Result<(), VoteError> {
+        votes
+            .iter()
+            .try_for_each(|v| self.process_vote(v, slot_hashes))
     }
 
-    pub fn process_vote(&mut self, vote: &Vote, slot_hashes: &[(u64, Hash)]) {
+    /// Apply `vote` if `slot_hashes` hold its slot and bank hash. Votes for
+    /// slots older than any in `slot_hashes` can't be checked and are dropped, votes for any
+    /// other slot missing from them or with a different hash were cast on another fork.
+    pub fn process_vote(
+        &mut self,
+        vote: &Vote,
+        slot_hashes: &[(u64, Hash)],
+    ) -> Result<(), VoteError> {
+        if !vote.has_valid_domain() {
+            return Err(VoteError::InvalidDomain);
+        }
+
         // Ignore votes for slots earlier than we already have votes for
-        if self
-            .votes
-            .back()
-            .map_or(false, |old_vote| old_vote.slot >= vote.slot)
-        {
-            return;
+        if self.is_stale(vote) {
+            return Ok(());
         }
 
-        // drop votes for which there is no matching slot and hash
-        if !slot_hashes
-            .iter()
-            .any(|(slot, hash)| vote.slot == *slot && vote.hash == *hash)
-        {
-            if log_enabled!(log::Level::Warn) {
-                for (slot, hash) in slot_hashes {
-                    if vote.slot == *slot {
-                        // warn!(
-                        //     "dropped vote {:?} matched slot {}, but not hash {:?}",
-                        //     vote, *slot, *hash
-                        // );
-                        println!(
-                            "{}",
-                            Warn(
-                                format!("dropped vote {:?} matched slot {}, but not hash {:?}",
-                                    vote, *slot, *hash).to_string(),
-                                module_path!().to_string()
-                            )
-                        );
-                    }
-                    if vote.hash == *hash {
-                        // warn!(
-                        //     "dropped vote {:?} matched hash {:?}, but not slot {}",
-                        //     vote, *hash, *slot,
-                        // );
-                        println!(
-                            "{}",
-                            Warn(
-                                format!("dropped vote {:?} matched hash {:?}, but not slot {}",
-                                    vote, *hash, *slot).to_string(),
-                                module_path!().to_string()
-                            )
-                        );
-                    }
+        match slot_hashes.iter().find(|(slot, _)| vote.slot == *slot) {
+            Some((_, hash)) if vote.hash == *hash => (),
+            Some((slot, hash)) => {
+                println!(
+                    "{}",
+                    Warn(
+                        format!(
+                            "rejected vote {:?} matched slot {}, but not hash {:?}",
+                            vote, *slot, *hash
+                        )
+                        .to_string(),
+                        module_path!().to_string()
+                    )
+                );
+                return Err(VoteError::WrongFork);
+            }
+            None => {
+                let oldest_slot = slot_hashes.iter().map(|(slot, _)| *slot).min();
+                if oldest_slot.map_or(true, |oldest_slot| vote.slot < oldest_slot) {
+                    println!(
+                        "{}",
+                        Warn(
+                            format!("dropped vote {:?} older than the slot hashes", vote)
+                                .to_string(),
+                            module_path!().to_string()
+                        )
+                    );
+                    return Ok(());
                 }
+                println!(
+                    "{}",
+                    Warn(
+                        format!("rejected vote {:?} for a slot not on this fork", vote).to_string(),
+                        module_path!().to_string()
+                    )
+                );
+                return Err(VoteError::WrongFork);
             }
-            return;
         }
 
-        let vote = Lockout::new(&vote);
+        self.apply_vote(vote);
+        Ok(())
+    }
 
-        // TODO: Integrity checks
-        // Verify the vote's bank hash matches what is expected
+    fn is_stale(&self, vote: &Vote) -> bool {
+        self.votes
+            .back()
+            .map_or(false, |old_vote| old_vote.slot >= vote.slot)
+    }
+
+    fn apply_vote(&mut self, vote: &Vote) {
+        let vote = Lockout::new(vote);
 
         self.pop_expired_votes(vote.slot);
         // Once the stack is full, pop the oldest vote and distribute rewards
@@ -197,8 +259,12 @@ impl VoteState {
         self.double_lockouts();
     }
 
+    /// Apply `vote` without checking its domain or bank hash, for votes this node casts or
+    /// has already checked
     pub fn process_vote_unchecked(&mut self, vote: &Vote) {
-        self.process_vote(vote, &[(vote.slot, vote.hash)]);
+        if !self.is_stale(vote) {
+            self.apply_vote(vote);
+        }
     }
     pub fn process_slot_vote_unchecked(&mut self, slot: u64) {
         self.process_vote_unchecked(&Vote::new(slot, Hash::default()));
@@ -313,7 +379,9 @@ pub fn process_votes(
         return Err(InstructionError::MissingRequiredSignature);
     }
 
-    vote_state.process_votes(&votes, &slot_hashes);
+    vote_state
+        .process_votes(&votes, &slot_hashes)
+        .map_err(|err| InstructionError::CustomError(err as u32))?;
     vote_account.set_state(&vote_state)
 }
 
@@ -482,16 +550,28 @@ mod tests {
         let vote = Vote::new(0, hash);
 
         // wrong hash
-        let vote_state = simulate_process_vote(
-            &vote_pubkey,
-            &mut vote_account,
-            &vote,
-            &[(0, Hash::default())],
-        )
-        .unwrap();
-        assert_eq!(vote_state.votes.len(), 0);
+        assert_eq!(
+            simulate_process_vote(
+                &vote_pubkey,
+                &mut vote_account,
+                &vote,
+                &[(0, Hash::default())],
+            ),
+            Err(InstructionError::CustomError(VoteError::WrongFork as u32))
+        );
+
+        // slot missing from this fork
+        assert_eq!(
+            simulate_process_vote(
+                &vote_pubkey,
+                &mut vote_account,
+                &Vote::new(1, hash),
+                &[(2, hash), (0, hash)],
+            ),
+            Err(InstructionError::CustomError(VoteError::WrongFork as u32))
+        );
 
-        // wrong slot
+        // slot older than the slot hashes kept
         let vote_state =
             simulate_process_vote(&vote_pubkey, &mut vote_account, &vote, &[(1, hash)]).unwrap();
         assert_eq!(vote_state.votes.len(), 0);
@@ -515,6 +595,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_vote_domain() {
+        let (vote_pubkey, mut vote_account) = create_test_account();
+
+        let hash = hash(&[0u8]);
+        let vote = Vote::new(1, hash);
+        assert!(vote.has_valid_domain());
+        assert_ne!(Vote::domain_hash(1, &hash), Vote::domain_hash(2, &hash));
+        assert_ne!(Vote::domain_hash(1, &hash), hash);
+
+        // a vote whose domain hash covers a different bank hash
+        let mut forged_vote = vote.clone();
+        forged_vote.hash = Hash::default();
+        assert!(!forged_vote.has_valid_domain());
+        assert_eq!(
+            simulate_process_vote(
+                &vote_pubkey,
+                &mut vote_account,
+                &forged_vote,
+                &[(1, Hash::default())],
+            ),
+            Err(InstructionError::CustomError(
+                VoteError::InvalidDomain as u32
+            ))
+        );
+
+        let vote_state =
+            simulate_process_vote(&vote_pubkey, &mut vote_account, &vote, &[(1, hash)]).unwrap();
+        assert_eq!(vote_state.votes, vec![Lockout::new(&vote)]);
+    }
+
     #[test]
     fn test_vote_signature() {
         let (vote_pubkey, mut vote_account) = create_test_account();
@@ -781,8 +892,8 @@ mod tests {
             .collect();
         let slot_hashes: Vec<_> = votes.iter().map(|vote| (vote.slot, vote.hash)).collect();
 
-        vote_state_a.process_votes(&votes, &slot_hashes);
-        vote_state_b.process_votes(&votes, &slot_hashes);
+        vote_state_a.process_votes(&votes, &slot_hashes).unwrap();
+        vote_state_b.process_votes(&votes, &slot_hashes).unwrap();
         assert_eq!(recent_votes(&vote_state_a), recent_votes(&vote_state_b));
     }
 
@@ -16,6 +16,8 @@ use morgan::packet::to_packets_chunked;
 use morgan::waterClockRecorder::WorkingBankEntries;
 use morgan::service::Service;
 use morgan::testTx::test_tx;
+use morgan::transactionPolicy::{AllowAll, TransactionPolicyConfig};
+use morgan::transactionTrace::TransactionTraces;
 use morgan_runtime::bank::Bank;
 use morgan_interface::hash::hash;
 use morgan_interface::pubkey::Pubkey;
@@ -74,8 +76,13 @@ fn bench_consume_buffered(bencher: &mut Bencher) {
         // This tests the performance of buffering packets.
         // If the packet buffers are copied, performance will be poor.
         bencher.iter(move || {
-            let _ignored =
-                BankingStage::consume_buffered_packets(&my_pubkey, &poh_recorder, &mut packets);
+            let _ignored = BankingStage::consume_buffered_packets(
+                &my_pubkey,
+                &poh_recorder,
+                &mut packets,
+                &TransactionTraces::default(),
+                &AllowAll,
+            );
         });
 
         exit.store(true, Ordering::Relaxed);
@@ -102,7 +109,6 @@ fn bench_banking_stage_multi_accounts(bencher: &mut Bencher) {
     genesis_block.ticks_per_slot = 10_000;
 
     let (verified_sender, verified_receiver) = channel();
-    let (vote_sender, vote_receiver) = channel();
     let bank = Arc::new(Bank::new(&genesis_block));
     let to_pubkey = Pubkey::new_rand();
     let dummy = system_transaction::transfer(&mint_keypair, &to_pubkey, 1, genesis_block.hash());
@@ -162,8 +168,9 @@ fn bench_banking_stage_multi_accounts(bencher: &mut Bencher) {
         let _banking_stage = BankingStage::new(
             &cluster_info,
             &poh_recorder,
-            verified_receiver,
-            vote_receiver,
+            verified_receiver.into(),
+            &Arc::new(TransactionTraces::default()),
+            &TransactionPolicyConfig::default().build(),
         );
         poh_recorder.lock().unwrap().set_bank(&bank);
 
@@ -191,7 +198,6 @@ fn bench_banking_stage_multi_accounts(bencher: &mut Bencher) {
             start += half_len;
             start %= verified.len();
         });
-        drop(vote_sender);
         exit.store(true, Ordering::Relaxed);
         poh_service.join().unwrap();
     }
@@ -213,7 +219,6 @@ fn bench_banking_stage_multi_programs(bencher: &mut Bencher) {
     } = create_genesis_block(mint_total);
 
     let (verified_sender, verified_receiver) = channel();
-    let (vote_sender, vote_receiver) = channel();
     let bank = Arc::new(Bank::new(&genesis_block));
     let to_pubkey = Pubkey::new_rand();
     let dummy = system_transaction::transfer(&mint_keypair, &to_pubkey, 1, genesis_block.hash());
@@ -288,8 +293,9 @@ fn bench_banking_stage_multi_programs(bencher: &mut Bencher) {
         let _banking_stage = BankingStage::new(
             &cluster_info,
             &poh_recorder,
-            verified_receiver,
-            vote_receiver,
+            verified_receiver.into(),
+            &Arc::new(TransactionTraces::default()),
+            &TransactionPolicyConfig::default().build(),
         );
         poh_recorder.lock().unwrap().set_bank(&bank);
 
@@ -314,7 +320,6 @@ fn bench_banking_stage_multi_programs(bencher: &mut Bencher) {
             start += half_len;
             start %= verified.len();
         });
-        drop(vote_sender);
         exit.store(true, Ordering::Relaxed);
         poh_service.join().unwrap();
     }
@@ -0,0 +1,106 @@
+#![feature(test)]
+
+extern crate test;
+
+use morgan::connectionInfo::ContactInfo;
+use morgan::connectionInfoTable::Crds;
+use morgan::propagationValue::{CrdsValue, CrdsValueLabel};
+use morgan_interface::pubkey::Pubkey;
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+use test::Bencher;
+
+const NUM_WRITERS: usize = 4;
+const NUM_READERS: usize = 4;
+
+fn new_values(num: usize) -> Vec<CrdsValue> {
+    (0..num)
+        .map(|_| CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0)))
+        .collect()
+}
+
+// every writer inserts its own chunk while the readers look up the whole set
+fn run_contended<W, R>(values: &[CrdsValue], write: W, read: R)
+where
+    W: Fn(Vec<CrdsValue>) + Send + Sync + 'static,
+    R: Fn(&CrdsValueLabel) + Send + Sync + 'static,
+{
+    let write = Arc::new(write);
+    let read = Arc::new(read);
+    let labels: Arc<Vec<_>> = Arc::new(values.iter().map(CrdsValue::label).collect());
+    let mut threads: Vec<JoinHandle<()>> = values
+        .chunks(values.len() / NUM_WRITERS)
+        .map(|chunk| {
+            let chunk = chunk.to_vec();
+            let write = write.clone();
+            thread::spawn(move || {
+                for value in chunk {
+                    write(vec![value]);
+                }
+            })
+        })
+        .collect();
+    threads.extend((0..NUM_READERS).map(|_| {
+        let labels = labels.clone();
+        let read = read.clone();
+        thread::spawn(move || labels.iter().for_each(|label| read(label)))
+    }));
+    threads.into_iter().for_each(|t| t.join().unwrap());
+}
+
+#[bench]
+fn bench_crds_insert(bencher: &mut Bencher) {
+    let values = new_values(4096);
+    bencher.iter(|| {
+        let crds = Crds::default();
+        for value in values.clone() {
+            let _ = crds.insert(value, 0);
+        }
+    })
+}
+
+#[bench]
+fn bench_crds_insert_batch(bencher: &mut Bencher) {
+    let values = new_values(4096);
+    bencher.iter(|| {
+        let crds = Crds::default();
+        let _ = crds.insert_batch(values.clone(), 0);
+    })
+}
+
+// baseline: the whole table behind a single lock, as gossip held it before sharding
+#[bench]
+fn bench_crds_contended_single_lock(bencher: &mut Bencher) {
+    let values = new_values(4096);
+    bencher.iter(|| {
+        let crds = Arc::new(RwLock::new(Crds::default()));
+        let reader = crds.clone();
+        run_contended(
+            &values,
+            move |batch| {
+                let _ = crds.write().unwrap().insert_batch(batch, 0);
+            },
+            move |label| {
+                test::black_box(reader.read().unwrap().lookup(label));
+            },
+        );
+    })
+}
+
+#[bench]
+fn bench_crds_contended_sharded(bencher: &mut Bencher) {
+    let values = new_values(4096);
+    bencher.iter(|| {
+        let crds = Arc::new(Crds::default());
+        let reader = crds.clone();
+        run_contended(
+            &values,
+            move |batch| {
+                let _ = crds.insert_batch(batch, 0);
+            },
+            move |label| {
+                test::black_box(reader.lookup(label));
+            },
+        );
+    })
+}
@@ -4,7 +4,8 @@ extern crate test;
 
 use morgan::packet::to_packets;
 use morgan::signatureVerify;
-use morgan::testTx::test_tx;
+use morgan::testTx::{test_tx, TestTxGenerator};
+use morgan_interface::hash::Hash;
 use test::Bencher;
 
 #[bench]
@@ -16,6 +17,30 @@ fn bench_sigverify(bencher: &mut Bencher) {
 
     // verify packets
     bencher.iter(|| {
-        let _ans = sigverify::ed25519_verify(&batches);
+        let _ans = signatureVerify::ed25519_verify(&batches);
+    })
+}
+
+#[bench]
+fn bench_sigverify_mixed(bencher: &mut Bencher) {
+    let mut generator = TestTxGenerator::new([0u8; 32]);
+    let payers = generator.keypairs(4);
+    let blockhash = Hash::default();
+
+    // transfers, multi-instruction transfers and votes, each signed by its own payers
+    let mut txs = generator.contended_transfer_txs(&payers, 64, 1, blockhash);
+    for payer in &payers {
+        txs.extend((0..8).map(|_| generator.multi_transfer_tx(payer, 4, 1, blockhash)));
+    }
+    for (slot, payer) in payers.iter().enumerate() {
+        let vote_keypair = generator.keypair();
+        txs.extend(
+            (0..8).map(|_| generator.vote_tx(payer, &vote_keypair, &[slot as u64], blockhash)),
+        );
+    }
+    let batches = to_packets(&txs);
+
+    bencher.iter(|| {
+        let _ans = signatureVerify::ed25519_verify(&batches);
     })
 }
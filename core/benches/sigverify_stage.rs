@@ -7,21 +7,19 @@ use log::*;
 use rand::{thread_rng, Rng};
 use morgan::packet::to_packets_chunked;
 use morgan::service::Service;
-use morgan::signatureVerifyStage::SigVerifyStage;
-use morgan::testTx::test_tx;
+use morgan::signatureVerifyStage::{SigVerifyStage, MAX_QUEUED_VERIFIED_BATCHES};
+use morgan::streamer::{queue_channel, MAX_QUEUED_PACKET_BATCHES};
+use morgan::testTx::{test_tx, TestTxGenerator};
 use morgan_interface::hash::Hash;
-use morgan_interface::signature::{Keypair, KeypairUtil};
-use morgan_interface::system_transaction;
 use morgan_interface::timing::duration_as_ms;
-use std::sync::mpsc::channel;
 use std::time::{Duration, Instant};
 use test::Bencher;
 
 #[bench]
 fn bench_sigverify_stage(bencher: &mut Bencher) {
     morgan_logger::setup();
-    let (packet_s, packet_r) = channel();
-    let (verified_s, verified_r) = channel();
+    let (packet_s, packet_r) = queue_channel("bench-packets", MAX_QUEUED_PACKET_BATCHES);
+    let (verified_s, verified_r) = queue_channel("bench-verified", MAX_QUEUED_VERIFIED_BATCHES);
     let sigverify_disabled = false;
     let stage = SigVerifyStage::new(packet_r, sigverify_disabled, verified_s);
 
@@ -33,21 +31,9 @@ fn bench_sigverify_stage(bencher: &mut Bencher) {
         let tx = test_tx();
         to_packets_chunked(&vec![tx; len], chunk_size)
     } else {
-        let from_keypair = Keypair::new();
-        let to_keypair = Keypair::new();
-        let txs: Vec<_> = (0..len)
-            .into_iter()
-            .map(|_| {
-                let amount = thread_rng().gen();
-                let tx = system_transaction::transfer(
-                    &from_keypair,
-                    &to_keypair.pubkey(),
-                    amount,
-                    Hash::default(),
-                );
-                tx
-            })
-            .collect();
+        let mut generator = TestTxGenerator::new(thread_rng().gen());
+        let payers = generator.keypairs(1);
+        let txs = generator.contended_transfer_txs(&payers, len, 1, Hash::default());
         to_packets_chunked(&txs, chunk_size)
     };
 
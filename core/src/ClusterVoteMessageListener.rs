@@ -1,17 +1,228 @@
 use crate::clusterMessage::{ClusterInfo, GOSSIP_SLEEP_MILLIS};
-use crate::waterClockRecorder::PohRecorder;
+use crate::entryInfo::{self, Entry};
+use crate::packet;
+use crate::waterClockRecorder::{PohRecorder, MAX_RECORD_TRANSACTIONS};
 use crate::result::Result;
+use crate::rpcSubscriptions::RpcSubscriptions;
 use crate::service::Service;
-use crate::signatureVerifyStage::VerifiedPackets;
-use crate::{packet, signatureVerify};
+use crate::treasuryStage::BankingStage;
+use bincode::deserialize;
+use hashbrown::HashMap;
 use morgan_metricbot::inc_new_counter_debug;
+use morgan_runtime::bank::Bank;
+use morgan_runtime::stakes::VoteAccounts;
+use morgan_interface::account::Account;
+use morgan_interface::hash::Hash;
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::timing::timestamp;
+use morgan_interface::transaction::Transaction;
+use morgan_vote_api::vote_instruction::VoteInstruction;
+use morgan_vote_api::vote_state::{Vote, VoteState};
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::cmp;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread::{self, sleep, Builder, JoinHandle};
 use std::time::Duration;
 use morgan_helper::logHelper::*;
 
+/// Votes further than this past the root are ignored, bounding the tracker's memory
+pub const MAX_TRACKED_SLOTS_AHEAD: u64 = 4096;
+
+/// Votes observed in gossip, used by ReplayStage to find the slots the cluster has already
+/// confirmed and by RepairService to find the slots worth repairing first. Votes are recorded
+/// unweighted; stake is applied when a bank asks.
+#[derive(Default)]
+pub struct VoteTracker {
+    root: u64,
+    /// slot -> voted bank hash -> vote account -> key that signed the vote
+    votes: HashMap<u64, HashMap<Hash, HashMap<Pubkey, Pubkey>>>,
+}
+
+/// The (vote account, signer of the authorized voter, votes) of each vote instruction in `tx`
+fn parse_vote_transaction(tx: &Transaction) -> Vec<(Pubkey, Pubkey, Vec<Vote>)> {
+    let message = tx.message();
+    let num_signers = message.header.num_required_signatures as usize;
+    let signer_key = |index: Option<&u8>| {
+        index
+            .map(|index| *index as usize)
+            .filter(|index| *index < num_signers)
+            .and_then(|index| message.account_keys.get(index))
+    };
+    message
+        .instructions
+        .iter()
+        .filter_map(|instruction| {
+            let program_id = message
+                .account_keys
+                .get(instruction.program_ids_index as usize);
+            if program_id != Some(&morgan_vote_api::id()) {
+                return None;
+            }
+            let votes = match deserialize(&instruction.data) {
+                Ok(VoteInstruction::Vote(votes)) => votes,
+                _ => return None,
+            };
+            // accounts are [sender, vote account, slot_hashes, authorized voter]; the last
+            // one is only present when the vote account doesn't sign for itself
+            let vote_pubkey = instruction
+                .accounts
+                .get(1)
+                .and_then(|index| message.account_keys.get(*index as usize))?;
+            let signer = signer_key(instruction.accounts.get(1))
+                .or_else(|| signer_key(instruction.accounts.get(3)))?;
+            Some((*vote_pubkey, *signer, votes))
+        })
+        .collect()
+}
+
+impl VoteTracker {
+    /// Record the votes carried by a transaction whose signatures have been verified
+    pub fn record_vote_transaction(&mut self, tx: &Transaction) {
+        for (vote_pubkey, signer, votes) in parse_vote_transaction(tx) {
+            for vote in votes {
+                if vote.slot <= self.root
+                    || vote.slot > self.root + MAX_TRACKED_SLOTS_AHEAD
+                    || !vote.has_valid_domain()
+                {
+                    continue;
+                }
+                self.votes
+                    .entry(vote.slot)
+                    .or_insert_with(HashMap::new)
+                    .entry(vote.hash)
+                    .or_insert_with(HashMap::new)
+                    .insert(vote_pubkey, signer);
+            }
+        }
+    }
+
+    pub fn root(&self) -> u64 {
+        self.root
+    }
+
+    /// Forget votes for slots at or below the new root
+    pub fn set_root(&mut self, root: u64) {
+        self.root = root;
+        self.votes.retain(|slot, _| *slot > root);
+    }
+
+    /// The bank hash at `slot` voted for by a supermajority of the epoch stake known to `bank`.
+    /// Votes only count if signed by the vote account's authorized voter.
+    pub fn confirmed_hash(&self, slot: u64, bank: &Bank) -> Option<Hash> {
+        let slot_votes = self.votes.get(&slot)?;
+        let (epoch, _) = bank.get_epoch_and_slot_index(slot);
+        let vote_accounts = bank.epoch_vote_accounts(epoch)?;
+        let total_stake: u64 = vote_accounts.values().map(|(stake, _)| *stake).sum();
+        let supermajority_stake = total_stake * 2 / 3;
+
+        slot_votes
+            .iter()
+            .find(|(_, voters)| Self::voter_stake(voters, vote_accounts) > supermajority_stake)
+            .map(|(hash, _)| *hash)
+    }
+
+    /// Slots past the root that staked validators voted for, ordered by the stake behind them,
+    /// most first. These are the slots the cluster is building on.
+    pub fn voted_slots_by_stake(&self, bank: &Bank) -> Vec<u64> {
+        let mut slot_stakes: Vec<_> = self
+            .votes
+            .iter()
+            .filter_map(|(slot, slot_votes)| {
+                let (epoch, _) = bank.get_epoch_and_slot_index(*slot);
+                let vote_accounts = bank.epoch_vote_accounts(epoch)?;
+                let stake: u64 = slot_votes
+                    .values()
+                    .map(|voters| Self::voter_stake(voters, vote_accounts))
+                    .sum();
+                Some((*slot, stake)).filter(|(_, stake)| *stake > 0)
+            })
+            .collect();
+        slot_stakes.sort_by(|(slot_a, stake_a), (slot_b, stake_b)| {
+            stake_b.cmp(stake_a).then(slot_a.cmp(slot_b))
+        });
+        slot_stakes.into_iter().map(|(slot, _)| slot).collect()
+    }
+
+    /// Stake of the `voters` whose votes were signed by their vote account's authorized voter
+    fn voter_stake(voters: &HashMap<Pubkey, Pubkey>, vote_accounts: &VoteAccounts) -> u64 {
+        voters
+            .iter()
+            .filter_map(|(vote_pubkey, signer)| {
+                let (stake, account) = vote_accounts.get(vote_pubkey)?;
+                VoteState::deserialize(&account.data)
+                    .ok()
+                    .filter(|vote_state| vote_state.authorized_voter_pubkey == *signer)
+                    .map(|_| *stake)
+            })
+            .sum()
+    }
+
+    /// Slots at or after `start_slot` that a supermajority voted for
+    pub fn confirmed_slots(&self, start_slot: u64, bank: &Bank) -> Vec<u64> {
+        self.votes
+            .keys()
+            .filter(|slot| **slot >= start_slot)
+            .filter(|slot| self.confirmed_hash(**slot, bank).is_some())
+            .cloned()
+            .collect()
+    }
+}
+
+/// Verified gossip votes waiting to be recorded while this node is leader, at most one per vote
+/// account and slot. Votes for different slots are all kept, since they may be on different
+/// forks and a validator's newest vote only carries the lockouts of its own fork.
+#[derive(Default)]
+pub struct VoteBatch {
+    root: u64,
+    /// (vote account, slot voted on) -> vote transaction
+    votes: HashMap<(Pubkey, u64), Transaction>,
+}
+
+impl VoteBatch {
+    /// Add a verified vote unless one from the same vote account for the same slot is already
+    /// batched. Returns false if the vote was a duplicate, outside the tracked slots, or isn't
+    /// a vote.
+    pub fn add(&mut self, tx: Transaction) -> bool {
+        let key = match parse_vote_transaction(&tx)
+            .into_iter()
+            .find_map(|(vote_pubkey, _, votes)| Some((vote_pubkey, votes.last()?.slot)))
+        {
+            Some(key) => key,
+            None => return false,
+        };
+        let (_, slot) = key;
+        if slot <= self.root || slot > self.root + MAX_TRACKED_SLOTS_AHEAD {
+            return false;
+        }
+        if self.votes.contains_key(&key) {
+            return false;
+        }
+        self.votes.insert(key, tx);
+        true
+    }
+
+    /// Forget votes for slots at or below the new root
+    pub fn set_root(&mut self, root: u64) {
+        self.root = root;
+        self.votes.retain(|(_, slot), _| *slot > root);
+    }
+
+    pub fn len(&self) -> usize {
+        self.votes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.votes.is_empty()
+    }
+
+    /// Take the batched votes, leaving the batch empty
+    pub fn drain(&mut self) -> Vec<Transaction> {
+        self.votes.drain().map(|(_, tx)| tx).collect()
+    }
+}
+
 pub struct ClusterInfoVoteListener {
     thread_hdls: Vec<JoinHandle<()>>,
 }
@@ -21,11 +232,14 @@ impl ClusterInfoVoteListener {
         exit: &Arc<AtomicBool>,
         cluster_info: Arc<RwLock<ClusterInfo>>,
         sigverify_disabled: bool,
-        sender: Sender<VerifiedPackets>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
+        vote_tracker: &Arc<RwLock<VoteTracker>>,
+        subscriptions: &Arc<RpcSubscriptions>,
     ) -> Self {
         let exit = exit.clone();
         let poh_recorder = poh_recorder.clone();
+        let vote_tracker = vote_tracker.clone();
+        let subscriptions = subscriptions.clone();
         let thread = Builder::new()
             .name("morgan-cluster_info_vote_listener".to_string())
             .spawn(move || {
@@ -33,8 +247,9 @@ impl ClusterInfoVoteListener {
                     exit,
                     &cluster_info,
                     sigverify_disabled,
-                    &sender,
                     poh_recorder,
+                    &vote_tracker,
+                    &subscriptions,
                 );
             })
             .unwrap();
@@ -46,31 +261,118 @@ impl ClusterInfoVoteListener {
         exit: Arc<AtomicBool>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         sigverify_disabled: bool,
-        sender: &Sender<VerifiedPackets>,
         poh_recorder: Arc<Mutex<PohRecorder>>,
+        vote_tracker: &RwLock<VoteTracker>,
+        subscriptions: &RpcSubscriptions,
     ) -> Result<()> {
         let mut last_ts = 0;
+        let mut vote_batch = VoteBatch::default();
         loop {
             if exit.load(Ordering::Relaxed) {
                 return Ok(());
             }
             let (votes, new_ts) = cluster_info.read().unwrap().get_votes(last_ts);
-            if poh_recorder.lock().unwrap().bank().is_some() {
-                last_ts = new_ts;
-                inc_new_counter_debug!("cluster_info_vote_listener-recv_count", votes.len());
-                let msgs = packet::to_packets(&votes);
-                if !msgs.is_empty() {
-                    let r = if sigverify_disabled {
-                        signatureVerify::ed25519_verify_disabled(&msgs)
-                    } else {
-                        signatureVerify::ed25519_verify_cpu(&msgs)
-                    };
-                    sender.send(msgs.into_iter().zip(r).collect())?;
+            last_ts = new_ts;
+            inc_new_counter_debug!("cluster_info_vote_listener-recv_count", votes.len());
+            let votes = Self::verify_votes(votes, sigverify_disabled);
+            Self::track_votes(&votes, vote_tracker);
+            Self::notify_votes(&votes, subscriptions);
+            vote_batch.set_root(vote_tracker.read().unwrap().root());
+            let duplicates = votes
+                .into_iter()
+                .map(|vote| vote_batch.add(vote))
+                .filter(|added| !added)
+                .count();
+            inc_new_counter_debug!("cluster_info_vote_listener-duplicate_count", duplicates);
+
+            if !vote_batch.is_empty() {
+                let bank = poh_recorder.lock().unwrap().bank();
+                if let Some(bank) = bank {
+                    Self::record_votes(&bank, &mut vote_batch, &poh_recorder);
                 }
             }
             sleep(Duration::from_millis(GOSSIP_SLEEP_MILLIS));
         }
     }
+
+    /// Drop the votes that fail signature verification
+    fn verify_votes(votes: Vec<Transaction>, sigverify_disabled: bool) -> Vec<Transaction> {
+        if sigverify_disabled {
+            return votes;
+        }
+        votes
+            .into_par_iter()
+            .filter(|vote| vote.verify_signatures())
+            .collect()
+    }
+
+    /// Record the batched votes in the leader's working bank, packing as many validators' votes
+    /// into each entry as fit in a blob. The votes were verified once when they came in from
+    /// gossip, so they skip sigverify and the banking stage's packet queues. Votes that
+    /// couldn't be recorded, because their accounts were in use or the slot ended, stay batched.
+    fn record_votes(
+        bank: &Bank,
+        vote_batch: &mut VoteBatch,
+        poh_recorder: &Arc<Mutex<PohRecorder>>,
+    ) {
+        let votes = vote_batch.drain();
+        inc_new_counter_debug!("cluster_info_vote_listener-batch_size", votes.len());
+        let mut chunk_start = 0;
+        let mut unprocessed = vec![];
+        while chunk_start != votes.len() {
+            let chunk_max = cmp::min(votes.len(), chunk_start + MAX_RECORD_TRANSACTIONS);
+            let chunk_end = chunk_start
+                + entryInfo::num_will_fit(
+                    &votes[chunk_start..chunk_max],
+                    packet::BLOB_DATA_SIZE as u64,
+                    &Entry::serialized_to_blob_size,
+                );
+            let (result, unprocessed_in_chunk) = BankingStage::process_and_record_transactions(
+                bank,
+                &votes[chunk_start..chunk_end],
+                poh_recorder,
+                chunk_start,
+            );
+            unprocessed.extend(unprocessed_in_chunk);
+            if result.is_err() {
+                unprocessed.extend(chunk_start..votes.len());
+                break;
+            }
+            inc_new_counter_debug!(
+                "cluster_info_vote_listener-entry_votes",
+                chunk_end - chunk_start
+            );
+            chunk_start = chunk_end;
+        }
+        for index in unprocessed {
+            vote_batch.add(votes[index].clone());
+        }
+    }
+
+    /// Record verified votes in the tracker
+    fn track_votes(votes: &[Transaction], vote_tracker: &RwLock<VoteTracker>) {
+        if votes.is_empty() {
+            return;
+        }
+        inc_new_counter_debug!("cluster_info_vote_listener-tracked_count", votes.len());
+        let mut w_vote_tracker = vote_tracker.write().unwrap();
+        for vote in votes {
+            w_vote_tracker.record_vote_transaction(vote);
+        }
+    }
+
+    /// Send each verified vote to the subscribers of its vote account, a vote transaction is
+    /// reported by the newest of the votes it carries
+    fn notify_votes(votes: &[Transaction], subscriptions: &RpcSubscriptions) {
+        let now = timestamp();
+        for vote in votes {
+            for (vote_pubkey, _, votes) in parse_vote_transaction(vote) {
+                if let Some(newest) = votes.last() {
+                    subscriptions.notify_vote(&vote_pubkey, newest, now);
+                }
+            }
+        }
+    }
 }
 
 impl Service for ClusterInfoVoteListener {
@@ -86,13 +388,23 @@ impl Service for ClusterInfoVoteListener {
 
 #[cfg(test)]
 mod tests {
+    use super::{ClusterInfoVoteListener, VoteBatch, VoteTracker, MAX_TRACKED_SLOTS_AHEAD};
+    use crate::blockBufferPool::{get_tmp_ledger_path, Blocktree};
     use crate::forkSelection::MAX_RECENT_VOTES;
+    use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
+    use crate::leaderArrangeCache::LeaderScheduleCache;
     use crate::packet;
+    use crate::waterClockRecorder::{PohRecorder, WorkingBank};
+    use crate::{get_tmp_ledger_path, tmp_ledger_name};
+    use morgan_runtime::bank::Bank;
     use morgan_interface::hash::Hash;
+    use morgan_interface::poh_config::PohConfig;
+    use morgan_interface::pubkey::Pubkey;
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::transaction::Transaction;
     use morgan_vote_api::vote_instruction;
     use morgan_vote_api::vote_state::Vote;
+    use std::sync::{Arc, Mutex};
     use morgan_helper::logHelper::*;
 
     #[test]
@@ -127,4 +439,205 @@ mod tests {
 
         assert_eq!(msgs.len(), 1);
     }
+
+    fn new_vote_tx(vote_keypair: &Keypair, signer: &Keypair, slot: u64, hash: Hash) -> Transaction {
+        let node_keypair = Keypair::new();
+        let vote_ix = vote_instruction::vote(
+            &node_keypair.pubkey(),
+            &vote_keypair.pubkey(),
+            &signer.pubkey(),
+            vec![Vote::new(slot, hash)],
+        );
+        let mut vote_tx = Transaction::new_unsigned_instructions(vec![vote_ix]);
+        vote_tx.partial_sign(&[&node_keypair], Hash::default());
+        vote_tx.partial_sign(&[signer], Hash::default());
+        vote_tx
+    }
+
+    #[test]
+    fn test_vote_tracker_confirmed_hash() {
+        let GenesisBlockInfo {
+            genesis_block,
+            voting_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let hash = Hash::new(&[1; 32]);
+        let mut vote_tracker = VoteTracker::default();
+
+        // a vote signed by someone other than the authorized voter doesn't count
+        vote_tracker.record_vote_transaction(&new_vote_tx(
+            &voting_keypair,
+            &Keypair::new(),
+            1,
+            hash,
+        ));
+        assert_eq!(vote_tracker.confirmed_hash(1, &bank), None);
+
+        // the bootstrap leader holds all the stake
+        vote_tracker.record_vote_transaction(&new_vote_tx(
+            &voting_keypair,
+            &voting_keypair,
+            1,
+            hash,
+        ));
+        assert_eq!(vote_tracker.confirmed_hash(1, &bank), Some(hash));
+        assert_eq!(vote_tracker.confirmed_slots(0, &bank), vec![1]);
+        assert!(vote_tracker.confirmed_slots(2, &bank).is_empty());
+
+        // unstaked vote accounts don't count
+        let unstaked = Keypair::new();
+        vote_tracker.record_vote_transaction(&new_vote_tx(&unstaked, &unstaked, 2, hash));
+        assert_eq!(vote_tracker.confirmed_hash(2, &bank), None);
+
+        vote_tracker.set_root(1);
+        assert_eq!(vote_tracker.confirmed_hash(1, &bank), None);
+        vote_tracker.record_vote_transaction(&new_vote_tx(
+            &voting_keypair,
+            &voting_keypair,
+            1,
+            hash,
+        ));
+        assert_eq!(vote_tracker.confirmed_hash(1, &bank), None);
+    }
+
+    #[test]
+    fn test_vote_tracker_voted_slots_by_stake() {
+        let GenesisBlockInfo {
+            genesis_block,
+            voting_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let mut vote_tracker = VoteTracker::default();
+        assert!(vote_tracker.voted_slots_by_stake(&bank).is_empty());
+
+        let unstaked = Keypair::new();
+        let hash = Hash::default();
+        vote_tracker.record_vote_transaction(&new_vote_tx(&unstaked, &unstaked, 2, hash));
+        assert!(vote_tracker.voted_slots_by_stake(&bank).is_empty());
+
+        // slots with equal stake behind them come lowest first
+        for slot in &[3, 1] {
+            vote_tracker.record_vote_transaction(&new_vote_tx(
+                &voting_keypair,
+                &voting_keypair,
+                *slot,
+                hash,
+            ));
+        }
+        assert_eq!(vote_tracker.voted_slots_by_stake(&bank), vec![1, 3]);
+
+        vote_tracker.set_root(1);
+        assert_eq!(vote_tracker.voted_slots_by_stake(&bank), vec![3]);
+    }
+
+    #[test]
+    fn test_vote_batch() {
+        let vote_keypair = Keypair::new();
+        let other_vote_keypair = Keypair::new();
+        let vote1 = new_vote_tx(&vote_keypair, &vote_keypair, 1, Hash::default());
+        let vote2 = new_vote_tx(&vote_keypair, &vote_keypair, 2, Hash::default());
+        let other_vote = new_vote_tx(&other_vote_keypair, &other_vote_keypair, 1, Hash::default());
+
+        let mut vote_batch = VoteBatch::default();
+        assert!(vote_batch.add(vote1.clone()));
+        assert!(vote_batch.add(other_vote.clone()));
+        // votes for other slots are kept, they may be on another fork
+        assert!(vote_batch.add(vote2.clone()));
+        assert!(!vote_batch.add(vote1.clone()));
+        assert!(!vote_batch.add(Transaction::new_unsigned_instructions(vec![])));
+        assert_eq!(vote_batch.len(), 3);
+
+        let mut votes = vote_batch.drain();
+        votes.sort_by_key(|vote| vote.signatures[0]);
+        let mut expected = vec![vote1.clone(), vote2.clone(), other_vote];
+        expected.sort_by_key(|vote| vote.signatures[0]);
+        assert_eq!(votes, expected);
+        assert!(vote_batch.is_empty());
+
+        // votes at or below the root are dropped
+        assert!(vote_batch.add(vote1));
+        assert!(vote_batch.add(vote2.clone()));
+        vote_batch.set_root(1);
+        assert_eq!(vote_batch.drain(), vec![vote2]);
+        let far_vote = new_vote_tx(
+            &vote_keypair,
+            &vote_keypair,
+            2 + MAX_TRACKED_SLOTS_AHEAD,
+            Hash::default(),
+        );
+        assert!(!vote_batch.add(far_vote));
+    }
+    #[test]
+    fn test_record_votes() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Arc::new(Bank::new(&genesis_block));
+        // votes from validators whose node accounts can pay for them
+        let mut votes: Vec<_> = (0..4)
+            .map(|_| {
+                let node_keypair = Keypair::new();
+                let vote_keypair = Keypair::new();
+                bank.transfer(1, &mint_keypair, &node_keypair.pubkey()).unwrap();
+                let vote_ix = vote_instruction::vote(
+                    &node_keypair.pubkey(),
+                    &vote_keypair.pubkey(),
+                    &vote_keypair.pubkey(),
+                    vec![Vote::new(1, Hash::default())],
+                );
+                Transaction::new_signed_instructions(
+                    &[&node_keypair, &vote_keypair],
+                    vec![vote_ix],
+                    genesis_block.hash(),
+                )
+            })
+            .collect();
+        let late_vote = votes.pop().unwrap();
+        let mut vote_batch = VoteBatch::default();
+        for vote in &votes {
+            assert!(vote_batch.add(vote.clone()));
+        }
+
+        let working_bank = WorkingBank {
+            bank: bank.clone(),
+            min_tick_height: bank.tick_height(),
+            max_tick_height: bank.tick_height() + 1,
+        };
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree =
+                Blocktree::open(&ledger_path).expect("Expected to be able to open database ledger");
+            let (poh_recorder, entry_receiver) = PohRecorder::new(
+                bank.tick_height(),
+                bank.last_blockhash(),
+                bank.slot(),
+                Some(4),
+                bank.ticks_per_slot(),
+                &Pubkey::default(),
+                &Arc::new(blocktree),
+                &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+                &Arc::new(PohConfig::default()),
+            );
+            let poh_recorder = Arc::new(Mutex::new(poh_recorder));
+            poh_recorder.lock().unwrap().set_working_bank(working_bank);
+
+            // the validators' votes are recorded together, in a single entry
+            ClusterInfoVoteListener::record_votes(&bank, &mut vote_batch, &poh_recorder);
+            assert!(vote_batch.is_empty());
+            let (_, entries) = entry_receiver.recv().unwrap();
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].0.transactions.len(), votes.len());
+
+            // once the slot is over, the votes stay batched for the next one
+            assert!(vote_batch.add(late_vote.clone()));
+            poh_recorder.lock().unwrap().tick();
+            ClusterInfoVoteListener::record_votes(&bank, &mut vote_batch, &poh_recorder);
+            assert_eq!(vote_batch.drain(), vec![late_vote]);
+        }
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
 }
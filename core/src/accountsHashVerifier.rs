@@ -0,0 +1,258 @@
+//! The `accountsHashVerifier` service hashes the full account state at the first slot rooted
+//! in every epoch, gossips the hash, and compares it against the hash held by a stake-weighted
+//! majority of the nodes that gossiped one for the same slot. A mismatch means this node's
+//! accounts have silently diverged from the cluster's.  The accounts are taken by BankForks
+//! as the slot is rooted and hashed here, off the accounts locks.
+//! Every `DELTA_HASH_CHECK_INTERVAL_SLOTS` roots it also hashes the accounts stored in the
+//! root slot from scratch, to check the hash the accounts store kept up to date for it.
+
+use crate::clusterMessage::ClusterInfo;
+use crate::service::Service;
+use crate::stakingUtils;
+use crate::treasuryForks::BankForks;
+use hashbrown::HashMap;
+use morgan_runtime::bank::Bank;
+use morgan_interface::hash::Hash;
+use morgan_interface::pubkey::Pubkey;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::Duration;
+use morgan_helper::logHelper::*;
+
+const CHECK_INTERVAL_MS: u64 = 1000;
+const DELTA_HASH_CHECK_INTERVAL_SLOTS: u64 = 100;
+
+/// The hash this node computed and is waiting for the cluster to agree on
+#[derive(Debug, PartialEq)]
+struct PendingHash {
+    epoch: u64,
+    slot: u64,
+    hash: Hash,
+}
+
+pub struct AccountsHashVerifier {
+    t_verifier: JoinHandle<()>,
+}
+
+impl AccountsHashVerifier {
+    /// With `halt_on_mismatch` the node exits instead of only reporting a divergence
+    pub fn new(
+        bank_forks: &Arc<RwLock<BankForks>>,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        halt_on_mismatch: bool,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        bank_forks.write().unwrap().capture_epoch_accounts();
+        let bank_forks = bank_forks.clone();
+        let cluster_info = cluster_info.clone();
+        let exit = exit.clone();
+        let t_verifier = Builder::new()
+            .name("morgan-accounts-hash-verifier".to_string())
+            .spawn(move || {
+                let mut pending = None;
+                let mut last_delta_check = 0;
+                while !exit.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(CHECK_INTERVAL_MS));
+                    let root_bank = match Self::root_bank(&bank_forks) {
+                        Some(root_bank) => root_bank,
+                        None => continue,
+                    };
+                    if root_bank.slot() >= last_delta_check + DELTA_HASH_CHECK_INTERVAL_SLOTS {
+                        last_delta_check = root_bank.slot();
+                        if !Self::check_delta_hash(&root_bank) && halt_on_mismatch {
+                            exit.store(true, Ordering::Relaxed);
+                        }
+                    }
+
+                    let epoch_accounts = bank_forks.write().unwrap().take_epoch_accounts();
+                    if let Some(epoch_accounts) = epoch_accounts {
+                        let pending_hash = PendingHash {
+                            epoch: epoch_accounts.epoch,
+                            slot: epoch_accounts.slot,
+                            hash: Bank::hash_snapshot_accounts(&epoch_accounts.accounts),
+                        };
+                        cluster_info.write().unwrap().push_accounts_hash(
+                            pending_hash.epoch,
+                            pending_hash.slot,
+                            pending_hash.hash,
+                        );
+                        pending = Some(pending_hash);
+                    }
+
+                    if let Some(pending_hash) = &pending {
+                        let matched =
+                            Self::check_pending_hash(pending_hash, &root_bank, &cluster_info);
+                        if matched == Some(false) && halt_on_mismatch {
+                            exit.store(true, Ordering::Relaxed);
+                        }
+                        if matched.is_some() {
+                            pending = None;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+        Self { t_verifier }
+    }
+
+    /// The bank at the current root, None after a restart until replay roots a bank again
+    fn root_bank(bank_forks: &Arc<RwLock<BankForks>>) -> Option<Arc<Bank>> {
+        let bank_forks = bank_forks.read().unwrap();
+        bank_forks.get(bank_forks.root()).cloned()
+    }
+
+    /// Whether `pending_hash` agrees with the cluster, or None while no majority has reported
+    fn check_pending_hash(
+        pending_hash: &PendingHash,
+        root_bank: &Bank,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+    ) -> Option<bool> {
+        let stakes = stakingUtils::staked_nodes_at_epoch(root_bank, pending_hash.epoch)?;
+        let reports: Vec<_> = cluster_info
+            .read()
+            .unwrap()
+            .get_accounts_hashes(pending_hash.slot)
+            .into_iter()
+            .map(|accounts_hash| (accounts_hash.from, accounts_hash.hash))
+            .collect();
+        let majority_hash = Self::majority_hash(&reports, &stakes)?;
+        let matched = majority_hash == pending_hash.hash;
+        if matched {
+            datapoint_info!(
+                "accounts_hash_verifier-matched",
+                ("slot", pending_hash.slot, i64)
+            );
+        } else {
+            datapoint_error!(
+                "accounts_hash_verifier-mismatch",
+                ("slot", pending_hash.slot, i64)
+            );
+            println!(
+                "{}",
+                Error(
+                    format!(
+                        "accounts hash mismatch at slot {}: ours {} cluster {}",
+                        pending_hash.slot, pending_hash.hash, majority_hash
+                    ),
+                    module_path!().to_string()
+                )
+            );
+        }
+        Some(matched)
+    }
+
+    /// Whether the hash of the accounts stored in `root_bank`'s slot agrees with hashing
+    /// them all again
+    fn check_delta_hash(root_bank: &Bank) -> bool {
+        let verified = root_bank.verify_hash_internal_state();
+        if !verified {
+            datapoint_error!(
+                "accounts_hash_verifier-delta_hash_mismatch",
+                ("slot", root_bank.slot(), i64)
+            );
+            println!(
+                "{}",
+                Error(
+                    format!("accounts delta hash mismatch at slot {}", root_bank.slot()),
+                    module_path!().to_string()
+                )
+            );
+        }
+        verified
+    }
+
+    /// The hash reported by nodes holding more than half of the epoch's stake, if any
+    pub(crate) fn majority_hash(
+        reports: &[(Pubkey, Hash)],
+        stakes: &HashMap<Pubkey, u64>,
+    ) -> Option<Hash> {
+        let total_stake: u64 = stakes.values().sum();
+        let mut hash_stakes: HashMap<Hash, u64> = HashMap::new();
+        for (from, hash) in reports {
+            *hash_stakes.entry(*hash).or_insert(0) += stakes.get(from).cloned().unwrap_or(0);
+        }
+        hash_stakes
+            .into_iter()
+            .find(|(_, stake)| *stake * 2 > total_stake)
+            .map(|(hash, _)| hash)
+    }
+}
+
+impl Service for AccountsHashVerifier {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.t_verifier.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectionInfo::ContactInfo;
+    use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
+    use morgan_interface::hash::hash;
+
+    #[test]
+    fn test_majority_hash() {
+        let (node0, node1, node2) = (Pubkey::new_rand(), Pubkey::new_rand(), Pubkey::new_rand());
+        let stakes: HashMap<_, _> = vec![(node0, 3), (node1, 2), (node2, 1)]
+            .into_iter()
+            .collect();
+        let (hash0, hash1) = (hash(&[0]), hash(&[1]));
+
+        assert_eq!(AccountsHashVerifier::majority_hash(&[], &stakes), None);
+        // half the stake isn't a majority
+        assert_eq!(
+            AccountsHashVerifier::majority_hash(&[(node0, hash0)], &stakes),
+            None
+        );
+        assert_eq!(
+            AccountsHashVerifier::majority_hash(&[(node0, hash0), (node2, hash0)], &stakes),
+            Some(hash0)
+        );
+        assert_eq!(
+            AccountsHashVerifier::majority_hash(
+                &[(node0, hash0), (node1, hash1), (node2, hash1)],
+                &stakes
+            ),
+            None
+        );
+        // unstaked nodes don't count
+        assert_eq!(
+            AccountsHashVerifier::majority_hash(
+                &[(node1, hash1), (Pubkey::new_rand(), hash1)],
+                &stakes
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_check_delta_hash() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        bank.transfer(1_000, &mint_keypair, &Pubkey::new_rand())
+            .unwrap();
+        assert!(AccountsHashVerifier::check_delta_hash(&bank));
+    }
+
+    #[test]
+    fn test_accounts_hash_verifier_exit() {
+        let genesis_block = create_genesis_block(10_000).genesis_block;
+        let bank = Bank::new(&genesis_block);
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(0, bank)));
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+            ContactInfo::default(),
+        )));
+        let exit = Arc::new(AtomicBool::new(false));
+        let verifier = AccountsHashVerifier::new(&bank_forks, &cluster_info, false, &exit);
+        exit.store(true, Ordering::Relaxed);
+        verifier.join().unwrap();
+    }
+}
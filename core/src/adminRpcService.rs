@@ -0,0 +1,614 @@
+//! The `adminRpcService` module serves JSON RPC methods for the operator of a running node.
+//! It only listens on the loopback interface, the methods are never exposed to the cluster.
+//! Requests must be addressed to the loopback address and carry the token the service writes
+//! to `ADMIN_RPC_TOKEN_FILE` in the ledger directory, as an `Authorization: Bearer` header, so
+//! a web page the operator visits can't reach the methods by rebinding a domain to loopback.
+
+use crate::clusterMessage::ClusterInfo;
+use crate::service::Service;
+use crate::socketStats::{socket_stats, SocketStatsSnapshot};
+use crate::stakingUtils;
+use crate::startupProgress::{StartupPhase, StartupProgress};
+use crate::treasuryForks::BankForks;
+use jsonrpc_core::{Error, ErrorCode, MetaIoHandler, Metadata, Result};
+use jsonrpc_derive::rpc;
+use jsonrpc_http_server::{hyper, RequestMiddlewareAction, ServerBuilder};
+use morgan_interface::signature::{read_keypair, KeypairUtil};
+use morgan_runtime::account_lock_stats::AccountLockStat;
+use rand::{thread_rng, Rng};
+use ring::constant_time;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::Duration;
+use morgan_helper::logHelper::*;
+
+/// How many slots the root may trail the highest root a peer advertises and still be healthy
+pub const HEALTH_CHECK_SLOT_DISTANCE: u64 = 150;
+
+/// How many accounts `getHottestAccounts` reports unless asked for another number
+pub const DEFAULT_HOTTEST_ACCOUNTS: usize = 20;
+
+/// The file in the ledger directory holding the token requests must carry
+pub const ADMIN_RPC_TOKEN_FILE: &str = "admin-rpc.token";
+
+#[derive(Clone)]
+pub struct AdminMeta {
+    pub cluster_info: Arc<RwLock<ClusterInfo>>,
+    pub bank_forks: Arc<RwLock<BankForks>>,
+    pub startup_progress: Arc<StartupProgress>,
+    pub shutdown_request: Arc<AtomicBool>,
+}
+impl Metadata for AdminMeta {}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminHealth {
+    /// Done starting up and close enough to the cluster's root
+    pub healthy: bool,
+    pub startup: StartupPhase,
+    pub root: u64,
+    /// The highest root a staked peer advertises in gossip
+    pub cluster_root: u64,
+}
+
+/// An account transactions contend for, how long they waited on and held it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AdminHotAccount {
+    pub pubkey: String,
+    #[serde(flatten)]
+    pub stat: AccountLockStat,
+}
+
+#[rpc(server)]
+pub trait AdminRpc {
+    type Metadata;
+
+    #[rpc(meta, name = "setLogFilter")]
+    fn set_log_filter(&self, _: Self::Metadata, _: String) -> Result<()>;
+
+    #[rpc(meta, name = "getSocketStats")]
+    fn get_socket_stats(&self, _: Self::Metadata) -> Result<Vec<SocketStatsSnapshot>>;
+
+    #[rpc(meta, name = "exit")]
+    fn exit(&self, _: Self::Metadata) -> Result<bool>;
+
+    #[rpc(meta, name = "getHealth")]
+    fn get_health(&self, _: Self::Metadata) -> Result<AdminHealth>;
+
+    #[rpc(meta, name = "setIdentity")]
+    fn set_identity(&self, _: Self::Metadata, _: String) -> Result<String>;
+
+    #[rpc(meta, name = "getHottestAccounts")]
+    fn get_hottest_accounts(
+        &self,
+        _: Self::Metadata,
+        _: Option<usize>,
+    ) -> Result<Vec<AdminHotAccount>>;
+}
+
+pub struct AdminRpcImpl;
+impl AdminRpc for AdminRpcImpl {
+    type Metadata = AdminMeta;
+
+    fn set_log_filter(&self, _meta: Self::Metadata, filter: String) -> Result<()> {
+        if filter.trim().is_empty() {
+            return Err(Error::invalid_params("log filter is empty"));
+        }
+        println!(
+            "{}",
+            printLn(
+                format!("admin rpc: setting log filter to {:?}", filter),
+                module_path!().to_string()
+            )
+        );
+        morgan_logger::setup_with_filter(&filter);
+        Ok(())
+    }
+
+    fn get_socket_stats(&self, _meta: Self::Metadata) -> Result<Vec<SocketStatsSnapshot>> {
+        Ok(socket_stats())
+    }
+
+    fn exit(&self, meta: Self::Metadata) -> Result<bool> {
+        println!(
+            "{}",
+            Warn(
+                "admin rpc: exit requested".to_string(),
+                module_path!().to_string()
+            )
+        );
+        meta.shutdown_request.store(true, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    fn get_health(&self, meta: Self::Metadata) -> Result<AdminHealth> {
+        let (root, staked_nodes) = {
+            let bank_forks = meta.bank_forks.read().unwrap();
+            let staked_nodes = bank_forks
+                .root_bank()
+                .map(|bank| stakingUtils::staked_nodes(&bank))
+                .unwrap_or_default();
+            (bank_forks.root(), staked_nodes)
+        };
+        // anyone can gossip a root, only those with something at stake are taken at their word
+        let cluster_root = {
+            let cluster_info = meta.cluster_info.read().unwrap();
+            cluster_info
+                .tvu_peers()
+                .iter()
+                .filter(|peer| staked_nodes.get(&peer.id).map_or(false, |stake| *stake > 0))
+                .filter_map(|peer| cluster_info.get_gossiped_root_for_node(&peer.id, None))
+                .max()
+                .unwrap_or(root)
+        };
+        Ok(AdminHealth {
+            healthy: meta.startup_progress.is_running()
+                && root + HEALTH_CHECK_SLOT_DISTANCE >= cluster_root,
+            startup: meta.startup_progress.phase(),
+            root,
+            cluster_root,
+        })
+    }
+
+    fn set_identity(&self, meta: Self::Metadata, keypair_path: String) -> Result<String> {
+        let keypair = read_keypair(&keypair_path).map_err(|e| {
+            Error::invalid_params(format!("failed to read {}: {}", keypair_path, e))
+        })?;
+        let id = keypair.pubkey();
+        // the services take the identity as they start, held until the swap so startup can't
+        // move on to them in between
+        let mut cluster_info = meta.cluster_info.write().unwrap();
+        match meta.startup_progress.phase() {
+            StartupPhase::StartingServices | StartupPhase::Running => {
+                return Err(Error {
+                    code: ErrorCode::InvalidRequest,
+                    message: "the identity can only be set before the node starts its services"
+                        .to_string(),
+                    data: None,
+                });
+            }
+            _ => (),
+        }
+        println!(
+            "{}",
+            printLn(
+                format!("admin rpc: setting identity to {}", id),
+                module_path!().to_string()
+            )
+        );
+        cluster_info.set_keypair(Arc::new(keypair));
+        Ok(id.to_string())
+    }
+
+    fn get_hottest_accounts(
+        &self,
+        meta: Self::Metadata,
+        num: Option<usize>,
+    ) -> Result<Vec<AdminHotAccount>> {
+        let bank = meta.bank_forks.read().unwrap().working_bank();
+        Ok(bank
+            .hottest_accounts(num.unwrap_or(DEFAULT_HOTTEST_ACCOUNTS))
+            .into_iter()
+            .map(|(pubkey, stat)| AdminHotAccount {
+                pubkey: pubkey.to_string(),
+                stat,
+            })
+            .collect())
+    }
+}
+
+/// Write a fresh token to `path`, readable by the operator only
+fn write_token(path: &Path) -> io::Result<String> {
+    let token = bs58::encode(thread_rng().gen::<[u8; 32]>()).into_string();
+    // the mode only applies to a new file
+    let _ = fs::remove_file(path);
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(token.as_bytes())?;
+    Ok(token)
+}
+
+fn is_authorized(request: &hyper::Request<hyper::Body>, token: &str) -> bool {
+    request
+        .headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .map_or(false, |value| {
+            // compared in constant time, so the token can't be guessed a byte at a time
+            constant_time::verify_slices_are_equal(
+                value.as_bytes(),
+                format!("Bearer {}", token).as_bytes(),
+            )
+            .is_ok()
+        })
+}
+
+pub struct AdminRpcService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl AdminRpcService {
+    pub fn new(
+        admin_rpc_port: u16,
+        ledger_path: &str,
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        startup_progress: &Arc<StartupProgress>,
+        shutdown_request: &Arc<AtomicBool>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let admin_rpc_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), admin_rpc_port);
+        println!(
+            "{}",
+            printLn(
+                format!("admin rpc bound to {:?}", admin_rpc_addr),
+                module_path!().to_string()
+            )
+        );
+        let meta = AdminMeta {
+            cluster_info: cluster_info.clone(),
+            bank_forks: bank_forks.clone(),
+            startup_progress: startup_progress.clone(),
+            shutdown_request: shutdown_request.clone(),
+        };
+        let token_path = Path::new(ledger_path).join(ADMIN_RPC_TOKEN_FILE);
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("morgan-admin-rpc".to_string())
+            .spawn(move || {
+                let token = match write_token(&token_path) {
+                    Ok(token) => token,
+                    Err(e) => {
+                        println!(
+                            "{}",
+                            Warn(
+                                format!(
+                                    "admin RPC service unavailable, failed to write {:?}: {:?}",
+                                    token_path, e
+                                ),
+                                module_path!().to_string()
+                            )
+                        );
+                        return;
+                    }
+                };
+                let mut io = MetaIoHandler::default();
+                io.extend_with(AdminRpcImpl.to_delegate());
+
+                let server = ServerBuilder::with_meta_extractor(
+                    io,
+                    move |_req: &hyper::Request<hyper::Body>| meta.clone(),
+                )
+                .threads(1)
+                .allow_only_bind_host()
+                .request_middleware(move |request: hyper::Request<hyper::Body>| {
+                    if is_authorized(&request, &token) {
+                        RequestMiddlewareAction::from(request)
+                    } else {
+                        let mut response = hyper::Response::new(hyper::Body::empty());
+                        *response.status_mut() = hyper::StatusCode::UNAUTHORIZED;
+                        RequestMiddlewareAction::from(response)
+                    }
+                })
+                .start_http(&admin_rpc_addr);
+                if let Err(e) = server {
+                    println!(
+                        "{}",
+                        Warn(
+                            format!(
+                                "admin RPC service unavailable error: {:?}. \nAlso, check that port {} is not already in use by another application",
+                                e,
+                                admin_rpc_addr.port()
+                            ),
+                            module_path!().to_string()
+                        )
+                    );
+                    return;
+                }
+                while !exit.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(100));
+                }
+                server.unwrap().close();
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+}
+
+impl Service for AdminRpcService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockBufferPool::get_tmp_ledger_path;
+    use crate::connectionInfo::ContactInfo;
+    use crate::genesisUtils::{create_genesis_block_with_leader, GenesisBlockInfo};
+    use crate::socketStats::SocketStats;
+    use jsonrpc_core::{Output, Response};
+    use morgan_interface::genesis_block::create_genesis_block;
+    use morgan_interface::pubkey::Pubkey;
+    use morgan_interface::signature::{gen_keypair_file, Keypair};
+    use morgan_runtime::bank::Bank;
+    use std::collections::BTreeSet;
+    use std::fs::{create_dir_all, remove_dir_all, remove_file};
+    use std::net::UdpSocket;
+
+    fn new_admin_meta() -> AdminMeta {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), 0);
+        let (genesis_block, _mint_keypair) = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        AdminMeta {
+            cluster_info: Arc::new(RwLock::new(ClusterInfo::new(contact_info, keypair))),
+            bank_forks: Arc::new(RwLock::new(BankForks::new(bank.slot(), bank))),
+            startup_progress: Arc::new(StartupProgress::default()),
+            shutdown_request: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn admin_request_with_meta(meta: &AdminMeta, method: &str, params: &str) -> Response {
+        let mut io = MetaIoHandler::default();
+        io.extend_with(AdminRpcImpl.to_delegate());
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"{}","params":{}}}"#,
+            method, params
+        );
+        let res = io.handle_request_sync(&req, meta.clone());
+        serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization")
+    }
+
+    fn admin_request(method: &str, params: &str) -> Response {
+        admin_request_with_meta(&new_admin_meta(), method, params)
+    }
+
+    fn admin_result(response: Response) -> serde_json::Value {
+        match response {
+            Response::Single(Output::Success(success)) => success.result,
+            _ => panic!("unexpected response {:?}", response),
+        }
+    }
+
+    #[test]
+    fn test_set_log_filter() {
+        let expected: Response = serde_json::from_str(r#"{"jsonrpc":"2.0","result":null,"id":1}"#)
+            .expect("expected response deserialization");
+        assert_eq!(
+            admin_request(
+                "setLogFilter",
+                r#"["morgan=info,morgan::forkSelection=trace"]"#
+            ),
+            expected
+        );
+        assert!(log_enabled!(target: "morgan::forkSelection", log::Level::Trace));
+        assert!(!log_enabled!(target: "morgan::rpc", log::Level::Debug));
+
+        let expected: Response = serde_json::from_str(
+            r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"log filter is empty"},"id":1}"#,
+        )
+        .expect("expected response deserialization");
+        assert_eq!(admin_request("setLogFilter", r#"[" "]"#), expected);
+    }
+
+    #[test]
+    fn test_admin_rpc_get_socket_stats() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let stats = SocketStats::register("test", &socket);
+        stats.record_received(4);
+
+        let result = admin_result(admin_request("getSocketStats", "[]"));
+        let snapshots: Vec<SocketStatsSnapshot> = serde_json::from_value(result).unwrap();
+        let snapshot = snapshots
+            .into_iter()
+            .find(|snapshot| snapshot.local_addr == socket.local_addr().ok())
+            .unwrap();
+        assert_eq!(snapshot.name, "test");
+        assert_eq!(snapshot.received_packets, 4);
+    }
+
+    #[test]
+    fn test_admin_rpc_exit() {
+        let meta = new_admin_meta();
+        assert_eq!(
+            admin_result(admin_request_with_meta(&meta, "exit", "[]")),
+            serde_json::Value::Bool(true)
+        );
+        assert!(meta.shutdown_request.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_admin_rpc_get_health() {
+        let staked_id = Pubkey::new_rand();
+        let GenesisBlockInfo { genesis_block, .. } =
+            create_genesis_block_with_leader(10_000, &staked_id, 100);
+        let bank = Bank::new(&genesis_block);
+        let meta = AdminMeta {
+            bank_forks: Arc::new(RwLock::new(BankForks::new(bank.slot(), bank))),
+            ..new_admin_meta()
+        };
+        let get_health = || {
+            let result = admin_result(admin_request_with_meta(&meta, "getHealth", "[]"));
+            serde_json::from_value::<AdminHealth>(result).unwrap()
+        };
+        // not healthy until it's done starting up
+        meta.startup_progress
+            .set_phase(StartupPhase::ProcessingLedger {
+                slot: 3,
+                max_slot: 10,
+            });
+        assert_eq!(
+            get_health(),
+            AdminHealth {
+                healthy: false,
+                startup: StartupPhase::ProcessingLedger {
+                    slot: 3,
+                    max_slot: 10,
+                },
+                root: 0,
+                cluster_root: 0,
+            }
+        );
+
+        // alone, the node is as far as the cluster is
+        meta.startup_progress.set_phase(StartupPhase::Running);
+        assert_eq!(
+            get_health(),
+            AdminHealth {
+                healthy: true,
+                startup: StartupPhase::Running,
+                root: 0,
+                cluster_root: 0,
+            }
+        );
+
+        let push_peer_root = |id, root| {
+            let peer = ContactInfo::new_localhost(&id, 0);
+            let mut cluster_info = meta.cluster_info.write().unwrap();
+            cluster_info.insert_info(peer.clone());
+            cluster_info.push_epoch_slots(peer.id, root, BTreeSet::new());
+        };
+        // roots gossiped by peers without stake aren't taken into account
+        push_peer_root(Pubkey::new_rand(), HEALTH_CHECK_SLOT_DISTANCE + 1);
+        assert_eq!(get_health().cluster_root, 0);
+        assert!(get_health().healthy);
+
+        // staked peers may be rooted up to the distance ahead
+        push_peer_root(staked_id, HEALTH_CHECK_SLOT_DISTANCE + 1);
+        assert_eq!(
+            get_health(),
+            AdminHealth {
+                healthy: false,
+                startup: StartupPhase::Running,
+                root: 0,
+                cluster_root: HEALTH_CHECK_SLOT_DISTANCE + 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_admin_rpc_set_identity() {
+        let meta = new_admin_meta();
+        let keypair_path = format!(
+            "{}/tmp/test_admin_rpc_set_identity-{}.json",
+            env!("CARGO_MANIFEST_DIR"),
+            Pubkey::new_rand()
+        );
+        gen_keypair_file(&keypair_path).unwrap();
+        let id = read_keypair(&keypair_path).unwrap().pubkey();
+
+        let result = admin_result(admin_request_with_meta(
+            &meta,
+            "setIdentity",
+            &format!(r#"["{}"]"#, keypair_path),
+        ));
+        assert_eq!(result, serde_json::Value::String(id.to_string()));
+        let cluster_info = meta.cluster_info.read().unwrap();
+        assert_eq!(cluster_info.id(), id);
+        assert_eq!(cluster_info.my_data().id, id);
+        assert_eq!(cluster_info.keypair.pubkey(), id);
+        drop(cluster_info);
+        remove_file(&keypair_path).unwrap();
+
+        let response = admin_request_with_meta(&meta, "setIdentity", r#"["/no/such/keypair"]"#);
+        match response {
+            Response::Single(Output::Failure(_)) => (),
+            _ => panic!("unexpected response {:?}", response),
+        }
+        assert_eq!(meta.cluster_info.read().unwrap().id(), id);
+
+        // once services are starting they keep the identity they have
+        meta.startup_progress
+            .set_phase(StartupPhase::StartingServices);
+        gen_keypair_file(&keypair_path).unwrap();
+        let response =
+            admin_request_with_meta(&meta, "setIdentity", &format!(r#"["{}"]"#, keypair_path));
+        match response {
+            Response::Single(Output::Failure(_)) => (),
+            _ => panic!("unexpected response {:?}", response),
+        }
+        assert_eq!(meta.cluster_info.read().unwrap().id(), id);
+        remove_file(&keypair_path).unwrap();
+    }
+
+    #[test]
+    fn test_is_authorized() {
+        let request = |authorization: Option<&str>| {
+            let mut builder = hyper::Request::builder();
+            if let Some(authorization) = authorization {
+                builder.header(hyper::header::AUTHORIZATION, authorization);
+            }
+            builder.body(hyper::Body::empty()).unwrap()
+        };
+        assert!(is_authorized(&request(Some("Bearer token")), "token"));
+        assert!(!is_authorized(&request(Some("Bearer other")), "token"));
+        assert!(!is_authorized(&request(Some("token")), "token"));
+        assert!(!is_authorized(&request(None), "token"));
+    }
+
+    #[test]
+    fn test_admin_rpc_get_hottest_accounts() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let meta = AdminMeta {
+            bank_forks: Arc::new(RwLock::new(BankForks::new(bank.slot(), bank))),
+            ..new_admin_meta()
+        };
+        let get_hottest_accounts = |params| {
+            let result = admin_result(admin_request_with_meta(&meta, "getHottestAccounts", params));
+            serde_json::from_value::<Vec<AdminHotAccount>>(result).unwrap()
+        };
+        assert!(get_hottest_accounts("[]").is_empty());
+
+        // a transfer locks the mint, the recipient and the system program
+        let bank = meta.bank_forks.read().unwrap().working_bank();
+        bank.transfer(1, &mint_keypair, &Pubkey::new_rand())
+            .unwrap();
+        assert_eq!(get_hottest_accounts("[]").len(), 3);
+        let hottest = get_hottest_accounts("[1]");
+        assert_eq!(hottest.len(), 1);
+        assert_eq!(hottest[0].stat.locks, 1);
+    }
+
+    #[test]
+    fn test_admin_rpc_service_exit() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let port = morgan_netutil::find_available_port_in_range((10000, 65535)).unwrap();
+        let meta = new_admin_meta();
+        let ledger_path = get_tmp_ledger_path("test_admin_rpc_service_exit");
+        create_dir_all(&ledger_path).unwrap();
+        let admin_rpc_service = AdminRpcService::new(
+            port,
+            &ledger_path,
+            &meta.cluster_info,
+            &meta.bank_forks,
+            &meta.startup_progress,
+            &meta.shutdown_request,
+            &exit,
+        );
+        assert_eq!(
+            admin_rpc_service.thread_hdl.thread().name().unwrap(),
+            "morgan-admin-rpc"
+        );
+        exit.store(true, Ordering::Relaxed);
+        admin_rpc_service.join().unwrap();
+        assert!(Path::new(&ledger_path).join(ADMIN_RPC_TOKEN_FILE).exists());
+        remove_dir_all(&ledger_path).unwrap();
+    }
+}
@@ -20,7 +20,8 @@ use morgan_metricbot::{datapoint_error, datapoint_info};
 
 use morgan_interface::genesis_block::GenesisBlock;
 use morgan_interface::hash::Hash;
-use morgan_interface::signature::{Keypair, KeypairUtil};
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::{Keypair, KeypairUtil, Signature};
 use morgan_helper::logHelper::*;
 
 use std::borrow::{Borrow, Cow};
@@ -30,14 +31,18 @@ use std::fs;
 use std::io;
 use std::rc::Rc;
 use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
 
+pub use self::db::WalConfig;
 pub use self::meta::*;
 pub use self::rooted_slot_iterator::*;
+pub use self::salvage::SalvageAction;
 
 mod db;
 mod meta;
 mod rooted_slot_iterator;
+mod salvage;
 
 macro_rules! db_imports {
     { $mod:ident, $db:ident, $db_path:expr } => {
@@ -67,6 +72,7 @@ db_imports! {rocks, Rocks, "rocksdb"}
 db_imports! {kvs, Kvs, "kvstore"}
 
 pub const MAX_COMPLETED_SLOTS_IN_CHANNEL: usize = 100_000;
+pub const DEFAULT_MAX_PENDING_BLOBS: usize = 1024;
 
 pub type CompletedSlotsReceiver = Receiver<Vec<u64>>;
 
@@ -80,6 +86,35 @@ pub enum BlocktreeError {
     SlotNotRooted,
 }
 
+/// Tunes how blobs arriving from the network are aggregated before being written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlocktreeConfig {
+    /// How long a slot's blobs may wait to be written as one batch, zero writes them immediately
+    pub write_flush_interval: Duration,
+    /// Number of pending blobs that forces a slot's batch to be written early
+    pub max_pending_blobs: usize,
+    pub wal: WalConfig,
+    /// Truncate slots a crash left inconsistent when opening, instead of failing on them later
+    pub recovery_mode: bool,
+}
+
+impl Default for BlocktreeConfig {
+    fn default() -> Self {
+        BlocktreeConfig {
+            write_flush_interval: Duration::from_millis(0),
+            max_pending_blobs: DEFAULT_MAX_PENDING_BLOBS,
+            wal: WalConfig::default(),
+            recovery_mode: false,
+        }
+    }
+}
+
+// Blobs for a slot waiting to be written in a single batch
+struct PendingBlobs {
+    blobs: Vec<Blob>,
+    first_pending: Instant,
+}
+
 // ledger window
 pub struct Blocktree {
     db: Arc<Database>,
@@ -90,8 +125,11 @@ pub struct Blocktree {
     orphans_cf: LedgerColumn<cf::Orphans>,
     batch_processor: Arc<RwLock<BatchProcessor>>,
     session: Arc<expunge::Session>,
+    config: BlocktreeConfig,
+    pending_blobs: Mutex<HashMap<u64, PendingBlobs>>,
     pub new_blobs_signals: Vec<SyncSender<bool>>,
     pub completed_slots_senders: Vec<SyncSender<Vec<u64>>>,
+    salvage_actions: Vec<SalvageAction>,
 }
 
 // Column family for metadata about a leader slot
@@ -105,17 +143,27 @@ pub const ERASURE_META_CF: &str = "erasure_meta";
 pub const ORPHANS_CF: &str = "orphans";
 // Column family for root data
 pub const ROOT_CF: &str = "root";
+// Column family indexing rooted transaction signatures by address
+pub const ADDRESS_SIGNATURES_CF: &str = "address_signatures";
+// Column family for the slot of each indexed signature
+pub const SIGNATURE_SLOTS_CF: &str = "signature_slots";
 
 impl Blocktree {
     /// Opens a Ledger in directory, provides "infinite" window of blobs
     pub fn open(ledger_path: &str) -> Result<Blocktree> {
+        Self::open_with_config(ledger_path, BlocktreeConfig::default())
+    }
+
+    pub fn open_with_config(ledger_path: &str, config: BlocktreeConfig) -> Result<Blocktree> {
         use std::path::Path;
 
         fs::create_dir_all(&ledger_path)?;
         let ledger_path = Path::new(&ledger_path).join(BLOCKTREE_DIRECTORY);
 
-        // Open the database
-        let db = Database::open(&ledger_path)?;
+        // Open the database, in recovery mode dropping log records torn by a crash
+        let mut wal_config = config.wal;
+        wal_config.point_in_time_recovery |= config.recovery_mode;
+        let db = Database::open(&ledger_path, &wal_config)?;
 
         let batch_processor = unsafe { Arc::new(RwLock::new(db.batch_processor())) };
 
@@ -140,7 +188,7 @@ impl Blocktree {
 
         let db = Arc::new(db);
 
-        Ok(Blocktree {
+        let mut blocktree = Blocktree {
             db,
             meta_cf,
             data_cf,
@@ -148,16 +196,44 @@ impl Blocktree {
             erasure_meta_cf,
             orphans_cf,
             session,
+            config,
+            pending_blobs: Mutex::new(HashMap::new()),
             new_blobs_signals: vec![],
             batch_processor,
             completed_slots_senders: vec![],
-        })
+            salvage_actions: vec![],
+        };
+
+        if config.recovery_mode {
+            blocktree.salvage_actions = blocktree.salvage()?;
+            for action in &blocktree.salvage_actions {
+                println!(
+                    "{}",
+                    Warn(
+                        format!("ledger recovery: {}", action).to_string(),
+                        module_path!().to_string()
+                    )
+                );
+            }
+            datapoint_info!(
+                "blocktree-salvage",
+                ("actions", blocktree.salvage_actions.len() as i64, i64)
+            );
+        }
+        Ok(blocktree)
     }
 
     pub fn open_with_signal(
         ledger_path: &str,
     ) -> Result<(Self, Receiver<bool>, CompletedSlotsReceiver)> {
-        let mut blocktree = Self::open(ledger_path)?;
+        Self::open_with_config_and_signal(ledger_path, BlocktreeConfig::default())
+    }
+
+    pub fn open_with_config_and_signal(
+        ledger_path: &str,
+        config: BlocktreeConfig,
+    ) -> Result<(Self, Receiver<bool>, CompletedSlotsReceiver)> {
+        let mut blocktree = Self::open_with_config(ledger_path, config)?;
         let (signal_sender, signal_receiver) = sync_channel(1);
         let (completed_slots_sender, completed_slots_receiver) =
             sync_channel(MAX_COMPLETED_SLOTS_IN_CHANNEL);
@@ -174,6 +250,11 @@ impl Blocktree {
         Database::destroy(&path)
     }
 
+    /// What recovery mode dropped when the ledger was opened
+    pub fn salvage_actions(&self) -> &[SalvageAction] {
+        &self.salvage_actions
+    }
+
     pub fn meta(&self, slot: u64) -> Result<Option<SlotMeta>> {
         self.meta_cf.get(slot)
     }
@@ -288,6 +369,78 @@ impl Blocktree {
         self.write_blobs(&blobs)
     }
 
+    /// Queues data blobs per slot and writes each slot's blobs as one batch once
+    /// its flush interval elapses, it holds `max_pending_blobs`, or its last blob arrives
+    pub fn buffer_data_blobs<I>(&self, new_blobs: I) -> Result<()>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Blob>,
+    {
+        if self.config.write_flush_interval == Duration::from_millis(0) {
+            return self.insert_data_blobs(new_blobs);
+        }
+
+        let mut ready_slots = vec![];
+        {
+            let mut pending_blobs = self.pending_blobs.lock().unwrap();
+            for blob in new_blobs {
+                let blob = blob.borrow();
+                let pending = pending_blobs
+                    .entry(blob.slot())
+                    .or_insert_with(|| PendingBlobs {
+                        blobs: vec![],
+                        first_pending: Instant::now(),
+                    });
+                pending.blobs.push(blob.clone());
+                if blob.is_last_in_slot() || pending.blobs.len() >= self.config.max_pending_blobs {
+                    ready_slots.push(blob.slot());
+                }
+            }
+        }
+
+        self.flush_slots(ready_slots)?;
+        self.flush_due_blobs()
+    }
+
+    /// Writes the pending blobs of every slot whose flush interval has elapsed
+    pub fn flush_due_blobs(&self) -> Result<()> {
+        let due_slots: Vec<_> = self
+            .pending_blobs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, pending)| {
+                pending.first_pending.elapsed() >= self.config.write_flush_interval
+            })
+            .map(|(slot, _)| *slot)
+            .collect();
+
+        self.flush_slots(due_slots)
+    }
+
+    /// Writes all pending blobs regardless of how long they have waited
+    pub fn flush_pending_blobs(&self) -> Result<()> {
+        let slots: Vec<_> = self.pending_blobs.lock().unwrap().keys().cloned().collect();
+        self.flush_slots(slots)
+    }
+
+    /// Writes all pending blobs and syncs the write-ahead log, so nothing inserted so far is
+    /// lost if the process stops
+    pub fn flush(&self) -> Result<()> {
+        self.flush_pending_blobs()?;
+        self.db.sync_wal()
+    }
+
+    fn flush_slots(&self, slots: Vec<u64>) -> Result<()> {
+        for slot in slots {
+            let pending = self.pending_blobs.lock().unwrap().remove(&slot);
+            if let Some(pending) = pending {
+                self.insert_data_blobs(&pending.blobs)?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn insert_data_blobs<I>(&self, new_blobs: I) -> Result<()>
     where
         I: IntoIterator,
@@ -388,8 +541,28 @@ impl Blocktree {
             write_batch.put::<cf::ErasureMeta>((slot, set_index), &erasure_meta)?;
         }
 
+        let payload_bytes: usize = new_blobs
+            .iter()
+            .map(|blob| BLOB_HEADER_SIZE + blob.borrow().size())
+            .sum();
+        let written_bytes = write_batch.size_in_bytes();
+
         batch_processor.write(write_batch)?;
 
+        if payload_bytes > 0 {
+            datapoint_info!(
+                "blocktree-write_amplification",
+                ("blobs", new_blobs.len(), i64),
+                ("payload_bytes", payload_bytes, i64),
+                ("written_bytes", written_bytes, i64),
+                (
+                    "amplification_x100",
+                    written_bytes * 100 / payload_bytes,
+                    i64
+                )
+            );
+        }
+
         if should_signal {
             for signal in &self.new_blobs_signals {
                 let _ = signal.try_send(true);
@@ -826,9 +999,11 @@ impl Blocktree {
             let mut write_batch = batch_processor.batch()?;
             if new_root == 0 {
                 write_batch.put::<cf::Root>(0, &true)?;
+                self.index_slot_signatures(&mut write_batch, 0)?;
             } else {
                 while current_slot != prev_root {
                     write_batch.put::<cf::Root>(current_slot, &true)?;
+                    self.index_slot_signatures(&mut write_batch, current_slot)?;
                     current_slot = self.meta(current_slot).unwrap().unwrap().parent_slot;
                 }
             }
@@ -838,6 +1013,157 @@ impl Blocktree {
         Ok(())
     }
 
+    // Index the transactions of a newly rooted slot under every address they reference,
+    // failed transactions included
+    fn index_slot_signatures(&self, write_batch: &mut WriteBatch, slot: u64) -> Result<()> {
+        let entries = self.get_slot_entries(slot, 0, None)?;
+        Self::put_slot_signatures(write_batch, slot, &entries)
+    }
+
+    fn put_slot_signatures(
+        write_batch: &mut WriteBatch,
+        slot: u64,
+        entries: &[Entry],
+    ) -> Result<()> {
+        for entry in entries {
+            for transaction in &entry.transactions {
+                let signature = transaction.signatures[0];
+                write_batch.put::<cf::SignatureSlots>(signature, &slot)?;
+                for address in &transaction.message.account_keys {
+                    write_batch.put::<cf::AddressSignatures>((*address, slot, signature), &true)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Index the transactions in `entries` of the rooted `slot` unless they already are, for
+    /// ledgers whose roots were set before the signature index was kept
+    pub fn index_root_signatures(&self, slot: u64, entries: &[Entry]) -> Result<()> {
+        let first_signature = entries
+            .iter()
+            .flat_map(|entry| entry.transactions.iter())
+            .map(|transaction| transaction.signatures[0])
+            .next();
+        match first_signature {
+            Some(signature) if self.get_signature_slot(&signature)? != Some(slot) => {
+                let mut batch_processor = self.batch_processor.write().unwrap();
+                let mut write_batch = batch_processor.batch()?;
+                Self::put_slot_signatures(&mut write_batch, slot, entries)?;
+                batch_processor.write(write_batch)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The slot a rooted transaction was recorded in
+    pub fn get_signature_slot(&self, signature: &Signature) -> Result<Option<u64>> {
+        self.db.get::<cf::SignatureSlots>(*signature)
+    }
+
+    /// Signatures of the rooted transactions referencing `address` along with their slots,
+    /// newest slot first. Starts after `before` and stops short of `until`, returning at most
+    /// `limit` of them.
+    pub fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        before: Option<&Signature>,
+        until: Option<&Signature>,
+        limit: usize,
+    ) -> Result<Vec<(u64, Signature)>> {
+        let start = match before {
+            Some(before) => match self.get_signature_slot(before)? {
+                Some(slot) => (*address, slot, *before),
+                None => return Ok(vec![]),
+            },
+            None => (*address, std::u64::MAX, Signature::default()),
+        };
+
+        let mut results = vec![];
+        let mut cursor = self.db.cursor::<cf::AddressSignatures>()?;
+        cursor.seek(start);
+        while cursor.valid() && results.len() < limit {
+            let (key_address, slot, signature) = cursor.key().unwrap();
+            if key_address != *address || Some(&signature) == until {
+                break;
+            }
+            if Some(&signature) != before {
+                results.push((slot, signature));
+            }
+            cursor.next();
+        }
+        Ok(results)
+    }
+
+    /// Signatures of the rooted transactions referencing `address` recorded in the slots from
+    /// `start_slot` through `end_slot`, oldest slot first
+    pub fn get_confirmed_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> Result<Vec<Signature>> {
+        let mut signatures = vec![];
+        let mut cursor = self.db.cursor::<cf::AddressSignatures>()?;
+        cursor.seek((*address, end_slot, Signature::default()));
+        while cursor.valid() {
+            let (key_address, slot, signature) = cursor.key().unwrap();
+            if key_address != *address || slot < start_slot {
+                break;
+            }
+            signatures.push(signature);
+            cursor.next();
+        }
+        signatures.reverse();
+        Ok(signatures)
+    }
+
+    /// The lowest and highest slots the ledger holds, None while it holds none.  Everything
+    /// below the lowest slot was purged
+    pub fn ledger_range(&self) -> Option<(u64, u64)> {
+        let mut cursor = self.db.cursor::<cf::SlotMeta>().ok()?;
+        cursor.seek_to_first();
+        let first_slot = cursor.key()?;
+        cursor.seek_to_last();
+        let last_slot = cursor.key()?;
+        Some((first_slot, last_slot))
+    }
+
+    /// Delete everything stored for the slots below `slot`, rooted transactions' signature
+    /// index included, returning how many slots were purged.  Each slot goes in its own write
+    /// batch, oldest first.
+    pub fn purge_slots_below(&self, slot: u64) -> Result<usize> {
+        let slots: Vec<u64> = self
+            .db
+            .iter::<cf::SlotMeta>(None)?
+            .map(|(meta_slot, _)| meta_slot)
+            .take_while(|meta_slot| *meta_slot < slot)
+            .collect();
+
+        for slot in &slots {
+            let mut batch_processor = self.batch_processor.write().unwrap();
+            let mut write_batch = batch_processor.batch()?;
+            if self.is_root(*slot) {
+                for entry in self.get_slot_entries(*slot, 0, None)? {
+                    for transaction in &entry.transactions {
+                        let signature = transaction.signatures[0];
+                        write_batch.delete::<cf::SignatureSlots>(signature)?;
+                        for address in &transaction.message.account_keys {
+                            write_batch
+                                .delete::<cf::AddressSignatures>((*address, *slot, signature))?;
+                        }
+                    }
+                }
+            }
+            self.drop_blobs(&mut write_batch, *slot, 0)?;
+            write_batch.delete::<cf::SlotMeta>(*slot)?;
+            write_batch.delete::<cf::Orphans>(*slot)?;
+            write_batch.delete::<cf::Root>(*slot)?;
+            batch_processor.write(write_batch)?;
+        }
+        Ok(slots.len())
+    }
+
     pub fn get_orphans(&self, max: Option<usize>) -> Vec<u64> {
         let mut results = vec![];
 
@@ -879,6 +1205,21 @@ impl Blocktree {
     }
 }
 
+impl Drop for Blocktree {
+    fn drop(&mut self) {
+        // Don't lose blobs that were still waiting for their batch to be written
+        if let Err(e) = self.flush_pending_blobs() {
+            println!(
+                "{}",
+                Error(
+                    format!("failed to flush pending blobs: {:?}", e).to_string(),
+                    module_path!().to_string()
+                )
+            );
+        }
+    }
+}
+
 fn insert_data_blob_batch<'a, I>(
     new_blobs: I,
     db: &Database,
@@ -1549,7 +1890,6 @@ where
 {
     let blocktree = Blocktree::open(ledger_path)?;
 
-    // TODO sign these blobs with keypair
     let blobs: Vec<_> = entries
         .into_iter()
         .enumerate()
@@ -1558,6 +1898,7 @@ where
             b.set_index(idx as u64);
             b.set_id(&keypair.pubkey());
             b.set_slot(0);
+            b.sign(keypair);
             b
         })
         .collect();
@@ -1645,6 +1986,7 @@ pub mod tests {
     use rand::Rng;
     use morgan_interface::hash::Hash;
     use morgan_interface::pubkey::Pubkey;
+    use morgan_interface::system_transaction;
     use std::cmp::min;
     use std::collections::HashSet;
     use std::iter::once;
@@ -2165,6 +2507,41 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    pub fn test_buffer_data_blobs() {
+        let blocktree_path = get_tmp_ledger_path("test_buffer_data_blobs");
+        {
+            let config = BlocktreeConfig {
+                write_flush_interval: Duration::from_secs(60),
+                max_pending_blobs: 4,
+                ..BlocktreeConfig::default()
+            };
+            let blocktree = Blocktree::open_with_config(&blocktree_path, config).unwrap();
+
+            let num_entries = 10;
+            let (blobs, entries) = make_slot_entries(0, 0, num_entries);
+
+            // Pending blobs aren't visible until the slot's batch is written
+            blocktree.buffer_data_blobs(&blobs[..3]).unwrap();
+            assert!(blocktree.meta(0).unwrap().is_none());
+
+            // Reaching max_pending_blobs writes the batch
+            blocktree.buffer_data_blobs(&blobs[3..4]).unwrap();
+            assert_eq!(blocktree.meta(0).unwrap().unwrap().consumed, 4);
+
+            blocktree.buffer_data_blobs(&blobs[4..6]).unwrap();
+            assert_eq!(blocktree.meta(0).unwrap().unwrap().consumed, 4);
+            blocktree.flush_pending_blobs().unwrap();
+            assert_eq!(blocktree.meta(0).unwrap().unwrap().consumed, 6);
+
+            // The last blob in the slot writes the batch right away
+            blocktree.buffer_data_blobs(&blobs[6..]).unwrap();
+            assert_eq!(blocktree.get_slot_entries(0, 0, None).unwrap(), entries);
+            assert!(blocktree.meta(0).unwrap().unwrap().is_full());
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_genesis_and_entry_iterator() {
         let entries = make_tiny_test_entries_from_hash(&Hash::default(), 10);
@@ -3170,6 +3547,297 @@ pub mod tests {
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    fn test_recovery_mode_salvage() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            for (slot_blobs, _) in make_chaining_slot_entries(&[0, 1, 2], 10) {
+                blocktree.insert_data_blobs(&slot_blobs[..]).unwrap();
+            }
+            assert!(blocktree.meta(2).unwrap().unwrap().is_connected);
+            assert!(blocktree.salvage_actions().is_empty());
+
+            // a blob of slot 1 torn by a crash, and a slot whose meta can't be read
+            let bytes = blocktree.get_data_blob_bytes(1, 5).unwrap().unwrap();
+            blocktree
+                .put_data_blob_bytes(1, 5, &bytes[..bytes.len() - 10])
+                .unwrap();
+            blocktree
+                .db
+                .put_bytes::<cf::SlotMeta>(5, &[1, 2, 3])
+                .unwrap();
+        }
+
+        let config = BlocktreeConfig {
+            recovery_mode: true,
+            ..BlocktreeConfig::default()
+        };
+        let blocktree = Blocktree::open_with_config(&blocktree_path, config).unwrap();
+        assert_eq!(
+            blocktree.salvage_actions(),
+            &[
+                SalvageAction::PurgedSlot {
+                    slot: 5,
+                    dropped_blobs: 0,
+                },
+                SalvageAction::TruncatedSlot {
+                    slot: 1,
+                    index: 5,
+                    dropped_blobs: 5,
+                },
+            ]
+        );
+        assert_eq!(blocktree.meta(5).unwrap(), None);
+        assert!(blocktree.meta(0).unwrap().unwrap().is_connected);
+
+        let meta = blocktree.meta(1).unwrap().unwrap();
+        assert_eq!(meta.consumed, 5);
+        assert_eq!(meta.received, 5);
+        assert_eq!(meta.last_index, std::u64::MAX);
+        assert!(!meta.is_connected);
+        assert_eq!(blocktree.get_slot_entries(1, 0, None).unwrap().len(), 5);
+
+        // slot 2 is intact but no longer chains to the root
+        let meta = blocktree.meta(2).unwrap().unwrap();
+        assert_eq!(meta.consumed, 10);
+        assert!(!meta.is_connected);
+
+        // a consistent ledger is left alone
+        drop(blocktree);
+        let blocktree = Blocktree::open_with_config(&blocktree_path, config).unwrap();
+        assert!(blocktree.salvage_actions().is_empty());
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_ledger_range() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+        assert_eq!(blocktree.ledger_range(), None);
+
+        for slot in 2..=4 {
+            let entries = vec![Entry::new(&Hash::default(), 1, vec![])];
+            let blobs = entries_to_blobs(&entries, slot, slot - 1, true);
+            blocktree.insert_data_blobs(&blobs).unwrap();
+        }
+        // the parent of the first slot inserted gets a meta too
+        assert_eq!(blocktree.ledger_range(), Some((1, 4)));
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_purge_slots_below() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+
+        let alice = Keypair::new();
+        let bob = Pubkey::new_rand();
+        let mut signatures = vec![];
+        for slot in 1..=4 {
+            let tx = system_transaction::transfer(&alice, &bob, slot, Hash::default());
+            signatures.push(tx.signatures[0]);
+            let entries = vec![Entry::new(&Hash::default(), 1, vec![tx])];
+            let blobs = entries_to_blobs(&entries, slot, slot - 1, true);
+            blocktree.insert_data_blobs(&blobs).unwrap();
+        }
+        blocktree.set_root(3, 0).unwrap();
+
+        // slot 1 chained a meta for its parent, slot 0, into the ledger
+        assert_eq!(blocktree.purge_slots_below(3).unwrap(), 3);
+        assert_eq!(blocktree.ledger_range(), Some((3, 4)));
+        for slot in 1..3 {
+            assert!(blocktree.meta(slot).unwrap().is_none());
+            assert!(!blocktree.is_root(slot));
+            assert!(blocktree
+                .get_slot_entries(slot, 0, None)
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                blocktree
+                    .get_signature_slot(&signatures[slot as usize - 1])
+                    .unwrap(),
+                None
+            );
+        }
+        assert!(blocktree.is_root(3));
+        assert_eq!(blocktree.get_slot_entries(4, 0, None).unwrap().len(), 1);
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, None, 10)
+                .unwrap(),
+            vec![(3, signatures[2])]
+        );
+
+        // nothing left to purge below the same slot
+        assert_eq!(blocktree.purge_slots_below(3).unwrap(), 0);
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_get_signatures_for_address() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+        blocktree.set_root(0, 0).unwrap();
+
+        let alice = Keypair::new();
+        let bob = Pubkey::new_rand();
+        let transfer =
+            |to: &Pubkey, difs| system_transaction::transfer(&alice, to, difs, Hash::default());
+        let slot_txs = vec![
+            vec![transfer(&bob, 1), transfer(&Pubkey::new_rand(), 2)],
+            vec![transfer(&bob, 3)],
+            vec![transfer(&bob, 4)],
+        ];
+        for (i, txs) in slot_txs.iter().enumerate() {
+            let slot = i as u64 + 1;
+            let entries = vec![Entry::new(&Hash::default(), 1, txs.clone())];
+            let blobs = entries_to_blobs(&entries, slot, slot - 1, true);
+            blocktree.insert_data_blobs(&blobs).unwrap();
+        }
+        let signature = |slot: usize, i: usize| slot_txs[slot - 1][i].signatures[0];
+
+        // nothing is indexed until it's rooted
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, None, 10)
+                .unwrap(),
+            vec![]
+        );
+        blocktree.set_root(2, 0).unwrap();
+
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, None, 10)
+                .unwrap(),
+            vec![(2, signature(2, 0)), (1, signature(1, 0))]
+        );
+        let mut alice_signatures = blocktree
+            .get_signatures_for_address(&alice.pubkey(), None, None, 10)
+            .unwrap();
+        assert_eq!(alice_signatures.len(), 3);
+        assert_eq!(alice_signatures.remove(0), (2, signature(2, 0)));
+        assert_eq!(
+            blocktree.get_signature_slot(&signature(1, 1)).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            blocktree.get_signature_slot(&signature(3, 0)).unwrap(),
+            None
+        );
+
+        // paginate
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, None, 1)
+                .unwrap(),
+            vec![(2, signature(2, 0))]
+        );
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, Some(&signature(2, 0)), None, 1)
+                .unwrap(),
+            vec![(1, signature(1, 0))]
+        );
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, Some(&signature(1, 0)), 10)
+                .unwrap(),
+            vec![(2, signature(2, 0))]
+        );
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, Some(&signature(3, 0)), None, 10)
+                .unwrap(),
+            vec![]
+        );
+
+        blocktree.set_root(3, 2).unwrap();
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, None, 10)
+                .unwrap(),
+            vec![
+                (3, signature(3, 0)),
+                (2, signature(2, 0)),
+                (1, signature(1, 0))
+            ]
+        );
+
+        // slot ranges, oldest first
+        assert_eq!(
+            blocktree
+                .get_confirmed_signatures_for_address(&bob, 0, 2)
+                .unwrap(),
+            vec![signature(1, 0), signature(2, 0)]
+        );
+        assert_eq!(
+            blocktree
+                .get_confirmed_signatures_for_address(&bob, 2, 3)
+                .unwrap(),
+            vec![signature(2, 0), signature(3, 0)]
+        );
+        assert_eq!(
+            blocktree
+                .get_confirmed_signatures_for_address(&bob, 4, 10)
+                .unwrap(),
+            vec![]
+        );
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_index_root_signatures() {
+        let blocktree_path = get_tmp_ledger_path!();
+        let blocktree = Blocktree::open(&blocktree_path).unwrap();
+
+        let alice = Keypair::new();
+        let bob = Pubkey::new_rand();
+        let tx = system_transaction::transfer(&alice, &bob, 1, Hash::default());
+        let entries = vec![Entry::new(&Hash::default(), 1, vec![tx.clone()])];
+        let blobs = entries_to_blobs(&entries, 1, 0, true);
+        blocktree.insert_data_blobs(&blobs).unwrap();
+
+        // a root set before the index was kept
+        blocktree.db.put::<cf::Root>(1, &true).unwrap();
+        assert_eq!(
+            blocktree.get_signature_slot(&tx.signatures[0]).unwrap(),
+            None
+        );
+
+        blocktree.index_root_signatures(1, &entries).unwrap();
+        assert_eq!(
+            blocktree.get_signature_slot(&tx.signatures[0]).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            blocktree
+                .get_confirmed_signatures_for_address(&bob, 1, 1)
+                .unwrap(),
+            vec![tx.signatures[0]]
+        );
+
+        // indexing again changes nothing
+        blocktree.index_root_signatures(1, &entries).unwrap();
+        assert_eq!(
+            blocktree
+                .get_signatures_for_address(&bob, None, None, 10)
+                .unwrap(),
+            vec![(1, tx.signatures[0])]
+        );
+
+        drop(blocktree);
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     mod erasure {
         use super::*;
         use crate::blockBufferPool::meta::ErasureMetaStatus;
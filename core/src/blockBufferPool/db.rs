@@ -35,6 +35,25 @@ pub mod columns {
     #[derive(Debug)]
     /// The root column
     pub struct Root;
+
+    #[derive(Debug)]
+    /// Rooted transaction signatures by the addresses they reference, newest slot first
+    pub struct AddressSignatures;
+
+    #[derive(Debug)]
+    /// The rooted slot of each indexed transaction signature
+    pub struct SignatureSlots;
+}
+
+/// How the backend's write-ahead log trades durability for insert throughput
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WalConfig {
+    /// Sync the log to disk before a batch write returns
+    pub sync: bool,
+    /// Sync written files in the background every this many bytes, 0 leaves it to the OS
+    pub bytes_per_sync: u64,
+    /// Replay the log only up to its first corrupt record instead of refusing to open
+    pub point_in_time_recovery: bool,
 }
 
 pub trait Backend: Sized + Send + Sync {
@@ -46,7 +65,7 @@ pub trait Backend: Sized + Send + Sync {
     type WriteBatch: IWriteBatch<Self>;
     type Error: Into<Error>;
 
-    fn open(path: &Path) -> Result<Self>;
+    fn open(path: &Path, wal_config: &WalConfig) -> Result<Self>;
 
     fn columns(&self) -> Vec<&'static str>;
 
@@ -67,6 +86,9 @@ pub trait Backend: Sized + Send + Sync {
     fn write(&self, batch: Self::WriteBatch) -> Result<()>;
 
     fn batch(&self) -> Result<Self::WriteBatch>;
+
+    /// Sync the write-ahead log to disk, whatever `WalConfig::sync` is
+    fn sync_wal(&self) -> Result<()>;
 }
 
 pub trait Column<B>
@@ -90,6 +112,8 @@ where
 
     fn seek_to_first(&mut self);
 
+    fn seek_to_last(&mut self);
+
     fn next(&mut self);
 
     fn key(&self) -> Option<B::OwnedKey>;
@@ -103,6 +127,7 @@ where
 {
     fn put_cf(&mut self, cf: B::ColumnFamily, key: &B::Key, value: &[u8]) -> Result<()>;
     fn delete_cf(&mut self, cf: B::ColumnFamily, key: &B::Key) -> Result<()>;
+    fn size_in_bytes(&self) -> usize;
 }
 
 pub trait TypedColumn<B>: Column<B>
@@ -163,8 +188,8 @@ impl<B> Database<B>
 where
     B: Backend,
 {
-    pub fn open(path: &Path) -> Result<Self> {
-        let backend = Arc::new(B::open(path)?);
+    pub fn open(path: &Path, wal_config: &WalConfig) -> Result<Self> {
+        let backend = Arc::new(B::open(path, wal_config)?);
 
         Ok(Database { backend })
     }
@@ -175,6 +200,10 @@ where
         Ok(())
     }
 
+    pub fn sync_wal(&self) -> Result<()> {
+        self.backend.sync_wal()
+    }
+
     pub fn get_bytes<C>(&self, key: C::Index) -> Result<Option<Vec<u8>>>
     where
         C: Column<B>,
@@ -333,6 +362,10 @@ where
         self.db_cursor.seek_to_first();
     }
 
+    pub fn seek_to_last(&mut self) {
+        self.db_cursor.seek_to_last();
+    }
+
     pub fn next(&mut self) {
         self.db_cursor.next();
     }
@@ -465,6 +498,11 @@ where
             .put_cf(self.get_cf::<C>(), C::key(key).borrow(), &serialized_value)
     }
 
+    /// Bytes the batch will write, keys and values of every column included
+    pub fn size_in_bytes(&self) -> usize {
+        self.write_batch.size_in_bytes()
+    }
+
     #[inline]
     fn get_cf<C: Column<B>>(&self) -> B::ColumnFamily {
         self.map[C::NAME].clone()
@@ -1,8 +1,10 @@
 use crate::blockBufferPool::db::columns as cf;
-use crate::blockBufferPool::db::{Backend, Column, DbCursor, IWriteBatch, TypedColumn};
+use crate::blockBufferPool::db::{Backend, Column, DbCursor, IWriteBatch, TypedColumn, WalConfig};
 use crate::blockBufferPool::BlocktreeError;
 use crate::result::{Error, Result};
 use byteorder::{BigEndian, ByteOrder};
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::Signature;
 use morgan_kvstore::{self as kvstore, Key, KvStore};
 use std::path::Path;
 
@@ -24,7 +26,7 @@ impl Backend for Kvs {
     type WriteBatch = Dummy;
     type Error = kvstore::Error;
 
-    fn open(_path: &Path) -> Result<Kvs> {
+    fn open(_path: &Path, _wal_config: &WalConfig) -> Result<Kvs> {
         unimplemented!()
     }
 
@@ -67,6 +69,10 @@ impl Backend for Kvs {
     fn write(&self, _batch: Dummy) -> Result<()> {
         unimplemented!()
     }
+
+    fn sync_wal(&self) -> Result<()> {
+        unimplemented!()
+    }
 }
 
 impl Column<Kvs> for cf::Coding {
@@ -138,6 +144,42 @@ impl TypedColumn<Kvs> for cf::Root {
     type Type = bool;
 }
 
+// kvstore keys are too short to hold an address and a signature, the address index isn't
+// kept by this backend
+impl Column<Kvs> for cf::AddressSignatures {
+    const NAME: &'static str = super::ADDRESS_SIGNATURES_CF;
+    type Index = (Pubkey, u64, Signature);
+
+    fn key(_index: (Pubkey, u64, Signature)) -> Key {
+        unimplemented!()
+    }
+
+    fn index(_key: &Key) -> (Pubkey, u64, Signature) {
+        unimplemented!()
+    }
+}
+
+impl TypedColumn<Kvs> for cf::AddressSignatures {
+    type Type = bool;
+}
+
+impl Column<Kvs> for cf::SignatureSlots {
+    const NAME: &'static str = super::SIGNATURE_SLOTS_CF;
+    type Index = Signature;
+
+    fn key(_signature: Signature) -> Key {
+        unimplemented!()
+    }
+
+    fn index(_key: &Key) -> Signature {
+        unimplemented!()
+    }
+}
+
+impl TypedColumn<Kvs> for cf::SignatureSlots {
+    type Type = u64;
+}
+
 impl Column<Kvs> for cf::SlotMeta {
     const NAME: &'static str = super::META_CF;
     type Index = u64;
@@ -207,6 +249,10 @@ impl DbCursor<Kvs> for Dummy {
         unimplemented!()
     }
 
+    fn seek_to_last(&mut self) {
+        unimplemented!()
+    }
+
     fn next(&mut self) {
         unimplemented!()
     }
@@ -228,6 +274,10 @@ impl IWriteBatch<Kvs> for Dummy {
     fn delete_cf(&mut self, _cf: ColumnFamily, _key: &Key) -> Result<()> {
         unimplemented!()
     }
+
+    fn size_in_bytes(&self) -> usize {
+        unimplemented!()
+    }
 }
 
 impl Iterator for Dummy {
@@ -1,13 +1,16 @@
 use crate::blockBufferPool::db::columns as cf;
-use crate::blockBufferPool::db::{Backend, Column, DbCursor, IWriteBatch, TypedColumn};
+use crate::blockBufferPool::db::{Backend, Column, DbCursor, IWriteBatch, TypedColumn, WalConfig};
 use crate::blockBufferPool::BlocktreeError;
 use crate::result::{Error, Result};
 
 use byteorder::{BigEndian, ByteOrder};
 
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::Signature;
+
 use rocksdb::{
-    self, ColumnFamily, ColumnFamilyDescriptor, DBIterator, DBRawIterator, Direction, IteratorMode,
-    Options, WriteBatch as RWriteBatch, DB,
+    self, ColumnFamily, ColumnFamilyDescriptor, DBIterator, DBRawIterator, DBRecoveryMode,
+    Direction, IteratorMode, Options, WriteBatch as RWriteBatch, WriteOptions, DB,
 };
 
 use std::fs;
@@ -18,7 +21,7 @@ const TOTAL_THREADS: i32 = 8;
 const MAX_WRITE_BUFFER_SIZE: usize = 512 * 1024 * 1024;
 
 #[derive(Debug)]
-pub struct Rocks(rocksdb::DB);
+pub struct Rocks(rocksdb::DB, WalConfig);
 
 impl Backend for Rocks {
     type Key = [u8];
@@ -29,13 +32,15 @@ impl Backend for Rocks {
     type WriteBatch = RWriteBatch;
     type Error = rocksdb::Error;
 
-    fn open(path: &Path) -> Result<Rocks> {
-        use crate::blockBufferPool::db::columns::{Coding, Data, ErasureMeta, Orphans, Root, SlotMeta};
+    fn open(path: &Path, wal_config: &WalConfig) -> Result<Rocks> {
+        use crate::blockBufferPool::db::columns::{
+            AddressSignatures, Coding, Data, ErasureMeta, Orphans, Root, SignatureSlots, SlotMeta,
+        };
 
         fs::create_dir_all(&path)?;
 
         // Use default database options
-        let db_options = get_db_options();
+        let db_options = get_db_options(wal_config);
 
         // Column family names
         let meta_cf_descriptor = ColumnFamilyDescriptor::new(SlotMeta::NAME, get_cf_options());
@@ -45,6 +50,10 @@ impl Backend for Rocks {
             ColumnFamilyDescriptor::new(ErasureMeta::NAME, get_cf_options());
         let orphans_cf_descriptor = ColumnFamilyDescriptor::new(Orphans::NAME, get_cf_options());
         let root_cf_descriptor = ColumnFamilyDescriptor::new(Root::NAME, get_cf_options());
+        let address_signatures_cf_descriptor =
+            ColumnFamilyDescriptor::new(AddressSignatures::NAME, get_cf_options());
+        let signature_slots_cf_descriptor =
+            ColumnFamilyDescriptor::new(SignatureSlots::NAME, get_cf_options());
 
         let cfs = vec![
             meta_cf_descriptor,
@@ -53,23 +62,32 @@ impl Backend for Rocks {
             erasure_meta_cf_descriptor,
             orphans_cf_descriptor,
             root_cf_descriptor,
+            address_signatures_cf_descriptor,
+            signature_slots_cf_descriptor,
         ];
 
         // Open the database
-        let db = Rocks(DB::open_cf_descriptors(&db_options, path, cfs)?);
+        let db = Rocks(
+            DB::open_cf_descriptors(&db_options, path, cfs)?,
+            *wal_config,
+        );
 
         Ok(db)
     }
 
     fn columns(&self) -> Vec<&'static str> {
-        use crate::blockBufferPool::db::columns::{Coding, Data, ErasureMeta, Orphans, Root, SlotMeta};
+        use crate::blockBufferPool::db::columns::{
+            AddressSignatures, Coding, Data, ErasureMeta, Orphans, Root, SignatureSlots, SlotMeta,
+        };
 
         vec![
+            AddressSignatures::NAME,
             Coding::NAME,
             ErasureMeta::NAME,
             Data::NAME,
             Orphans::NAME,
             Root::NAME,
+            SignatureSlots::NAME,
             SlotMeta::NAME,
         ]
     }
@@ -125,7 +143,17 @@ impl Backend for Rocks {
     }
 
     fn write(&self, batch: RWriteBatch) -> Result<()> {
-        self.0.write(batch)?;
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(self.1.sync);
+        self.0.write_opt(batch, &write_options)?;
+        Ok(())
+    }
+
+    fn sync_wal(&self) -> Result<()> {
+        // A synced write also syncs the log entries of every write before it
+        let mut write_options = WriteOptions::default();
+        write_options.set_sync(true);
+        self.0.write_opt(RWriteBatch::default(), &write_options)?;
         Ok(())
     }
 }
@@ -199,6 +227,48 @@ impl TypedColumn<Rocks> for cf::Root {
     type Type = bool;
 }
 
+impl Column<Rocks> for cf::AddressSignatures {
+    const NAME: &'static str = super::ADDRESS_SIGNATURES_CF;
+    type Index = (Pubkey, u64, Signature);
+
+    // The slot is stored inverted so that iterating an address goes from the newest slot back
+    fn key((address, slot, signature): (Pubkey, u64, Signature)) -> Vec<u8> {
+        let mut key = vec![0; 104];
+        key[..32].copy_from_slice(address.as_ref());
+        BigEndian::write_u64(&mut key[32..40], !slot);
+        key[40..].copy_from_slice(signature.as_ref());
+        key
+    }
+
+    fn index(key: &[u8]) -> (Pubkey, u64, Signature) {
+        let address = Pubkey::new(&key[..32]);
+        let slot = !BigEndian::read_u64(&key[32..40]);
+        let signature = Signature::new(&key[40..104]);
+        (address, slot, signature)
+    }
+}
+
+impl TypedColumn<Rocks> for cf::AddressSignatures {
+    type Type = bool;
+}
+
+impl Column<Rocks> for cf::SignatureSlots {
+    const NAME: &'static str = super::SIGNATURE_SLOTS_CF;
+    type Index = Signature;
+
+    fn key(signature: Signature) -> Vec<u8> {
+        signature.as_ref().to_vec()
+    }
+
+    fn index(key: &[u8]) -> Signature {
+        Signature::new(&key[..64])
+    }
+}
+
+impl TypedColumn<Rocks> for cf::SignatureSlots {
+    type Type = u64;
+}
+
 impl Column<Rocks> for cf::SlotMeta {
     const NAME: &'static str = super::META_CF;
     type Index = u64;
@@ -254,6 +324,10 @@ impl DbCursor<Rocks> for DBRawIterator {
         DBRawIterator::seek_to_first(self);
     }
 
+    fn seek_to_last(&mut self) {
+        DBRawIterator::seek_to_last(self);
+    }
+
     fn next(&mut self) {
         DBRawIterator::next(self);
     }
@@ -277,6 +351,10 @@ impl IWriteBatch<Rocks> for RWriteBatch {
         RWriteBatch::delete_cf(self, cf, key)?;
         Ok(())
     }
+
+    fn size_in_bytes(&self) -> usize {
+        RWriteBatch::size_in_bytes(self)
+    }
 }
 
 impl std::convert::From<rocksdb::Error> for Error {
@@ -293,8 +371,12 @@ fn get_cf_options() -> Options {
     options
 }
 
-fn get_db_options() -> Options {
+fn get_db_options(wal_config: &WalConfig) -> Options {
     let mut options = Options::default();
+    options.set_bytes_per_sync(wal_config.bytes_per_sync);
+    if wal_config.point_in_time_recovery {
+        options.set_wal_recovery_mode(DBRecoveryMode::PointInTime);
+    }
     options.create_if_missing(true);
     options.create_missing_column_families(true);
     options.increase_parallelism(TOTAL_THREADS);
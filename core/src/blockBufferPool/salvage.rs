@@ -0,0 +1,231 @@
+//! Recovery pass run when a ledger is opened in recovery mode.  A machine that crashed
+//! mid-write can leave slots whose metadata no longer matches their blobs, or blobs that
+//! were only partially written; replaying such a slot panics.  The pass finds those slots and
+//! cuts them back to their last consistent blob, so the validator starts and repairs the
+//! rest from the cluster.
+
+use super::*;
+use crate::packet::BLOB_SIZE;
+use std::fmt;
+
+/// What the recovery pass changed to make a slot consistent again
+#[derive(Debug, Clone, PartialEq)]
+pub enum SalvageAction {
+    /// The slot's metadata couldn't be read, so the whole slot was dropped
+    PurgedSlot { slot: u64, dropped_blobs: u64 },
+    /// Blobs from `index` on were missing, torn, or unaccounted for, and were dropped
+    TruncatedSlot {
+        slot: u64,
+        index: u64,
+        dropped_blobs: u64,
+    },
+}
+
+impl fmt::Display for SalvageAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SalvageAction::PurgedSlot {
+                slot,
+                dropped_blobs,
+            } => write!(
+                f,
+                "purged slot {} with unreadable metadata, dropped {} blobs",
+                slot, dropped_blobs
+            ),
+            SalvageAction::TruncatedSlot {
+                slot,
+                index,
+                dropped_blobs,
+            } => write!(
+                f,
+                "truncated slot {} at blob {}, dropped {} blobs",
+                slot, index, dropped_blobs
+            ),
+        }
+    }
+}
+
+/// True if `bytes`, stored under (`slot`, `index`), hold a whole blob with readable entries
+fn is_blob_intact(slot: u64, index: u64, bytes: &[u8]) -> bool {
+    if bytes.len() < BLOB_HEADER_SIZE || bytes.len() > BLOB_SIZE {
+        return false;
+    }
+    let blob = Blob::new(bytes);
+    blob.slot() == slot
+        && blob.index() == index
+        && blob.data_size() as usize == bytes.len()
+        && Blocktree::deserialize_blob_data(&bytes[BLOB_HEADER_SIZE..]).is_ok()
+}
+
+/// The first blob of a slot that doesn't agree with `meta`: one missing below `consumed`,
+/// one that is torn, or one the metadata doesn't account for
+fn find_truncation_index<I>(slot: u64, meta: &SlotMeta, blobs: I) -> Option<u64>
+where
+    I: Iterator<Item = (u64, Box<[u8]>)>,
+{
+    let mut next = 0;
+    for (index, bytes) in blobs {
+        if index > next && next < meta.consumed {
+            return Some(next);
+        }
+        if index >= meta.received
+            || (meta.last_index != std::u64::MAX && index > meta.last_index)
+            || !is_blob_intact(slot, index, &bytes)
+        {
+            return Some(index);
+        }
+        next = index + 1;
+    }
+    if next < meta.consumed {
+        Some(next)
+    } else {
+        None
+    }
+}
+
+impl Blocktree {
+    /// Make every slot consistent with its blobs, returning what had to be dropped
+    pub(super) fn salvage(&self) -> Result<Vec<SalvageAction>> {
+        let mut actions = vec![];
+        let mut metas = HashMap::new();
+        let mut unreadable_slots = vec![];
+        for (slot, bytes) in self.db.iter::<cf::SlotMeta>(None)? {
+            match deserialize::<SlotMeta>(&bytes) {
+                Ok(meta) => {
+                    metas.insert(slot, meta);
+                }
+                Err(_) => unreadable_slots.push(slot),
+            }
+        }
+
+        let mut batch_processor = unsafe { self.db.batch_processor() };
+        let mut write_batch = batch_processor.batch()?;
+        let mut updated_metas = HashMap::new();
+        let mut disconnected_slots = vec![];
+
+        for slot in unreadable_slots {
+            let dropped_blobs = self.drop_blobs(&mut write_batch, slot, 0)?;
+            write_batch.delete::<cf::SlotMeta>(slot)?;
+            for (child, meta) in metas.iter().filter(|(_, meta)| meta.parent_slot == slot) {
+                // the child's parent is gone until repair fetches it again
+                write_batch.put::<cf::Orphans>(*child, &true)?;
+                if meta.is_connected {
+                    disconnected_slots.push(*child);
+                }
+            }
+            actions.push(SalvageAction::PurgedSlot {
+                slot,
+                dropped_blobs,
+            });
+        }
+
+        let mut slots: Vec<_> = metas.keys().cloned().collect();
+        slots.sort();
+        for slot in slots {
+            let meta = &metas[&slot];
+            let blobs = self
+                .slot_data_iterator(slot)?
+                .map(|((_, index), bytes)| (index, bytes));
+            let index = match find_truncation_index(slot, meta, blobs) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            let dropped_blobs = self.drop_blobs(&mut write_batch, slot, index)?;
+            let mut meta = meta.clone();
+            meta.consumed = cmp::min(meta.consumed, index);
+            meta.received = self
+                .slot_data_iterator(slot)?
+                .map(|((_, blob_index), _)| blob_index + 1)
+                .take_while(|received| *received <= index)
+                .last()
+                .unwrap_or(0);
+            if meta.last_index != std::u64::MAX && meta.last_index >= index {
+                meta.last_index = std::u64::MAX;
+            }
+            if meta.is_connected && !meta.is_full() {
+                meta.is_connected = slot == 0;
+                disconnected_slots.extend(meta.next_slots.iter().cloned());
+            }
+            updated_metas.insert(slot, meta);
+            actions.push(SalvageAction::TruncatedSlot {
+                slot,
+                index,
+                dropped_blobs,
+            });
+        }
+
+        // descendants of a slot that is no longer full aren't connected either
+        while let Some(slot) = disconnected_slots.pop() {
+            let meta = match updated_metas.get(&slot).or_else(|| metas.get(&slot)) {
+                Some(meta) if meta.is_connected => meta.clone(),
+                _ => continue,
+            };
+            disconnected_slots.extend(meta.next_slots.iter().cloned());
+            updated_metas.insert(
+                slot,
+                SlotMeta {
+                    is_connected: false,
+                    ..meta
+                },
+            );
+        }
+        for (slot, meta) in &updated_metas {
+            write_batch.put::<cf::SlotMeta>(*slot, meta)?;
+        }
+
+        batch_processor.write(write_batch)?;
+        Ok(actions)
+    }
+
+    /// Delete the data blobs of `slot` from `index` on, along with their presence in the
+    /// erasure metadata.  Dropping a slot from its first blob deletes its coding blobs too.
+    pub(super) fn drop_blobs(
+        &self,
+        write_batch: &mut WriteBatch,
+        slot: u64,
+        index: u64,
+    ) -> Result<u64> {
+        let mut erasure_metas = HashMap::new();
+        let mut dropped_blobs = 0;
+        for ((_, blob_index), _) in self.slot_data_iterator(slot)? {
+            if blob_index < index {
+                continue;
+            }
+            write_batch.delete::<cf::Data>((slot, blob_index))?;
+            dropped_blobs += 1;
+
+            let set_index = ErasureMeta::set_index_for(blob_index);
+            if !erasure_metas.contains_key(&set_index) {
+                if let Some(erasure_meta) = self.erasure_meta(slot, set_index)? {
+                    erasure_metas.insert(set_index, erasure_meta);
+                }
+            }
+            if let Some(erasure_meta) = erasure_metas.get_mut(&set_index) {
+                erasure_meta.set_data_present(blob_index, false);
+            }
+        }
+
+        if index == 0 {
+            let coding_blobs = self
+                .db
+                .iter::<cf::Coding>(Some((slot, 0)))?
+                .take_while(|((blob_slot, _), _)| *blob_slot == slot);
+            for (key, _) in coding_blobs {
+                write_batch.delete::<cf::Coding>(key)?;
+            }
+            let erasure_meta_keys = self
+                .db
+                .iter::<cf::ErasureMeta>(Some((slot, 0)))?
+                .take_while(|((meta_slot, _), _)| *meta_slot == slot);
+            for (key, _) in erasure_meta_keys {
+                write_batch.delete::<cf::ErasureMeta>(key)?;
+            }
+        } else {
+            for (set_index, erasure_meta) in &erasure_metas {
+                write_batch.put::<cf::ErasureMeta>((slot, *set_index), erasure_meta)?;
+            }
+        }
+        Ok(dropped_blobs)
+    }
+}
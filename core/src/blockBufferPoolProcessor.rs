@@ -3,15 +3,16 @@ use crate::treasuryForks::BankForks;
 use crate::blockBufferPool::Blocktree;
 use crate::entryInfo::{Entry, EntrySlice};
 use crate::leaderArrangeCache::LeaderScheduleCache;
+use crate::startupProgress::{StartupPhase, StartupProgress};
 use rayon::prelude::*;
+use rayon::ThreadPool;
 use morgan_metricbot::{datapoint, datapoint_error, inc_new_counter_debug};
 use morgan_runtime::bank::Bank;
 use morgan_runtime::locked_accounts_results::LockedAccountsResults;
 use morgan_interface::genesis_block::GenesisBlock;
 use morgan_interface::timing::duration_as_ms;
-use morgan_interface::timing::MAX_RECENT_BLOCKHASHES;
 use morgan_interface::transaction::Result;
-use morgan_interface::transaction::Transaction;
+use morgan_interface::transaction::{Transaction, TransactionError};
 use std::result;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -26,18 +27,21 @@ fn first_err(results: &[Result<()>]) -> Result<()> {
     Ok(())
 }
 
+/// A transaction error and the index of the entry it came from
+type EntryResult = result::Result<(), (usize, TransactionError)>;
+
 fn par_execute_entries(
     bank: &Bank,
-    entries: &[(&Entry, LockedAccountsResults<Transaction>)],
-) -> Result<()> {
+    entries: &[(usize, &Entry, LockedAccountsResults<Transaction>)],
+) -> EntryResult {
     inc_new_counter_debug!("bank-par_execute_entries-count", entries.len());
-    let results: Vec<Result<()>> = entries
+    let results: Vec<EntryResult> = entries
         .into_par_iter()
-        .map(|(e, locked_accounts)| {
+        .map(|(index, e, locked_accounts)| {
             let results = bank.load_execute_and_commit_transactions(
                 &e.transactions,
                 locked_accounts,
-                MAX_RECENT_BLOCKHASHES,
+                bank.runtime_params().max_recent_blockhashes,
             );
             let mut first_err = None;
             for (r, tx) in results.iter().zip(e.transactions.iter()) {
@@ -61,11 +65,14 @@ fn par_execute_entries(
                     }
                 }
             }
-            first_err.unwrap_or(Ok(()))
+            first_err.unwrap_or(Ok(())).map_err(|err| (*index, err))
         })
         .collect();
 
-    first_err(&results)
+    results
+        .into_iter()
+        .find(|result| result.is_err())
+        .unwrap_or(Ok(()))
 }
 
 /// Process an ordered list of entries in parallel
@@ -74,9 +81,14 @@ fn par_execute_entries(
 /// 3. Register the `Tick` if it's available
 /// 4. Update the leader scheduler, goto 1
 pub fn process_entries(bank: &Bank, entries: &[Entry]) -> Result<()> {
+    process_entries_find_failed(bank, entries).map_err(|(_, err)| err)
+}
+
+/// Like `process_entries`, but a failure also says which of `entries` failed
+pub fn process_entries_find_failed(bank: &Bank, entries: &[Entry]) -> EntryResult {
     // accumulator for entries that can be processed in parallel
     let mut mt_group = vec![];
-    for entry in entries {
+    for (index, entry) in entries.iter().enumerate() {
         if entry.is_tick() {
             // if its a tick, execute the group and register the tick
             par_execute_entries(bank, &mt_group)?;
@@ -94,7 +106,7 @@ pub fn process_entries(bank: &Bank, entries: &[Entry]) -> Result<()> {
             // if locking worked
             if first_lock_err.is_ok() {
                 // push the entry to the mt_group
-                mt_group.push((entry, lock_results));
+                mt_group.push((index, entry, lock_results));
                 // done with this entry
                 break;
             }
@@ -114,7 +126,7 @@ pub fn process_entries(bank: &Bank, entries: &[Entry]) -> Result<()> {
                     )
                 );
                 // bail
-                first_lock_err?;
+                first_lock_err.map_err(|err| (index, err))?;
             } else {
                 // else we have an entry that conflicts with a prior entry
                 // execute the current queue and try to process this entry again
@@ -142,20 +154,76 @@ pub fn process_blocktree(
     genesis_block: &GenesisBlock,
     blocktree: &Blocktree,
     account_paths: Option<String>,
+    index_memory_budget: Option<usize>,
+    execution_pool: Option<Arc<ThreadPool>>,
+    progress: Option<&StartupProgress>,
+) -> result::Result<(BankForks, Vec<BankForksInfo>, LeaderScheduleCache), BlocktreeProcessorError> {
+    // Setup bank for slot 0
+    let mut bank =
+        Bank::new_with_index_memory_budget(&genesis_block, account_paths, index_memory_budget);
+    bank.set_execution_pool(execution_pool);
+    let bank = Arc::new(bank);
+    blocktree.set_root(0, 0).expect("Couldn't set first root");
+    process_blocktree_from_root(blocktree, bank, progress)
+}
+
+/// Check that `bank`, restored at a root instead of replayed, agrees with the ledger: its slot
+/// must be a full root of `blocktree` whose last entry hash is the bank's last blockhash
+fn verify_root_bank(
+    blocktree: &Blocktree,
+    bank: &Bank,
+) -> result::Result<(), BlocktreeProcessorError> {
+    let slot = bank.slot();
+    let last_entry_hash = match blocktree.meta(slot) {
+        Ok(Some(ref meta)) if meta.is_full() && blocktree.is_root(slot) => blocktree
+            .get_slot_entries(slot, 0, None)
+            .ok()
+            .and_then(|entries| entries.last().map(|entry| entry.hash)),
+        _ => None,
+    };
+    if last_entry_hash != Some(bank.last_blockhash()) {
+        println!(
+            "{}",
+            Warn(
+                format!(
+                    "Root bank for slot {} doesn't match the ledger: blockhash {} ledger {:?}",
+                    slot,
+                    bank.last_blockhash(),
+                    last_entry_hash
+                ),
+                module_path!().to_string()
+            )
+        );
+        return Err(BlocktreeProcessorError::LedgerVerificationFailed);
+    }
+    Ok(())
+}
+
+/// Replay the full slots of `blocktree` descending from `bank`, which is either the unprocessed
+/// bank for slot 0 or a frozen bank restored at a root, e.g. from a snapshot. A restored root is
+/// cross-checked against the ledger instead of being replayed, and entry heights then count
+/// from the end of its slot. The slot being replayed is reported to `progress`.
+pub fn process_blocktree_from_root(
+    blocktree: &Blocktree,
+    bank: Arc<Bank>,
+    progress: Option<&StartupProgress>,
 ) -> result::Result<(BankForks, Vec<BankForksInfo>, LeaderScheduleCache), BlocktreeProcessorError> {
     let now = Instant::now();
+    let root_slot = bank.slot();
+    let restored_root = bank.is_frozen();
     // info!("{}", Info(format!("processing ledger...").to_string()));
-    let loginfo: String = format!("processing ledger...").to_string();
-    println!("{}",
+    println!(
+        "{}",
         printLn(
-            loginfo,
+            format!("processing ledger from slot {}...", root_slot),
             module_path!().to_string()
         )
     );
-    // Setup bank for slot 0
+    if restored_root {
+        verify_root_bank(blocktree, &bank)?;
+    }
     let mut pending_slots = {
-        let slot = 0;
-        let bank = Arc::new(Bank::new_with_paths(&genesis_block, account_paths));
+        let slot = root_slot;
         let entry_height = 0;
         let last_entry_hash = bank.last_blockhash();
 
@@ -178,17 +246,28 @@ pub fn process_blocktree(
         vec![(slot, meta, bank, entry_height, last_entry_hash)]
     };
 
-    blocktree.set_root(0, 0).expect("Couldn't set first root");
+    let leader_schedule_cache =
+        LeaderScheduleCache::new(*pending_slots[0].2.epoch_schedule(), root_slot);
 
-    let leader_schedule_cache = LeaderScheduleCache::new(*pending_slots[0].2.epoch_schedule(), 0);
+    // the highest slot the ledger holds, only looked up if there's someone to report to
+    let max_slot = progress.map_or(root_slot, |_| {
+        blocktree
+            .slot_meta_iterator(root_slot)
+            .ok()
+            .and_then(|metas| metas.map(|(slot, _)| slot).last())
+            .unwrap_or(root_slot)
+    });
 
     let mut fork_info = vec![];
     let mut last_status_report = Instant::now();
-    let mut root = 0;
+    let mut root = root_slot;
     while !pending_slots.is_empty() {
         let (slot, meta, bank, mut entry_height, mut last_entry_hash) =
             pending_slots.pop().unwrap();
 
+        if let Some(progress) = progress {
+            progress.set_phase(StartupPhase::ProcessingLedger { slot, max_slot });
+        }
         if last_status_report.elapsed() > Duration::from_secs(2) {
             // info!("{}", Info(format!("processing ledger...block {}", slot).to_string()));
             let loginfo: String = format!("processing ledger...block {}", slot).to_string();
@@ -201,20 +280,25 @@ pub fn process_blocktree(
             last_status_report = Instant::now();
         }
 
-        // Fetch all entries for this slot
-        let mut entries = blocktree.get_slot_entries(slot, 0, None).map_err(|err| {
-            // warn!("Failed to load entries for slot {}: {:?}", slot, err);
-            println!(
-                "{}",
-                Warn(
-                    format!("Failed to load entries for slot {}: {:?}", slot, err).to_string(),
-                    module_path!().to_string()
-                )
-            );
-            BlocktreeProcessorError::LedgerVerificationFailed
-        })?;
+        // Fetch all entries for this slot, a restored root already holds the state they produce
+        let replay = !(restored_root && slot == root_slot);
+        let mut entries = if replay {
+            blocktree.get_slot_entries(slot, 0, None).map_err(|err| {
+                // warn!("Failed to load entries for slot {}: {:?}", slot, err);
+                println!(
+                    "{}",
+                    Warn(
+                        format!("Failed to load entries for slot {}: {:?}", slot, err),
+                        module_path!().to_string()
+                    )
+                );
+                BlocktreeProcessorError::LedgerVerificationFailed
+            })?
+        } else {
+            vec![]
+        };
 
-        if slot == 0 {
+        if slot == 0 && replay {
             // The first entry in the ledger is a pseudo-tick used only to ensure the number of ticks
             // in slot 0 is the same as the number of ticks in all subsequent slots.  It is not
             // processed by the bank, skip over it.
@@ -281,6 +365,18 @@ pub fn process_blocktree(
         bank.freeze(); // all banks handled by this routine are created from complete slots
 
         if blocktree.is_root(slot) {
+            blocktree
+                .index_root_signatures(slot, &entries)
+                .map_err(|err| {
+                    println!(
+                        "{}",
+                        Warn(
+                            format!("Failed to index signatures for slot {}: {:?}", slot, err),
+                            module_path!().to_string()
+                        )
+                    );
+                    BlocktreeProcessorError::LedgerVerificationFailed
+                })?;
             root = slot;
             leader_schedule_cache.set_root(slot);
             bank.squash();
@@ -481,7 +577,7 @@ pub mod tests {
         fill_blocktree_slot_with_ticks(&blocktree, ticks_per_slot, 2, 1, blockhash);
 
         let (mut _bank_forks, bank_forks_info, _) =
-            process_blocktree(&genesis_block, &blocktree, None).unwrap();
+            process_blocktree(&genesis_block, &blocktree, None, None, None, None).unwrap();
 
         assert_eq!(bank_forks_info.len(), 1);
         assert_eq!(
@@ -553,7 +649,7 @@ pub mod tests {
         blocktree.set_root(4, 0).unwrap();
 
         let (bank_forks, bank_forks_info, _) =
-            process_blocktree(&genesis_block, &blocktree, None).unwrap();
+            process_blocktree(&genesis_block, &blocktree, None, None, None, None).unwrap();
 
         assert_eq!(bank_forks_info.len(), 1); // One fork, other one is ignored b/c not a descendant of the root
 
@@ -641,7 +737,7 @@ pub mod tests {
         blocktree.set_root(1, 0).unwrap();
 
         let (bank_forks, bank_forks_info, _) =
-            process_blocktree(&genesis_block, &blocktree, None).unwrap();
+            process_blocktree(&genesis_block, &blocktree, None, None, None, None).unwrap();
 
         assert_eq!(bank_forks_info.len(), 2); // There are two forks
         assert_eq!(
@@ -721,7 +817,7 @@ pub mod tests {
 
         // Check that we can properly restart the ledger / leader scheduler doesn't fail
         let (bank_forks, bank_forks_info, _) =
-            process_blocktree(&genesis_block, &blocktree, None).unwrap();
+            process_blocktree(&genesis_block, &blocktree, None, None, None, None).unwrap();
 
         assert_eq!(bank_forks_info.len(), 1); // There is one fork
         assert_eq!(
@@ -741,6 +837,64 @@ pub mod tests {
             .is_empty());
     }
 
+    #[test]
+    fn test_process_blocktree_from_root() {
+        morgan_logger::setup();
+
+        let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(10_000);
+        let ticks_per_slot = genesis_block.ticks_per_slot;
+
+        // Create a single chain of slots 0 to 4, rooted at slot 2
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree =
+            Blocktree::open(&ledger_path).expect("Expected to successfully open database ledger");
+        let mut last_entry_hash = blockhash;
+        for slot in 1..=4 {
+            last_entry_hash = fill_blocktree_slot_with_ticks(
+                &blocktree,
+                ticks_per_slot,
+                slot,
+                slot - 1,
+                last_entry_hash,
+            );
+        }
+        blocktree.set_root(2, 0).unwrap();
+
+        let (bank_forks, _, _) =
+            process_blocktree(&genesis_block, &blocktree, None, None, None, None).unwrap();
+        let root_bank = bank_forks[4]
+            .parents()
+            .into_iter()
+            .find(|bank| bank.slot() == 2)
+            .unwrap();
+
+        // Starting from the root only replays the slots after it
+        let (restored_bank_forks, bank_forks_info, _) =
+            process_blocktree_from_root(&blocktree, root_bank, None).unwrap();
+        assert_eq!(
+            bank_forks_info,
+            vec![BankForksInfo {
+                bank_slot: 4,
+                entry_height: ticks_per_slot * 2,
+            }]
+        );
+        assert_eq!(
+            restored_bank_forks[4].last_blockhash(),
+            bank_forks[4].last_blockhash()
+        );
+
+        // A root bank that doesn't match the ledger is rejected
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bad_root_bank = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 2));
+        bad_root_bank.freeze();
+        assert!(process_blocktree_from_root(&blocktree, bad_root_bank, None).is_err());
+
+        // So is a root bank for a slot the ledger hasn't rooted
+        let unrooted_bank = bank_forks[4].parents()[0].clone();
+        assert_eq!(unrooted_bank.slot(), 3);
+        assert!(process_blocktree_from_root(&blocktree, unrooted_bank, None).is_err());
+    }
+
     #[test]
     fn test_first_err() {
         assert_eq!(first_err(&[Ok(())]), Ok(()));
@@ -856,8 +1010,23 @@ pub mod tests {
             .write_entries(1, 0, 0, genesis_block.ticks_per_slot, &entries)
             .unwrap();
         let entry_height = genesis_block.ticks_per_slot + entries.len() as u64;
-        let (bank_forks, bank_forks_info, _) =
-            process_blocktree(&genesis_block, &blocktree, None).unwrap();
+        let progress = StartupProgress::default();
+        let (bank_forks, bank_forks_info, _) = process_blocktree(
+            &genesis_block,
+            &blocktree,
+            None,
+            None,
+            None,
+            Some(&progress),
+        )
+        .unwrap();
+        assert_eq!(
+            progress.phase(),
+            StartupPhase::ProcessingLedger {
+                slot: 1,
+                max_slot: 1,
+            }
+        );
 
         assert_eq!(bank_forks_info.len(), 1);
         assert_eq!(bank_forks.root(), 0);
@@ -888,7 +1057,7 @@ pub mod tests {
 
         let blocktree = Blocktree::open(&ledger_path).unwrap();
         let (bank_forks, bank_forks_info, _) =
-            process_blocktree(&genesis_block, &blocktree, None).unwrap();
+            process_blocktree(&genesis_block, &blocktree, None, None, None, None).unwrap();
 
         assert_eq!(bank_forks_info.len(), 1);
         assert_eq!(
@@ -1296,9 +1465,16 @@ pub mod tests {
         );
         let entry_3 = next_entry(&entry_2.hash, 1, vec![tx]);
         assert_eq!(
-            process_entries(&bank, &[entry_3]),
+            process_entries(&bank, &[entry_3.clone()]),
             Err(TransactionError::AccountNotFound)
         );
+
+        // and which entry it came from
+        let tick = next_entry(&entry_2.hash, 1, vec![]);
+        assert_eq!(
+            process_entries_find_failed(&bank, &[tick, entry_3]),
+            Err((1, TransactionError::AccountNotFound))
+        );
     }
 
     #[test]
@@ -1,6 +1,6 @@
 //! The `blockstream` module provides a method for streaming entries out via a
-//! local unix socket, to provide client services such as a block explorer with
-//! real-time access to entries.
+//! local unix socket, a TCP connection or the stdin of an external process, to
+//! provide client services such as a block explorer with real-time access to entries.
 
 use crate::entryInfo::Entry;
 use crate::result::Result;
@@ -9,24 +9,69 @@ use chrono::{SecondsFormat, Utc};
 use serde_json::json;
 use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::Signature;
 use std::cell::RefCell;
+use std::io;
 use std::io::prelude::*;
-use std::net::Shutdown;
+use std::net::{Shutdown, SocketAddr, TcpStream};
 use std::os::unix::net::UnixStream;
 use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::time::Duration;
 use log::*;
 use morgan_helper::logHelper::*;
 
-pub trait EntryWriter: std::fmt::Debug {
+/// Somewhere blockstream events are written to, one JSON object per `write`
+pub trait BlockstreamSink: std::fmt::Debug + Send {
     fn write(&self, payload: String) -> Result<()>;
 }
 
+impl BlockstreamSink for Box<dyn BlockstreamSink> {
+    fn write(&self, payload: String) -> Result<()> {
+        (**self).write(payload)
+    }
+}
+
+/// Where the validator streams blockstream events to
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockstreamConfig {
+    /// A unix domain socket, connected to for every event
+    UnixSocket(String),
+    /// A TCP server, sent one JSON object per line over a single connection
+    Tcp(SocketAddr),
+    /// A shell command fed one JSON object per line on its stdin, for message bus clients
+    Process(String),
+}
+
+impl FromStr for BlockstreamConfig {
+    type Err = String;
+
+    /// `tcp://<host:port>`, `exec:<command>` or else the path of a unix domain socket
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.starts_with("tcp://") {
+            s["tcp://".len()..]
+                .parse()
+                .map(BlockstreamConfig::Tcp)
+                .map_err(|err| format!("invalid blockstream address {:?}: {:?}", s, err))
+        } else if s.starts_with("exec:") {
+            let command = s["exec:".len()..].trim();
+            if command.is_empty() {
+                return Err("blockstream command is empty".to_string());
+            }
+            Ok(BlockstreamConfig::Process(command.to_string()))
+        } else {
+            Ok(BlockstreamConfig::UnixSocket(s.to_string()))
+        }
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct EntryVec {
     values: RefCell<Vec<String>>,
 }
 
-impl EntryWriter for EntryVec {
+impl BlockstreamSink for EntryVec {
     fn write(&self, payload: String) -> Result<()> {
         self.values.borrow_mut().push(payload);
         Ok(())
@@ -52,7 +97,7 @@ pub struct EntrySocket {
 
 const MESSAGE_TERMINATOR: &str = "\n";
 
-impl EntryWriter for EntrySocket {
+impl BlockstreamSink for EntrySocket {
     fn write(&self, payload: String) -> Result<()> {
         let mut socket = UnixStream::connect(Path::new(&self.socket))?;
         socket.write_all(payload.as_bytes())?;
@@ -62,13 +107,143 @@ impl EntryWriter for EntrySocket {
     }
 }
 
+/// How long a `TcpSink` waits to connect, or for a write to go through, so a stalled
+/// consumer can't hold up the blockstream thread
+pub const TCP_SINK_TIMEOUT_MS: u64 = 1000;
+
+/// Streams JSON lines over one TCP connection, reconnecting after a failed write
+#[derive(Debug)]
+pub struct TcpSink {
+    addr: SocketAddr,
+    stream: RefCell<Option<TcpStream>>,
+}
+
+impl TcpSink {
+    pub fn new(addr: SocketAddr) -> Self {
+        TcpSink {
+            addr,
+            stream: RefCell::new(None),
+        }
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let timeout = Duration::from_millis(TCP_SINK_TIMEOUT_MS);
+        let stream = TcpStream::connect_timeout(&self.addr, timeout)?;
+        stream.set_write_timeout(Some(timeout))?;
+        Ok(stream)
+    }
+
+    fn write_line(stream: &mut TcpStream, payload: &str) -> io::Result<()> {
+        stream.write_all(payload.as_bytes())?;
+        stream.write_all(MESSAGE_TERMINATOR.as_bytes())
+    }
+}
+
+impl BlockstreamSink for TcpSink {
+    fn write(&self, payload: String) -> Result<()> {
+        let mut stream = self.stream.borrow_mut();
+        let reused = stream.is_some();
+        if !reused {
+            *stream = Some(self.connect()?);
+        }
+        let mut result = Self::write_line(stream.as_mut().unwrap(), &payload);
+        if result.is_err() && reused {
+            // The consumer may have dropped the connection since the last write, try once more
+            // on a fresh one
+            *stream = None;
+            *stream = Some(self.connect()?);
+            result = Self::write_line(stream.as_mut().unwrap(), &payload);
+        }
+        if result.is_err() {
+            *stream = None;
+        }
+        result?;
+        Ok(())
+    }
+}
+
+/// Pipes JSON lines into the stdin of a shell command, such as a Kafka or NATS
+/// producer, respawning it after it exits
+#[derive(Debug)]
+pub struct ProcessSink {
+    command: String,
+    child: RefCell<Option<Child>>,
+}
+
+impl ProcessSink {
+    pub fn new(command: String) -> Self {
+        ProcessSink {
+            command,
+            child: RefCell::new(None),
+        }
+    }
+
+    fn spawn(&self) -> Result<Child> {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.command)
+            .stdin(Stdio::piped())
+            .spawn()?;
+        println!(
+            "{}",
+            printLn(
+                format!(
+                    "blockstream: spawned {:?}, pid {}",
+                    self.command,
+                    child.id()
+                ),
+                module_path!().to_string()
+            )
+        );
+        Ok(child)
+    }
+}
+
+impl BlockstreamSink for ProcessSink {
+    fn write(&self, payload: String) -> Result<()> {
+        let mut child = self.child.borrow_mut();
+        let exited = match child.as_mut() {
+            Some(process) => process.try_wait()?.is_some(),
+            None => true,
+        };
+        if exited {
+            *child = Some(self.spawn()?);
+        }
+        let result = {
+            let stdin = child.as_mut().unwrap().stdin.as_mut().unwrap();
+            stdin
+                .write_all(payload.as_bytes())
+                .and_then(|_| stdin.write_all(MESSAGE_TERMINATOR.as_bytes()))
+        };
+        if result.is_err() {
+            if let Some(mut process) = child.take() {
+                let _ = process.kill();
+                let _ = process.wait();
+            }
+        }
+        result?;
+        Ok(())
+    }
+}
+
+impl Drop for ProcessSink {
+    fn drop(&mut self) {
+        if let Some(mut process) = self.child.borrow_mut().take() {
+            let _ = process.kill();
+            let _ = process.wait();
+        }
+    }
+}
+
 pub trait BlockstreamEvents {
+    /// `traces` pairs the entry's traced transactions with the trace ids their clients gave
     fn emit_entry_event(
         &self,
         slot: u64,
         tick_height: u64,
         leader_pubkey: &Pubkey,
         entries: &Entry,
+        traces: &[(Signature, String)],
     ) -> Result<()>;
     fn emit_block_event(
         &self,
@@ -80,13 +255,13 @@ pub trait BlockstreamEvents {
 }
 
 #[derive(Debug)]
-pub struct Blockstream<T: EntryWriter> {
+pub struct Blockstream<T: BlockstreamSink> {
     pub output: T,
 }
 
 impl<T> BlockstreamEvents for Blockstream<T>
 where
-    T: EntryWriter,
+    T: BlockstreamSink,
 {
     fn emit_entry_event(
         &self,
@@ -94,6 +269,7 @@ where
         tick_height: u64,
         leader_pubkey: &Pubkey,
         entry: &Entry,
+        traces: &[(Signature, String)],
     ) -> Result<()> {
         let transactions: Vec<Vec<u8>> = serialize_transactions(entry);
         let mut isVoteTx = false;
@@ -115,13 +291,38 @@ where
                     "transactions": transactions
                 });
                 let json_entry = serde_json::to_string(&stream_entry)?;
+                let json_traces = if traces.is_empty() {
+                    String::new()
+                } else {
+                    let traces: serde_json::Map<String, serde_json::Value> = traces
+                        .iter()
+                        .map(|(signature, trace_id)| (signature.to_string(), json!(trace_id)))
+                        .collect();
+                    format!(r#","traces":{}"#, serde_json::to_string(&traces)?)
+                };
+                // token transfer memos, for exchanges to attribute deposits by
+                let memos: serde_json::Map<String, serde_json::Value> = entry
+                    .transactions
+                    .iter()
+                    .filter_map(|tx| {
+                        morgan_token_api::message_memo(&tx.message)
+                            .map(|memo| (tx.signatures[0].to_string(), json!(memo)))
+                    })
+                    .collect();
+                let json_memos = if memos.is_empty() {
+                    String::new()
+                } else {
+                    format!(r#","memos":{}"#, serde_json::to_string(&memos)?)
+                };
                 let payload = format!(
-                    r#"{{"dt":"{}","t":"entry","s":{},"h":{},"l":"{:?}","entry":{}}}"#,
+                    r#"{{"dt":"{}","t":"entry","s":{},"h":{},"l":"{:?}","entry":{}{}{}}}"#,
                     Utc::now().to_rfc3339_opts(SecondsFormat::Nanos, true),
                     slot,
                     tick_height,
                     leader_pubkey,
                     json_entry,
+                    json_traces,
+                    json_memos,
                 );
                 // error!("{}", Error(format!("entry event: {:?}", entry).to_string()));
                 println!(
@@ -176,10 +377,23 @@ impl SocketBlockstream {
     }
 }
 
+pub type SinkBlockstream = Blockstream<Box<dyn BlockstreamSink>>;
+
+impl SinkBlockstream {
+    pub fn new(config: BlockstreamConfig) -> Self {
+        let output: Box<dyn BlockstreamSink> = match config {
+            BlockstreamConfig::UnixSocket(socket) => Box::new(EntrySocket { socket }),
+            BlockstreamConfig::Tcp(addr) => Box::new(TcpSink::new(addr)),
+            BlockstreamConfig::Process(command) => Box::new(ProcessSink::new(command)),
+        };
+        Blockstream { output }
+    }
+}
+
 pub type MockBlockstream = Blockstream<EntryVec>;
 
 impl MockBlockstream {
-    pub fn new(_: String) -> Self {
+    pub fn new(_: BlockstreamConfig) -> Self {
         Blockstream {
             output: EntryVec::new(),
         }
@@ -205,9 +419,18 @@ mod test {
     use chrono::{DateTime, FixedOffset};
     use serde_json::Value;
     use morgan_interface::hash::Hash;
+    use morgan_interface::instruction::{AccountMeta, Instruction};
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_transaction;
+    use morgan_interface::transaction::Transaction;
+    use morgan_token_api::TokenInstruction;
     use std::collections::HashSet;
+    use std::env;
+    use std::fs;
+    use std::io::BufReader;
+    use std::net::TcpListener;
+    use std::thread::sleep;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_serialize_transactions() {
@@ -228,9 +451,76 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_blockstream_config_from_str() {
+        assert_eq!(
+            "/tmp/blockstream.sock".parse(),
+            Ok(BlockstreamConfig::UnixSocket(
+                "/tmp/blockstream.sock".to_string()
+            ))
+        );
+        assert_eq!(
+            "tcp://127.0.0.1:8000".parse(),
+            Ok(BlockstreamConfig::Tcp("127.0.0.1:8000".parse().unwrap()))
+        );
+        assert!("tcp://localhost".parse::<BlockstreamConfig>().is_err());
+        assert_eq!(
+            "exec: nats pub blocks".parse(),
+            Ok(BlockstreamConfig::Process("nats pub blocks".to_string()))
+        );
+        assert!("exec: ".parse::<BlockstreamConfig>().is_err());
+    }
+
+    #[test]
+    fn test_tcp_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sink = TcpSink::new(listener.local_addr().unwrap());
+        sink.write("one".to_string()).unwrap();
+        sink.write("two".to_string()).unwrap();
+        drop(sink);
+
+        let (stream, _) = listener.accept().unwrap();
+        let lines: Vec<_> = BufReader::new(stream)
+            .lines()
+            .map(|line| line.unwrap())
+            .collect();
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn test_tcp_sink_write_timeout() {
+        // The consumer never reads, so the write stalls once the socket buffers fill up
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sink = TcpSink::new(listener.local_addr().unwrap());
+        let payload = "x".repeat(64 * 1024 * 1024);
+        let now = Instant::now();
+        assert!(sink.write(payload).is_err());
+        assert!(now.elapsed() < Duration::from_millis(10 * TCP_SINK_TIMEOUT_MS));
+        assert!(sink.stream.borrow().is_none());
+    }
+
+    #[test]
+    fn test_process_sink() {
+        let path = env::temp_dir().join(format!("test_process_sink-{}", Pubkey::new_rand()));
+        let sink = ProcessSink::new(format!("head -n 2 > {}", path.display()));
+        sink.write("one".to_string()).unwrap();
+        sink.write("two".to_string()).unwrap();
+
+        // head exits after two lines
+        let mut retries = 0;
+        let mut child = sink.child.borrow_mut();
+        while child.as_mut().unwrap().try_wait().unwrap().is_none() {
+            retries += 1;
+            assert!(retries < 100, "blockstream process didn't exit");
+            sleep(Duration::from_millis(50));
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo\n");
+        fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_blockstream() -> () {
-        let blockstream = MockBlockstream::new("test_stream".to_string());
+        let blockstream = MockBlockstream::new("test_stream".parse().unwrap());
         let ticks_per_slot = 5;
 
         let mut blockhash = Hash::default();
@@ -252,7 +542,7 @@ mod test {
             let entry = Entry::new(&mut blockhash, 1, vec![]); // just ticks
             blockhash = entry.hash;
             blockstream
-                .emit_entry_event(curr_slot, tick_height, &leader_pubkey, &entry)
+                .emit_entry_event(curr_slot, tick_height, &leader_pubkey, &entry, &[])
                 .unwrap();
             expected_entries.push(entry.clone());
             entries.push(entry);
@@ -304,4 +594,63 @@ mod test {
         assert_eq!(matched_slots.len(), 2);
         assert_eq!(matched_blocks.len(), 1);
     }
+
+    #[test]
+    fn test_blockstream_entry_traces() {
+        let blockstream = MockBlockstream::new("test_stream".parse().unwrap());
+        let keypair = Keypair::new();
+        let tx = system_transaction::transfer(&keypair, &Pubkey::new_rand(), 1, Hash::default());
+        let signature = tx.signatures[0];
+        let entry = Entry::new(&Hash::default(), 1, vec![tx]);
+        let leader_pubkey = Pubkey::new_rand();
+
+        blockstream
+            .emit_entry_event(1, 1, &leader_pubkey, &entry, &[])
+            .unwrap();
+        blockstream
+            .emit_entry_event(
+                1,
+                1,
+                &leader_pubkey,
+                &entry,
+                &[(signature, "dapp-42".to_string())],
+            )
+            .unwrap();
+
+        let events: Vec<Value> = blockstream
+            .entries()
+            .iter()
+            .map(|item| serde_json::from_str(&item).unwrap())
+            .collect();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].get("traces").is_none());
+        assert_eq!(events[1]["traces"][signature.to_string()], "dapp-42");
+    }
+
+    #[test]
+    fn test_blockstream_entry_memos() {
+        let blockstream = MockBlockstream::new("test_stream".parse().unwrap());
+        let keypair = Keypair::new();
+        let instruction = Instruction::new(
+            morgan_token_api::id(),
+            &TokenInstruction::TransferWithMemo(1, b"deposit 42".to_vec()),
+            vec![AccountMeta::new(keypair.pubkey(), true)],
+        );
+        let memo_tx =
+            Transaction::new_signed_instructions(&[&keypair], vec![instruction], Hash::default());
+        let tx = system_transaction::transfer(&keypair, &Pubkey::new_rand(), 1, Hash::default());
+        let signatures = (memo_tx.signatures[0], tx.signatures[0]);
+        let entry = Entry::new(&Hash::default(), 1, vec![memo_tx, tx]);
+
+        blockstream
+            .emit_entry_event(1, 1, &Pubkey::new_rand(), &entry, &[])
+            .unwrap();
+
+        let event: Value = serde_json::from_str(&blockstream.entries()[0]).unwrap();
+        assert_eq!(
+            event["memos"][signatures.0.to_string()],
+            json!(b"deposit 42".to_vec())
+        );
+        assert!(event["memos"].get(signatures.1.to_string()).is_none());
+    }
 }
@@ -2,14 +2,15 @@
 //! using the `blockstream` module, providing client services such as a block explorer with
 //! real-time access to entries.
 
-use crate::blockStream::BlockstreamEvents;
+use crate::blockStream::{BlockstreamConfig, BlockstreamEvents};
 #[cfg(test)]
 use crate::blockStream::MockBlockstream as Blockstream;
 #[cfg(not(test))]
-use crate::blockStream::SocketBlockstream as Blockstream;
+use crate::blockStream::SinkBlockstream as Blockstream;
 use crate::blockBufferPool::Blocktree;
 use crate::result::{Error, Result};
 use crate::service::Service;
+use crate::transactionTrace::TransactionTraces;
 use morgan_interface::pubkey::Pubkey;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
@@ -27,10 +28,12 @@ impl BlockstreamService {
     pub fn new(
         slot_full_receiver: Receiver<(u64, Pubkey)>,
         blocktree: Arc<Blocktree>,
-        blockstream_socket: String,
+        blockstream_config: BlockstreamConfig,
+        transaction_traces: &Arc<TransactionTraces>,
         exit: &Arc<AtomicBool>,
     ) -> Self {
-        let mut blockstream = Blockstream::new(blockstream_socket);
+        let mut blockstream = Blockstream::new(blockstream_config);
+        let transaction_traces = transaction_traces.clone();
         let exit = exit.clone();
         let t_blockstream = Builder::new()
             .name("morgan-blockstream".to_string())
@@ -38,9 +41,12 @@ impl BlockstreamService {
                 if exit.load(Ordering::Relaxed) {
                     break;
                 }
-                if let Err(e) =
-                    Self::process_entries(&slot_full_receiver, &blocktree, &mut blockstream)
-                {
+                if let Err(e) = Self::process_entries(
+                    &slot_full_receiver,
+                    &blocktree,
+                    &mut blockstream,
+                    &transaction_traces,
+                ) {
                     match e {
                         Error::RecvTimeoutError(RecvTimeoutError::Disconnected) => break,
                         Error::RecvTimeoutError(RecvTimeoutError::Timeout) => (),
@@ -64,6 +70,7 @@ impl BlockstreamService {
         slot_full_receiver: &Receiver<(u64, Pubkey)>,
         blocktree: &Arc<Blocktree>,
         blockstream: &mut Blockstream,
+        transaction_traces: &TransactionTraces,
     ) -> Result<()> {
         let timeout = Duration::new(1, 0);
         let (slot, slot_leader) = slot_full_receiver.recv_timeout(timeout)?;
@@ -89,8 +96,23 @@ impl BlockstreamService {
             if entry.is_tick() {
                 tick_height += 1;
             }
+            // streaming is the last stage a traced transaction reaches
+            let traces: Vec<_> = if transaction_traces.is_empty() {
+                vec![]
+            } else {
+                entry
+                    .transactions
+                    .iter()
+                    .filter_map(|tx| {
+                        let signature = *tx.signatures.get(0)?;
+                        transaction_traces
+                            .finish(&signature, "blockstream")
+                            .map(|trace_id| (signature, trace_id))
+                    })
+                    .collect()
+            };
             blockstream
-                .emit_entry_event(slot, tick_height, &slot_leader, &entry)
+                .emit_entry_event(slot, tick_height, &slot_leader, &entry, &traces)
                 .unwrap_or_else(|e| {
                     debug!("Blockstream error: {:?}, {:?}", e, blockstream.output);
                 });
@@ -143,7 +165,7 @@ mod test {
         let blocktree = Blocktree::open(&ledger_path).unwrap();
 
         // Set up blockstream
-        let mut blockstream = Blockstream::new("test_stream".to_string());
+        let mut blockstream = Blockstream::new("test_stream".parse().unwrap());
 
         // Set up dummy channel to receive a full-slot notification
         let (slot_full_sender, slot_full_receiver) = channel();
@@ -177,6 +199,7 @@ mod test {
             &slot_full_receiver,
             &Arc::new(blocktree),
             &mut blockstream,
+            &TransactionTraces::default(),
         )
         .unwrap();
         assert_eq!(blockstream.entries().len(), 7);
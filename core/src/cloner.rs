@@ -9,7 +9,7 @@ use crate::packet::to_shared_blob;
 use crate::fixMissingSpotService::{RepairSlotRange, RepairStrategy};
 use crate::result::Result;
 use crate::service::Service;
-use crate::streamer::{receiver, responder};
+use crate::streamer::{queue_channel, receiver, responder, MAX_QUEUED_PACKET_BATCHES};
 use crate::spotTransmitService::WindowService;
 use bincode::deserialize;
 use rand::thread_rng;
@@ -27,7 +27,9 @@ use morgan_interface::signature::{Keypair, KeypairUtil, Signature};
 use morgan_interface::timing::timestamp;
 use morgan_interface::transaction::Transaction;
 use morgan_interface::transport::TransportError;
-use morgan_storage_api::{get_segment_from_slot, storage_instruction, SLOTS_PER_SEGMENT};
+use morgan_storage_api::{
+    get_segment_from_slot, storage_instruction, PROOF_WINDOW_SEGMENTS, SLOTS_PER_SEGMENT,
+};
 use std::fs::File;
 use std::io::{self, BufReader, Error, ErrorKind, Read, Seek, SeekFrom};
 use std::mem::size_of;
@@ -115,9 +117,11 @@ fn get_slot_from_blockhash(signature: &ed25519_dalek::Signature, storage_slot: u
         | (u64::from(signature_vec[1]) << 8)
         | (u64::from(signature_vec[1]) << 16)
         | (u64::from(signature_vec[2]) << 24);
-    let max_segment_index = get_segment_from_slot(storage_slot);
-    segment_index %= max_segment_index as u64;
-    segment_index * SLOTS_PER_SEGMENT
+    // only segments whose proof window is still open are worth storing
+    let max_segment_index = get_segment_from_slot(storage_slot) as u64;
+    let min_segment_index = max_segment_index.saturating_sub(PROOF_WINDOW_SEGMENTS as u64);
+    segment_index %= max_segment_index - min_segment_index;
+    (min_segment_index + segment_index) * SLOTS_PER_SEGMENT
 }
 
 fn create_request_processor(
@@ -126,7 +130,7 @@ fn create_request_processor(
     slot: u64,
 ) -> Vec<JoinHandle<()>> {
     let mut thread_handles = vec![];
-    let (s_reader, r_reader) = channel();
+    let (s_reader, r_reader) = queue_channel("replicator-requests", MAX_QUEUED_PACKET_BATCHES);
     let (s_responder, r_responder) = channel();
     let storage_socket = Arc::new(socket);
     let t_receiver = receiver(storage_socket.clone(), exit, s_reader);
@@ -180,7 +184,7 @@ impl Replicator {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(
         ledger_path: &str,
-        node: Node,
+        mut node: Node,
         cluster_entrypoint: ContactInfo,
         keypair: Arc<Keypair>,
         storage_keypair: Arc<Keypair>,
@@ -201,10 +205,6 @@ impl Replicator {
                 module_path!().to_string()
             )
         );
-        let mut cluster_info = ClusterInfo::new(node.info.clone(), keypair.clone());
-        cluster_info.set_entrypoint(cluster_entrypoint.clone());
-        let cluster_info = Arc::new(RwLock::new(cluster_info));
-
         // Note for now, this ledger will not contain any of the existing entries
         // in the ledger located at ledger_path, and will only append on newly received
         // entries after being passed to window_service
@@ -212,6 +212,12 @@ impl Replicator {
             GenesisBlock::load(ledger_path).expect("Expected to successfully open genesis block");
         let bank = Bank::new_with_paths(&genesis_block, None);
         let genesis_blockhash = bank.last_blockhash();
+
+        node.info.cluster_id = ContactInfo::compute_cluster_id(&genesis_blockhash);
+        let mut cluster_info = ClusterInfo::new(node.info.clone(), keypair.clone());
+        cluster_info.set_entrypoint(cluster_entrypoint.clone());
+        let cluster_info = Arc::new(RwLock::new(cluster_info));
+
         let blocktree = Arc::new(
             Blocktree::open(ledger_path).expect("Expected to be able to open database ledger"),
         );
@@ -687,4 +693,20 @@ mod tests {
         let res = sample_file(&in_path, &samples);
         assert!(res.is_err());
     }
+
+    #[test]
+    fn test_get_slot_from_blockhash() {
+        let keypair = Keypair::new();
+        let storage_slot = 20 * SLOTS_PER_SEGMENT;
+        let min_slot = (20 - PROOF_WINDOW_SEGMENTS as u64) * SLOTS_PER_SEGMENT;
+        for i in 0..16u8 {
+            let signature = keypair.sign(&[i]);
+            let slot = get_slot_from_blockhash(&signature, storage_slot);
+            assert_eq!(slot % SLOTS_PER_SEGMENT, 0);
+            assert!(slot >= min_slot && slot < storage_slot);
+        }
+        // early on every complete segment is eligible
+        let signature = keypair.sign(&[0]);
+        assert_eq!(get_slot_from_blockhash(&signature, SLOTS_PER_SEGMENT), 0);
+    }
 }
@@ -19,18 +19,25 @@ use crate::connectionInfo::ContactInfo;
 use crate::gossip::CrdsGossip;
 use crate::gossipErrorType::CrdsGossipError;
 use crate::pullFromGossip::CRDS_GOSSIP_PULL_CRDS_TIMEOUT_MS;
-use crate::propagationValue::{CrdsValue, CrdsValueLabel, EpochSlots, Vote};
+use crate::propagationValue::{
+    AccountsHash, CrdsValue, CrdsValueLabel, EpochSlots, EpochSlotsUpdate, LedgerRange, Version,
+    Vote,
+};
 use crate::packet::{to_shared_blob, Blob, SharedBlob, BLOB_SIZE};
 use crate::fixMissingSpotService::RepairType;
 use crate::result::Result;
+use crate::sendmmsg::{batch_send, multicast};
 use crate::stakingUtils;
 use crate::streamer::{BlobReceiver, BlobSender};
-use bincode::{deserialize, serialize};
+use crate::weightedShuffle::weighted_shuffle;
+use bincode::serialize;
 use core::cmp;
 use hashbrown::HashMap;
 use rand::{thread_rng, Rng};
 use rayon::prelude::*;
-use morgan_metricbot::{datapoint_debug, inc_new_counter_debug, inc_new_counter_error};
+use morgan_metricbot::{
+    datapoint_debug, inc_new_counter_debug, inc_new_counter_error, inc_new_counter_warn,
+};
 use morgan_netutil::{
     bind_in_range, bind_to, find_available_port_in_range, multi_bind_in_range, PortRange,
 };
@@ -61,6 +68,11 @@ pub const GOSSIP_SLEEP_MILLIS: u64 = 100;
 /// the number of slots to respond with when responding to `Orphan` requests
 pub const MAX_ORPHAN_REPAIR_RESPONSES: usize = 10;
 
+/// milliseconds a peer stays quarantined after it last advertised another cluster
+pub const QUARANTINE_TIMEOUT_MS: u64 = 60 * 60 * 1000;
+/// most peers kept in quarantine, the longest quarantined make room for new ones
+pub const MAX_QUARANTINED_PEERS: usize = 1024;
+
 #[derive(Debug, PartialEq, Eq)]
 pub enum ClusterInfoError {
     NoPeers,
@@ -79,6 +91,11 @@ pub struct ClusterInfo {
     gossip_leader_pubkey: Pubkey,
     /// The network entrypoint
     entrypoint: Option<ContactInfo>,
+    /// Peers that advertised a different cluster id and when they last did, all of their
+    /// traffic is dropped until the quarantine times out
+    quarantined_peers: HashMap<Pubkey, u64>,
+    /// Contact info of another node running with our identity, if one has been seen
+    duplicate_instance: Option<ContactInfo>,
 }
 
 #[derive(Default, Clone)]
@@ -179,6 +196,8 @@ impl ClusterInfo {
             keypair,
             gossip_leader_pubkey: Pubkey::default(),
             entrypoint: None,
+            quarantined_peers: HashMap::new(),
+            duplicate_instance: None,
         };
         let id = contact_info.id;
         me.gossip.set_self(&id);
@@ -201,8 +220,14 @@ impl ClusterInfo {
         my_data.wallclock = now;
         let mut entry = CrdsValue::ContactInfo(my_data);
         entry.sign(&self.keypair);
+        let mut version = CrdsValue::Version(Version::new(
+            self.id(),
+            env!("CARGO_PKG_VERSION").to_string(),
+            now,
+        ));
+        version.sign(&self.keypair);
         self.gossip.refresh_push_active_set(stakes);
-        self.gossip.process_push_message(vec![entry], now);
+        self.gossip.process_push_message(vec![entry, version], now);
     }
 
     // TODO kill insert_info, only used by tests
@@ -212,6 +237,18 @@ impl ClusterInfo {
         let _ = self.gossip.crds.insert(value, timestamp());
     }
 
+    /// Take on the identity of `keypair`, gossiping this node's contact info under it from
+    /// now on. Services that took the old id when they started keep it until restarted, so
+    /// only swap it before the validator starts them
+    pub fn set_keypair(&mut self, keypair: Arc<Keypair>) {
+        let mut my_data = self.my_data();
+        my_data.id = keypair.pubkey();
+        self.keypair = keypair;
+        self.gossip.set_self(&my_data.id);
+        self.insert_self(my_data);
+        self.push_self(&HashMap::new());
+    }
+
     pub fn set_entrypoint(&mut self, entrypoint: ContactInfo) {
         self.entrypoint = Some(entrypoint)
     }
@@ -220,20 +257,153 @@ impl ClusterInfo {
         self.gossip.id
     }
 
-    pub fn lookup(&self, id: &Pubkey) -> Option<&ContactInfo> {
+    /// Peers whose gossip, repair and retransmit traffic is being dropped
+    pub fn quarantined_peers(&self) -> Vec<Pubkey> {
+        let now = timestamp();
+        self.quarantined_peers
+            .keys()
+            .filter(|id| self.is_quarantined_at(id, now))
+            .cloned()
+            .collect()
+    }
+
+    pub fn is_quarantined(&self, id: &Pubkey) -> bool {
+        self.is_quarantined_at(id, timestamp())
+    }
+
+    fn is_quarantined_at(&self, id: &Pubkey, now: u64) -> bool {
+        self.quarantined_peers
+            .get(id)
+            .map_or(false, |since| now < since + QUARANTINE_TIMEOUT_MS)
+    }
+
+    fn quarantine(&mut self, id: &Pubkey) {
+        self.quarantine_at(id, timestamp())
+    }
+
+    fn quarantine_at(&mut self, id: &Pubkey, now: u64) {
+        if *id == self.id() {
+            return;
+        }
+        let was_quarantined = self.is_quarantined_at(id, now);
+        self.quarantined_peers
+            .retain(|_, since| now < *since + QUARANTINE_TIMEOUT_MS);
+        if !was_quarantined && self.quarantined_peers.len() >= MAX_QUARANTINED_PEERS {
+            let oldest = self
+                .quarantined_peers
+                .iter()
+                .min_by_key(|(_, since)| **since)
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.quarantined_peers.remove(&oldest);
+            }
+        }
+        self.quarantined_peers.insert(*id, now);
+        if was_quarantined {
+            return;
+        }
+        inc_new_counter_warn!("cluster_info-quarantined_peers", 1);
+        println!(
+            "{}",
+            Warn(
+                format!("{}: quarantined {}, cluster id mismatch", self.id(), id),
+                module_path!().to_string()
+            )
+        );
+        for label in CrdsValue::record_labels(id).iter() {
+            self.gossip.crds.remove(label);
+        }
+    }
+
+    /// Quarantine the signer of `contact_info` if it belongs to another cluster.
+    /// Returns true if the peer is quarantined.
+    fn quarantine_if_mismatched(&mut self, contact_info: &ContactInfo) -> bool {
+        if !self.my_data().is_same_cluster(contact_info) {
+            self.quarantine(&contact_info.id);
+        }
+        self.is_quarantined(&contact_info.id)
+    }
+
+    /// Another node gossiping with our identity, seen as a contact info for our pubkey that is
+    /// newer than our own but advertises different sockets. It corrupts gossip and voting,
+    /// the node should stop once this is set.
+    pub fn duplicate_instance(&self) -> Option<&ContactInfo> {
+        self.duplicate_instance.as_ref()
+    }
+
+    /// Returns true, and records the duplicate, if `contact_info` comes from another instance
+    /// of this node
+    fn check_duplicate_instance(&mut self, contact_info: &ContactInfo) -> bool {
+        if contact_info.id != self.id() {
+            return false;
+        }
+        let me = self.my_data();
+        if contact_info.wallclock <= me.wallclock || contact_info.same_addresses(&me) {
+            return false;
+        }
+        if self.duplicate_instance.is_none() {
+            inc_new_counter_error!("cluster_info-duplicate_instance", 1);
+            println!(
+                "{}",
+                Error(
+                    format!(
+                        "{}: another node is running with this identity, gossip {} tvu {} tpu {}. \
+                         Stop one of them, two instances would corrupt gossip and voting",
+                        me.id, contact_info.gossip, contact_info.tvu, contact_info.tpu
+                    ),
+                    module_path!().to_string()
+                )
+            );
+        }
+        self.duplicate_instance = Some(contact_info.clone());
+        true
+    }
+
+    /// Drop contact infos from another instance of this node so they can't replace our own
+    fn retain_own_instance(&mut self, data: &mut Vec<CrdsValue>) {
+        data.retain(|v| {
+            v.contact_info().map_or(true, |contact_info| {
+                !self.check_duplicate_instance(contact_info)
+            })
+        });
+    }
+
+    /// Drop gossip values sent by or originating from quarantined peers
+    fn retain_same_cluster(&mut self, from: &Pubkey, data: &mut Vec<CrdsValue>) {
+        if self.is_quarantined(from) {
+            inc_new_counter_debug!("cluster_info-gossip_quarantined_sender", 1);
+            data.clear();
+            return;
+        }
+        let me = self.my_data();
+        let mismatched: Vec<_> = data
+            .iter()
+            .filter_map(CrdsValue::contact_info)
+            .filter(|contact_info| !me.is_same_cluster(contact_info))
+            .map(|contact_info| contact_info.id)
+            .collect();
+        for id in &mismatched {
+            self.quarantine(id);
+        }
+        let len = data.len();
+        data.retain(|v| !self.is_quarantined(&v.label().pubkey()));
+        inc_new_counter_debug!("cluster_info-gossip_quarantined_values", len - data.len());
+    }
+
+    pub fn lookup(&self, id: &Pubkey) -> Option<ContactInfo> {
         let entry = CrdsValueLabel::ContactInfo(*id);
         self.gossip
             .crds
             .lookup(&entry)
-            .and_then(CrdsValue::contact_info)
+            .and_then(|v| v.contact_info().cloned())
     }
 
     pub fn my_data(&self) -> ContactInfo {
-        self.lookup(&self.id()).cloned().unwrap()
+        self.lookup(&self.id()).unwrap()
     }
 
     // Deprecated: don't use leader_data().
-    pub fn leader_data(&self) -> Option<&ContactInfo> {
+    pub fn leader_data(&self) -> Option<ContactInfo> {
         let leader_pubkey = self.gossip_leader_pubkey;
         if leader_pubkey == Pubkey::default() {
             return None;
@@ -317,9 +487,41 @@ impl ClusterInfo {
         }
     }
 
+    /// Advertise the slots past `root` this node completed, only the ones added since the last
+    /// `EpochSlots` go out while that's enough
     pub fn push_epoch_slots(&mut self, id: Pubkey, root: u64, slots: BTreeSet<u64>) {
         let now = timestamp();
-        let mut entry = CrdsValue::EpochSlots(EpochSlots::new(id, root, slots, now));
+        let mut epoch_slots = EpochSlots::new(id, root, slots, now);
+        let dropped = epoch_slots.truncate();
+        if dropped > 0 {
+            inc_new_counter_warn!("cluster_info-epoch_slots-truncated", dropped);
+        }
+        let update = self
+            .gossip
+            .crds
+            .lookup(&CrdsValueLabel::EpochSlots(id))
+            .and_then(|base| EpochSlotsUpdate::new(base.epoch_slots().unwrap(), &epoch_slots));
+        let mut entry = match update {
+            Some(update) => CrdsValue::EpochSlotsUpdate(update),
+            None => CrdsValue::EpochSlots(epoch_slots),
+        };
+        entry.sign(&self.keypair);
+        self.gossip.process_push_message(vec![entry], now);
+    }
+
+    pub fn push_accounts_hash(&mut self, epoch: u64, slot: u64, hash: Hash) {
+        let now = timestamp();
+        let mut entry =
+            CrdsValue::AccountsHash(AccountsHash::new(self.id(), epoch, slot, hash, now));
+        entry.sign(&self.keypair);
+        self.gossip.process_push_message(vec![entry], now);
+    }
+
+    /// Advertise the slots this node's ledger holds, from `first_slot` up to `last_slot`
+    pub fn push_ledger_range(&mut self, first_slot: u64, last_slot: u64) {
+        let now = timestamp();
+        let mut entry =
+            CrdsValue::LedgerRange(LedgerRange::new(self.id(), first_slot, last_slot, now));
         entry.sign(&self.keypair);
         self.gossip.process_push_message(vec![entry], now);
     }
@@ -338,45 +540,57 @@ impl ClusterInfo {
     ///
     /// * return - The votes, and the max timestamp from the new set.
     pub fn get_votes(&self, since: u64) -> (Vec<Transaction>, u64) {
-        let votes: Vec<_> = self
-            .gossip
-            .crds
-            .table
-            .values()
-            .filter(|x| x.insert_timestamp > since)
-            .filter_map(|x| {
-                x.value
-                    .vote()
-                    .map(|v| (x.insert_timestamp, v.transaction.clone()))
-            })
-            .collect();
+        let votes: Vec<_> = self.gossip.crds.with_values(|values| {
+            values
+                .filter(|x| x.insert_timestamp > since)
+                .filter_map(|x| {
+                    x.value
+                        .vote()
+                        .map(|v| (x.insert_timestamp, v.transaction.clone()))
+                })
+                .collect()
+        });
         let max_ts = votes.iter().map(|x| x.0).max().unwrap_or(since);
         let txs: Vec<Transaction> = votes.into_iter().map(|x| x.1).collect();
         (txs, max_ts)
     }
 
+    /// The slots `pubkey` advertises along with any update to them, if either came in after
+    /// `since`
     pub fn get_epoch_state_for_node(
         &self,
         pubkey: &Pubkey,
         since: Option<u64>,
-    ) -> Option<(&EpochSlots, u64)> {
-        self.gossip
+    ) -> Option<(EpochSlots, u64)> {
+        let base = self
+            .gossip
             .crds
-            .table
-            .get(&CrdsValueLabel::EpochSlots(*pubkey))
+            .lookup_versioned(&CrdsValueLabel::EpochSlots(*pubkey))?;
+        let mut epoch_slots = base.value.epoch_slots().unwrap().clone();
+        let mut insert_timestamp = base.insert_timestamp;
+        // an update to an older or newer set than the one here doesn't apply
+        if let Some(update) = self
+            .gossip
+            .crds
+            .lookup_versioned(&CrdsValueLabel::EpochSlotsUpdate(*pubkey))
             .filter(|x| {
-                since
-                    .map(|since| x.insert_timestamp > since)
-                    .unwrap_or(true)
+                x.value.epoch_slots_update().unwrap().base_wallclock == epoch_slots.wallclock
             })
-            .map(|x| (x.value.epoch_slots().unwrap(), x.insert_timestamp))
+        {
+            let update_slots = &update.value.epoch_slots_update().unwrap().slots;
+            epoch_slots.slots.extend(update_slots.iter().cloned());
+            insert_timestamp = insert_timestamp.max(update.insert_timestamp);
+        }
+        if since.map_or(false, |since| insert_timestamp <= since) {
+            return None;
+        }
+        Some((epoch_slots, insert_timestamp))
     }
 
     pub fn get_gossiped_root_for_node(&self, pubkey: &Pubkey, since: Option<u64>) -> Option<u64> {
         self.gossip
             .crds
-            .table
-            .get(&CrdsValueLabel::EpochSlots(*pubkey))
+            .lookup_versioned(&CrdsValueLabel::EpochSlots(*pubkey))
             .filter(|x| {
                 since
                     .map(|since| x.insert_timestamp > since)
@@ -385,12 +599,41 @@ impl ClusterInfo {
             .map(|x| x.value.epoch_slots().unwrap().root)
     }
 
-    pub fn get_contact_info_for_node(&self, pubkey: &Pubkey) -> Option<&ContactInfo> {
+    /// Accounts hashes gossiped by every node for `slot`
+    pub fn get_accounts_hashes(&self, slot: u64) -> Vec<AccountsHash> {
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.accounts_hash())
+                .filter(|accounts_hash| accounts_hash.slot == slot)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// The software version `pubkey` advertised over gossip
+    pub fn get_node_version(&self, pubkey: &Pubkey) -> Option<String> {
+        self.gossip
+            .crds
+            .lookup_versioned(&CrdsValueLabel::Version(*pubkey))
+            .map(|x| x.value.version().unwrap().version.clone())
+    }
+
+    /// The first and last slots `pubkey` advertised its ledger holds
+    pub fn get_ledger_range_for_node(&self, pubkey: &Pubkey) -> Option<(u64, u64)> {
+        self.gossip
+            .crds
+            .lookup_versioned(&CrdsValueLabel::LedgerRange(*pubkey))
+            .map(|x| {
+                let ledger_range = x.value.ledger_range().unwrap();
+                (ledger_range.first_slot, ledger_range.last_slot)
+            })
+    }
+
+    pub fn get_contact_info_for_node(&self, pubkey: &Pubkey) -> Option<ContactInfo> {
         self.gossip
             .crds
-            .table
-            .get(&CrdsValueLabel::ContactInfo(*pubkey))
-            .map(|x| x.value.contact_info().unwrap())
+            .lookup_versioned(&CrdsValueLabel::ContactInfo(*pubkey))
+            .map(|x| x.value.contact_info().unwrap().clone())
     }
 
     pub fn purge(&mut self, now: u64) {
@@ -399,93 +642,89 @@ impl ClusterInfo {
 
     pub fn rpc_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| x.value.contact_info())
-            .filter(|x| x.id != me)
-            .filter(|x| ContactInfo::is_valid_address(&x.rpc))
-            .cloned()
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.contact_info())
+                .filter(|x| x.id != me)
+                .filter(|x| ContactInfo::is_valid_address(&x.rpc))
+                .cloned()
+                .collect()
+        })
     }
 
     // All nodes in gossip (including spy nodes) and the last time we heard about them
     pub(crate) fn all_peers(&self) -> Vec<(ContactInfo, u64)> {
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| {
-                x.value
-                    .contact_info()
-                    .map(|ci| (ci.clone(), x.local_timestamp))
-            })
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| {
+                    x.value
+                        .contact_info()
+                        .map(|ci| (ci.clone(), x.local_timestamp))
+                })
+                .collect()
+        })
     }
 
     pub fn gossip_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| x.value.contact_info())
-            .filter(|x| x.id != me)
-            .filter(|x| ContactInfo::is_valid_address(&x.gossip))
-            .cloned()
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.contact_info())
+                .filter(|x| x.id != me)
+                .filter(|x| ContactInfo::is_valid_address(&x.gossip))
+                .cloned()
+                .collect()
+        })
     }
 
     /// all peers that have a valid tvu port.
     pub fn tvu_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| x.value.contact_info())
-            .filter(|x| ContactInfo::is_valid_address(&x.tvu))
-            .filter(|x| x.id != me)
-            .cloned()
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.contact_info())
+                .filter(|x| ContactInfo::is_valid_address(&x.tvu))
+                .filter(|x| x.id != me)
+                .cloned()
+                .collect()
+        })
     }
 
     /// all peers that have a valid storage addr
     pub fn storage_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| x.value.contact_info())
-            .filter(|x| ContactInfo::is_valid_address(&x.storage_addr))
-            .filter(|x| x.id != me)
-            .cloned()
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.contact_info())
+                .filter(|x| ContactInfo::is_valid_address(&x.storage_addr))
+                .filter(|x| x.id != me)
+                .cloned()
+                .collect()
+        })
     }
 
     /// all peers that have a valid tvu
     pub fn retransmit_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| x.value.contact_info())
-            .filter(|x| x.id != me)
-            .filter(|x| ContactInfo::is_valid_address(&x.tvu))
-            .cloned()
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.contact_info())
+                .filter(|x| x.id != me)
+                .filter(|x| ContactInfo::is_valid_address(&x.tvu))
+                .filter(|x| !self.is_quarantined(&x.id))
+                .cloned()
+                .collect()
+        })
     }
 
     /// all tvu peers with valid gossip addrs
-    fn repair_peers(&self) -> Vec<ContactInfo> {
+    pub fn repair_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
         ClusterInfo::tvu_peers(self)
             .into_iter()
             .filter(|x| x.id != me)
             .filter(|x| ContactInfo::is_valid_address(&x.gossip))
+            .filter(|x| !self.is_quarantined(&x.id))
             .collect()
     }
 
@@ -525,20 +764,35 @@ impl ClusterInfo {
         peers_with_stakes
     }
 
-    /// Return sorted Retransmit peers and index of `Self.id()` as if it were in that list
-    fn sorted_peers_and_index<S: std::hash::BuildHasher>(
+    /// Return Retransmit peers and `Self` sorted by stake, along with their stakes.  Every node
+    /// shuffles this same list to lay out the retransmit tree of a blob
+    pub fn sorted_retransmit_peers_and_stakes<S: std::hash::BuildHasher>(
         &self,
         stakes: Option<&HashMap<Pubkey, u64, S>>,
-    ) -> (usize, Vec<ContactInfo>) {
+    ) -> (Vec<ContactInfo>, Vec<u64>) {
         let mut peers = self.retransmit_peers();
-        peers.push(self.lookup(&self.id()).unwrap().clone());
-        let contacts_and_stakes: Vec<_> = ClusterInfo::sort_by_stake(&peers, stakes);
+        peers.push(self.lookup(&self.id()).unwrap());
+        ClusterInfo::sort_by_stake(&peers, stakes)
+            .into_iter()
+            .map(|(stake, peer)| (peer, stake))
+            .unzip()
+    }
+
+    /// Shuffle `peers` by stake with `seed`, return the index of `id` in the shuffle and the
+    /// other peers in shuffled order
+    pub fn shuffle_peers_and_index<'a>(
+        id: &Pubkey,
+        peers: &'a [ContactInfo],
+        stakes: &[u64],
+        seed: [u8; 32],
+    ) -> (usize, Vec<&'a ContactInfo>) {
         let mut index = 0;
-        let peers: Vec<_> = contacts_and_stakes
+        let peers: Vec<_> = weighted_shuffle(stakes, seed)
             .into_iter()
             .enumerate()
-            .filter_map(|(i, (_, peer))| {
-                if peer.id == self.id() {
+            .filter_map(|(i, peer_index)| {
+                let peer = &peers[peer_index];
+                if peer.id == *id {
                     index = i;
                     None
                 } else {
@@ -561,15 +815,14 @@ impl ClusterInfo {
     /// compute broadcast table
     pub fn tpu_peers(&self) -> Vec<ContactInfo> {
         let me = self.my_data().id;
-        self.gossip
-            .crds
-            .table
-            .values()
-            .filter_map(|x| x.value.contact_info())
-            .filter(|x| x.id != me)
-            .filter(|x| ContactInfo::is_valid_address(&x.tpu))
-            .cloned()
-            .collect()
+        self.gossip.crds.with_values(|values| {
+            values
+                .filter_map(|x| x.value.contact_info())
+                .filter(|x| x.id != me)
+                .filter(|x| ContactInfo::is_valid_address(&x.tpu))
+                .cloned()
+                .collect()
+        })
     }
 
     /// Given a node count and fanout, it calculates how many layers are needed and at what index each layer begins.
@@ -703,30 +956,33 @@ impl ClusterInfo {
 
     /// broadcast messages from the leader to layer 1 nodes
     /// # Remarks
+    /// `peers` and `stakes` come from `sorted_retransmit_peers_and_stakes`, each blob goes to
+    /// the layer 1 of its own retransmit tree
     pub fn broadcast(
         id: &Pubkey,
         contains_last_tick: bool,
-        broadcast_table: &[ContactInfo],
+        peers: &[ContactInfo],
+        stakes: &[u64],
         s: &UdpSocket,
         blobs: &[SharedBlob],
     ) -> Result<()> {
-        if broadcast_table.is_empty() {
+        if peers.iter().all(|peer| peer.id == *id) {
             debug!("{}:not enough peers in cluster_info table", id);
             inc_new_counter_error!("cluster_info-broadcast-not_enough_peers_error", 1);
             Err(ClusterInfoError::NoPeers)?;
         }
 
-        let orders = Self::create_broadcast_orders(contains_last_tick, blobs, broadcast_table);
+        let orders = Self::create_broadcast_orders(contains_last_tick, blobs, |blob| {
+            let seed = blob.read().unwrap().seed();
+            let (_, peers) = Self::shuffle_peers_and_index(id, peers, stakes, seed);
+            peers.into_iter().take(DATA_PLANE_FANOUT).collect()
+        });
 
         trace!("broadcast orders table {}", orders.len());
 
-        let errs = Self::send_orders(id, s, orders);
-
-        for e in errs {
-            if let Err(e) = &e {
-                trace!("{}: broadcast result {:?}", id, e);
-            }
-            e?;
+        if let Err(e) = Self::send_orders(id, s, orders) {
+            trace!("{}: broadcast result {:?}", id, e);
+            Err(e)?;
         }
 
         inc_new_counter_debug!("cluster_info-broadcast-max_idx", blobs.len());
@@ -755,8 +1011,8 @@ impl ClusterInfo {
         let was_forwarded = !wblob.should_forward();
         wblob.set_forwarded(forwarded);
         trace!("retransmit orders {}", orders.len());
-        let errs: Vec<_> = orders
-            .par_iter()
+        let dests: Vec<_> = orders
+            .iter()
             .filter(|v| v.id != slot_leader_pubkey.unwrap_or_default())
             .map(|v| {
                 debug!(
@@ -766,27 +1022,25 @@ impl ClusterInfo {
                     v.id,
                     v.tvu,
                 );
-                //TODO profile this, may need multiple sockets for par_iter
-                assert!(wblob.meta.size <= BLOB_SIZE);
-                s.send_to(&wblob.data[..wblob.meta.size], &v.tvu)
+                &v.tvu
             })
             .collect();
+        assert!(wblob.meta.size <= BLOB_SIZE);
+        let result = multicast(s, &wblob.data[..wblob.meta.size], &dests);
         // reset the blob to its old state. This avoids us having to copy the blob to modify it
         wblob.set_forwarded(was_forwarded);
-        for e in errs {
-            if let Err(e) = &e {
-                inc_new_counter_error!("cluster_info-retransmit-send_to_error", 1, 1);
-                // error!("{}", Error(format!("retransmit result {:?}", e).to_string()));
-                println!(
-                    "{}",
-                    Error(
-                        format!("retransmit result {:?}", e).to_string(),
-                        module_path!().to_string()
-                    )
-                );
-            }
-            e?;
+        if let Err(e) = &result {
+            inc_new_counter_error!("cluster_info-retransmit-send_to_error", 1, 1);
+            // error!("{}", Error(format!("retransmit result {:?}", e).to_string()));
+            println!(
+                "{}",
+                Error(
+                    format!("retransmit result {:?}", e).to_string(),
+                    module_path!().to_string()
+                )
+            );
         }
+        result?;
         Ok(())
     }
 
@@ -794,10 +1048,10 @@ impl ClusterInfo {
         id: &Pubkey,
         s: &UdpSocket,
         orders: Vec<(SharedBlob, Vec<&ContactInfo>)>,
-    ) -> Vec<io::Result<usize>> {
-        orders
-            .into_iter()
-            .flat_map(|(b, vs)| {
+    ) -> io::Result<()> {
+        let blobs: Vec<_> = orders
+            .iter()
+            .map(|(b, vs)| {
                 let blob = b.read().unwrap();
 
                 let ids_and_tvus = if log_enabled!(log::Level::Trace) {
@@ -820,46 +1074,51 @@ impl ClusterInfo {
                 };
 
                 assert!(blob.meta.size <= BLOB_SIZE);
-                let send_errs_for_blob: Vec<_> = vs
-                    .iter()
-                    .map(move |v| {
-                        let e = s.send_to(&blob.data[..blob.meta.size], &v.tvu);
-                        trace!(
-                            "{}: done broadcast {} to {:?}",
-                            id,
-                            blob.meta.size,
-                            ids_and_tvus
-                        );
-                        e
-                    })
-                    .collect();
-                send_errs_for_blob
+                (blob, vs, ids_and_tvus)
             })
-            .collect()
+            .collect();
+
+        // every blob to every one of its nodes, in as few syscalls as it takes
+        let packets: Vec<_> = blobs
+            .iter()
+            .flat_map(|(blob, vs, _)| {
+                vs.iter()
+                    .map(move |v| (&blob.data[..blob.meta.size], &v.tvu))
+            })
+            .collect();
+        let result = batch_send(s, &packets);
+        for (blob, _, ids_and_tvus) in &blobs {
+            trace!(
+                "{}: done broadcast {} to {:?}",
+                id,
+                blob.meta.size,
+                ids_and_tvus
+            );
+        }
+        result
     }
 
-    pub fn create_broadcast_orders<'a, T>(
+    /// Each blob goes to the first node of its layer 1, `layer_1` lists those nodes in the order
+    /// of the blob's retransmit tree
+    pub fn create_broadcast_orders<'a, T, F>(
         contains_last_tick: bool,
         blobs: &[T],
-        broadcast_table: &'a [ContactInfo],
+        layer_1: F,
     ) -> Vec<(T, Vec<&'a ContactInfo>)>
     where
         T: Clone,
+        F: Fn(&T) -> Vec<&'a ContactInfo>,
     {
-        // enumerate all the blobs in the window, those are the indices
-        // transmit them to nodes, starting from a different node.
         if blobs.is_empty() {
             return vec![];
         }
-        let mut orders = Vec::with_capacity(blobs.len());
-
-        let x = thread_rng().gen_range(0, broadcast_table.len());
-        for (i, blob) in blobs.iter().enumerate() {
-            let br_idx = (x + i) % broadcast_table.len();
-
-            trace!("broadcast order data br_idx {}", br_idx);
+        let mut orders = Vec::with_capacity(blobs.len() + 1);
 
-            orders.push((blob.clone(), vec![&broadcast_table[br_idx]]));
+        for blob in blobs {
+            if let Some(peer) = layer_1(blob).first() {
+                trace!("broadcast order data to {}", peer.id);
+                orders.push((blob.clone(), vec![*peer]));
+            }
         }
 
         if contains_last_tick {
@@ -869,10 +1128,8 @@ impl ClusterInfo {
             // If we had a tick at max_tick_height, then we know it must be the last
             // Blob in the broadcast, There cannot be an entry that got sent after the
             // last tick, guaranteed by the PohService).
-            orders.push((
-                blobs.last().unwrap().clone(),
-                broadcast_table.iter().collect(),
-            ));
+            let last = blobs.last().unwrap();
+            orders.push((last.clone(), layer_1(last)));
         }
 
         orders
@@ -905,6 +1162,13 @@ impl ClusterInfo {
         }
         let n = thread_rng().gen::<usize>() % valid.len();
         let addr = valid[n].gossip; // send the request to the peer's gossip port
+        let out = self.map_repair_request(repair_request)?;
+
+        Ok((addr, out))
+    }
+
+    /// The request for `repair_request`, to be sent to a repair peer's gossip port
+    pub fn map_repair_request(&self, repair_request: &RepairType) -> Result<Vec<u8>> {
         let out = {
             match repair_request {
                 RepairType::Blob(slot, blob_index) => {
@@ -930,7 +1194,7 @@ impl ClusterInfo {
             }
         };
 
-        Ok((addr, out))
+        Ok(out)
     }
     // If the network entrypoint hasn't been discovered yet, add it to the crds table
     fn add_entrypoint(&mut self, pulls: &mut Vec<(Pubkey, Bloom<Hash>, SocketAddr, CrdsValue)>) {
@@ -946,7 +1210,7 @@ impl ClusterInfo {
                     entrypoint.id,
                     self.gossip.pull.build_crds_filter(&self.gossip.crds),
                     entrypoint.gossip,
-                    self_info.clone(),
+                    self_info,
                 ))
             }
             None => (),
@@ -969,7 +1233,7 @@ impl ClusterInfo {
                 self.gossip
                     .crds
                     .lookup(&peer_label)
-                    .and_then(CrdsValue::contact_info)
+                    .and_then(|v| v.contact_info().cloned())
                     .map(|peer_info| (peer, filter, peer_info.gossip, self_info))
             })
             .collect();
@@ -993,7 +1257,7 @@ impl ClusterInfo {
                 self.gossip
                     .crds
                     .lookup(&peer_label)
-                    .and_then(CrdsValue::contact_info)
+                    .and_then(|v| v.contact_info().cloned())
                     .map(|p| p.gossip)
             })
             .map(|peer| (peer, Protocol::PushMessage(self_id, msgs.clone())))
@@ -1155,7 +1419,10 @@ impl ClusterInfo {
         blocktree: Option<&Arc<Blocktree>>,
         blob: &Blob,
     ) -> Vec<SharedBlob> {
-        deserialize(&blob.data[..blob.meta.size])
+        // the request is untrusted, don't let its length prefixes allocate past a blob
+        bincode::config()
+            .limit(BLOB_SIZE as u64)
+            .deserialize(&blob.data[..blob.meta.size])
             .into_iter()
             .flat_map(|request| {
                 ClusterInfo::handle_protocol(obj, &blob.meta.addr(), blocktree, request)
@@ -1215,10 +1482,17 @@ impl ClusterInfo {
         let now = Instant::now();
         let self_id = me.read().unwrap().gossip.id;
         trace!("PullResponse me: {} from: {} len={}", self_id, from, len);
+        // insert under the read lock so gossip readers aren't blocked behind the table update
+        let old_hashes = me
+            .read()
+            .unwrap()
+            .gossip
+            .insert_pull_response(from, data, timestamp());
         me.write()
             .unwrap()
             .gossip
-            .process_pull_response(from, data, timestamp());
+            .pull
+            .record_old_hashes(old_hashes);
         inc_new_counter_debug!("cluster_info-pull_request_response", 1);
         inc_new_counter_debug!("cluster_info-pull_request_response-size", len);
 
@@ -1232,15 +1506,17 @@ impl ClusterInfo {
         let self_id = me.read().unwrap().gossip.id;
         inc_new_counter_debug!("cluster_info-push_message", 1, 0, 1000);
 
+        let now = timestamp();
+        let inserted = me.read().unwrap().gossip.insert_push_messages(data, now);
         let prunes: Vec<_> = me
             .write()
             .unwrap()
             .gossip
-            .process_push_message(data, timestamp());
+            .record_push_messages(inserted, now);
 
         if !prunes.is_empty() {
             inc_new_counter_debug!("cluster_info-push_message-prunes", prunes.len());
-            let ci = me.read().unwrap().lookup(from).cloned();
+            let ci = me.read().unwrap().lookup(from);
             let pushes: Vec<_> = me.write().unwrap().new_push_requests();
             inc_new_counter_debug!("cluster_info-push_message-pushes", pushes.len());
             let mut rsp: Vec<_> = ci
@@ -1310,6 +1586,15 @@ impl ClusterInfo {
             inc_new_counter_debug!("cluster_info-handle-repair--eq", 1);
             return vec![];
         }
+        // the sender's contact info is unsigned, so a mismatch only rejects the request
+        // and never quarantines the claimed id
+        {
+            let r_me = me.read().unwrap();
+            if r_me.is_quarantined(&from.id) || !r_me.my_data().is_same_cluster(from) {
+                inc_new_counter_debug!("cluster_info-handle-repair-quarantined", 1);
+                return vec![];
+            }
+        }
 
         me.write()
             .unwrap()
@@ -1375,6 +1660,17 @@ impl ClusterInfo {
                 if !caller.verify() {
                     inc_new_counter_error!("cluster_info-gossip_pull_request_verify_fail", 1);
                     vec![]
+                } else if caller
+                    .contact_info()
+                    .map_or(false, |ci| me.write().unwrap().quarantine_if_mismatched(ci))
+                {
+                    inc_new_counter_debug!("cluster_info-gossip_pull_request_quarantined", 1);
+                    vec![]
+                } else if caller
+                    .contact_info()
+                    .map_or(false, |ci| me.write().unwrap().check_duplicate_instance(ci))
+                {
+                    vec![]
                 } else {
                     Self::handle_pull_request(me, filter, caller, from_addr)
                 }
@@ -1387,6 +1683,11 @@ impl ClusterInfo {
                     }
                     ret
                 });
+                {
+                    let mut w_me = me.write().unwrap();
+                    w_me.retain_same_cluster(&from, &mut data);
+                    w_me.retain_own_instance(&mut data);
+                }
                 Self::handle_pull_response(me, &from, data);
                 vec![]
             }
@@ -1398,10 +1699,17 @@ impl ClusterInfo {
                     }
                     ret
                 });
+                {
+                    let mut w_me = me.write().unwrap();
+                    w_me.retain_same_cluster(&from, &mut data);
+                    w_me.retain_own_instance(&mut data);
+                }
                 Self::handle_push_message(me, &from, data)
             }
             Protocol::PruneMessage(from, data) => {
-                if data.verify() {
+                if me.read().unwrap().is_quarantined(&from) {
+                    inc_new_counter_debug!("cluster_info-gossip_quarantined_sender", 1);
+                } else if data.verify() {
                     inc_new_counter_debug!("cluster_info-prune_message", 1);
                     inc_new_counter_debug!("cluster_info-prune_message-size", data.prunes.len());
                     match me.write().unwrap().gossip.process_prune_msg(
@@ -1470,12 +1778,16 @@ impl ClusterInfo {
                 if exit.load(Ordering::Relaxed) {
                     return;
                 }
+                if me.read().unwrap().duplicate_instance().is_some() {
+                    exit.store(true, Ordering::Relaxed);
+                    return;
+                }
                 if e.is_err() {
                     let me = me.read().unwrap();
                     debug!(
                         "{}: run_listen timeout, table size: {}",
                         me.gossip.id,
-                        me.gossip.crds.table.len()
+                        me.gossip.crds.len()
                     );
                 }
             })
@@ -1528,28 +1840,32 @@ impl ClusterInfo {
 /// 1.2 - If no, then figure out what layer the node is in and who the neighbors are and only broadcast to them
 ///      1 - also check if there are nodes in the next layer and repeat the layer 1 to layer 2 logic
 
-/// Returns Neighbor Nodes and Children Nodes `(neighbors, children)` for a given node based on its stake (Bank Balance)
-pub fn compute_retransmit_peers<S: std::hash::BuildHasher>(
-    stakes: Option<&HashMap<Pubkey, u64, S>>,
-    cluster_info: &Arc<RwLock<ClusterInfo>>,
+/// Returns Neighbor Nodes and Children Nodes `(neighbors, children)` for the node at `my_index`
+/// of a blob's retransmit tree, `peers` are the other nodes of the tree in order, see
+/// `ClusterInfo::shuffle_peers_and_index`
+pub fn compute_retransmit_peers(
     fanout: usize,
+    my_index: usize,
+    peers: &[&ContactInfo],
 ) -> (Vec<ContactInfo>, Vec<ContactInfo>) {
-    let (my_index, peers) = cluster_info.read().unwrap().sorted_peers_and_index(stakes);
     //calc num_layers and num_neighborhoods using the total number of nodes
     let (num_layers, layer_indices) = ClusterInfo::describe_data_plane(peers.len(), fanout);
 
     if num_layers <= 1 {
         /* single layer data plane */
-        (peers, vec![])
+        (peers.iter().map(|peer| (*peer).clone()).collect(), vec![])
     } else {
         //find my layer
         let locality = ClusterInfo::localize(&layer_indices, fanout, my_index);
         let upper_bound = cmp::min(locality.neighbor_bounds.1, peers.len());
-        let neighbors = peers[locality.neighbor_bounds.0..upper_bound].to_vec();
+        let neighbors = peers[locality.neighbor_bounds.0..upper_bound]
+            .iter()
+            .map(|peer| (*peer).clone())
+            .collect();
         let mut children = Vec::new();
         for ix in locality.next_layer_peers {
             if let Some(peer) = peers.get(ix) {
-                children.push(peer.clone());
+                children.push((*peer).clone());
                 continue;
             }
             break;
@@ -1568,6 +1884,7 @@ pub struct Sockets {
     pub repair: UdpSocket,
     pub retransmit: UdpSocket,
     pub storage: Option<UdpSocket>,
+    pub direct_stream: Option<UdpSocket>,
 }
 
 #[derive(Debug)]
@@ -1613,6 +1930,7 @@ impl Node {
                 repair,
                 retransmit,
                 storage: Some(storage),
+                direct_stream: None,
             },
         }
     }
@@ -1631,7 +1949,8 @@ impl Node {
         let broadcast = UdpSocket::bind("0.0.0.0:0").unwrap();
         let retransmit = UdpSocket::bind("0.0.0.0:0").unwrap();
         let storage = UdpSocket::bind("0.0.0.0:0").unwrap();
-        let info = ContactInfo::new(
+        let direct_stream = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut info = ContactInfo::new(
             pubkey,
             gossip.local_addr().unwrap(),
             tvu.local_addr().unwrap(),
@@ -1642,6 +1961,7 @@ impl Node {
             rpc_pubsub_addr,
             timestamp(),
         );
+        info.direct_stream = direct_stream.local_addr().unwrap();
         Node {
             info,
             sockets: Sockets {
@@ -1653,6 +1973,7 @@ impl Node {
                 repair,
                 retransmit,
                 storage: None,
+                direct_stream: Some(direct_stream),
             },
         }
     }
@@ -1688,8 +2009,9 @@ impl Node {
         let (_, repair) = Self::bind(port_range);
         let (_, broadcast) = Self::bind(port_range);
         let (_, retransmit) = Self::bind(port_range);
+        let (direct_stream_port, direct_stream) = Self::bind(port_range);
 
-        let info = ContactInfo::new(
+        let mut info = ContactInfo::new(
             pubkey,
             SocketAddr::new(gossip_addr.ip(), gossip_port),
             SocketAddr::new(gossip_addr.ip(), tvu_port),
@@ -1700,6 +2022,7 @@ impl Node {
             socketaddr_any!(),
             0,
         );
+        info.direct_stream = SocketAddr::new(gossip_addr.ip(), direct_stream_port);
         trace!("new ContactInfo: {:?}", info);
 
         Node {
@@ -1713,6 +2036,7 @@ impl Node {
                 repair,
                 retransmit,
                 storage: None,
+                direct_stream: Some(direct_stream),
             },
         }
     }
@@ -1730,8 +2054,10 @@ impl Node {
         let empty = socketaddr_any!();
         new.info.tpu = empty;
         new.info.tpu_via_blobs = empty;
+        new.info.direct_stream = empty;
         new.sockets.tpu = vec![];
         new.sockets.tpu_via_blobs = vec![];
+        new.sockets.direct_stream = None;
 
         new
     }
@@ -2150,6 +2476,34 @@ mod tests {
         ClusterInfo::describe_data_plane(nodes, fanout).0
     }
 
+    #[test]
+    fn test_shuffle_peers_and_index() {
+        let me = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+        let mut cluster_info = ClusterInfo::new_with_invalid_keypair(me.clone());
+        let mut stakes = HashMap::new();
+        for i in 0..10 {
+            let peer = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+            stakes.insert(peer.id, i * 100);
+            cluster_info.insert_info(peer);
+        }
+        let (peers, peer_stakes) = cluster_info.sorted_retransmit_peers_and_stakes(Some(&stakes));
+        assert_eq!(peers.len(), 11);
+        assert_eq!(stakes[&peers[0].id], 900);
+
+        // every node finds itself in the same tree for a blob
+        let seed = [3u8; 32];
+        let (my_index, shuffled) =
+            ClusterInfo::shuffle_peers_and_index(&me.id, &peers, &peer_stakes, seed);
+        let other = shuffled[0].id;
+        let (other_index, other_shuffled) =
+            ClusterInfo::shuffle_peers_and_index(&other, &peers, &peer_stakes, seed);
+        let mut tree: Vec<_> = shuffled.iter().map(|peer| peer.id).collect();
+        tree.insert(my_index, me.id);
+        let mut other_tree: Vec<_> = other_shuffled.iter().map(|peer| peer.id).collect();
+        other_tree.insert(other_index, other);
+        assert_eq!(tree, other_tree);
+    }
+
     #[test]
     fn test_describe_data_plane() {
         // no nodes
@@ -2316,6 +2670,236 @@ mod tests {
         assert_eq!(votes, vec![]);
         assert_eq!(max_ts, new_max_ts);
     }
+
+    #[test]
+    fn test_push_epoch_slots_update() {
+        let keys = Keypair::new();
+        let id = keys.pubkey();
+        let contact_info = ContactInfo::new_localhost(&id, 0);
+        let mut cluster_info = ClusterInfo::new_with_invalid_keypair(contact_info);
+        let base: BTreeSet<u64> = (1..1_000).step_by(2).collect();
+        cluster_info.push_epoch_slots(id, 0, base.clone());
+        let (_, base_ts) = cluster_info.get_epoch_state_for_node(&id, None).unwrap();
+
+        // new slots go out on their own and come back merged with the rest
+        sleep(Duration::from_millis(1));
+        let mut slots = base.clone();
+        slots.insert(2_000);
+        cluster_info.push_epoch_slots(id, 0, slots.clone());
+        let update_label = CrdsValueLabel::EpochSlotsUpdate(id);
+        let update = cluster_info.gossip.crds.lookup(&update_label).unwrap();
+        assert_eq!(
+            update.epoch_slots_update().unwrap().slots,
+            vec![2_000].into_iter().collect()
+        );
+        let (epoch_slots, ts) = cluster_info
+            .get_epoch_state_for_node(&id, Some(base_ts))
+            .unwrap();
+        assert_eq!(epoch_slots.slots, slots);
+        assert!(cluster_info
+            .get_epoch_state_for_node(&id, Some(ts))
+            .is_none());
+
+        // a new root replaces the whole set, the update to the old one no longer applies
+        sleep(Duration::from_millis(1));
+        let slots: BTreeSet<u64> = (3..1_000).step_by(2).collect();
+        cluster_info.push_epoch_slots(id, 2, slots.clone());
+        let (epoch_slots, _) = cluster_info.get_epoch_state_for_node(&id, None).unwrap();
+        assert_eq!(epoch_slots.root, 2);
+        assert_eq!(epoch_slots.slots, slots);
+    }
+
+    #[test]
+    fn test_push_version() {
+        let keys = Keypair::new();
+        let cluster_info =
+            ClusterInfo::new_with_invalid_keypair(ContactInfo::new_localhost(&keys.pubkey(), 0));
+        assert_eq!(
+            cluster_info.get_node_version(&keys.pubkey()),
+            Some(env!("CARGO_PKG_VERSION").to_string())
+        );
+        assert_eq!(cluster_info.get_node_version(&Pubkey::new_rand()), None);
+    }
+
+    #[test]
+    fn test_push_ledger_range() {
+        let keys = Keypair::new();
+        let mut cluster_info =
+            ClusterInfo::new_with_invalid_keypair(ContactInfo::new_localhost(&keys.pubkey(), 0));
+        assert_eq!(cluster_info.get_ledger_range_for_node(&keys.pubkey()), None);
+
+        cluster_info.push_ledger_range(3, 10);
+        assert_eq!(
+            cluster_info.get_ledger_range_for_node(&keys.pubkey()),
+            Some((3, 10))
+        );
+        assert_eq!(
+            cluster_info.get_ledger_range_for_node(&Pubkey::new_rand()),
+            None
+        );
+    }
+
+    #[test]
+    fn test_duplicate_instance() {
+        let keypair = Arc::new(Keypair::new());
+        let contact_info = ContactInfo::new_localhost(&keypair.pubkey(), timestamp());
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(
+            contact_info.clone(),
+            keypair.clone(),
+        )));
+        let from_addr = socketaddr!("127.0.0.1:1234");
+        let my_value = |contact_info: ContactInfo| {
+            let mut value = CrdsValue::ContactInfo(contact_info);
+            value.sign(&keypair);
+            value
+        };
+        let push = |value| Protocol::PushMessage(Pubkey::new_rand(), vec![value]);
+
+        // our own contact info echoed back, or a stale one from a previous run, is fine
+        let mut echo = contact_info.clone();
+        echo.wallclock += 1;
+        ClusterInfo::handle_protocol(&cluster_info, &from_addr, None, push(my_value(echo)));
+        let mut stale = contact_info.clone();
+        stale.gossip = socketaddr!("127.0.0.1:1235");
+        stale.wallclock = 0;
+        ClusterInfo::handle_protocol(&cluster_info, &from_addr, None, push(my_value(stale)));
+        assert!(cluster_info.read().unwrap().duplicate_instance().is_none());
+
+        // a newer one with different sockets is another instance, and is dropped
+        let mut duplicate = contact_info.clone();
+        duplicate.gossip = socketaddr!("127.0.0.1:1235");
+        duplicate.wallclock = timestamp() + 1000;
+        ClusterInfo::handle_protocol(
+            &cluster_info,
+            &from_addr,
+            None,
+            push(my_value(duplicate.clone())),
+        );
+        let r_cluster_info = cluster_info.read().unwrap();
+        assert_eq!(
+            r_cluster_info.duplicate_instance().map(|ci| ci.gossip),
+            Some(duplicate.gossip)
+        );
+        assert_eq!(r_cluster_info.my_data().gossip, contact_info.gossip);
+    }
+
+    #[test]
+    fn test_quarantine_mismatched_cluster() {
+        let cluster_id = ContactInfo::compute_cluster_id(&Hash::new(&[1; 32]));
+        let keypair = Arc::new(Keypair::new());
+        let mut contact_info = ContactInfo::new_localhost(&keypair.pubkey(), timestamp());
+        contact_info.cluster_id = cluster_id;
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(contact_info, keypair)));
+        let from_addr = socketaddr!("127.0.0.1:1234");
+
+        let peer_value = |peer_cluster_id| {
+            let peer_keypair = Keypair::new();
+            let mut peer = ContactInfo::new_localhost(&peer_keypair.pubkey(), timestamp());
+            peer.cluster_id = peer_cluster_id;
+            let mut value = CrdsValue::ContactInfo(peer);
+            value.sign(&peer_keypair);
+            value
+        };
+
+        // peers of the same cluster, or that don't advertise one, are accepted
+        let same = peer_value(cluster_id);
+        let unknown = peer_value(0);
+        let other = peer_value(cluster_id.wrapping_add(1).max(1));
+        let push = Protocol::PushMessage(
+            Pubkey::new_rand(),
+            vec![same.clone(), unknown.clone(), other.clone()],
+        );
+        ClusterInfo::handle_protocol(&cluster_info, &from_addr, None, push);
+        {
+            let r_cluster_info = cluster_info.read().unwrap();
+            assert!(r_cluster_info.lookup(&same.pubkey()).is_some());
+            assert!(r_cluster_info.lookup(&unknown.pubkey()).is_some());
+            assert!(r_cluster_info.lookup(&other.pubkey()).is_none());
+            assert_eq!(r_cluster_info.quarantined_peers(), vec![other.pubkey()]);
+            assert!(r_cluster_info
+                .retransmit_peers()
+                .iter()
+                .all(|peer| peer.id != other.pubkey()));
+        }
+
+        // everything relayed by a quarantined peer is dropped
+        let relayed = peer_value(cluster_id);
+        let push = Protocol::PushMessage(other.pubkey(), vec![relayed.clone()]);
+        ClusterInfo::handle_protocol(&cluster_info, &from_addr, None, push);
+        assert!(cluster_info
+            .read()
+            .unwrap()
+            .lookup(&relayed.pubkey())
+            .is_none());
+
+        // and so are its pull requests
+        let pull = Protocol::PullRequest(Bloom::random(1, 0.1, 1024), other);
+        let rsp = ClusterInfo::handle_protocol(&cluster_info, &from_addr, None, pull);
+        assert!(rsp.is_empty());
+    }
+
+    #[test]
+    fn test_quarantine_expires() {
+        let mut cluster_info = ClusterInfo::new_with_invalid_keypair(ContactInfo::default());
+        let id = Pubkey::new_rand();
+        cluster_info.quarantine_at(&id, 1);
+        assert!(cluster_info.is_quarantined_at(&id, QUARANTINE_TIMEOUT_MS));
+        assert!(!cluster_info.is_quarantined_at(&id, QUARANTINE_TIMEOUT_MS + 1));
+
+        // advertising another cluster again restarts the quarantine
+        cluster_info.quarantine_at(&id, QUARANTINE_TIMEOUT_MS);
+        assert!(cluster_info.is_quarantined_at(&id, QUARANTINE_TIMEOUT_MS + 1));
+
+        // past the cap the longest quarantined peer is let go
+        let now = QUARANTINE_TIMEOUT_MS + 1;
+        let ids: Vec<_> = (0..MAX_QUARANTINED_PEERS)
+            .map(|_| Pubkey::new_rand())
+            .collect();
+        for id in &ids {
+            cluster_info.quarantine_at(id, now);
+        }
+        assert_eq!(cluster_info.quarantined_peers.len(), MAX_QUARANTINED_PEERS);
+        assert!(!cluster_info.is_quarantined_at(&id, now));
+        assert!(ids.iter().all(|id| cluster_info.is_quarantined_at(id, now)));
+    }
+
+    #[test]
+    fn test_repair_rejects_mismatched_cluster() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+            let (blobs, _) = make_many_slot_entries(1, 1, 1);
+            blocktree.write_blobs(&blobs).unwrap();
+
+            let cluster_id = ContactInfo::compute_cluster_id(&Hash::new(&[1; 32]));
+            let mut me = ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp());
+            me.cluster_id = cluster_id;
+            let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(me)));
+            let from_addr = socketaddr!("127.0.0.1:1234");
+
+            let mut requester = ContactInfo::new_localhost(&Pubkey::new_rand(), timestamp());
+            requester.cluster_id = cluster_id;
+            let rsp = ClusterInfo::handle_protocol(
+                &cluster_info,
+                &from_addr,
+                Some(&blocktree),
+                Protocol::RequestHighestWindowIndex(requester.clone(), 1, 0),
+            );
+            assert!(!rsp.is_empty());
+
+            requester.cluster_id = cluster_id.wrapping_add(1).max(1);
+            let rsp = ClusterInfo::handle_protocol(
+                &cluster_info,
+                &from_addr,
+                Some(&blocktree),
+                Protocol::RequestHighestWindowIndex(requester.clone(), 1, 0),
+            );
+            assert!(rsp.is_empty());
+            // an unsigned repair request never quarantines the claimed id
+            assert!(!cluster_info.read().unwrap().is_quarantined(&requester.id));
+        }
+        Blocktree::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
 }
 #[test]
 fn test_add_entrypoint() {
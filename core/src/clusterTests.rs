@@ -8,11 +8,14 @@ use crate::connectionInfo::ContactInfo;
 use crate::entryInfo::{Entry, EntrySlice};
 use crate::gossipService::discover_cluster;
 use crate::forkSelection::VOTE_THRESHOLD_DEPTH;
-use morgan_client::thin_client::create_client;
+use bincode::deserialize;
+use morgan_client::thin_client::{create_client, ThinClient};
 use morgan_runtime::epoch_schedule::MINIMUM_SLOT_LENGTH;
 use morgan_interface::client::SyncClient;
 use morgan_interface::hash::Hash;
+use morgan_interface::message::Message;
 use morgan_interface::poh_config::PohConfig;
+use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil, Signature};
 use morgan_interface::system_transaction;
 use morgan_interface::timing::{
@@ -20,12 +23,21 @@ use morgan_interface::timing::{
     NUM_CONSECUTIVE_LEADER_SLOTS,
 };
 use morgan_interface::transport::TransportError;
+use morgan_storage_api::storage_contract::{ProofStatus, StorageContract};
+use morgan_storage_api::storage_instruction;
+use morgan_storage_api::{get_segment_from_slot, SLOTS_PER_SEGMENT};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use morgan_helper::logHelper::*;
 
 const DEFAULT_SLOT_MILLIS: u64 = (DEFAULT_TICKS_PER_SLOT * 1000) / DEFAULT_NUM_TICKS_PER_SECOND;
 
+/// Difs the storage rewards claimed by `claim_and_verify_storage_rewards` are paid out of
+const MINING_POOL_DIFS: u64 = 1_000;
+
+/// How long a storage account may take to have a reward to claim
+const STORAGE_REWARD_TIMEOUT_SECS: u64 = 180;
+
 /// Spend and verify from every node in the network
 pub fn spend_and_verify_all_nodes(
     entry_point_info: &ContactInfo,
@@ -259,6 +271,128 @@ pub fn kill_entry_and_spend_and_verify_rest(
     }
 }
 
+/// Fund a mining pool, then claim the storage rewards of every validator and replicator as
+/// soon as they have one, and check the pool paid each of them what their validated proofs
+/// earned
+pub fn claim_and_verify_storage_rewards(
+    entry_point_info: &ContactInfo,
+    funding_keypair: &Keypair,
+    validator_storage_pubkeys: &[Pubkey],
+    replicator_storage_pubkeys: &[Pubkey],
+) {
+    let client = create_client(entry_point_info.client_facing_addr(), FULLNODE_PORT_RANGE);
+    let mining_pool = Keypair::new();
+    let message = Message::new_with_payer(
+        storage_instruction::create_mining_pool_account(
+            &funding_keypair.pubkey(),
+            &mining_pool.pubkey(),
+            MINING_POOL_DIFS,
+        ),
+        Some(&funding_keypair.pubkey()),
+    );
+    client.send_message(&[funding_keypair], message).unwrap();
+    let mut pool_balance = client.poll_get_balance(&mining_pool.pubkey()).unwrap();
+    assert_eq!(pool_balance, MINING_POOL_DIFS);
+
+    // validators first, a replicator's proofs are gone once it claims them so they can't be
+    // validated any more
+    for storage_pubkey in validator_storage_pubkeys
+        .iter()
+        .chain(replicator_storage_pubkeys)
+    {
+        let rewards = wait_for_storage_rewards(&client, storage_pubkey);
+        for (segment, reward) in rewards {
+            let balance = client.poll_get_balance(storage_pubkey).unwrap();
+            let message = Message::new_with_payer(
+                vec![storage_instruction::claim_reward(
+                    storage_pubkey,
+                    &mining_pool.pubkey(),
+                    segment as u64 * SLOTS_PER_SEGMENT,
+                )],
+                Some(&funding_keypair.pubkey()),
+            );
+            client.send_message(&[funding_keypair], message).unwrap();
+            println!(
+                "{}",
+                printLn(
+                    format!(
+                        "{} claimed {} difs for segment {}",
+                        storage_pubkey, reward, segment
+                    ),
+                    module_path!().to_string()
+                )
+            );
+
+            assert_eq!(
+                client.wait_for_balance(storage_pubkey, Some(balance + reward)),
+                Some(balance + reward)
+            );
+            pool_balance -= reward;
+            assert_eq!(
+                client.wait_for_balance(&mining_pool.pubkey(), Some(pool_balance)),
+                Some(pool_balance)
+            );
+        }
+    }
+    assert!(pool_balance < MINING_POOL_DIFS);
+}
+
+/// The segments `storage_pubkey` can claim a reward for, along with the reward, once it has
+/// at least one
+fn wait_for_storage_rewards(client: &ThinClient, storage_pubkey: &Pubkey) -> Vec<(usize, u64)> {
+    let start = Instant::now();
+    loop {
+        let contract = client
+            .get_account_data(storage_pubkey)
+            .unwrap()
+            .and_then(|data| deserialize(&data).ok());
+        let rewards = match contract {
+            Some(StorageContract::ValidatorStorage {
+                slot,
+                reward_validations,
+                ..
+            }) => reward_validations
+                .into_iter()
+                .filter(|(segment, _)| *segment < get_segment_from_slot(slot))
+                .map(|(segment, validations)| {
+                    let valid = validations
+                        .values()
+                        .filter(|status| **status == ProofStatus::Valid)
+                        .count();
+                    (segment, valid as u64)
+                })
+                .collect(),
+            Some(StorageContract::ReplicatorStorage {
+                proofs,
+                reward_validations,
+            }) => reward_validations
+                .into_iter()
+                .filter(|(segment, _)| proofs.contains_key(segment))
+                .map(|(segment, validations)| {
+                    let statuses: Vec<_> = validations.values().flatten().collect();
+                    let valid = statuses
+                        .iter()
+                        .filter(|status| ***status == ProofStatus::Valid)
+                        .count();
+                    // a replicator is only paid if every validation of its proofs passed
+                    let reward = if valid == statuses.len() { valid } else { 0 };
+                    (segment, reward as u64)
+                })
+                .collect(),
+            _ => vec![],
+        };
+        if !rewards.is_empty() {
+            return rewards;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(STORAGE_REWARD_TIMEOUT_SECS),
+            "no storage reward to claim for {}",
+            storage_pubkey
+        );
+        sleep(Duration::from_millis(DEFAULT_SLOT_MILLIS));
+    }
+}
+
 fn poll_all_nodes_for_signature(
     entry_point_info: &ContactInfo,
     cluster_nodes: &[ContactInfo],
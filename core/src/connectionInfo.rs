@@ -1,4 +1,5 @@
 use bincode::serialize;
+use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 #[cfg(test)]
 use morgan_interface::rpc_port;
@@ -31,6 +32,12 @@ pub struct ContactInfo {
     pub rpc_pubsub: SocketAddr,
     /// latest wallclock picked
     pub wallclock: u64,
+    /// id of the cluster this node belongs to, derived from its genesis blockhash, 0 if unknown
+    pub cluster_id: u16,
+    /// address of the drone embedded in this node, unspecified if it doesn't run one
+    pub drone: SocketAddr,
+    /// address the leader streams its blobs straight to, unspecified if this node takes none
+    pub direct_stream: SocketAddr,
 }
 
 impl Ord for ContactInfo {
@@ -83,6 +90,9 @@ impl Default for ContactInfo {
             rpc_pubsub: socketaddr_any!(),
             wallclock: 0,
             signature: Signature::default(),
+            cluster_id: 0,
+            drone: socketaddr_any!(),
+            direct_stream: socketaddr_any!(),
         }
     }
 }
@@ -110,6 +120,9 @@ impl ContactInfo {
             rpc,
             rpc_pubsub,
             wallclock: now,
+            cluster_id: 0,
+            drone: socketaddr_any!(),
+            direct_stream: socketaddr_any!(),
         }
     }
 
@@ -207,10 +220,58 @@ impl ContactInfo {
         (addr.port() != 0) && Self::is_valid_ip(addr.ip())
     }
 
+    /// Fold the genesis blockhash into the id shared by every node of a cluster
+    pub fn compute_cluster_id(genesis_blockhash: &Hash) -> u16 {
+        let cluster_id = genesis_blockhash
+            .as_ref()
+            .chunks(2)
+            .fold(0u16, |acc, chunk| {
+                acc ^ u16::from_le_bytes([chunk[0], chunk[1]])
+            });
+        // 0 is reserved for nodes that do not advertise a cluster id
+        cluster_id.max(1)
+    }
+
+    /// true unless both nodes advertise a cluster id and the ids differ
+    pub fn is_same_cluster(&self, other: &ContactInfo) -> bool {
+        self.cluster_id == 0 || other.cluster_id == 0 || self.cluster_id == other.cluster_id
+    }
+
     pub fn client_facing_addr(&self) -> (SocketAddr, SocketAddr) {
         (self.rpc, self.tpu)
     }
 
+    /// Address of this node's embedded drone, if it advertises one
+    pub fn valid_drone_addr(&self) -> Option<SocketAddr> {
+        if ContactInfo::is_valid_address(&self.drone) {
+            Some(self.drone)
+        } else {
+            None
+        }
+    }
+
+    /// Address this node takes directly streamed blobs on, if it advertises one
+    pub fn valid_direct_stream_addr(&self) -> Option<SocketAddr> {
+        if ContactInfo::is_valid_address(&self.direct_stream) {
+            Some(self.direct_stream)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `other` advertises the same sockets as this node, ignoring the wallclock
+    pub fn same_addresses(&self, other: &ContactInfo) -> bool {
+        self.gossip == other.gossip
+            && self.tvu == other.tvu
+            && self.tpu == other.tpu
+            && self.tpu_via_blobs == other.tpu_via_blobs
+            && self.storage_addr == other.storage_addr
+            && self.rpc == other.rpc
+            && self.rpc_pubsub == other.rpc_pubsub
+            && self.drone == other.drone
+            && self.direct_stream == other.direct_stream
+    }
+
     pub fn valid_client_facing_addr(&self) -> Option<(SocketAddr, SocketAddr)> {
         if ContactInfo::is_valid_address(&self.rpc) && ContactInfo::is_valid_address(&self.tpu) {
             Some((self.rpc, self.tpu))
@@ -237,6 +298,9 @@ impl Signable for ContactInfo {
             rpc: SocketAddr,
             rpc_pubsub: SocketAddr,
             wallclock: u64,
+            cluster_id: u16,
+            drone: SocketAddr,
+            direct_stream: SocketAddr,
         }
 
         let me = self;
@@ -250,6 +314,9 @@ impl Signable for ContactInfo {
             rpc: me.rpc,
             rpc_pubsub: me.rpc_pubsub,
             wallclock: me.wallclock,
+            cluster_id: me.cluster_id,
+            drone: me.drone,
+            direct_stream: me.direct_stream,
         };
         serialize(&data).expect("failed to serialize ContactInfo")
     }
@@ -291,6 +358,8 @@ mod tests {
         assert!(ci.rpc_pubsub.ip().is_unspecified());
         assert!(ci.tpu.ip().is_unspecified());
         assert!(ci.storage_addr.ip().is_unspecified());
+        assert!(ci.drone.ip().is_unspecified());
+        assert!(ci.direct_stream.ip().is_unspecified());
     }
     #[test]
     fn test_multicast() {
@@ -352,4 +421,42 @@ mod tests {
         ci.rpc = socketaddr!("127.0.0.1:234");
         assert!(ci.valid_client_facing_addr().is_some());
     }
+
+    #[test]
+    fn test_valid_drone_addr() {
+        let mut ci = ContactInfo::default();
+        assert_eq!(ci.valid_drone_addr(), None);
+        ci.drone = socketaddr!("127.0.0.1:345");
+        assert_eq!(ci.valid_drone_addr(), Some(ci.drone));
+    }
+
+    #[test]
+    fn test_valid_direct_stream_addr() {
+        let mut ci = ContactInfo::default();
+        assert_eq!(ci.valid_direct_stream_addr(), None);
+        ci.direct_stream = socketaddr!("127.0.0.1:346");
+        assert_eq!(ci.valid_direct_stream_addr(), Some(ci.direct_stream));
+    }
+
+    #[test]
+    fn test_cluster_id() {
+        let genesis_blockhash = Hash::new(&[1; 32]);
+        let cluster_id = ContactInfo::compute_cluster_id(&genesis_blockhash);
+        assert_ne!(cluster_id, 0);
+        assert_eq!(
+            cluster_id,
+            ContactInfo::compute_cluster_id(&genesis_blockhash)
+        );
+        assert_eq!(ContactInfo::compute_cluster_id(&Hash::default()), 1);
+
+        let mut ci = ContactInfo::default();
+        let mut other = ContactInfo::default();
+        assert!(ci.is_same_cluster(&other));
+        ci.cluster_id = cluster_id;
+        assert!(ci.is_same_cluster(&other));
+        other.cluster_id = cluster_id;
+        assert!(ci.is_same_cluster(&other));
+        other.cluster_id = cluster_id.wrapping_add(1).max(1);
+        assert!(!ci.is_same_cluster(&other));
+    }
 }
@@ -9,10 +9,16 @@
 //! The Label, the record Pubkey, and all the record labels can be derived
 //! from a single CrdsValue.
 //!
-//! The actual data is stored in a single map of
+//! The actual data is stored in maps of
 //! `CrdsValueLabel(Pubkey) -> CrdsValue` This allows for partial record
 //! updates to be propagated through the network.
 //!
+//! The maps are sharded by the record Pubkey, so all the labels of a record
+//! live in the same shard. Each shard has its own lock, so the table is
+//! shared rather than borrowed mutably: batches of values from push and pull
+//! messages are inserted into the shards in parallel, and readers only wait
+//! on the shards being written to.
+//!
 //! This means that full `Record` updates are not atomic.
 //!
 //! Additional labels can be added by appending them to the CrdsValueLabel,
@@ -29,12 +35,18 @@ use bincode::serialize;
 use indexmap::map::IndexMap;
 use morgan_interface::hash::{hash, Hash};
 use morgan_interface::pubkey::Pubkey;
+use rayon::prelude::*;
 use std::cmp;
+use std::sync::RwLock;
+
+/// The number of shards the table is split into
+pub const NUM_CRDS_SHARDS: usize = 16;
+
+type CrdsShard = IndexMap<CrdsValueLabel, VersionedCrdsValue>;
 
-#[derive(Clone)]
 pub struct Crds {
-    /// Stores the map of labels and values
-    pub table: IndexMap<CrdsValueLabel, VersionedCrdsValue>,
+    /// Stores the maps of labels and values, indexed by `shard_index`
+    shards: Vec<RwLock<CrdsShard>>,
 }
 
 #[derive(PartialEq, Debug)]
@@ -82,60 +94,159 @@ impl VersionedCrdsValue {
 impl Default for Crds {
     fn default() -> Self {
         Crds {
-            table: IndexMap::new(),
+            shards: (0..NUM_CRDS_SHARDS)
+                .map(|_| RwLock::new(IndexMap::new()))
+                .collect(),
+        }
+    }
+}
+
+impl Clone for Crds {
+    fn clone(&self) -> Self {
+        Crds {
+            shards: self
+                .shards
+                .iter()
+                .map(|shard| RwLock::new(shard.read().unwrap().clone()))
+                .collect(),
         }
     }
 }
 
+/// The shard holding the records of `pubkey`
+fn shard_index(pubkey: &Pubkey) -> usize {
+    pubkey.as_ref()[0] as usize % NUM_CRDS_SHARDS
+}
+
+fn insert_into_shard(
+    shard: &mut CrdsShard,
+    new_value: VersionedCrdsValue,
+) -> Result<Option<VersionedCrdsValue>, CrdsError> {
+    let label = new_value.value.label();
+    let wallclock = new_value.value.wallclock();
+    let do_insert = shard
+        .get(&label)
+        .map(|current| new_value > *current)
+        .unwrap_or(true);
+    if do_insert {
+        let old = shard.insert(label, new_value);
+        Ok(old)
+    } else {
+        trace!("INSERT FAILED data: {} new.wallclock: {}", label, wallclock,);
+        Err(CrdsError::InsertFailed)
+    }
+}
+
 impl Crds {
+    fn shard(&self, label: &CrdsValueLabel) -> &RwLock<CrdsShard> {
+        &self.shards[shard_index(&label.pubkey())]
+    }
+
     /// must be called atomically with `insert_versioned`
     pub fn new_versioned(&self, local_timestamp: u64, value: CrdsValue) -> VersionedCrdsValue {
         VersionedCrdsValue::new(local_timestamp, value)
     }
     /// insert the new value, returns the old value if insert succeeds
     pub fn insert_versioned(
-        &mut self,
+        &self,
         new_value: VersionedCrdsValue,
     ) -> Result<Option<VersionedCrdsValue>, CrdsError> {
-        let label = new_value.value.label();
-        let wallclock = new_value.value.wallclock();
-        let do_insert = self
-            .table
-            .get(&label)
-            .map(|current| new_value > *current)
-            .unwrap_or(true);
-        if do_insert {
-            let old = self.table.insert(label, new_value);
-            Ok(old)
-        } else {
-            trace!("INSERT FAILED data: {} new.wallclock: {}", label, wallclock,);
-            Err(CrdsError::InsertFailed)
+        let mut shard = self.shard(&new_value.value.label()).write().unwrap();
+        insert_into_shard(&mut shard, new_value)
+    }
+    /// insert a batch of new values, each shard in parallel, returns the result of each insert
+    /// in the order of `new_values`. Values of the same record are inserted in order.
+    pub fn insert_versioned_batch(
+        &self,
+        new_values: Vec<VersionedCrdsValue>,
+    ) -> Vec<Result<Option<VersionedCrdsValue>, CrdsError>> {
+        let num_values = new_values.len();
+        let mut batches: Vec<Vec<(usize, VersionedCrdsValue)>> = vec![vec![]; NUM_CRDS_SHARDS];
+        for (i, new_value) in new_values.into_iter().enumerate() {
+            batches[shard_index(&new_value.value.label().pubkey())].push((i, new_value));
+        }
+        let shard_results: Vec<Vec<_>> = self
+            .shards
+            .par_iter()
+            .zip(batches)
+            .filter(|(_, batch)| !batch.is_empty())
+            .map(|(shard, batch)| {
+                let mut shard = shard.write().unwrap();
+                batch
+                    .into_iter()
+                    .map(|(i, new_value)| (i, insert_into_shard(&mut shard, new_value)))
+                    .collect()
+            })
+            .collect();
+        let mut results: Vec<_> = (0..num_values).map(|_| Ok(None)).collect();
+        for (i, result) in shard_results.into_iter().flatten() {
+            results[i] = result;
         }
+        results
+    }
+    /// hash `values` in parallel and insert them as a batch, see `insert_versioned_batch`
+    pub fn insert_batch(
+        &self,
+        values: Vec<CrdsValue>,
+        local_timestamp: u64,
+    ) -> Vec<Result<Option<VersionedCrdsValue>, CrdsError>> {
+        let new_values = values
+            .into_par_iter()
+            .map(|value| VersionedCrdsValue::new(local_timestamp, value))
+            .collect();
+        self.insert_versioned_batch(new_values)
     }
     pub fn insert(
-        &mut self,
+        &self,
         value: CrdsValue,
         local_timestamp: u64,
     ) -> Result<Option<VersionedCrdsValue>, CrdsError> {
         let new_value = self.new_versioned(local_timestamp, value);
         self.insert_versioned(new_value)
     }
-    pub fn lookup(&self, label: &CrdsValueLabel) -> Option<&CrdsValue> {
-        self.table.get(label).map(|x| &x.value)
+    pub fn lookup(&self, label: &CrdsValueLabel) -> Option<CrdsValue> {
+        self.lookup_versioned(label).map(|x| x.value)
+    }
+
+    pub fn lookup_versioned(&self, label: &CrdsValueLabel) -> Option<VersionedCrdsValue> {
+        self.shard(label).read().unwrap().get(label).cloned()
+    }
+
+    /// Call `f` with all the values in the table, shard by shard. Every shard is read locked
+    /// until `f` returns, so `f` must not go back to the table.
+    pub fn with_values<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&mut dyn Iterator<Item = &VersionedCrdsValue>) -> T,
+    {
+        let shards: Vec<_> = self
+            .shards
+            .iter()
+            .map(|shard| shard.read().unwrap())
+            .collect();
+        f(&mut shards.iter().flat_map(|shard| shard.values()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.read().unwrap().len())
+            .sum()
     }
 
-    pub fn lookup_versioned(&self, label: &CrdsValueLabel) -> Option<&VersionedCrdsValue> {
-        self.table.get(label)
+    pub fn is_empty(&self) -> bool {
+        self.shards
+            .iter()
+            .all(|shard| shard.read().unwrap().is_empty())
     }
 
-    fn update_label_timestamp(&mut self, id: &CrdsValueLabel, now: u64) {
-        if let Some(e) = self.table.get_mut(id) {
+    fn update_label_timestamp(&self, id: &CrdsValueLabel, now: u64) {
+        if let Some(e) = self.shard(id).write().unwrap().get_mut(id) {
             e.local_timestamp = cmp::max(e.local_timestamp, now);
         }
     }
 
     /// Update the timestamp's of all the labels that are assosciated with Pubkey
-    pub fn update_record_timestamp(&mut self, pubkey: &Pubkey, now: u64) {
+    pub fn update_record_timestamp(&self, pubkey: &Pubkey, now: u64) {
         for label in &CrdsValue::record_labels(pubkey) {
             self.update_label_timestamp(label, now);
         }
@@ -143,21 +254,23 @@ impl Crds {
 
     /// find all the keys that are older or equal to min_ts
     pub fn find_old_labels(&self, min_ts: u64) -> Vec<CrdsValueLabel> {
-        self.table
+        self.shards
             .iter()
-            .filter_map(|(k, v)| {
-                if v.local_timestamp <= min_ts {
-                    Some(k)
-                } else {
-                    None
-                }
+            .flat_map(|shard| {
+                shard
+                    .read()
+                    .unwrap()
+                    .iter()
+                    .filter(|(_, v)| v.local_timestamp <= min_ts)
+                    .map(|(k, _)| k.clone())
+                    .collect::<Vec<_>>()
             })
-            .cloned()
             .collect()
     }
 
-    pub fn remove(&mut self, key: &CrdsValueLabel) {
-        self.table.remove(key);
+    /// remove the value at `key`, returns it if there was one
+    pub fn remove(&self, key: &CrdsValueLabel) -> Option<VersionedCrdsValue> {
+        self.shard(key).write().unwrap().remove(key)
     }
 }
 
@@ -168,24 +281,30 @@ mod test {
 
     #[test]
     fn test_insert() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let val = CrdsValue::ContactInfo(ContactInfo::default());
         assert_eq!(crds.insert(val.clone(), 0).ok(), Some(None));
-        assert_eq!(crds.table.len(), 1);
-        assert!(crds.table.contains_key(&val.label()));
-        assert_eq!(crds.table[&val.label()].local_timestamp, 0);
+        assert_eq!(crds.len(), 1);
+        assert!(crds.lookup(&val.label()).is_some());
+        assert_eq!(
+            crds.lookup_versioned(&val.label()).unwrap().local_timestamp,
+            0
+        );
     }
     #[test]
     fn test_update_old() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let val = CrdsValue::ContactInfo(ContactInfo::default());
         assert_eq!(crds.insert(val.clone(), 0), Ok(None));
         assert_eq!(crds.insert(val.clone(), 1), Err(CrdsError::InsertFailed));
-        assert_eq!(crds.table[&val.label()].local_timestamp, 0);
+        assert_eq!(
+            crds.lookup_versioned(&val.label()).unwrap().local_timestamp,
+            0
+        );
     }
     #[test]
     fn test_update_new() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let original = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::default(), 0));
         assert_matches!(crds.insert(original.clone(), 0), Ok(_));
         let val = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::default(), 1));
@@ -193,42 +312,123 @@ mod test {
             crds.insert(val.clone(), 1).unwrap().unwrap().value,
             original
         );
-        assert_eq!(crds.table[&val.label()].local_timestamp, 1);
+        assert_eq!(
+            crds.lookup_versioned(&val.label()).unwrap().local_timestamp,
+            1
+        );
+    }
+    #[test]
+    fn test_insert_batch() {
+        let crds = Crds::default();
+        let pubkeys: Vec<_> = (0..64).map(|_| Pubkey::new_rand()).collect();
+        let values: Vec<_> = pubkeys
+            .iter()
+            .map(|pubkey| CrdsValue::ContactInfo(ContactInfo::new_localhost(pubkey, 0)))
+            .collect();
+        let results = crds.insert_batch(values.clone(), 0);
+        assert!(results.iter().all(|result| *result == Ok(None)));
+        assert_eq!(crds.len(), pubkeys.len());
+        assert_eq!(crds.with_values(|values| values.count()), pubkeys.len());
+
+        // values of the same record are applied in order, results keep the batch order
+        let newer = CrdsValue::ContactInfo(ContactInfo::new_localhost(&pubkeys[1], 1));
+        let batch = vec![values[0].clone(), newer.clone(), values[1].clone()];
+        let mut results = crds.insert_batch(batch, 1).into_iter();
+        assert_eq!(results.next(), Some(Err(CrdsError::InsertFailed)));
+        assert_eq!(results.next().unwrap().unwrap().unwrap().value, values[1]);
+        assert_eq!(results.next(), Some(Err(CrdsError::InsertFailed)));
+        assert_eq!(crds.lookup(&newer.label()), Some(newer.clone()));
+        assert_eq!(crds.len(), pubkeys.len());
+
+        crds.remove(&newer.label());
+        assert_eq!(crds.lookup(&newer.label()), None);
+        assert_eq!(crds.len(), pubkeys.len() - 1);
     }
     #[test]
     fn test_update_timestamp() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let val = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::default(), 0));
+        let val2 = CrdsValue::ContactInfo(ContactInfo::default());
+        // equal wallclocks are ordered by value hash, insert the lower one first
+        let (val, val2) =
+            if VersionedCrdsValue::new(0, val.clone()) < VersionedCrdsValue::new(0, val2.clone()) {
+                (val, val2)
+            } else {
+                (val2, val)
+            };
         assert_eq!(crds.insert(val.clone(), 0), Ok(None));
 
         crds.update_label_timestamp(&val.label(), 1);
-        assert_eq!(crds.table[&val.label()].local_timestamp, 1);
-        assert_eq!(crds.table[&val.label()].insert_timestamp, 0);
+        assert_eq!(
+            crds.lookup_versioned(&val.label()).unwrap().local_timestamp,
+            1
+        );
+        assert_eq!(
+            crds.lookup_versioned(&val.label())
+                .unwrap()
+                .insert_timestamp,
+            0
+        );
 
-        let val2 = CrdsValue::ContactInfo(ContactInfo::default());
         assert_eq!(val2.label().pubkey(), val.label().pubkey());
         assert_matches!(crds.insert(val2.clone(), 0), Ok(Some(_)));
 
         crds.update_record_timestamp(&val.label().pubkey(), 2);
-        assert_eq!(crds.table[&val.label()].local_timestamp, 2);
-        assert_eq!(crds.table[&val.label()].insert_timestamp, 0);
-        assert_eq!(crds.table[&val2.label()].local_timestamp, 2);
-        assert_eq!(crds.table[&val2.label()].insert_timestamp, 0);
+        assert_eq!(
+            crds.lookup_versioned(&val.label()).unwrap().local_timestamp,
+            2
+        );
+        assert_eq!(
+            crds.lookup_versioned(&val.label())
+                .unwrap()
+                .insert_timestamp,
+            0
+        );
+        assert_eq!(
+            crds.lookup_versioned(&val2.label())
+                .unwrap()
+                .local_timestamp,
+            2
+        );
+        assert_eq!(
+            crds.lookup_versioned(&val2.label())
+                .unwrap()
+                .insert_timestamp,
+            0
+        );
 
         crds.update_record_timestamp(&val.label().pubkey(), 1);
-        assert_eq!(crds.table[&val.label()].local_timestamp, 2);
-        assert_eq!(crds.table[&val.label()].insert_timestamp, 0);
+        assert_eq!(
+            crds.lookup_versioned(&val.label()).unwrap().local_timestamp,
+            2
+        );
+        assert_eq!(
+            crds.lookup_versioned(&val.label())
+                .unwrap()
+                .insert_timestamp,
+            0
+        );
 
         let mut ci = ContactInfo::default();
         ci.wallclock += 1;
         let val3 = CrdsValue::ContactInfo(ci);
         assert_matches!(crds.insert(val3.clone(), 3), Ok(Some(_)));
-        assert_eq!(crds.table[&val2.label()].local_timestamp, 3);
-        assert_eq!(crds.table[&val2.label()].insert_timestamp, 3);
+        assert_eq!(
+            crds.lookup_versioned(&val2.label())
+                .unwrap()
+                .local_timestamp,
+            3
+        );
+        assert_eq!(
+            crds.lookup_versioned(&val2.label())
+                .unwrap()
+                .insert_timestamp,
+            3
+        );
     }
     #[test]
     fn test_find_old_records() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let val = CrdsValue::ContactInfo(ContactInfo::default());
         assert_eq!(crds.insert(val.clone(), 1), Ok(None));
 
@@ -238,7 +438,7 @@ mod test {
     }
     #[test]
     fn test_remove() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let val = CrdsValue::ContactInfo(ContactInfo::default());
         assert_matches!(crds.insert(val.clone(), 1), Ok(_));
 
@@ -0,0 +1,387 @@
+//! The `directStream` module streams the blobs of a leader straight to a handful of the
+//! highest staked validators next to broadcasting them, so those validators don't wait on
+//! the broadcast tree for the first hop.  Receivers drive the flow: a leader only sends a
+//! validator as many blobs as the validator last granted it credits for, and a validator
+//! grants credits back once it has drained its socket, fewer the further behind it is.
+//! Grants are only taken from the address the validator advertises for its stream, and a
+//! leader that heard no grant for `GRANT_TIMEOUT_MS` while out of credits probes with a
+//! single blob, so a lost grant or a grant of 0 doesn't stall the stream.
+//! Streamed blobs take the same path as broadcast ones, the window service verifies them
+//! and the ledger keeps whichever copy of a blob lands first.
+
+use crate::connectionInfo::ContactInfo;
+use crate::packet::{Blob, SharedBlob, BLOB_HEADER_SIZE};
+use crate::result::{Error, Result};
+use crate::service::Service;
+use crate::streamer::BlobSender;
+use bincode::{deserialize, serialize};
+use hashbrown::{HashMap, HashSet};
+use morgan_interface::pubkey::Pubkey;
+use morgan_metricbot::{inc_new_counter_debug, inc_new_counter_error};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Blobs a validator may have in flight from a leader before it grants more
+pub const DIRECT_STREAM_WINDOW: u64 = 256;
+
+/// How long a leader out of credits for a validator waits on a grant before it sends the
+/// validator a blob anyway, for the validator to answer with a new grant
+pub const GRANT_TIMEOUT_MS: u64 = 200;
+
+/// Sent by a validator to the leader streaming to it, how many blobs the leader may send
+/// it until the next grant
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct CreditGrant {
+    pub from: Pubkey,
+    pub credits: u64,
+}
+
+/// What the leader may still send a validator and where the validator streams from
+struct PeerCredits {
+    addr: SocketAddr,
+    credits: u64,
+    last_grant: Instant,
+}
+
+/// The socket a node takes streamed blobs on, and streams its own blobs from as leader
+pub struct DirectStream {
+    id: Pubkey,
+    socket: UdpSocket,
+    num_peers: usize,
+    credits: RwLock<HashMap<Pubkey, PeerCredits>>,
+}
+
+impl DirectStream {
+    /// As leader, stream to the `num_peers` highest staked validators, 0 only takes streams
+    pub fn new(id: &Pubkey, socket: UdpSocket, num_peers: usize) -> Self {
+        Self {
+            id: *id,
+            socket,
+            num_peers,
+            credits: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// The blobs the leader may still send `id`, None until it streamed to `id`
+    pub fn credits(&self, id: &Pubkey) -> Option<u64> {
+        self.credits
+            .read()
+            .unwrap()
+            .get(id)
+            .map(|peer| peer.credits)
+    }
+
+    /// Send each of the `num_peers` highest staked of `peers` as many of `blobs` as it has
+    /// credits for, `peers` and `stakes` sorted by stake the way broadcast takes them, and
+    /// a blob to any it has been waiting on a grant from for `GRANT_TIMEOUT_MS`.
+    /// Returns the number of blobs sent
+    pub fn send(&self, peers: &[ContactInfo], stakes: &[u64], blobs: &[SharedBlob]) -> usize {
+        if self.num_peers == 0 {
+            return 0;
+        }
+        let orders: Vec<_> = {
+            let mut credits = self.credits.write().unwrap();
+            peers
+                .iter()
+                .zip(stakes)
+                .filter(|(peer, stake)| peer.id != self.id && **stake > 0)
+                .filter_map(|(peer, _)| Some((peer.id, peer.valid_direct_stream_addr()?)))
+                .take(self.num_peers)
+                .map(|(id, addr)| {
+                    let peer = credits.entry(id).or_insert_with(|| PeerCredits {
+                        addr,
+                        credits: DIRECT_STREAM_WINDOW,
+                        last_grant: Instant::now(),
+                    });
+                    // the validator may have moved its stream since it last granted
+                    peer.addr = addr;
+                    if peer.credits == 0
+                        && peer.last_grant.elapsed() >= Duration::from_millis(GRANT_TIMEOUT_MS)
+                    {
+                        peer.credits = 1;
+                        peer.last_grant = Instant::now();
+                    }
+                    let num_blobs = blobs.len().min(peer.credits as usize);
+                    peer.credits -= num_blobs as u64;
+                    (addr, num_blobs)
+                })
+                .collect()
+        };
+
+        let mut sent = 0;
+        for (addr, num_blobs) in orders {
+            for blob in &blobs[..num_blobs] {
+                let blob = blob.read().unwrap();
+                if let Err(e) = self.socket.send_to(&blob.data[..blob.meta.size], addr) {
+                    inc_new_counter_error!("direct_stream-send_to_error", 1, 1);
+                    trace!("{}: direct stream to {} failed: {:?}", self.id, addr, e);
+                    break;
+                }
+                sent += 1;
+            }
+        }
+        inc_new_counter_debug!("direct_stream-sent", sent);
+        sent
+    }
+
+    /// Take the credits `grant` gives, only from a validator this node streams to and only
+    /// when it came from where that validator streams from
+    pub fn grant(&self, grant: &CreditGrant, from_addr: &SocketAddr) {
+        match self.credits.write().unwrap().get_mut(&grant.from) {
+            Some(peer) if peer.addr == *from_addr => {
+                peer.credits = grant.credits.min(DIRECT_STREAM_WINDOW);
+                peer.last_grant = Instant::now();
+            }
+            Some(_) => inc_new_counter_error!("direct_stream-grant_addr_mismatch", 1, 1),
+            None => (),
+        }
+    }
+}
+
+fn recv_direct_stream(direct_stream: &DirectStream, sender: &BlobSender) -> Result<()> {
+    let mut blobs = Blob::recv_from(&direct_stream.socket)?;
+
+    // the more blobs waited on the socket the further behind this node is
+    let credits = DIRECT_STREAM_WINDOW.saturating_sub(blobs.len() as u64);
+    let mut sources = HashSet::new();
+    blobs.retain(|blob| {
+        let blob = blob.read().unwrap();
+        if blob.meta.size < BLOB_HEADER_SIZE {
+            // too short to be a blob, a grant from a validator this node streams to
+            if let Ok(grant) = deserialize(&blob.data[..blob.meta.size]) {
+                direct_stream.grant(&grant, &blob.meta.addr());
+            }
+            false
+        } else {
+            sources.insert(blob.meta.addr());
+            true
+        }
+    });
+    if blobs.is_empty() {
+        return Ok(());
+    }
+    inc_new_counter_debug!("direct_stream-received", blobs.len());
+    sender.send(blobs)?;
+
+    let grant = serialize(&CreditGrant {
+        from: direct_stream.id,
+        credits,
+    })?;
+    for addr in sources {
+        if direct_stream.socket.send_to(&grant, addr).is_err() {
+            inc_new_counter_error!("direct_stream-grant_error", 1, 1);
+        }
+    }
+    Ok(())
+}
+
+/// Takes the blobs streamed to this node into the tvu, and the credits granted to it as
+/// leader
+pub struct DirectStreamService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl DirectStreamService {
+    pub fn new(
+        direct_stream: &Arc<DirectStream>,
+        sender: BlobSender,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let direct_stream = direct_stream.clone();
+        let exit = exit.clone();
+        direct_stream
+            .socket
+            .set_read_timeout(Some(Duration::new(1, 0)))
+            .expect("set socket timeout");
+        let thread_hdl = Builder::new()
+            .name("morgan-direct-stream".to_string())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    match recv_direct_stream(&direct_stream, &sender) {
+                        Err(Error::SendError) => break,
+                        Err(Error::IO(_)) | Ok(()) => (),
+                        Err(_) => inc_new_counter_error!("direct_stream-recv_error", 1, 1),
+                    }
+                }
+            })
+            .unwrap();
+
+        Self { thread_hdl }
+    }
+}
+
+impl Service for DirectStreamService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    fn new_direct_stream(num_peers: usize) -> (DirectStream, ContactInfo) {
+        let id = Pubkey::new_rand();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut info = ContactInfo::new_localhost(&id, 0);
+        info.direct_stream = socket.local_addr().unwrap();
+        (DirectStream::new(&id, socket, num_peers), info)
+    }
+
+    fn new_blobs(num_blobs: usize) -> Vec<SharedBlob> {
+        (0..num_blobs)
+            .map(|i| {
+                let blob = SharedBlob::default();
+                {
+                    let mut blob = blob.write().unwrap();
+                    blob.set_index(i as u64);
+                    blob.meta.size = BLOB_HEADER_SIZE + 1;
+                }
+                blob
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_direct_stream_credits() {
+        let (leader, leader_info) = new_direct_stream(1);
+        let (_, top_info) = new_direct_stream(0);
+        let (_, other_info) = new_direct_stream(0);
+        let (_, unstaked_info) = new_direct_stream(0);
+        let peers = vec![
+            leader_info.clone(),
+            top_info.clone(),
+            other_info.clone(),
+            unstaked_info.clone(),
+        ];
+        let stakes = vec![10, 5, 1, 0];
+
+        // only the highest staked peer past the leader itself gets the blobs
+        assert_eq!(leader.send(&peers, &stakes, &new_blobs(3)), 3);
+        assert_eq!(leader.credits(&top_info.id), Some(DIRECT_STREAM_WINDOW - 3));
+        assert_eq!(leader.credits(&other_info.id), None);
+        assert_eq!(leader.credits(&leader_info.id), None);
+
+        // grants replace what's left, capped at the window
+        leader.grant(
+            &CreditGrant {
+                from: top_info.id,
+                credits: 2,
+            },
+            &top_info.direct_stream,
+        );
+        assert_eq!(leader.send(&peers, &stakes, &new_blobs(3)), 2);
+        assert_eq!(leader.credits(&top_info.id), Some(0));
+        assert_eq!(leader.send(&peers, &stakes, &new_blobs(3)), 0);
+
+        // grants from anywhere but where the validator streams from are dropped
+        leader.grant(
+            &CreditGrant {
+                from: top_info.id,
+                credits: 1,
+            },
+            &other_info.direct_stream,
+        );
+        assert_eq!(leader.credits(&top_info.id), Some(0));
+
+        // without a grant the leader probes with a single blob once the grant is overdue
+        thread::sleep(Duration::from_millis(GRANT_TIMEOUT_MS));
+        assert_eq!(leader.send(&peers, &stakes, &new_blobs(3)), 1);
+        assert_eq!(leader.send(&peers, &stakes, &new_blobs(3)), 0);
+
+        leader.grant(
+            &CreditGrant {
+                from: top_info.id,
+                credits: DIRECT_STREAM_WINDOW * 2,
+            },
+            &top_info.direct_stream,
+        );
+        assert_eq!(leader.credits(&top_info.id), Some(DIRECT_STREAM_WINDOW));
+
+        // no credits for validators the leader doesn't stream to
+        leader.grant(
+            &CreditGrant {
+                from: other_info.id,
+                credits: 1,
+            },
+            &other_info.direct_stream,
+        );
+        assert_eq!(leader.credits(&other_info.id), None);
+
+        // a peer that isn't leader doesn't stream
+        let (peer, _) = new_direct_stream(0);
+        assert_eq!(peer.send(&peers, &stakes, &new_blobs(3)), 0);
+    }
+
+    #[test]
+    fn test_direct_stream_service() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (leader, leader_info) = new_direct_stream(1);
+        let (peer, peer_info) = new_direct_stream(0);
+        let leader = Arc::new(leader);
+        let peer = Arc::new(peer);
+        let (leader_sender, _leader_receiver) = channel();
+        let (peer_sender, peer_receiver) = channel();
+        let leader_service = DirectStreamService::new(&leader, leader_sender, &exit);
+        let peer_service = DirectStreamService::new(&peer, peer_sender, &exit);
+
+        // the peer takes the blobs streamed to it and grants credits back to their sender
+        let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for blob in new_blobs(3) {
+            let blob = blob.read().unwrap();
+            sender
+                .send_to(&blob.data[..blob.meta.size], &peer_info.direct_stream)
+                .unwrap();
+        }
+        let mut received = vec![];
+        while received.len() < 3 {
+            let mut blobs = peer_receiver.recv_timeout(Duration::new(1, 0)).unwrap();
+            received.append(&mut blobs);
+        }
+        let indexes: Vec<_> = received
+            .iter()
+            .map(|blob| blob.read().unwrap().index())
+            .collect();
+        assert_eq!(indexes, vec![0, 1, 2]);
+
+        let mut buf = [0; BLOB_HEADER_SIZE];
+        sender.set_read_timeout(Some(Duration::new(1, 0))).unwrap();
+        let (size, _) = sender.recv_from(&mut buf).unwrap();
+        let grant: CreditGrant = deserialize(&buf[..size]).unwrap();
+        assert_eq!(grant.from, peer_info.id);
+        assert!(grant.credits >= DIRECT_STREAM_WINDOW - 3);
+
+        // the leader takes the credits granted by a validator it streams to
+        let mut receiver_info = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+        receiver_info.direct_stream = sender.local_addr().unwrap();
+        let peers = vec![leader_info, receiver_info.clone()];
+        assert_eq!(leader.send(&peers, &[2, 1], &new_blobs(3)), 3);
+        let grant = serialize(&CreditGrant {
+            from: receiver_info.id,
+            credits: 1,
+        })
+        .unwrap();
+        sender
+            .send_to(&grant, &leader.socket.local_addr().unwrap())
+            .unwrap();
+        let mut credits = None;
+        for _ in 0..100 {
+            credits = leader.credits(&receiver_info.id);
+            if credits == Some(1) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(credits, Some(1));
+
+        exit.store(true, Ordering::Relaxed);
+        leader_service.join().unwrap();
+        peer_service.join().unwrap();
+    }
+}
@@ -214,6 +214,8 @@ where
 pub trait EntrySlice {
     /// Verifies the hashes and counts of a slice of transactions are all consistent.
     fn verify(&self, start_hash: &Hash) -> bool;
+    /// Verifies the signatures of every transaction in the slice.
+    fn verify_tx_signatures(&self) -> bool;
     fn to_shared_blobs(&self) -> Vec<SharedBlob>;
     fn to_blobs(&self) -> Vec<Blob>;
     fn to_single_entry_blobs(&self) -> Vec<Blob>;
@@ -221,6 +223,15 @@ pub trait EntrySlice {
 }
 
 impl EntrySlice for [Entry] {
+    fn verify_tx_signatures(&self) -> bool {
+        self.par_iter().all(|entry| {
+            entry
+                .transactions
+                .par_iter()
+                .all(Transaction::verify_signatures)
+        })
+    }
+
     fn verify(&self, start_hash: &Hash) -> bool {
         let genesis = [Entry {
             num_hashes: 0,
@@ -416,7 +427,7 @@ pub fn make_large_test_entries(num_entries: usize) -> Vec<Entry> {
 
 #[cfg(test)]
 pub fn make_consecutive_blobs(
-    id: &morgan_interface::pubkey::Pubkey,
+    keypair: &Keypair,
     num_blobs_to_make: u64,
     start_height: u64,
     start_hash: Hash,
@@ -429,7 +440,8 @@ pub fn make_consecutive_blobs(
     for blob in &blobs {
         let mut blob = blob.write().unwrap();
         blob.set_index(index);
-        blob.set_id(id);
+        blob.set_id(&keypair.pubkey());
+        blob.sign(keypair);
         blob.meta.set_addr(addr);
         index += 1;
     }
@@ -575,6 +587,20 @@ mod tests {
         assert!(!bad_ticks.verify(&zero)); // inductive step, bad
     }
 
+    #[test]
+    fn test_verify_tx_signatures() {
+        let zero = Hash::default();
+        let keypair = Keypair::new();
+        let tx = create_sample_payment(&keypair, zero);
+        let mut entries = vec![next_entry(&zero, 1, vec![tx.clone()]); 2];
+        assert!(entries.verify_tx_signatures());
+
+        let mut forged = tx;
+        forged.signatures[0] = Keypair::new().sign_message(&forged.message_data());
+        entries[1].transactions.push(forged);
+        assert!(!entries.verify_tx_signatures());
+    }
+
     fn blob_sized_entries(num_entries: usize) -> Vec<Entry> {
         // rough guess
         let mut magic_len = BLOB_DATA_SIZE
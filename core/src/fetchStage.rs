@@ -3,12 +3,14 @@
 use crate::waterClockRecorder::PohRecorder;
 use crate::result::{Error, Result};
 use crate::service::Service;
-use crate::streamer::{self, PacketReceiver, PacketSender};
+use crate::streamer::{
+    self, queue_channel, PacketReceiver, PacketSender, MAX_QUEUED_PACKET_BATCHES,
+};
 use morgan_metricbot::{inc_new_counter_debug, inc_new_counter_info};
 use morgan_interface::timing::DEFAULT_TICKS_PER_SLOT;
 use std::net::UdpSocket;
 use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::sync::mpsc::RecvTimeoutError;
 use std::sync::{Arc, Mutex};
 use std::thread::{self, Builder, JoinHandle};
 use morgan_helper::logHelper::*;
@@ -25,7 +27,7 @@ impl FetchStage {
         exit: &Arc<AtomicBool>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
     ) -> (Self, PacketReceiver) {
-        let (sender, receiver) = channel();
+        let (sender, receiver) = queue_channel("fetch_stage-packets", MAX_QUEUED_PACKET_BATCHES);
         (
             Self::new_with_sender(sockets, tpu_via_blobs_sockets, exit, &sender, &poh_recorder),
             receiver,
@@ -69,9 +71,7 @@ impl FetchStage {
         {
             inc_new_counter_debug!("fetch_stage-honor_forwards", len);
             for packets in batch {
-                if sendr.send(packets).is_err() {
-                    return Err(Error::SendError);
-                }
+                streamer::send_packets(sendr, packets)?;
             }
         } else {
             inc_new_counter_info!("fetch_stage-discard_forwards", len);
@@ -91,7 +91,8 @@ impl FetchStage {
             .into_iter()
             .map(|socket| streamer::receiver(socket, &exit, sender.clone()));
 
-        let (forward_sender, forward_receiver) = channel();
+        let (forward_sender, forward_receiver) =
+            queue_channel("fetch_stage-forwarded_packets", MAX_QUEUED_PACKET_BATCHES);
         let tpu_via_blobs_threads = tpu_via_blobs_sockets
             .into_iter()
             .map(|socket| streamer::blob_packet_receiver(socket, &exit, forward_sender.clone()));
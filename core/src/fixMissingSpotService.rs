@@ -5,12 +5,17 @@
 use crate::treasuryForks::BankForks;
 use crate::blockBufferPool::{Blocktree, CompletedSlotsReceiver, SlotMeta};
 use crate::clusterMessage::ClusterInfo;
+use crate::connectionInfo::ContactInfo;
 use crate::ClusterFixMessageListener::ClusterInfoRepairListener;
+use crate::ClusterVoteMessageListener::VoteTracker;
 use crate::result::Result;
 use crate::service::Service;
+use crate::stakingUtils;
+use hashbrown::{HashMap, HashSet};
 use morgan_metricbot::datapoint_info;
 use morgan_runtime::epoch_schedule::EpochSchedule;
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::timing::timestamp;
 use std::collections::BTreeSet;
 use std::net::UdpSocket;
 use std::ops::Bound::{Excluded, Unbounded};
@@ -20,12 +25,24 @@ use std::thread::sleep;
 use std::thread::{self, Builder, JoinHandle};
 use std::time::Duration;
 use morgan_helper::logHelper::*;
+use rand::{thread_rng, Rng};
 
 pub const MAX_REPAIR_LENGTH: usize = 16;
 pub const REPAIR_MS: u64 = 100;
 pub const MAX_REPAIR_TRIES: u64 = 128;
 pub const NUM_FORKS_TO_REPAIR: usize = 5;
 pub const MAX_ORPHANS: usize = 5;
+/// How long a repair request may go unanswered before its peer counts as having missed it
+pub const REPAIR_RESPONSE_TIMEOUT_MS: u64 = 400;
+/// How long a peer is skipped after missing a request, doubled with each further miss in a row
+pub const REPAIR_PEER_BACKOFF_MS: u64 = 200;
+pub const MAX_REPAIR_PEER_BACKOFF_MS: u64 = 60_000;
+/// Repairs generated each round to pick the batch from, so repairs still backed off don't
+/// leave the batch empty
+pub const MAX_REPAIR_CANDIDATES: usize = 4 * MAX_REPAIR_LENGTH;
+/// How long before an unanswered repair is requested again, doubled with every request
+pub const REPAIR_REQUEST_BACKOFF_MS: u64 = REPAIR_RESPONSE_TIMEOUT_MS;
+pub const MAX_REPAIR_REQUEST_BACKOFF_MS: u64 = 10_000;
 
 pub enum RepairStrategy {
     RepairRange(RepairSlotRange),
@@ -33,16 +50,27 @@ pub enum RepairStrategy {
         bank_forks: Arc<RwLock<BankForks>>,
         completed_slots_receiver: CompletedSlotsReceiver,
         epoch_schedule: EpochSchedule,
+        vote_tracker: Arc<RwLock<VoteTracker>>,
     },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RepairType {
     Orphan(u64),
     HighestBlob(u64, u64),
     Blob(u64, u64),
 }
 
+impl RepairType {
+    pub fn slot(&self) -> u64 {
+        match *self {
+            RepairType::Orphan(slot) => slot,
+            RepairType::HighestBlob(slot, _) => slot,
+            RepairType::Blob(slot, _) => slot,
+        }
+    }
+}
+
 pub struct RepairSlotRange {
     pub start: u64,
     pub end: u64,
@@ -57,6 +85,186 @@ impl Default for RepairSlotRange {
     }
 }
 
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RepairPeerStats {
+    pub requests: u64,
+    pub responses: u64,
+    /// Requests missed since the last one that was answered
+    pub misses: u32,
+    /// Timestamp until which the peer is sent no requests
+    pub backoff_until: u64,
+}
+
+/// Tracks how well each repair peer answers, and spreads requests over the staked peers
+/// that aren't backed off by their stake, then over the unstaked ones
+#[derive(Default)]
+pub struct RepairPeers {
+    stats: HashMap<Pubkey, RepairPeerStats>,
+    // When each repair still waiting to be answered was first sent to each peer, and whether
+    // the peer had advertised the slot, so it could be expected to answer
+    outstanding: HashMap<(RepairType, Pubkey), (u64, bool)>,
+}
+
+impl RepairPeers {
+    pub fn stats(&self, id: &Pubkey) -> Option<&RepairPeerStats> {
+        self.stats.get(id)
+    }
+
+    /// The peer to send the next request to, a staked one picked with odds proportional to
+    /// its stake. If every peer is backed off, the one whose backoff ends first.
+    pub fn select<'a, R: Rng>(
+        &self,
+        peers: &'a [ContactInfo],
+        stakes: &HashMap<Pubkey, u64>,
+        now: u64,
+        rng: &mut R,
+    ) -> Option<&'a ContactInfo> {
+        let backoff_until = |peer: &ContactInfo| {
+            self.stats
+                .get(&peer.id)
+                .map(|stats| stats.backoff_until)
+                .unwrap_or(0)
+        };
+        let stake = |peer: &ContactInfo| stakes.get(&peer.id).cloned().unwrap_or(0);
+
+        let candidates: Vec<_> = peers
+            .iter()
+            .filter(|peer| backoff_until(peer) <= now)
+            .collect();
+        if candidates.is_empty() {
+            return peers.iter().min_by_key(|peer| backoff_until(peer));
+        }
+        let total_stake: u64 = candidates.iter().map(|peer| stake(peer)).sum();
+        if total_stake == 0 {
+            return Some(candidates[rng.gen_range(0, candidates.len())]);
+        }
+        let mut pick = rng.gen_range(0, total_stake);
+        candidates.into_iter().find(|peer| {
+            let stake = stake(peer);
+            if pick < stake {
+                true
+            } else {
+                pick -= stake;
+                false
+            }
+        })
+    }
+
+    /// Record that `repair` was sent to `id`, `has_slot` telling whether `id` advertised
+    /// the slot `repair` is for
+    pub fn record_request(&mut self, repair: RepairType, id: Pubkey, has_slot: bool, now: u64) {
+        self.stats
+            .entry(id)
+            .or_insert_with(RepairPeerStats::default)
+            .requests += 1;
+        self.outstanding
+            .entry((repair, id))
+            .or_insert((now, has_slot));
+    }
+
+    /// Settle the outstanding requests, those `is_repaired` says were answered are credited
+    /// to the peers they were sent to, the ones older than REPAIR_RESPONSE_TIMEOUT_MS back
+    /// their peers off. A peer that never advertised the slot may not have the data, so it
+    /// isn't backed off for leaving the request unanswered.
+    pub fn update<F>(&mut self, is_repaired: F, now: u64)
+    where
+        F: Fn(&RepairType) -> bool,
+    {
+        let mut answered = vec![];
+        let mut missed = vec![];
+        let mut unavailable = 0;
+        self.outstanding.retain(|(repair, id), (sent, has_slot)| {
+            if is_repaired(repair) {
+                answered.push(*id);
+                false
+            } else if now.saturating_sub(*sent) >= REPAIR_RESPONSE_TIMEOUT_MS {
+                if *has_slot {
+                    missed.push(*id);
+                } else {
+                    unavailable += 1;
+                }
+                false
+            } else {
+                true
+            }
+        });
+        if answered.is_empty() && missed.is_empty() && unavailable == 0 {
+            return;
+        }
+        datapoint_info!(
+            "repair_service-peers",
+            ("answered", answered.len() as i64, i64),
+            ("missed", missed.len() as i64, i64),
+            ("unavailable", unavailable as i64, i64)
+        );
+
+        for id in &missed {
+            let stats = self
+                .stats
+                .entry(*id)
+                .or_insert_with(RepairPeerStats::default);
+            stats.misses += 1;
+            let backoff = REPAIR_PEER_BACKOFF_MS << (stats.misses - 1).min(16);
+            stats.backoff_until = now + backoff.min(MAX_REPAIR_PEER_BACKOFF_MS);
+        }
+        // A peer that answered anything isn't backed off
+        for id in &answered {
+            let stats = self
+                .stats
+                .entry(*id)
+                .or_insert_with(RepairPeerStats::default);
+            stats.responses += 1;
+            stats.misses = 0;
+            stats.backoff_until = 0;
+        }
+    }
+
+    /// Forget the stats and outstanding requests of peers no longer in `peers`
+    pub fn retain_peers(&mut self, peers: &[ContactInfo]) {
+        let ids: HashSet<_> = peers.iter().map(|peer| peer.id).collect();
+        self.stats.retain(|id, _| ids.contains(id));
+        self.outstanding.retain(|(_, id), _| ids.contains(id));
+    }
+}
+
+/// Backs off requesting each repair again, doubling the wait every time it goes unanswered
+#[derive(Default)]
+pub struct RepairRequests {
+    // How many times each repair was requested, and when it may be requested again
+    requests: HashMap<RepairType, (u32, u64)>,
+}
+
+impl RepairRequests {
+    pub fn tries(&self, repair: &RepairType) -> u32 {
+        self.requests
+            .get(repair)
+            .map(|(tries, _)| *tries)
+            .unwrap_or(0)
+    }
+
+    pub fn is_due(&self, repair: &RepairType, now: u64) -> bool {
+        self.requests
+            .get(repair)
+            .map(|(_, next_request)| *next_request <= now)
+            .unwrap_or(true)
+    }
+
+    pub fn record_request(&mut self, repair: RepairType, now: u64) {
+        let (tries, next_request) = self.requests.entry(repair).or_insert((0, 0));
+        let backoff = REPAIR_REQUEST_BACKOFF_MS << (*tries).min(16);
+        *tries += 1;
+        *next_request = now + backoff.min(MAX_REPAIR_REQUEST_BACKOFF_MS);
+    }
+
+    /// Forget the repairs `is_pending` says are no longer needed
+    pub fn retain<F>(&mut self, is_pending: F)
+    where
+        F: Fn(&RepairType) -> bool,
+    {
+        self.requests.retain(|repair, _| is_pending(repair));
+    }
+}
+
 pub struct RepairService {
     t_repair: JoinHandle<()>,
     cluster_info_repair_listener: Option<ClusterInfoRepairListener>,
@@ -110,6 +318,12 @@ impl RepairService {
         repair_strategy: RepairStrategy,
     ) {
         let mut epoch_slots: BTreeSet<u64> = BTreeSet::new();
+        let mut repair_peers = RepairPeers::default();
+        let mut repair_requests = RepairRequests::default();
+        let mut ledger_range = None;
+        // Stakes of the staked nodes, recomputed when the working bank enters a new epoch
+        let mut stakes = HashMap::new();
+        let mut stakes_epoch = None;
         let id = cluster_info.read().unwrap().id();
         let mut current_root = 0;
         if let RepairStrategy::RepairAll {
@@ -139,7 +353,7 @@ impl RepairService {
                         // Strategy used by replicators
                         Self::generate_repairs_in_range(
                             blocktree,
-                            MAX_REPAIR_LENGTH,
+                            MAX_REPAIR_CANDIDATES,
                             repair_slot_range,
                         )
                     }
@@ -147,9 +361,13 @@ impl RepairService {
                     RepairStrategy::RepairAll {
                         ref bank_forks,
                         ref completed_slots_receiver,
+                        ref vote_tracker,
                         ..
                     } => {
-                        let new_root = bank_forks.read().unwrap().root();
+                        let (new_root, working_bank) = {
+                            let bank_forks = bank_forks.read().unwrap();
+                            (bank_forks.root(), bank_forks.working_bank())
+                        };
                         Self::update_epoch_slots(
                             id,
                             new_root,
@@ -158,21 +376,81 @@ impl RepairService {
                             &cluster_info,
                             completed_slots_receiver,
                         );
-                        Self::generate_repairs(blocktree, new_root, MAX_REPAIR_LENGTH)
+                        Self::update_ledger_range(blocktree, &mut ledger_range, &cluster_info);
+                        let voted_slots = vote_tracker
+                            .read()
+                            .unwrap()
+                            .voted_slots_by_stake(&working_bank);
+                        let (epoch, _) = working_bank.get_epoch_and_slot_index(working_bank.slot());
+                        if stakes_epoch != Some(epoch) {
+                            stakes = stakingUtils::staked_nodes(&working_bank);
+                            stakes_epoch = Some(epoch);
+                        }
+                        Self::generate_repairs(
+                            blocktree,
+                            new_root,
+                            &voted_slots,
+                            MAX_REPAIR_CANDIDATES,
+                        )
                     }
                 }
             };
 
             if let Ok(repairs) = repairs {
+                let now = timestamp();
+                let is_repaired = |repair: &RepairType| Self::is_repaired(blocktree, repair);
+                repair_peers.update(is_repaired, now);
+                repair_requests
+                    .retain(|repair| repair.slot() >= current_root && !is_repaired(repair));
+                let repairs: Vec<_> = repairs
+                    .into_iter()
+                    .filter(|repair| repair_requests.is_due(repair, now))
+                    .take(MAX_REPAIR_LENGTH)
+                    .collect();
+
+                let (peers, first_slots) = {
+                    let cluster_info = cluster_info.read().unwrap();
+                    let peers = cluster_info.repair_peers();
+                    repair_peers.retain_peers(&peers);
+                    let first_slots: HashMap<_, _> = peers
+                        .iter()
+                        .filter_map(|peer| {
+                            let (first_slot, _) =
+                                cluster_info.get_ledger_range_for_node(&peer.id)?;
+                            Some((peer.id, first_slot))
+                        })
+                        .collect();
+                    (peers, first_slots)
+                };
+                let mut rng = thread_rng();
                 let reqs: Vec<_> = repairs
                     .into_iter()
                     .filter_map(|repair_request| {
-                        cluster_info
-                            .read()
-                            .unwrap()
-                            .repair_request(&repair_request)
-                            .map(|result| (result, repair_request))
-                            .ok()
+                        // a peer that purged the slot can't repair it
+                        let holders: Vec<_> = peers
+                            .iter()
+                            .filter(|peer| {
+                                first_slots
+                                    .get(&peer.id)
+                                    .map_or(true, |first_slot| *first_slot <= repair_request.slot())
+                            })
+                            .cloned()
+                            .collect();
+                        let peer = repair_peers.select(&holders, &stakes, now, &mut rng)?;
+                        let (req, has_slot) = {
+                            let cluster_info = cluster_info.read().unwrap();
+                            let req = cluster_info.map_repair_request(&repair_request).ok()?;
+                            let has_slot = cluster_info
+                                .get_epoch_state_for_node(&peer.id, None)
+                                .map_or(false, |(epoch_slots, _)| {
+                                    epoch_slots.slots.contains(&repair_request.slot())
+                                });
+                            (req, has_slot)
+                        };
+                        repair_peers.record_request(repair_request, peer.id, has_slot, now);
+                        repair_requests.record_request(repair_request, now);
+                        // send the request to the peer's gossip port
+                        Some(((peer.gossip, req), repair_request))
                     })
                     .collect();
 
@@ -202,6 +480,30 @@ impl RepairService {
         }
     }
 
+    // Whether what `repair` asked for has arrived, from whichever peer
+    fn is_repaired(blocktree: &Blocktree, repair: &RepairType) -> bool {
+        match *repair {
+            RepairType::Blob(slot, blob_index) => blocktree
+                .get_data_blob_bytes(slot, blob_index)
+                .map(|blob| blob.is_some())
+                .unwrap_or(false),
+            RepairType::HighestBlob(slot, blob_index) => blocktree
+                .meta(slot)
+                .ok()
+                .and_then(|meta| meta)
+                .map(|meta| meta.received > blob_index)
+                .unwrap_or(false),
+            RepairType::Orphan(slot) => {
+                let has_meta = blocktree.meta(slot).map(|meta| meta.is_some());
+                let is_orphan = blocktree.orphan(slot).map(|orphan| orphan == Some(true));
+                match (has_meta, is_orphan) {
+                    (Ok(true), Ok(false)) => true,
+                    _ => false,
+                }
+            }
+        }
+    }
+
     // Generate repairs for all slots `x` in the repair_range.start <= x <= repair_range.end
     fn generate_repairs_in_range(
         blocktree: &Blocktree,
@@ -235,22 +537,40 @@ impl RepairService {
         Ok(repairs)
     }
 
+    // `voted_slots` are the slots staked validators voted for, most stake first. Those the
+    // cluster is building on make the cut before the forks descending from the root, then
+    // the repairs are ordered closest to the root first, the order the slots get replayed in.
     fn generate_repairs(
         blocktree: &Blocktree,
         root: u64,
+        voted_slots: &[u64],
         max_repairs: usize,
     ) -> Result<(Vec<RepairType>)> {
         // Slot height and blob indexes for blobs we want to repair
         let mut repairs: Vec<RepairType> = vec![];
+        Self::generate_repairs_for_voted_slots(
+            blocktree,
+            &mut repairs,
+            max_repairs,
+            root,
+            voted_slots,
+        );
         Self::generate_repairs_for_fork(blocktree, &mut repairs, max_repairs, root);
 
-        // TODO: Incorporate gossip to determine priorities for repair?
-
         // Try to resolve orphans in blocktree
         let orphans = blocktree.get_orphans(Some(MAX_ORPHANS));
 
         Self::generate_repairs_for_orphans(&orphans[..], &mut repairs);
-        Ok(repairs)
+
+        // A voted slot is usually on a fork descending from the root as well
+        let mut unique_repairs = Vec::with_capacity(repairs.len());
+        for repair in repairs {
+            if !unique_repairs.contains(&repair) {
+                unique_repairs.push(repair);
+            }
+        }
+        unique_repairs.sort_by_key(RepairType::slot);
+        Ok(unique_repairs)
     }
 
     fn generate_repairs_for_slot(
@@ -281,16 +601,57 @@ impl RepairService {
         repairs.extend(orphans.iter().map(|h| RepairType::Orphan(*h)));
     }
 
-    /// Repairs any fork starting at the input slot
+    /// Repairs the voted slots and whatever is missing from the chain linking each of them
+    /// back to the root
+    fn generate_repairs_for_voted_slots(
+        blocktree: &Blocktree,
+        repairs: &mut Vec<RepairType>,
+        max_repairs: usize,
+        root: u64,
+        voted_slots: &[u64],
+    ) {
+        for voted_slot in voted_slots.iter().take(NUM_FORKS_TO_REPAIR) {
+            let mut slot = *voted_slot;
+            while repairs.len() < max_repairs && slot > root {
+                match blocktree.meta(slot).unwrap() {
+                    // Nothing is known about the slot yet, ask for it and its ancestors
+                    None => {
+                        repairs.push(RepairType::Orphan(slot));
+                        break;
+                    }
+                    Some(slot_meta) => {
+                        let new_repairs = Self::generate_repairs_for_slot(
+                            blocktree,
+                            slot,
+                            &slot_meta,
+                            max_repairs - repairs.len(),
+                        );
+                        repairs.extend(new_repairs);
+                        if slot_meta.is_connected || !slot_meta.is_parent_set() {
+                            break;
+                        }
+                        slot = slot_meta.parent_slot;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Repairs any fork starting at the input slot, lowest slots first
     fn generate_repairs_for_fork(
         blocktree: &Blocktree,
         repairs: &mut Vec<RepairType>,
         max_repairs: usize,
         slot: u64,
     ) {
-        let mut pending_slots = vec![slot];
-        while repairs.len() < max_repairs && !pending_slots.is_empty() {
-            let slot = pending_slots.pop().unwrap();
+        let mut pending_slots = BTreeSet::new();
+        pending_slots.insert(slot);
+        while repairs.len() < max_repairs {
+            let slot = match pending_slots.iter().next() {
+                Some(slot) => *slot,
+                None => break,
+            };
+            pending_slots.remove(&slot);
             if let Some(slot_meta) = blocktree.meta(slot).unwrap() {
                 let new_repairs = Self::generate_repairs_for_slot(
                     blocktree,
@@ -387,6 +748,24 @@ impl RepairService {
         }
     }
 
+    /// Advertise the slots the ledger holds if they changed since they were `advertised`
+    fn update_ledger_range(
+        blocktree: &Blocktree,
+        advertised: &mut Option<(u64, u64)>,
+        cluster_info: &RwLock<ClusterInfo>,
+    ) {
+        let ledger_range = blocktree.ledger_range();
+        if ledger_range != *advertised {
+            if let Some((first_slot, last_slot)) = ledger_range {
+                cluster_info
+                    .write()
+                    .unwrap()
+                    .push_ledger_range(first_slot, last_slot);
+            }
+            *advertised = ledger_range;
+        }
+    }
+
     fn retain_slots_greater_than_root(slot_set: &mut BTreeSet<u64>, root: u64) {
         *slot_set = slot_set
             .range((Excluded(&root), Unbounded))
@@ -419,7 +798,8 @@ mod test {
     use crate::blockBufferPool::{get_tmp_ledger_path, Blocktree};
     use crate::clusterMessage::Node;
     use rand::seq::SliceRandom;
-    use rand::{thread_rng, Rng};
+    use rand::{thread_rng, Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
     use std::cmp::min;
     use std::sync::mpsc::channel;
     use std::thread::Builder;
@@ -436,7 +816,7 @@ mod test {
             blobs.extend(blobs2);
             blocktree.write_blobs(&blobs).unwrap();
             assert_eq!(
-                RepairService::generate_repairs(&blocktree, 0, 2).unwrap(),
+                RepairService::generate_repairs(&blocktree, 0, &[], 2).unwrap(),
                 vec![
                     RepairType::HighestBlob(0, 0),
                     RepairType::Orphan(0),
@@ -462,13 +842,45 @@ mod test {
 
             // Check that repair tries to patch the empty slot
             assert_eq!(
-                RepairService::generate_repairs(&blocktree, 0, 2).unwrap(),
+                RepairService::generate_repairs(&blocktree, 0, &[], 2).unwrap(),
                 vec![RepairType::HighestBlob(0, 0), RepairType::Orphan(0)]
             );
         }
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
     }
 
+    #[test]
+    pub fn test_repair_voted_slots_first() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+
+            // Slot 1 is missing its last blob, slot 3 chains to it through slot 2, of which
+            // nothing has been received yet
+            let (mut blobs, _) = make_slot_entries(1, 0, 2);
+            blobs.pop();
+            let (blobs3, _) = make_slot_entries(3, 2, 1);
+            blobs.extend(blobs3);
+            blocktree.write_blobs(&blobs).unwrap();
+
+            assert_eq!(
+                RepairService::generate_repairs(&blocktree, 0, &[], 1).unwrap()[0],
+                RepairType::HighestBlob(0, 0)
+            );
+
+            // The cluster voted on slot 3, so its chain makes the cut, closest to the root first
+            assert_eq!(
+                RepairService::generate_repairs(&blocktree, 0, &[3, 4], 3).unwrap()[..3],
+                [
+                    RepairType::HighestBlob(0, 0),
+                    RepairType::HighestBlob(2, 0),
+                    RepairType::Orphan(4),
+                ]
+            );
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
+
     #[test]
     pub fn test_generate_repairs() {
         let blocktree_path = get_tmp_ledger_path!();
@@ -501,13 +913,14 @@ mod test {
                 .collect();
 
             assert_eq!(
-                RepairService::generate_repairs(&blocktree, 0, std::usize::MAX).unwrap(),
+                RepairService::generate_repairs(&blocktree, 0, &[], std::usize::MAX).unwrap(),
                 expected
             );
 
+            let max_repairs = expected.len() - 2;
             assert_eq!(
-                RepairService::generate_repairs(&blocktree, 0, expected.len() - 2).unwrap()[..],
-                expected[0..expected.len() - 2]
+                RepairService::generate_repairs(&blocktree, 0, &[], max_repairs).unwrap()[..],
+                expected[0..max_repairs]
             );
         }
         Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
@@ -533,7 +946,7 @@ mod test {
             let expected: Vec<RepairType> = vec![RepairType::HighestBlob(0, num_entries_per_slot)];
 
             assert_eq!(
-                RepairService::generate_repairs(&blocktree, 0, std::usize::MAX).unwrap(),
+                RepairService::generate_repairs(&blocktree, 0, &[], std::usize::MAX).unwrap(),
                 expected
             );
         }
@@ -803,12 +1216,9 @@ mod test {
         let (my_epoch_slots_in_gossip, updated_ts) = {
             let r_cluster_info = cluster_info.read().unwrap();
 
-            let (my_epoch_slots_in_gossip, updated_ts) = r_cluster_info
+            r_cluster_info
                 .get_epoch_state_for_node(&my_pubkey, None)
-                .clone()
-                .unwrap();
-
-            (my_epoch_slots_in_gossip.clone(), updated_ts)
+                .unwrap()
         };
 
         assert_eq!(my_epoch_slots_in_gossip.root, 0);
@@ -851,7 +1261,6 @@ mod test {
 
         let (my_epoch_slots_in_gossip, _) = r_cluster_info
             .get_epoch_state_for_node(&my_pubkey, Some(updated_ts))
-            .clone()
             .unwrap();
 
         // Check the root was updated correctly
@@ -862,4 +1271,178 @@ mod test {
             .slots
             .contains(&newly_completed_slot));
     }
+
+    #[test]
+    fn test_repair_peers_select() {
+        let peers: Vec<_> = (0..3)
+            .map(|_| ContactInfo::new_localhost(&Pubkey::new_rand(), 0))
+            .collect();
+        let mut repair_peers = RepairPeers::default();
+        let repair = RepairType::Blob(1, 0);
+        let mut rng = ChaChaRng::from_seed([1u8; 32]);
+        let mut select_counts = |repair_peers: &RepairPeers, stakes: &HashMap<_, _>, now| {
+            let mut counts = HashMap::new();
+            for _ in 0..1000 {
+                let peer = repair_peers.select(&peers, stakes, now, &mut rng).unwrap();
+                *counts.entry(peer.id).or_insert(0) += 1;
+            }
+            counts
+        };
+
+        // without stakes every peer takes a share
+        let mut stakes = HashMap::new();
+        assert_eq!(select_counts(&repair_peers, &stakes, 0).len(), 3);
+
+        // staked peers are picked by their stake
+        stakes.insert(peers[0].id, 1);
+        stakes.insert(peers[1].id, 9);
+        let counts = select_counts(&repair_peers, &stakes, 0);
+        assert_eq!(counts.len(), 2);
+        assert!(counts[&peers[0].id] > 0);
+        assert!(counts[&peers[1].id] > 5 * counts[&peers[0].id]);
+
+        // a staked peer that misses a request is skipped until its backoff ends
+        repair_peers.record_request(repair, peers[1].id, true, 0);
+        repair_peers.update(|_| false, REPAIR_RESPONSE_TIMEOUT_MS - 1);
+        assert_eq!(repair_peers.stats(&peers[1].id).unwrap().misses, 0);
+        let now = REPAIR_RESPONSE_TIMEOUT_MS;
+        repair_peers.update(|_| false, now);
+        assert_eq!(
+            repair_peers.stats(&peers[1].id),
+            Some(&RepairPeerStats {
+                requests: 1,
+                responses: 0,
+                misses: 1,
+                backoff_until: now + REPAIR_PEER_BACKOFF_MS,
+            })
+        );
+        let counts = select_counts(&repair_peers, &stakes, now);
+        assert_eq!(counts.keys().collect::<Vec<_>>(), vec![&peers[0].id]);
+        let counts = select_counts(&repair_peers, &stakes, now + REPAIR_PEER_BACKOFF_MS);
+        assert_eq!(counts.len(), 2);
+
+        // with every staked peer backed off the unstaked ones are used
+        repair_peers.record_request(repair, peers[0].id, true, 100);
+        let now = 100 + REPAIR_RESPONSE_TIMEOUT_MS;
+        repair_peers.update(|_| false, now);
+        assert_eq!(
+            repair_peers
+                .select(&peers, &stakes, now, &mut rng)
+                .unwrap()
+                .id,
+            peers[2].id
+        );
+
+        // and with every peer backed off, the one that is back first
+        let mut repair_peers = RepairPeers::default();
+        repair_peers.record_request(repair, peers[2].id, true, 0);
+        repair_peers.update(|_| false, REPAIR_RESPONSE_TIMEOUT_MS);
+        repair_peers.record_request(repair, peers[0].id, true, 100);
+        repair_peers.record_request(repair, peers[1].id, true, 100);
+        let now = 100 + REPAIR_RESPONSE_TIMEOUT_MS;
+        repair_peers.update(|_| false, now);
+        assert_eq!(
+            repair_peers
+                .select(&peers, &stakes, now, &mut rng)
+                .unwrap()
+                .id,
+            peers[2].id
+        );
+        assert_eq!(repair_peers.select(&[], &stakes, now, &mut rng), None);
+    }
+
+    #[test]
+    fn test_repair_peers_unavailable() {
+        let peers: Vec<_> = (0..2)
+            .map(|_| ContactInfo::new_localhost(&Pubkey::new_rand(), 0))
+            .collect();
+        let mut repair_peers = RepairPeers::default();
+        let repair = RepairType::Blob(1, 0);
+
+        // a peer that never advertised the slot isn't backed off for not answering
+        repair_peers.record_request(repair, peers[0].id, false, 0);
+        repair_peers.record_request(repair, peers[1].id, true, 0);
+        repair_peers.update(|_| false, REPAIR_RESPONSE_TIMEOUT_MS);
+        assert_eq!(repair_peers.stats(&peers[0].id).unwrap().misses, 0);
+        assert_eq!(repair_peers.stats(&peers[0].id).unwrap().backoff_until, 0);
+        assert_eq!(repair_peers.stats(&peers[1].id).unwrap().misses, 1);
+
+        // peers that left are forgotten
+        repair_peers.record_request(repair, peers[1].id, true, 1);
+        repair_peers.retain_peers(&peers[..1]);
+        assert!(repair_peers.stats(&peers[0].id).is_some());
+        assert!(repair_peers.stats(&peers[1].id).is_none());
+        assert!(repair_peers.outstanding.is_empty());
+    }
+
+    #[test]
+    fn test_repair_requests_backoff() {
+        let repair = RepairType::Blob(1, 0);
+        let mut repair_requests = RepairRequests::default();
+        let mut now = 0;
+        let mut expected_backoff = REPAIR_REQUEST_BACKOFF_MS;
+        assert!(repair_requests.is_due(&repair, now));
+        for tries in 1..20 {
+            repair_requests.record_request(repair, now);
+            assert_eq!(repair_requests.tries(&repair), tries);
+            assert!(!repair_requests.is_due(&repair, now + expected_backoff - 1));
+            now += expected_backoff;
+            assert!(repair_requests.is_due(&repair, now));
+            expected_backoff = min(expected_backoff * 2, MAX_REPAIR_REQUEST_BACKOFF_MS);
+        }
+        assert_eq!(expected_backoff, MAX_REPAIR_REQUEST_BACKOFF_MS);
+
+        // other repairs are requested right away, and a repair no longer needed starts over
+        assert!(repair_requests.is_due(&RepairType::Blob(1, 1), now));
+        repair_requests.record_request(repair, now);
+        repair_requests.retain(|pending| *pending != repair);
+        assert_eq!(repair_requests.tries(&repair), 0);
+        assert!(repair_requests.is_due(&repair, now));
+    }
+
+    #[test]
+    fn test_repair_peers_backoff() {
+        let id = Pubkey::new_rand();
+        let mut repair_peers = RepairPeers::default();
+        let mut now = 0;
+        let mut expected_backoff = REPAIR_PEER_BACKOFF_MS;
+        for misses in 1..20 {
+            repair_peers.record_request(RepairType::Orphan(misses as u64), id, true, now);
+            now += REPAIR_RESPONSE_TIMEOUT_MS;
+            repair_peers.update(|_| false, now);
+            let stats = repair_peers.stats(&id).unwrap();
+            assert_eq!(stats.misses, misses);
+            assert_eq!(stats.backoff_until, now + expected_backoff);
+            expected_backoff = min(expected_backoff * 2, MAX_REPAIR_PEER_BACKOFF_MS);
+        }
+
+        // answering a request, even late, clears the backoff
+        repair_peers.record_request(RepairType::Orphan(0), id, true, now);
+        repair_peers.update(|_| true, now + 1);
+        let stats = repair_peers.stats(&id).unwrap();
+        assert_eq!(stats.requests, 20);
+        assert_eq!(stats.responses, 1);
+        assert_eq!(stats.misses, 0);
+        assert_eq!(stats.backoff_until, 0);
+    }
+
+    #[test]
+    fn test_is_repaired() {
+        let blocktree_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&blocktree_path).unwrap();
+            let (blobs, _) = make_slot_entries(2, 0, 3);
+            blocktree.insert_data_blobs(&blobs[1..2]).unwrap();
+            let is_repaired = |repair: RepairType| RepairService::is_repaired(&blocktree, &repair);
+
+            assert!(is_repaired(RepairType::Blob(2, 1)));
+            assert!(!is_repaired(RepairType::Blob(2, 0)));
+            assert!(is_repaired(RepairType::HighestBlob(2, 1)));
+            assert!(!is_repaired(RepairType::HighestBlob(2, 2)));
+            // slot 0 is missing, so slot 2 is still an orphan
+            assert!(!is_repaired(RepairType::Orphan(2)));
+            assert!(!is_repaired(RepairType::Orphan(3)));
+        }
+        Blocktree::destroy(&blocktree_path).expect("Expected successful database destruction");
+    }
 }
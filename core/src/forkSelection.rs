@@ -253,7 +253,7 @@ impl Locktower {
 
     pub fn record_vote(&mut self, slot: u64, hash: Hash) -> Option<u64> {
         let root_slot = self.lockouts.root_slot;
-        let vote = Vote { slot, hash };
+        let vote = Vote::new(slot, hash);
         self.lockouts.process_vote_unchecked(&vote);
 
         // vote_state doesn't keep around the hashes, so we save them in recent_votes
@@ -396,7 +396,8 @@ impl Locktower {
                 )
             })
             .collect();
-        bank_weights.sort_by_key(|b| (b.0, b.1));
+        // frozen banks come out of a HashMap, break ties by slot so every node picks the same
+        bank_weights.sort_by_key(|b| (b.0, b.1, b.2.slot()));
         bank_weights.pop().map(|b| b.2)
     }
 
@@ -6,7 +6,7 @@
 use crate::connectionInfoTable::Crds;
 use crate::gossipErrorType::CrdsGossipError;
 use crate::pullFromGossip::CrdsGossipPull;
-use crate::pushToGossip::{CrdsGossipPush, CRDS_GOSSIP_NUM_ACTIVE};
+use crate::pushToGossip::{CrdsGossipPush, InsertedPushMessage, CRDS_GOSSIP_NUM_ACTIVE};
 use crate::propagationValue::CrdsValue;
 use hashbrown::HashMap;
 use morgan_runtime::bloom::Bloom;
@@ -41,12 +41,29 @@ impl CrdsGossip {
     }
     /// process a push message to the network
     pub fn process_push_message(&mut self, values: Vec<CrdsValue>, now: u64) -> Vec<Pubkey> {
-        let labels: Vec<_> = values.iter().map(CrdsValue::label).collect();
+        let inserted = self.insert_push_messages(values, now);
+        self.record_push_messages(inserted, now)
+    }
 
-        let results: Vec<_> = values
-            .into_iter()
-            .map(|val| self.push.process_push_message(&mut self.crds, val, now))
-            .collect();
+    /// The first half of `process_push_message`, needs only shared access so the table can be
+    /// read while the values are inserted
+    pub fn insert_push_messages(
+        &self,
+        values: Vec<CrdsValue>,
+        now: u64,
+    ) -> Vec<InsertedPushMessage> {
+        self.push.insert_push_messages(&self.crds, values, now)
+    }
+
+    /// The second half of `process_push_message`, returns the origins to prune
+    pub fn record_push_messages(
+        &mut self,
+        inserted: Vec<InsertedPushMessage>,
+        now: u64,
+    ) -> Vec<Pubkey> {
+        let labels: Vec<_> = inserted.iter().map(|msg| msg.label.clone()).collect();
+
+        let results = self.push.record_push_messages(inserted, now);
 
         results
             .into_iter()
@@ -128,7 +145,7 @@ impl CrdsGossip {
         now: u64,
     ) -> Vec<CrdsValue> {
         self.pull
-            .process_pull_request(&mut self.crds, caller, filter, now)
+            .process_pull_request(&self.crds, caller, filter, now)
     }
     /// process a pull response
     pub fn process_pull_response(
@@ -138,7 +155,18 @@ impl CrdsGossip {
         now: u64,
     ) -> usize {
         self.pull
-            .process_pull_response(&mut self.crds, from, response, now)
+            .process_pull_response(&self.crds, from, response, now)
+    }
+    /// The part of `process_pull_response` that needs only shared access, returns the hashes
+    /// of the replaced values for `pull.record_old_hashes`
+    pub fn insert_pull_response(
+        &self,
+        from: &Pubkey,
+        response: Vec<CrdsValue>,
+        now: u64,
+    ) -> Vec<(Hash, u64)> {
+        let (_, old_hashes) = CrdsGossipPull::insert_pull_response(&self.crds, from, response, now);
+        old_hashes
     }
     pub fn purge(&mut self, now: u64) {
         if now > self.push.msg_timeout {
@@ -151,7 +179,7 @@ impl CrdsGossip {
         }
         if now > self.pull.crds_timeout {
             let min = now - self.pull.crds_timeout;
-            self.pull.purge_active(&mut self.crds, &self.id, min);
+            self.pull.purge_active(&self.crds, &self.id, min);
         }
         if now > 5 * self.pull.crds_timeout {
             let min = now - 5 * self.pull.crds_timeout;
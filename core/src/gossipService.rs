@@ -9,6 +9,7 @@ use crate::connectionInfo::ContactInfo;
 use crate::service::Service;
 use crate::streamer;
 use rand::{thread_rng, Rng};
+use morgan_client::retry_policy::RetryPolicy;
 use morgan_client::thin_client::{create_client, ThinClient};
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil};
@@ -62,7 +63,25 @@ pub fn discover_cluster(
     entry_point: &SocketAddr,
     num_nodes: usize,
 ) -> std::io::Result<(Vec<ContactInfo>, Vec<ContactInfo>)> {
-    discover(entry_point, Some(num_nodes), Some(30), None, None)
+    let policy = RetryPolicy::new(Duration::from_secs(1), 3, Duration::from_secs(120));
+    discover_cluster_with_retry(entry_point, num_nodes, &policy)
+}
+
+/// Discover Nodes and Replicators in a cluster, starting discovery over as allowed by `policy`
+/// while no nodes are found
+pub fn discover_cluster_with_retry(
+    entry_point: &SocketAddr,
+    num_nodes: usize,
+    policy: &RetryPolicy,
+) -> std::io::Result<(Vec<ContactInfo>, Vec<ContactInfo>)> {
+    policy
+        .retry(|| discover(entry_point, Some(num_nodes), Some(30), None, None))
+        .map_err(|err| {
+            std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("discover_cluster from {} {}", entry_point, err),
+            )
+        })
 }
 
 pub fn discover(
@@ -255,7 +274,7 @@ fn spy(
 
 /// Makes a spy or gossip node based on whether or not a gossip_addr was passed in
 /// Pass in a gossip addr to fully participate in gossip instead of relying on just pulls
-fn make_gossip_node(
+pub(crate) fn make_gossip_node(
     entry_point: &SocketAddr,
     exit: &Arc<AtomicBool>,
     gossip_addr: Option<&SocketAddr>,
@@ -6,20 +6,44 @@ use morgan_interface::timing::NUM_CONSECUTIVE_LEADER_SLOTS;
 
 /// Return the leader schedule for the given epoch.
 pub fn leader_schedule(epoch_height: u64, bank: &Bank) -> Option<LeaderSchedule> {
-    stakingUtils::staked_nodes_at_epoch(bank, epoch_height).map(|stakes| {
-        let mut seed = [0u8; 32];
-        seed[0..8].copy_from_slice(&epoch_height.to_le_bytes());
-        let mut stakes: Vec<_> = stakes.into_iter().collect();
-        sort_stakes(&mut stakes);
-        LeaderSchedule::new(
-            &stakes,
-            seed,
-            bank.get_slots_in_epoch(epoch_height),
-            NUM_CONSECUTIVE_LEADER_SLOTS,
-        )
+    stakingUtils::staked_nodes_at_epoch(bank, epoch_height).and_then(|stakes| {
+        leader_schedule_for_stakes(epoch_height, stakes, bank.get_slots_in_epoch(epoch_height))
     })
 }
 
+/// Return the leader schedule the given epoch would have if its staked nodes held `stakes`.
+/// Lets staking tools preview the effect of a delegation before making it, nodes without
+/// stake are never scheduled and None is returned if no node has any.
+pub fn leader_schedule_for_stakes<I>(
+    epoch_height: u64,
+    stakes: I,
+    slots_in_epoch: u64,
+) -> Option<LeaderSchedule>
+where
+    I: IntoIterator<Item = (Pubkey, u64)>,
+{
+    let mut stakes: Vec<_> = stakes.into_iter().filter(|(_, stake)| *stake > 0).collect();
+    if stakes.is_empty() {
+        return None;
+    }
+    let mut seed = [0u8; 32];
+    seed[0..8].copy_from_slice(&epoch_height.to_le_bytes());
+    sort_stakes(&mut stakes);
+    Some(LeaderSchedule::new(
+        &stakes,
+        seed,
+        slots_in_epoch,
+        NUM_CONSECUTIVE_LEADER_SLOTS,
+    ))
+}
+
+/// Return the first epoch whose leader schedule sees stake changed at `slot`.  Each epoch's
+/// stakes are snapshotted by the first bank whose stakers epoch it is, so a change shows up
+/// one epoch after the stakers epoch of `slot`.
+pub fn first_epoch_with_stake_change(bank: &Bank, slot: u64) -> u64 {
+    bank.get_stakers_epoch(slot) + 1
+}
+
 /// Return the leader for the given slot.
 pub fn slot_leader_at(slot: u64, bank: &Bank) -> Option<Pubkey> {
     let (epoch, slot_index) = bank.get_epoch_and_slot_index(slot);
@@ -56,10 +80,15 @@ fn sort_stakes(stakes: &mut Vec<(Pubkey, u64)>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
     use crate::stakingUtils;
+    use crate::stakingUtils::tests::setup_vote_and_stake_accounts;
     use morgan_runtime::genesis_utils::{
         create_genesis_block_with_leader, BOOTSTRAP_LEADER_DIFS,
     };
+    use rand::seq::SliceRandom;
+    use rand::Rng;
+    use std::sync::Arc;
 
     #[test]
     fn test_leader_schedule_via_bank() {
@@ -95,6 +124,100 @@ mod tests {
         assert_eq!(slot_leader_at(bank.slot(), &bank).unwrap(), pubkey);
     }
 
+    #[test]
+    fn test_leader_schedule_for_stakes() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let epoch = rng.gen_range(0, 100);
+            let mut stakes: Vec<_> = (0..rng.gen_range(1, 20))
+                .map(|_| (Pubkey::new_rand(), rng.gen_range(1, 1_000)))
+                .collect();
+            let leader_schedule = leader_schedule_for_stakes(epoch, stakes.clone(), 64).unwrap();
+
+            // Neither the order of the stakes nor unstaked nodes change the schedule
+            stakes.push((Pubkey::new_rand(), 0));
+            stakes.shuffle(&mut rng);
+            assert_eq!(
+                leader_schedule_for_stakes(epoch, stakes, 64),
+                Some(leader_schedule)
+            );
+        }
+        assert_eq!(
+            leader_schedule_for_stakes(0, vec![(Pubkey::new_rand(), 0)], 64),
+            None
+        );
+    }
+
+    #[test]
+    fn test_leader_schedule_pinned_to_stakers_epoch() {
+        let GenesisBlockInfo {
+            mut genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        genesis_block.epoch_warmup = false;
+        let bank = Arc::new(Bank::new(&genesis_block));
+        let stakers_epoch = bank.get_stakers_epoch(0);
+        let leader_schedules: Vec<_> = (0..=stakers_epoch)
+            .map(|epoch| leader_schedule(epoch, &bank).unwrap())
+            .collect();
+
+        // A new node stakes in the first slot, it can only be scheduled once the next stakers
+        // epoch starts
+        let node_pubkey = Pubkey::new_rand();
+        setup_vote_and_stake_accounts(
+            &bank,
+            &mint_keypair,
+            &Pubkey::new_rand(),
+            &node_pubkey,
+            BOOTSTRAP_LEADER_DIFS,
+        );
+        let epoch = first_epoch_with_stake_change(&bank, bank.slot());
+        assert_eq!(epoch, stakers_epoch + 1);
+        let mut boundary_slot = 1;
+        while bank.get_stakers_epoch(boundary_slot) < epoch {
+            boundary_slot += 1;
+        }
+
+        // However the banks before the boundary are spread, they keep the schedules they
+        // started with
+        let mut rng = rand::thread_rng();
+        let mut slots: Vec<_> = (0..10).map(|_| rng.gen_range(1, boundary_slot)).collect();
+        slots.sort();
+        slots.dedup();
+        let mut parent = bank;
+        for slot in slots {
+            let bank = Arc::new(Bank::new_from_parent(&parent, &Pubkey::default(), slot));
+            for (i, expected) in leader_schedules.iter().enumerate() {
+                assert_eq!(leader_schedule(i as u64, &bank).as_ref(), Some(expected));
+            }
+            assert_eq!(leader_schedule(epoch, &bank), None);
+            parent = bank;
+        }
+
+        // The schedule predicted from the stakes at the boundary is the one the epoch gets
+        let expected = leader_schedule_for_stakes(
+            epoch,
+            stakingUtils::staked_nodes(&parent),
+            parent.get_slots_in_epoch(epoch),
+        )
+        .unwrap();
+        let bank = Bank::new_from_parent(&parent, &Pubkey::default(), boundary_slot);
+        assert_eq!(leader_schedule(epoch, &bank).as_ref(), Some(&expected));
+        assert!((0..bank.get_slots_in_epoch(epoch)).any(|i| expected[i] == node_pubkey));
+
+        // and stake changed after the boundary waits for the epoch after
+        setup_vote_and_stake_accounts(
+            &bank,
+            &mint_keypair,
+            &Pubkey::new_rand(),
+            &Pubkey::new_rand(),
+            BOOTSTRAP_LEADER_DIFS,
+        );
+        assert_eq!(first_epoch_with_stake_change(&bank, bank.slot()), epoch + 1);
+        assert_eq!(leader_schedule(epoch, &bank), Some(expected));
+    }
+
     #[test]
     fn test_sort_stakes_basic() {
         let pubkey0 = Pubkey::new_rand();
@@ -0,0 +1,652 @@
+//! The `ledgerArchive` module offloads rooted ledger history to an S3-compatible object store.
+//! Rooted slots are grouped into fixed size segments, each uploaded as one compressed object
+//! once the whole segment is behind the root, and RPC reads fall back to the archive for slots
+//! the local blocktree does not have.
+
+use crate::blockBufferPool::Blocktree;
+use crate::entryInfo::Entry;
+use crate::result::Result;
+use crate::service::Service;
+use crate::treasuryForks::BankForks;
+use bincode::{deserialize, serialize};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hashbrown::HashMap;
+use morgan_interface::timing::duration_as_ms;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+use morgan_helper::logHelper::*;
+
+/// Slots per archived segment
+pub const DEFAULT_SEGMENT_SLOTS: u64 = 1024;
+
+/// How often the archiver checks whether the root has sealed another segment
+const ARCHIVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Segments kept in memory to serve consecutive RPC reads without refetching
+const MAX_CACHED_SEGMENTS: usize = 4;
+
+/// How long RPC reads trust the archived progress, a missing segment or a failed fetch before
+/// asking the store again
+const ARCHIVE_CACHE_TTL: Duration = Duration::from_secs(10);
+
+/// Missing segments remembered at once
+const MAX_MISSING_SEGMENTS: usize = 1024;
+
+/// Requests to the store fail after this long, RPC reads wait on them
+pub const ARCHIVE_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+pub struct LedgerArchiveConfig {
+    pub endpoint: String, // e.g. https://s3.us-east-1.amazonaws.com
+    pub bucket: String,
+    pub region: String,
+    pub prefix: String, // Prepended to every object key
+    pub access_key: String,
+    pub secret_key: String,
+    pub segment_slots: u64,
+}
+
+impl Default for LedgerArchiveConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            bucket: String::new(),
+            region: "us-east-1".to_string(),
+            prefix: "ledger".to_string(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            segment_slots: DEFAULT_SEGMENT_SLOTS,
+        }
+    }
+}
+
+// Keep the secret out of logs
+impl fmt::Debug for LedgerArchiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LedgerArchiveConfig")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("prefix", &self.prefix)
+            .field("access_key", &self.access_key)
+            .field("segment_slots", &self.segment_slots)
+            .finish()
+    }
+}
+
+pub trait ObjectStore: Send + Sync {
+    fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()>;
+    /// Returns None if there is no object under `key`
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+}
+
+/// An `ObjectStore` that lives in memory, for tests
+#[derive(Default)]
+pub struct MemoryObjectStore {
+    objects: RwLock<HashMap<String, Vec<u8>>>,
+}
+
+impl ObjectStore for MemoryObjectStore {
+    fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+        self.objects.write().unwrap().insert(key.to_string(), data);
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.objects.read().unwrap().get(key).cloned())
+    }
+}
+
+type Segment = Vec<(u64, Vec<Entry>)>;
+
+pub struct LedgerArchive {
+    store: Box<dyn ObjectStore>,
+    prefix: String,
+    segment_slots: u64,
+    cache: Mutex<Vec<(u64, Arc<Segment>)>>,
+    // Segments the store didn't have, or failed to return, and when it was asked
+    missing: Mutex<HashMap<u64, Instant>>,
+    // `archived_until` as last read for RPC, and when
+    archived_until_cache: Mutex<Option<(u64, Instant)>>,
+}
+
+impl LedgerArchive {
+    pub fn new(store: Box<dyn ObjectStore>, prefix: &str, segment_slots: u64) -> Self {
+        assert!(segment_slots > 0);
+        Self {
+            store,
+            prefix: prefix.trim_matches('/').to_string(),
+            segment_slots,
+            cache: Mutex::new(vec![]),
+            missing: Mutex::new(HashMap::new()),
+            archived_until_cache: Mutex::new(None),
+        }
+    }
+
+    #[cfg(feature = "ledger-archive")]
+    pub fn from_config(config: &LedgerArchiveConfig) -> io::Result<Self> {
+        let store = S3ObjectStore::new(config)?;
+        Ok(Self::new(
+            Box::new(store),
+            &config.prefix,
+            config.segment_slots,
+        ))
+    }
+
+    #[cfg(not(feature = "ledger-archive"))]
+    pub fn from_config(_config: &LedgerArchiveConfig) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "built without the ledger-archive feature",
+        ))
+    }
+
+    pub fn segment_slots(&self) -> u64 {
+        self.segment_slots
+    }
+
+    /// First slot of the segment holding `slot`
+    pub fn segment_start(&self, slot: u64) -> u64 {
+        slot - slot % self.segment_slots
+    }
+
+    fn segment_key(&self, start: u64) -> String {
+        format!(
+            "{}/{:020}-{:020}.segment",
+            self.prefix,
+            start,
+            start + self.segment_slots - 1
+        )
+    }
+
+    fn progress_key(&self) -> String {
+        format!("{}/archived", self.prefix)
+    }
+
+    /// Every slot below the returned one has been archived
+    pub fn archived_until(&self) -> Result<u64> {
+        match self.store.get(&self.progress_key())? {
+            Some(data) => Ok(deserialize(&data)?),
+            None => Ok(0),
+        }
+    }
+
+    /// Upload the rooted slots of the segment starting at `start`, returns how many there were
+    pub fn archive_segment(&self, blocktree: &Blocktree, start: u64) -> Result<usize> {
+        assert_eq!(self.segment_start(start), start);
+        let mut segment = vec![];
+        for slot in start..start + self.segment_slots {
+            if blocktree.is_root(slot) {
+                segment.push((slot, blocktree.get_slot_entries(slot, 0, None)?));
+            }
+        }
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&serialize(&segment)?)?;
+        let data = encoder.finish()?;
+        self.store.put(&self.segment_key(start), data)?;
+        // Only advance once the segment itself is stored, a crash in between re-uploads it
+        let archived_until = serialize(&(start + self.segment_slots))?;
+        self.store.put(&self.progress_key(), archived_until)?;
+        Ok(segment.len())
+    }
+
+    /// Like `archived_until`, but reads the store at most once per `ARCHIVE_CACHE_TTL`. A failed
+    /// read is remembered as nothing archived for as long.
+    fn cached_archived_until(&self) -> Result<u64> {
+        if let Some((archived_until, read)) = *self.archived_until_cache.lock().unwrap() {
+            if read.elapsed() < ARCHIVE_CACHE_TTL {
+                return Ok(archived_until);
+            }
+        }
+        let result = self.archived_until();
+        let archived_until = *result.as_ref().unwrap_or(&0);
+        *self.archived_until_cache.lock().unwrap() = Some((archived_until, Instant::now()));
+        result
+    }
+
+    /// The entries of an archived `slot`, None if the slot was never archived. Called from RPC,
+    /// so slots past the archived progress and recent misses don't go to the store.
+    pub fn get_slot_entries(&self, slot: u64) -> Result<Option<Vec<Entry>>> {
+        if slot >= self.cached_archived_until()? {
+            return Ok(None);
+        }
+        let start = self.segment_start(slot);
+        let segment = match self.get_segment(start)? {
+            Some(segment) => segment,
+            None => return Ok(None),
+        };
+        Ok(segment
+            .iter()
+            .find(|(s, _)| *s == slot)
+            .map(|(_, entries)| entries.clone()))
+    }
+
+    fn get_segment(&self, start: u64) -> Result<Option<Arc<Segment>>> {
+        if let Some((_, segment)) = self.cache.lock().unwrap().iter().find(|(s, _)| *s == start) {
+            return Ok(Some(segment.clone()));
+        }
+        if let Some(asked) = self.missing.lock().unwrap().get(&start) {
+            if asked.elapsed() < ARCHIVE_CACHE_TTL {
+                return Ok(None);
+            }
+        }
+
+        // No lock is held while the store is read
+        let segment = match self.fetch_segment(start) {
+            Ok(Some(segment)) => segment,
+            result => {
+                let mut missing = self.missing.lock().unwrap();
+                if missing.len() >= MAX_MISSING_SEGMENTS {
+                    missing.retain(|_, asked| asked.elapsed() < ARCHIVE_CACHE_TTL);
+                    if missing.len() >= MAX_MISSING_SEGMENTS {
+                        missing.clear();
+                    }
+                }
+                missing.insert(start, Instant::now());
+                return result;
+            }
+        };
+
+        let mut cache = self.cache.lock().unwrap();
+        if cache.len() >= MAX_CACHED_SEGMENTS {
+            cache.remove(0);
+        }
+        cache.push((start, segment.clone()));
+        self.missing.lock().unwrap().remove(&start);
+        Ok(Some(segment))
+    }
+
+    fn fetch_segment(&self, start: u64) -> Result<Option<Arc<Segment>>> {
+        let data = match self.store.get(&self.segment_key(start))? {
+            Some(data) => data,
+            None => return Ok(None),
+        };
+        let mut serialized = vec![];
+        GzDecoder::new(&data[..]).read_to_end(&mut serialized)?;
+        Ok(Some(Arc::new(deserialize(&serialized)?)))
+    }
+}
+
+pub struct LedgerArchiveService {
+    t_archive: JoinHandle<()>,
+}
+
+impl LedgerArchiveService {
+    pub fn new(
+        ledger_archive: Arc<LedgerArchive>,
+        blocktree: Arc<Blocktree>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let t_archive = Builder::new()
+            .name("morgan-ledger-archive".to_string())
+            .spawn(move || {
+                let mut next_segment = None;
+                while !exit.load(Ordering::Relaxed) {
+                    if let Err(e) = Self::archive_sealed_segments(
+                        &ledger_archive,
+                        &blocktree,
+                        &bank_forks,
+                        &mut next_segment,
+                    ) {
+                        println!(
+                            "{}",
+                            Warn(
+                                format!("ledger archive failed: {:?}", e).to_string(),
+                                module_path!().to_string()
+                            )
+                        );
+                    }
+                    sleep(ARCHIVE_POLL_INTERVAL);
+                }
+            })
+            .unwrap();
+        Self { t_archive }
+    }
+
+    fn archive_sealed_segments(
+        ledger_archive: &LedgerArchive,
+        blocktree: &Blocktree,
+        bank_forks: &RwLock<BankForks>,
+        next_segment: &mut Option<u64>,
+    ) -> Result<()> {
+        let mut start = match *next_segment {
+            Some(start) => start,
+            None => ledger_archive.archived_until()?,
+        };
+        *next_segment = Some(start);
+
+        let root = bank_forks.read().unwrap().root();
+        while start + ledger_archive.segment_slots() <= root + 1 {
+            let now = Instant::now();
+            let slots = ledger_archive.archive_segment(blocktree, start)?;
+            datapoint_info!(
+                "ledger-archive",
+                ("segment_start", start as i64, i64),
+                ("rooted_slots", slots as i64, i64),
+                ("elapsed_ms", duration_as_ms(&now.elapsed()) as i64, i64)
+            );
+            start += ledger_archive.segment_slots();
+            *next_segment = Some(start);
+        }
+        Ok(())
+    }
+}
+
+impl Service for LedgerArchiveService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.t_archive.join()
+    }
+}
+
+/// An `ObjectStore` speaking the S3 REST protocol, signed with AWS Signature Version 4
+#[cfg(feature = "ledger-archive")]
+pub struct S3ObjectStore {
+    client: reqwest::Client,
+    endpoint: reqwest::Url,
+    host: String,
+    // The endpoint's own path, signed as part of every object's canonical URI
+    base_path: String,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+#[cfg(feature = "ledger-archive")]
+impl S3ObjectStore {
+    pub fn new(config: &LedgerArchiveConfig) -> io::Result<Self> {
+        let invalid = |err: String| io::Error::new(io::ErrorKind::InvalidInput, err);
+        let endpoint = reqwest::Url::parse(config.endpoint.trim_end_matches('/'))
+            .map_err(|err| invalid(format!("invalid endpoint {}: {}", config.endpoint, err)))?;
+        let host = match (endpoint.host_str(), endpoint.port()) {
+            (Some(host), Some(port)) => format!("{}:{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => return Err(invalid(format!("no host in {}", config.endpoint))),
+        };
+        if endpoint.query().is_some() || endpoint.fragment().is_some() {
+            return Err(invalid(format!(
+                "query or fragment in endpoint {}",
+                config.endpoint
+            )));
+        }
+        let base_path = endpoint.path().trim_end_matches('/').to_string();
+        let client = reqwest::Client::builder()
+            .timeout(ARCHIVE_REQUEST_TIMEOUT)
+            .build()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        Ok(Self {
+            client,
+            endpoint,
+            host,
+            base_path,
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+        })
+    }
+
+    // The URI-encoded path of `key`, exactly as it is requested and signed
+    fn object_path(&self, key: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.base_path,
+            uri_encode(&self.bucket),
+            uri_encode(key)
+        )
+    }
+
+    fn request(
+        &self,
+        method: reqwest::Method,
+        key: &str,
+        body: Vec<u8>,
+    ) -> io::Result<reqwest::Response> {
+        use ring::digest::{digest, SHA256};
+
+        let path = self.object_path(key);
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = to_hex(digest(&SHA256, &body).as_ref());
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, self.host, payload_hash, amz_date, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            to_hex(digest(&SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let signing_key = signing_key(&self.secret_key, &date, &self.region, "s3");
+        let signature = to_hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        let url = format!("{}://{}{}", self.endpoint.scheme(), self.host, path);
+        self.client
+            .request(method, &url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization)
+            .body(body)
+            .send()
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+    }
+}
+
+#[cfg(feature = "ledger-archive")]
+impl ObjectStore for S3ObjectStore {
+    fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+        let response = self.request(reqwest::Method::PUT, key, data)?;
+        if !response.status().is_success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("put {} failed: {}", key, response.status()),
+            ));
+        }
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        let mut response = self.request(reqwest::Method::GET, key, vec![])?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("get {} failed: {}", key, response.status()),
+            ));
+        }
+        let mut data = vec![];
+        response
+            .copy_to(&mut data)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        Ok(Some(data))
+    }
+}
+
+#[cfg(feature = "ledger-archive")]
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    use ring::{digest, hmac};
+    let key = hmac::SigningKey::new(&digest::SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+#[cfg(feature = "ledger-archive")]
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> Vec<u8> {
+    let key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    hmac_sha256(&key, b"aws4_request")
+}
+
+#[cfg(feature = "ledger-archive")]
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Percent-encode everything but unreserved characters and path separators
+#[cfg(feature = "ledger-archive")]
+fn uri_encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockBufferPool::create_new_tmp_ledger;
+    use crate::blockBufferPool::tests::make_slot_entries;
+    use crate::genesisUtils::create_genesis_block;
+    use std::sync::atomic::AtomicUsize;
+
+    // Counts the reads that reach a shared `MemoryObjectStore`
+    struct CountingObjectStore {
+        store: Arc<MemoryObjectStore>,
+        gets: Arc<AtomicUsize>,
+    }
+
+    impl ObjectStore for CountingObjectStore {
+        fn put(&self, key: &str, data: Vec<u8>) -> io::Result<()> {
+            self.store.put(key, data)
+        }
+
+        fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            self.store.get(key)
+        }
+    }
+
+    #[test]
+    fn test_ledger_archive_segments() {
+        let genesis_block = create_genesis_block(10_000).genesis_block;
+        let (ledger_path, _) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree = Blocktree::open(&ledger_path).unwrap();
+
+        // Slots 1..4 chain off slot 0, only the first three are rooted
+        for slot in 1..5 {
+            let (blobs, _) = make_slot_entries(slot, slot - 1, 1);
+            blocktree.write_blobs(&blobs).unwrap();
+        }
+        blocktree.set_root(0, 0).unwrap();
+        blocktree.set_root(3, 0).unwrap();
+
+        let archive = LedgerArchive::new(Box::new(MemoryObjectStore::default()), "/ledger/", 2);
+        assert_eq!(archive.archived_until().unwrap(), 0);
+
+        assert_eq!(archive.archive_segment(&blocktree, 0).unwrap(), 2);
+        assert_eq!(archive.archive_segment(&blocktree, 2).unwrap(), 2);
+        assert_eq!(archive.archive_segment(&blocktree, 4).unwrap(), 0);
+        assert_eq!(archive.archived_until().unwrap(), 6);
+
+        for slot in 0..4 {
+            assert_eq!(
+                archive.get_slot_entries(slot).unwrap(),
+                Some(blocktree.get_slot_entries(slot, 0, None).unwrap())
+            );
+        }
+        assert_eq!(archive.get_slot_entries(4).unwrap(), None);
+        assert_eq!(archive.get_slot_entries(6).unwrap(), None);
+
+        drop(blocktree);
+        Blocktree::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+
+    #[test]
+    fn test_ledger_archive_caches_misses() {
+        let store = Arc::new(MemoryObjectStore::default());
+        let gets = Arc::new(AtomicUsize::new(0));
+        let archive = LedgerArchive::new(
+            Box::new(CountingObjectStore {
+                store: store.clone(),
+                gets: gets.clone(),
+            }),
+            "ledger",
+            2,
+        );
+
+        // Nothing archived yet, only the progress is read, and only once
+        assert_eq!(archive.get_slot_entries(1).unwrap(), None);
+        assert_eq!(archive.get_slot_entries(1).unwrap(), None);
+        assert_eq!(gets.load(Ordering::Relaxed), 1);
+
+        // Progress recorded without the segment, as after a lost upload
+        store
+            .put(&archive.progress_key(), serialize(&4u64).unwrap())
+            .unwrap();
+        assert_eq!(archive.get_slot_entries(1).unwrap(), None);
+        assert_eq!(gets.load(Ordering::Relaxed), 1);
+
+        // Once the cached progress expires the segment is asked for once
+        *archive.archived_until_cache.lock().unwrap() = None;
+        assert_eq!(archive.get_slot_entries(1).unwrap(), None);
+        assert_eq!(gets.load(Ordering::Relaxed), 3);
+        assert_eq!(archive.get_slot_entries(0).unwrap(), None);
+        assert_eq!(gets.load(Ordering::Relaxed), 3);
+
+        // Slots past the archived progress never reach the store
+        assert_eq!(archive.get_slot_entries(4).unwrap(), None);
+        assert_eq!(gets.load(Ordering::Relaxed), 3);
+    }
+
+    #[cfg(feature = "ledger-archive")]
+    #[test]
+    fn test_signing_key() {
+        // The example from the AWS Signature Version 4 documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        assert_eq!(
+            to_hex(&key),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+        assert_eq!(uri_encode("ledger/a b.segment"), "ledger/a%20b.segment");
+    }
+
+    #[cfg(feature = "ledger-archive")]
+    #[test]
+    fn test_s3_object_path() {
+        let mut config = LedgerArchiveConfig::default();
+        config.bucket = "archive".to_string();
+
+        config.endpoint = "https://s3.us-east-1.amazonaws.com".to_string();
+        let store = S3ObjectStore::new(&config).unwrap();
+        assert_eq!(store.object_path("ledger/1"), "/archive/ledger/1");
+
+        // A path on the endpoint is part of the signed URI too
+        config.endpoint = "http://127.0.0.1:9000/minio/s3/".to_string();
+        let store = S3ObjectStore::new(&config).unwrap();
+        assert_eq!(store.object_path("ledger/1"), "/minio/s3/archive/ledger/1");
+
+        config.endpoint = "http://127.0.0.1:9000/?versionId=1".to_string();
+        assert!(S3ObjectStore::new(&config).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! The `ledgerCleanupService` module keeps the ledger to a window of slots below the root,
+//! purging older slots from the blocktree so a long running validator doesn't fill its disk.
+//! Nothing at or above the root is purged, nor anything the newest snapshot or the ledger
+//! archive still needs.
+
+use crate::blockBufferPool::Blocktree;
+use crate::ledgerArchive::LedgerArchive;
+use crate::result::Result;
+use crate::service::Service;
+use crate::treasuryForks::BankForks;
+use morgan_helper::logHelper::*;
+use morgan_interface::timing::duration_as_ms;
+use morgan_runtime::snapshot::snapshot_slots;
+use std::cmp;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// How often the service checks whether the root has moved past more slots to purge
+const CLEANUP_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long the service sleeps between checks for exit, so shutdown isn't held up by a
+/// whole `CLEANUP_POLL_INTERVAL`
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub struct LedgerCleanupService {
+    t_cleanup: JoinHandle<()>,
+}
+
+impl LedgerCleanupService {
+    pub fn new(
+        blocktree: Arc<Blocktree>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        max_ledger_slots: u64,
+        snapshot_path: Option<PathBuf>,
+        ledger_archive: Option<Arc<LedgerArchive>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let exit = exit.clone();
+        let t_cleanup = Builder::new()
+            .name("morgan-ledger-cleanup".to_string())
+            .spawn(move || {
+                let mut purged_below = 0;
+                let mut last_cleanup: Option<Instant> = None;
+                while !exit.load(Ordering::Relaxed) {
+                    if last_cleanup.map_or(false, |last| last.elapsed() < CLEANUP_POLL_INTERVAL) {
+                        sleep(EXIT_POLL_INTERVAL);
+                        continue;
+                    }
+                    last_cleanup = Some(Instant::now());
+                    if let Err(e) = Self::cleanup_ledger(
+                        &blocktree,
+                        &bank_forks,
+                        max_ledger_slots,
+                        snapshot_path.as_ref(),
+                        ledger_archive.as_ref().map(|archive| archive.as_ref()),
+                        &mut purged_below,
+                    ) {
+                        println!(
+                            "{}",
+                            Warn(
+                                format!("ledger cleanup failed: {:?}", e).to_string(),
+                                module_path!().to_string()
+                            )
+                        );
+                    }
+                }
+            })
+            .unwrap();
+        Self { t_cleanup }
+    }
+
+    /// The slot everything below may be purged: `max_ledger_slots` below the root, but
+    /// never past the newest snapshot a restart replays from or what isn't archived yet
+    fn purge_floor(
+        root: u64,
+        max_ledger_slots: u64,
+        snapshot_path: Option<&PathBuf>,
+        ledger_archive: Option<&LedgerArchive>,
+    ) -> Result<u64> {
+        let mut floor = root.saturating_sub(max_ledger_slots);
+        if let Some(snapshot_path) = snapshot_path {
+            let newest_snapshot = snapshot_slots(snapshot_path)
+                .ok()
+                .and_then(|slots| slots.first().cloned());
+            floor = cmp::min(floor, newest_snapshot.unwrap_or(0));
+        }
+        if let Some(ledger_archive) = ledger_archive {
+            floor = cmp::min(floor, ledger_archive.archived_until()?);
+        }
+        Ok(floor)
+    }
+
+    fn cleanup_ledger(
+        blocktree: &Blocktree,
+        bank_forks: &RwLock<BankForks>,
+        max_ledger_slots: u64,
+        snapshot_path: Option<&PathBuf>,
+        ledger_archive: Option<&LedgerArchive>,
+        purged_below: &mut u64,
+    ) -> Result<()> {
+        let root = bank_forks.read().unwrap().root();
+        let floor = Self::purge_floor(root, max_ledger_slots, snapshot_path, ledger_archive)?;
+        if floor <= *purged_below {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let purged_slots = blocktree.purge_slots_below(floor)?;
+        *purged_below = floor;
+        datapoint_info!(
+            "ledger-cleanup",
+            ("purged_below", floor as i64, i64),
+            ("purged_slots", purged_slots as i64, i64),
+            ("elapsed_ms", duration_as_ms(&now.elapsed()) as i64, i64)
+        );
+        Ok(())
+    }
+}
+
+impl Service for LedgerCleanupService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.t_cleanup.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ledgerArchive::MemoryObjectStore;
+
+    #[test]
+    fn test_purge_floor() {
+        assert_eq!(
+            LedgerCleanupService::purge_floor(100, 10, None, None).unwrap(),
+            90
+        );
+        assert_eq!(
+            LedgerCleanupService::purge_floor(5, 10, None, None).unwrap(),
+            0
+        );
+
+        // nothing is archived yet
+        let ledger_archive = LedgerArchive::new(Box::new(MemoryObjectStore::default()), "test", 4);
+        assert_eq!(
+            LedgerCleanupService::purge_floor(100, 10, None, Some(&ledger_archive)).unwrap(),
+            0
+        );
+
+        // no snapshot to restart from yet
+        let snapshot_path = PathBuf::from("target").join("test_purge_floor");
+        assert_eq!(
+            LedgerCleanupService::purge_floor(100, 10, Some(&snapshot_path), None).unwrap(),
+            0
+        );
+    }
+}
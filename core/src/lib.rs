@@ -6,6 +6,8 @@
 //!
 
 // pub mod bank_forks;
+pub mod accountsHashVerifier;
+pub mod adminRpcService;
 pub mod treasuryForks;
 pub mod treasuryStage;
 pub mod fetchSpotStage;
@@ -32,6 +34,7 @@ pub mod cluster;
 pub mod clusterMessage;
 pub mod ClusterFixMessageListener;
 pub mod clusterTests;
+pub mod directStream;
 pub mod entryInfo;
 pub mod expunge;
 pub mod fetchStage;
@@ -41,6 +44,8 @@ pub mod gossipService;
 pub mod leaderArrange;
 pub mod leaderArrangeCache;
 pub mod leaderArrangeUtils;
+pub mod ledgerArchive;
+pub mod ledgerCleanupService;
 pub mod localCluster;
 pub mod localVoteSignerService;
 pub mod forkSelection;
@@ -49,8 +54,10 @@ pub mod waterClock;
 pub mod waterClockRecorder;
 pub mod waterClockService;
 pub mod recvmmsg;
+pub mod sendmmsg;
 pub mod fixMissingSpotService;
 pub mod repeatStage;
+pub mod replayErrors;
 pub mod cloner;
 pub mod result;
 pub mod retransmitStage;
@@ -59,16 +66,25 @@ pub mod rpcPubsub;
 pub mod rpcPubSsubService;
 pub mod rpcService;
 pub mod rpcSubscriptions;
+pub mod samplePerformanceService;
+pub mod sendTransactionService;
 pub mod service;
 pub mod signatureVerify;
 pub mod signatureVerifyStage;
+pub mod snapshotDownload;
+pub mod snapshotService;
+pub mod socketStats;
 pub mod stakingUtils;
+pub mod startupProgress;
 pub mod storageStage;
 pub mod streamer;
 pub mod testTx;
+pub mod transactionPolicy;
 pub mod transactionProcessCentre;
+pub mod transactionTrace;
 pub mod transactionVerifyCentre;
 pub mod verifier;
+pub mod weightedShuffle;
 pub mod spotTransmitService;
 
 #[macro_use]
@@ -79,6 +95,9 @@ extern crate morgan_budget_controller;
 #[macro_use]
 extern crate hex_literal;
 
+#[macro_use]
+extern crate lazy_static;
+
 #[macro_use]
 extern crate log;
 
@@ -9,6 +9,8 @@ use crate::service::Service;
 use crate::verifier::{Validator, ValidatorConfig};
 use morgan_client::thin_client::create_client;
 use morgan_client::thin_client::ThinClient;
+use morgan_netutil::find_available_port_in_range;
+use morgan_runtime::epoch_schedule::MINIMUM_SLOT_LENGTH;
 use morgan_interface::client::SyncClient;
 use morgan_interface::genesis_block::GenesisBlock;
 use morgan_interface::message::Message;
@@ -27,7 +29,9 @@ use morgan_vote_api::vote_state::VoteState;
 use std::collections::HashMap;
 use std::fs::remove_dir_all;
 use std::io::{Error, ErrorKind, Result};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use morgan_helper::logHelper::*;
 
 pub struct ValidatorInfo {
@@ -69,6 +73,8 @@ pub struct ClusterConfig {
     pub stakers_slot_offset: u64,
     pub native_instruction_processors: Vec<(String, Pubkey)>,
     pub poh_config: PohConfig,
+    /// Run a drone funded by the mint inside the leader, at `entry_point_info.drone`
+    pub embedded_drone: bool,
 }
 
 impl Default for ClusterConfig {
@@ -84,6 +90,52 @@ impl Default for ClusterConfig {
             stakers_slot_offset: DEFAULT_SLOTS_PER_EPOCH,
             native_instruction_processors: vec![],
             poh_config: PohConfig::default(),
+            embedded_drone: false,
+        }
+    }
+}
+
+/// Shortest tick `with_simulated_time` speeds ticks up to, nodes fall behind below this
+pub const MIN_SIMULATED_TICK_DURATION: Duration = Duration::from_millis(1);
+
+impl ClusterConfig {
+    /// Epochs of the minimum length, so tests can cross several epoch boundaries
+    pub fn fast_epochs() -> Self {
+        ClusterConfig {
+            slots_per_epoch: MINIMUM_SLOT_LENGTH as u64,
+            ..ClusterConfig::default()
+        }
+    }
+
+    /// PoH that sleeps for each tick instead of hashing, so how long slots take doesn't
+    /// depend on how fast the host hashes
+    pub fn sleep_poh() -> Self {
+        ClusterConfig {
+            poh_config: PohConfig::new_sleep(PohConfig::default().target_tick_duration),
+            ..ClusterConfig::default()
+        }
+    }
+
+    /// Longer leader slots, so throughput tests spend less time rotating leaders.
+    /// Replicators time out with slots this long
+    pub fn high_tps() -> Self {
+        ClusterConfig {
+            ticks_per_slot: DEFAULT_TICKS_PER_SLOT * 4,
+            ..ClusterConfig::sleep_poh()
+        }
+    }
+
+    /// Sleep based PoH with ticks `speedup` times shorter than the target tick duration, so
+    /// tests waiting on slots and epochs finish sooner
+    pub fn with_simulated_time(self, speedup: u32) -> Self {
+        assert!(speedup > 0);
+        let target_tick_duration = std::cmp::max(
+            self.poh_config.target_tick_duration / speedup,
+            MIN_SIMULATED_TICK_DURATION,
+        );
+        ClusterConfig {
+            poh_config: PohConfig::new_sleep(target_tick_duration),
+            ..self
         }
     }
 }
@@ -121,7 +173,7 @@ impl LocalCluster {
     pub fn new(config: &ClusterConfig) -> Self {
         let leader_keypair = Arc::new(Keypair::new());
         let leader_pubkey = leader_keypair.pubkey();
-        let leader_node = Node::new_localhost_with_pubkey(&leader_keypair.pubkey());
+        let mut leader_node = Node::new_localhost_with_pubkey(&leader_keypair.pubkey());
         let GenesisBlockInfo {
             mut genesis_block,
             mint_keypair,
@@ -143,6 +195,13 @@ impl LocalCluster {
 
         let (genesis_ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_block);
         let leader_ledger_path = tmp_copy_blocktree!(&genesis_ledger_path);
+        let mut leader_config = config.validator_config.clone();
+        if config.embedded_drone {
+            let drone_keypair = Keypair::from_bytes(&mint_keypair.to_bytes()).unwrap();
+            leader_config.drone_keypair = Some(Arc::new(drone_keypair));
+            let drone_port = find_available_port_in_range((1024, 65535)).unwrap();
+            leader_node.info.drone = SocketAddr::new(leader_node.info.rpc.ip(), drone_port);
+        }
         let leader_contact_info = leader_node.info.clone();
         let leader_storage_keypair = Arc::new(storage_keypair);
         let leader_voting_keypair = Arc::new(voting_keypair);
@@ -154,7 +213,7 @@ impl LocalCluster {
             &leader_voting_keypair,
             &leader_storage_keypair,
             None,
-            &config.validator_config,
+            &leader_config,
         );
 
         let mut fullnodes = HashMap::new();
@@ -590,7 +649,6 @@ impl Drop for LocalCluster {
 mod test {
     use super::*;
     use crate::storageStage::STORAGE_ROTATE_TEST_COUNT;
-    use morgan_runtime::epoch_schedule::MINIMUM_SLOT_LENGTH;
 
     #[test]
     fn test_local_cluster_start_and_exit() {
@@ -623,4 +681,29 @@ mod test {
         assert_eq!(cluster.replicators.len(), num_replicators);
     }
 
+    #[test]
+    fn test_cluster_config_presets() {
+        let config = ClusterConfig::fast_epochs();
+        assert_eq!(config.slots_per_epoch, MINIMUM_SLOT_LENGTH as u64);
+
+        let config = ClusterConfig::high_tps();
+        assert!(config.ticks_per_slot > DEFAULT_TICKS_PER_SLOT);
+        assert_eq!(config.poh_config.hashes_per_tick, None);
+
+        let config = ClusterConfig::fast_epochs().with_simulated_time(10);
+        assert_eq!(config.slots_per_epoch, MINIMUM_SLOT_LENGTH as u64);
+        assert_eq!(
+            config.poh_config.target_tick_duration,
+            PohConfig::default().target_tick_duration / 10
+        );
+        assert_eq!(config.poh_config.hashes_per_tick, None);
+        assert_eq!(
+            ClusterConfig::default()
+                .with_simulated_time(std::u32::MAX)
+                .poh_config
+                .target_tick_duration,
+            MIN_SIMULATED_TICK_DURATION
+        );
+    }
+
 }
@@ -1,13 +1,15 @@
 //! The `packet` module defines data structures and methods to pull data from the network.
 use crate::recvmmsg::{recv_mmsg, NUM_RCVMMSGS};
 use crate::result::{Error, Result};
+use crate::sendmmsg::batch_send;
 use bincode;
 use byteorder::{ByteOrder, LittleEndian};
 use serde::Serialize;
 use morgan_metricbot::inc_new_counter_debug;
-use morgan_interface::hash::Hash;
+use morgan_interface::hash::{hashv, Hash};
 pub use morgan_interface::packet::PACKET_DATA_SIZE;
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::{Keypair, KeypairUtil, Signature};
 use std::borrow::Borrow;
 use std::cmp;
 use std::fmt;
@@ -188,6 +190,7 @@ impl DerefMut for Blob {
 }
 
 #[derive(Clone, Default, PartialEq)]
+#[repr(C)]
 pub struct Blob {
     _data: BlobData, // hidden member, passed through by Deref
     pub meta: Meta,
@@ -212,6 +215,12 @@ pub enum BlobError {
     VerificationFailed,
 }
 
+#[derive(Debug)]
+pub enum PacketError {
+    /// the Packet's contents claim more bytes than were received
+    InvalidLen,
+}
+
 impl Packets {
     pub fn recv_from(&mut self, socket: &UdpSocket) -> Result<usize> {
         let mut i = 0;
@@ -251,10 +260,14 @@ impl Packets {
     }
 
     pub fn send_to(&self, socket: &UdpSocket) -> Result<()> {
-        for p in &self.packets {
-            let a = p.meta.addr();
-            socket.send_to(&p.data[..p.meta.size], &a)?;
-        }
+        let addrs: Vec<_> = self.packets.iter().map(|p| p.meta.addr()).collect();
+        let packets: Vec<_> = self
+            .packets
+            .iter()
+            .zip(&addrs)
+            .map(|(p, a)| (&p.data[..p.meta.size], a))
+            .collect();
+        batch_send(socket, &packets)?;
         Ok(())
     }
 }
@@ -328,6 +341,9 @@ pub fn deserialize_packets_in_blob(
     serialized_packet_size: usize,
     serialized_meta_size: usize,
 ) -> Result<Vec<Packet>> {
+    if serialized_packet_size < serialized_meta_size + PACKET_DATA_SIZE {
+        return Err(Error::PacketError(PacketError::InvalidLen));
+    }
     let mut packets: Vec<Packet> = Vec::with_capacity(data.len() / serialized_packet_size);
     let mut pos = 0;
     while pos + serialized_packet_size <= data.len() {
@@ -342,7 +358,11 @@ pub fn deserialize_packets_in_blob(
 }
 
 fn deserialize_single_packet_in_blob(data: &[u8], serialized_meta_size: usize) -> Result<Packet> {
-    let meta = bincode::deserialize(&data[..serialized_meta_size])?;
+    let meta: Meta = bincode::deserialize(&data[..serialized_meta_size])?;
+    // the meta is as untrusted as the data, a size past the data would be read out of bounds
+    if meta.size > PACKET_DATA_SIZE {
+        return Err(Error::PacketError(PacketError::InvalidLen));
+    }
     let mut packet_data = [0; PACKET_DATA_SIZE];
     packet_data
         .copy_from_slice(&data[serialized_meta_size..serialized_meta_size + PACKET_DATA_SIZE]);
@@ -355,12 +375,13 @@ macro_rules! range {
     };
 }
 
-const PARENT_RANGE: std::ops::Range<usize> = range!(0, u64);
+pub(crate) const SIGNATURE_RANGE: std::ops::Range<usize> = range!(0, Signature);
+const FORWARDED_RANGE: std::ops::Range<usize> = range!(SIGNATURE_RANGE.end, bool);
+const PARENT_RANGE: std::ops::Range<usize> = range!(FORWARDED_RANGE.end, u64);
 const SLOT_RANGE: std::ops::Range<usize> = range!(PARENT_RANGE.end, u64);
 const INDEX_RANGE: std::ops::Range<usize> = range!(SLOT_RANGE.end, u64);
-const ID_RANGE: std::ops::Range<usize> = range!(INDEX_RANGE.end, Pubkey);
-const FORWARDED_RANGE: std::ops::Range<usize> = range!(ID_RANGE.end, bool);
-const GENESIS_RANGE: std::ops::Range<usize> = range!(FORWARDED_RANGE.end, Hash);
+pub(crate) const ID_RANGE: std::ops::Range<usize> = range!(INDEX_RANGE.end, Pubkey);
+const GENESIS_RANGE: std::ops::Range<usize> = range!(ID_RANGE.end, Hash);
 const FLAGS_RANGE: std::ops::Range<usize> = range!(GENESIS_RANGE.end, u32);
 const SIZE_RANGE: std::ops::Range<usize> = range!(FLAGS_RANGE.end, u64);
 
@@ -372,6 +393,10 @@ macro_rules! align {
 
 pub const BLOB_HEADER_SIZE: usize = align!(SIZE_RANGE.end, BLOB_DATA_ALIGN); // make sure data() is safe for erasure
 
+/// The signature covers the blob from here to `data_size()`, everything but itself and the
+/// forwarded flag, which retransmit rewrites
+pub const BLOB_SIGNED_DATA_OFFSET: usize = FORWARDED_RANGE.end;
+
 pub const BLOB_FLAG_IS_LAST_IN_SLOT: u32 = 0x2;
 
 pub const BLOB_FLAG_IS_CODING: u32 = 0x1;
@@ -421,7 +446,7 @@ impl Blob {
     }
 
     /// sender id, we use this for identifying if its a blob from the leader that we should
-    /// retransmit.  eventually blobs should have a signature that we can use for spam filtering
+    /// retransmit.  `verify()` checks the blob was signed by this id
     pub fn id(&self) -> Pubkey {
         Pubkey::new(&self.data[ID_RANGE])
     }
@@ -430,9 +455,23 @@ impl Blob {
         self.data[ID_RANGE].copy_from_slice(id.as_ref())
     }
 
+    /// Seed of the stake-weighted shuffle that lays out the retransmit tree of this blob, every
+    /// node derives the same one from the signed header
+    pub fn seed(&self) -> [u8; 32] {
+        let mut seed = [0u8; 32];
+        seed.copy_from_slice(
+            hashv(&[
+                &self.data[ID_RANGE],
+                &self.data[SLOT_RANGE],
+                &self.data[INDEX_RANGE],
+            ])
+            .as_ref(),
+        );
+        seed
+    }
+
     /// Used to determine whether or not this blob should be forwarded in retransmit
-    /// A bool is used here instead of a flag because this item is not intended to be signed when
-    /// blob signatures are introduced
+    /// A bool is used here instead of a flag because this item is not signed
     pub fn should_forward(&self) -> bool {
         self.data[FORWARDED_RANGE][0] & 0x1 == 0
     }
@@ -442,6 +481,38 @@ impl Blob {
         self.data[FORWARDED_RANGE][0] = u8::from(forward)
     }
 
+    pub fn signature(&self) -> Signature {
+        Signature::new(&self.data[SIGNATURE_RANGE])
+    }
+
+    /// Sign the blob with the key of its id, once the rest of the header and the data are set
+    pub fn sign(&mut self, keypair: &Keypair) {
+        let signature = keypair.sign_message(&self.data[self.signed_data_range()]);
+        self.data[SIGNATURE_RANGE].copy_from_slice(signature.as_ref());
+    }
+
+    /// Whether the blob was signed by the key of its id
+    pub fn verify(&self) -> bool {
+        self.signed_data_len() > 0
+            && self
+                .signature()
+                .verify(self.id().as_ref(), &self.data[self.signed_data_range()])
+    }
+
+    /// Bytes covered by the signature, 0 if the header size is out of bounds
+    pub(crate) fn signed_data_len(&self) -> usize {
+        let size = self.data_size() as usize;
+        if size >= BLOB_HEADER_SIZE && size <= BLOB_SIZE {
+            size - BLOB_SIGNED_DATA_OFFSET
+        } else {
+            0
+        }
+    }
+
+    fn signed_data_range(&self) -> std::ops::Range<usize> {
+        BLOB_SIGNED_DATA_OFFSET..BLOB_SIGNED_DATA_OFFSET + self.signed_data_len()
+    }
+
     pub fn set_genesis_blockhash(&mut self, blockhash: &Hash) {
         self.data[GENESIS_RANGE].copy_from_slice(blockhash.as_ref())
     }
@@ -577,26 +648,23 @@ impl Blob {
         Ok(v)
     }
     pub fn send_to(socket: &UdpSocket, v: SharedBlobs) -> Result<()> {
-        for r in v {
-            {
-                let p = r.read().unwrap();
-                let a = p.meta.addr();
-                if let Err(e) = socket.send_to(&p.data[..p.meta.size], &a) {
-                    // warn!(
-                    //     "error sending {} byte packet to {:?}: {:?}",
-                    //     p.meta.size, a, e
-                    // );
-                    println!(
-                        "{}",
-                        Warn(
-                            format!("error sending {} byte packet to {:?}: {:?}",
-                                p.meta.size, a, e).to_string(),
-                            module_path!().to_string()
-                        )
-                    );
-                    Err(e)?;
-                }
-            }
+        let blobs: Vec<_> = v.iter().map(|r| r.read().unwrap()).collect();
+        let addrs: Vec<_> = blobs.iter().map(|p| p.meta.addr()).collect();
+        let packets: Vec<_> = blobs
+            .iter()
+            .zip(&addrs)
+            .map(|(p, a)| (&p.data[..p.meta.size], a))
+            .collect();
+        if let Err(e) = batch_send(socket, &packets) {
+            // warn!("error sending {} blobs: {:?}", blobs.len(), e);
+            println!(
+                "{}",
+                Warn(
+                    format!("error sending {} blobs: {:?}", blobs.len(), e).to_string(),
+                    module_path!().to_string()
+                )
+            );
+            Err(e)?;
         }
         Ok(())
     }
@@ -854,6 +922,27 @@ mod tests {
         assert_eq!(result, packets);
     }
 
+    #[test]
+    fn test_deserialize_packets_in_blob_invalid() {
+        let meta = Meta::default();
+        let serialized_meta_size = bincode::serialized_size(&meta).unwrap() as usize;
+        let serialized_packet_size = serialized_meta_size + PACKET_DATA_SIZE;
+
+        // a packet size that can't hold the meta and the data is rejected, not looped on
+        assert!(deserialize_packets_in_blob(&[0u8; 64], 0, serialized_meta_size).is_err());
+
+        let mut packet = Packet::default();
+        packet.meta.size = PACKET_DATA_SIZE + 1;
+        let mut blob = Blob::default();
+        assert_eq!(blob.store_packets(&[packet]), 1);
+        assert!(deserialize_packets_in_blob(
+            &blob.data()[..blob.size()],
+            serialized_packet_size,
+            serialized_meta_size,
+        )
+        .is_err());
+    }
+
     #[test]
     fn test_blob_data_align() {
         assert_eq!(std::mem::align_of::<BlobData>(), BLOB_DATA_ALIGN);
@@ -888,4 +977,31 @@ mod tests {
         assert_eq!(blob.genesis_blockhash(), hash);
     }
 
+    #[test]
+    fn test_blob_sign_verify() {
+        let keypair = Keypair::new();
+        let mut blob = Blob::from_serializable(&vec![1u8; 100]);
+        blob.set_id(&keypair.pubkey());
+        assert!(!blob.verify());
+
+        blob.sign(&keypair);
+        assert!(blob.verify());
+
+        // retransmit may flip the forwarded flag without breaking the signature
+        blob.set_forwarded(true);
+        assert!(blob.verify());
+
+        blob.set_index(1);
+        assert!(!blob.verify());
+        blob.sign(&keypair);
+        blob.set_id(&Pubkey::new_rand());
+        assert!(!blob.verify());
+
+        // an out of bounds size is never valid
+        blob.set_id(&keypair.pubkey());
+        blob.sign(&keypair);
+        blob.set_data_size(BLOB_SIZE as u64 + 1);
+        assert!(!blob.verify());
+    }
+
 }
@@ -1,10 +1,11 @@
 //! A stage to broadcast data from a leader node to validators
 //!
 use crate::blockBufferPool::Blocktree;
-use crate::clusterMessage::{ClusterInfo, ClusterInfoError, DATA_PLANE_FANOUT};
+use crate::clusterMessage::{ClusterInfo, ClusterInfoError};
+use crate::directStream::DirectStream;
 use crate::entryInfo::EntrySlice;
 use crate::expunge::CodingGenerator;
-use crate::packet::index_blobs_with_genesis;
+use crate::packet::{index_blobs_with_genesis, SharedBlob};
 use crate::waterClockRecorder::WorkingBankEntries;
 use crate::result::{Error, Result};
 use crate::service::Service;
@@ -16,6 +17,7 @@ use morgan_metricbot::{
 };
 use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::Keypair;
 use morgan_interface::timing::duration_as_ms;
 use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -39,7 +41,9 @@ struct BroadcastStats {
 
 struct Broadcast {
     id: Pubkey,
+    keypair: Arc<Keypair>,
     coding_generator: CodingGenerator,
+    direct_stream: Option<Arc<DirectStream>>,
     stats: BroadcastStats,
 }
 
@@ -84,14 +88,14 @@ impl Broadcast {
         }
 
         let bank_epoch = bank.get_stakers_epoch(bank.slot());
-        let mut broadcast_table = cluster_info
+        let (peers, peer_stakes) = cluster_info
             .read()
             .unwrap()
-            .sorted_tvu_peers(stakingUtils::staked_nodes_at_epoch(&bank, bank_epoch).as_ref());
+            .sorted_retransmit_peers_and_stakes(
+                stakingUtils::staked_nodes_at_epoch(&bank, bank_epoch).as_ref(),
+            );
 
-        inc_new_counter_warn!("broadcast_service-num_peers", broadcast_table.len() + 1);
-        // Layer 1, leader nodes are limited to the fanout size.
-        broadcast_table.truncate(DATA_PLANE_FANOUT);
+        inc_new_counter_warn!("broadcast_service-num_peers", peers.len());
 
         inc_new_counter_info!("broadcast_service-entries_received", num_entries);
 
@@ -126,22 +130,37 @@ impl Broadcast {
         if contains_last_tick {
             blobs.last().unwrap().write().unwrap().set_is_last_in_slot();
         }
+        self.sign_blobs(&blobs);
 
         blocktree.write_shared_blobs(&blobs)?;
 
         let coding = self.coding_generator.next(&blobs);
+        self.sign_blobs(&coding);
 
         let to_blobs_elapsed = duration_as_ms(&to_blobs_start.elapsed());
 
         let broadcast_start = Instant::now();
 
+        // Stream straight to the highest staked validators ahead of the broadcast tree
+        if let Some(direct_stream) = &self.direct_stream {
+            let sent = direct_stream.send(&peers, &peer_stakes, &blobs);
+            inc_new_counter_debug!("broadcast_service-direct_stream_sent", sent);
+        }
+
         // Send out data
-        ClusterInfo::broadcast(&self.id, contains_last_tick, &broadcast_table, sock, &blobs)?;
+        ClusterInfo::broadcast(
+            &self.id,
+            contains_last_tick,
+            &peers,
+            &peer_stakes,
+            sock,
+            &blobs,
+        )?;
 
         inc_new_counter_debug!("streamer-broadcast-sent", blobs.len());
 
         // send out erasures
-        ClusterInfo::broadcast(&self.id, false, &broadcast_table, sock, &coding)?;
+        ClusterInfo::broadcast(&self.id, false, &peers, &peer_stakes, sock, &coding)?;
 
         self.update_broadcast_stats(
             duration_as_ms(&broadcast_start.elapsed()),
@@ -154,6 +173,13 @@ impl Broadcast {
         Ok(())
     }
 
+    /// Sign the blobs as the leader once their headers are final
+    fn sign_blobs(&self, blobs: &[SharedBlob]) {
+        blobs
+            .par_iter()
+            .for_each(|blob| blob.write().unwrap().sign(&self.keypair));
+    }
+
     fn update_broadcast_stats(
         &mut self,
         broadcast_elapsed: u64,
@@ -218,15 +244,19 @@ impl BroadcastStage {
         sock: &UdpSocket,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         receiver: &Receiver<WorkingBankEntries>,
+        direct_stream: Option<Arc<DirectStream>>,
         blocktree: &Arc<Blocktree>,
         genesis_blockhash: &Hash,
     ) -> BroadcastStageReturnType {
         let me = cluster_info.read().unwrap().my_data().clone();
+        let keypair = cluster_info.read().unwrap().keypair.clone();
         let coding_generator = CodingGenerator::default();
 
         let mut broadcast = Broadcast {
             id: me.id,
+            keypair,
             coding_generator,
+            direct_stream,
             stats: BroadcastStats::default(),
         };
 
@@ -264,6 +294,7 @@ impl BroadcastStage {
     /// * `cluster_info` - ClusterInfo structure
     /// * `window` - Cache of blobs that we have broadcast
     /// * `receiver` - Receive channel for blobs to be retransmitted to all the layer 1 nodes.
+    /// * `direct_stream` - Streams the blobs straight to the highest staked validators too.
     /// * `exit_sender` - Set to true when this service exits, allows rest of Tpu to exit cleanly.
     /// Otherwise, when a Tpu closes, it only closes the stages that come after it. The stages
     /// that come before could be blocked on a receive, and never notice that they need to
@@ -276,6 +307,7 @@ impl BroadcastStage {
         sock: UdpSocket,
         cluster_info: Arc<RwLock<ClusterInfo>>,
         receiver: Receiver<WorkingBankEntries>,
+        direct_stream: Option<Arc<DirectStream>>,
         exit_sender: &Arc<AtomicBool>,
         blocktree: &Arc<Blocktree>,
         genesis_blockhash: &Hash,
@@ -291,6 +323,7 @@ impl BroadcastStage {
                     &sock,
                     &cluster_info,
                     &receiver,
+                    direct_stream,
                     &blocktree,
                     &genesis_blockhash,
                 )
@@ -362,6 +395,7 @@ mod test {
             leader_info.sockets.broadcast,
             cluster_info,
             entry_receiver,
+            None,
             &exit_sender,
             &blocktree,
             &Hash::default(),
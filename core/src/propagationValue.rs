@@ -1,5 +1,7 @@
 use crate::connectionInfo::ContactInfo;
+use crate::packet::BLOB_DATA_SIZE;
 use bincode::serialize;
+use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, Signable, Signature};
 use morgan_interface::transaction::Transaction;
@@ -15,12 +17,174 @@ pub enum CrdsValue {
     Vote(Vote),
     /// * Merge Strategy - Latest wallclock is picked
     EpochSlots(EpochSlots),
+    /// * Merge Strategy - Latest wallclock is picked
+    AccountsHash(AccountsHash),
+    /// * Merge Strategy - Latest wallclock is picked
+    Version(Version),
+    /// * Merge Strategy - Latest wallclock is picked
+    LedgerRange(LedgerRange),
+    /// * Merge Strategy - Latest wallclock is picked
+    EpochSlotsUpdate(EpochSlotsUpdate),
+}
+
+/// Full accounts hash a node computed for the first root it reached in an epoch
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AccountsHash {
+    pub from: Pubkey,
+    pub epoch: u64,
+    pub slot: u64,
+    pub hash: Hash,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
+impl AccountsHash {
+    pub fn new(from: Pubkey, epoch: u64, slot: u64, hash: Hash, wallclock: u64) -> Self {
+        Self {
+            from,
+            epoch,
+            slot,
+            hash,
+            signature: Signature::default(),
+            wallclock,
+        }
+    }
+}
+
+impl Signable for AccountsHash {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData {
+            epoch: u64,
+            slot: u64,
+            hash: Hash,
+            wallclock: u64,
+        }
+        let data = SignData {
+            epoch: self.epoch,
+            slot: self.slot,
+            hash: self.hash,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize AccountsHash")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature;
+    }
+}
+
+/// Software version a node advertises to the cluster
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Version {
+    pub from: Pubkey,
+    pub version: String,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
+impl Version {
+    pub fn new(from: Pubkey, version: String, wallclock: u64) -> Self {
+        Self {
+            from,
+            version,
+            signature: Signature::default(),
+            wallclock,
+        }
+    }
+}
+
+impl Signable for Version {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData<'a> {
+            version: &'a str,
+            wallclock: u64,
+        }
+        let data = SignData {
+            version: &self.version,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize Version")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature;
+    }
+}
+
+/// The slots a node's ledger holds, a node that purged a slot can't repair it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LedgerRange {
+    pub from: Pubkey,
+    pub first_slot: u64,
+    pub last_slot: u64,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
+impl LedgerRange {
+    pub fn new(from: Pubkey, first_slot: u64, last_slot: u64, wallclock: u64) -> Self {
+        Self {
+            from,
+            first_slot,
+            last_slot,
+            signature: Signature::default(),
+            wallclock,
+        }
+    }
+}
+
+impl Signable for LedgerRange {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData {
+            first_slot: u64,
+            last_slot: u64,
+            wallclock: u64,
+        }
+        let data = SignData {
+            first_slot: self.first_slot,
+            last_slot: self.last_slot,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize LedgerRange")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature;
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct EpochSlots {
     pub from: Pubkey,
     pub root: u64,
+    #[serde(with = "compressed_slots")]
     pub slots: BTreeSet<u64>,
     pub signature: Signature,
     pub wallclock: u64,
@@ -36,6 +200,166 @@ impl EpochSlots {
             wallclock,
         }
     }
+
+    /// Drop the highest slots until the rest fit in `MAX_EPOCH_SLOTS_BYTES` and number no more
+    /// than `MAX_EPOCH_SLOTS`, the slots right past the root are the ones repair wants first.
+    /// Returns the number of slots dropped
+    pub fn truncate(&mut self) -> usize {
+        let first = match self.slots.iter().next() {
+            Some(first) => *first,
+            None => return 0,
+        };
+        // the size of a prefix only depends on how many runs it makes and the slots it spans
+        let mut num_runs = 0;
+        let mut prev: Option<u64> = None;
+        let mut cut = None;
+        for (num_slots, slot) in self.slots.iter().enumerate() {
+            if prev.map_or(true, |prev| prev + 1 != *slot) {
+                num_runs += 1;
+            }
+            if num_slots as u64 == MAX_EPOCH_SLOTS
+                || CompressedSlots::serialized_size(num_runs, first, *slot)
+                    > MAX_EPOCH_SLOTS_BYTES as u64
+            {
+                cut = Some(*slot);
+                break;
+            }
+            prev = Some(*slot);
+        }
+        cut.map_or(0, |cut| self.slots.split_off(&cut).len())
+    }
+}
+
+/// Most bytes the slots of an `EpochSlots` take on the wire, so the value fits in a push
+/// message along with the rest of it
+pub const MAX_EPOCH_SLOTS_BYTES: usize = BLOB_DATA_SIZE - 1024;
+
+/// Most slots an `EpochSlots` advertises, as many as a bitmap of `MAX_EPOCH_SLOTS_BYTES` holds
+pub const MAX_EPOCH_SLOTS: u64 = MAX_EPOCH_SLOTS_BYTES as u64 * 8;
+
+/// The slots of an `EpochSlots` as they go over the wire, in whichever form is smaller
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum CompressedSlots {
+    /// First slot and length of every run of consecutive slots
+    Runs(Vec<(u64, u64)>),
+    /// Bit `i` of `bits` is set if slot `first + i` is
+    Bitmap { first: u64, bits: Vec<u8> },
+}
+
+impl CompressedSlots {
+    /// `slots` sorted and without duplicates, the way a `BTreeSet` hands them out
+    fn new(slots: &[u64]) -> Self {
+        let mut runs: Vec<(u64, u64)> = vec![];
+        for slot in slots {
+            match runs.last_mut() {
+                Some((first, len)) if *first + *len == *slot => *len += 1,
+                _ => runs.push((*slot, 1)),
+            }
+        }
+        let (first, last) = match (slots.first(), slots.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return CompressedSlots::Runs(runs),
+        };
+        if Self::prefers_runs(runs.len(), first, last) {
+            return CompressedSlots::Runs(runs);
+        }
+        let mut bits = vec![0u8; Self::bitmap_len(first, last) as usize];
+        for slot in slots {
+            let i = slot - first;
+            bits[(i / 8) as usize] |= 1 << (i % 8);
+        }
+        CompressedSlots::Bitmap { first, bits }
+    }
+
+    fn bitmap_len(first: u64, last: u64) -> u64 {
+        (last - first) / 8 + 1
+    }
+
+    /// A run takes two u64s, the bitmap a bit for every slot it spans
+    fn prefers_runs(num_runs: usize, first: u64, last: u64) -> bool {
+        Self::bitmap_len(first, last) >= num_runs as u64 * 16
+    }
+
+    /// Bytes `new` serializes slots making `num_runs` runs from `first` to `last` into
+    fn serialized_size(num_runs: usize, first: u64, last: u64) -> u64 {
+        // a u32 variant tag, then a u64 length ahead of the runs, or the bitmap's first slot
+        // and a u64 length ahead of its bits
+        if Self::prefers_runs(num_runs, first, last) {
+            4 + 8 + num_runs as u64 * 16
+        } else {
+            4 + 8 + 8 + Self::bitmap_len(first, last)
+        }
+    }
+
+    /// Bytes `new` serializes `slots` into
+    fn serialized_size_of(slots: &BTreeSet<u64>) -> u64 {
+        let (first, last) = match (slots.iter().next(), slots.iter().next_back()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return 4 + 8,
+        };
+        let num_runs = slots
+            .iter()
+            .zip(slots.iter().skip(1))
+            .filter(|(prev, slot)| **prev + 1 != **slot)
+            .count()
+            + 1;
+        Self::serialized_size(num_runs, first, last)
+    }
+
+    /// The slots these stand for, refusing more than `MAX_EPOCH_SLOTS` of them
+    fn decompress(self) -> Result<BTreeSet<u64>, &'static str> {
+        match self {
+            CompressedSlots::Runs(runs) => {
+                let mut num_slots = 0u64;
+                for (first, len) in &runs {
+                    if first.checked_add(*len).is_none() {
+                        return Err("slot run overflows");
+                    }
+                    num_slots = num_slots.saturating_add(*len);
+                }
+                if num_slots > MAX_EPOCH_SLOTS {
+                    return Err("too many slots");
+                }
+                Ok(runs
+                    .into_iter()
+                    .flat_map(|(first, len)| first..first + len)
+                    .collect())
+            }
+            CompressedSlots::Bitmap { first, bits } => {
+                let span = bits.len() as u64 * 8;
+                if span > MAX_EPOCH_SLOTS {
+                    return Err("slot bitmap too large");
+                }
+                (0..span)
+                    .filter(|i| bits[(i / 8) as usize] & (1 << (i % 8)) != 0)
+                    .map(|i| first.checked_add(i).ok_or("slot bitmap overflows"))
+                    .collect()
+            }
+        }
+    }
+}
+
+mod compressed_slots {
+    use super::CompressedSlots;
+    use serde::de::{Deserialize, Deserializer, Error};
+    use serde::ser::{Serialize, Serializer};
+    use std::collections::BTreeSet;
+
+    pub fn serialize<S: Serializer>(
+        slots: &BTreeSet<u64>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let slots: Vec<u64> = slots.iter().cloned().collect();
+        CompressedSlots::new(&slots).serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeSet<u64>, D::Error> {
+        CompressedSlots::deserialize(deserializer)?
+            .decompress()
+            .map_err(D::Error::custom)
+    }
 }
 
 impl Signable for EpochSlots {
@@ -67,6 +391,74 @@ impl Signable for EpochSlots {
     }
 }
 
+/// The slots a node completed since its `EpochSlots` went out, pushed in place of the whole set
+/// while the root holds. Every update carries all of them, so a lost one costs nothing
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EpochSlotsUpdate {
+    pub from: Pubkey,
+    /// Wallclock of the `EpochSlots` these slots add to
+    pub base_wallclock: u64,
+    #[serde(with = "compressed_slots")]
+    pub slots: BTreeSet<u64>,
+    pub signature: Signature,
+    pub wallclock: u64,
+}
+
+impl EpochSlotsUpdate {
+    /// The slots `epoch_slots` adds to `base`, unless the root moved, slots were dropped or the
+    /// update wouldn't be much smaller than `epoch_slots`, then the whole set has to go out
+    pub fn new(base: &EpochSlots, epoch_slots: &EpochSlots) -> Option<Self> {
+        if base.from != epoch_slots.from
+            || base.root != epoch_slots.root
+            || !base.slots.is_subset(&epoch_slots.slots)
+        {
+            return None;
+        }
+        let slots: BTreeSet<u64> = epoch_slots.slots.difference(&base.slots).cloned().collect();
+        if CompressedSlots::serialized_size_of(&slots) * 2
+            > CompressedSlots::serialized_size_of(&epoch_slots.slots)
+        {
+            return None;
+        }
+        Some(Self {
+            from: epoch_slots.from,
+            base_wallclock: base.wallclock,
+            slots,
+            signature: Signature::default(),
+            wallclock: epoch_slots.wallclock,
+        })
+    }
+}
+
+impl Signable for EpochSlotsUpdate {
+    fn pubkey(&self) -> Pubkey {
+        self.from
+    }
+
+    fn signable_data(&self) -> Vec<u8> {
+        #[derive(Serialize)]
+        struct SignData<'a> {
+            base_wallclock: u64,
+            slots: &'a BTreeSet<u64>,
+            wallclock: u64,
+        }
+        let data = SignData {
+            base_wallclock: self.base_wallclock,
+            slots: &self.slots,
+            wallclock: self.wallclock,
+        };
+        serialize(&data).expect("unable to serialize EpochSlotsUpdate")
+    }
+
+    fn get_signature(&self) -> Signature {
+        self.signature
+    }
+
+    fn set_signature(&mut self, signature: Signature) {
+        self.signature = signature;
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Vote {
     pub from: Pubkey,
@@ -120,6 +512,10 @@ pub enum CrdsValueLabel {
     ContactInfo(Pubkey),
     Vote(Pubkey),
     EpochSlots(Pubkey),
+    AccountsHash(Pubkey),
+    Version(Pubkey),
+    LedgerRange(Pubkey),
+    EpochSlotsUpdate(Pubkey),
 }
 
 impl fmt::Display for CrdsValueLabel {
@@ -128,6 +524,12 @@ impl fmt::Display for CrdsValueLabel {
             CrdsValueLabel::ContactInfo(_) => write!(f, "ContactInfo({})", self.pubkey()),
             CrdsValueLabel::Vote(_) => write!(f, "Vote({})", self.pubkey()),
             CrdsValueLabel::EpochSlots(_) => write!(f, "EpochSlots({})", self.pubkey()),
+            CrdsValueLabel::AccountsHash(_) => write!(f, "AccountsHash({})", self.pubkey()),
+            CrdsValueLabel::Version(_) => write!(f, "Version({})", self.pubkey()),
+            CrdsValueLabel::LedgerRange(_) => write!(f, "LedgerRange({})", self.pubkey()),
+            CrdsValueLabel::EpochSlotsUpdate(_) => {
+                write!(f, "EpochSlotsUpdate({})", self.pubkey())
+            }
         }
     }
 }
@@ -138,6 +540,10 @@ impl CrdsValueLabel {
             CrdsValueLabel::ContactInfo(p) => *p,
             CrdsValueLabel::Vote(p) => *p,
             CrdsValueLabel::EpochSlots(p) => *p,
+            CrdsValueLabel::AccountsHash(p) => *p,
+            CrdsValueLabel::Version(p) => *p,
+            CrdsValueLabel::LedgerRange(p) => *p,
+            CrdsValueLabel::EpochSlotsUpdate(p) => *p,
         }
     }
 }
@@ -151,6 +557,10 @@ impl CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.wallclock,
             CrdsValue::Vote(vote) => vote.wallclock,
             CrdsValue::EpochSlots(vote) => vote.wallclock,
+            CrdsValue::AccountsHash(accounts_hash) => accounts_hash.wallclock,
+            CrdsValue::Version(version) => version.wallclock,
+            CrdsValue::LedgerRange(ledger_range) => ledger_range.wallclock,
+            CrdsValue::EpochSlotsUpdate(update) => update.wallclock,
         }
     }
     pub fn label(&self) -> CrdsValueLabel {
@@ -160,6 +570,16 @@ impl CrdsValue {
             }
             CrdsValue::Vote(vote) => CrdsValueLabel::Vote(vote.pubkey()),
             CrdsValue::EpochSlots(slots) => CrdsValueLabel::EpochSlots(slots.pubkey()),
+            CrdsValue::AccountsHash(accounts_hash) => {
+                CrdsValueLabel::AccountsHash(accounts_hash.pubkey())
+            }
+            CrdsValue::Version(version) => CrdsValueLabel::Version(version.pubkey()),
+            CrdsValue::LedgerRange(ledger_range) => {
+                CrdsValueLabel::LedgerRange(ledger_range.pubkey())
+            }
+            CrdsValue::EpochSlotsUpdate(update) => {
+                CrdsValueLabel::EpochSlotsUpdate(update.pubkey())
+            }
         }
     }
     pub fn contact_info(&self) -> Option<&ContactInfo> {
@@ -180,12 +600,40 @@ impl CrdsValue {
             _ => None,
         }
     }
+    pub fn accounts_hash(&self) -> Option<&AccountsHash> {
+        match self {
+            CrdsValue::AccountsHash(accounts_hash) => Some(accounts_hash),
+            _ => None,
+        }
+    }
+    pub fn version(&self) -> Option<&Version> {
+        match self {
+            CrdsValue::Version(version) => Some(version),
+            _ => None,
+        }
+    }
+    pub fn ledger_range(&self) -> Option<&LedgerRange> {
+        match self {
+            CrdsValue::LedgerRange(ledger_range) => Some(ledger_range),
+            _ => None,
+        }
+    }
+    pub fn epoch_slots_update(&self) -> Option<&EpochSlotsUpdate> {
+        match self {
+            CrdsValue::EpochSlotsUpdate(update) => Some(update),
+            _ => None,
+        }
+    }
     /// Return all the possible labels for a record identified by Pubkey.
-    pub fn record_labels(key: &Pubkey) -> [CrdsValueLabel; 3] {
+    pub fn record_labels(key: &Pubkey) -> [CrdsValueLabel; 7] {
         [
             CrdsValueLabel::ContactInfo(*key),
             CrdsValueLabel::Vote(*key),
             CrdsValueLabel::EpochSlots(*key),
+            CrdsValueLabel::AccountsHash(*key),
+            CrdsValueLabel::Version(*key),
+            CrdsValueLabel::LedgerRange(*key),
+            CrdsValueLabel::EpochSlotsUpdate(*key),
         ]
     }
 }
@@ -196,6 +644,10 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.sign(keypair),
             CrdsValue::Vote(vote) => vote.sign(keypair),
             CrdsValue::EpochSlots(epoch_slots) => epoch_slots.sign(keypair),
+            CrdsValue::AccountsHash(accounts_hash) => accounts_hash.sign(keypair),
+            CrdsValue::Version(version) => version.sign(keypair),
+            CrdsValue::LedgerRange(ledger_range) => ledger_range.sign(keypair),
+            CrdsValue::EpochSlotsUpdate(update) => update.sign(keypair),
         };
     }
 
@@ -204,6 +656,10 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.verify(),
             CrdsValue::Vote(vote) => vote.verify(),
             CrdsValue::EpochSlots(epoch_slots) => epoch_slots.verify(),
+            CrdsValue::AccountsHash(accounts_hash) => accounts_hash.verify(),
+            CrdsValue::Version(version) => version.verify(),
+            CrdsValue::LedgerRange(ledger_range) => ledger_range.verify(),
+            CrdsValue::EpochSlotsUpdate(update) => update.verify(),
         }
     }
 
@@ -212,6 +668,10 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.pubkey(),
             CrdsValue::Vote(vote) => vote.pubkey(),
             CrdsValue::EpochSlots(epoch_slots) => epoch_slots.pubkey(),
+            CrdsValue::AccountsHash(accounts_hash) => accounts_hash.pubkey(),
+            CrdsValue::Version(version) => version.pubkey(),
+            CrdsValue::LedgerRange(ledger_range) => ledger_range.pubkey(),
+            CrdsValue::EpochSlotsUpdate(update) => update.pubkey(),
         }
     }
 
@@ -224,6 +684,10 @@ impl Signable for CrdsValue {
             CrdsValue::ContactInfo(contact_info) => contact_info.get_signature(),
             CrdsValue::Vote(vote) => vote.get_signature(),
             CrdsValue::EpochSlots(epoch_slots) => epoch_slots.get_signature(),
+            CrdsValue::AccountsHash(accounts_hash) => accounts_hash.get_signature(),
+            CrdsValue::Version(version) => version.get_signature(),
+            CrdsValue::LedgerRange(ledger_range) => ledger_range.get_signature(),
+            CrdsValue::EpochSlotsUpdate(update) => update.get_signature(),
         }
     }
 
@@ -243,13 +707,17 @@ mod test {
 
     #[test]
     fn test_labels() {
-        let mut hits = [false; 3];
+        let mut hits = [false; 7];
         // this method should cover all the possible labels
         for v in &CrdsValue::record_labels(&Pubkey::default()) {
             match v {
                 CrdsValueLabel::ContactInfo(_) => hits[0] = true,
                 CrdsValueLabel::Vote(_) => hits[1] = true,
                 CrdsValueLabel::EpochSlots(_) => hits[2] = true,
+                CrdsValueLabel::AccountsHash(_) => hits[3] = true,
+                CrdsValueLabel::Version(_) => hits[4] = true,
+                CrdsValueLabel::LedgerRange(_) => hits[5] = true,
+                CrdsValueLabel::EpochSlotsUpdate(_) => hits[6] = true,
             }
         }
         assert!(hits.iter().all(|x| *x));
@@ -270,6 +738,38 @@ mod test {
         assert_eq!(v.wallclock(), 0);
         let key = v.clone().epoch_slots().unwrap().from;
         assert_eq!(v.label(), CrdsValueLabel::EpochSlots(key));
+
+        let v = CrdsValue::AccountsHash(AccountsHash::new(
+            Pubkey::default(),
+            0,
+            0,
+            Hash::default(),
+            0,
+        ));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().accounts_hash().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::AccountsHash(key));
+
+        let v = CrdsValue::Version(Version::new(Pubkey::default(), "0.1.0".to_string(), 0));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().version().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::Version(key));
+
+        let v = CrdsValue::LedgerRange(LedgerRange::new(Pubkey::default(), 0, 0, 0));
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().ledger_range().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::LedgerRange(key));
+
+        let v = CrdsValue::EpochSlotsUpdate(EpochSlotsUpdate {
+            from: Pubkey::default(),
+            base_wallclock: 0,
+            slots: BTreeSet::new(),
+            signature: Signature::default(),
+            wallclock: 0,
+        });
+        assert_eq!(v.wallclock(), 0);
+        let key = v.clone().epoch_slots_update().unwrap().from;
+        assert_eq!(v.label(), CrdsValueLabel::EpochSlotsUpdate(key));
     }
     #[test]
     fn test_signature() {
@@ -283,6 +783,138 @@ mod test {
         let btreeset: BTreeSet<u64> = vec![1, 2, 3, 6, 8].into_iter().collect();
         v = CrdsValue::EpochSlots(EpochSlots::new(keypair.pubkey(), 0, btreeset, timestamp()));
         verify_signatures(&mut v, &keypair, &wrong_keypair);
+        v = CrdsValue::AccountsHash(AccountsHash::new(
+            keypair.pubkey(),
+            1,
+            32,
+            Hash::default(),
+            timestamp(),
+        ));
+        verify_signatures(&mut v, &keypair, &wrong_keypair);
+        v = CrdsValue::Version(Version::new(
+            keypair.pubkey(),
+            "0.1.0".to_string(),
+            timestamp(),
+        ));
+        verify_signatures(&mut v, &keypair, &wrong_keypair);
+        v = CrdsValue::LedgerRange(LedgerRange::new(keypair.pubkey(), 3, 10, timestamp()));
+        verify_signatures(&mut v, &keypair, &wrong_keypair);
+        v = CrdsValue::EpochSlotsUpdate(EpochSlotsUpdate {
+            from: keypair.pubkey(),
+            base_wallclock: timestamp(),
+            slots: vec![6, 8].into_iter().collect(),
+            signature: Signature::default(),
+            wallclock: timestamp(),
+        });
+        verify_signatures(&mut v, &keypair, &wrong_keypair);
+    }
+
+    #[test]
+    fn test_compressed_slots() {
+        let round_trip = |slots: &BTreeSet<u64>| {
+            let value = EpochSlots::new(Pubkey::default(), 0, slots.clone(), 0);
+            let value: EpochSlots = deserialize(&serialize(&value).unwrap()).unwrap();
+            assert_eq!(value.slots, *slots);
+        };
+
+        // runs of consecutive slots go as runs, scattered ones as a bitmap
+        let runs: BTreeSet<u64> = (10..1_000).chain(5_000..6_000).collect();
+        let slots: Vec<_> = runs.iter().cloned().collect();
+        assert_eq!(
+            CompressedSlots::new(&slots),
+            CompressedSlots::Runs(vec![(10, 990), (5_000, 1_000)])
+        );
+        round_trip(&runs);
+
+        let scattered: BTreeSet<u64> = (3..1_000).step_by(3).collect();
+        let slots: Vec<_> = scattered.iter().cloned().collect();
+        match CompressedSlots::new(&slots) {
+            CompressedSlots::Bitmap { first, bits } => {
+                assert_eq!(first, 3);
+                assert_eq!(bits.len(), 125);
+            }
+            slots => panic!("expected a bitmap, got {:?}", slots),
+        }
+        round_trip(&scattered);
+        round_trip(&BTreeSet::new());
+        round_trip(&vec![std::u64::MAX - 1].into_iter().collect());
+
+        // the size is known without compressing
+        for slots in &[runs, scattered, BTreeSet::new()] {
+            assert_eq!(
+                CompressedSlots::serialized_size_of(slots),
+                bincode::serialized_size(&CompressedSlots::new(
+                    &slots.iter().cloned().collect::<Vec<_>>()
+                ))
+                .unwrap()
+            );
+        }
+
+        // no more slots than an honest node advertises
+        let value = CompressedSlots::Runs(vec![(0, MAX_EPOCH_SLOTS + 1)]);
+        assert!(value.decompress().is_err());
+        let value = CompressedSlots::Runs(vec![(std::u64::MAX, 2)]);
+        assert!(value.decompress().is_err());
+        let value = CompressedSlots::Bitmap {
+            first: 0,
+            bits: vec![0; MAX_EPOCH_SLOTS_BYTES + 1],
+        };
+        assert!(value.decompress().is_err());
+        let value = CompressedSlots::Bitmap {
+            first: std::u64::MAX,
+            bits: vec![2],
+        };
+        assert!(value.decompress().is_err());
+    }
+
+    #[test]
+    fn test_epoch_slots_truncate() {
+        let slots: BTreeSet<u64> = (0..1_000).collect();
+        let mut value = EpochSlots::new(Pubkey::default(), 0, slots.clone(), 0);
+        assert_eq!(value.truncate(), 0);
+        assert_eq!(value.slots, slots);
+
+        // every other slot past what the bitmap holds, the lowest ones are kept
+        let slots: BTreeSet<u64> = (0..MAX_EPOCH_SLOTS * 3).step_by(2).collect();
+        let mut value = EpochSlots::new(Pubkey::default(), 0, slots.clone(), 0);
+        let dropped = value.truncate();
+        assert!(dropped > 0);
+        assert_eq!(value.slots.len() + dropped, slots.len());
+        assert!(value.slots.iter().eq(slots.iter().take(value.slots.len())));
+        assert!(serialize(&value).unwrap().len() <= MAX_EPOCH_SLOTS_BYTES + 128);
+        // the next slot would have pushed it past the bound
+        let next = slots.iter().nth(value.slots.len()).unwrap();
+        value.slots.insert(*next);
+        assert!(CompressedSlots::serialized_size_of(&value.slots) > MAX_EPOCH_SLOTS_BYTES as u64);
+
+        // a single run stops at the number of slots the bitmap holds
+        let slots: BTreeSet<u64> = (0..MAX_EPOCH_SLOTS * 2).collect();
+        let mut value = EpochSlots::new(Pubkey::default(), 0, slots, 0);
+        assert_eq!(value.truncate(), MAX_EPOCH_SLOTS as usize);
+        assert_eq!(value.slots.len() as u64, MAX_EPOCH_SLOTS);
+    }
+
+    #[test]
+    fn test_epoch_slots_update() {
+        let id = Pubkey::new_rand();
+        let base_slots: BTreeSet<u64> = (1..1_000).step_by(2).collect();
+        let base = EpochSlots::new(id, 0, base_slots.clone(), 1);
+
+        // a few more slots go out on their own
+        let mut slots = base_slots.clone();
+        slots.extend(vec![2_000, 2_002]);
+        let update =
+            EpochSlotsUpdate::new(&base, &EpochSlots::new(id, 0, slots.clone(), 2)).unwrap();
+        assert_eq!(update.base_wallclock, 1);
+        assert_eq!(update.wallclock, 2);
+        assert_eq!(update.slots, vec![2_000, 2_002].into_iter().collect());
+
+        // a new root, dropped slots or as many new slots as there were take the whole set
+        assert!(EpochSlotsUpdate::new(&base, &EpochSlots::new(id, 1, slots.clone(), 2)).is_none());
+        slots.remove(&1);
+        assert!(EpochSlotsUpdate::new(&base, &EpochSlots::new(id, 0, slots, 2)).is_none());
+        let slots: BTreeSet<u64> = (1..4_000).step_by(2).collect();
+        assert!(EpochSlotsUpdate::new(&base, &EpochSlots::new(id, 0, slots, 2)).is_none());
     }
 
     fn test_serialize_deserialize_value(value: &mut CrdsValue, keypair: &Keypair) {
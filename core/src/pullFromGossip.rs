@@ -67,29 +67,30 @@ impl CrdsGossipPull {
         let self_info = crds
             .lookup(&CrdsValueLabel::ContactInfo(*self_id))
             .unwrap_or_else(|| panic!("self_id invalid {}", self_id));
-        Ok((options[random].1.id, filter, self_info.clone()))
+        Ok((options[random].1.id, filter, self_info))
     }
 
-    fn pull_options<'a>(
+    fn pull_options(
         &self,
-        crds: &'a Crds,
+        crds: &Crds,
         self_id: &Pubkey,
         now: u64,
         stakes: &HashMap<Pubkey, u64>,
-    ) -> Vec<(f32, &'a ContactInfo)> {
-        crds.table
-            .values()
-            .filter_map(|v| v.value.contact_info())
-            .filter(|v| v.id != *self_id && ContactInfo::is_valid_address(&v.gossip))
-            .map(|item| {
-                let max_weight = f32::from(u16::max_value()) - 1.0;
-                let req_time: u64 = *self.pull_request_time.get(&item.id).unwrap_or(&0);
-                let since = ((now - req_time) / 1024) as u32;
-                let stake = get_stake(&item.id, stakes);
-                let weight = get_weight(max_weight, since, stake);
-                (weight, item)
-            })
-            .collect()
+    ) -> Vec<(f32, ContactInfo)> {
+        crds.with_values(|values| {
+            values
+                .filter_map(|v| v.value.contact_info())
+                .filter(|v| v.id != *self_id && ContactInfo::is_valid_address(&v.gossip))
+                .map(|item| {
+                    let max_weight = f32::from(u16::max_value()) - 1.0;
+                    let req_time: u64 = *self.pull_request_time.get(&item.id).unwrap_or(&0);
+                    let since = ((now - req_time) / 1024) as u32;
+                    let stake = get_stake(&item.id, stakes);
+                    let weight = get_weight(max_weight, since, stake);
+                    (weight, item.clone())
+                })
+                .collect()
+        })
     }
 
     /// time when a request to `from` was initiated
@@ -105,10 +106,15 @@ impl CrdsGossipPull {
         self.purged_values.push_back((hash, timestamp))
     }
 
+    /// Store the (hash, timestamp) of old values in the purged values set
+    pub fn record_old_hashes(&mut self, hashes: Vec<(Hash, u64)>) {
+        self.purged_values.extend(hashes)
+    }
+
     /// process a pull request and create a response
     pub fn process_pull_request(
         &mut self,
-        crds: &mut Crds,
+        crds: &Crds,
         caller: CrdsValue,
         mut filter: Bloom<Hash>,
         now: u64,
@@ -126,71 +132,84 @@ impl CrdsGossipPull {
     /// process a pull response
     pub fn process_pull_response(
         &mut self,
-        crds: &mut Crds,
+        crds: &Crds,
         from: &Pubkey,
         response: Vec<CrdsValue>,
         now: u64,
     ) -> usize {
+        let (failed, old_hashes) = Self::insert_pull_response(crds, from, response, now);
+        self.record_old_hashes(old_hashes);
+        failed
+    }
+    /// The part of `process_pull_response` that only needs the table: inserts the response and
+    /// returns the number of values that failed to insert along with the (hash, timestamp) of
+    /// the values they replaced, for `record_old_hashes`
+    pub fn insert_pull_response(
+        crds: &Crds,
+        from: &Pubkey,
+        response: Vec<CrdsValue>,
+        now: u64,
+    ) -> (usize, Vec<(Hash, u64)>) {
         let mut failed = 0;
-        for r in response {
-            let owner = r.label().pubkey();
-            let old = crds.insert(r, now);
+        let mut old_hashes = vec![];
+        let owners: Vec<_> = response.iter().map(|r| r.label().pubkey()).collect();
+        let results = crds.insert_batch(response, now);
+        for (owner, old) in owners.into_iter().zip(results) {
             failed += old.is_err() as usize;
             old.ok().map(|opt| {
                 crds.update_record_timestamp(&owner, now);
-                opt.map(|val| {
-                    self.purged_values
-                        .push_back((val.value_hash, val.local_timestamp))
-                })
+                opt.map(|val| old_hashes.push((val.value_hash, val.local_timestamp)))
             });
         }
         crds.update_record_timestamp(from, now);
-        failed
+        (failed, old_hashes)
     }
     /// build a filter of the current crds table
     pub fn build_crds_filter(&self, crds: &Crds) -> Bloom<Hash> {
-        let num = cmp::max(
-            CRDS_GOSSIP_BLOOM_SIZE,
-            crds.table.values().count() + self.purged_values.len(),
-        );
-        let mut bloom = Bloom::random(num, 0.1, 4 * 1024 * 8 - 1);
-        for v in crds.table.values() {
-            bloom.add(&v.value_hash);
-        }
-        for (value_hash, _insert_timestamp) in &self.purged_values {
-            bloom.add(value_hash);
-        }
-        bloom
+        crds.with_values(|values| {
+            let values: Vec<_> = values.map(|v| v.value_hash).collect();
+            let num = cmp::max(
+                CRDS_GOSSIP_BLOOM_SIZE,
+                values.len() + self.purged_values.len(),
+            );
+            let mut bloom = Bloom::random(num, 0.1, 4 * 1024 * 8 - 1);
+            for value_hash in &values {
+                bloom.add(value_hash);
+            }
+            for (value_hash, _insert_timestamp) in &self.purged_values {
+                bloom.add(value_hash);
+            }
+            bloom
+        })
     }
     /// filter values that fail the bloom filter up to max_bytes
     fn filter_crds_values(&self, crds: &Crds, filter: &mut Bloom<Hash>) -> Vec<CrdsValue> {
         let mut max_bytes = self.max_bytes as isize;
         let mut ret = vec![];
-        for v in crds.table.values() {
-            if filter.contains(&v.value_hash) {
-                continue;
-            }
-            max_bytes -= serialized_size(&v.value).unwrap() as isize;
-            if max_bytes < 0 {
-                break;
+        crds.with_values(|values| {
+            for v in values {
+                if filter.contains(&v.value_hash) {
+                    continue;
+                }
+                max_bytes -= serialized_size(&v.value).unwrap() as isize;
+                if max_bytes < 0 {
+                    break;
+                }
+                ret.push(v.value.clone());
             }
-            ret.push(v.value.clone());
-        }
+        });
         ret
     }
     /// Purge values from the crds that are older then `active_timeout`
     /// The value_hash of an active item is put into self.purged_values queue
-    pub fn purge_active(&mut self, crds: &mut Crds, self_id: &Pubkey, min_ts: u64) {
+    pub fn purge_active(&mut self, crds: &Crds, self_id: &Pubkey, min_ts: u64) {
         let old = crds.find_old_labels(min_ts);
         let mut purged: VecDeque<_> = old
             .iter()
             .filter(|label| label.pubkey() != *self_id)
             .filter_map(|label| {
-                let rv = crds
-                    .lookup_versioned(label)
-                    .map(|val| (val.value_hash, val.local_timestamp));
-                crds.remove(label);
-                rv
+                crds.remove(label)
+                    .map(|val| (val.value_hash, val.local_timestamp))
             })
             .collect();
         self.purged_values.append(&mut purged);
@@ -212,7 +231,7 @@ mod test {
 
     #[test]
     fn test_new_pull_with_stakes() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut stakes = HashMap::new();
         let node = CrdsGossipPull::default();
         let me = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
@@ -236,7 +255,7 @@ mod test {
 
     #[test]
     fn test_new_pull_request() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let entry = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         let id = entry.label().pubkey();
         let node = CrdsGossipPull::default();
@@ -261,7 +280,7 @@ mod test {
 
     #[test]
     fn test_new_mark_creation_time() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let entry = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         let node_pubkey = entry.label().pubkey();
         let mut node = CrdsGossipPull::default();
@@ -285,7 +304,7 @@ mod test {
 
     #[test]
     fn test_process_pull_request() {
-        let mut node_crds = Crds::default();
+        let node_crds = Crds::default();
         let entry = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         let node_pubkey = entry.label().pubkey();
         let node = CrdsGossipPull::default();
@@ -294,10 +313,10 @@ mod test {
         node_crds.insert(new.clone(), 0).unwrap();
         let req = node.new_pull_request(&node_crds, &node_pubkey, 0, &HashMap::new());
 
-        let mut dest_crds = Crds::default();
+        let dest_crds = Crds::default();
         let mut dest = CrdsGossipPull::default();
         let (_, filter, caller) = req.unwrap();
-        let rsp = dest.process_pull_request(&mut dest_crds, caller.clone(), filter, 1);
+        let rsp = dest.process_pull_request(&dest_crds, caller.clone(), filter, 1);
         assert!(rsp.is_empty());
         assert!(dest_crds.lookup(&caller.label()).is_some());
         assert_eq!(
@@ -317,7 +336,7 @@ mod test {
     }
     #[test]
     fn test_process_pull_request_response() {
-        let mut node_crds = Crds::default();
+        let node_crds = Crds::default();
         let entry = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         let node_pubkey = entry.label().pubkey();
         let mut node = CrdsGossipPull::default();
@@ -327,7 +346,7 @@ mod test {
         node_crds.insert(new.clone(), 0).unwrap();
 
         let mut dest = CrdsGossipPull::default();
-        let mut dest_crds = Crds::default();
+        let dest_crds = Crds::default();
         let new_id = Pubkey::new_rand();
         let new = CrdsValue::ContactInfo(ContactInfo::new_localhost(&new_id, 1));
         dest_crds.insert(new.clone(), 0).unwrap();
@@ -349,7 +368,7 @@ mod test {
             // there is a chance of a false positive with bloom filters
             let req = node.new_pull_request(&node_crds, &node_pubkey, 0, &HashMap::new());
             let (_, filter, caller) = req.unwrap();
-            let rsp = dest.process_pull_request(&mut dest_crds, caller, filter, 0);
+            let rsp = dest.process_pull_request(&dest_crds, caller, filter, 0);
             // if there is a false positive this is empty
             // prob should be around 0.1 per iteration
             if rsp.is_empty() {
@@ -357,7 +376,7 @@ mod test {
             }
 
             assert_eq!(rsp.len(), 1);
-            let failed = node.process_pull_response(&mut node_crds, &node_pubkey, rsp, 1);
+            let failed = node.process_pull_response(&node_crds, &node_pubkey, rsp, 1);
             assert_eq!(failed, 0);
             assert_eq!(
                 node_crds
@@ -381,7 +400,7 @@ mod test {
     }
     #[test]
     fn test_gossip_purge() {
-        let mut node_crds = Crds::default();
+        let node_crds = Crds::default();
         let entry = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         let node_label = entry.label();
         let node_pubkey = node_label.pubkey();
@@ -395,7 +414,7 @@ mod test {
         assert_eq!(node_crds.lookup(&node_label).unwrap().label(), node_label);
 
         // purge
-        node.purge_active(&mut node_crds, &node_pubkey, 1);
+        node.purge_active(&node_crds, &node_pubkey, 1);
 
         //verify self is still valid after purge
         assert_eq!(node_crds.lookup(&node_label).unwrap().label(), node_label);
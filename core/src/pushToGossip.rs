@@ -9,7 +9,7 @@
 //! 2. The prune set is stored in a Bloom filter.
 
 use crate::connectionInfo::ContactInfo;
-use crate::connectionInfoTable::{Crds, VersionedCrdsValue};
+use crate::connectionInfoTable::{Crds, CrdsError, VersionedCrdsValue};
 use crate::gossip::{get_stake, get_weight, CRDS_GOSSIP_BLOOM_SIZE};
 use crate::gossipErrorType::CrdsGossipError;
 use crate::propagationValue::{CrdsValue, CrdsValueLabel};
@@ -20,17 +20,27 @@ use indexmap::map::IndexMap;
 use rand;
 use rand::distributions::{Distribution, WeightedIndex};
 use rand::seq::SliceRandom;
+use rayon::prelude::*;
 use morgan_runtime::bloom::Bloom;
 use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::timing::timestamp;
 use std::cmp;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 
 pub const CRDS_GOSSIP_NUM_ACTIVE: usize = 30;
 pub const CRDS_GOSSIP_PUSH_FANOUT: usize = 6;
 pub const CRDS_GOSSIP_PUSH_MSG_TIMEOUT_MS: u64 = 5000;
 pub const CRDS_GOSSIP_PRUNE_MSG_TIMEOUT_MS: u64 = 500;
 
+/// A push message checked and, unless that failed, inserted into the table by
+/// `insert_push_messages`, to be recorded by `record_push_messages`
+pub struct InsertedPushMessage {
+    pub label: CrdsValueLabel,
+    /// the value's hash and the result of inserting it
+    result: Result<(Hash, Result<Option<VersionedCrdsValue>, CrdsError>), CrdsGossipError>,
+}
+
 #[derive(Clone)]
 pub struct CrdsGossipPush {
     /// max bytes per message
@@ -67,30 +77,107 @@ impl CrdsGossipPush {
     /// process a push message to the network
     pub fn process_push_message(
         &mut self,
-        crds: &mut Crds,
+        crds: &Crds,
         value: CrdsValue,
         now: u64,
     ) -> Result<Option<VersionedCrdsValue>, CrdsGossipError> {
+        self.process_push_messages(crds, vec![value], now)
+            .pop()
+            .unwrap()
+    }
+
+    /// process a batch of push messages, the values are hashed and inserted into `crds` in
+    /// parallel. Returns the same results as processing each value in order.
+    pub fn process_push_messages(
+        &mut self,
+        crds: &Crds,
+        values: Vec<CrdsValue>,
+        now: u64,
+    ) -> Vec<Result<Option<VersionedCrdsValue>, CrdsGossipError>> {
+        let inserted = self.insert_push_messages(crds, values, now);
+        self.record_push_messages(inserted, now)
+    }
+
+    /// The part of `process_push_messages` that doesn't change the push state: checks the
+    /// values and inserts the ones that weren't pushed before into `crds`
+    pub fn insert_push_messages(
+        &self,
+        crds: &Crds,
+        values: Vec<CrdsValue>,
+        now: u64,
+    ) -> Vec<InsertedPushMessage> {
+        let mut inserted: Vec<_> = values
+            .iter()
+            .map(|value| InsertedPushMessage {
+                label: value.label(),
+                result: self
+                    .check_wallclock(value, now)
+                    .map(|_| (Hash::default(), Ok(None))),
+            })
+            .collect();
+        let new_values: Vec<_> = values
+            .into_par_iter()
+            .zip(inserted.par_iter())
+            .filter(|(_, inserted)| inserted.result.is_ok())
+            .map(|(value, _)| VersionedCrdsValue::new(now, value))
+            .collect();
+        let indexes: Vec<_> = (0..inserted.len())
+            .filter(|i| inserted[*i].result.is_ok())
+            .collect();
+
+        let mut batch = vec![];
+        let mut batch_indexes = vec![];
+        for (i, new_value) in indexes.into_iter().zip(new_values) {
+            if self.pushed_once.get(&new_value.value_hash).is_some() {
+                inserted[i].result = Err(CrdsGossipError::PushMessagePrune);
+            } else {
+                batch_indexes.push((i, new_value.value_hash));
+                batch.push(new_value);
+            }
+        }
+
+        let results = crds.insert_versioned_batch(batch);
+        for ((i, value_hash), old) in batch_indexes.into_iter().zip(results) {
+            inserted[i].result = Ok((value_hash, old));
+        }
+        inserted
+    }
+
+    /// Queue the values `insert_push_messages` inserted to be pushed on, returns the result of
+    /// processing each of them
+    pub fn record_push_messages(
+        &mut self,
+        inserted: Vec<InsertedPushMessage>,
+        now: u64,
+    ) -> Vec<Result<Option<VersionedCrdsValue>, CrdsGossipError>> {
+        inserted
+            .into_iter()
+            .map(|InsertedPushMessage { label, result }| {
+                let (value_hash, old) = result?;
+                match old {
+                    Ok(old) => {
+                        self.push_messages.insert(label, value_hash);
+                        self.pushed_once.insert(value_hash, now);
+                        Ok(old)
+                    }
+                    // an earlier copy of the value in this batch was inserted
+                    Err(_) if self.pushed_once.get(&value_hash).is_some() => {
+                        Err(CrdsGossipError::PushMessagePrune)
+                    }
+                    Err(_) => Err(CrdsGossipError::PushMessageOldVersion),
+                }
+            })
+            .collect()
+    }
+
+    fn check_wallclock(&self, value: &CrdsValue, now: u64) -> Result<(), CrdsGossipError> {
         if now > value.wallclock() + self.msg_timeout {
             return Err(CrdsGossipError::PushMessageTimeout);
         }
         if now + self.msg_timeout < value.wallclock() {
             return Err(CrdsGossipError::PushMessageTimeout);
         }
-        let label = value.label();
-
-        let new_value = crds.new_versioned(now, value);
-        let value_hash = new_value.value_hash;
-        if self.pushed_once.get(&value_hash).is_some() {
-            return Err(CrdsGossipError::PushMessagePrune);
-        }
-        let old = crds.insert_versioned(new_value);
-        if old.is_err() {
-            return Err(CrdsGossipError::PushMessageOldVersion);
-        }
-        self.push_messages.insert(label, value_hash);
-        self.pushed_once.insert(value_hash, now);
-        Ok(old.ok().and_then(|opt| opt))
+        Ok(())
     }
 
     /// New push message to broadcast to peers.
@@ -127,15 +214,15 @@ impl CrdsGossipPush {
             if version.value_hash != *hash {
                 continue;
             }
-            let value = &version.value;
+            let value = version.value;
             if value.wallclock() > now || value.wallclock() + self.msg_timeout < now {
                 continue;
             }
-            total_bytes += serialized_size(value).unwrap() as usize;
+            total_bytes += serialized_size(&value).unwrap() as usize;
             if total_bytes > self.max_bytes {
                 break;
             }
-            values.push(value.clone());
+            values.push(value);
         }
         for v in &values {
             self.push_messages.remove(&v.label());
@@ -159,6 +246,10 @@ impl CrdsGossipPush {
 
     /// refresh the push active set
     /// * ratio - active_set.len()/ratio is the number of actives to rotate
+    ///
+    /// Peers are sampled by stake weight, divided down for every active peer already in the
+    /// same subnet, so the active set spreads over networks instead of landing in a single
+    /// data center.  Contact infos carry no ASN, so the /16 is the only topology hint.
     pub fn refresh_push_active_set(
         &mut self,
         crds: &Crds,
@@ -174,15 +265,19 @@ impl CrdsGossipPush {
         if options.is_empty() {
             return;
         }
+        let mut subnet_counts = self.active_set_subnet_counts(crds);
         while new_items.len() < need {
-            let index = WeightedIndex::new(options.iter().map(|weighted| weighted.0));
+            let index = WeightedIndex::new(
+                options
+                    .iter()
+                    .map(|(weight, info)| Self::spread_weight(*weight, info, &subnet_counts)),
+            );
             if index.is_err() {
                 break;
             }
             let index = index.unwrap();
             let index = index.sample(&mut rand::thread_rng());
-            let item = options[index].1;
-            options.remove(index);
+            let (_, item) = options.remove(index);
             if self.active_set.get(&item.id).is_some() {
                 continue;
             }
@@ -191,6 +286,9 @@ impl CrdsGossipPush {
             }
             let size = cmp::max(CRDS_GOSSIP_BLOOM_SIZE, network_size);
             let bloom = Bloom::random(size, 0.1, 1024 * 8 * 4);
+            *subnet_counts
+                .entry(gossip_subnet(&item.gossip))
+                .or_insert(0) += 1;
             new_items.insert(item.id, bloom);
         }
         let mut keys: Vec<Pubkey> = self.active_set.keys().cloned().collect();
@@ -204,26 +302,58 @@ impl CrdsGossipPush {
         }
     }
 
-    fn push_options<'a>(
+    /// Number of active peers in each subnet
+    fn active_set_subnet_counts(&self, crds: &Crds) -> HashMap<IpAddr, usize> {
+        let mut subnet_counts = HashMap::new();
+        for id in self.active_set.keys() {
+            if let Some(subnet) = crds
+                .lookup(&CrdsValueLabel::ContactInfo(*id))
+                .and_then(|value| value.contact_info().map(|info| gossip_subnet(&info.gossip)))
+            {
+                *subnet_counts.entry(subnet).or_insert(0) += 1;
+            }
+        }
+        subnet_counts
+    }
+
+    /// `weight` divided by the square of one more than the number of active peers sharing the
+    /// subnet of `info`, a peer in a fresh subnet keeps its whole stake weight
+    fn spread_weight(
+        weight: f32,
+        info: &ContactInfo,
+        subnet_counts: &HashMap<IpAddr, usize>,
+    ) -> f32 {
+        let count = subnet_counts
+            .get(&gossip_subnet(&info.gossip))
+            .cloned()
+            .unwrap_or(0);
+        let penalty = (1 + count) as f32;
+        weight / (penalty * penalty)
+    }
+
+    fn push_options(
         &self,
-        crds: &'a Crds,
+        crds: &Crds,
         self_id: &Pubkey,
         stakes: &HashMap<Pubkey, u64>,
-    ) -> Vec<(f32, &'a ContactInfo)> {
-        crds.table
-            .values()
-            .filter(|v| v.value.contact_info().is_some())
-            .map(|v| (v.value.contact_info().unwrap(), v))
-            .filter(|(info, _)| info.id != *self_id && ContactInfo::is_valid_address(&info.gossip))
-            .map(|(info, value)| {
-                let max_weight = f32::from(u16::max_value()) - 1.0;
-                let last_updated: u64 = value.local_timestamp;
-                let since = ((timestamp() - last_updated) / 1024) as u32;
-                let stake = get_stake(&info.id, stakes);
-                let weight = get_weight(max_weight, since, stake);
-                (weight, info)
-            })
-            .collect()
+    ) -> Vec<(f32, ContactInfo)> {
+        crds.with_values(|values| {
+            values
+                .filter(|v| v.value.contact_info().is_some())
+                .map(|v| (v.value.contact_info().unwrap(), v))
+                .filter(|(info, _)| {
+                    info.id != *self_id && ContactInfo::is_valid_address(&info.gossip)
+                })
+                .map(|(info, value)| {
+                    let max_weight = f32::from(u16::max_value()) - 1.0;
+                    let last_updated: u64 = value.local_timestamp;
+                    let since = ((timestamp() - last_updated) / 1024) as u32;
+                    let stake = get_stake(&info.id, stakes);
+                    let weight = get_weight(max_weight, since, stake);
+                    (weight, info.clone())
+                })
+                .collect()
+        })
     }
 
     /// purge old pending push messages
@@ -262,6 +392,20 @@ impl CrdsGossipPush {
     }
 }
 
+/// The subnet push peers are spread over, the /16 of an IPv4 address or the /32 of an IPv6 one
+fn gossip_subnet(addr: &SocketAddr) -> IpAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], 0, 0))
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            IpAddr::V6(Ipv6Addr::new(segments[0], segments[1], 0, 0, 0, 0, 0, 0))
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -269,45 +413,42 @@ mod test {
 
     #[test]
     fn test_process_push() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let value = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         let label = value.label();
         // push a new message
-        assert_eq!(
-            push.process_push_message(&mut crds, value.clone(), 0),
-            Ok(None)
-        );
-        assert_eq!(crds.lookup(&label), Some(&value));
+        assert_eq!(push.process_push_message(&crds, value.clone(), 0), Ok(None));
+        assert_eq!(crds.lookup(&label), Some(value.clone()));
 
         // push it again
         assert_eq!(
-            push.process_push_message(&mut crds, value.clone(), 0),
+            push.process_push_message(&crds, value.clone(), 0),
             Err(CrdsGossipError::PushMessagePrune)
         );
     }
     #[test]
     fn test_process_push_old_version() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let mut ci = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
         ci.wallclock = 1;
         let value = CrdsValue::ContactInfo(ci.clone());
 
         // push a new message
-        assert_eq!(push.process_push_message(&mut crds, value, 0), Ok(None));
+        assert_eq!(push.process_push_message(&crds, value, 0), Ok(None));
 
         // push an old version
         ci.wallclock = 0;
         let value = CrdsValue::ContactInfo(ci.clone());
         assert_eq!(
-            push.process_push_message(&mut crds, value, 0),
+            push.process_push_message(&crds, value, 0),
             Err(CrdsGossipError::PushMessageOldVersion)
         );
     }
     #[test]
     fn test_process_push_timeout() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let timeout = push.msg_timeout;
         let mut ci = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
@@ -316,7 +457,7 @@ mod test {
         ci.wallclock = timeout + 1;
         let value = CrdsValue::ContactInfo(ci.clone());
         assert_eq!(
-            push.process_push_message(&mut crds, value, 0),
+            push.process_push_message(&crds, value, 0),
             Err(CrdsGossipError::PushMessageTimeout)
         );
 
@@ -324,13 +465,13 @@ mod test {
         ci.wallclock = 0;
         let value = CrdsValue::ContactInfo(ci.clone());
         assert_eq!(
-            push.process_push_message(&mut crds, value, timeout + 1),
+            push.process_push_message(&crds, value, timeout + 1),
             Err(CrdsGossipError::PushMessageTimeout)
         );
     }
     #[test]
     fn test_process_push_update() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let mut ci = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
         ci.wallclock = 0;
@@ -338,7 +479,7 @@ mod test {
 
         // push a new message
         assert_eq!(
-            push.process_push_message(&mut crds, value_old.clone(), 0),
+            push.process_push_message(&crds, value_old.clone(), 0),
             Ok(None)
         );
 
@@ -346,7 +487,7 @@ mod test {
         ci.wallclock = 1;
         let value = CrdsValue::ContactInfo(ci.clone());
         assert_eq!(
-            push.process_push_message(&mut crds, value, 0)
+            push.process_push_message(&crds, value, 0)
                 .unwrap()
                 .unwrap()
                 .value,
@@ -354,6 +495,41 @@ mod test {
         );
     }
     #[test]
+    fn test_process_push_messages() {
+        let crds = Crds::default();
+        let mut push = CrdsGossipPush::default();
+        let mut ci = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+        ci.wallclock = 1;
+        let value = CrdsValue::ContactInfo(ci.clone());
+        ci.wallclock = 0;
+        let value_old = CrdsValue::ContactInfo(ci);
+        let mut late = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+        late.wallclock = push.msg_timeout + 1;
+        let other = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
+
+        // the batch gets the same results as pushing each value in order
+        let values = vec![
+            value.clone(),
+            value.clone(),
+            value_old,
+            CrdsValue::ContactInfo(late),
+            other.clone(),
+        ];
+        assert_eq!(
+            push.process_push_messages(&crds, values, 0),
+            vec![
+                Ok(None),
+                Err(CrdsGossipError::PushMessagePrune),
+                Err(CrdsGossipError::PushMessageOldVersion),
+                Err(CrdsGossipError::PushMessageTimeout),
+                Ok(None),
+            ]
+        );
+        assert_eq!(crds.lookup(&value.label()), Some(value.clone()));
+        assert_eq!(crds.lookup(&other.label()), Some(other.clone()));
+        assert_eq!(push.num_pending(), 2);
+    }
+    #[test]
     fn test_compute_need() {
         assert_eq!(CrdsGossipPush::compute_need(30, 0, 10), 30);
         assert_eq!(CrdsGossipPush::compute_need(30, 1, 10), 29);
@@ -363,7 +539,7 @@ mod test {
     #[test]
     fn test_refresh_active_set() {
         morgan_logger::setup();
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let value1 = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
 
@@ -392,7 +568,7 @@ mod test {
     #[test]
     fn test_active_set_refresh_with_bank() {
         let time = timestamp() - 1024; //make sure there's at least a 1 second delay
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let push = CrdsGossipPush::default();
         let mut stakes = HashMap::new();
         for i in 1..=100 {
@@ -412,8 +588,69 @@ mod test {
         );
     }
     #[test]
+    fn test_gossip_subnet() {
+        assert_eq!(
+            gossip_subnet(&socketaddr!([10, 1, 2, 3], 8000)),
+            gossip_subnet(&socketaddr!([10, 1, 200, 3], 8001))
+        );
+        assert_ne!(
+            gossip_subnet(&socketaddr!([10, 1, 2, 3], 8000)),
+            gossip_subnet(&socketaddr!([10, 2, 2, 3], 8000))
+        );
+    }
+    #[test]
+    fn test_refresh_active_set_subnet_spread() {
+        let crds = Crds::default();
+        let mut push = CrdsGossipPush::default();
+        push.num_active = 4;
+        let mut stakes = HashMap::new();
+        let mut insert_peer = |ip: [u8; 4], stake: u64| {
+            let mut info = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+            info.gossip = socketaddr!(ip, 8000);
+            stakes.insert(info.id, stake);
+            crds.insert(CrdsValue::ContactInfo(info), 0).unwrap();
+        };
+        // a crowded data center and a few lone peers in other networks
+        for i in 0..20 {
+            insert_peer([10, 1, 0, i], 100);
+        }
+        for i in 2..6 {
+            insert_peer([10, i, 0, 1], 100);
+        }
+
+        let crowded = gossip_subnet(&socketaddr!([10, 1, 0, 0], 0));
+        let mut spread = 0;
+        for _ in 0..100 {
+            push.active_set.clear();
+            push.refresh_push_active_set(&crds, &stakes, &Pubkey::default(), 1, 1);
+            assert_eq!(push.active_set.len(), push.num_active);
+            spread += push
+                .active_set
+                .keys()
+                .filter_map(|id| crds.lookup(&CrdsValueLabel::ContactInfo(*id)))
+                .filter(|value| gossip_subnet(&value.contact_info().unwrap().gossip) != crowded)
+                .count();
+        }
+        // sampling by stake alone would pick the lone peers 1 time in 6
+        assert!(spread > 100 * push.num_active / 3);
+    }
+    #[test]
+    fn test_spread_weight() {
+        let info = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
+        let mut subnet_counts = HashMap::new();
+        assert_eq!(
+            CrdsGossipPush::spread_weight(8.0, &info, &subnet_counts),
+            8.0
+        );
+        subnet_counts.insert(gossip_subnet(&info.gossip), 1);
+        assert_eq!(
+            CrdsGossipPush::spread_weight(8.0, &info, &subnet_counts),
+            2.0
+        );
+    }
+    #[test]
     fn test_new_push_messages() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let peer = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         assert_eq!(crds.insert(peer.clone(), 0), Ok(None));
@@ -421,7 +658,7 @@ mod test {
 
         let new_msg = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         assert_eq!(
-            push.process_push_message(&mut crds, new_msg.clone(), 0),
+            push.process_push_message(&crds, new_msg.clone(), 0),
             Ok(None)
         );
         assert_eq!(push.active_set.len(), 1);
@@ -432,7 +669,7 @@ mod test {
     }
     #[test]
     fn test_process_prune() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let peer = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         assert_eq!(crds.insert(peer.clone(), 0), Ok(None));
@@ -440,7 +677,7 @@ mod test {
 
         let new_msg = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         assert_eq!(
-            push.process_push_message(&mut crds, new_msg.clone(), 0),
+            push.process_push_message(&crds, new_msg.clone(), 0),
             Ok(None)
         );
         push.process_prune_msg(&peer.label().pubkey(), &[new_msg.label().pubkey()]);
@@ -451,7 +688,7 @@ mod test {
     }
     #[test]
     fn test_purge_old_pending_push_messages() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let peer = CrdsValue::ContactInfo(ContactInfo::new_localhost(&Pubkey::new_rand(), 0));
         assert_eq!(crds.insert(peer.clone(), 0), Ok(None));
@@ -461,7 +698,7 @@ mod test {
         ci.wallclock = 1;
         let new_msg = CrdsValue::ContactInfo(ci.clone());
         assert_eq!(
-            push.process_push_message(&mut crds, new_msg.clone(), 1),
+            push.process_push_message(&crds, new_msg.clone(), 1),
             Ok(None)
         );
         push.purge_old_pending_push_messages(&crds, 0);
@@ -473,22 +710,19 @@ mod test {
 
     #[test]
     fn test_purge_old_pushed_once_messages() {
-        let mut crds = Crds::default();
+        let crds = Crds::default();
         let mut push = CrdsGossipPush::default();
         let mut ci = ContactInfo::new_localhost(&Pubkey::new_rand(), 0);
         ci.wallclock = 0;
         let value = CrdsValue::ContactInfo(ci.clone());
         let label = value.label();
         // push a new message
-        assert_eq!(
-            push.process_push_message(&mut crds, value.clone(), 0),
-            Ok(None)
-        );
-        assert_eq!(crds.lookup(&label), Some(&value));
+        assert_eq!(push.process_push_message(&crds, value.clone(), 0), Ok(None));
+        assert_eq!(crds.lookup(&label), Some(value.clone()));
 
         // push it again
         assert_eq!(
-            push.process_push_message(&mut crds, value.clone(), 0),
+            push.process_push_message(&crds, value.clone(), 0),
             Err(CrdsGossipError::PushMessagePrune)
         );
 
@@ -497,7 +731,7 @@ mod test {
 
         // push it again
         assert_eq!(
-            push.process_push_message(&mut crds, value.clone(), 0),
+            push.process_push_message(&crds, value.clone(), 0),
             Err(CrdsGossipError::PushMessageOldVersion)
         );
     }
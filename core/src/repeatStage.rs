@@ -5,24 +5,27 @@ use crate::treasuryForks::BankForks;
 use crate::blockBufferPool::Blocktree;
 use crate::blockBufferPoolProcessor;
 use crate::clusterMessage::ClusterInfo;
+use crate::ClusterVoteMessageListener::VoteTracker;
 use crate::entryInfo::{Entry, EntrySlice};
 use crate::leaderArrangeCache::LeaderScheduleCache;
 use crate::leaderArrangeUtils;
 use crate::forkSelection::{Locktower, StakeLockout};
 use crate::packet::BlobError;
 use crate::waterClockRecorder::PohRecorder;
+use crate::replayErrors::{ForkReplayErrors, ReplayErrors};
 use crate::result::{Error, Result};
 use crate::rpcSubscriptions::RpcSubscriptions;
 use crate::service::Service;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use morgan_metricbot::{datapoint_warn, inc_new_counter_error, inc_new_counter_info};
 use morgan_runtime::bank::Bank;
 use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::KeypairUtil;
 use morgan_interface::timing::{self, duration_as_ms};
-use morgan_interface::transaction::Transaction;
+use morgan_interface::transaction::{Transaction, TransactionError};
 use morgan_vote_api::vote_instruction;
+use std::result;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex, RwLock};
@@ -34,6 +37,9 @@ use chrono::prelude::*;
 
 pub const MAX_ENTRY_RECV_PER_ITER: usize = 512;
 
+/// How many ticks before our leader slot starts its bank is created
+pub const DEFAULT_LEADER_WARMUP_TICKS: u64 = 2;
+
 // Implement a destructor for the ReplayStage thread to signal it exited
 // even on panics
 struct Finalizer {
@@ -60,14 +66,61 @@ pub struct ReplayStage {
 struct ForkProgress {
     last_entry: Hash,
     num_blobs: usize,
+    num_entries: usize,
     started_ms: u64,
+    /// set once gossip votes show a supermajority confirmed this slot or a descendant of it,
+    /// from then on transaction signatures are not verified during replay
+    cluster_confirmed: bool,
+    /// set once the slot replayed to another hash than the one the cluster confirmed, the
+    /// slot and its descendants are never voted on
+    is_dead: bool,
+    errors: ForkReplayErrors,
 }
 impl ForkProgress {
     pub fn new(last_entry: Hash) -> Self {
         Self {
             last_entry,
             num_blobs: 0,
+            num_entries: 0,
             started_ms: timing::timestamp(),
+            cluster_confirmed: false,
+            is_dead: false,
+            errors: ForkReplayErrors::default(),
+        }
+    }
+}
+
+/// Why replaying a batch of entries failed, with the index in the batch of the entry that did
+#[derive(Debug, PartialEq)]
+enum ReplayFailure {
+    EntryVerification(usize),
+    SignatureVerification(usize),
+    Transaction(usize, TransactionError),
+}
+
+impl ReplayFailure {
+    fn record(&self, first_entry: usize, errors: &mut ForkReplayErrors) {
+        match self {
+            ReplayFailure::EntryVerification(index) => {
+                errors.record_entry_verification_failure((first_entry + index) as u64)
+            }
+            ReplayFailure::SignatureVerification(index) => {
+                errors.record_signature_verification_failure((first_entry + index) as u64)
+            }
+            ReplayFailure::Transaction(index, err) => {
+                errors.record_transaction_error((first_entry + index) as u64, err)
+            }
+        }
+    }
+}
+
+impl From<ReplayFailure> for Error {
+    fn from(failure: ReplayFailure) -> Self {
+        match failure {
+            ReplayFailure::EntryVerification(_) | ReplayFailure::SignatureVerification(_) => {
+                Error::BlobError(BlobError::VerificationFailed)
+            }
+            ReplayFailure::Transaction(_, err) => Error::TransactionError(err),
         }
     }
 }
@@ -86,6 +139,9 @@ impl ReplayStage {
         subscriptions: &Arc<RpcSubscriptions>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         leader_schedule_cache: &Arc<LeaderScheduleCache>,
+        vote_tracker: &Arc<RwLock<VoteTracker>>,
+        replay_errors: &Arc<ReplayErrors>,
+        leader_warmup_ticks: u64,
     ) -> (Self, Receiver<(u64, Pubkey)>, Receiver<Vec<u64>>)
     where
         T: 'static + KeypairUtil + Send + Sync,
@@ -104,11 +160,15 @@ impl ReplayStage {
         let leader_schedule_cache = leader_schedule_cache.clone();
         let vote_account = *vote_account;
         let voting_keypair = voting_keypair.cloned();
+        let vote_tracker = vote_tracker.clone();
+        let replay_errors = replay_errors.clone();
         let t_replay = Builder::new()
             .name("morgan-replay-stage".to_string())
             .spawn(move || {
                 let _exit = Finalizer::new(exit_.clone());
                 let mut progress = HashMap::new();
+                // bank of our next leader slot, created before the slot starts
+                let mut warm_bank = None;
                 loop {
                     let now = Instant::now();
                     // Stop getting entries if we get exit signal
@@ -131,6 +191,8 @@ impl ReplayStage {
                         &mut ticks_per_slot,
                         &mut progress,
                         &slot_full_sender,
+                        &vote_tracker,
+                        &replay_errors,
                     )?;
 
                     if ticks_per_slot == 0 {
@@ -156,6 +218,8 @@ impl ReplayStage {
                             &blocktree,
                             &leader_schedule_cache,
                             &root_slot_sender,
+                            &vote_tracker,
+                            &replay_errors,
                         )?;
 
                         Self::reset_poh_recorder(
@@ -193,7 +257,18 @@ impl ReplayStage {
                             reached_leader_tick,
                             grace_ticks,
                             &leader_schedule_cache,
+                            &mut warm_bank,
                         );
+                        if !reached_leader_tick {
+                            Self::warm_up_leader_bank(
+                                &my_pubkey,
+                                &bank_forks,
+                                &poh_recorder,
+                                &leader_schedule_cache,
+                                leader_warmup_ticks,
+                                &mut warm_bank,
+                            );
+                        }
                     }
 
                     inc_new_counter_info!(
@@ -213,6 +288,7 @@ impl ReplayStage {
             .unwrap();
         (Self { t_replay }, slot_full_receiver, root_slot_receiver)
     }
+    #[allow(clippy::too_many_arguments)]
     pub fn start_leader(
         my_pubkey: &Pubkey,
         bank_forks: &Arc<RwLock<BankForks>>,
@@ -222,6 +298,7 @@ impl ReplayStage {
         reached_leader_tick: bool,
         grace_ticks: u64,
         leader_schedule_cache: &Arc<LeaderScheduleCache>,
+        warm_bank: &mut Option<Bank>,
     ) {
         trace!("{} checking poh slot {}", my_pubkey, poh_slot);
         if bank_forks.read().unwrap().get(poh_slot).is_none() {
@@ -247,7 +324,16 @@ impl ReplayStage {
                             "replay_stage-new_leader",
                             ("count", poh_slot, i64),
                             ("grace", grace_ticks, i64));
-                        let tpu_bank = Bank::new_from_parent(&parent, my_pubkey, poh_slot);
+                        let tpu_bank = match warm_bank
+                            .take()
+                            .filter(|bank| Self::is_warm_bank_for(bank, poh_slot, parent_slot))
+                        {
+                            Some(bank) => {
+                                inc_new_counter_info!("replay_stage-warm_leader_bank_used", 1);
+                                bank
+                            }
+                            None => Bank::new_from_parent(&parent, my_pubkey, poh_slot),
+                        };
                         bank_forks.write().unwrap().insert(tpu_bank);
                         if let Some(tpu_bank) = bank_forks.read().unwrap().get(poh_slot).cloned() {
                             assert_eq!(
@@ -277,14 +363,90 @@ impl ReplayStage {
                 });
         }
     }
+    /// Create the bank of our next leader slot once poh is within `leader_warmup_ticks` of it,
+    /// so `start_leader` only has to hand it to poh when the slot starts
+    fn warm_up_leader_bank(
+        my_pubkey: &Pubkey,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        poh_recorder: &Arc<Mutex<PohRecorder>>,
+        leader_schedule_cache: &Arc<LeaderScheduleCache>,
+        leader_warmup_ticks: u64,
+        warm_bank: &mut Option<Bank>,
+    ) {
+        if leader_warmup_ticks == 0 {
+            return;
+        }
+        let (leader_slot, parent_slot) = {
+            let poh = poh_recorder.lock().unwrap();
+            match poh.next_leader_slot() {
+                Some(slot) if poh.would_be_leader(leader_warmup_ticks) => (slot, poh.start_slot()),
+                _ => return,
+            }
+        };
+        if let Some(bank) = warm_bank {
+            if Self::is_warm_bank_for(bank, leader_slot, parent_slot) {
+                return;
+            }
+        }
+
+        let parent = {
+            let r_bank_forks = bank_forks.read().unwrap();
+            if r_bank_forks.get(leader_slot).is_some() {
+                return;
+            }
+            match r_bank_forks.get(parent_slot) {
+                Some(parent) if parent.is_frozen() => parent.clone(),
+                _ => return,
+            }
+        };
+        if leader_schedule_cache.slot_leader_at(leader_slot, Some(&parent)) != Some(*my_pubkey) {
+            return;
+        }
+        debug!(
+            "{} warming up bank for leader slot {} on parent {}",
+            my_pubkey, leader_slot, parent_slot
+        );
+        // A stale warm bank for the same slot purges that slot's fork when it drops, so it has to
+        // be gone before the new bank creates its storage
+        drop(warm_bank.take());
+        *warm_bank = Some(Bank::new_from_parent(&parent, my_pubkey, leader_slot));
+    }
+
+    /// A bank warmed up for another slot or parent was made before poh was reset and is stale
+    fn is_warm_bank_for(bank: &Bank, slot: u64, parent_slot: u64) -> bool {
+        bank.slot() == slot && bank.parent().map(|parent| parent.slot()) == Some(parent_slot)
+    }
+
     fn replay_blocktree_into_bank(
         bank: &Bank,
         blocktree: &Blocktree,
         progress: &mut HashMap<u64, ForkProgress>,
+        vote_tracker: &RwLock<VoteTracker>,
+        replay_errors: &ReplayErrors,
     ) -> Result<()> {
         let (entries, num) = Self::load_blocktree_entries(bank, blocktree, progress)?;
         let len = entries.len();
-        let result = Self::replay_entries_into_bank(bank, entries, progress, num);
+        let bank_progress = progress
+            .entry(bank.slot())
+            .or_insert_with(|| ForkProgress::new(bank.last_blockhash()));
+        if !bank_progress.cluster_confirmed
+            && Self::is_cluster_confirmed(bank, blocktree, &vote_tracker.read().unwrap())
+        {
+            debug!("slot {} confirmed by gossip votes", bank.slot());
+            bank_progress.cluster_confirmed = true;
+        }
+        let verify_tx_signatures = !bank_progress.cluster_confirmed;
+        if !verify_tx_signatures {
+            inc_new_counter_info!("replicate-stage_skipped_sigverify_entries", len);
+        }
+        let result = Self::replay_entries_into_bank(
+            bank,
+            entries,
+            progress,
+            num,
+            verify_tx_signatures,
+            replay_errors,
+        );
         if result.is_ok() {
             trace!("verified entries {}", len);
             inc_new_counter_info!("replicate-stage_process_entries", len);
@@ -314,6 +476,8 @@ impl ReplayStage {
         blocktree: &Arc<Blocktree>,
         leader_schedule_cache: &Arc<LeaderScheduleCache>,
         root_slot_sender: &Sender<Vec<u64>>,
+        vote_tracker: &RwLock<VoteTracker>,
+        replay_errors: &ReplayErrors,
     ) -> Result<()>
     where
         T: 'static + KeypairUtil + Send + Sync,
@@ -342,6 +506,8 @@ impl ReplayStage {
             leader_schedule_cache.set_root(new_root);
             bank_forks.write().unwrap().set_root(new_root);
             Self::handle_new_root(&bank_forks, progress);
+            vote_tracker.write().unwrap().set_root(new_root);
+            replay_errors.set_root(new_root);
             root_slot_sender.send(rooted_slots)?;
         }
         locktower.update_epoch(&bank);
@@ -397,6 +563,8 @@ impl ReplayStage {
         ticks_per_slot: &mut u64,
         progress: &mut HashMap<u64, ForkProgress>,
         slot_full_sender: &Sender<(u64, Pubkey)>,
+        vote_tracker: &RwLock<VoteTracker>,
+        replay_errors: &ReplayErrors,
     ) -> Result<()> {
         let active_banks = bank_forks.read().unwrap().active_banks();
         trace!("active banks {:?}", active_banks);
@@ -405,16 +573,89 @@ impl ReplayStage {
             let bank = bank_forks.read().unwrap().get(*bank_slot).unwrap().clone();
             *ticks_per_slot = bank.ticks_per_slot();
             if bank.collector_id() != *my_pubkey {
-                Self::replay_blocktree_into_bank(&bank, &blocktree, progress)?;
+                Self::replay_blocktree_into_bank(
+                    &bank,
+                    &blocktree,
+                    progress,
+                    vote_tracker,
+                    replay_errors,
+                )?;
             }
             let max_tick_height = (*bank_slot + 1) * bank.ticks_per_slot() - 1;
             if bank.tick_height() == max_tick_height {
-                Self::process_completed_bank(my_pubkey, bank, slot_full_sender);
+                Self::process_completed_bank(my_pubkey, bank.clone(), slot_full_sender);
+                if let Some(bank_progress) = progress.get_mut(bank_slot) {
+                    if bank_progress.cluster_confirmed
+                        && !Self::check_cluster_confirmed_hash(&bank, &vote_tracker.read().unwrap())
+                    {
+                        bank_progress.is_dead = true;
+                    }
+                }
             }
         }
         Ok(())
     }
 
+    /// True if a supermajority voted in gossip for `bank`'s slot, or for a slot that chains
+    /// to it in the blocktree
+    fn is_cluster_confirmed(
+        bank: &Bank,
+        blocktree: &Blocktree,
+        vote_tracker: &VoteTracker,
+    ) -> bool {
+        vote_tracker
+            .confirmed_slots(bank.slot(), bank)
+            .into_iter()
+            .any(|confirmed_slot| Self::is_ancestor_or_self(blocktree, bank.slot(), confirmed_slot))
+    }
+
+    fn is_ancestor_or_self(blocktree: &Blocktree, slot: u64, mut descendant: u64) -> bool {
+        while descendant > slot {
+            descendant = match blocktree.meta(descendant) {
+                Ok(Some(meta)) if meta.parent_slot < descendant => meta.parent_slot,
+                _ => return false,
+            };
+        }
+        descendant == slot
+    }
+
+    /// A slot replayed without signature verification must hash to what the cluster voted for.
+    /// Returns false if it doesn't
+    fn check_cluster_confirmed_hash(bank: &Bank, vote_tracker: &VoteTracker) -> bool {
+        if let Some(confirmed_hash) = vote_tracker.confirmed_hash(bank.slot(), bank) {
+            if confirmed_hash != bank.hash() {
+                inc_new_counter_error!("replicate-stage_cluster_confirmed_hash_mismatch", 1);
+                println!(
+                    "{}",
+                    Warn(
+                        format!(
+                            "slot {} replayed to {} but the cluster confirmed {}",
+                            bank.slot(),
+                            bank.hash(),
+                            confirmed_hash
+                        ),
+                        module_path!().to_string()
+                    )
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    /// True if `slot` or one of its ancestors was marked dead
+    fn is_dead_fork(
+        slot: u64,
+        ancestors: &HashMap<u64, HashSet<u64>>,
+        progress: &HashMap<u64, ForkProgress>,
+    ) -> bool {
+        let is_dead = |slot: &u64| progress.get(slot).map_or(false, |p| p.is_dead);
+        is_dead(&slot)
+            || ancestors
+                .get(&slot)
+                .map_or(false, |a| a.iter().any(is_dead))
+    }
+
     fn generate_votable_banks(
         bank_forks: &Arc<RwLock<BankForks>>,
         locktower: &Locktower,
@@ -426,6 +667,12 @@ impl ReplayStage {
         let ancestors = bank_forks.read().unwrap().ancestors();
         let frozen_banks = bank_forks.read().unwrap().frozen_banks();
 
+        let dead_slots: HashSet<u64> = frozen_banks
+            .keys()
+            .filter(|slot| Self::is_dead_fork(**slot, &ancestors, progress))
+            .cloned()
+            .collect();
+
         trace!("frozen_banks {}", frozen_banks.len());
         let mut votable: Vec<(u128, Arc<Bank>)> = frozen_banks
             .values()
@@ -434,6 +681,11 @@ impl ReplayStage {
                 trace!("bank is votable: {} {}", b.slot(), is_votable);
                 is_votable
             })
+            .filter(|b| {
+                let is_dead = dead_slots.contains(&b.slot());
+                trace!("bank is dead: {} {}", b.slot(), is_dead);
+                !is_dead
+            })
             .filter(|b| {
                 let is_recent_epoch = locktower.is_recent_epoch(b);
                 trace!("bank is is_recent_epoch: {} {}", b.slot(), is_recent_epoch);
@@ -516,7 +768,9 @@ impl ReplayStage {
     ) {
         progress.retain(|slot, prog| {
             let duration = timing::timestamp() - prog.started_ms;
-            if locktower.is_slot_confirmed(*slot, stake_lockouts)
+            // dead slots are remembered until they are pruned from the bank forks
+            if !prog.is_dead
+                && locktower.is_slot_confirmed(*slot, stake_lockouts)
                 && bank_forks
                     .read()
                     .unwrap()
@@ -562,23 +816,46 @@ impl ReplayStage {
         entries: Vec<Entry>,
         progress: &mut HashMap<u64, ForkProgress>,
         num: usize,
+        verify_tx_signatures: bool,
+        replay_errors: &ReplayErrors,
     ) -> Result<()> {
         let bank_progress = &mut progress
             .entry(bank.slot())
             .or_insert(ForkProgress::new(bank.last_blockhash()));
-        let result = Self::verify_and_process_entries(&bank, &entries, &bank_progress.last_entry);
+        let result = Self::replay_entries(
+            &bank,
+            &entries,
+            &bank_progress.last_entry,
+            verify_tx_signatures,
+        );
+        if let Err(failure) = &result {
+            failure.record(bank_progress.num_entries, &mut bank_progress.errors);
+            replay_errors.update(bank.slot(), &bank_progress.errors);
+        }
         bank_progress.num_blobs += num;
+        bank_progress.num_entries += entries.len();
         if let Some(last_entry) = entries.last() {
             bank_progress.last_entry = last_entry.hash;
         }
-        result
+        result.map_err(Error::from)
     }
 
     pub fn verify_and_process_entries(
         bank: &Bank,
         entries: &[Entry],
         last_entry: &Hash,
+        verify_tx_signatures: bool,
     ) -> Result<()> {
+        Self::replay_entries(bank, entries, last_entry, verify_tx_signatures)?;
+        Ok(())
+    }
+
+    fn replay_entries(
+        bank: &Bank,
+        entries: &[Entry],
+        last_entry: &Hash,
+        verify_tx_signatures: bool,
+    ) -> result::Result<(), ReplayFailure> {
         if !entries.verify(last_entry) {
             trace!(
                 "entry verification failed {} {} {} {}",
@@ -587,11 +864,30 @@ impl ReplayStage {
                 last_entry,
                 bank.last_blockhash()
             );
-            return Err(Error::BlobError(BlobError::VerificationFailed));
+            let mut previous = *last_entry;
+            let index = entries.iter().position(|entry| {
+                let verified = entry.verify(&previous);
+                previous = entry.hash;
+                !verified
+            });
+            return Err(ReplayFailure::EntryVerification(index.unwrap_or(0)));
         }
-        blockBufferPoolProcessor::process_entries(bank, entries)?;
-
-        Ok(())
+        if verify_tx_signatures && !entries.verify_tx_signatures() {
+            trace!(
+                "transaction signature verification failed {} {}",
+                entries.len(),
+                bank.slot()
+            );
+            let index = entries.iter().position(|entry| {
+                !entry
+                    .transactions
+                    .iter()
+                    .all(Transaction::verify_signatures)
+            });
+            return Err(ReplayFailure::SignatureVerification(index.unwrap_or(0)));
+        }
+        blockBufferPoolProcessor::process_entries_find_failed(bank, entries)
+            .map_err(|(index, err)| ReplayFailure::Transaction(index, err))
     }
 
     fn handle_new_root(
@@ -672,10 +968,19 @@ impl Service for ReplayStage {
 mod test {
     use super::*;
     use crate::blockBufferPool::get_tmp_ledger_path;
-    use crate::genesisUtils::create_genesis_block;
+    use crate::connectionInfo::ContactInfo;
+    use crate::entryInfo::next_entry;
+    use crate::genesisUtils::{
+        create_genesis_block, create_genesis_block_with_leader, GenesisBlockInfo,
+        BOOTSTRAP_LEADER_DIFS,
+    };
     use crate::packet::Blob;
+    use morgan_interface::signature::Keypair;
+    use morgan_interface::system_transaction;
     use crate::repeatStage::ReplayStage;
     use morgan_interface::hash::Hash;
+    use morgan_interface::poh_config::PohConfig;
+    use morgan_vote_api::vote_state::Vote;
     use std::fs::remove_dir_all;
     use std::sync::{Arc, RwLock};
 
@@ -724,6 +1029,80 @@ mod test {
         let _ignored = remove_dir_all(&ledger_path);
     }
 
+    #[test]
+    fn test_warm_up_leader_bank() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+            let my_pubkey = Pubkey::new_rand();
+            let genesis_block =
+                create_genesis_block_with_leader(10_000, &my_pubkey, BOOTSTRAP_LEADER_DIFS)
+                    .genesis_block;
+            let bank0 = Bank::new(&genesis_block);
+            let ticks_per_slot = bank0.ticks_per_slot();
+            let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank0));
+            let bank_forks = Arc::new(RwLock::new(BankForks::new(0, bank0)));
+            let bank0 = bank_forks.read().unwrap().working_bank();
+            bank0.freeze();
+            let (poh_recorder, _entry_receiver) = PohRecorder::new(
+                bank0.tick_height(),
+                bank0.last_blockhash(),
+                0,
+                Some(1),
+                ticks_per_slot,
+                &my_pubkey,
+                &blocktree,
+                &leader_schedule_cache,
+                &Arc::new(PohConfig::default()),
+            );
+            let poh_recorder = Arc::new(Mutex::new(poh_recorder));
+            let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                ContactInfo::new_localhost(&my_pubkey, 0),
+            )));
+            let warm_up = |warm_bank: &mut Option<Bank>| {
+                ReplayStage::warm_up_leader_bank(
+                    &my_pubkey,
+                    &bank_forks,
+                    &poh_recorder,
+                    &leader_schedule_cache,
+                    2,
+                    warm_bank,
+                )
+            };
+
+            // slot 1 is still more than 2 ticks away
+            let mut warm_bank = None;
+            warm_up(&mut warm_bank);
+            assert!(warm_bank.is_none());
+
+            for _ in 0..ticks_per_slot - 2 {
+                poh_recorder.lock().unwrap().tick();
+            }
+            warm_up(&mut warm_bank);
+            let bank1 = warm_bank.as_ref().unwrap();
+            assert_eq!(bank1.slot(), 1);
+            assert_eq!(bank1.parent().unwrap().slot(), 0);
+            assert!(bank_forks.read().unwrap().get(1).is_none());
+
+            // the warmed up bank becomes the working bank when the slot starts
+            ReplayStage::start_leader(
+                &my_pubkey,
+                &bank_forks,
+                &poh_recorder,
+                &cluster_info,
+                1,
+                true,
+                0,
+                &leader_schedule_cache,
+                &mut warm_bank,
+            );
+            assert!(warm_bank.is_none());
+            assert_eq!(poh_recorder.lock().unwrap().bank().unwrap().slot(), 1);
+            assert!(bank_forks.read().unwrap().get(1).is_some());
+        }
+        let _ignored = remove_dir_all(&ledger_path);
+    }
+
     #[test]
     fn test_handle_new_root() {
         let genesis_block = create_genesis_block(10_000).genesis_block;
@@ -734,4 +1113,168 @@ mod test {
         ReplayStage::handle_new_root(&bank_forks, &mut progress);
         assert!(progress.is_empty());
     }
+
+    #[test]
+    fn test_verify_and_process_entries_tx_signatures() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let blockhash = bank.last_blockhash();
+        let mut tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_rand(), 1, blockhash);
+        tx.signatures[0] = Keypair::new().sign_message(&tx.message_data());
+        let entries = vec![next_entry(&blockhash, 1, vec![tx])];
+
+        assert_matches!(
+            ReplayStage::verify_and_process_entries(&bank, &entries, &blockhash, true),
+            Err(Error::BlobError(BlobError::VerificationFailed))
+        );
+        // cluster-confirmed slots only check PoH
+        assert_matches!(
+            ReplayStage::verify_and_process_entries(&bank, &entries, &blockhash, false),
+            Ok(())
+        );
+        assert_matches!(
+            ReplayStage::verify_and_process_entries(&bank, &entries, &Hash::default(), false),
+            Err(Error::BlobError(BlobError::VerificationFailed))
+        );
+    }
+
+    #[test]
+    fn test_replay_entries_into_bank_records_errors() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let blockhash = bank.last_blockhash();
+        let replay_errors = ReplayErrors::default();
+        let mut progress = HashMap::new();
+
+        let tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_rand(), 1, blockhash);
+        let entry = next_entry(&blockhash, 1, vec![tx]);
+        assert_matches!(
+            ReplayStage::replay_entries_into_bank(
+                &bank,
+                vec![entry.clone()],
+                &mut progress,
+                1,
+                true,
+                &replay_errors,
+            ),
+            Ok(())
+        );
+        assert_eq!(replay_errors.get(bank.slot()), None);
+
+        // the second entry of this batch, the slot's third, spends from an empty account
+        let tick = next_entry(&entry.hash, 1, vec![]);
+        let tx = system_transaction::transfer(&Keypair::new(), &Pubkey::new_rand(), 1, blockhash);
+        let entry = next_entry(&tick.hash, 1, vec![tx]);
+        assert_matches!(
+            ReplayStage::replay_entries_into_bank(
+                &bank,
+                vec![tick, entry],
+                &mut progress,
+                1,
+                true,
+                &replay_errors,
+            ),
+            Err(Error::TransactionError(TransactionError::AccountNotFound))
+        );
+        let errors = replay_errors.get(bank.slot()).unwrap();
+        assert_eq!(errors.transaction_errors["AccountNotFound"], 1);
+        assert_eq!(errors.failed_entry, Some(2));
+
+        let entry = next_entry(&Hash::default(), 1, vec![]);
+        assert_matches!(
+            ReplayStage::replay_entries_into_bank(
+                &bank,
+                vec![entry],
+                &mut progress,
+                1,
+                true,
+                &replay_errors,
+            ),
+            Err(Error::BlobError(BlobError::VerificationFailed))
+        );
+        let errors = replay_errors.get(bank.slot()).unwrap();
+        assert_eq!(errors.entry_verification_failures, 1);
+        assert_eq!(errors.failed_entry, Some(3));
+        assert_eq!(progress[&bank.slot()].errors, errors);
+    }
+
+    #[test]
+    fn test_cluster_confirmed_hash_mismatch_kills_fork() {
+        let GenesisBlockInfo {
+            genesis_block,
+            voting_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        bank1.freeze();
+
+        let vote_tx = |hash| {
+            let node_keypair = Keypair::new();
+            let vote_ix = vote_instruction::vote(
+                &node_keypair.pubkey(),
+                &voting_keypair.pubkey(),
+                &voting_keypair.pubkey(),
+                vec![Vote::new(1, hash)],
+            );
+            let mut vote_tx = Transaction::new_unsigned_instructions(vec![vote_ix]);
+            vote_tx.partial_sign(&[&node_keypair, &voting_keypair], Hash::default());
+            vote_tx
+        };
+        let mut vote_tracker = VoteTracker::default();
+        vote_tracker.record_vote_transaction(&vote_tx(bank1.hash()));
+        assert!(ReplayStage::check_cluster_confirmed_hash(
+            &bank1,
+            &vote_tracker
+        ));
+        let mut vote_tracker = VoteTracker::default();
+        vote_tracker.record_vote_transaction(&vote_tx(Hash::new(&[1; 32])));
+        assert!(!ReplayStage::check_cluster_confirmed_hash(
+            &bank1,
+            &vote_tracker
+        ));
+
+        // a dead slot takes its descendants with it, but not its ancestors
+        let mut progress = HashMap::new();
+        progress.insert(1, ForkProgress::new(Hash::default()));
+        progress.get_mut(&1).unwrap().is_dead = true;
+        let mut ancestors = HashMap::new();
+        ancestors.insert(0, HashSet::new());
+        ancestors.insert(1, vec![0].into_iter().collect());
+        ancestors.insert(2, vec![0, 1].into_iter().collect());
+        ancestors.insert(3, vec![0].into_iter().collect());
+        assert!(!ReplayStage::is_dead_fork(0, &ancestors, &progress));
+        assert!(ReplayStage::is_dead_fork(1, &ancestors, &progress));
+        assert!(ReplayStage::is_dead_fork(2, &ancestors, &progress));
+        assert!(!ReplayStage::is_dead_fork(3, &ancestors, &progress));
+    }
+
+    #[test]
+    fn test_is_ancestor_or_self() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&ledger_path).unwrap();
+            // 0 -> 1 -> 2, 0 -> 3
+            for (slot, parent) in &[(1, 0), (2, 1), (3, 0)] {
+                let mut blob = Blob::default();
+                blob.set_slot(*slot);
+                blob.set_parent(*parent);
+                blocktree.insert_data_blobs(&vec![blob]).unwrap();
+            }
+            assert!(ReplayStage::is_ancestor_or_self(&blocktree, 1, 2));
+            assert!(ReplayStage::is_ancestor_or_self(&blocktree, 2, 2));
+            assert!(!ReplayStage::is_ancestor_or_self(&blocktree, 1, 3));
+            assert!(!ReplayStage::is_ancestor_or_self(&blocktree, 2, 1));
+            assert!(!ReplayStage::is_ancestor_or_self(&blocktree, 1, 4));
+        }
+        let _ignored = remove_dir_all(&ledger_path);
+    }
 }
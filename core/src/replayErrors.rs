@@ -0,0 +1,130 @@
+//! The `replayErrors` module counts what went wrong replaying each fork, so an operator can
+//! see why a fork failed instead of digging through the logs. The replay stage records into
+//! it and the RPC `getForkReplayErrors` method reads it.
+
+use hashbrown::HashMap;
+use morgan_interface::transaction::TransactionError;
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+/// What went wrong replaying the entries of one slot
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ForkReplayErrors {
+    /// Entries whose hash didn't follow from the previous entry
+    pub entry_verification_failures: u64,
+    /// Entries holding a transaction with a bad signature
+    pub signature_verification_failures: u64,
+    /// Failed transactions, by `TransactionError` variant
+    pub transaction_errors: BTreeMap<String, u64>,
+    /// Index in the slot of the last entry that failed to replay
+    pub failed_entry: Option<u64>,
+}
+
+impl ForkReplayErrors {
+    pub fn is_empty(&self) -> bool {
+        self.failed_entry.is_none()
+    }
+
+    pub fn record_entry_verification_failure(&mut self, entry_index: u64) {
+        self.entry_verification_failures += 1;
+        self.failed_entry = Some(entry_index);
+    }
+
+    pub fn record_signature_verification_failure(&mut self, entry_index: u64) {
+        self.signature_verification_failures += 1;
+        self.failed_entry = Some(entry_index);
+    }
+
+    pub fn record_transaction_error(&mut self, entry_index: u64, err: &TransactionError) {
+        *self
+            .transaction_errors
+            .entry(Self::error_kind(err))
+            .or_insert(0) += 1;
+        self.failed_entry = Some(entry_index);
+    }
+
+    /// The variant name of `err`, without what it carries
+    fn error_kind(err: &TransactionError) -> String {
+        let debug = format!("{:?}", err);
+        debug.split('(').next().unwrap_or(&debug).to_string()
+    }
+}
+
+/// The replay errors of the forks above the root, shared by the replay stage and rpc
+#[derive(Debug, Default)]
+pub struct ReplayErrors {
+    forks: RwLock<HashMap<u64, ForkReplayErrors>>,
+}
+
+impl ReplayErrors {
+    pub fn update(&self, slot: u64, errors: &ForkReplayErrors) {
+        self.forks.write().unwrap().insert(slot, errors.clone());
+    }
+
+    /// Forget the forks below `root`, those at or above it are kept even once pruned from
+    /// bank forks so the errors of a dead fork stay around until the root passes it
+    pub fn set_root(&self, root: u64) {
+        self.forks.write().unwrap().retain(|slot, _| *slot >= root);
+    }
+
+    pub fn get(&self, slot: u64) -> Option<ForkReplayErrors> {
+        self.forks.read().unwrap().get(&slot).cloned()
+    }
+
+    /// All the forks with errors, by slot
+    pub fn forks(&self) -> Vec<(u64, ForkReplayErrors)> {
+        let mut forks: Vec<_> = self
+            .forks
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(slot, errors)| (*slot, errors.clone()))
+            .collect();
+        forks.sort_by_key(|(slot, _)| *slot);
+        forks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morgan_interface::instruction::InstructionError;
+
+    #[test]
+    fn test_fork_replay_errors() {
+        let mut errors = ForkReplayErrors::default();
+        assert!(errors.is_empty());
+
+        errors.record_entry_verification_failure(3);
+        errors.record_transaction_error(5, &TransactionError::AccountNotFound);
+        errors.record_transaction_error(
+            6,
+            &TransactionError::InstructionError(0, InstructionError::GenericError),
+        );
+        errors.record_transaction_error(7, &TransactionError::AccountNotFound);
+        assert!(!errors.is_empty());
+        assert_eq!(errors.entry_verification_failures, 1);
+        assert_eq!(errors.signature_verification_failures, 0);
+        assert_eq!(errors.transaction_errors["AccountNotFound"], 2);
+        assert_eq!(errors.transaction_errors["InstructionError"], 1);
+        assert_eq!(errors.failed_entry, Some(7));
+    }
+
+    #[test]
+    fn test_replay_errors_set_root() {
+        let replay_errors = ReplayErrors::default();
+        let mut errors = ForkReplayErrors::default();
+        errors.record_signature_verification_failure(0);
+        replay_errors.update(4, &errors);
+        replay_errors.update(2, &errors);
+        assert_eq!(
+            replay_errors.forks(),
+            vec![(2, errors.clone()), (4, errors.clone())]
+        );
+
+        replay_errors.set_root(3);
+        assert_eq!(replay_errors.get(2), None);
+        assert_eq!(replay_errors.get(4), Some(errors));
+    }
+}
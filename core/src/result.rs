@@ -23,6 +23,7 @@ pub enum Error {
     TransactionError(transaction::TransactionError),
     ClusterInfoError(clusterMessage::ClusterInfoError),
     BlobError(packet::BlobError),
+    PacketError(packet::PacketError),
     ErasureError(reed_solomon_erasure::Error),
     SendError,
     PohRecorderError(waterClockRecorder::PohRecorderError),
@@ -6,11 +6,13 @@ use crate::blockBufferPool::{Blocktree, CompletedSlotsReceiver};
 use crate::clusterMessage::{compute_retransmit_peers, ClusterInfo, DATA_PLANE_FANOUT};
 use crate::leaderArrangeCache::LeaderScheduleCache;
 use crate::fixMissingSpotService::RepairStrategy;
+use crate::packet::SharedBlob;
 use crate::result::{Error, Result};
 use crate::service::Service;
 use crate::stakingUtils;
 use crate::streamer::BlobReceiver;
 use crate::spotTransmitService::{should_retransmit_and_persist, WindowService};
+use crate::ClusterVoteMessageListener::VoteTracker;
 use morgan_metricbot::{datapoint_info, inc_new_counter_error};
 use morgan_runtime::epoch_schedule::EpochSchedule;
 use morgan_interface::hash::Hash;
@@ -22,6 +24,14 @@ use std::sync::{Arc, RwLock};
 use std::thread::{self, Builder, JoinHandle};
 use std::time::Duration;
 
+/// Drop the blobs of slots older than `root` from `blobs`, returns how many were dropped.
+/// The window service stored them already, and peers still missing rooted slots repair them
+fn drop_rooted_blobs(blobs: &mut Vec<SharedBlob>, root: u64) -> usize {
+    let len = blobs.len();
+    blobs.retain(|blob| blob.read().unwrap().slot() >= root);
+    len - blobs.len()
+}
+
 fn retransmit(
     bank_forks: &Arc<RwLock<BankForks>>,
     leader_schedule_cache: &Arc<LeaderScheduleCache>,
@@ -35,18 +45,39 @@ fn retransmit(
         blobs.append(&mut nq);
     }
 
-    datapoint_info!("retransmit-stage", ("count", blobs.len(), i64));
+    let (r_bank, root) = {
+        let bank_forks = bank_forks.read().unwrap();
+        (bank_forks.working_bank(), bank_forks.root())
+    };
+    let num_rooted = drop_rooted_blobs(&mut blobs, root);
+    datapoint_info!(
+        "retransmit-stage",
+        ("count", blobs.len(), i64),
+        ("rooted", num_rooted, i64)
+    );
+    if blobs.is_empty() {
+        return Ok(());
+    }
 
-    let r_bank = bank_forks.read().unwrap().working_bank();
     let bank_epoch = r_bank.get_stakers_epoch(r_bank.slot());
-    let (neighbors, children) = compute_retransmit_peers(
-        stakingUtils::staked_nodes_at_epoch(&r_bank, bank_epoch).as_ref(),
-        cluster_info,
-        DATA_PLANE_FANOUT,
-    );
+    let (me, peers, peer_stakes) = {
+        let cluster_info = cluster_info.read().unwrap();
+        let (peers, peer_stakes) = cluster_info.sorted_retransmit_peers_and_stakes(
+            stakingUtils::staked_nodes_at_epoch(&r_bank, bank_epoch).as_ref(),
+        );
+        (cluster_info.id(), peers, peer_stakes)
+    };
     for blob in &blobs {
-        let leader = leader_schedule_cache
-            .slot_leader_at(blob.read().unwrap().slot(), Some(r_bank.as_ref()));
+        let (slot, seed) = {
+            let blob = blob.read().unwrap();
+            (blob.slot(), blob.seed())
+        };
+        // every blob gets its own tree so the load of the upper layers rotates over the nodes
+        let (my_index, shuffled_peers) =
+            ClusterInfo::shuffle_peers_and_index(&me, &peers, &peer_stakes, seed);
+        let (neighbors, children) =
+            compute_retransmit_peers(DATA_PLANE_FANOUT, my_index, &shuffled_peers);
+        let leader = leader_schedule_cache.slot_leader_at(slot, Some(r_bank.as_ref()));
         if blob.read().unwrap().meta.forward {
             ClusterInfo::retransmit_to(&cluster_info, &neighbors, blob, leader, sock, true)?;
             ClusterInfo::retransmit_to(&cluster_info, &children, blob, leader, sock, false)?;
@@ -120,6 +151,7 @@ impl RetransmitStage {
         genesis_blockhash: &Hash,
         completed_slots_receiver: CompletedSlotsReceiver,
         epoch_schedule: EpochSchedule,
+        vote_tracker: &Arc<RwLock<VoteTracker>>,
     ) -> Self {
         let (retransmit_sender, retransmit_receiver) = channel();
 
@@ -135,6 +167,7 @@ impl RetransmitStage {
             bank_forks,
             completed_slots_receiver,
             epoch_schedule,
+            vote_tracker: vote_tracker.clone(),
         };
         let leader_schedule_cache = leader_schedule_cache.clone();
         let window_service = WindowService::new(
@@ -170,3 +203,26 @@ impl Service for RetransmitStage {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packet::Blob;
+
+    #[test]
+    fn test_drop_rooted_blobs() {
+        let mut blobs: Vec<_> = (0..4)
+            .map(|slot| {
+                let mut blob = Blob::default();
+                blob.set_slot(slot);
+                Arc::new(RwLock::new(blob))
+            })
+            .collect();
+
+        // the root itself may still be in flight, only older slots are dropped
+        assert_eq!(drop_rooted_blobs(&mut blobs, 0), 0);
+        assert_eq!(drop_rooted_blobs(&mut blobs, 2), 2);
+        let slots: Vec<_> = blobs.iter().map(|b| b.read().unwrap().slot()).collect();
+        assert_eq!(slots, vec![2, 3]);
+    }
+}
@@ -2,10 +2,17 @@
 
 // use crate::bank_forks::BankForks;
 use crate::treasuryForks::BankForks;
+use crate::blockBufferPool::Blocktree;
 use crate::clusterMessage::ClusterInfo;
 use crate::connectionInfo::ContactInfo;
+use crate::entryInfo::Entry;
+use crate::ledgerArchive::{LedgerArchive, LedgerArchiveConfig};
 use crate::packet::PACKET_DATA_SIZE;
+use crate::replayErrors::{ForkReplayErrors, ReplayErrors};
+use crate::samplePerformanceService::{PerformanceSamples, MAX_PERFORMANCE_SAMPLES};
+use crate::sendTransactionService::TransactionInfo;
 use crate::storageStage::StorageState;
+use crate::transactionTrace::TransactionTraces;
 use bincode::{deserialize, serialize};
 use jsonrpc_core::{Error, Metadata, Result};
 use jsonrpc_derive::rpc;
@@ -13,21 +20,46 @@ use morgan_tokenbot::drone::{request_airdrop_transaction, request_reputation_air
 use morgan_runtime::bank::Bank;
 use morgan_interface::account::Account;
 use morgan_interface::fee_calculator::FeeCalculator;
+use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::Signature;
+use morgan_interface::syscall::slot_hashes::{self, SlotHashes};
 use morgan_interface::transaction::{self, Transaction};
+use morgan_stake_api::stake_state::{StakeState, STAKE_ACTIVE};
 use morgan_vote_api::vote_state::VoteState;
+use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 use morgan_helper::logHelper::*;
 
+/// Default cap on the size of a single HTTP RPC response body, before compression
+pub const DEFAULT_MAX_RESPONSE_BYTES: usize = 16 * 1024 * 1024;
+
+/// Most slots a single `getSlotProof` response walks back over
+pub const MAX_SLOT_PROOF_SLOTS: usize = 64;
+
+/// Most transactions a single `sendTxnBatch` request may carry
+pub const MAX_TRANSACTION_BATCH: usize = 64;
+
+/// Most signatures a single `getSignaturesForAddress` response returns
+pub const MAX_GET_SIGNATURES_FOR_ADDRESS_LIMIT: usize = 1000;
+
+/// Most slots a single `getConfirmedSignaturesForAddress` request may span
+pub const MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE: u64 = 10_000;
+
 #[derive(Debug, Clone)]
 pub struct JsonRpcConfig {
     pub enable_fullnode_exit: bool, // Enable the 'fullnodeExit' command
     pub drone_addr: Option<SocketAddr>,
+    pub max_response_bytes: usize, // Larger responses are replaced with an error
+    pub enable_debug_rpc: bool,    // Enable the 'getSlotEntries' and 'getForkReplayErrors' commands
+    pub ledger_archive: Option<LedgerArchiveConfig>, // Serve slots missing locally from here
+    pub snapshot_path: Option<PathBuf>, // Serve the snapshot archives saved here
 }
 
 impl Default for JsonRpcConfig {
@@ -35,6 +67,10 @@ impl Default for JsonRpcConfig {
         Self {
             enable_fullnode_exit: false,
             drone_addr: None,
+            max_response_bytes: DEFAULT_MAX_RESPONSE_BYTES,
+            enable_debug_rpc: false,
+            ledger_archive: None,
+            snapshot_path: None,
         }
     }
 }
@@ -42,9 +78,15 @@ impl Default for JsonRpcConfig {
 #[derive(Clone)]
 pub struct JsonRpcRequestProcessor {
     bank_forks: Arc<RwLock<BankForks>>,
+    blocktree: Option<Arc<Blocktree>>,
     storage_state: StorageState,
     config: JsonRpcConfig,
     fullnode_exit: Arc<AtomicBool>,
+    transaction_sender: Option<Arc<Mutex<Sender<TransactionInfo>>>>,
+    performance_samples: PerformanceSamples,
+    transaction_traces: Arc<TransactionTraces>,
+    replay_errors: Arc<ReplayErrors>,
+    ledger_archive: Option<Arc<LedgerArchive>>,
 }
 
 impl JsonRpcRequestProcessor {
@@ -56,13 +98,91 @@ impl JsonRpcRequestProcessor {
         storage_state: StorageState,
         config: JsonRpcConfig,
         bank_forks: Arc<RwLock<BankForks>>,
+        blocktree: Option<Arc<Blocktree>>,
         fullnode_exit: &Arc<AtomicBool>,
+        transaction_sender: Option<Sender<TransactionInfo>>,
+        transaction_traces: Arc<TransactionTraces>,
+        replay_errors: Arc<ReplayErrors>,
     ) -> Self {
+        let ledger_archive = config.ledger_archive.as_ref().and_then(|ledger_archive| {
+            LedgerArchive::from_config(ledger_archive)
+                .map_err(|err| {
+                    println!(
+                        "{}",
+                        Warn(
+                            format!("ledger archive unavailable to rpc: {:?}", err).to_string(),
+                            module_path!().to_string()
+                        )
+                    );
+                })
+                .ok()
+                .map(Arc::new)
+        });
         JsonRpcRequestProcessor {
             bank_forks,
+            blocktree,
             storage_state,
             config,
             fullnode_exit: fullnode_exit.clone(),
+            transaction_sender: transaction_sender.map(|sender| Arc::new(Mutex::new(sender))),
+            performance_samples: PerformanceSamples::default(),
+            transaction_traces,
+            replay_errors,
+            ledger_archive,
+        }
+    }
+
+    /// The samples the sample performance service fills in
+    pub fn performance_samples(&self) -> &PerformanceSamples {
+        &self.performance_samples
+    }
+
+    /// Follow a submitted transaction through the validator under the client's `trace_id`,
+    /// returns false if it isn't traced
+    pub fn trace_transaction(&self, signature: Signature, trace_id: String) -> bool {
+        if !self.transaction_traces.insert(signature, trace_id) {
+            debug!("trace table full, not tracing {}", signature);
+            return false;
+        }
+        self.transaction_traces.record(&signature, "rpc-received");
+        true
+    }
+
+    /// Stop tracing a transaction that never left the RPC service
+    pub fn untrace_transaction(&self, signature: &Signature, reason: &str) {
+        self.transaction_traces
+            .drop_transaction(signature, "rpc", reason);
+    }
+
+    /// Check a wire transaction the way the leader would before it costs anything to forward
+    pub fn preflight_transaction(&self, data: &[u8]) -> std::result::Result<Transaction, String> {
+        if data.len() >= PACKET_DATA_SIZE {
+            return Err(format!(
+                "transaction too large: {} bytes (max: {} bytes)",
+                data.len(),
+                PACKET_DATA_SIZE
+            ));
+        }
+        let tx: Transaction =
+            deserialize(data).map_err(|err| format!("deserialize error: {:?}", err))?;
+        if tx.signatures.is_empty() || !tx.verify_refs() || !tx.verify_signatures() {
+            return Err("invalid transaction signature or account references".to_string());
+        }
+        let bank = self.bank();
+        if !bank.check_blockhash_age(
+            &tx.message().recent_blockhash,
+            bank.runtime_params().max_processing_age,
+        ) {
+            return Err("blockhash not found or too old".to_string());
+        }
+        Ok(tx)
+    }
+
+    /// Hand a submitted transaction to the send transaction service for rebroadcast
+    pub fn retry_transaction(&self, transaction_info: TransactionInfo) {
+        if let Some(transaction_sender) = &self.transaction_sender {
+            // the service only goes away once the node exits
+            let _ = transaction_sender.lock().unwrap().send(transaction_info);
         }
     }
 
@@ -87,6 +207,19 @@ impl JsonRpcRequestProcessor {
         )
     }
 
+    fn get_fees(&self) -> RpcFees {
+        let bank = self.bank();
+        let blockhash = bank.confirmed_last_blockhash();
+        let last_valid_slot = bank
+            .get_blockhash_last_valid_slot(&blockhash, bank.runtime_params().max_processing_age)
+            .unwrap_or_else(|| bank.slot());
+        RpcFees {
+            blockhash: blockhash.to_string(),
+            fee_calculator: bank.fee_calculator.clone(),
+            last_valid_slot,
+        }
+    }
+
     pub fn get_signature_status(&self, signature: Signature) -> Option<transaction::Result<()>> {
         self.get_signature_confirmation_status(signature)
             .map(|x| x.1)
@@ -108,6 +241,25 @@ impl JsonRpcRequestProcessor {
         Ok(self.bank().transaction_count() as u64)
     }
 
+    /// The newest `limit` samples, newest first
+    fn get_performance_samples(&self, limit: Option<usize>) -> Result<Vec<RpcPerfSample>> {
+        let limit = limit.unwrap_or(MAX_PERFORMANCE_SAMPLES);
+        if limit > MAX_PERFORMANCE_SAMPLES {
+            return Err(Error::invalid_params(format!(
+                "limit must be at most {}",
+                MAX_PERFORMANCE_SAMPLES
+            )));
+        }
+        Ok(self
+            .performance_samples
+            .read()
+            .unwrap()
+            .iter()
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
     fn get_epoch_vote_accounts(&self) -> Result<Vec<(Pubkey, u64, VoteState)>> {
         let bank = self.bank();
         Ok(bank
@@ -118,6 +270,28 @@ impl JsonRpcRequestProcessor {
             .collect::<Vec<_>>())
     }
 
+    /// Where the stake held by `pubkey` stands against the stakes the current epoch runs on
+    fn get_stake_activation(&self, pubkey: &Pubkey) -> Result<RpcStakeActivation> {
+        let bank = self.bank();
+        let account = bank
+            .get_account(pubkey)
+            .ok_or_else(Error::invalid_request)?;
+        if !morgan_stake_api::check_id(&account.owner) {
+            return Err(Error::invalid_params("not a stake account"));
+        }
+        let (epoch, _) = bank.get_epoch_and_slot_index(bank.slot());
+        let epoch_account = bank
+            .epoch_stake_accounts(epoch)
+            .and_then(|stake_accounts| stake_accounts.get(pubkey))
+            .map(|account| &**account);
+        Ok(stake_activation(
+            &account,
+            epoch_account,
+            bank.tick_height(),
+            bank.get_ticks_in_epoch(epoch),
+        ))
+    }
+
     fn get_storage_blockhash(&self) -> Result<String> {
         Ok(self.storage_state.get_storage_blockhash().to_string())
     }
@@ -130,6 +304,215 @@ impl JsonRpcRequestProcessor {
         Ok(self.storage_state.get_pubkeys_for_slot(slot))
     }
 
+    /// The entries of `slot` as this node's blocktree holds them, for comparing nodes' views
+    /// of a slot. Only answered with `enable_debug_rpc`.
+    pub fn get_slot_entries(&self, slot: u64) -> Result<Vec<RpcEntry>> {
+        if !self.config.enable_debug_rpc {
+            return Err(Error::method_not_found());
+        }
+        let blocktree = self.blocktree.as_ref().ok_or_else(Error::internal_error)?;
+        let mut entries = blocktree.get_slot_entries(slot, 0, None).map_err(|err| {
+            debug!("get_slot_entries {} failed: {:?}", slot, err);
+            Error::internal_error()
+        })?;
+        if entries.is_empty() {
+            if let Some(ledger_archive) = &self.ledger_archive {
+                entries = ledger_archive
+                    .get_slot_entries(slot)
+                    .map_err(|err| {
+                        debug!("archived get_slot_entries {} failed: {:?}", slot, err);
+                        Error::internal_error()
+                    })?
+                    .unwrap_or_default();
+            }
+        }
+        Ok(entries.iter().map(RpcEntry::from).collect())
+    }
+
+    /// The first and last slots this node's ledger holds, slots below the first were purged
+    pub fn get_ledger_range(&self) -> Result<RpcLedgerRange> {
+        let blocktree = self.blocktree.as_ref().ok_or_else(Error::internal_error)?;
+        let (first_slot, last_slot) = blocktree.ledger_range().ok_or_else(Error::internal_error)?;
+        Ok(RpcLedgerRange {
+            first_slot,
+            last_slot,
+        })
+    }
+
+    /// What went wrong replaying the forks above the root, for the forks that failed. Only
+    /// answered with `enable_debug_rpc`.
+    pub fn get_fork_replay_errors(&self) -> Result<Vec<RpcForkReplayErrors>> {
+        if !self.config.enable_debug_rpc {
+            return Err(Error::method_not_found());
+        }
+        Ok(self
+            .replay_errors
+            .forks()
+            .into_iter()
+            .map(|(slot, errors)| RpcForkReplayErrors { slot, errors })
+            .collect())
+    }
+
+    /// The entries chaining the last entry hash of `trusted_slot` to the end of `slot`, which
+    /// must be a rooted descendant of it, along with the bank hash of `slot` if this node
+    /// still knows it. Replaying the entry hashes from `start_hash` proves each transaction
+    /// signature they mix in was recorded in that slot.
+    pub fn get_slot_proof(&self, slot: u64, trusted_slot: u64) -> Result<RpcSlotProof> {
+        let blocktree = self.blocktree.as_ref().ok_or_else(Error::internal_error)?;
+        if trusted_slot >= slot || !blocktree.is_root(slot) {
+            return Err(Error::invalid_request());
+        }
+        let get_entries = |slot| {
+            blocktree.get_slot_entries(slot, 0, None).map_err(|err| {
+                debug!("get_slot_proof entries of {} failed: {:?}", slot, err);
+                Error::internal_error()
+            })
+        };
+
+        let mut slots = vec![];
+        let mut current_slot = slot;
+        while current_slot != trusted_slot {
+            if current_slot < trusted_slot || slots.len() >= MAX_SLOT_PROOF_SLOTS {
+                return Err(Error::invalid_request());
+            }
+            let meta = blocktree
+                .meta(current_slot)
+                .map_err(|err| {
+                    debug!("get_slot_proof meta of {} failed: {:?}", current_slot, err);
+                    Error::internal_error()
+                })?
+                .filter(|meta| meta.is_full())
+                .ok_or_else(Error::invalid_request)?;
+            slots.push(RpcSlotEntries {
+                slot: current_slot,
+                parent_slot: meta.parent_slot,
+                entries: get_entries(current_slot)?
+                    .iter()
+                    .map(RpcEntry::from)
+                    .collect(),
+            });
+            current_slot = meta.parent_slot;
+        }
+        slots.reverse();
+
+        let start_hash = get_entries(trusted_slot)?
+            .last()
+            .map(|entry| entry.hash)
+            .ok_or_else(Error::invalid_request)?;
+        Ok(RpcSlotProof {
+            slot,
+            trusted_slot,
+            start_hash: start_hash.to_string(),
+            slots,
+            bank_hash: self.get_bank_hash(slot).map(|hash| hash.to_string()),
+        })
+    }
+
+    /// Signatures of the rooted transactions referencing `address`, newest first, paged with
+    /// the `before` and `until` signatures of `config`
+    pub fn get_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        config: RpcSignaturesForAddressConfig,
+    ) -> Result<Vec<RpcAddressSignature>> {
+        let blocktree = self.blocktree.as_ref().ok_or_else(Error::internal_error)?;
+        let limit = config.limit.unwrap_or(MAX_GET_SIGNATURES_FOR_ADDRESS_LIMIT);
+        if limit == 0 || limit > MAX_GET_SIGNATURES_FOR_ADDRESS_LIMIT {
+            return Err(Error::invalid_request());
+        }
+        let before = config
+            .before
+            .as_ref()
+            .map(|before| verify_signature(before))
+            .transpose()?;
+        let until = config
+            .until
+            .as_ref()
+            .map(|until| verify_signature(until))
+            .transpose()?;
+
+        let signatures = blocktree
+            .get_signatures_for_address(address, before.as_ref(), until.as_ref(), limit)
+            .map_err(|err| {
+                debug!("get_signatures_for_address {} failed: {:?}", address, err);
+                Error::internal_error()
+            })?;
+        let mut slot_memos: HashMap<u64, HashMap<Signature, Vec<u8>>> = HashMap::new();
+        Ok(signatures
+            .into_iter()
+            .map(|(slot, signature)| {
+                let memos = slot_memos
+                    .entry(slot)
+                    .or_insert_with(|| Self::get_slot_memos(blocktree, slot));
+                RpcAddressSignature {
+                    signature: signature.to_string(),
+                    slot,
+                    memo: memos.get(&signature).cloned(),
+                }
+            })
+            .collect())
+    }
+
+    /// Signatures of the rooted transactions referencing `address` from `start_slot` through
+    /// `end_slot`, oldest first
+    pub fn get_confirmed_signatures_for_address(
+        &self,
+        address: &Pubkey,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> Result<Vec<String>> {
+        let blocktree = self.blocktree.as_ref().ok_or_else(Error::internal_error)?;
+        if end_slot < start_slot {
+            return Err(Error::invalid_params(format!(
+                "start slot {} is past end slot {}",
+                start_slot, end_slot
+            )));
+        }
+        if end_slot - start_slot > MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE {
+            return Err(Error::invalid_params(format!(
+                "slot range exceeds {}",
+                MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE
+            )));
+        }
+        blocktree
+            .get_confirmed_signatures_for_address(address, start_slot, end_slot)
+            .map(|signatures| signatures.iter().map(|s| s.to_string()).collect())
+            .map_err(|err| {
+                debug!(
+                    "get_confirmed_signatures_for_address {} failed: {:?}",
+                    address, err
+                );
+                Error::internal_error()
+            })
+    }
+
+    /// The memos of the token transfers in `slot`, by transaction signature
+    fn get_slot_memos(blocktree: &Blocktree, slot: u64) -> HashMap<Signature, Vec<u8>> {
+        blocktree
+            .get_slot_entries(slot, 0, None)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|entry| &entry.transactions)
+            .filter_map(|tx| {
+                morgan_token_api::message_memo(&tx.message).map(|memo| (tx.signatures[0], memo))
+            })
+            .collect()
+    }
+
+    /// The bank hash of `slot`, from its bank if that is still around and otherwise from the
+    /// recent slot hashes of the working bank
+    fn get_bank_hash(&self, slot: u64) -> Option<Hash> {
+        let bank_forks = self.bank_forks.read().unwrap();
+        if let Some(bank) = bank_forks.get(slot).filter(|bank| bank.is_frozen()) {
+            return Some(bank.hash());
+        }
+        let account = bank_forks.working_bank().get_account(&slot_hashes::id())?;
+        SlotHashes::from(&account)?
+            .iter()
+            .find(|(hash_slot, _)| *hash_slot == slot)
+            .map(|(_, hash)| *hash)
+    }
+
     pub fn fullnode_exit(&self) -> Result<bool> {
         if self.config.enable_fullnode_exit {
             // warn!("fullnode_exit request...");
@@ -154,6 +537,53 @@ fn get_tpu_addr(cluster_info: &Arc<RwLock<ClusterInfo>>) -> Result<SocketAddr> {
     Ok(contact_info.tpu)
 }
 
+/// The activation of the stake held by `account` at `tick_height`, where `epoch_account` is the
+/// same account as it was when the stakes of the current epoch were saved off.  Stake
+/// delegated since only counts once the next epoch's stakes are saved off, and stake
+/// deactivated since still counts until then or until its cooldown of `cooldown_ticks` is
+/// over, whichever is later
+fn stake_activation(
+    account: &Account,
+    epoch_account: Option<&Account>,
+    tick_height: u64,
+    cooldown_ticks: u64,
+) -> RpcStakeActivation {
+    let delegated_difs =
+        |account: &Account| StakeState::voter_pubkey_from(account).map_or(0, |_| account.difs);
+    let effective = epoch_account.map_or(0, delegated_difs);
+    let delegated = delegated_difs(account);
+    let activating = delegated.saturating_sub(effective);
+
+    let (voter_pubkey, cooling_down) = match StakeState::from(account) {
+        Some(StakeState::Delegate {
+            voter_pubkey,
+            stake,
+            deactivated,
+            ..
+        }) if stake > 0 => (
+            Some(voter_pubkey.to_string()),
+            deactivated != STAKE_ACTIVE && tick_height < deactivated.saturating_add(cooldown_ticks),
+        ),
+        _ => (None, false),
+    };
+
+    let state = if activating > 0 {
+        StakeActivationState::Activating
+    } else if delegated < effective || cooling_down {
+        StakeActivationState::Deactivating
+    } else if effective > 0 {
+        StakeActivationState::Active
+    } else {
+        StakeActivationState::Inactive
+    };
+    RpcStakeActivation {
+        state,
+        effective,
+        activating,
+        voter_pubkey,
+    }
+}
+
 fn verify_pubkey(input: String) -> Result<Pubkey> {
     input.parse().map_err(|_e| Error::invalid_request())
 }
@@ -179,6 +609,149 @@ pub struct RpcContactInfo {
     pub tpu: Option<SocketAddr>,
     /// JSON RPC port
     pub rpc: Option<SocketAddr>,
+    /// Software version the node advertises over gossip
+    pub version: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcFees {
+    /// Base58 blockhash for new transactions
+    pub blockhash: String,
+    pub fee_calculator: FeeCalculator,
+    /// Last slot leaders accept transactions using `blockhash` in
+    pub last_valid_slot: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcPerfSample {
+    pub slot: u64,
+    pub num_transactions: u64,
+    pub num_entries: u64,
+    /// Time between this slot and the previously sampled one getting rooted
+    pub slot_duration_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RpcEntry {
+    /// Number of hashes since the previous entry
+    pub num_hashes: u64,
+    /// Base58 entry hash
+    pub hash: String,
+    /// Base58 signature of each transaction, empty for a tick
+    pub signatures: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RpcForkReplayErrors {
+    pub slot: u64,
+    #[serde(flatten)]
+    pub errors: ForkReplayErrors,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSlotEntries {
+    pub slot: u64,
+    pub parent_slot: u64,
+    pub entries: Vec<RpcEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcLedgerRange {
+    pub first_slot: u64,
+    pub last_slot: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSlotProof {
+    pub slot: u64,
+    pub trusted_slot: u64,
+    /// Base58 hash of the last entry of `trusted_slot`, which the first entry chains from
+    pub start_hash: String,
+    /// The slots after `trusted_slot` up to and including `slot`, oldest first
+    pub slots: Vec<RpcSlotEntries>,
+    /// Base58 bank hash of `slot`, None once this node has forgotten it
+    pub bank_hash: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSignaturesForAddressConfig {
+    /// Base58 signature to start after, the newest signature if None
+    pub before: Option<String>,
+    /// Base58 signature to stop short of
+    pub until: Option<String>,
+    /// At most MAX_GET_SIGNATURES_FOR_ADDRESS_LIMIT, the default
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcAddressSignature {
+    /// Base58 signature
+    pub signature: String,
+    pub slot: u64,
+    /// Memo of the token transfer the transaction made, if it carried one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memo: Option<Vec<u8>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcSendBatchConfig {
+    /// Send nothing unless every transaction passes preflight
+    pub all_or_nothing: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RpcBatchTransactionStatus {
+    /// Base58 signature, None if the transaction failed preflight
+    pub signature: Option<String>,
+    /// Why the transaction wasn't sent, None if it was
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum StakeActivationState {
+    /// Counted in full by the current epoch's stakes
+    Active,
+    /// Delegated, but not yet counted in full by the current epoch's stakes
+    Activating,
+    /// Deactivated, but still counted by the current epoch's stakes or cooling down
+    Deactivating,
+    /// Neither counted nor locked
+    Inactive,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcStakeActivation {
+    pub state: StakeActivationState,
+    /// Difs counted towards the vote account by the current epoch's stakes
+    pub effective: u64,
+    /// Difs delegated that only count once the next epoch's stakes are saved off
+    pub activating: u64,
+    /// Base58 vote account the stake is delegated to, None if it never was
+    pub voter_pubkey: Option<String>,
+}
+
+impl<'a> From<&'a Entry> for RpcEntry {
+    fn from(entry: &'a Entry) -> Self {
+        Self {
+            num_hashes: entry.num_hashes,
+            hash: entry.hash.to_string(),
+            signatures: entry
+                .transactions
+                .iter()
+                .map(|tx| tx.signatures[0].to_string())
+                .collect(),
+        }
+    }
 }
 
 #[rpc(server)]
@@ -203,6 +776,9 @@ pub trait RpcSol {
     #[rpc(meta, name = "getLatestBlockhash")]
     fn get_recent_blockhash(&self, _: Self::Metadata) -> Result<(String, FeeCalculator)>;
 
+    #[rpc(meta, name = "getFees")]
+    fn get_fees(&self, _: Self::Metadata) -> Result<RpcFees>;
+
     #[rpc(meta, name = "getSignatureState")]
     fn get_signature_status(
         &self,
@@ -213,6 +789,13 @@ pub trait RpcSol {
     #[rpc(meta, name = "getTxnCnt")]
     fn get_transaction_count(&self, _: Self::Metadata) -> Result<u64>;
 
+    #[rpc(meta, name = "getPerformanceSamples")]
+    fn get_performance_samples(
+        &self,
+        _: Self::Metadata,
+        _: Option<usize>,
+    ) -> Result<Vec<RpcPerfSample>>;
+
     #[rpc(meta, name = "requestDif")]
     fn request_airdrop(&self, _: Self::Metadata, _: String, _: u64) -> Result<String>;
 
@@ -220,7 +803,15 @@ pub trait RpcSol {
     fn request_reputation(&self, _: Self::Metadata, _: String, _: u64) -> Result<String>;
 
     #[rpc(meta, name = "sendTxn")]
-    fn send_transaction(&self, _: Self::Metadata, _: Vec<u8>) -> Result<String>;
+    fn send_transaction(&self, _: Self::Metadata, _: Vec<u8>, _: Option<String>) -> Result<String>;
+
+    #[rpc(meta, name = "sendTxnBatch")]
+    fn send_transaction_batch(
+        &self,
+        _: Self::Metadata,
+        _: Vec<Vec<u8>>,
+        _: Option<RpcSendBatchConfig>,
+    ) -> Result<Vec<RpcBatchTransactionStatus>>;
 
     #[rpc(meta, name = "getRoundLeader")]
     fn get_slot_leader(&self, _: Self::Metadata) -> Result<String>;
@@ -228,6 +819,9 @@ pub trait RpcSol {
     #[rpc(meta, name = "getEpochVoteAccounts")]
     fn get_epoch_vote_accounts(&self, _: Self::Metadata) -> Result<Vec<(Pubkey, u64, VoteState)>>;
 
+    #[rpc(meta, name = "getStakeActivation")]
+    fn get_stake_activation(&self, _: Self::Metadata, _: String) -> Result<RpcStakeActivation>;
+
     #[rpc(meta, name = "getStorageBlockhash")]
     fn get_storage_blockhash(&self, _: Self::Metadata) -> Result<String>;
 
@@ -240,6 +834,38 @@ pub trait RpcSol {
     #[rpc(meta, name = "fullnodeQuit")]
     fn fullnode_exit(&self, _: Self::Metadata) -> Result<bool>;
 
+    #[rpc(meta, name = "getSlotEntries")]
+    fn get_slot_entries(&self, _: Self::Metadata, _: u64) -> Result<Vec<RpcEntry>>;
+
+    #[rpc(meta, name = "getMinimumLedgerSlot")]
+    fn get_minimum_ledger_slot(&self, _: Self::Metadata) -> Result<u64>;
+
+    #[rpc(meta, name = "getLedgerRange")]
+    fn get_ledger_range(&self, _: Self::Metadata) -> Result<RpcLedgerRange>;
+
+    #[rpc(meta, name = "getForkReplayErrors")]
+    fn get_fork_replay_errors(&self, _: Self::Metadata) -> Result<Vec<RpcForkReplayErrors>>;
+
+    #[rpc(meta, name = "getSlotProof")]
+    fn get_slot_proof(&self, _: Self::Metadata, _: u64, _: u64) -> Result<RpcSlotProof>;
+
+    #[rpc(meta, name = "getSignaturesForAddress")]
+    fn get_signatures_for_address(
+        &self,
+        _: Self::Metadata,
+        _: String,
+        _: Option<RpcSignaturesForAddressConfig>,
+    ) -> Result<Vec<RpcAddressSignature>>;
+
+    #[rpc(meta, name = "getConfirmedSignaturesForAddress")]
+    fn get_confirmed_signatures_for_address(
+        &self,
+        _: Self::Metadata,
+        _: String,
+        _: u64,
+        _: u64,
+    ) -> Result<Vec<String>>;
+
     #[rpc(meta, name = "getNumBlocksSinceSignatureConfirmation")]
     fn get_num_blocks_since_signature_confirmation(
         &self,
@@ -309,6 +935,7 @@ impl RpcSol for RpcSolImpl {
                         gossip: Some(contact_info.gossip),
                         tpu: valid_address_or_none(&contact_info.tpu),
                         rpc: valid_address_or_none(&contact_info.rpc),
+                        version: cluster_info.get_node_version(&contact_info.id),
                     })
                 } else {
                     None // Exclude spy nodes
@@ -326,6 +953,11 @@ impl RpcSol for RpcSolImpl {
             .get_recent_blockhash())
     }
 
+    fn get_fees(&self, meta: Self::Metadata) -> Result<RpcFees> {
+        debug!("get_fees rpc request received");
+        Ok(meta.request_processor.read().unwrap().get_fees())
+    }
+
     fn get_signature_status(
         &self,
         meta: Self::Metadata,
@@ -366,6 +998,17 @@ impl RpcSol for RpcSolImpl {
             .get_transaction_count()
     }
 
+    fn get_performance_samples(
+        &self,
+        meta: Self::Metadata,
+        limit: Option<usize>,
+    ) -> Result<Vec<RpcPerfSample>> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_performance_samples(limit)
+    }
+
     fn request_airdrop(&self, meta: Self::Metadata, id: String, difs: u64) -> Result<String> {
         trace!("request_airdrop id={} difs={}", id, difs);
 
@@ -544,7 +1187,12 @@ impl RpcSol for RpcSolImpl {
         }
     }
 
-    fn send_transaction(&self, meta: Self::Metadata, data: Vec<u8>) -> Result<String> {
+    fn send_transaction(
+        &self,
+        meta: Self::Metadata,
+        data: Vec<u8>,
+        trace_id: Option<String>,
+    ) -> Result<String> {
         let tx: Transaction = deserialize(&data).map_err(|err| {
             // info!("{}", Info(format!("send_transaction: deserialize error: {:?}", err).to_string()));
             println!("{}",
@@ -575,9 +1223,25 @@ impl RpcSol for RpcSolImpl {
         let transactions_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         let transactions_addr = get_tpu_addr(&meta.cluster_info)?;
         trace!("send_transaction: leader is {:?}", &transactions_addr);
+        let traced = match (trace_id, tx.signatures.get(0)) {
+            (Some(trace_id), Some(signature)) => {
+                // registered before sending so the banking stage can't see the transaction first
+                meta.request_processor
+                    .read()
+                    .unwrap()
+                    .trace_transaction(*signature, trace_id)
+            }
+            _ => false,
+        };
         transactions_socket
             .send_to(&data, transactions_addr)
             .map_err(|err| {
+                if traced {
+                    meta.request_processor.read().unwrap().untrace_transaction(
+                        &tx.signatures[0],
+                        &format!("send_to error: {:?}", err),
+                    );
+                }
                 // info!("{}", Info(format!("send_transaction: send_to error: {:?}", err).to_string()));
                 println!("{}",
                     printLn(
@@ -587,6 +1251,14 @@ impl RpcSol for RpcSolImpl {
                 );
                 Error::internal_error()
             })?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .retry_transaction(TransactionInfo::new(
+                tx.signatures[0],
+                data.clone(),
+                tx.message().recent_blockhash,
+            ));
         let signature = tx.signatures[0].to_string();
         trace!(
             "send_transaction: sent {} bytes, signature={}",
@@ -596,6 +1268,76 @@ impl RpcSol for RpcSolImpl {
         Ok(signature)
     }
 
+    fn send_transaction_batch(
+        &self,
+        meta: Self::Metadata,
+        batch: Vec<Vec<u8>>,
+        config: Option<RpcSendBatchConfig>,
+    ) -> Result<Vec<RpcBatchTransactionStatus>> {
+        if batch.is_empty() || batch.len() > MAX_TRANSACTION_BATCH {
+            return Err(Error::invalid_request());
+        }
+        let all_or_nothing = config.map_or(false, |config| config.all_or_nothing);
+
+        let mut statuses = Vec::with_capacity(batch.len());
+        let mut transactions = Vec::with_capacity(batch.len());
+        {
+            let request_processor = meta.request_processor.read().unwrap();
+            for data in &batch {
+                match request_processor.preflight_transaction(data) {
+                    Ok(tx) => {
+                        statuses.push(RpcBatchTransactionStatus {
+                            signature: Some(tx.signatures[0].to_string()),
+                            error: None,
+                        });
+                        transactions.push((statuses.len() - 1, tx, data));
+                    }
+                    Err(err) => {
+                        statuses.push(RpcBatchTransactionStatus {
+                            signature: None,
+                            error: Some(err),
+                        });
+                        if all_or_nothing {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        if all_or_nothing && statuses.iter().any(|status| status.error.is_some()) {
+            // report why nothing was sent, leaving the transactions after the failure unchecked
+            for status in statuses.iter_mut().filter(|status| status.error.is_none()) {
+                status.error = Some("not sent, another transaction failed preflight".to_string());
+            }
+            return Ok(statuses);
+        }
+
+        // one burst from one socket, so the leader sees the transactions back to back
+        let transactions_socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+        let transactions_addr = get_tpu_addr(&meta.cluster_info)?;
+        trace!(
+            "send_transaction_batch: sending {} to {:?}",
+            transactions.len(),
+            &transactions_addr
+        );
+        for (index, tx, data) in transactions {
+            if let Err(err) = transactions_socket.send_to(data, transactions_addr) {
+                debug!("send_transaction_batch: send_to error: {:?}", err);
+                statuses[index].error = Some(format!("send_to error: {:?}", err));
+                continue;
+            }
+            meta.request_processor
+                .read()
+                .unwrap()
+                .retry_transaction(TransactionInfo::new(
+                    tx.signatures[0],
+                    data.clone(),
+                    tx.message().recent_blockhash,
+                ));
+        }
+        Ok(statuses)
+    }
+
     fn get_slot_leader(&self, meta: Self::Metadata) -> Result<String> {
         let cluster_info = meta.cluster_info.read().unwrap();
         let leader_data_option = cluster_info.leader_data();
@@ -615,6 +1357,15 @@ impl RpcSol for RpcSolImpl {
             .get_epoch_vote_accounts()
     }
 
+    fn get_stake_activation(&self, meta: Self::Metadata, id: String) -> Result<RpcStakeActivation> {
+        debug!("get_stake_activation rpc request received: {:?}", id);
+        let pubkey = verify_pubkey(id)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_stake_activation(&pubkey)
+    }
+
     fn get_storage_blockhash(&self, meta: Self::Metadata) -> Result<String> {
         meta.request_processor
             .read()
@@ -633,6 +1384,68 @@ impl RpcSol for RpcSolImpl {
             .get_storage_pubkeys_for_slot(slot)
     }
 
+    fn get_slot_entries(&self, meta: Self::Metadata, slot: u64) -> Result<Vec<RpcEntry>> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_slot_entries(slot)
+    }
+
+    fn get_minimum_ledger_slot(&self, meta: Self::Metadata) -> Result<u64> {
+        let ledger_range = meta.request_processor.read().unwrap().get_ledger_range()?;
+        Ok(ledger_range.first_slot)
+    }
+
+    fn get_ledger_range(&self, meta: Self::Metadata) -> Result<RpcLedgerRange> {
+        meta.request_processor.read().unwrap().get_ledger_range()
+    }
+
+    fn get_fork_replay_errors(&self, meta: Self::Metadata) -> Result<Vec<RpcForkReplayErrors>> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_fork_replay_errors()
+    }
+
+    fn get_slot_proof(
+        &self,
+        meta: Self::Metadata,
+        slot: u64,
+        trusted_slot: u64,
+    ) -> Result<RpcSlotProof> {
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_slot_proof(slot, trusted_slot)
+    }
+
+    fn get_signatures_for_address(
+        &self,
+        meta: Self::Metadata,
+        address: String,
+        config: Option<RpcSignaturesForAddressConfig>,
+    ) -> Result<Vec<RpcAddressSignature>> {
+        let address = verify_pubkey(address)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_signatures_for_address(&address, config.unwrap_or_default())
+    }
+
+    fn get_confirmed_signatures_for_address(
+        &self,
+        meta: Self::Metadata,
+        address: String,
+        start_slot: u64,
+        end_slot: u64,
+    ) -> Result<Vec<String>> {
+        let address = verify_pubkey(address)?;
+        meta.request_processor
+            .read()
+            .unwrap()
+            .get_confirmed_signatures_for_address(&address, start_slot, end_slot)
+    }
+
     fn fullnode_exit(&self, meta: Self::Metadata) -> Result<bool> {
         meta.request_processor.read().unwrap().fullnode_exit()
     }
@@ -641,14 +1454,22 @@ impl RpcSol for RpcSolImpl {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::blockBufferPool::create_new_tmp_ledger;
+    use crate::blockBufferPool::tests::entries_to_blobs;
     use crate::connectionInfo::ContactInfo;
+    use crate::entryInfo::next_entry;
     use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
-    use jsonrpc_core::{MetaIoHandler, Response};
+    use crate::propagationValue::{CrdsValue, Version};
+    use jsonrpc_core::{ErrorCode, MetaIoHandler, Response};
+    use morgan_interface::account_utils::State;
     use morgan_interface::hash::{hash, Hash};
     use morgan_interface::instruction::InstructionError;
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_transaction;
+    use morgan_interface::timing::{timestamp, MAX_PROCESSING_AGE};
     use morgan_interface::transaction::TransactionError;
+    use morgan_stake_api::stake_state;
+    use serde_json::Value;
     use std::thread;
 
     fn start_rpc_handler_with_tx(
@@ -669,7 +1490,11 @@ mod tests {
             StorageState::default(),
             JsonRpcConfig::default(),
             bank_forks,
+            None,
             &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
         )));
         let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
             ContactInfo::default(),
@@ -698,7 +1523,11 @@ mod tests {
             StorageState::default(),
             JsonRpcConfig::default(),
             bank_forks,
+            None,
             &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
         );
         thread::spawn(move || {
             let blockhash = bank.confirmed_last_blockhash();
@@ -750,6 +1579,14 @@ mod tests {
     fn test_rpc_get_cluster_nodes() {
         let bob_pubkey = Pubkey::new_rand();
         let (io, meta, _blockhash, _alice, leader_pubkey) = start_rpc_handler_with_tx(&bob_pubkey);
+        let version = CrdsValue::Version(Version::new(leader_pubkey, "0.1.0".to_string(), 0));
+        meta.cluster_info
+            .write()
+            .unwrap()
+            .gossip
+            .crds
+            .insert(version, timestamp())
+            .unwrap();
 
         let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getClusterNodes"}}"#);
         let res = io.handle_request_sync(&req, meta);
@@ -757,7 +1594,7 @@ mod tests {
             .expect("actual response deserialization");
 
         let expected = format!(
-            r#"{{"jsonrpc":"2.0","result":[{{"id": "{}", "gossip": "127.0.0.1:1235", "tpu": "127.0.0.1:1234", "rpc": "127.0.0.1:10099"}}],"id":1}}"#,
+            r#"{{"jsonrpc":"2.0","result":[{{"id": "{}", "gossip": "127.0.0.1:1235", "tpu": "127.0.0.1:1234", "rpc": "127.0.0.1:10099", "version": "0.1.0"}}],"id":1}}"#,
             leader_pubkey,
         );
 
@@ -798,20 +1635,69 @@ mod tests {
     }
 
     #[test]
-    fn test_rpc_get_account_info() {
+    fn test_rpc_get_performance_samples() {
         let bob_pubkey = Pubkey::new_rand();
         let (io, meta, _blockhash, _alice, _leader_pubkey) = start_rpc_handler_with_tx(&bob_pubkey);
+        {
+            let request_processor = meta.request_processor.read().unwrap();
+            let mut samples = request_processor.performance_samples().write().unwrap();
+            for slot in 1..=3 {
+                samples.push_front(RpcPerfSample {
+                    slot,
+                    num_transactions: 2 * slot,
+                    num_entries: 4,
+                    slot_duration_ms: 400,
+                });
+            }
+        }
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getPerformanceSamples","params":[2]}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let expected = json!({
+            "jsonrpc": "2.0",
+            "result": [
+                {"slot": 3, "numTransactions": 6, "numEntries": 4, "slotDurationMs": 400},
+                {"slot": 2, "numTransactions": 4, "numEntries": 4, "slotDurationMs": 400},
+            ],
+            "id": 1
+        });
+        let expected: Response =
+            serde_json::from_value(expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+
+        let req = r#"{"jsonrpc":"2.0","id":1,"method":"getPerformanceSamples"}"#;
+        let res = io.handle_request_sync(req, meta.clone());
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["result"].as_array().unwrap().len(), 3);
 
         let req = format!(
-            r#"{{"jsonrpc":"2.0","id":1,"method":"getAccountInfo","params":["{}"]}}"#,
-            bob_pubkey
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getPerformanceSamples","params":[{}]}}"#,
+            MAX_PERFORMANCE_SAMPLES + 1
         );
         let res = io.handle_request_sync(&req, meta);
-        let expected = r#"{
-            "jsonrpc":"2.0",
-            "result":{
-                "owner": [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
-                "difs": 20,
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["error"]["code"], ErrorCode::InvalidParams.code());
+    }
+
+    #[test]
+    fn test_rpc_get_account_info() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, _blockhash, _alice, _leader_pubkey) = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"getAccountInfo","params":["{}"]}}"#,
+            bob_pubkey
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let expected = r#"{
+            "jsonrpc":"2.0",
+            "result":{
+                "owner": [0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],
+                "difs": 20,
                 "reputations": 0,
                 "data": [],
                 "executable": false
@@ -926,6 +1812,24 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_get_fees() {
+        let bob_pubkey = Pubkey::new_rand();
+        let (io, meta, blockhash, _alice, _leader_pubkey) = start_rpc_handler_with_tx(&bob_pubkey);
+
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"getFees"}}"#);
+        let res = io.handle_request_sync(&req, meta);
+        let expected = format!(
+            r#"{{"jsonrpc":"2.0","result":{{"blockhash":"{}","feeCalculator":{{"difsPerSignature":0}},"lastValidSlot":{}}},"id":1}}"#,
+            blockhash, MAX_PROCESSING_AGE
+        );
+        let expected: Response =
+            serde_json::from_str(&expected).expect("expected response deserialization");
+        let result: Response = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(expected, result);
+    }
+
     #[test]
     fn test_rpc_fail_request_airdrop() {
         let bob_pubkey = Pubkey::new_rand();
@@ -959,7 +1863,11 @@ mod tests {
                     StorageState::default(),
                     JsonRpcConfig::default(),
                     new_bank_forks().0,
+                    None,
                     &exit,
+                    None,
+                    Arc::new(TransactionTraces::default()),
+                    Arc::new(ReplayErrors::default()),
                 );
                 Arc::new(RwLock::new(request_processor))
             },
@@ -980,6 +1888,258 @@ mod tests {
         assert_eq!(expected, result);
     }
 
+    #[test]
+    fn test_rpc_send_tx_with_trace_id() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (bank_forks, alice) = new_bank_forks();
+        let blockhash = bank_forks.read().unwrap().working_bank().last_blockhash();
+
+        let mut io = MetaIoHandler::default();
+        let rpc = RpcSolImpl;
+        io.extend_with(rpc.to_delegate());
+        let transaction_traces = Arc::new(TransactionTraces::default());
+        let meta = Meta {
+            request_processor: Arc::new(RwLock::new(JsonRpcRequestProcessor::new(
+                StorageState::default(),
+                JsonRpcConfig::default(),
+                bank_forks,
+                None,
+                &exit,
+                None,
+                transaction_traces.clone(),
+                Arc::new(ReplayErrors::default()),
+            ))),
+            cluster_info: Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                ContactInfo::new_with_socketaddr(&socketaddr!("127.0.0.1:1234")),
+            ))),
+        };
+
+        let tx = system_transaction::transfer(&alice, &Pubkey::new_rand(), 20, blockhash);
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"sendTxn","params":[{}]}}"#,
+            json!(serialize(&tx).unwrap())
+        );
+        io.handle_request_sync(&req, meta.clone());
+        assert_eq!(transaction_traces.trace_id(&tx.signatures[0]), None);
+
+        let tx = system_transaction::transfer(&alice, &Pubkey::new_rand(), 20, blockhash);
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"sendTxn","params":[{}, "dapp-1"]}}"#,
+            json!(serialize(&tx).unwrap())
+        );
+        let res = io.handle_request_sync(&req, meta);
+        let result: Value = serde_json::from_str(&res.expect("actual response"))
+            .expect("actual response deserialization");
+        assert_eq!(result["result"], tx.signatures[0].to_string());
+        assert_eq!(
+            transaction_traces.trace_id(&tx.signatures[0]),
+            Some("dapp-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_rpc_send_transaction_batch() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (bank_forks, alice) = new_bank_forks();
+        let blockhash = bank_forks.read().unwrap().working_bank().last_blockhash();
+
+        let mut io = MetaIoHandler::default();
+        let rpc = RpcSolImpl;
+        io.extend_with(rpc.to_delegate());
+        let meta = Meta {
+            request_processor: Arc::new(RwLock::new(JsonRpcRequestProcessor::new(
+                StorageState::default(),
+                JsonRpcConfig::default(),
+                bank_forks,
+                None,
+                &exit,
+                None,
+                Arc::new(TransactionTraces::default()),
+                Arc::new(ReplayErrors::default()),
+            ))),
+            cluster_info: Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                ContactInfo::new_with_socketaddr(&socketaddr!("127.0.0.1:1234")),
+            ))),
+        };
+        let send_batch = |batch: &[Vec<u8>], all_or_nothing: bool| -> Value {
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"sendTxnBatch","params":[{}, {{"allOrNothing":{}}}]}}"#,
+                json!(batch),
+                all_or_nothing
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            serde_json::from_str(&res.expect("actual response"))
+                .expect("actual response deserialization")
+        };
+        let statuses = |result: Value| -> Vec<RpcBatchTransactionStatus> {
+            serde_json::from_value(result["result"].clone()).unwrap()
+        };
+
+        let tx = system_transaction::transfer(&alice, &Pubkey::new_rand(), 20, blockhash);
+        let stale_tx =
+            system_transaction::transfer(&alice, &Pubkey::new_rand(), 20, Hash::default());
+        let batch = vec![
+            serialize(&tx).unwrap(),
+            serialize(&stale_tx).unwrap(),
+            vec![0; 8],
+            serialize(&tx).unwrap(),
+        ];
+        let sent = RpcBatchTransactionStatus {
+            signature: Some(tx.signatures[0].to_string()),
+            error: None,
+        };
+        let stale = RpcBatchTransactionStatus {
+            signature: None,
+            error: Some("blockhash not found or too old".to_string()),
+        };
+
+        let results = statuses(send_batch(&batch, false));
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0], sent);
+        assert_eq!(results[1], stale);
+        assert!(results[2].error.is_some());
+        assert_eq!(results[3], sent);
+
+        // nothing goes out, and nothing after the first failure is checked
+        let results = statuses(send_batch(&batch, true));
+        assert_eq!(
+            results,
+            vec![
+                RpcBatchTransactionStatus {
+                    signature: sent.signature.clone(),
+                    error: Some("not sent, another transaction failed preflight".to_string()),
+                },
+                stale,
+            ]
+        );
+        assert_eq!(statuses(send_batch(&batch[..1], true)), vec![sent]);
+
+        let result = send_batch(&vec![batch[0].clone(); MAX_TRANSACTION_BATCH + 1], false);
+        assert_eq!(result["error"]["code"], ErrorCode::InvalidRequest.code());
+        let result = send_batch(&[], false);
+        assert_eq!(result["error"]["code"], ErrorCode::InvalidRequest.code());
+    }
+
+    #[test]
+    fn test_rpc_get_stake_activation() {
+        let GenesisBlockInfo {
+            mut genesis_block,
+            mint_keypair,
+            voting_keypair,
+        } = create_genesis_block(10_000);
+        let stake_pubkey = Pubkey::new_rand();
+        genesis_block.accounts.push((
+            stake_pubkey,
+            stake_state::create_delegate_stake_account(
+                &voting_keypair.pubkey(),
+                &VoteState::default(),
+                100,
+            ),
+        ));
+        let bank = Bank::new(&genesis_block);
+        let exit = Arc::new(AtomicBool::new(false));
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            Arc::new(RwLock::new(BankForks::new(bank.slot(), bank))),
+            None,
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
+        );
+
+        assert_eq!(
+            request_processor.get_stake_activation(&stake_pubkey),
+            Ok(RpcStakeActivation {
+                state: StakeActivationState::Active,
+                effective: 100,
+                activating: 0,
+                voter_pubkey: Some(voting_keypair.pubkey().to_string()),
+            })
+        );
+        assert!(request_processor
+            .get_stake_activation(&mint_keypair.pubkey())
+            .is_err());
+        assert!(request_processor
+            .get_stake_activation(&Pubkey::new_rand())
+            .is_err());
+    }
+
+    #[test]
+    fn test_stake_activation() {
+        const COOLDOWN_TICKS: u64 = 64;
+        let voter_pubkey = Pubkey::new_rand();
+        let account =
+            stake_state::create_delegate_stake_account(&voter_pubkey, &VoteState::default(), 100);
+        let mut deactivated_account = account.clone();
+        deactivated_account
+            .set_state(&StakeState::Delegate {
+                voter_pubkey,
+                credits_observed: 0,
+                stake: 100,
+                deactivated: 10,
+            })
+            .unwrap();
+        let activation = |account: &Account, epoch_account: Option<&Account>, tick_height: u64| {
+            let activation = stake_activation(account, epoch_account, tick_height, COOLDOWN_TICKS);
+            (
+                activation.state,
+                activation.effective,
+                activation.activating,
+            )
+        };
+
+        // delegated since the current epoch's stakes were saved off
+        assert_eq!(
+            activation(&account, None, 0),
+            (StakeActivationState::Activating, 0, 100)
+        );
+        assert_eq!(
+            activation(&account, Some(&account), 0),
+            (StakeActivationState::Active, 100, 0)
+        );
+
+        // deactivated, still counted by the current epoch's stakes
+        assert_eq!(
+            activation(&deactivated_account, Some(&account), 10),
+            (StakeActivationState::Deactivating, 100, 0)
+        );
+        // no longer counted, but cooling down
+        assert_eq!(
+            activation(&deactivated_account, Some(&deactivated_account), 10),
+            (StakeActivationState::Deactivating, 0, 0)
+        );
+        assert_eq!(
+            activation(
+                &deactivated_account,
+                Some(&deactivated_account),
+                10 + COOLDOWN_TICKS
+            ),
+            (StakeActivationState::Inactive, 0, 0)
+        );
+        assert_eq!(
+            stake_activation(&deactivated_account, None, 0, COOLDOWN_TICKS).voter_pubkey,
+            Some(voter_pubkey.to_string())
+        );
+
+        let undelegated = Account::new(
+            100,
+            0,
+            std::mem::size_of::<StakeState>(),
+            &morgan_stake_api::id(),
+        );
+        assert_eq!(
+            stake_activation(&undelegated, None, 0, COOLDOWN_TICKS),
+            RpcStakeActivation {
+                state: StakeActivationState::Inactive,
+                effective: 0,
+                activating: 0,
+                voter_pubkey: None,
+            }
+        );
+    }
+
     #[test]
     fn test_rpc_get_tpu_addr() {
         let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
@@ -1036,7 +2196,11 @@ mod tests {
             StorageState::default(),
             JsonRpcConfig::default(),
             new_bank_forks().0,
+            None,
             &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
         );
         assert_eq!(request_processor.fullnode_exit(), Ok(false));
         assert_eq!(exit.load(Ordering::Relaxed), false);
@@ -1051,9 +2215,391 @@ mod tests {
             StorageState::default(),
             config,
             new_bank_forks().0,
+            None,
             &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
         );
         assert_eq!(request_processor.fullnode_exit(), Ok(true));
         assert_eq!(exit.load(Ordering::Relaxed), true);
     }
+
+    #[test]
+    fn test_rpc_get_slot_entries() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_rand(), 1, blockhash);
+        let entry = next_entry(&blockhash, 1, vec![tx.clone()]);
+        let blobs = entries_to_blobs(&vec![entry.clone()], 1, 0, false);
+        blocktree.insert_data_blobs(blobs.iter()).unwrap();
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let mut config = JsonRpcConfig::default();
+        config.enable_debug_rpc = true;
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            config,
+            new_bank_forks().0,
+            Some(blocktree.clone()),
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
+        );
+        let ticks = request_processor.get_slot_entries(0).unwrap();
+        assert_eq!(ticks.len() as u64, genesis_block.ticks_per_slot);
+        assert!(ticks.iter().all(|tick| tick.signatures.is_empty()));
+        assert_eq!(
+            request_processor.get_slot_entries(1),
+            Ok(vec![RpcEntry {
+                num_hashes: 1,
+                hash: entry.hash.to_string(),
+                signatures: vec![tx.signatures[0].to_string()],
+            }])
+        );
+        assert_eq!(request_processor.get_slot_entries(2), Ok(vec![]));
+
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            new_bank_forks().0,
+            Some(blocktree),
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
+        );
+        assert_eq!(
+            request_processor.get_slot_entries(1),
+            Err(Error::method_not_found())
+        );
+    }
+
+    #[test]
+    fn test_rpc_get_ledger_range() {
+        let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(10_000);
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        for slot in 1..4 {
+            let entry = next_entry(&blockhash, 1, vec![]);
+            let blobs = entries_to_blobs(&vec![entry], slot, slot - 1, false);
+            blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        }
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let request_processor = Arc::new(RwLock::new(JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            new_bank_forks().0,
+            Some(blocktree.clone()),
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
+        )));
+        let mut io = MetaIoHandler::default();
+        io.extend_with(RpcSolImpl.to_delegate());
+        let meta = Meta {
+            request_processor,
+            cluster_info: Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                ContactInfo::default(),
+            ))),
+        };
+        let request = |method| {
+            let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"{}"}}"#, method);
+            let res = io.handle_request_sync(&req, meta.clone()).unwrap();
+            serde_json::from_str::<Value>(&res).unwrap()["result"].clone()
+        };
+        assert_eq!(
+            request("getLedgerRange"),
+            json!({"firstSlot": 0, "lastSlot": 3})
+        );
+        assert_eq!(request("getMinimumLedgerSlot"), json!(0));
+    }
+
+    #[test]
+    fn test_rpc_get_fork_replay_errors() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let replay_errors = Arc::new(ReplayErrors::default());
+        let mut errors = ForkReplayErrors::default();
+        errors.record_transaction_error(3, &transaction::TransactionError::AccountNotFound);
+        replay_errors.update(2, &errors);
+
+        let mut config = JsonRpcConfig::default();
+        config.enable_debug_rpc = true;
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            config,
+            new_bank_forks().0,
+            None,
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            replay_errors.clone(),
+        );
+        let forks = request_processor.get_fork_replay_errors().unwrap();
+        assert_eq!(
+            serde_json::to_value(&forks).unwrap(),
+            json!([{
+                "slot": 2,
+                "entryVerificationFailures": 0,
+                "signatureVerificationFailures": 0,
+                "transactionErrors": {"AccountNotFound": 1},
+                "failedEntry": 3,
+            }])
+        );
+
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            new_bank_forks().0,
+            None,
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            replay_errors,
+        );
+        assert_eq!(
+            request_processor.get_fork_replay_errors(),
+            Err(Error::method_not_found())
+        );
+    }
+
+    #[test]
+    fn test_rpc_get_slot_proof() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_rand(), 1, blockhash);
+        let entry = next_entry(&blockhash, 1, vec![tx]);
+        let tick = next_entry(&entry.hash, 1, vec![]);
+        let slot1_entries = vec![entry, tick.clone()];
+        let slot3_entries = vec![next_entry(&tick.hash, 2, vec![])];
+        let slot4_entries = vec![next_entry(&slot3_entries[0].hash, 1, vec![])];
+        let blobs = entries_to_blobs(&slot1_entries, 1, 0, true);
+        blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        let blobs = entries_to_blobs(&slot3_entries, 3, 1, true);
+        blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        let blobs = entries_to_blobs(&slot4_entries, 4, 3, true);
+        blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        blocktree.set_root(3, 0).unwrap();
+
+        let (bank_forks, _) = new_bank_forks();
+        let bank0 = bank_forks.read().unwrap().working_bank();
+        let bank3 = Bank::new_from_parent(&bank0, &Pubkey::default(), 3);
+        bank3.freeze();
+        let bank3_hash = bank3.hash();
+        bank_forks.write().unwrap().insert(bank3);
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            JsonRpcConfig::default(),
+            bank_forks,
+            Some(blocktree),
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
+        );
+        let to_rpc_entries = |entries: &[Entry]| entries.iter().map(RpcEntry::from).collect();
+        assert_eq!(
+            request_processor.get_slot_proof(3, 0),
+            Ok(RpcSlotProof {
+                slot: 3,
+                trusted_slot: 0,
+                start_hash: blockhash.to_string(),
+                slots: vec![
+                    RpcSlotEntries {
+                        slot: 1,
+                        parent_slot: 0,
+                        entries: to_rpc_entries(&slot1_entries),
+                    },
+                    RpcSlotEntries {
+                        slot: 3,
+                        parent_slot: 1,
+                        entries: to_rpc_entries(&slot3_entries),
+                    },
+                ],
+                bank_hash: Some(bank3_hash.to_string()),
+            })
+        );
+
+        let proof = request_processor.get_slot_proof(1, 0).unwrap();
+        assert_eq!(proof.slots.len(), 1);
+        assert_eq!(proof.bank_hash, None);
+        let proof = request_processor.get_slot_proof(3, 1).unwrap();
+        assert_eq!(proof.start_hash, tick.hash.to_string());
+        assert_eq!(proof.slots.len(), 1);
+
+        // slot 2 was skipped, slot 4 isn't rooted
+        assert!(request_processor.get_slot_proof(3, 2).is_err());
+        assert!(request_processor.get_slot_proof(4, 3).is_err());
+        assert!(request_processor.get_slot_proof(1, 3).is_err());
+    }
+
+    #[test]
+    fn test_rpc_get_signatures_for_address() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let (ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let bob_pubkey = Pubkey::new_rand();
+        let tx1 = system_transaction::transfer(&mint_keypair, &bob_pubkey, 1, blockhash);
+        let tx2 = system_transaction::transfer(&mint_keypair, &bob_pubkey, 2, blockhash);
+        let entry1 = next_entry(&blockhash, 1, vec![tx1.clone()]);
+        let entry2 = next_entry(&entry1.hash, 1, vec![tx2.clone()]);
+        let blobs = entries_to_blobs(&vec![entry1], 1, 0, true);
+        blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        let blobs = entries_to_blobs(&vec![entry2], 2, 1, true);
+        blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        blocktree.set_root(2, 0).unwrap();
+
+        let mut io = MetaIoHandler::default();
+        let rpc = RpcSolImpl;
+        io.extend_with(rpc.to_delegate());
+        let (bank_forks, _) = new_bank_forks();
+        let exit = Arc::new(AtomicBool::new(false));
+        let meta = Meta {
+            request_processor: Arc::new(RwLock::new(JsonRpcRequestProcessor::new(
+                StorageState::default(),
+                JsonRpcConfig::default(),
+                bank_forks,
+                Some(blocktree),
+                &exit,
+                None,
+                Arc::new(TransactionTraces::default()),
+                Arc::new(ReplayErrors::default()),
+            ))),
+            cluster_info: Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                ContactInfo::default(),
+            ))),
+        };
+        let get_signatures = |params: String| -> Value {
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"getSignaturesForAddress","params":{}}}"#,
+                params
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            serde_json::from_str(&res.expect("actual response"))
+                .expect("actual response deserialization")
+        };
+
+        let result = get_signatures(format!(r#"["{}"]"#, bob_pubkey));
+        let expected = json!([
+            {"signature": tx2.signatures[0].to_string(), "slot": 2},
+            {"signature": tx1.signatures[0].to_string(), "slot": 1},
+        ]);
+        assert_eq!(result["result"], expected);
+
+        let result = get_signatures(format!(
+            r#"["{}", {{"before": "{}", "limit": 1}}]"#,
+            bob_pubkey, tx2.signatures[0]
+        ));
+        assert_eq!(result["result"], json!([expected[1]]));
+        let result = get_signatures(format!(
+            r#"["{}", {{"until": "{}"}}]"#,
+            bob_pubkey, tx1.signatures[0]
+        ));
+        assert_eq!(result["result"], json!([expected[0]]));
+
+        let result = get_signatures(format!(r#"["{}", {{"limit": 0}}]"#, bob_pubkey));
+        assert_eq!(result["error"]["code"], ErrorCode::InvalidRequest.code());
+        let result = get_signatures(format!(
+            r#"["{}", {{"limit": {}}}]"#,
+            bob_pubkey,
+            MAX_GET_SIGNATURES_FOR_ADDRESS_LIMIT + 1
+        ));
+        assert_eq!(result["error"]["code"], ErrorCode::InvalidRequest.code());
+        let result = get_signatures(format!(r#"["{}", {{"before": "bad"}}]"#, bob_pubkey));
+        assert_eq!(result["error"]["code"], ErrorCode::InvalidRequest.code());
+
+        let get_confirmed_signatures = |start_slot: u64, end_slot: u64| -> Value {
+            let req = format!(
+                r#"{{"jsonrpc":"2.0","id":1,"method":"getConfirmedSignaturesForAddress","params":["{}", {}, {}]}}"#,
+                bob_pubkey, start_slot, end_slot
+            );
+            let res = io.handle_request_sync(&req, meta.clone());
+            serde_json::from_str(&res.expect("actual response"))
+                .expect("actual response deserialization")
+        };
+        assert_eq!(
+            get_confirmed_signatures(0, 2)["result"],
+            json!([tx1.signatures[0].to_string(), tx2.signatures[0].to_string()])
+        );
+        assert_eq!(
+            get_confirmed_signatures(2, 5)["result"],
+            json!([tx2.signatures[0].to_string()])
+        );
+        assert_eq!(
+            get_confirmed_signatures(2, 1)["error"]["code"],
+            ErrorCode::InvalidParams.code()
+        );
+        assert_eq!(
+            get_confirmed_signatures(0, MAX_GET_CONFIRMED_SIGNATURES_FOR_ADDRESS_SLOT_RANGE + 1)
+                ["error"]["code"],
+            ErrorCode::InvalidParams.code()
+        );
+    }
+
+    #[test]
+    fn test_rpc_get_slot_entries_from_ledger_archive() {
+        use crate::ledgerArchive::MemoryObjectStore;
+
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let (archived_ledger_path, blockhash) = create_new_tmp_ledger!(&genesis_block);
+        let archived_blocktree = Blocktree::open(&archived_ledger_path).unwrap();
+        let tx = system_transaction::transfer(&mint_keypair, &Pubkey::new_rand(), 1, blockhash);
+        let entry = next_entry(&blockhash, 1, vec![tx]);
+        let blobs = entries_to_blobs(&vec![entry.clone()], 1, 0, false);
+        archived_blocktree.insert_data_blobs(blobs.iter()).unwrap();
+        archived_blocktree.set_root(1, 0).unwrap();
+        let ledger_archive =
+            LedgerArchive::new(Box::new(MemoryObjectStore::default()), "ledger", 4);
+        ledger_archive
+            .archive_segment(&archived_blocktree, 0)
+            .unwrap();
+
+        // A node whose own ledger never saw slot 1
+        let (ledger_path, _) = create_new_tmp_ledger!(&genesis_block);
+        let blocktree = Arc::new(Blocktree::open(&ledger_path).unwrap());
+        let exit = Arc::new(AtomicBool::new(false));
+        let mut config = JsonRpcConfig::default();
+        config.enable_debug_rpc = true;
+        let mut request_processor = JsonRpcRequestProcessor::new(
+            StorageState::default(),
+            config,
+            new_bank_forks().0,
+            Some(blocktree),
+            &exit,
+            None,
+            Arc::new(TransactionTraces::default()),
+            Arc::new(ReplayErrors::default()),
+        );
+        assert_eq!(request_processor.get_slot_entries(1), Ok(vec![]));
+
+        request_processor.ledger_archive = Some(Arc::new(ledger_archive));
+        assert_eq!(
+            request_processor.get_slot_entries(1),
+            Ok(vec![RpcEntry::from(&entry)])
+        );
+        assert_eq!(request_processor.get_slot_entries(5), Ok(vec![]));
+    }
 }
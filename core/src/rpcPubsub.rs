@@ -1,6 +1,6 @@
 //! The `pubsub` module implements a threaded subscription service on client RPC request
 
-use crate::rpcSubscriptions::{Confirmations, RpcSubscriptions};
+use crate::rpcSubscriptions::{Confirmations, DataSlice, RpcSubscriptions, RpcVote};
 use jsonrpc_core::{Error, ErrorCode, Result};
 use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::typed::Subscriber;
@@ -17,7 +17,8 @@ pub trait RpcSolPubSub {
     type Metadata;
 
     // Get notification every time account data is changed
-    // Accepts pubkey parameter as base-58 encoded string
+    // Accepts pubkey parameter as base-58 encoded string, and an optional data
+    // slice to limit the account data included in each notification
     #[pubsub(
         subscription = "accountNotification",
         subscribe,
@@ -29,6 +30,7 @@ pub trait RpcSolPubSub {
         _: Subscriber<Account>,
         _: String,
         _: Option<Confirmations>,
+        _: Option<DataSlice>,
     );
 
     // Unsubscribe from account notification subscription.
@@ -40,7 +42,8 @@ pub trait RpcSolPubSub {
     fn account_unsubscribe(&self, _: Option<Self::Metadata>, _: SubscriptionId) -> Result<bool>;
 
     // Get notification every time account data owned by a particular program is changed
-    // Accepts pubkey parameter as base-58 encoded string
+    // Accepts pubkey parameter as base-58 encoded string, and an optional data
+    // slice to limit the account data included in each notification
     #[pubsub(
         subscription = "programNotification",
         subscribe,
@@ -52,6 +55,7 @@ pub trait RpcSolPubSub {
         _: Subscriber<(String, Account)>,
         _: String,
         _: Option<Confirmations>,
+        _: Option<DataSlice>,
     );
 
     // Unsubscribe from account notification subscription.
@@ -84,6 +88,19 @@ pub trait RpcSolPubSub {
         name = "signatureUnsubscribe"
     )]
     fn signature_unsubscribe(&self, _: Option<Self::Metadata>, _: SubscriptionId) -> Result<bool>;
+
+    // Get notification every time a vote of the given vote account is seen in gossip
+    // Accepts vote account pubkey parameter as base-58 encoded string
+    #[pubsub(subscription = "voteNotification", subscribe, name = "voteSubscribe")]
+    fn vote_subscribe(&self, _: Self::Metadata, _: Subscriber<RpcVote>, _: String);
+
+    // Unsubscribe from vote notification subscription.
+    #[pubsub(
+        subscription = "voteNotification",
+        unsubscribe,
+        name = "voteUnsubscribe"
+    )]
+    fn vote_unsubscribe(&self, _: Option<Self::Metadata>, _: SubscriptionId) -> Result<bool>;
 }
 
 #[derive(Default)]
@@ -118,6 +135,7 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         subscriber: Subscriber<Account>,
         pubkey_str: String,
         confirmations: Option<Confirmations>,
+        data_slice: Option<DataSlice>,
     ) {
         match param::<Pubkey>(&pubkey_str, "pubkey") {
             Ok(pubkey) => {
@@ -132,8 +150,13 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
                 );
                 let sink = subscriber.assign_id(sub_id.clone()).unwrap();
 
-                self.subscriptions
-                    .add_account_subscription(&pubkey, confirmations, &sub_id, &sink)
+                self.subscriptions.add_account_subscription(
+                    &pubkey,
+                    confirmations,
+                    data_slice,
+                    &sub_id,
+                    &sink,
+                )
             }
             Err(e) => subscriber.reject(e).unwrap(),
         }
@@ -168,6 +191,7 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
         subscriber: Subscriber<(String, Account)>,
         pubkey_str: String,
         confirmations: Option<Confirmations>,
+        data_slice: Option<DataSlice>,
     ) {
         match param::<Pubkey>(&pubkey_str, "pubkey") {
             Ok(pubkey) => {
@@ -182,8 +206,13 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
                 );
                 let sink = subscriber.assign_id(sub_id.clone()).unwrap();
 
-                self.subscriptions
-                    .add_program_subscription(&pubkey, confirmations, &sub_id, &sink)
+                self.subscriptions.add_program_subscription(
+                    &pubkey,
+                    confirmations,
+                    data_slice,
+                    &sub_id,
+                    &sink,
+                )
             }
             Err(e) => subscriber.reject(e).unwrap(),
         }
@@ -277,6 +306,49 @@ impl RpcSolPubSub for RpcSolPubSubImpl {
             })
         }
     }
+
+    fn vote_subscribe(
+        &self,
+        _meta: Self::Metadata,
+        subscriber: Subscriber<RpcVote>,
+        pubkey_str: String,
+    ) {
+        match param::<Pubkey>(&pubkey_str, "pubkey") {
+            Ok(vote_pubkey) => {
+                let id = self.uid.fetch_add(1, atomic::Ordering::SeqCst);
+                let sub_id = SubscriptionId::Number(id as u64);
+                println!("{}",
+                    printLn(
+                        format!("vote_subscribe: account={:?} id={:?}", vote_pubkey, sub_id).to_string(),
+                        module_path!().to_string()
+                    )
+                );
+                let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+
+                self.subscriptions
+                    .add_vote_subscription(&vote_pubkey, &sub_id, &sink)
+            }
+            Err(e) => subscriber.reject(e).unwrap(),
+        }
+    }
+
+    fn vote_unsubscribe(&self, _meta: Option<Self::Metadata>, id: SubscriptionId) -> Result<bool> {
+        println!("{}",
+            printLn(
+                format!("vote_unsubscribe: id={:?}", id).to_string(),
+                module_path!().to_string()
+            )
+        );
+        if self.subscriptions.remove_vote_subscription(&id) {
+            Ok(true)
+        } else {
+            Err(Error {
+                code: ErrorCode::InvalidParams,
+                message: "Invalid Request: Subscription id does not exist".into(),
+                data: None,
+            })
+        }
+    }
 }
 
 #[cfg(test)]
@@ -434,6 +506,7 @@ mod tests {
             subscriber,
             contract_state.pubkey().to_string(),
             None,
+            None,
         );
 
         let tx = system_transaction::create_user_account(
@@ -561,7 +634,13 @@ mod tests {
         let rpc = RpcSolPubSubImpl::default();
         let session = create_session();
         let (subscriber, _id_receiver, mut receiver) = Subscriber::new_test("accountNotification");
-        rpc.account_subscribe(session, subscriber, bob.pubkey().to_string(), Some(2));
+        rpc.account_subscribe(
+            session,
+            subscriber,
+            bob.pubkey().to_string(),
+            Some(2),
+            None,
+        );
 
         let tx = system_transaction::transfer(&alice, &bob.pubkey(), 100, blockhash);
         bank_forks
@@ -590,7 +669,13 @@ mod tests {
         let rpc = RpcSolPubSubImpl::default();
         let session = create_session();
         let (subscriber, _id_receiver, mut receiver) = Subscriber::new_test("accountNotification");
-        rpc.account_subscribe(session, subscriber, bob.pubkey().to_string(), Some(2));
+        rpc.account_subscribe(
+            session,
+            subscriber,
+            bob.pubkey().to_string(),
+            Some(2),
+            None,
+        );
 
         let tx = system_transaction::transfer(&alice, &bob.pubkey(), 100, blockhash);
         bank_forks
@@ -629,4 +714,38 @@ mod tests {
             assert_eq!(serde_json::to_string(&expected).unwrap(), response);
         }
     }
+
+    #[test]
+    fn test_vote_subscribe_unsubscribe() {
+        let session = create_session();
+
+        let mut io = PubSubHandler::default();
+        let rpc = RpcSolPubSubImpl::default();
+        io.extend_with(rpc.to_delegate());
+
+        let req = format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"voteSubscribe","params":["{}"]}}"#,
+            Pubkey::new_rand()
+        );
+        let res = io.handle_request_sync(&req, session.clone());
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":0,"id":1}}"#);
+        let expected: Response = serde_json::from_str(&expected).unwrap();
+        let result: Response = serde_json::from_str(&res.unwrap()).unwrap();
+        assert_eq!(expected, result);
+
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"voteUnsubscribe","params":[0]}}"#);
+        let res = io.handle_request_sync(&req, session.clone());
+        let expected = format!(r#"{{"jsonrpc":"2.0","result":true,"id":1}}"#);
+        let expected: Response = serde_json::from_str(&expected).unwrap();
+        let result: Response = serde_json::from_str(&res.unwrap()).unwrap();
+        assert_eq!(expected, result);
+
+        // Test bad parameter
+        let req = format!(r#"{{"jsonrpc":"2.0","id":1,"method":"voteSubscribe","params":["x"]}}"#);
+        let res = io.handle_request_sync(&req, session.clone());
+        let expected = format!(r#"{{"jsonrpc":"2.0","error":{{"code":-32602,"message":"Invalid Request: Invalid pubkey provided"}},"id":1}}"#);
+        let expected: Response = serde_json::from_str(&expected).unwrap();
+        let result: Response = serde_json::from_str(&res.unwrap()).unwrap();
+        assert_eq!(expected, result);
+    }
 }
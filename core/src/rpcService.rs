@@ -2,33 +2,335 @@
 
 // use crate::bank_forks::BankForks;
 use crate::treasuryForks::BankForks;
+use crate::blockBufferPool::Blocktree;
 use crate::clusterMessage::ClusterInfo;
 use crate::rpc::*;
+use crate::samplePerformanceService::SamplePerformanceService;
+use crate::sendTransactionService::SendTransactionService;
 use crate::service::Service;
 use crate::storageStage::StorageState;
-use jsonrpc_core::MetaIoHandler;
-use jsonrpc_http_server::{hyper, AccessControlAllowOrigin, DomainsValidation, ServerBuilder};
+use crate::replayErrors::ReplayErrors;
+use crate::transactionTrace::TransactionTraces;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+use jsonrpc_core::futures::{future, stream, Future, Stream};
+use jsonrpc_core::{ErrorCode, Failure, MetaIoHandler, Output};
+use jsonrpc_http_server::{
+    hyper, AccessControlAllowOrigin, DomainsValidation, RequestMiddleware, RequestMiddlewareAction,
+    ServerBuilder,
+};
+use morgan_runtime::snapshot::{snapshot_archive_path, snapshot_slots};
+use serde_json::json;
+use std::fs::File;
+use std::io::{self, Read, Write};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::channel;
 use std::sync::{Arc, RwLock};
 use std::thread::{self, sleep, Builder, JoinHandle};
 use std::time::Duration;
 use morgan_helper::logHelper::*;
 
+// Same limit jsonrpc_http_server applies to the requests it reads itself
+const MAX_REQUEST_BODY_BYTES: usize = 5 * 1024 * 1024;
+
+// Responses smaller than this aren't worth compressing
+const MIN_COMPRESSED_RESPONSE_BYTES: usize = 1024;
+
+// Server error code returned in place of a response above `max_response_bytes`
+const RESPONSE_TOO_LARGE: i64 = -32008;
+
+/// GET path the newest full snapshot archive is served under, the others are served under
+/// their file names
+pub const SNAPSHOT_DOWNLOAD_PATH: &str = "/snapshot.bin.gz";
+
+// Bytes of a snapshot archive sent at once
+const SNAPSHOT_CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ContentEncoding {
+    Gzip,
+    Deflate,
+    Identity,
+}
+
+impl ContentEncoding {
+    /// Pick an encoding from an `Accept-Encoding` header, preferring gzip
+    fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let accepted: Vec<_> = accept_encoding
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|coding| {
+                let mut params = coding.split(';').map(str::trim);
+                let name = params.next()?.to_ascii_lowercase();
+                let refused = params.any(|p| {
+                    p.starts_with("q=") && p[2..].parse::<f32>().map(|q| q == 0.0) == Ok(true)
+                });
+                if refused {
+                    None
+                } else {
+                    Some(name)
+                }
+            })
+            .collect();
+        let accepts = |name: &str| accepted.iter().any(|a| a == name || a == "*");
+        if accepts("gzip") {
+            ContentEncoding::Gzip
+        } else if accepts("deflate") {
+            ContentEncoding::Deflate
+        } else {
+            ContentEncoding::Identity
+        }
+    }
+
+    fn header_value(self) -> Option<&'static str> {
+        match self {
+            ContentEncoding::Gzip => Some("gzip"),
+            ContentEncoding::Deflate => Some("deflate"),
+            ContentEncoding::Identity => None,
+        }
+    }
+
+    fn encode(self, body: Vec<u8>) -> Vec<u8> {
+        match self {
+            ContentEncoding::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], Compression::default());
+                encoder.write_all(&body).unwrap();
+                encoder.finish().unwrap()
+            }
+            ContentEncoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+                encoder.write_all(&body).unwrap();
+                encoder.finish().unwrap()
+            }
+            ContentEncoding::Identity => body,
+        }
+    }
+}
+
+/// Replace every result of a response larger than `max_response_bytes` with an
+/// error telling the client to ask for less at a time
+fn cap_response(response: String, max_response_bytes: usize) -> String {
+    if response.len() <= max_response_bytes {
+        return response;
+    }
+    let response_len = response.len();
+    let too_large = |output: Output| match output {
+        Output::Success(success) => Output::Failure(Failure {
+            jsonrpc: success.jsonrpc,
+            error: jsonrpc_core::Error {
+                code: ErrorCode::ServerError(RESPONSE_TOO_LARGE),
+                message: format!(
+                    "Response of {} bytes exceeds the {} byte limit",
+                    response_len, max_response_bytes
+                ),
+                data: Some(json!({
+                    "maxResponseBytes": max_response_bytes,
+                    "hint": "request fewer items per call, e.g. split batches or use a dataSlice",
+                })),
+            },
+            id: success.id,
+        }),
+        failure => failure,
+    };
+    match serde_json::from_str(&response) {
+        Ok(jsonrpc_core::Response::Single(output)) => {
+            serde_json::to_string(&jsonrpc_core::Response::Single(too_large(output))).unwrap()
+        }
+        Ok(jsonrpc_core::Response::Batch(outputs)) => {
+            let outputs = outputs.into_iter().map(too_large).collect();
+            serde_json::to_string(&jsonrpc_core::Response::Batch(outputs)).unwrap()
+        }
+        Err(_) => response,
+    }
+}
+
+fn http_response(
+    status: hyper::StatusCode,
+    body: String,
+    encoding: ContentEncoding,
+) -> hyper::Response<hyper::Body> {
+    let encoding = if body.len() < MIN_COMPRESSED_RESPONSE_BYTES {
+        ContentEncoding::Identity
+    } else {
+        encoding
+    };
+    let mut builder = hyper::Response::builder();
+    builder
+        .status(status)
+        .header(
+            hyper::header::CONTENT_TYPE,
+            "application/json; charset=utf-8",
+        )
+        .header(hyper::header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(hyper::header::VARY, "Accept-Encoding");
+    if let Some(value) = encoding.header_value() {
+        builder.header(hyper::header::CONTENT_ENCODING, value);
+    }
+    builder
+        .body(hyper::Body::from(encoding.encode(body.into_bytes())))
+        .unwrap()
+}
+
+/// The archive under `snapshot_path` a GET of `request_path` asks for, if there is one
+fn requested_snapshot_archive(snapshot_path: &Path, request_path: &str) -> Option<PathBuf> {
+    let slots = snapshot_slots(snapshot_path).ok()?;
+    if request_path == SNAPSHOT_DOWNLOAD_PATH {
+        return slots
+            .first()
+            .map(|slot| snapshot_archive_path(snapshot_path, *slot));
+    }
+    // only names of archives that exist are answered, nothing else under the directory
+    slots
+        .into_iter()
+        .map(|slot| snapshot_archive_path(snapshot_path, slot))
+        .find(|archive_path| {
+            archive_path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .map_or(false, |file_name| {
+                    request_path.trim_start_matches('/') == file_name
+                })
+        })
+}
+
+/// Reads a snapshot archive `SNAPSHOT_CHUNK_SIZE` bytes at a time, to stream it out
+struct ArchiveChunks(File);
+
+impl Iterator for ArchiveChunks {
+    type Item = io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = vec![0; SNAPSHOT_CHUNK_SIZE];
+        match self.0.read(&mut chunk) {
+            Ok(0) => None,
+            Ok(len) => {
+                chunk.truncate(len);
+                Some(Ok(chunk))
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+fn snapshot_response(archive_path: &Path) -> hyper::Response<hyper::Body> {
+    let file = match File::open(archive_path) {
+        Ok(file) => file,
+        // removed by the snapshot service since it was looked up
+        Err(_) => {
+            return http_response(
+                hyper::StatusCode::NOT_FOUND,
+                String::new(),
+                ContentEncoding::Identity,
+            )
+        }
+    };
+    let mut builder = hyper::Response::builder();
+    builder
+        .status(hyper::StatusCode::OK)
+        .header(hyper::header::CONTENT_TYPE, "application/octet-stream");
+    if let Ok(metadata) = file.metadata() {
+        builder.header(hyper::header::CONTENT_LENGTH, metadata.len());
+    }
+    builder
+        .body(hyper::Body::wrap_stream(stream::iter_result(
+            ArchiveChunks(file),
+        )))
+        .unwrap()
+}
+
+/// Answers RPC POSTs itself so responses can be capped and compressed, which
+/// jsonrpc_http_server has no hook for, and GETs of snapshot archives
+struct RpcRequestMiddleware {
+    io: Arc<MetaIoHandler<Meta>>,
+    meta: Meta,
+    max_response_bytes: usize,
+    snapshot_path: Option<PathBuf>,
+}
+
+impl RequestMiddleware for RpcRequestMiddleware {
+    fn on_request(&self, request: hyper::Request<hyper::Body>) -> RequestMiddlewareAction {
+        if request.method() == hyper::Method::GET {
+            let archive_path = self.snapshot_path.as_ref().and_then(|snapshot_path| {
+                requested_snapshot_archive(snapshot_path, request.uri().path())
+            });
+            if let Some(archive_path) = archive_path {
+                return RequestMiddlewareAction::Respond {
+                    should_validate_hosts: true,
+                    response: Box::new(future::ok(snapshot_response(&archive_path))),
+                };
+            }
+        }
+        if request.method() != hyper::Method::POST {
+            return request.into();
+        }
+        let encoding = ContentEncoding::negotiate(
+            request
+                .headers()
+                .get(hyper::header::ACCEPT_ENCODING)
+                .and_then(|value| value.to_str().ok()),
+        );
+        let io = self.io.clone();
+        let meta = self.meta.clone();
+        let max_response_bytes = self.max_response_bytes;
+
+        let response = request
+            .into_body()
+            .fold((vec![], false), |(mut body, too_large), chunk| {
+                // keep draining the connection, but stop buffering an oversized request
+                let too_large = too_large || body.len() + chunk.len() > MAX_REQUEST_BODY_BYTES;
+                if !too_large {
+                    body.extend_from_slice(&chunk);
+                }
+                future::ok::<_, hyper::Error>((body, too_large))
+            })
+            .and_then(move |(body, too_large)| {
+                if too_large {
+                    return future::Either::A(future::ok(http_response(
+                        hyper::StatusCode::PAYLOAD_TOO_LARGE,
+                        String::new(),
+                        ContentEncoding::Identity,
+                    )));
+                }
+                let body = String::from_utf8_lossy(&body);
+                future::Either::B(io.handle_request(&body, meta).then(move |response| {
+                    // notifications have no response
+                    let response = response.ok().and_then(|r| r).unwrap_or_default();
+                    Ok::<_, hyper::Error>(http_response(
+                        hyper::StatusCode::OK,
+                        cap_response(response, max_response_bytes),
+                        encoding,
+                    ))
+                }))
+            });
+        RequestMiddlewareAction::Respond {
+            should_validate_hosts: true,
+            response: Box::new(response),
+        }
+    }
+}
+
 pub struct JsonRpcService {
     thread_hdl: JoinHandle<()>,
+    send_transaction_service: SendTransactionService,
+    sample_performance_service: Option<SamplePerformanceService>,
 
     #[cfg(test)]
     pub request_processor: Arc<RwLock<JsonRpcRequestProcessor>>, // Used only by test_rpc_new()...
 }
 
 impl JsonRpcService {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         rpc_addr: SocketAddr,
         storage_state: StorageState,
         config: JsonRpcConfig,
         bank_forks: Arc<RwLock<BankForks>>,
+        blocktree: Option<Arc<Blocktree>>,
+        transaction_traces: &Arc<TransactionTraces>,
+        replay_errors: &Arc<ReplayErrors>,
         exit: &Arc<AtomicBool>,
     ) -> Self {
         // info!("{}", Info(format!("rpc bound to {:?}", rpc_addr).to_string()));
@@ -45,12 +347,30 @@ impl JsonRpcService {
                 module_path!().to_string()
             )
         );
-        let request_processor = Arc::new(RwLock::new(JsonRpcRequestProcessor::new(
+        let max_response_bytes = config.max_response_bytes;
+        let snapshot_path = config.snapshot_path.clone();
+        let (transaction_sender, transaction_receiver) = channel();
+        let send_transaction_service =
+            SendTransactionService::new(cluster_info, &bank_forks, transaction_receiver, exit);
+        let request_processor = JsonRpcRequestProcessor::new(
             storage_state,
             config,
-            bank_forks,
+            bank_forks.clone(),
+            blocktree.clone(),
             exit,
-        )));
+            Some(transaction_sender),
+            transaction_traces.clone(),
+            replay_errors.clone(),
+        );
+        let sample_performance_service = blocktree.map(|blocktree| {
+            SamplePerformanceService::new(
+                &bank_forks,
+                &blocktree,
+                request_processor.performance_samples(),
+                exit,
+            )
+        });
+        let request_processor = Arc::new(RwLock::new(request_processor));
         let request_processor_ = request_processor.clone();
 
         let cluster_info = cluster_info.clone();
@@ -63,11 +383,24 @@ impl JsonRpcService {
                 let rpc = RpcSolImpl;
                 io.extend_with(rpc.to_delegate());
 
+                let meta = Meta {
+                    request_processor: request_processor_,
+                    cluster_info,
+                };
+                // the middleware answers POSTs with its own handler
+                let mut middleware_io = MetaIoHandler::default();
+                middleware_io.extend_with(RpcSolImpl.to_delegate());
+                let middleware = RpcRequestMiddleware {
+                    io: Arc::new(middleware_io),
+                    meta: meta.clone(),
+                    max_response_bytes,
+                    snapshot_path,
+                };
+
                 let server =
-                    ServerBuilder::with_meta_extractor(io, move |_req: &hyper::Request<hyper::Body>| Meta {
-                        request_processor: request_processor_.clone(),
-                        cluster_info: cluster_info.clone(),
-                    }).threads(4)
+                    ServerBuilder::with_meta_extractor(io, move |_req: &hyper::Request<hyper::Body>| meta.clone())
+                    .request_middleware(middleware)
+                    .threads(4)
                         .cors(DomainsValidation::AllowOnly(vec![
                             AccessControlAllowOrigin::Any,
                         ]))
@@ -91,6 +424,8 @@ impl JsonRpcService {
             .unwrap();
         Self {
             thread_hdl,
+            send_transaction_service,
+            sample_performance_service,
             #[cfg(test)]
             request_processor,
         }
@@ -101,6 +436,10 @@ impl Service for JsonRpcService {
     type JoinReturnType = ();
 
     fn join(self) -> thread::Result<()> {
+        self.send_transaction_service.join()?;
+        if let Some(sample_performance_service) = self.sample_performance_service {
+            sample_performance_service.join()?;
+        }
         self.thread_hdl.join()
     }
 }
@@ -137,6 +476,9 @@ mod tests {
             StorageState::default(),
             JsonRpcConfig::default(),
             bank_forks,
+            None,
+            &Arc::new(TransactionTraces::default()),
+            &Arc::new(ReplayErrors::default()),
             &exit,
         );
         let thread = rpc_service.thread_hdl.thread();
@@ -153,4 +495,97 @@ mod tests {
         exit.store(true, Ordering::Relaxed);
         rpc_service.join().unwrap();
     }
+
+    #[test]
+    fn test_content_encoding_negotiate() {
+        assert_eq!(ContentEncoding::negotiate(None), ContentEncoding::Identity);
+        assert_eq!(
+            ContentEncoding::negotiate(Some("deflate, gzip;q=1.0")),
+            ContentEncoding::Gzip
+        );
+        assert_eq!(
+            ContentEncoding::negotiate(Some("gzip;q=0, deflate")),
+            ContentEncoding::Deflate
+        );
+        assert_eq!(ContentEncoding::negotiate(Some("*")), ContentEncoding::Gzip);
+        assert_eq!(
+            ContentEncoding::negotiate(Some("br")),
+            ContentEncoding::Identity
+        );
+
+        let body = vec![7; MIN_COMPRESSED_RESPONSE_BYTES];
+        let mut decoded = vec![];
+        flate2::read::GzDecoder::new(&ContentEncoding::Gzip.encode(body.clone())[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, body);
+    }
+
+    #[test]
+    fn test_requested_snapshot_archive() {
+        let snapshot_path = PathBuf::from("target").join("test_requested_snapshot_archive");
+        let _ignored = std::fs::remove_dir_all(&snapshot_path);
+        assert_eq!(
+            requested_snapshot_archive(&snapshot_path, SNAPSHOT_DOWNLOAD_PATH),
+            None
+        );
+        std::fs::create_dir_all(&snapshot_path).unwrap();
+        for slot in &[1, 3] {
+            File::create(snapshot_archive_path(&snapshot_path, *slot)).unwrap();
+        }
+
+        assert_eq!(
+            requested_snapshot_archive(&snapshot_path, SNAPSHOT_DOWNLOAD_PATH),
+            Some(snapshot_archive_path(&snapshot_path, 3))
+        );
+        assert_eq!(
+            requested_snapshot_archive(&snapshot_path, "/snapshot-1.bin.gz"),
+            Some(snapshot_archive_path(&snapshot_path, 1))
+        );
+        assert_eq!(
+            requested_snapshot_archive(&snapshot_path, "/snapshot-2.bin.gz"),
+            None
+        );
+        assert_eq!(
+            requested_snapshot_archive(&snapshot_path, "/../snapshot-1.bin.gz"),
+            None
+        );
+        let _ignored = std::fs::remove_dir_all(&snapshot_path);
+    }
+
+    #[test]
+    fn test_cap_response() {
+        let response = r#"{"jsonrpc":"2.0","result":"0123456789","id":1}"#.to_string();
+        assert_eq!(cap_response(response.clone(), 1024), response);
+
+        let capped: jsonrpc_core::Response =
+            serde_json::from_str(&cap_response(response, 16)).unwrap();
+        match capped {
+            jsonrpc_core::Response::Single(Output::Failure(failure)) => {
+                assert_eq!(failure.id, jsonrpc_core::Id::Num(1));
+                assert_eq!(
+                    failure.error.code,
+                    ErrorCode::ServerError(RESPONSE_TOO_LARGE)
+                );
+            }
+            _ => panic!("expected a failure"),
+        }
+
+        // errors are passed through as-is
+        let batch = r#"[{"jsonrpc":"2.0","result":"0123456789","id":1},{"jsonrpc":"2.0","error":{"code":-32601,"message":"Method not found"},"id":2}]"#;
+        let capped: jsonrpc_core::Response =
+            serde_json::from_str(&cap_response(batch.to_string(), 16)).unwrap();
+        match capped {
+            jsonrpc_core::Response::Batch(outputs) => {
+                assert_eq!(outputs.len(), 2);
+                match &outputs[1] {
+                    Output::Failure(failure) => {
+                        assert_eq!(failure.error.code, ErrorCode::MethodNotFound)
+                    }
+                    _ => panic!("expected a failure"),
+                }
+            }
+            _ => panic!("expected a batch"),
+        }
+    }
 }
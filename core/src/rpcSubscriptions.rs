@@ -12,24 +12,56 @@ use morgan_interface::account::Account;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::Signature;
 use morgan_interface::transaction;
-use morgan_vote_api::vote_state::MAX_LOCKOUT_HISTORY;
+use morgan_vote_api::vote_state::{Vote, MAX_LOCKOUT_HISTORY};
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
 
 pub type Confirmations = usize;
 
+/// Window of account data to include in a notification, so subscribers to large
+/// accounts only receive the bytes they care about
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct DataSlice {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl DataSlice {
+    /// Return a copy of `account` holding only the sliced data; a slice that runs
+    /// past the end of the data is truncated
+    pub fn apply(&self, account: &Account) -> Account {
+        let start = self.offset.min(account.data.len());
+        let end = start.saturating_add(self.length).min(account.data.len());
+        Account {
+            data: account.data[start..end].to_vec(),
+            ..account.clone()
+        }
+    }
+}
+
+/// A vote observed in gossip, sent to the subscribers of its vote account
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RpcVote {
+    pub slot: u64,
+    pub hash: String,
+    pub timestamp: u64, // When this node observed the vote, in ms since the epoch
+}
+
+type Subscription<S> = (Sink<S>, Confirmations, Option<DataSlice>);
+
 type RpcAccountSubscriptions =
-    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, (Sink<Account>, Confirmations)>>>;
+    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, Subscription<Account>>>>;
 type RpcProgramSubscriptions =
-    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, (Sink<(String, Account)>, Confirmations)>>>;
-type RpcSignatureSubscriptions = RwLock<
-    HashMap<Signature, HashMap<SubscriptionId, (Sink<transaction::Result<()>>, Confirmations)>>,
->;
+    RwLock<HashMap<Pubkey, HashMap<SubscriptionId, Subscription<(String, Account)>>>>;
+type RpcSignatureSubscriptions =
+    RwLock<HashMap<Signature, HashMap<SubscriptionId, Subscription<transaction::Result<()>>>>>;
+type RpcVoteSubscriptions = RwLock<HashMap<Pubkey, HashMap<SubscriptionId, Subscription<RpcVote>>>>;
 
 fn add_subscription<K, S>(
-    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, (Sink<S>, Confirmations)>>,
+    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, Subscription<S>>>,
     hashmap_key: &K,
     confirmations: Option<Confirmations>,
+    data_slice: Option<DataSlice>,
     sub_id: &SubscriptionId,
     sink: &Sink<S>,
 ) where
@@ -43,16 +75,16 @@ fn add_subscription<K, S>(
         confirmations
     };
     if let Some(current_hashmap) = subscriptions.get_mut(hashmap_key) {
-        current_hashmap.insert(sub_id.clone(), (sink.clone(), confirmations));
+        current_hashmap.insert(sub_id.clone(), (sink.clone(), confirmations, data_slice));
         return;
     }
     let mut hashmap = HashMap::new();
-    hashmap.insert(sub_id.clone(), (sink.clone(), confirmations));
+    hashmap.insert(sub_id.clone(), (sink.clone(), confirmations, data_slice));
     subscriptions.insert(*hashmap_key, hashmap);
 }
 
 fn remove_subscription<K, S>(
-    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, (Sink<S>, Confirmations)>>,
+    subscriptions: &mut HashMap<K, HashMap<SubscriptionId, Subscription<S>>>,
     sub_id: &SubscriptionId,
 ) -> bool
 where
@@ -73,7 +105,7 @@ where
 }
 
 fn check_confirmations_and_notify<K, S, F, N, X>(
-    subscriptions: &HashMap<K, HashMap<SubscriptionId, (Sink<S>, Confirmations)>>,
+    subscriptions: &HashMap<K, HashMap<SubscriptionId, Subscription<S>>>,
     hashmap_key: &K,
     current_slot: u64,
     bank_forks: &Arc<RwLock<BankForks>>,
@@ -83,7 +115,7 @@ fn check_confirmations_and_notify<K, S, F, N, X>(
     K: Eq + Hash + Clone + Copy,
     S: Clone + Serialize,
     F: Fn(&Bank, &K) -> X,
-    N: Fn(X, &Sink<S>, u64),
+    N: Fn(X, &Sink<S>, Option<DataSlice>, u64),
     X: Clone + Serialize,
 {
     let current_ancestors = bank_forks
@@ -94,7 +126,7 @@ fn check_confirmations_and_notify<K, S, F, N, X>(
         .ancestors
         .clone();
     if let Some(hashmap) = subscriptions.get(hashmap_key) {
-        for (_bank_sub_id, (sink, confirmations)) in hashmap.iter() {
+        for (_bank_sub_id, (sink, confirmations, data_slice)) in hashmap.iter() {
             let desired_slot: Vec<u64> = current_ancestors
                 .iter()
                 .filter(|(_, &v)| v == *confirmations)
@@ -116,25 +148,35 @@ fn check_confirmations_and_notify<K, S, F, N, X>(
                     .unwrap()
                     .clone();
                 let result = bank_method(&desired_bank, hashmap_key);
-                notify(result, &sink, root);
+                notify(result, &sink, *data_slice, root);
             }
         }
     }
 }
 
-fn notify_account<S>(result: Option<(S, u64)>, sink: &Sink<S>, root: u64)
-where
-    S: Clone + Serialize,
-{
+fn notify_account(
+    result: Option<(Account, u64)>,
+    sink: &Sink<Account>,
+    data_slice: Option<DataSlice>,
+    root: u64,
+) {
     if let Some((account, fork)) = result {
         if fork >= root {
+            let account = match data_slice {
+                Some(data_slice) => data_slice.apply(&account),
+                None => account,
+            };
             sink.notify(Ok(account)).wait().unwrap();
         }
     }
 }
 
-fn notify_signature<S>(result: Option<S>, sink: &Sink<S>, _root: u64)
-where
+fn notify_signature<S>(
+    result: Option<S>,
+    sink: &Sink<S>,
+    _data_slice: Option<DataSlice>,
+    _root: u64,
+) where
     S: Clone + Serialize,
 {
     if let Some(result) = result {
@@ -142,9 +184,18 @@ where
     }
 }
 
-fn notify_program(accounts: Vec<(Pubkey, Account)>, sink: &Sink<(String, Account)>, _root: u64) {
-    for (pubkey, account) in accounts.iter() {
-        sink.notify(Ok((pubkey.to_string(), account.clone())))
+fn notify_program(
+    accounts: Vec<(Pubkey, Account)>,
+    sink: &Sink<(String, Account)>,
+    data_slice: Option<DataSlice>,
+    _root: u64,
+) {
+    for (pubkey, account) in accounts.into_iter() {
+        let account = match data_slice {
+            Some(data_slice) => data_slice.apply(&account),
+            None => account,
+        };
+        sink.notify(Ok((pubkey.to_string(), account)))
             .wait()
             .unwrap();
     }
@@ -154,6 +205,7 @@ pub struct RpcSubscriptions {
     account_subscriptions: RpcAccountSubscriptions,
     program_subscriptions: RpcProgramSubscriptions,
     signature_subscriptions: RpcSignatureSubscriptions,
+    vote_subscriptions: RpcVoteSubscriptions,
 }
 
 impl Default for RpcSubscriptions {
@@ -162,6 +214,7 @@ impl Default for RpcSubscriptions {
             account_subscriptions: RpcAccountSubscriptions::default(),
             program_subscriptions: RpcProgramSubscriptions::default(),
             signature_subscriptions: RpcSignatureSubscriptions::default(),
+            vote_subscriptions: RpcVoteSubscriptions::default(),
         }
     }
 }
@@ -223,11 +276,19 @@ impl RpcSubscriptions {
         &self,
         pubkey: &Pubkey,
         confirmations: Option<Confirmations>,
+        data_slice: Option<DataSlice>,
         sub_id: &SubscriptionId,
         sink: &Sink<Account>,
     ) {
         let mut subscriptions = self.account_subscriptions.write().unwrap();
-        add_subscription(&mut subscriptions, pubkey, confirmations, sub_id, sink);
+        add_subscription(
+            &mut subscriptions,
+            pubkey,
+            confirmations,
+            data_slice,
+            sub_id,
+            sink,
+        );
     }
 
     pub fn remove_account_subscription(&self, id: &SubscriptionId) -> bool {
@@ -239,11 +300,19 @@ impl RpcSubscriptions {
         &self,
         program_id: &Pubkey,
         confirmations: Option<Confirmations>,
+        data_slice: Option<DataSlice>,
         sub_id: &SubscriptionId,
         sink: &Sink<(String, Account)>,
     ) {
         let mut subscriptions = self.program_subscriptions.write().unwrap();
-        add_subscription(&mut subscriptions, program_id, confirmations, sub_id, sink);
+        add_subscription(
+            &mut subscriptions,
+            program_id,
+            confirmations,
+            data_slice,
+            sub_id,
+            sink,
+        );
     }
 
     pub fn remove_program_subscription(&self, id: &SubscriptionId) -> bool {
@@ -259,7 +328,14 @@ impl RpcSubscriptions {
         sink: &Sink<transaction::Result<()>>,
     ) {
         let mut subscriptions = self.signature_subscriptions.write().unwrap();
-        add_subscription(&mut subscriptions, signature, confirmations, sub_id, sink);
+        add_subscription(
+            &mut subscriptions,
+            signature,
+            confirmations,
+            None,
+            sub_id,
+            sink,
+        );
     }
 
     pub fn remove_signature_subscription(&self, id: &SubscriptionId) -> bool {
@@ -267,6 +343,37 @@ impl RpcSubscriptions {
         remove_subscription(&mut subscriptions, id)
     }
 
+    pub fn add_vote_subscription(
+        &self,
+        vote_pubkey: &Pubkey,
+        sub_id: &SubscriptionId,
+        sink: &Sink<RpcVote>,
+    ) {
+        let mut subscriptions = self.vote_subscriptions.write().unwrap();
+        add_subscription(&mut subscriptions, vote_pubkey, None, None, sub_id, sink);
+    }
+
+    pub fn remove_vote_subscription(&self, id: &SubscriptionId) -> bool {
+        let mut subscriptions = self.vote_subscriptions.write().unwrap();
+        remove_subscription(&mut subscriptions, id)
+    }
+
+    /// Notify the subscribers of `vote_pubkey` of a vote seen at `timestamp`. Votes aren't
+    /// tied to a bank, so they are sent as soon as they are observed.
+    pub fn notify_vote(&self, vote_pubkey: &Pubkey, vote: &Vote, timestamp: u64) {
+        let subscriptions = self.vote_subscriptions.read().unwrap();
+        if let Some(hashmap) = subscriptions.get(vote_pubkey) {
+            let rpc_vote = RpcVote {
+                slot: vote.slot,
+                hash: vote.hash.to_string(),
+                timestamp,
+            };
+            for (sink, _, _) in hashmap.values() {
+                sink.notify(Ok(rpc_vote.clone())).wait().unwrap();
+            }
+        }
+    }
+
     /// Notify subscribers of changes to any accounts or new signatures since
     /// the bank's last checkpoint.
     pub fn notify_subscribers(&self, current_slot: u64, bank_forks: &Arc<RwLock<BankForks>>) {
@@ -302,6 +409,7 @@ pub mod tests {
     use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
     use jsonrpc_pubsub::typed::Subscriber;
     use morgan_budget_api;
+    use morgan_interface::hash::Hash;
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_transaction;
     use tokio::prelude::{Async, Stream};
@@ -338,7 +446,7 @@ pub mod tests {
         let sub_id = SubscriptionId::Number(0 as u64);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
         let subscriptions = RpcSubscriptions::default();
-        subscriptions.add_account_subscription(&alice.pubkey(), None, &sub_id, &sink);
+        subscriptions.add_account_subscription(&alice.pubkey(), None, None, &sub_id, &sink);
 
         assert!(subscriptions
             .account_subscriptions
@@ -362,6 +470,82 @@ pub mod tests {
             .contains_key(&alice.pubkey()));
     }
 
+    #[test]
+    fn test_data_slice_apply() {
+        let account = Account {
+            data: vec![1, 2, 3, 4, 5],
+            ..Account::default()
+        };
+        let slice = DataSlice {
+            offset: 1,
+            length: 3,
+        };
+        assert_eq!(slice.apply(&account).data, vec![2, 3, 4]);
+        let slice = DataSlice {
+            offset: 3,
+            length: 10,
+        };
+        assert_eq!(slice.apply(&account).data, vec![4, 5]);
+        let slice = DataSlice {
+            offset: 10,
+            length: 1,
+        };
+        assert!(slice.apply(&account).data.is_empty());
+        assert_eq!(slice.apply(&account).difs, account.difs);
+    }
+
+    #[test]
+    fn test_check_account_subscribe_data_slice() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(100);
+        let bank = Bank::new(&genesis_block);
+        let blockhash = bank.last_blockhash();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(0, bank)));
+        let alice = Keypair::new();
+        let tx = system_transaction::create_account(
+            &mint_keypair,
+            &alice.pubkey(),
+            blockhash,
+            1,
+            16,
+            &morgan_budget_api::id(),
+        );
+        bank_forks
+            .write()
+            .unwrap()
+            .get(0)
+            .unwrap()
+            .process_transaction(&tx)
+            .unwrap();
+
+        let (subscriber, _id_receiver, mut transport_receiver) =
+            Subscriber::new_test("accountNotification");
+        let sub_id = SubscriptionId::Number(0 as u64);
+        let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+        let subscriptions = RpcSubscriptions::default();
+        let data_slice = DataSlice {
+            offset: 4,
+            length: 2,
+        };
+        subscriptions.add_account_subscription(
+            &alice.pubkey(),
+            None,
+            Some(data_slice),
+            &sub_id,
+            &sink,
+        );
+
+        subscriptions.check_account(&alice.pubkey(), 0, &bank_forks);
+        let string = transport_receiver.poll();
+        if let Async::Ready(Some(response)) = string.unwrap() {
+            let expected = r#"{"jsonrpc":"2.0","method":"accountNotification","params":{"result":{"data":[0,0],"difs":1,"executable":false,"owner":[2,203,81,223,225,24,34,35,203,214,138,130,144,208,35,77,63,16,87,51,47,198,115,123,98,188,19,160,0,0,0,0],"reputations":0},"subscription":0}}"#;
+            assert_eq!(expected, response);
+        }
+    }
+
     #[test]
     fn test_check_program_subscribe() {
         let GenesisBlockInfo {
@@ -394,7 +578,13 @@ pub mod tests {
         let sub_id = SubscriptionId::Number(0 as u64);
         let sink = subscriber.assign_id(sub_id.clone()).unwrap();
         let subscriptions = RpcSubscriptions::default();
-        subscriptions.add_program_subscription(&morgan_budget_api::id(), None, &sub_id, &sink);
+        subscriptions.add_program_subscription(
+            &morgan_budget_api::id(),
+            None,
+            None,
+            &sub_id,
+            &sink,
+        );
 
         assert!(subscriptions
             .program_subscriptions
@@ -467,4 +657,32 @@ pub mod tests {
             .unwrap()
             .contains_key(&signature));
     }
+
+    #[test]
+    fn test_check_vote_subscribe() {
+        let vote_pubkey = Pubkey::new_rand();
+        let (subscriber, _id_receiver, mut transport_receiver) =
+            Subscriber::new_test("voteNotification");
+        let sub_id = SubscriptionId::Number(0 as u64);
+        let sink = subscriber.assign_id(sub_id.clone()).unwrap();
+        let subscriptions = RpcSubscriptions::default();
+        subscriptions.add_vote_subscription(&vote_pubkey, &sub_id, &sink);
+
+        // votes of other vote accounts aren't sent
+        let vote = Vote::new(3, Hash::default());
+        subscriptions.notify_vote(&Pubkey::new_rand(), &vote, 1234);
+        subscriptions.notify_vote(&vote_pubkey, &vote, 1234);
+        let string = transport_receiver.poll();
+        if let Async::Ready(Some(response)) = string.unwrap() {
+            let expected = format!(r#"{{"jsonrpc":"2.0","method":"voteNotification","params":{{"result":{{"slot":3,"hash":"{}","timestamp":1234}},"subscription":0}}}}"#, Hash::default());
+            assert_eq!(expected, response);
+        }
+
+        assert!(subscriptions.remove_vote_subscription(&sub_id));
+        assert!(!subscriptions
+            .vote_subscriptions
+            .read()
+            .unwrap()
+            .contains_key(&vote_pubkey));
+    }
 }
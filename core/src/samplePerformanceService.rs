@@ -0,0 +1,159 @@
+//! The `samplePerformanceService` records how many transactions and entries every newly rooted
+//! slot holds and roughly how long the slot took, keeping the most recent samples for the
+//! `getPerformanceSamples` RPC.
+
+use crate::blockBufferPool::Blocktree;
+use crate::rpc::RpcPerfSample;
+use crate::service::Service;
+use crate::treasuryForks::BankForks;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Samples kept, newest first
+pub const MAX_PERFORMANCE_SAMPLES: usize = 720;
+
+const SAMPLE_INTERVAL_MS: u64 = 100;
+
+pub type PerformanceSamples = Arc<RwLock<VecDeque<RpcPerfSample>>>;
+
+pub struct SamplePerformanceService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl SamplePerformanceService {
+    pub fn new(
+        bank_forks: &Arc<RwLock<BankForks>>,
+        blocktree: &Arc<Blocktree>,
+        samples: &PerformanceSamples,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let bank_forks = bank_forks.clone();
+        let blocktree = blocktree.clone();
+        let samples = samples.clone();
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("morgan-sample-performance".to_string())
+            .spawn(move || {
+                let mut last_root = bank_forks.read().unwrap().root();
+                let mut last_sample = Instant::now();
+                while !exit.load(Ordering::Relaxed) {
+                    sleep(Duration::from_millis(SAMPLE_INTERVAL_MS));
+                    let root = bank_forks.read().unwrap().root();
+                    if root <= last_root {
+                        continue;
+                    }
+                    let slots = Self::rooted_slots(&blocktree, last_root, root);
+                    // slots rooted together share the time since the last sample
+                    let slot_duration_ms =
+                        last_sample.elapsed().as_millis() as u64 / slots.len().max(1) as u64;
+                    for slot in slots {
+                        if let Some(sample) = Self::sample_slot(&blocktree, slot, slot_duration_ms)
+                        {
+                            Self::record(&samples, sample);
+                        }
+                    }
+                    last_root = root;
+                    last_sample = Instant::now();
+                }
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+
+    /// The slots rooted after `last_root` up to `root`, oldest first
+    fn rooted_slots(blocktree: &Blocktree, last_root: u64, root: u64) -> Vec<u64> {
+        let mut slots = vec![];
+        let mut slot = root;
+        while slot > last_root && slots.len() < MAX_PERFORMANCE_SAMPLES {
+            slots.push(slot);
+            match blocktree.meta(slot) {
+                Ok(Some(meta)) if meta.parent_slot < slot => slot = meta.parent_slot,
+                _ => break,
+            }
+        }
+        slots.reverse();
+        slots
+    }
+
+    fn sample_slot(
+        blocktree: &Blocktree,
+        slot: u64,
+        slot_duration_ms: u64,
+    ) -> Option<RpcPerfSample> {
+        let entries = blocktree.get_slot_entries(slot, 0, None).ok()?;
+        Some(RpcPerfSample {
+            slot,
+            num_transactions: entries
+                .iter()
+                .map(|entry| entry.transactions.len() as u64)
+                .sum(),
+            num_entries: entries.len() as u64,
+            slot_duration_ms,
+        })
+    }
+
+    fn record(samples: &PerformanceSamples, sample: RpcPerfSample) {
+        let mut samples = samples.write().unwrap();
+        samples.push_front(sample);
+        samples.truncate(MAX_PERFORMANCE_SAMPLES);
+    }
+}
+
+impl Service for SamplePerformanceService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockBufferPool::get_tmp_ledger_path;
+    use crate::blockBufferPool::tests::make_slot_entries;
+
+    #[test]
+    fn test_sample_rooted_slots() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree = Blocktree::open(&ledger_path).unwrap();
+            // 0 <- 1 <- 3, 2 is on another fork
+            for (slot, parent_slot, num_entries) in &[(0, 0, 1), (1, 0, 3), (2, 0, 1), (3, 1, 2)] {
+                let (blobs, _entries) = make_slot_entries(*slot, *parent_slot, *num_entries);
+                blocktree.insert_data_blobs(blobs.iter()).unwrap();
+            }
+
+            assert_eq!(
+                SamplePerformanceService::rooted_slots(&blocktree, 0, 3),
+                vec![1, 3]
+            );
+            assert_eq!(
+                SamplePerformanceService::rooted_slots(&blocktree, 1, 3),
+                vec![3]
+            );
+            assert!(SamplePerformanceService::rooted_slots(&blocktree, 3, 3).is_empty());
+
+            let sample = SamplePerformanceService::sample_slot(&blocktree, 1, 400).unwrap();
+            assert_eq!(
+                sample,
+                RpcPerfSample {
+                    slot: 1,
+                    num_transactions: 3,
+                    num_entries: 3,
+                    slot_duration_ms: 400,
+                }
+            );
+
+            let samples = PerformanceSamples::default();
+            for _ in 0..=MAX_PERFORMANCE_SAMPLES {
+                SamplePerformanceService::record(&samples, sample.clone());
+            }
+            assert_eq!(samples.read().unwrap().len(), MAX_PERFORMANCE_SAMPLES);
+        }
+        Blocktree::destroy(&ledger_path).expect("Expected successful database destruction");
+    }
+}
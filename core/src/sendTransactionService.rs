@@ -0,0 +1,262 @@
+//! The `sendTransactionService` keeps transactions submitted through RPC and resends them to
+//! the current and next leaders until they land or their blockhash expires.
+
+use crate::clusterMessage::ClusterInfo;
+use crate::leaderArrangeUtils::slot_leader_at;
+use crate::service::Service;
+use crate::treasuryForks::BankForks;
+use morgan_client::paced_sender::{PacedSender, PacingConfig, DEFAULT_BURST};
+use morgan_runtime::bank::Bank;
+use morgan_interface::hash::Hash;
+use morgan_interface::signature::Signature;
+use morgan_interface::timing::NUM_CONSECUTIVE_LEADER_SLOTS;
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Time between resends of a transaction that hasn't landed
+pub const RETRY_INTERVAL_MS: u64 = 2000;
+
+/// Transactions beyond this many are dropped instead of queued
+pub const MAX_QUEUED_TRANSACTIONS: usize = 10_000;
+
+/// Packets per second resends go out at, enough to resend a full queue to two leaders
+/// within a retry interval
+pub const SEND_PACKETS_PER_SECOND: u64 =
+    2 * MAX_QUEUED_TRANSACTIONS as u64 * 1000 / RETRY_INTERVAL_MS;
+
+pub struct TransactionInfo {
+    pub signature: Signature,
+    pub wire_transaction: Vec<u8>,
+    pub blockhash: Hash,
+}
+
+impl TransactionInfo {
+    pub fn new(signature: Signature, wire_transaction: Vec<u8>, blockhash: Hash) -> Self {
+        Self {
+            signature,
+            wire_transaction,
+            blockhash,
+        }
+    }
+}
+
+#[derive(Default, Debug, PartialEq)]
+struct ProcessTransactionsResult {
+    landed: usize,
+    expired: usize,
+    retried: usize,
+}
+
+pub struct SendTransactionService {
+    thread_hdl: JoinHandle<()>,
+}
+
+impl SendTransactionService {
+    pub fn new(
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        receiver: Receiver<TransactionInfo>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let cluster_info = cluster_info.clone();
+        let bank_forks = bank_forks.clone();
+        let exit = exit.clone();
+        let thread_hdl = Builder::new()
+            .name("morgan-send-tx-svc".to_string())
+            .spawn(move || {
+                Self::run(&cluster_info, &bank_forks, &receiver, &exit);
+            })
+            .unwrap();
+        Self { thread_hdl }
+    }
+
+    fn run(
+        cluster_info: &Arc<RwLock<ClusterInfo>>,
+        bank_forks: &Arc<RwLock<BankForks>>,
+        receiver: &Receiver<TransactionInfo>,
+        exit: &Arc<AtomicBool>,
+    ) {
+        let sender = PacedSender::new(
+            UdpSocket::bind("0.0.0.0:0").unwrap(),
+            PacingConfig::new(SEND_PACKETS_PER_SECOND, DEFAULT_BURST),
+        );
+        let retry_interval = Duration::from_millis(RETRY_INTERVAL_MS);
+        let mut transactions = HashMap::new();
+        let mut last_retry = Instant::now();
+        while !exit.load(Ordering::Relaxed) {
+            match receiver.recv_timeout(retry_interval) {
+                Err(RecvTimeoutError::Disconnected) => break,
+                Err(RecvTimeoutError::Timeout) => (),
+                Ok(transaction_info) => {
+                    if transactions.len() < MAX_QUEUED_TRANSACTIONS {
+                        transactions.insert(transaction_info.signature, transaction_info);
+                    } else {
+                        inc_new_counter_info!("send_transaction_service-queue_full", 1);
+                    }
+                }
+            }
+
+            if last_retry.elapsed() >= retry_interval {
+                last_retry = Instant::now();
+                let bank = bank_forks.read().unwrap().working_bank();
+                let tpu_addrs = Self::leader_tpu_addrs(cluster_info, &bank);
+                let result =
+                    Self::process_transactions(&bank, &mut transactions, &sender, &tpu_addrs);
+                datapoint_info!(
+                    "send_transaction_service",
+                    ("queue_depth", transactions.len(), i64),
+                    ("landed", result.landed, i64),
+                    ("expired", result.expired, i64),
+                    ("retried", result.retried, i64)
+                );
+            }
+        }
+    }
+
+    /// TPU addresses of the current and next leaders, or our own if neither is known
+    fn leader_tpu_addrs(cluster_info: &Arc<RwLock<ClusterInfo>>, bank: &Bank) -> Vec<SocketAddr> {
+        let cluster_info = cluster_info.read().unwrap();
+        let mut tpu_addrs: Vec<_> = [bank.slot(), bank.slot() + NUM_CONSECUTIVE_LEADER_SLOTS]
+            .iter()
+            .filter_map(|slot| slot_leader_at(*slot, bank))
+            .filter_map(|leader| cluster_info.lookup(&leader).map(|info| info.tpu))
+            .collect();
+        tpu_addrs.dedup();
+        if tpu_addrs.is_empty() {
+            tpu_addrs.push(cluster_info.my_data().tpu);
+        }
+        tpu_addrs
+    }
+
+    fn process_transactions(
+        bank: &Bank,
+        transactions: &mut HashMap<Signature, TransactionInfo>,
+        sender: &PacedSender,
+        tpu_addrs: &[SocketAddr],
+    ) -> ProcessTransactionsResult {
+        let mut result = ProcessTransactionsResult::default();
+        transactions.retain(|signature, transaction_info| {
+            if bank.get_signature_status(signature).is_some() {
+                result.landed += 1;
+                false
+            } else if !bank.check_blockhash_age(
+                &transaction_info.blockhash,
+                bank.runtime_params().max_processing_age,
+            ) {
+                result.expired += 1;
+                false
+            } else {
+                result.retried += 1;
+                for tpu_addr in tpu_addrs {
+                    if let Err(err) = sender.send_to(&transaction_info.wire_transaction, tpu_addr) {
+                        debug!(
+                            "send_transaction_service: send_to {} error: {:?}",
+                            tpu_addr, err
+                        );
+                    }
+                }
+                true
+            }
+        });
+        result
+    }
+}
+
+impl Service for SendTransactionService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.thread_hdl.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesisUtils::create_genesis_block;
+    use morgan_interface::pubkey::Pubkey;
+    use morgan_interface::signature::{Keypair, KeypairUtil};
+    use morgan_interface::system_transaction;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn test_send_transaction_service_exit() {
+        let genesis_block = create_genesis_block(10_000).genesis_block;
+        let bank = Bank::new(&genesis_block);
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(0, bank)));
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+            Default::default(),
+        )));
+        let exit = Arc::new(AtomicBool::new(false));
+        let (_sender, receiver) = channel();
+        let service = SendTransactionService::new(&cluster_info, &bank_forks, receiver, &exit);
+        exit.store(true, Ordering::Relaxed);
+        service.join().unwrap();
+    }
+
+    #[test]
+    fn test_process_transactions() {
+        let genesis_block_info = create_genesis_block(10_000);
+        let mint_keypair = genesis_block_info.mint_keypair;
+        let bank = Bank::new(&genesis_block_info.genesis_block);
+        let blockhash = bank.last_blockhash();
+        let sender = PacedSender::new(
+            UdpSocket::bind("0.0.0.0:0").unwrap(),
+            PacingConfig::default(),
+        );
+        let receive_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let tpu_addrs = vec![receive_socket.local_addr().unwrap()];
+
+        let landed_tx = system_transaction::create_user_account(
+            &mint_keypair,
+            &Pubkey::new_rand(),
+            1,
+            blockhash,
+        );
+        bank.process_transaction(&landed_tx).unwrap();
+        let pending_tx = system_transaction::create_user_account(
+            &mint_keypair,
+            &Pubkey::new_rand(),
+            1,
+            blockhash,
+        );
+
+        let mut transactions = HashMap::new();
+        for tx in &[landed_tx, pending_tx.clone()] {
+            transactions.insert(
+                tx.signatures[0],
+                TransactionInfo::new(tx.signatures[0], vec![tx.signatures.len() as u8], blockhash),
+            );
+        }
+        let expired_signature = Keypair::new().sign_message(&[0]);
+        transactions.insert(
+            expired_signature,
+            TransactionInfo::new(expired_signature, vec![0], Hash::default()),
+        );
+
+        let result = SendTransactionService::process_transactions(
+            &bank,
+            &mut transactions,
+            &sender,
+            &tpu_addrs,
+        );
+        assert_eq!(
+            result,
+            ProcessTransactionsResult {
+                landed: 1,
+                expired: 1,
+                retried: 1,
+            }
+        );
+        assert_eq!(transactions.len(), 1);
+        assert!(transactions.contains_key(&pending_tx.signatures[0]));
+
+        let mut buf = [0; 8];
+        assert_eq!(receive_socket.recv(&mut buf).unwrap(), 1);
+    }
+}
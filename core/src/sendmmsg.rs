@@ -0,0 +1,144 @@
+//! The `sendmmsg` module provides sendmmsg() API implementation, the counterpart of `recvmmsg`
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+/// Most packets handed to the kernel in a single sendmmsg() call, Linux's UIO_MAXIOV
+pub const NUM_SNDMMSGS: usize = 1024;
+
+#[cfg(not(target_os = "linux"))]
+fn send_mmsg(sock: &UdpSocket, packets: &[(&[u8], &SocketAddr)]) -> io::Result<usize> {
+    let (data, addr) = packets[0];
+    sock.send_to(data, addr)?;
+    Ok(1)
+}
+
+/// Send as many of `packets` as one sendmmsg() call does, returns how many were sent.  Fails
+/// only if the first packet couldn't be sent
+#[cfg(target_os = "linux")]
+fn send_mmsg(sock: &UdpSocket, packets: &[(&[u8], &SocketAddr)]) -> io::Result<usize> {
+    use libc::{c_void, iovec, mmsghdr, sendmmsg};
+    use nix::sys::socket::{InetAddr, SockAddr};
+    use std::cmp;
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+
+    let count = cmp::min(NUM_SNDMMSGS, packets.len());
+    let addrs: Vec<_> = packets[..count]
+        .iter()
+        .map(|(_, addr)| SockAddr::new_inet(InetAddr::from_std(addr)))
+        .collect();
+    let mut iovs: Vec<iovec> = packets[..count]
+        .iter()
+        .map(|(data, _)| iovec {
+            iov_base: data.as_ptr() as *mut c_void,
+            iov_len: data.len(),
+        })
+        .collect();
+    let mut hdrs: Vec<mmsghdr> = vec![unsafe { mem::zeroed() }; count];
+    for ((hdr, iov), addr) in hdrs.iter_mut().zip(iovs.iter_mut()).zip(&addrs) {
+        let (addr, addrlen) = unsafe { addr.as_ffi_pair() };
+        hdr.msg_hdr.msg_name = addr as *const _ as *mut c_void;
+        hdr.msg_hdr.msg_namelen = addrlen;
+        hdr.msg_hdr.msg_iov = iov;
+        hdr.msg_hdr.msg_iovlen = 1;
+    }
+
+    match unsafe { sendmmsg(sock.as_raw_fd(), &mut hdrs[0], count as u32, 0) } {
+        -1 => Err(io::Error::last_os_error()),
+        n => Ok(n as usize),
+    }
+}
+
+/// Send each of `packets`, a payload and its destination, in as few system calls as it takes.
+/// A packet that fails doesn't hold back the ones after it, the first error is returned once
+/// all were tried
+pub fn batch_send(sock: &UdpSocket, packets: &[(&[u8], &SocketAddr)]) -> io::Result<()> {
+    let mut first_error = None;
+    let mut sent = 0;
+    while sent < packets.len() {
+        match send_mmsg(sock, &packets[sent..]) {
+            Ok(n) => sent += n,
+            Err(e) => {
+                // the packet at `sent` is the one that failed, move on past it
+                first_error.get_or_insert(e);
+                sent += 1;
+            }
+        }
+    }
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Send `data` to every one of `dests`
+pub fn multicast(sock: &UdpSocket, data: &[u8], dests: &[&SocketAddr]) -> io::Result<()> {
+    let packets: Vec<_> = dests.iter().map(|addr| (data, *addr)).collect();
+    batch_send(sock, &packets)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::packet::{Packet, PACKET_DATA_SIZE};
+    use crate::recvmmsg::{recv_mmsg, NUM_RCVMMSGS};
+    use crate::sendmmsg::*;
+
+    #[test]
+    pub fn test_batch_send() {
+        let reader = UdpSocket::bind("127.0.0.1:0").expect("bind");
+        let addr = reader.local_addr().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").expect("bind");
+        let saddr = sender.local_addr().unwrap();
+
+        let data: Vec<_> = (0..NUM_RCVMMSGS).map(|i| vec![i as u8; i + 1]).collect();
+        let packets: Vec<_> = data.iter().map(|data| (&data[..], &addr)).collect();
+        batch_send(&sender, &packets).unwrap();
+
+        let mut packets = vec![Packet::default(); NUM_RCVMMSGS];
+        let recv = recv_mmsg(&reader, &mut packets[..]).unwrap();
+        assert_eq!(recv, NUM_RCVMMSGS);
+        for (i, packet) in packets.iter().enumerate() {
+            assert_eq!(packet.meta.size, i + 1);
+            assert_eq!(packet.data[i], i as u8);
+            assert_eq!(packet.meta.addr(), saddr);
+        }
+    }
+
+    #[test]
+    pub fn test_multicast() {
+        let readers: Vec<_> = (0..3)
+            .map(|_| UdpSocket::bind("127.0.0.1:0").expect("bind"))
+            .collect();
+        let addrs: Vec<_> = readers.iter().map(|r| r.local_addr().unwrap()).collect();
+        let sender = UdpSocket::bind("127.0.0.1:0").expect("bind");
+
+        let data = [7u8; PACKET_DATA_SIZE];
+        multicast(&sender, &data, &addrs.iter().collect::<Vec<_>>()).unwrap();
+        for reader in &readers {
+            let mut packets = vec![Packet::default(); 1];
+            assert_eq!(recv_mmsg(reader, &mut packets[..]).unwrap(), 1);
+            assert_eq!(packets[0].meta.size, PACKET_DATA_SIZE);
+        }
+    }
+
+    #[test]
+    pub fn test_batch_send_error() {
+        let reader = UdpSocket::bind("127.0.0.1:0").expect("bind");
+        let addr = reader.local_addr().unwrap();
+        let sender = UdpSocket::bind("127.0.0.1:0").expect("bind");
+
+        // a v6 destination can't be reached from a v4 socket, the packets after it still go out
+        let bad_addr = "[::1]:1".parse().unwrap();
+        let data = [1u8; 8];
+        let packets = vec![
+            (&data[..], &bad_addr),
+            (&data[..], &addr),
+            (&data[..], &addr),
+        ];
+        assert!(batch_send(&sender, &packets).is_err());
+
+        let mut packets = vec![Packet::default(); NUM_RCVMMSGS];
+        assert_eq!(recv_mmsg(&reader, &mut packets[..]).unwrap(), 2);
+    }
+}
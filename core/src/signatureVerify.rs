@@ -1,27 +1,77 @@
 //! The `sigverify` module provides digital signature verification functions.
 //! By default, signatures are verified in parallel using all available CPU
 //! cores.  When `--features=cuda` is enabled, signature verification is
-//! offloaded to the GPU.
+//! offloaded to the GPU for batches large enough to amortize the transfer,
+//! provided a GPU was found at `init()`.  Leader blob signatures are verified in batches
+//! on the same path.
 //!
 
-use crate::packet::{Packet, Packets};
-use crate::result::Result;
+use crate::packet::{Packet, PacketError, Packets, SharedBlob, PACKET_DATA_SIZE};
+use crate::result::{Error, Result};
 use bincode::serialized_size;
-use morgan_metricbot::inc_new_counter_debug;
-use morgan_interface::message::MessageHeader;
+#[cfg(feature = "cuda")]
+use morgan_helper::logHelper::*;
+use morgan_metricbot::{datapoint_debug, inc_new_counter_debug};
+use morgan_interface::message::{MessageHeader, MESSAGE_VERSION_PREFIX};
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::short_vec::decode_len;
 use morgan_interface::signature::Signature;
+use morgan_interface::timing::duration_as_us;
 #[cfg(test)]
 use morgan_interface::transaction::Transaction;
 use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+type TxOffsets = (Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<Vec<Option<u32>>>);
+
+// micro-benchmarks show GPU time for smallest batch around 15-20ms
+// and CPU speed for 64-128 sigverifies around 10-20ms. 64 is a nice
+// power-of-two number around that accounting for the fact that the CPU
+// may be busy doing other things while being a real fullnode
+pub const DEFAULT_GPU_MIN_BATCH_SIZE: usize = 64;
+
+// Set by `init()` once the GPU has been probed successfully
+static GPU_AVAILABLE: AtomicBool = AtomicBool::new(false);
+static GPU_MIN_BATCH_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_GPU_MIN_BATCH_SIZE);
+
+/// Batches with fewer packets than `min_batch_size` are verified on the CPU
+pub fn set_gpu_min_batch_size(min_batch_size: usize) {
+    GPU_MIN_BATCH_SIZE.store(min_batch_size, Ordering::Relaxed);
+}
+
+pub fn gpu_min_batch_size() -> usize {
+    GPU_MIN_BATCH_SIZE.load(Ordering::Relaxed)
+}
+
+pub fn gpu_available() -> bool {
+    GPU_AVAILABLE.load(Ordering::Relaxed)
+}
+
+/// Whether a batch of `count` packets should be offloaded to the GPU
+pub fn use_gpu(gpu_available: bool, count: usize) -> bool {
+    gpu_available && count >= gpu_min_batch_size()
+}
 
-type TxOffsets = (Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<Vec<u32>>);
+fn record_throughput(path: &'static str, count: usize, elapsed: Duration) {
+    let elapsed_us = duration_as_us(&elapsed).max(1);
+    datapoint_debug!(
+        "sigverify-throughput",
+        ("path", path.to_string(), String),
+        ("packets", count, i64),
+        ("elapsed_us", elapsed_us, i64),
+        (
+            "packets_per_sec",
+            count as u64 * 1_000_000 / elapsed_us,
+            i64
+        )
+    );
+}
 
 #[cfg(feature = "cuda")]
 #[repr(C)]
 struct Elems {
-    elems: *const Packet,
+    elems: *const u8,
     num: u32,
 }
 
@@ -67,7 +117,10 @@ pub fn init() {
 }
 
 fn verify_packet(packet: &Packet) -> u8 {
-    let (sig_len, sig_start, msg_start, pubkey_start) = get_packet_offsets(packet, 0);
+    let (sig_len, sig_start, msg_start, pubkey_start) = match get_packet_offsets(packet, 0) {
+        Ok(offsets) => offsets,
+        Err(_) => return 0,
+    };
     let mut sig_start = sig_start as usize;
     let mut pubkey_start = pubkey_start as usize;
     let msg_start = msg_start as usize;
@@ -107,23 +160,42 @@ pub fn ed25519_verify(batches: &[Packets]) -> Vec<Vec<u8>> {
     ed25519_verify_cpu(batches)
 }
 
-pub fn get_packet_offsets(packet: &Packet, current_offset: u32) -> (u32, u32, u32, u32) {
-    let (sig_len, sig_size) = decode_len(&packet.data);
+/// Offsets of the signatures, message and signer pubkeys of the transaction in `packet`, or an
+/// error if the packet is too short for the lengths it claims
+pub fn get_packet_offsets(packet: &Packet, current_offset: u32) -> Result<(u32, u32, u32, u32)> {
+    let invalid_len = || Error::PacketError(PacketError::InvalidLen);
+    let packet_size = packet.meta.size.min(PACKET_DATA_SIZE);
+
+    let (sig_len, sig_size) = decode_len(&packet.data)?;
     let msg_start_offset = sig_size + sig_len * size_of::<Signature>();
+    if msg_start_offset >= packet_size {
+        return Err(invalid_len());
+    }
 
-    let (_pubkey_len, pubkey_size) = decode_len(&packet.data[msg_start_offset..]);
+    // versioned messages carry a one byte prefix ahead of the header
+    let version_size = if packet.data[msg_start_offset] & MESSAGE_VERSION_PREFIX != 0 {
+        1
+    } else {
+        0
+    };
+    let pubkey_len_offset = msg_start_offset
+        + version_size
+        + serialized_size(&MessageHeader::default())? as usize;
+    if pubkey_len_offset >= packet_size {
+        return Err(invalid_len());
+    }
+    let (_pubkey_len, pubkey_size) = decode_len(&packet.data[pubkey_len_offset..])?;
 
     let sig_start = current_offset as usize + sig_size;
     let msg_start = current_offset as usize + msg_start_offset;
-    let pubkey_start =
-        msg_start + serialized_size(&MessageHeader::default()).unwrap() as usize + pubkey_size;
+    let pubkey_start = current_offset as usize + pubkey_len_offset + pubkey_size;
 
-    (
+    Ok((
         sig_len as u32,
         sig_start as u32,
         msg_start as u32,
         pubkey_start as u32,
-    )
+    ))
 }
 
 pub fn generate_offsets(batches: &[Packets]) -> Result<TxOffsets> {
@@ -138,11 +210,19 @@ pub fn generate_offsets(batches: &[Packets]) -> Result<TxOffsets> {
         p.packets.iter().for_each(|packet| {
             let current_offset = current_packet as u32 * size_of::<Packet>() as u32;
 
+            // a malformed packet has no signatures to check and is never valid
             let (sig_len, sig_start, msg_start_offset, pubkey_offset) =
-                get_packet_offsets(packet, current_offset);
+                match get_packet_offsets(packet, current_offset) {
+                    Ok(offsets) => offsets,
+                    Err(_) => {
+                        sig_lens.push(None);
+                        current_packet += 1;
+                        return;
+                    }
+                };
             let mut pubkey_offset = pubkey_offset;
 
-            sig_lens.push(sig_len);
+            sig_lens.push(Some(sig_len));
 
             trace!("pubkey_offset: {}", pubkey_offset);
             let mut sig_offset = sig_start;
@@ -174,14 +254,36 @@ pub fn ed25519_verify_cpu(batches: &[Packets]) -> Vec<Vec<u8>> {
     use rayon::prelude::*;
     let count = batch_size(batches);
     debug!("CPU ECDSA for {}", batch_size(batches));
+    let now = Instant::now();
     let rv = batches
         .into_par_iter()
         .map(|p| p.packets.par_iter().map(verify_packet).collect())
         .collect();
+    record_throughput("cpu", count, now.elapsed());
     inc_new_counter_debug!("ed25519_verify_cpu", count);
     rv
 }
 
+/// Verify the leader signature of every blob, 1 for each valid one
+#[cfg(not(feature = "cuda"))]
+pub fn ed25519_verify_blobs(blobs: &[SharedBlob]) -> Vec<u8> {
+    ed25519_verify_blobs_cpu(blobs)
+}
+
+pub fn ed25519_verify_blobs_cpu(blobs: &[SharedBlob]) -> Vec<u8> {
+    use rayon::prelude::*;
+    let count = blobs.len();
+    debug!("CPU ECDSA for {} blobs", count);
+    let now = Instant::now();
+    let rv = blobs
+        .par_iter()
+        .map(|blob| u8::from(blob.read().unwrap().verify()))
+        .collect();
+    record_throughput("cpu_blobs", count, now.elapsed());
+    inc_new_counter_debug!("ed25519_verify_blobs_cpu", count);
+    rv
+}
+
 pub fn ed25519_verify_disabled(batches: &[Packets]) -> Vec<Vec<u8>> {
     use rayon::prelude::*;
     let count = batch_size(batches);
@@ -196,28 +298,32 @@ pub fn ed25519_verify_disabled(batches: &[Packets]) -> Vec<Vec<u8>> {
 
 #[cfg(feature = "cuda")]
 pub fn init() {
-    unsafe {
+    let available = unsafe {
         ed25519_set_verbose(true);
-        if !ed25519_init() {
-            panic!("ed25519_init() failed");
-        }
+        let available = ed25519_init();
         ed25519_set_verbose(false);
+        available
+    };
+    if !available {
+        println!(
+            "{}",
+            Warn(
+                "ed25519_init() failed, verifying signatures on the CPU".to_string(),
+                module_path!().to_string()
+            )
+        );
     }
+    GPU_AVAILABLE.store(available, Ordering::Relaxed);
 }
 
 #[cfg(feature = "cuda")]
 pub fn ed25519_verify(batches: &[Packets]) -> Vec<Vec<u8>> {
-    use crate::packet::PACKET_DATA_SIZE;
     let count = batch_size(batches);
 
-    // micro-benchmarks show GPU time for smallest batch around 15-20ms
-    // and CPU speed for 64-128 sigverifies around 10-20ms. 64 is a nice
-    // power-of-two number around that accounting for the fact that the CPU
-    // may be busy doing other things while being a real fullnode
-    // TODO: dynamically adjust this crossover
-    if count < 64 {
+    if !use_gpu(gpu_available(), count) {
         return ed25519_verify_cpu(batches);
     }
+    let now = Instant::now();
 
     let (signature_offsets, pubkey_offsets, msg_start_offsets, msg_sizes, sig_lens) =
         generate_offsets(batches).unwrap();
@@ -230,7 +336,7 @@ pub fn ed25519_verify(batches: &[Packets]) -> Vec<Vec<u8>> {
     let mut num_packets = 0;
     for p in batches {
         elems.push(Elems {
-            elems: p.packets.as_ptr(),
+            elems: p.packets.as_ptr() as *const u8,
             num: p.packets.len() as u32,
         });
         let mut v = Vec::new();
@@ -266,8 +372,8 @@ pub fn ed25519_verify(batches: &[Packets]) -> Vec<Vec<u8>> {
     let mut num = 0;
     for (vs, sig_vs) in rvs.iter_mut().zip(sig_lens.iter()) {
         for (v, sig_v) in vs.iter_mut().zip(sig_vs.iter()) {
-            let mut vout = 1;
-            for _ in 0..*sig_v {
+            let mut vout = u8::from(sig_v.is_some());
+            for _ in 0..sig_v.unwrap_or(0) {
                 if 0 == out[num] {
                     vout = 0;
                 }
@@ -279,10 +385,73 @@ pub fn ed25519_verify(batches: &[Packets]) -> Vec<Vec<u8>> {
             }
         }
     }
+    record_throughput("gpu", count, now.elapsed());
     inc_new_counter_debug!("ed25519_verify_gpu", count);
     rvs
 }
 
+#[cfg(feature = "cuda")]
+pub fn ed25519_verify_blobs(blobs: &[SharedBlob]) -> Vec<u8> {
+    use crate::packet::{Blob, BLOB_SIGNED_DATA_OFFSET, ID_RANGE, SIGNATURE_RANGE};
+    let count = blobs.len();
+
+    if !use_gpu(gpu_available(), count) {
+        return ed25519_verify_blobs_cpu(blobs);
+    }
+    let now = Instant::now();
+
+    // The GPU sees the blobs as one buffer of `Blob`s, each one starting with its data
+    // since `Blob` is `repr(C)`
+    let locks: Vec<_> = blobs.iter().map(|blob| blob.read().unwrap()).collect();
+    let mut signature_offsets = Vec::with_capacity(count);
+    let mut pubkey_offsets = Vec::with_capacity(count);
+    let mut msg_start_offsets = Vec::with_capacity(count);
+    let mut msg_sizes = Vec::with_capacity(count);
+    let mut elems = Vec::with_capacity(count);
+    for (i, blob) in locks.iter().enumerate() {
+        let offset = (i * size_of::<Blob>()) as u32;
+        signature_offsets.push(offset + SIGNATURE_RANGE.start as u32);
+        pubkey_offsets.push(offset + ID_RANGE.start as u32);
+        msg_start_offsets.push(offset + BLOB_SIGNED_DATA_OFFSET as u32);
+        msg_sizes.push(blob.signed_data_len() as u32);
+        elems.push(Elems {
+            elems: &**blob as *const Blob as *const u8,
+            num: 1,
+        });
+    }
+
+    debug!("CUDA ECDSA for {} blobs", count);
+    let mut out = vec![0u8; count];
+    const USE_NON_DEFAULT_STREAM: u8 = 1;
+    unsafe {
+        let res = ed25519_verify_many(
+            elems.as_ptr(),
+            elems.len() as u32,
+            size_of::<Blob>() as u32,
+            count as u32,
+            count as u32,
+            msg_sizes.as_ptr(),
+            pubkey_offsets.as_ptr(),
+            signature_offsets.as_ptr(),
+            msg_start_offsets.as_ptr(),
+            out.as_mut_ptr(),
+            USE_NON_DEFAULT_STREAM,
+        );
+        if res != 0 {
+            trace!("RETURN!!!: {}", res);
+        }
+    }
+    // a blob whose size is out of bounds signs nothing
+    for (v, msg_size) in out.iter_mut().zip(&msg_sizes) {
+        if *msg_size == 0 {
+            *v = 0;
+        }
+    }
+    record_throughput("gpu_blobs", count, now.elapsed());
+    inc_new_counter_debug!("ed25519_verify_blobs_gpu", count);
+    out
+}
+
 #[cfg(test)]
 pub fn make_packet_from_transaction(tx: Transaction) -> Packet {
     use bincode::serialize;
@@ -296,11 +465,16 @@ pub fn make_packet_from_transaction(tx: Transaction) -> Packet {
 
 #[cfg(test)]
 mod tests {
-    use crate::packet::{Packet, Packets};
+    use crate::packet::{to_packets, Packet, Packets};
     use crate::signatureVerify;
-    use crate::testTx::{test_multisig_tx, test_tx};
+    use crate::testTx::{test_multisig_tx, test_tx, TestTxGenerator};
     use bincode::{deserialize, serialize};
+    use morgan_interface::hash::Hash;
+    use morgan_interface::message::MessageAddressTableLookup;
+    use morgan_interface::pubkey::Pubkey;
+    use morgan_interface::signature::Signature;
     use morgan_interface::transaction::Transaction;
+    use std::mem::size_of;
 
     const SIG_OFFSET: usize = 1;
 
@@ -332,7 +506,7 @@ mod tests {
         let packet = signatureVerify::make_packet_from_transaction(tx.clone());
 
         let (sig_len, sig_start, msg_start_offset, pubkey_offset) =
-            signatureVerify::get_packet_offsets(&packet, 0);
+            signatureVerify::get_packet_offsets(&packet, 0).unwrap();
 
         assert_eq!(
             memfind(&tx_bytes, &tx.signatures[0].as_ref()),
@@ -378,7 +552,7 @@ mod tests {
     fn get_packet_offsets_from_tx(tx: Transaction, current_offset: u32) -> (u32, u32, u32, u32) {
         let packet = signatureVerify::make_packet_from_transaction(tx);
         let (sig_len, sig_start, msg_start_offset, pubkey_offset) =
-            signatureVerify::get_packet_offsets(&packet, current_offset);
+            signatureVerify::get_packet_offsets(&packet, current_offset).unwrap();
         (
             sig_len,
             sig_start - current_offset,
@@ -405,6 +579,50 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_packet_offsets_malformed() {
+        // no length prefix at all
+        let packet = Packet::default();
+        assert!(signatureVerify::get_packet_offsets(&packet, 0).is_err());
+
+        // more signatures than the packet can hold
+        let mut packet = signatureVerify::make_packet_from_transaction(test_tx());
+        packet.data[..3].copy_from_slice(&[0xff, 0xff, 0x03]);
+        assert!(signatureVerify::get_packet_offsets(&packet, 0).is_err());
+
+        // truncated ahead of the account keys
+        let mut packet = signatureVerify::make_packet_from_transaction(test_tx());
+        packet.meta.size = 1 + size_of::<Signature>() + 2;
+        assert!(signatureVerify::get_packet_offsets(&packet, 0).is_err());
+
+        let batches = vec![Packets::new(vec![packet])];
+        assert_eq!(signatureVerify::ed25519_verify(&batches), vec![vec![0u8]]);
+    }
+
+    #[test]
+    fn test_get_packet_offsets_versioned_message() {
+        let mut tx = test_tx();
+        tx.message.address_table_lookups = vec![MessageAddressTableLookup {
+            account_key: Pubkey::new_rand(),
+            writable_indexes: vec![0],
+            readonly_indexes: vec![],
+        }];
+        let tx_bytes = serialize(&tx).unwrap();
+        assert_eq!(get_packet_offsets_from_tx(tx.clone(), 0), (1, 1, 64, 5));
+
+        let packet = signatureVerify::make_packet_from_transaction(tx.clone());
+        let (_, _, msg_start_offset, pubkey_offset) =
+            signatureVerify::get_packet_offsets(&packet, 0).unwrap();
+        assert_eq!(
+            memfind(&tx_bytes, &tx.message().account_keys[0].as_ref()),
+            Some(pubkey_offset as usize)
+        );
+        assert_eq!(
+            memfind(&tx_bytes, &tx.message_data()),
+            Some(msg_start_offset as usize)
+        );
+    }
+
     fn generate_packet_vec(
         packet: &Packet,
         num_packets_per_batch: usize,
@@ -486,8 +704,51 @@ mod tests {
         assert_eq!(ans, ref_vec);
     }
 
+    #[test]
+    fn test_verify_generated_txs() {
+        let mut generator = TestTxGenerator::new([7u8; 32]);
+        let payers = generator.keypairs(2);
+        let blockhash = Hash::default();
+        let mut txs = generator.contended_transfer_txs(&payers, 4, 1, blockhash);
+        txs.push(generator.multi_transfer_tx(&payers[0], 8, 1, blockhash));
+        txs.push(generator.vote_tx(&payers[0], &payers[1], &[0, 1, 2], blockhash));
+        txs.push(generator.failing_tx(&payers[1], 1, blockhash));
+        let batches = to_packets(&txs);
+
+        // signatures hold whether or not the transactions would execute
+        let ans = signatureVerify::ed25519_verify(&batches);
+        assert_eq!(ans, vec![vec![1u8; txs.len()]]);
+    }
+
     #[test]
     fn test_verify_fail() {
         test_verify_n(5, true);
     }
+
+    #[test]
+    fn test_verify_blobs() {
+        use crate::packet::{index_blobs, Blob};
+        use morgan_interface::signature::{Keypair, KeypairUtil};
+        use std::sync::{Arc, RwLock};
+
+        let keypair = Keypair::new();
+        let blobs: Vec<_> = (0..4)
+            .map(|_| Arc::new(RwLock::new(Blob::from_serializable(&vec![1u8; 64]))))
+            .collect();
+        index_blobs(&blobs, &keypair.pubkey(), 0, 1, 0);
+        for blob in &blobs[..3] {
+            blob.write().unwrap().sign(&keypair);
+        }
+        blobs[1].write().unwrap().set_index(7);
+
+        assert_eq!(signatureVerify::ed25519_verify_blobs(&blobs), vec![1, 0, 1, 0]);
+    }
+
+    #[test]
+    fn test_use_gpu() {
+        let min_batch_size = signatureVerify::gpu_min_batch_size();
+        assert!(!signatureVerify::use_gpu(false, min_batch_size));
+        assert!(signatureVerify::use_gpu(true, min_batch_size));
+        assert!(!signatureVerify::use_gpu(true, min_batch_size - 1));
+    }
 }
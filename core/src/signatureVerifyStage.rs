@@ -3,16 +3,17 @@
 //! top-level list with a list of booleans, telling the next stage whether the
 //! signature in that packet is valid. It assumes each packet contains one
 //! transaction. All processing is done on the CPU by default and on a GPU
-//! if the `cuda` feature is enabled with `--features=cuda`.
+//! if the `cuda` feature is enabled with `--features=cuda`.  `verify_blobs` puts the leader
+//! signatures of blobs the TVU receives on the same path.
 
-use crate::packet::Packets;
+use crate::packet::{Packets, SharedBlobs};
 use crate::result::{Error, Result};
 use crate::service::Service;
 use crate::signatureVerify;
-use crate::streamer::{self, PacketReceiver};
+use crate::streamer::{self, PacketReceiver, QueueReceiver, QueueSender};
 use morgan_metricbot::{datapoint_info, inc_new_counter_info};
 use morgan_interface::timing;
-use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::mpsc::{RecvTimeoutError, TrySendError};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, Builder, JoinHandle};
 use std::time::Instant;
@@ -24,7 +25,12 @@ const RECV_BATCH_MAX: usize = 60_000;
 #[cfg(not(feature = "cuda"))]
 const RECV_BATCH_MAX: usize = 1000;
 
+/// Verified batches queued for the banking stage before the verifiers start waiting on it
+pub const MAX_QUEUED_VERIFIED_BATCHES: usize = 16;
+
 pub type VerifiedPackets = Vec<(Packets, Vec<u8>)>;
+pub type VerifiedSender = QueueSender<VerifiedPackets>;
+pub type VerifiedReceiver = QueueReceiver<VerifiedPackets>;
 
 pub struct SigVerifyStage {
     thread_hdls: Vec<JoinHandle<()>>,
@@ -33,9 +39,9 @@ pub struct SigVerifyStage {
 impl SigVerifyStage {
     #[allow(clippy::new_ret_no_self)]
     pub fn new(
-        packet_receiver: Receiver<Packets>,
+        packet_receiver: PacketReceiver,
         sigverify_disabled: bool,
-        verified_sender: Sender<VerifiedPackets>,
+        verified_sender: VerifiedSender,
     ) -> Self {
         signatureVerify::init();
         let thread_hdls =
@@ -52,9 +58,25 @@ impl SigVerifyStage {
         batch.into_iter().zip(r).collect()
     }
 
+    /// Keep the blobs signed by the node in their id field, verified as one batch
+    pub fn verify_blobs(blobs: SharedBlobs) -> SharedBlobs {
+        if blobs.is_empty() {
+            return blobs;
+        }
+        let r = signatureVerify::ed25519_verify_blobs(&blobs);
+        let len = blobs.len();
+        let verified: SharedBlobs = blobs
+            .into_iter()
+            .zip(r)
+            .filter_map(|(blob, v)| if v != 0 { Some(blob) } else { None })
+            .collect();
+        inc_new_counter_info!("sigverify_stage-blobs_failed", len - verified.len());
+        verified
+    }
+
     fn verifier(
         recvr: &Arc<Mutex<PacketReceiver>>,
-        sendr: &Sender<VerifiedPackets>,
+        sendr: &VerifiedSender,
         sigverify_disabled: bool,
         id: usize,
     ) -> Result<()> {
@@ -76,8 +98,16 @@ impl SigVerifyStage {
         let verified_batch = Self::verify_batch(batch, sigverify_disabled);
         inc_new_counter_info!("sigverify_stage-verified_packets_send", len);
 
-        if sendr.send(verified_batch).is_err() {
-            return Err(Error::SendError);
+        // a full channel means banking is behind, wait for it rather than verify more
+        match sendr.try_send(verified_batch) {
+            Ok(()) => (),
+            Err(TrySendError::Full(verified_batch)) => {
+                inc_new_counter_info!("sigverify_stage-verified_sender_full", 1);
+                if sendr.send(verified_batch).is_err() {
+                    return Err(Error::SendError);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => return Err(Error::SendError),
         }
 
         let total_time_ms = timing::duration_as_ms(&now.elapsed());
@@ -108,7 +138,7 @@ impl SigVerifyStage {
 
     fn verifier_service(
         packet_receiver: Arc<Mutex<PacketReceiver>>,
-        verified_sender: Sender<VerifiedPackets>,
+        verified_sender: VerifiedSender,
         sigverify_disabled: bool,
         id: usize,
     ) -> JoinHandle<()> {
@@ -142,7 +172,7 @@ impl SigVerifyStage {
 
     fn verifier_services(
         packet_receiver: PacketReceiver,
-        verified_sender: Sender<VerifiedPackets>,
+        verified_sender: VerifiedSender,
         sigverify_disabled: bool,
     ) -> Vec<JoinHandle<()>> {
         let receiver = Arc::new(Mutex::new(packet_receiver));
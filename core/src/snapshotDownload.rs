@@ -0,0 +1,276 @@
+//! The `snapshotDownload` module fetches a snapshot archive from a peer's RPC service, see
+//! `SNAPSHOT_DOWNLOAD_PATH`, so a new validator can start from it instead of replaying the
+//! whole ledger.  The peer isn't trusted: the archive's bank is restored and its hash checked
+//! against one the operator learned out of band, or its accounts hash against the hash a
+//! stake-weighted majority of the cluster gossiped for the same slot, before the archive is
+//! installed where the validator restores from.
+
+use crate::accountsHashVerifier::AccountsHashVerifier;
+use crate::clusterMessage::GOSSIP_SLEEP_MILLIS;
+use crate::gossipService::make_gossip_node;
+use crate::rpcService::SNAPSHOT_DOWNLOAD_PATH;
+use crate::service::Service;
+use crate::stakingUtils;
+use morgan_helper::logHelper::*;
+use morgan_interface::hash::Hash;
+use morgan_runtime::bank::Bank;
+use morgan_runtime::snapshot::{install_snapshot, restore_snapshot, snapshot_archive_path};
+use std::fs::{self, File};
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+/// What a downloaded snapshot is checked against before it's installed
+#[derive(Debug)]
+pub enum SnapshotTrust {
+    /// The hash its bank must have
+    BankHash(Hash),
+    /// The accounts hash gossiped for its slot by nodes holding more than half of the stake
+    /// its bank holds for the slot's epoch.  The cluster only gossips accounts hashes for the
+    /// first root of each epoch, so only snapshots of those slots can be checked this way
+    Gossip {
+        entrypoint: SocketAddr,
+        timeout: Duration,
+    },
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Download the archive of `slot`, or the newest one, from the RPC service at `rpc_addr` to
+/// `download_path`
+pub fn download_snapshot(
+    rpc_addr: &SocketAddr,
+    slot: Option<u64>,
+    download_path: &Path,
+) -> io::Result<()> {
+    let request_path = match slot {
+        Some(slot) => format!("/{}", snapshot_archive_path("", slot).to_string_lossy()),
+        None => SNAPSHOT_DOWNLOAD_PATH.to_string(),
+    };
+    let url = format!("http://{}{}", rpc_addr, request_path);
+    let mut response = reqwest::Client::new()
+        .get(&url)
+        .send()
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    if !response.status().is_success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("get {} failed: {}", url, response.status()),
+        ));
+    }
+    let mut file = File::create(download_path)?;
+    response
+        .copy_to(&mut file)
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    file.sync_all()
+}
+
+/// Restore the bank of the archive at `archive_path` and check it against `trust`
+pub fn verify_snapshot(archive_path: &Path, trust: &SnapshotTrust) -> io::Result<Bank> {
+    let bank = restore_snapshot(archive_path, None, None)?;
+    match trust {
+        SnapshotTrust::BankHash(trusted_hash) => {
+            if bank.hash() != *trusted_hash {
+                return Err(invalid_data(format!(
+                    "snapshot bank hash {} of slot {} does not match the trusted {}",
+                    bank.hash(),
+                    bank.slot(),
+                    trusted_hash
+                )));
+            }
+        }
+        SnapshotTrust::Gossip {
+            entrypoint,
+            timeout,
+        } => {
+            let cluster_hash = gossiped_accounts_hash(&bank, entrypoint, *timeout)?;
+            let accounts_hash = bank.hash_accounts();
+            if accounts_hash != cluster_hash {
+                return Err(invalid_data(format!(
+                    "snapshot accounts hash {} of slot {} does not match the cluster's {}",
+                    accounts_hash,
+                    bank.slot(),
+                    cluster_hash
+                )));
+            }
+        }
+    }
+    Ok(bank)
+}
+
+/// The accounts hash gossiped for `bank`'s slot by a stake-weighted majority, waiting up to
+/// `timeout` for enough of the cluster to be heard from
+fn gossiped_accounts_hash(
+    bank: &Bank,
+    entrypoint: &SocketAddr,
+    timeout: Duration,
+) -> io::Result<Hash> {
+    let epoch = bank.get_epoch_and_slot_index(bank.slot()).0;
+    let stakes = stakingUtils::staked_nodes_at_epoch(bank, epoch)
+        .ok_or_else(|| invalid_data(format!("snapshot has no stakes for epoch {}", epoch)))?;
+
+    let exit = Arc::new(AtomicBool::new(false));
+    let (gossip_service, spy_ref) = make_gossip_node(entrypoint, &exit, None);
+    let now = Instant::now();
+    let mut majority_hash = None;
+    while majority_hash.is_none() && now.elapsed() < timeout {
+        sleep(Duration::from_millis(GOSSIP_SLEEP_MILLIS));
+        let reports: Vec<_> = spy_ref
+            .read()
+            .unwrap()
+            .get_accounts_hashes(bank.slot())
+            .into_iter()
+            .map(|accounts_hash| (accounts_hash.from, accounts_hash.hash))
+            .collect();
+        majority_hash = AccountsHashVerifier::majority_hash(&reports, &stakes);
+    }
+    exit.store(true, Ordering::Relaxed);
+    gossip_service.join().unwrap();
+
+    majority_hash.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!(
+                "no stake-weighted majority gossiped an accounts hash for slot {} in {:?}",
+                bank.slot(),
+                timeout
+            ),
+        )
+    })
+}
+
+/// Download the archive of `slot`, or the newest one, from the RPC service at `rpc_addr`,
+/// check it against `trust` and install it under `snapshot_path` for a validator to restore
+/// from.  Returns the installed archive's path, an archive that fails its checks is removed.
+pub fn fetch_snapshot(
+    rpc_addr: &SocketAddr,
+    slot: Option<u64>,
+    trust: &SnapshotTrust,
+    snapshot_path: &Path,
+) -> io::Result<PathBuf> {
+    fs::create_dir_all(snapshot_path)?;
+    let download_path = snapshot_path.join("snapshot.download");
+    let result = download_snapshot(rpc_addr, slot, &download_path)
+        .and_then(|()| verify_snapshot(&download_path, trust))
+        .and_then(|bank| {
+            println!(
+                "{}",
+                printLn(
+                    format!(
+                        "verified snapshot of slot {} with bank hash {}",
+                        bank.slot(),
+                        bank.hash()
+                    )
+                    .to_string(),
+                    module_path!().to_string()
+                )
+            );
+            install_snapshot(&download_path, snapshot_path)
+        });
+    if result.is_err() {
+        let _ignored = fs::remove_file(&download_path);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clusterMessage::ClusterInfo;
+    use crate::connectionInfo::ContactInfo;
+    use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
+    use crate::replayErrors::ReplayErrors;
+    use crate::rpc::JsonRpcConfig;
+    use crate::rpcService::JsonRpcService;
+    use crate::storageStage::StorageState;
+    use crate::transactionTrace::TransactionTraces;
+    use crate::treasuryForks::BankForks;
+    use morgan_interface::hash::hash;
+    use morgan_interface::pubkey::Pubkey;
+    use morgan_runtime::snapshot::{restore_incremental_snapshot, save_snapshot};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::RwLock;
+
+    #[test]
+    fn test_verify_snapshot_bank_hash() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        bank.transfer(5, &mint_keypair, &Pubkey::new_rand())
+            .unwrap();
+        bank.freeze();
+        let snapshot_path = PathBuf::from("target").join("test_verify_snapshot_bank_hash");
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+        let archive_path = save_snapshot(&bank, &snapshot_path).unwrap();
+
+        let restored = verify_snapshot(&archive_path, &SnapshotTrust::BankHash(bank.hash()));
+        assert_eq!(restored.unwrap().hash(), bank.hash());
+        let err = verify_snapshot(&archive_path, &SnapshotTrust::BankHash(hash(&[0])))
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+
+    #[test]
+    fn test_fetch_snapshot() {
+        let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        bank.freeze();
+        let bank_hash = bank.hash();
+        let peer_snapshot_path = PathBuf::from("target").join("test_fetch_snapshot_peer");
+        let _ignored = fs::remove_dir_all(&peer_snapshot_path);
+        save_snapshot(&bank, &peer_snapshot_path).unwrap();
+
+        let exit = Arc::new(AtomicBool::new(false));
+        let rpc_addr = SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)),
+            morgan_netutil::find_available_port_in_range((10000, 65535)).unwrap(),
+        );
+        let rpc_service = JsonRpcService::new(
+            &Arc::new(RwLock::new(ClusterInfo::new_with_invalid_keypair(
+                ContactInfo::default(),
+            ))),
+            rpc_addr,
+            StorageState::default(),
+            JsonRpcConfig {
+                snapshot_path: Some(peer_snapshot_path.clone()),
+                ..JsonRpcConfig::default()
+            },
+            Arc::new(RwLock::new(BankForks::new(bank.slot(), bank))),
+            None,
+            &Arc::new(TransactionTraces::default()),
+            &Arc::new(ReplayErrors::default()),
+            &exit,
+        );
+        sleep(Duration::from_millis(500));
+
+        // A snapshot that doesn't match the trusted hash is refused and not kept
+        let snapshot_path = PathBuf::from("target").join("test_fetch_snapshot");
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+        let trust = SnapshotTrust::BankHash(hash(&[0]));
+        assert!(fetch_snapshot(&rpc_addr, None, &trust, &snapshot_path).is_err());
+        assert_eq!(fs::read_dir(&snapshot_path).unwrap().count(), 0);
+        let trust = SnapshotTrust::BankHash(bank_hash);
+        assert!(fetch_snapshot(&rpc_addr, Some(1), &trust, &snapshot_path).is_err());
+
+        let archive_path = fetch_snapshot(&rpc_addr, Some(0), &trust, &snapshot_path).unwrap();
+        assert_eq!(archive_path, snapshot_archive_path(&snapshot_path, 0));
+        let restored = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+        assert_eq!(restored.hash(), bank_hash);
+
+        exit.store(true, Ordering::Relaxed);
+        rpc_service.join().unwrap();
+        let _ignored = fs::remove_dir_all(&peer_snapshot_path);
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+}
@@ -0,0 +1,248 @@
+//! The `snapshotService` module saves a snapshot of the root bank every so many rooted slots,
+//! so a restarting validator can restore its bank from the newest one instead of replaying the
+//! whole ledger, see `Validator::new`.  `BankForks::set_root` takes the snapshots, later roots
+//! would show through the bank otherwise, and this service archives them.  Between full
+//! snapshots it can also archive incremental ones, holding only the accounts changed since the
+//! last full one.
+
+use crate::result::Result;
+use crate::service::Service;
+use crate::treasuryForks::BankForks;
+use morgan_helper::logHelper::*;
+use morgan_interface::timing::duration_as_ms;
+use morgan_runtime::snapshot::{
+    incremental_snapshot_archive_path, incremental_snapshot_slots, snapshot_archive_path,
+    snapshot_slots,
+};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Rooted slots between two snapshots
+pub const DEFAULT_SNAPSHOT_INTERVAL_SLOTS: u64 = 100;
+
+/// Archives kept on disk, older ones are removed once a new one is saved
+pub const MAX_SNAPSHOTS: usize = 2;
+
+/// Incremental archives kept on disk, older ones are removed once a new one is saved and all
+/// of them once a full one is
+pub const MAX_INCREMENTAL_SNAPSHOTS: usize = 4;
+
+/// How often the service checks for a snapshot to archive
+const SNAPSHOT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct SnapshotService {
+    t_snapshot: JoinHandle<()>,
+}
+
+impl SnapshotService {
+    pub fn new(
+        snapshot_path: PathBuf,
+        snapshot_interval_slots: u64,
+        incremental_snapshot_interval_slots: Option<u64>,
+        bank_forks: Arc<RwLock<BankForks>>,
+        exit: &Arc<AtomicBool>,
+    ) -> Self {
+        let last_snapshot_slot = snapshot_slots(&snapshot_path)
+            .ok()
+            .and_then(|slots| slots.first().cloned());
+        bank_forks
+            .write()
+            .unwrap()
+            .capture_snapshots(snapshot_interval_slots, last_snapshot_slot);
+        if let Some(incremental_snapshot_interval_slots) = incremental_snapshot_interval_slots {
+            let last_incremental_snapshot_slot = incremental_snapshot_slots(&snapshot_path)
+                .ok()
+                .and_then(|slots| slots.first().cloned());
+            bank_forks.write().unwrap().capture_incremental_snapshots(
+                incremental_snapshot_interval_slots,
+                last_incremental_snapshot_slot,
+            );
+        }
+        let exit = exit.clone();
+        let t_snapshot = Builder::new()
+            .name("morgan-snapshot".to_string())
+            .spawn(move || {
+                while !exit.load(Ordering::Relaxed) {
+                    if let Err(e) = Self::save_snapshot(&snapshot_path, &bank_forks) {
+                        println!(
+                            "{}",
+                            Warn(
+                                format!("snapshot failed: {:?}", e).to_string(),
+                                module_path!().to_string()
+                            )
+                        );
+                    }
+                    sleep(SNAPSHOT_POLL_INTERVAL);
+                }
+            })
+            .unwrap();
+        Self { t_snapshot }
+    }
+
+    fn save_snapshot(snapshot_path: &PathBuf, bank_forks: &RwLock<BankForks>) -> Result<()> {
+        let snapshot = match bank_forks.write().unwrap().take_snapshot() {
+            Some(snapshot) => snapshot,
+            None => return Ok(()),
+        };
+
+        let now = Instant::now();
+        snapshot.save(snapshot_path)?;
+        // The increments are against the last full snapshot, the manifest still lists the
+        // removed ones so the chain can be checked
+        let max_incremental_snapshots = match snapshot.base_slot() {
+            None => {
+                for slot in snapshot_slots(snapshot_path)?
+                    .into_iter()
+                    .skip(MAX_SNAPSHOTS)
+                {
+                    fs::remove_file(snapshot_archive_path(snapshot_path, slot))?;
+                }
+                0
+            }
+            Some(_) => MAX_INCREMENTAL_SNAPSHOTS,
+        };
+        for slot in incremental_snapshot_slots(snapshot_path)?
+            .into_iter()
+            .skip(max_incremental_snapshots)
+        {
+            fs::remove_file(incremental_snapshot_archive_path(snapshot_path, slot))?;
+        }
+        datapoint_info!(
+            "snapshot-service",
+            ("slot", snapshot.slot() as i64, i64),
+            ("incremental", snapshot.base_slot().is_some() as i64, i64),
+            ("elapsed_ms", duration_as_ms(&now.elapsed()) as i64, i64)
+        );
+        Ok(())
+    }
+}
+
+impl Service for SnapshotService {
+    type JoinReturnType = ();
+
+    fn join(self) -> thread::Result<()> {
+        self.t_snapshot.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
+    use morgan_interface::pubkey::Pubkey;
+    use morgan_runtime::bank::Bank;
+    use morgan_runtime::snapshot::{restore_incremental_snapshot, restore_snapshot};
+
+    #[test]
+    fn test_save_snapshot() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        bank.freeze();
+        let bank_forks = RwLock::new(BankForks::new(0, bank));
+        bank_forks.write().unwrap().capture_snapshots(2, None);
+        let snapshot_path = PathBuf::from("target").join("test_save_snapshot");
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+
+        let key = Pubkey::new_rand();
+        let mut bank4_hash = None;
+        for slot in 1..=6 {
+            let mut w_bank_forks = bank_forks.write().unwrap();
+            let parent = w_bank_forks.working_bank();
+            let bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+            bank.transfer(slot, &mint_keypair, &key).unwrap();
+            bank.freeze();
+            if slot == 4 {
+                bank4_hash = Some(bank.hash());
+            }
+            w_bank_forks.insert(bank);
+            w_bank_forks.set_root(slot - 1);
+            drop(w_bank_forks);
+            // archive the snapshots only once the next root is set
+            if slot % 2 == 0 {
+                SnapshotService::save_snapshot(&snapshot_path, &bank_forks).unwrap();
+            }
+        }
+
+        // Roots 0 through 5 were snapshotted at 0, 2 and 4, only the newest two are kept
+        assert_eq!(snapshot_slots(&snapshot_path).unwrap(), vec![4, 2]);
+        assert!(bank_forks.write().unwrap().take_snapshot().is_none());
+
+        // Root 5 doesn't show through the snapshot of root 4
+        let archive_path = snapshot_archive_path(&snapshot_path, 4);
+        let bank = restore_snapshot(&archive_path, None, None).unwrap();
+        assert_eq!(bank.get_balance(&key), 1 + 2 + 3 + 4);
+        assert_eq!(Some(bank.hash()), bank4_hash);
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+
+    #[test]
+    fn test_save_incremental_snapshot() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        bank.freeze();
+        let bank_forks = RwLock::new(BankForks::new(0, bank));
+        bank_forks.write().unwrap().capture_snapshots(8, None);
+        bank_forks
+            .write()
+            .unwrap()
+            .capture_incremental_snapshots(1, None);
+        let snapshot_path = PathBuf::from("target").join("test_save_incremental_snapshot");
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+
+        let key = Pubkey::new_rand();
+        let mut bank7_hash = None;
+        for slot in 1..=10 {
+            let mut w_bank_forks = bank_forks.write().unwrap();
+            let parent = w_bank_forks.working_bank();
+            let bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+            bank.transfer(slot, &mint_keypair, &key).unwrap();
+            bank.freeze();
+            if slot == 7 {
+                bank7_hash = Some(bank.hash());
+            }
+            w_bank_forks.insert(bank);
+            w_bank_forks.set_root(slot - 1);
+            drop(w_bank_forks);
+            // the full snapshot of root 8 is left pending while root 9 is set
+            if slot != 9 {
+                SnapshotService::save_snapshot(&snapshot_path, &bank_forks).unwrap();
+            }
+            if slot == 8 {
+                // Roots 1 through 7 were increments over root 0, only the newest are kept
+                assert_eq!(snapshot_slots(&snapshot_path).unwrap(), vec![0]);
+                assert_eq!(
+                    incremental_snapshot_slots(&snapshot_path).unwrap(),
+                    (8 - MAX_INCREMENTAL_SNAPSHOTS as u64..8)
+                        .rev()
+                        .collect::<Vec<_>>()
+                );
+                let bank = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+                assert_eq!(bank.slot(), 7);
+                assert_eq!(bank.get_balance(&key), (1..=7).sum::<u64>());
+                assert_eq!(Some(bank.hash()), bank7_hash);
+            }
+        }
+
+        // Root 9 didn't replace the pending full snapshot of root 8, which removed the increments
+        assert_eq!(snapshot_slots(&snapshot_path).unwrap(), vec![8, 0]);
+        assert!(incremental_snapshot_slots(&snapshot_path)
+            .unwrap()
+            .is_empty());
+        let bank = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+        assert_eq!(bank.slot(), 8);
+        assert_eq!(bank.get_balance(&key), (1..=8).sum::<u64>());
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+}
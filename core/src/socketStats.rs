@@ -0,0 +1,235 @@
+//! The `socketStats` module counts what each streamer socket receives and, where the kernel
+//! reports it, how much it dropped because the socket's receive buffer was full.  Drops happen
+//! before the streamer ever sees the packets, so they only show up in kernel counters; on
+//! Linux those are read from `/proc/net/udp` and `/proc/net/udp6`.
+
+use morgan_interface::timing::timestamp;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+
+/// Minimum time between two reports of the same socket
+const REPORT_INTERVAL_MS: usize = 1000;
+
+lazy_static! {
+    static ref SOCKET_STATS: RwLock<Vec<Weak<SocketStats>>> = RwLock::new(vec![]);
+}
+
+/// What the kernel reports about a socket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KernelSocketStats {
+    pub rx_queue_bytes: u64, // Received but not yet read
+    pub drops: u64,          // Dropped since the socket was opened
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SocketStatsSnapshot {
+    pub name: String,
+    pub local_addr: Option<SocketAddr>,
+    pub received_packets: u64,
+    pub received_batches: u64,
+    pub kernel_drops: Option<u64>,
+    pub rx_queue_bytes: Option<u64>,
+}
+
+pub struct SocketStats {
+    name: String,
+    local_addr: Option<SocketAddr>,
+    inode: Option<u64>,
+    received_packets: AtomicUsize,
+    received_batches: AtomicUsize,
+    reported_packets: AtomicUsize,
+    reported_batches: AtomicUsize,
+    reported_drops: AtomicUsize,
+    last_report: AtomicUsize,
+}
+
+impl SocketStats {
+    /// Start counting for `socket`, the counts are listed by `socket_stats()` until the returned
+    /// handle is dropped
+    pub fn register(name: &str, socket: &UdpSocket) -> Arc<Self> {
+        let stats = Arc::new(Self {
+            name: name.to_string(),
+            local_addr: socket.local_addr().ok(),
+            inode: socket_inode(socket),
+            received_packets: AtomicUsize::new(0),
+            received_batches: AtomicUsize::new(0),
+            reported_packets: AtomicUsize::new(0),
+            reported_batches: AtomicUsize::new(0),
+            reported_drops: AtomicUsize::new(0),
+            last_report: AtomicUsize::new(timestamp() as usize),
+        });
+        let mut registry = SOCKET_STATS.write().unwrap();
+        registry.retain(|stats| stats.upgrade().is_some());
+        registry.push(Arc::downgrade(&stats));
+        stats
+    }
+
+    pub fn record_received(&self, packets: usize) {
+        self.received_packets.fetch_add(packets, Ordering::Relaxed);
+        self.received_batches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> SocketStatsSnapshot {
+        let kernel_stats = self.inode.and_then(kernel_socket_stats);
+        SocketStatsSnapshot {
+            name: self.name.clone(),
+            local_addr: self.local_addr,
+            received_packets: self.received_packets.load(Ordering::Relaxed) as u64,
+            received_batches: self.received_batches.load(Ordering::Relaxed) as u64,
+            kernel_drops: kernel_stats.map(|stats| stats.drops),
+            rx_queue_bytes: kernel_stats.map(|stats| stats.rx_queue_bytes),
+        }
+    }
+
+    /// Submit the counts as metrics if the last report is older than `REPORT_INTERVAL_MS`.
+    /// Called from the receiving thread, which also wakes up when nothing arrives.
+    pub fn maybe_report(&self) {
+        let now = timestamp() as usize;
+        let last_report = self.last_report.load(Ordering::Relaxed);
+        if now.saturating_sub(last_report) < REPORT_INTERVAL_MS
+            || self
+                .last_report
+                .compare_and_swap(last_report, now, Ordering::Relaxed)
+                != last_report
+        {
+            return;
+        }
+
+        let snapshot = self.snapshot();
+        let since_report = |total: u64, reported: &AtomicUsize| {
+            let total = total as usize;
+            total.saturating_sub(reported.swap(total, Ordering::Relaxed))
+        };
+        let received_packets = since_report(snapshot.received_packets, &self.reported_packets);
+        let received_batches = since_report(snapshot.received_batches, &self.reported_batches);
+        let new_drops = since_report(snapshot.kernel_drops.unwrap_or(0), &self.reported_drops);
+        if new_drops > 0 {
+            inc_new_counter_warn!("streamer-socket_kernel_drops", new_drops);
+        }
+        datapoint_info!(
+            "socket-stats",
+            ("name", self.name.clone(), String),
+            (
+                "local_addr",
+                self.local_addr
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default(),
+                String
+            ),
+            ("received_packets", received_packets as i64, i64),
+            ("received_batches", received_batches as i64, i64),
+            ("kernel_drops", new_drops as i64, i64),
+            (
+                "rx_queue_bytes",
+                snapshot.rx_queue_bytes.unwrap_or(0) as i64,
+                i64
+            )
+        );
+    }
+}
+
+/// Snapshots of every socket currently being received from
+pub fn socket_stats() -> Vec<SocketStatsSnapshot> {
+    SOCKET_STATS
+        .read()
+        .unwrap()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|stats| stats.snapshot())
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn socket_inode(socket: &UdpSocket) -> Option<u64> {
+    use std::os::unix::io::AsRawFd;
+    nix::sys::stat::fstat(socket.as_raw_fd())
+        .ok()
+        .map(|stat| stat.st_ino as u64)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn socket_inode(_socket: &UdpSocket) -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn kernel_socket_stats(inode: u64) -> Option<KernelSocketStats> {
+    ["/proc/net/udp", "/proc/net/udp6"].iter().find_map(|path| {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|table| parse_proc_net_udp(&table, inode))
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kernel_socket_stats(_inode: u64) -> Option<KernelSocketStats> {
+    None
+}
+
+/// Find the socket `inode` in the contents of `/proc/net/udp`, whose lines look like
+/// `sl local_address rem_address st tx_queue:rx_queue tr:tm->when retrnsmt uid timeout inode
+/// ref pointer drops`
+fn parse_proc_net_udp(table: &str, inode: u64) -> Option<KernelSocketStats> {
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.len() < 13 || fields[9].parse::<u64>().ok()? != inode {
+            return None;
+        }
+        let rx_queue = fields[4].split(':').nth(1)?;
+        Some(KernelSocketStats {
+            rx_queue_bytes: u64::from_str_radix(rx_queue, 16).ok()?,
+            drops: fields[12].parse().ok()?,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc_net_udp() {
+        let table = "   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode ref pointer drops
+  541: 00000000:1F41 00000000:0000 07 00000000:00000000 00:00000000 00000000  1000        0 123456 2 0000000000000000 0
+  542: 0100007F:1F42 00000000:0000 07 00000000:00001A00 00:00000000 00000000  1000        0 654321 2 0000000000000000 17
+";
+        assert_eq!(
+            parse_proc_net_udp(table, 654321),
+            Some(KernelSocketStats {
+                rx_queue_bytes: 0x1a00,
+                drops: 17,
+            })
+        );
+        assert_eq!(
+            parse_proc_net_udp(table, 123456),
+            Some(KernelSocketStats {
+                rx_queue_bytes: 0,
+                drops: 0,
+            })
+        );
+        assert_eq!(parse_proc_net_udp(table, 1), None);
+    }
+
+    #[test]
+    fn test_socket_stats() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let stats = SocketStats::register("test", &socket);
+        stats.record_received(3);
+        stats.record_received(2);
+
+        let snapshot = socket_stats()
+            .into_iter()
+            .find(|snapshot| snapshot.local_addr == socket.local_addr().ok())
+            .unwrap();
+        assert_eq!(snapshot.name, "test");
+        assert_eq!(snapshot.received_packets, 5);
+        assert_eq!(snapshot.received_batches, 2);
+
+        drop(stats);
+        assert!(socket_stats()
+            .iter()
+            .all(|snapshot| snapshot.local_addr != socket.local_addr().ok()));
+    }
+}
@@ -8,6 +8,7 @@ use crate::packet::{Blob, SharedBlob, BLOB_HEADER_SIZE};
 use crate::fixMissingSpotService::{RepairService, RepairStrategy};
 use crate::result::{Error, Result};
 use crate::service::Service;
+use crate::signatureVerifyStage::SigVerifyStage;
 use crate::streamer::{BlobReceiver, BlobSender};
 use morgan_metricbot::{inc_new_counter_debug, inc_new_counter_error};
 use morgan_runtime::bank::Bank;
@@ -48,10 +49,10 @@ fn retransmit_blobs(blobs: &[SharedBlob], retransmit: &BlobSender, id: &Pubkey)
 
 /// Process a blob: Add blob to the ledger window.
 fn process_blobs(blobs: &[SharedBlob], blocktree: &Arc<Blocktree>) -> Result<()> {
-    // make an iterator for insert_data_blobs()
+    // make an iterator for buffer_data_blobs()
     let blobs: Vec<_> = blobs.iter().map(move |blob| blob.read().unwrap()).collect();
 
-    blocktree.insert_data_blobs(blobs.iter().filter_map(|blob| {
+    blocktree.buffer_data_blobs(blobs.iter().filter_map(|blob| {
         if !blob.is_coding() {
             Some(&(**blob))
         } else {
@@ -60,9 +61,6 @@ fn process_blobs(blobs: &[SharedBlob], blocktree: &Arc<Blocktree>) -> Result<()>
     }))?;
 
     for blob in blobs {
-        // TODO: Once the original leader signature is added to the blob, make sure that
-        // the blob was originally generated by the expected leader for this slot
-
         // Insert the new blob into block tree
         if blob.is_coding() {
             blocktree.put_coding_blob_bytes(
@@ -125,9 +123,16 @@ where
     inc_new_counter_debug!("streamer-recv_window-recv", blobs.len(), 0, 1000);
 
     blobs.retain(|blob| {
-        blob_filter(&blob.read().unwrap())
-            && blob.read().unwrap().genesis_blockhash() == *genesis_blockhash
+        let blob = blob.read().unwrap();
+        if blob.genesis_blockhash() != *genesis_blockhash {
+            // retransmitted by a node of another cluster
+            inc_new_counter_debug!("streamer-recv_window-genesis_mismatch", 1);
+            return false;
+        }
+        blob_filter(&blob)
     });
+    // only blobs signed by the leader `blob_filter` expects are kept and retransmitted
+    let blobs = SigVerifyStage::verify_blobs(blobs);
 
     retransmit_blobs(&blobs, retransmit, my_pubkey)?;
 
@@ -210,6 +215,7 @@ impl WindowService {
                 trace!("{}: RECV_WINDOW started", id);
                 loop {
                     if exit.load(Ordering::Relaxed) {
+                        let _ = blocktree.flush_pending_blobs();
                         break;
                     }
 
@@ -238,6 +244,19 @@ impl WindowService {
                             }
                         }
                     }
+
+                    // Slots that stopped receiving blobs still get written once their
+                    // flush interval elapses
+                    if let Err(e) = blocktree.flush_due_blobs() {
+                        inc_new_counter_error!("streamer-window-flush_error", 1, 1);
+                        println!(
+                            "{}",
+                            Error(
+                                format!("window flush error: {:?}", e).to_string(),
+                                module_path!().to_string()
+                            )
+                        );
+                    }
                 }
             })
             .unwrap();
@@ -270,6 +289,8 @@ mod test {
     use crate::packet::{index_blobs, Blob};
     use crate::service::Service;
     use crate::streamer::{blob_receiver, responder};
+    use crate::ClusterVoteMessageListener::VoteTracker;
+    use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_runtime::epoch_schedule::MINIMUM_SLOT_LENGTH;
     use morgan_interface::hash::Hash;
     use std::fs::remove_dir_all;
@@ -353,7 +374,8 @@ mod test {
         morgan_logger::setup();
         // setup a leader whose id is used to generates blobs and a validator
         // node whose window service will retransmit leader blobs.
-        let leader_node = Node::new_localhost();
+        let leader_keypair = Keypair::new();
+        let leader_node = Node::new_localhost_with_pubkey(&leader_keypair.pubkey());
         let validator_node = Node::new_localhost();
         let exit = Arc::new(AtomicBool::new(false));
         let cluster_info_me = ClusterInfo::new_with_invalid_keypair(validator_node.info.clone());
@@ -379,6 +401,7 @@ mod test {
                 .working_bank()
                 .epoch_schedule()
                 .clone(),
+            vote_tracker: Arc::new(RwLock::new(VoteTracker::default())),
         };
         let t_window = WindowService::new(
             blocktree,
@@ -400,7 +423,7 @@ mod test {
             let num_blobs_to_make = 10;
             let gossip_address = &leader_node.info.gossip;
             let msgs = make_consecutive_blobs(
-                &me_id,
+                &leader_keypair,
                 num_blobs_to_make,
                 0,
                 Hash::default(),
@@ -440,7 +463,8 @@ mod test {
         morgan_logger::setup();
         // setup a leader whose id is used to generates blobs and a validator
         // node whose window service will retransmit leader blobs.
-        let leader_node = Node::new_localhost();
+        let leader_keypair = Keypair::new();
+        let leader_node = Node::new_localhost_with_pubkey(&leader_keypair.pubkey());
         let validator_node = Node::new_localhost();
         let exit = Arc::new(AtomicBool::new(false));
         let cluster_info_me = ClusterInfo::new_with_invalid_keypair(validator_node.info.clone());
@@ -462,6 +486,7 @@ mod test {
             bank_forks,
             completed_slots_receiver,
             epoch_schedule,
+            vote_tracker: Arc::new(RwLock::new(VoteTracker::default())),
         };
         let t_window = WindowService::new(
             blocktree,
@@ -480,8 +505,13 @@ mod test {
                 leader_node.sockets.tvu.into_iter().map(Arc::new).collect();
             let t_responder = responder("window_send_test", blob_sockets[0].clone(), r_responder);
             let mut msgs = Vec::new();
-            let blobs =
-                make_consecutive_blobs(&me_id, 14u64, 0, Hash::default(), &leader_node.info.gossip);
+            let blobs = make_consecutive_blobs(
+                &leader_keypair,
+                14u64,
+                0,
+                Hash::default(),
+                &leader_node.info.gossip,
+            );
 
             for v in 0..10 {
                 let i = 9 - v;
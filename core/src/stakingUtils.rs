@@ -51,11 +51,11 @@ pub fn staked_nodes_at_epoch(bank: &Bank, epoch_height: u64) -> Option<HashMap<P
 }
 
 // input (vote_pubkey, (stake, vote_account)) => (stake, vote_state)
-fn to_vote_states(
-    node_staked_accounts: impl Iterator<Item = (impl Borrow<Pubkey>, impl Borrow<(u64, Account)>)>,
+fn to_vote_states<A: Borrow<Account>>(
+    node_staked_accounts: impl Iterator<Item = (impl Borrow<Pubkey>, impl Borrow<(u64, A)>)>,
 ) -> impl Iterator<Item = (u64, VoteState)> {
     node_staked_accounts.filter_map(|(_, stake_account)| {
-        VoteState::deserialize(&stake_account.borrow().1.data)
+        VoteState::deserialize(&stake_account.borrow().1.borrow().data)
             .ok()
             .map(|vote_state| (stake_account.borrow().0, vote_state))
     })
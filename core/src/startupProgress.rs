@@ -0,0 +1,108 @@
+//! The `startupProgress` module tracks how far a validator is into starting up.  Replaying a
+//! long ledger can take a while, the phase the node is in is logged as it changes and served
+//! by the admin RPC's `getHealth`, so an operator can tell a busy node from a hung one.
+
+use morgan_helper::logHelper::*;
+use std::mem;
+use std::sync::RwLock;
+
+/// A phase of validator startup, in the order they run
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", tag = "phase")]
+pub enum StartupPhase {
+    LoadingGenesis,
+    LoadingSnapshot,
+    /// Replaying `slot` of the ledger, whose highest slot is `max_slot`
+    #[serde(rename_all = "camelCase")]
+    ProcessingLedger {
+        slot: u64,
+        max_slot: u64,
+    },
+    StartingServices,
+    /// Startup is done
+    Running,
+}
+
+#[derive(Debug)]
+pub struct StartupProgress {
+    phase: RwLock<StartupPhase>,
+}
+
+impl Default for StartupProgress {
+    fn default() -> Self {
+        Self {
+            phase: RwLock::new(StartupPhase::LoadingGenesis),
+        }
+    }
+}
+
+impl StartupProgress {
+    pub fn phase(&self) -> StartupPhase {
+        self.phase.read().unwrap().clone()
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.phase.read().unwrap() == StartupPhase::Running
+    }
+
+    /// Move on to `phase`, logged only when it starts a phase, not on every slot replayed
+    pub fn set_phase(&self, phase: StartupPhase) {
+        let previous = mem::replace(&mut *self.phase.write().unwrap(), phase.clone());
+        if mem::discriminant(&previous) != mem::discriminant(&phase) {
+            println!(
+                "{}",
+                printLn(
+                    format!("startup phase: {:?}", phase),
+                    module_path!().to_string()
+                )
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_startup_progress() {
+        let progress = StartupProgress::default();
+        assert_eq!(progress.phase(), StartupPhase::LoadingGenesis);
+        assert!(!progress.is_running());
+
+        progress.set_phase(StartupPhase::ProcessingLedger {
+            slot: 3,
+            max_slot: 10,
+        });
+        progress.set_phase(StartupPhase::ProcessingLedger {
+            slot: 4,
+            max_slot: 10,
+        });
+        assert_eq!(
+            progress.phase(),
+            StartupPhase::ProcessingLedger {
+                slot: 4,
+                max_slot: 10,
+            }
+        );
+
+        progress.set_phase(StartupPhase::Running);
+        assert!(progress.is_running());
+    }
+
+    #[test]
+    fn test_startup_phase_serialize() {
+        assert_eq!(
+            serde_json::to_string(&StartupPhase::ProcessingLedger {
+                slot: 4,
+                max_slot: 10,
+            })
+            .unwrap(),
+            r#"{"phase":"processingLedger","slot":4,"maxSlot":10}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&StartupPhase::Running).unwrap(),
+            r#"{"phase":"running"}"#
+        );
+    }
+}
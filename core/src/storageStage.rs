@@ -21,7 +21,7 @@ use morgan_interface::signature::{Keypair, KeypairUtil, Signature};
 use morgan_interface::transaction::Transaction;
 use morgan_storage_api::storage_contract::{CheckedProof, Proof, ProofStatus};
 use morgan_storage_api::storage_instruction::{proof_validation, StorageInstruction};
-use morgan_storage_api::{get_segment_from_slot, storage_instruction};
+use morgan_storage_api::{get_segment_from_slot, storage_instruction, PROOF_WINDOW_SEGMENTS};
 use std::collections::HashMap;
 use std::io;
 use std::mem::size_of;
@@ -149,6 +149,7 @@ impl StorageStage {
             let storage_state_inner = storage_state.state.clone();
             let exit = exit.clone();
             let storage_keypair = storage_keypair.clone();
+            let bank_forks = bank_forks.clone();
             Builder::new()
                 .name("morgan-storage-mining-verify-stage".to_string())
                 .spawn(move || {
@@ -162,6 +163,7 @@ impl StorageStage {
                                 &storage_state_inner,
                                 &slot_receiver,
                                 &some_blocktree,
+                                &bank_forks,
                                 &mut slot_count,
                                 &mut last_root,
                                 &mut current_key,
@@ -318,20 +320,32 @@ impl StorageStage {
         Ok(())
     }
 
+    /// The bank hash of root `slot`, or of the current root if `slot` was already pruned. It
+    /// depends on the account state at that root, so nobody can know it while a segment is
+    /// being stored.
+    fn get_root_bank_hash(bank_forks: &Arc<RwLock<BankForks>>, slot: u64) -> Hash {
+        let bank_forks = bank_forks.read().unwrap();
+        bank_forks
+            .get(slot)
+            .or_else(|| bank_forks.get(bank_forks.root()))
+            .map(|bank| bank.hash())
+            .unwrap_or_default()
+    }
+
     fn process_entry_crossing(
         storage_keypair: &Arc<Keypair>,
         state: &Arc<RwLock<StorageStateInner>>,
         _blocktree: &Arc<Blocktree>,
-        entry_id: Hash,
+        storage_blockhash: Hash,
         slot: u64,
         instruction_sender: &InstructionSender,
     ) -> Result<()> {
         let mut seed = [0u8; 32];
-        let signature = storage_keypair.sign(&entry_id.as_ref());
+        let signature = storage_keypair.sign(&storage_blockhash.as_ref());
 
         let ix = storage_instruction::advertise_recent_blockhash(
             &storage_keypair.pubkey(),
-            entry_id,
+            storage_blockhash,
             slot,
         );
         instruction_sender.send(ix)?;
@@ -340,7 +354,11 @@ impl StorageStage {
 
         let mut rng = ChaChaRng::from_seed(seed);
 
-        state.write().unwrap().slot = slot;
+        {
+            let mut statew = state.write().unwrap();
+            statew.slot = slot;
+            statew.storage_blockhash = storage_blockhash;
+        }
 
         // Regenerate the answers
         let num_segments = get_segment_from_slot(slot) as usize;
@@ -417,7 +435,10 @@ impl StorageStage {
                 signature,
                 sha_state,
             }) => {
-                if proof_slot < slot {
+                let current_segment = get_segment_from_slot(slot);
+                if proof_slot < slot
+                    && current_segment <= get_segment_from_slot(proof_slot) + PROOF_WINDOW_SEGMENTS
+                {
                     {
                         debug!(
                             "generating storage_keys from storage txs current_key_idx: {}",
@@ -464,11 +485,13 @@ impl StorageStage {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn process_entries(
         storage_keypair: &Arc<Keypair>,
         storage_state: &Arc<RwLock<StorageStateInner>>,
         slot_receiver: &Receiver<Vec<u64>>,
         blocktree: &Arc<Blocktree>,
+        bank_forks: &Arc<RwLock<BankForks>>,
         slot_count: &mut u64,
         last_root: &mut u64,
         current_key_idx: &mut usize,
@@ -506,17 +529,18 @@ impl StorageStage {
                         }
                     }
                     if *slot_count % storage_rotate_count == 0 {
-                        // assume the last entry in the slot is the blockhash for that slot
-                        let entry_hash = entries.last().unwrap().hash;
+                        // challenge replicators with the root's bank hash, unlike an entry hash
+                        // it can't be read out of the ledger they store
+                        let storage_blockhash = Self::get_root_bank_hash(bank_forks, slot);
                         debug!(
-                            "crosses sending at root slot: {}! with last entry's hash {}",
-                            slot_count, entry_hash
+                            "crosses sending at root slot: {}! with bank hash {}",
+                            slot_count, storage_blockhash
                         );
                         Self::process_entry_crossing(
                             &storage_keypair,
                             &storage_state,
                             &blocktree,
-                            entries.last().unwrap().hash,
+                            storage_blockhash,
                             slot,
                             instruction_sender,
                         )?;
@@ -808,6 +832,17 @@ mod tests {
         remove_dir_all(ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_get_root_bank_hash() {
+        let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(1000);
+        let bank = Arc::new(Bank::new(&genesis_block));
+        let bank_hash = bank.hash();
+        let bank_forks = Arc::new(RwLock::new(BankForks::new_from_banks(&[bank], 0)));
+        assert_eq!(StorageStage::get_root_bank_hash(&bank_forks, 0), bank_hash);
+        // pruned roots fall back to the current one
+        assert_eq!(StorageStage::get_root_bank_hash(&bank_forks, 5), bank_hash);
+    }
+
     #[test]
     fn test_signature_distribution() {
         // See that signatures have an even-ish distribution..
@@ -5,22 +5,37 @@ use crate::packet::{
     deserialize_packets_in_blob, Blob, Meta, Packets, SharedBlobs, PACKET_DATA_SIZE,
 };
 use crate::result::{Error, Result};
+use crate::socketStats::SocketStats;
 use bincode;
-use morgan_interface::timing::duration_as_ms;
+use morgan_interface::timing::{duration_as_ms, timestamp};
 use std::net::UdpSocket;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{
+    sync_channel, Receiver, RecvError, RecvTimeoutError, SendError, Sender, SyncSender,
+    TryRecvError, TrySendError,
+};
 use std::sync::Arc;
 use std::thread::{Builder, JoinHandle};
 use std::time::{Duration, Instant};
 use morgan_helper::logHelper::*;
 
-pub type PacketReceiver = Receiver<Packets>;
-pub type PacketSender = Sender<Packets>;
+/// Packet batches a socket reader queues before it starts waiting on the consumer
+pub const MAX_QUEUED_PACKET_BATCHES: usize = 1024;
+
+/// Minimum time between two reports of the depth of the same queue
+const QUEUE_DEPTH_REPORT_INTERVAL_MS: usize = 1000;
+
+pub type PacketReceiver = QueueReceiver<Packets>;
+pub type PacketSender = QueueSender<Packets>;
 pub type BlobSender = Sender<SharedBlobs>;
 pub type BlobReceiver = Receiver<SharedBlobs>;
 
-fn recv_loop(sock: &UdpSocket, exit: Arc<AtomicBool>, channel: &PacketSender) -> Result<()> {
+fn recv_loop(
+    sock: &UdpSocket,
+    exit: Arc<AtomicBool>,
+    channel: &PacketSender,
+    stats: &SocketStats,
+) -> Result<()> {
     loop {
         let mut msgs = Packets::default();
         loop {
@@ -29,14 +44,168 @@ fn recv_loop(sock: &UdpSocket, exit: Arc<AtomicBool>, channel: &PacketSender) ->
             if exit.load(Ordering::Relaxed) {
                 return Ok(());
             }
-            if let Ok(_len) = msgs.recv_from(sock) {
-                channel.send(msgs)?;
+            stats.maybe_report();
+            if let Ok(len) = msgs.recv_from(sock) {
+                stats.record_received(len);
+                send_packets(channel, msgs)?;
                 break;
             }
         }
     }
 }
 
+/// Send `msgs` on a bounded packet channel, waiting while the consumer is behind. Waiting
+/// pushes back onto the socket, whose receive buffer then drops what doesn't fit.
+pub fn send_packets(sender: &PacketSender, msgs: Packets) -> Result<()> {
+    match sender.try_send(msgs) {
+        Ok(()) => Ok(()),
+        Err(TrySendError::Full(msgs)) => {
+            inc_new_counter_info!("streamer-packet_sender_full", 1);
+            sender.send(msgs)?;
+            Ok(())
+        }
+        Err(TrySendError::Disconnected(_)) => Err(Error::SendError),
+    }
+}
+
+/// Batches sent on a bounded channel but not received yet.  `std::sync::mpsc` doesn't expose
+/// the length of a channel, so the sending and receiving ends count alongside it.
+struct QueueDepth {
+    name: &'static str,
+    depth: AtomicUsize,
+    last_report: AtomicUsize,
+}
+
+impl QueueDepth {
+    /// Submit the depth as the `queue-depth` gauge if the last report is older than
+    /// `QUEUE_DEPTH_REPORT_INTERVAL_MS`.  Both ends report, so a stalled consumer still shows
+    fn maybe_report(&self) {
+        let now = timestamp() as usize;
+        let last_report = self.last_report.load(Ordering::Relaxed);
+        if now.saturating_sub(last_report) < QUEUE_DEPTH_REPORT_INTERVAL_MS
+            || self
+                .last_report
+                .compare_and_swap(last_report, now, Ordering::Relaxed)
+                != last_report
+        {
+            return;
+        }
+        datapoint_info!(
+            "queue-depth",
+            ("name", self.name.to_string(), String),
+            ("depth", self.depth.load(Ordering::Relaxed) as i64, i64)
+        );
+    }
+}
+
+/// A bounded channel whose depth is reported under `name`, see `QueueDepth`
+pub fn queue_channel<T>(name: &'static str, bound: usize) -> (QueueSender<T>, QueueReceiver<T>) {
+    let (sender, receiver) = sync_channel(bound);
+    let depth = Arc::new(QueueDepth {
+        name,
+        depth: AtomicUsize::new(0),
+        last_report: AtomicUsize::new(timestamp() as usize),
+    });
+    (
+        QueueSender {
+            sender,
+            depth: depth.clone(),
+        },
+        QueueReceiver {
+            receiver,
+            depth: Some(depth),
+        },
+    )
+}
+
+pub struct QueueSender<T> {
+    sender: SyncSender<T>,
+    depth: Arc<QueueDepth>,
+}
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            depth: self.depth.clone(),
+        }
+    }
+}
+
+impl<T> QueueSender<T> {
+    // Counted before it's sent, so the receiver never takes off more than was added
+    fn sending(&self) {
+        self.depth.depth.fetch_add(1, Ordering::Relaxed);
+        self.depth.maybe_report();
+    }
+
+    fn not_sent(&self) {
+        self.depth.depth.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn try_send(&self, t: T) -> std::result::Result<(), TrySendError<T>> {
+        self.sending();
+        self.sender.try_send(t).map_err(|err| {
+            self.not_sent();
+            err
+        })
+    }
+
+    pub fn send(&self, t: T) -> std::result::Result<(), SendError<T>> {
+        self.sending();
+        self.sender.send(t).map_err(|err| {
+            self.not_sent();
+            err
+        })
+    }
+}
+
+pub struct QueueReceiver<T> {
+    receiver: Receiver<T>,
+    depth: Option<Arc<QueueDepth>>,
+}
+
+/// Receive from a channel that isn't counted, such as the unbounded ones
+impl<T> From<Receiver<T>> for QueueReceiver<T> {
+    fn from(receiver: Receiver<T>) -> Self {
+        Self {
+            receiver,
+            depth: None,
+        }
+    }
+}
+
+impl<T> QueueReceiver<T> {
+    fn received(&self, t: T) -> T {
+        if let Some(depth) = &self.depth {
+            depth.depth.fetch_sub(1, Ordering::Relaxed);
+            depth.maybe_report();
+        }
+        t
+    }
+
+    /// Batches waiting on the channel, 0 if it isn't counted
+    pub fn depth(&self) -> usize {
+        self.depth
+            .as_ref()
+            .map_or(0, |depth| depth.depth.load(Ordering::Relaxed))
+    }
+
+    pub fn recv(&self) -> std::result::Result<T, RecvError> {
+        self.receiver.recv().map(|t| self.received(t))
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> std::result::Result<T, RecvTimeoutError> {
+        self.receiver
+            .recv_timeout(timeout)
+            .map(|t| self.received(t))
+    }
+
+    pub fn try_recv(&self) -> std::result::Result<T, TryRecvError> {
+        self.receiver.try_recv().map(|t| self.received(t))
+    }
+}
+
 pub fn receiver(
     sock: Arc<UdpSocket>,
     exit: &Arc<AtomicBool>,
@@ -50,7 +219,8 @@ pub fn receiver(
     Builder::new()
         .name("morgan-receiver".to_string())
         .spawn(move || {
-            let _ = recv_loop(&sock, exit, &packet_sender);
+            let stats = SocketStats::register("receiver", &sock);
+            let _ = recv_loop(&sock, exit, &packet_sender, &stats);
         })
         .unwrap()
 }
@@ -108,10 +278,11 @@ pub fn responder(name: &'static str, sock: Arc<UdpSocket>, r: BlobReceiver) -> J
 
 //TODO, we would need to stick block authentication before we create the
 //window.
-fn recv_blobs(sock: &UdpSocket, s: &BlobSender) -> Result<()> {
+fn recv_blobs(sock: &UdpSocket, s: &BlobSender, stats: &SocketStats) -> Result<()> {
     trace!("recv_blobs: receiving on {}", sock.local_addr().unwrap());
     let dq = Blob::recv_from(sock)?;
     if !dq.is_empty() {
+        stats.record_received(dq.len());
         s.send(dq)?;
     }
     Ok(())
@@ -130,16 +301,17 @@ pub fn blob_receiver(
     let exit = exit.clone();
     Builder::new()
         .name("morgan-blob_receiver".to_string())
-        .spawn(move || loop {
-            if exit.load(Ordering::Relaxed) {
-                break;
+        .spawn(move || {
+            let stats = SocketStats::register("blob_receiver", &sock);
+            while !exit.load(Ordering::Relaxed) {
+                stats.maybe_report();
+                let _ = recv_blobs(&sock, &s, &stats);
             }
-            let _ = recv_blobs(&sock, &s);
         })
         .unwrap()
 }
 
-fn recv_blob_packets(sock: &UdpSocket, s: &PacketSender) -> Result<()> {
+fn recv_blob_packets(sock: &UdpSocket, s: &PacketSender, stats: &SocketStats) -> Result<()> {
     trace!(
         "recv_blob_packets: receiving on {}",
         sock.local_addr().unwrap()
@@ -149,6 +321,7 @@ fn recv_blob_packets(sock: &UdpSocket, s: &PacketSender) -> Result<()> {
     let serialized_meta_size = bincode::serialized_size(&meta)? as usize;
     let serialized_packet_size = serialized_meta_size + PACKET_DATA_SIZE;
     let blobs = Blob::recv_from(sock)?;
+    stats.record_received(blobs.len());
     for blob in blobs {
         let r_blob = blob.read().unwrap();
         let data = {
@@ -164,7 +337,7 @@ fn recv_blob_packets(sock: &UdpSocket, s: &PacketSender) -> Result<()> {
         }
 
         let packets = packets?;
-        s.send(Packets::new(packets))?;
+        send_packets(s, Packets::new(packets))?;
     }
 
     Ok(())
@@ -183,11 +356,12 @@ pub fn blob_packet_receiver(
     let exit = exit.clone();
     Builder::new()
         .name("morgan-blob_packet_receiver".to_string())
-        .spawn(move || loop {
-            if exit.load(Ordering::Relaxed) {
-                break;
+        .spawn(move || {
+            let stats = SocketStats::register("blob_packet_receiver", &sock);
+            while !exit.load(Ordering::Relaxed) {
+                stats.maybe_report();
+                let _ = recv_blob_packets(&sock, &s, &stats);
             }
-            let _ = recv_blob_packets(&sock, &s);
         })
         .unwrap()
 }
@@ -218,6 +392,57 @@ mod test {
 
         Ok(())
     }
+    #[test]
+    fn test_send_packets() {
+        let (sender, receiver) = queue_channel("test_send_packets", 1);
+        send_packets(&sender, Packets::default()).unwrap();
+        // a full channel waits for the consumer
+        let t_consumer = std::thread::spawn(move || {
+            let mut num = 0;
+            while receiver.recv().is_ok() {
+                num += 1;
+            }
+            num
+        });
+        send_packets(&sender, Packets::default()).unwrap();
+        drop(sender);
+        assert_eq!(t_consumer.join().unwrap(), 2);
+
+        let (sender, receiver) = queue_channel("test_send_packets", 1);
+        drop(receiver);
+        assert_matches!(
+            send_packets(&sender, Packets::default()),
+            Err(Error::SendError)
+        );
+    }
+
+    #[test]
+    fn test_queue_depth() {
+        let (sender, receiver) = queue_channel("test_queue_depth", 2);
+        sender.send(1).unwrap();
+        sender.try_send(2).unwrap();
+        assert_matches!(sender.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(receiver.depth(), 2);
+
+        assert_eq!(receiver.recv().unwrap(), 1);
+        assert_eq!(receiver.depth(), 1);
+        assert_eq!(receiver.try_recv().unwrap(), 2);
+        assert_eq!(receiver.depth(), 0);
+        assert_matches!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+        // only what the receiver takes off counts, not what is never sent
+        drop(receiver);
+        assert!(sender.send(4).is_err());
+        assert_eq!(sender.depth.depth.load(Ordering::Relaxed), 0);
+
+        // channels created elsewhere aren't counted
+        let (sender, receiver) = channel();
+        let receiver = QueueReceiver::from(receiver);
+        sender.send(1).unwrap();
+        assert_eq!(receiver.depth(), 0);
+        assert_eq!(receiver.recv_timeout(Duration::new(1, 0)).unwrap(), 1);
+    }
+
     #[test]
     fn streamer_debug() {
         write!(io::sink(), "{:?}", Packet::default()).unwrap();
@@ -232,7 +457,7 @@ mod test {
         let addr = read.local_addr().unwrap();
         let send = UdpSocket::bind("127.0.0.1:0").expect("bind");
         let exit = Arc::new(AtomicBool::new(false));
-        let (s_reader, r_reader) = channel();
+        let (s_reader, r_reader) = queue_channel("streamer_send_test", 1);
         let t_receiver = receiver(Arc::new(read), &exit, s_reader);
         let t_responder = {
             let (s_responder, r_responder) = channel();
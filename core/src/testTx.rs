@@ -1,10 +1,15 @@
+use crate::createKeys::GenKeys;
 use morgan_interface::hash::Hash;
 use morgan_interface::instruction::CompiledInstruction;
 use morgan_interface::signature::{Keypair, KeypairUtil};
-use morgan_interface::system_instruction::SystemInstruction;
+use morgan_interface::system_instruction::{self, SystemInstruction};
 use morgan_interface::system_program;
 use morgan_interface::system_transaction;
 use morgan_interface::transaction::Transaction;
+use morgan_vote_api::vote_instruction;
+use morgan_vote_api::vote_state::Vote;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
 
 pub fn test_tx() -> Transaction {
     let keypair1 = Keypair::new();
@@ -38,3 +43,161 @@ pub fn test_multisig_tx() -> Transaction {
         instructions,
     )
 }
+
+/// Generates transactions for tests and benches, the same ones in the same order for the
+/// same seed.  Payers are passed in so callers can fund them, every other key is generated
+pub struct TestTxGenerator {
+    keys: GenKeys,
+    rng: ChaChaRng,
+}
+
+impl TestTxGenerator {
+    pub fn new(seed: [u8; 32]) -> Self {
+        let mut rng = ChaChaRng::from_seed(seed);
+        let keys = GenKeys::new(rng.gen());
+        Self { keys, rng }
+    }
+
+    pub fn keypair(&mut self) -> Keypair {
+        self.keys.gen_keypair()
+    }
+
+    pub fn keypairs(&mut self, n: u64) -> Vec<Keypair> {
+        self.keys.gen_n_keypairs(n)
+    }
+
+    /// One transaction moving `difs` from `from` to each of `num_instructions` new accounts
+    pub fn multi_transfer_tx(
+        &mut self,
+        from: &Keypair,
+        num_instructions: usize,
+        difs: u64,
+        blockhash: Hash,
+    ) -> Transaction {
+        let to_difs: Vec<_> = (0..num_instructions)
+            .map(|_| (self.keypair().pubkey(), difs))
+            .collect();
+        let instructions = system_instruction::transfer_many(&from.pubkey(), &to_difs);
+        Transaction::new_signed_instructions(&[from], instructions, blockhash)
+    }
+
+    /// `num_txs` transfers of `difs` to new accounts, each paid by one of `payers` picked at
+    /// random.  Payers are write locked, so the fewer there are the more the transactions
+    /// contend for them
+    pub fn contended_transfer_txs(
+        &mut self,
+        payers: &[Keypair],
+        num_txs: usize,
+        difs: u64,
+        blockhash: Hash,
+    ) -> Vec<Transaction> {
+        (0..num_txs)
+            .map(|_| {
+                let from = &payers[self.rng.gen_range(0, payers.len())];
+                let to = self.keypair().pubkey();
+                system_transaction::transfer(from, &to, difs, blockhash)
+            })
+            .collect()
+    }
+
+    /// A vote by `vote_keypair`, paid for and signed by `node_keypair`, on each of `slots`
+    pub fn vote_tx(
+        &mut self,
+        node_keypair: &Keypair,
+        vote_keypair: &Keypair,
+        slots: &[u64],
+        blockhash: Hash,
+    ) -> Transaction {
+        let votes = slots
+            .iter()
+            .map(|slot| Vote::new(*slot, Hash::new(&self.rng.gen::<[u8; 32]>())))
+            .collect();
+        let vote_ix = vote_instruction::vote(
+            &node_keypair.pubkey(),
+            &vote_keypair.pubkey(),
+            &vote_keypair.pubkey(),
+            votes,
+        );
+        Transaction::new_signed_instructions(
+            &[node_keypair, vote_keypair],
+            vec![vote_ix],
+            blockhash,
+        )
+    }
+
+    /// A transfer of `difs` from `from` followed by one `from` can never afford, so the
+    /// transaction fails on its last instruction and none of it is committed
+    pub fn failing_tx(&mut self, from: &Keypair, difs: u64, blockhash: Hash) -> Transaction {
+        let to = self.keypair().pubkey();
+        let instructions = vec![
+            system_instruction::transfer(&from.pubkey(), &to, difs),
+            system_instruction::transfer(&from.pubkey(), &to, std::u64::MAX),
+        ];
+        Transaction::new_signed_instructions(&[from], instructions, blockhash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
+    use morgan_interface::transaction::TransactionError;
+    use morgan_runtime::bank::Bank;
+
+    #[test]
+    fn test_tx_generator_is_deterministic() {
+        let seed = [3u8; 32];
+        let mut gen0 = TestTxGenerator::new(seed);
+        let mut gen1 = TestTxGenerator::new(seed);
+        let payers = gen0.keypairs(2);
+        let pubkeys: Vec<_> = gen1.keypairs(2).iter().map(|k| k.pubkey()).collect();
+        assert_eq!(pubkeys, vec![payers[0].pubkey(), payers[1].pubkey()]);
+
+        let blockhash = Hash::default();
+        assert_eq!(
+            gen0.contended_transfer_txs(&payers, 10, 1, blockhash),
+            gen1.contended_transfer_txs(&payers, 10, 1, blockhash)
+        );
+        assert_eq!(
+            gen0.vote_tx(&payers[0], &payers[1], &[1, 2], blockhash),
+            gen1.vote_tx(&payers[0], &payers[1], &[1, 2], blockhash)
+        );
+        assert_ne!(
+            TestTxGenerator::new([4u8; 32]).keypair().pubkey(),
+            TestTxGenerator::new(seed).keypair().pubkey()
+        );
+    }
+
+    #[test]
+    fn test_tx_generator_txs() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let blockhash = genesis_block.hash();
+        let mut generator = TestTxGenerator::new([0u8; 32]);
+        let payer = generator.keypair();
+        bank.transfer(1_000, &mint_keypair, &payer.pubkey()).unwrap();
+
+        let tx = generator.multi_transfer_tx(&payer, 4, 10, blockhash);
+        assert_eq!(tx.message.instructions.len(), 4);
+        assert_eq!(bank.process_transaction(&tx), Ok(()));
+        assert_eq!(bank.get_balance(&payer.pubkey()), 960);
+
+        let tx = generator.failing_tx(&payer, 10, blockhash);
+        assert_matches!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::InstructionError(1, _))
+        );
+        assert_eq!(bank.get_balance(&payer.pubkey()), 960);
+
+        // a single payer locks every transaction in a batch but the first out
+        let txs = generator.contended_transfer_txs(&[payer], 3, 1, blockhash);
+        let results = bank.process_transactions(&txs);
+        assert_eq!(results[0], Ok(()));
+        assert_eq!(results[1], Err(TransactionError::AccountInUse));
+        assert_eq!(results[2], Err(TransactionError::AccountInUse));
+    }
+}
@@ -0,0 +1,226 @@
+//! The `transactionPolicy` module decides which transactions the banking stage admits.  A
+//! policy sees each transaction before it is executed; the ones it refuses are dropped and
+//! neither recorded nor retried.  By default every transaction is admitted, private clusters
+//! can configure the builtin policies instead.  Vote transactions are always admitted, so no
+//! policy can halt consensus.
+
+use bincode::deserialize;
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::system_instruction::SystemInstruction;
+use morgan_interface::system_program;
+use morgan_interface::transaction::Transaction;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+pub trait TransactionPolicy: Debug + Send + Sync {
+    /// Ok if `transaction` may be executed, otherwise why it was refused
+    fn check(&self, transaction: &Transaction) -> Result<(), String>;
+}
+
+/// Whether every instruction of `transaction` calls the vote program, such transactions are
+/// admitted whatever the policy
+pub fn is_vote_transaction(transaction: &Transaction) -> bool {
+    let message = transaction.message();
+    !message.instructions.is_empty()
+        && message.instructions.iter().all(|instruction| {
+            message
+                .account_keys
+                .get(instruction.program_ids_index as usize)
+                == Some(&morgan_vote_api::id())
+        })
+}
+
+/// Admits every transaction
+#[derive(Debug, Default)]
+pub struct AllowAll;
+
+impl TransactionPolicy for AllowAll {
+    fn check(&self, _transaction: &Transaction) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Admits transactions whose instructions only call the listed programs
+#[derive(Debug)]
+pub struct ProgramAllowlist {
+    program_ids: Vec<Pubkey>,
+}
+
+impl ProgramAllowlist {
+    pub fn new(program_ids: Vec<Pubkey>) -> Self {
+        Self { program_ids }
+    }
+}
+
+impl TransactionPolicy for ProgramAllowlist {
+    fn check(&self, transaction: &Transaction) -> Result<(), String> {
+        let message = transaction.message();
+        for instruction in &message.instructions {
+            match message
+                .account_keys
+                .get(instruction.program_ids_index as usize)
+            {
+                Some(program_id) if self.program_ids.contains(program_id) => (),
+                Some(program_id) => return Err(format!("program {} is not allowed", program_id)),
+                None => return Err("program id is not a static account key".to_string()),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Refuses transactions that move more than `max_difs` in any one system transfer, nonce
+/// withdrawal or account creation
+#[derive(Debug)]
+pub struct MaxTransfer {
+    max_difs: u64,
+}
+
+impl MaxTransfer {
+    pub fn new(max_difs: u64) -> Self {
+        Self { max_difs }
+    }
+}
+
+impl TransactionPolicy for MaxTransfer {
+    fn check(&self, transaction: &Transaction) -> Result<(), String> {
+        let message = transaction.message();
+        for instruction in &message.instructions {
+            let program_id = message
+                .account_keys
+                .get(instruction.program_ids_index as usize);
+            if program_id != Some(&system_program::id()) {
+                continue;
+            }
+            let difs = match deserialize(&instruction.data) {
+                Ok(SystemInstruction::Transfer { difs })
+                | Ok(SystemInstruction::WithdrawNonceAccount { difs })
+                | Ok(SystemInstruction::CreateAccount { difs, .. }) => difs,
+                _ => continue,
+            };
+            if difs > self.max_difs {
+                return Err(format!(
+                    "transfer of {} difs is over the limit of {}",
+                    difs, self.max_difs
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Admits the transactions every one of its policies admits
+#[derive(Debug, Default)]
+pub struct PolicySet {
+    policies: Vec<Box<dyn TransactionPolicy>>,
+}
+
+impl PolicySet {
+    pub fn push(&mut self, policy: Box<dyn TransactionPolicy>) {
+        self.policies.push(policy);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.policies.is_empty()
+    }
+}
+
+impl TransactionPolicy for PolicySet {
+    fn check(&self, transaction: &Transaction) -> Result<(), String> {
+        self.policies
+            .iter()
+            .map(|policy| policy.check(transaction))
+            .find(Result::is_err)
+            .unwrap_or(Ok(()))
+    }
+}
+
+/// Which builtin policies a node enforces, with none set every transaction is admitted
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TransactionPolicyConfig {
+    pub allowed_programs: Option<Vec<Pubkey>>, // Only transactions calling these programs
+    pub max_transfer_difs: Option<u64>,        // No system transfer larger than this
+}
+
+impl TransactionPolicyConfig {
+    pub fn build(&self) -> Arc<dyn TransactionPolicy> {
+        let mut policies = PolicySet::default();
+        if let Some(allowed_programs) = &self.allowed_programs {
+            policies.push(Box::new(ProgramAllowlist::new(allowed_programs.clone())));
+        }
+        if let Some(max_transfer_difs) = self.max_transfer_difs {
+            policies.push(Box::new(MaxTransfer::new(max_transfer_difs)));
+        }
+        if policies.is_empty() {
+            Arc::new(AllowAll)
+        } else {
+            Arc::new(policies)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morgan_interface::hash::Hash;
+    use morgan_interface::signature::{Keypair, KeypairUtil};
+    use morgan_interface::system_instruction;
+    use morgan_interface::system_transaction;
+    use morgan_vote_api::vote_instruction;
+
+    #[test]
+    fn test_builtin_policies() {
+        let keypair = Keypair::new();
+        let to = Pubkey::new_rand();
+        let small = system_transaction::create_user_account(&keypair, &to, 10, Hash::default());
+        let large = system_transaction::transfer(&keypair, &to, 1_000, Hash::default());
+
+        let policy = TransactionPolicyConfig::default().build();
+        assert_eq!(policy.check(&small), Ok(()));
+        assert_eq!(policy.check(&large), Ok(()));
+
+        let policy = TransactionPolicyConfig {
+            max_transfer_difs: Some(100),
+            ..TransactionPolicyConfig::default()
+        }
+        .build();
+        assert_eq!(policy.check(&small), Ok(()));
+        assert!(policy.check(&large).is_err());
+
+        let policy = TransactionPolicyConfig {
+            allowed_programs: Some(vec![Pubkey::new_rand()]),
+            max_transfer_difs: Some(100),
+        }
+        .build();
+        assert!(policy.check(&small).is_err());
+
+        let policy = TransactionPolicyConfig {
+            allowed_programs: Some(vec![system_program::id()]),
+            max_transfer_difs: None,
+        }
+        .build();
+        assert_eq!(policy.check(&small), Ok(()));
+        assert_eq!(policy.check(&large), Ok(()));
+    }
+
+    #[test]
+    fn test_is_vote_transaction() {
+        let keypair = Keypair::new();
+        let pubkey = keypair.pubkey();
+        let vote_ix = vote_instruction::vote(&pubkey, &pubkey, &pubkey, vec![]);
+        let vote_tx = Transaction::new_signed_instructions(
+            &[&keypair],
+            vec![vote_ix.clone()],
+            Hash::default(),
+        );
+        assert!(is_vote_transaction(&vote_tx));
+
+        let transfer_ix = system_instruction::transfer(&pubkey, &Pubkey::new_rand(), 1);
+        let mixed_tx = Transaction::new_signed_instructions(
+            &[&keypair],
+            vec![vote_ix, transfer_ix],
+            Hash::default(),
+        );
+        assert!(!is_vote_transaction(&mixed_tx));
+    }
+}
@@ -5,20 +5,26 @@ use crate::treasuryStage::BankingStage;
 use crate::blockBufferPool::Blocktree;
 use crate::propagateStage::BroadcastStage;
 use crate::clusterMessage::ClusterInfo;
-use crate::ClusterVoteMessageListener::ClusterInfoVoteListener;
+use crate::directStream::DirectStream;
+use crate::ClusterVoteMessageListener::{ClusterInfoVoteListener, VoteTracker};
 use crate::fetchStage::FetchStage;
 use crate::waterClockRecorder::{PohRecorder, WorkingBankEntries};
+use crate::rpcSubscriptions::RpcSubscriptions;
 use crate::service::Service;
-use crate::signatureVerifyStage::SigVerifyStage;
+use crate::signatureVerifyStage::{SigVerifyStage, MAX_QUEUED_VERIFIED_BATCHES};
+use crate::streamer::{queue_channel, MAX_QUEUED_PACKET_BATCHES};
+use crate::transactionPolicy::TransactionPolicy;
+use crate::transactionTrace::TransactionTraces;
 use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 use std::net::UdpSocket;
-use std::sync::atomic::AtomicBool;
-use std::sync::mpsc::{channel, Receiver};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 
 pub struct Tpu {
+    fetch_exit: Arc<AtomicBool>, // Stops the fetch stage ahead of the rest of the pipeline
     fetch_stage: FetchStage,
     sigverify_stage: SigVerifyStage,
     banking_stage: BankingStage,
@@ -36,52 +42,65 @@ impl Tpu {
         transactions_sockets: Vec<UdpSocket>,
         tpu_via_blobs_sockets: Vec<UdpSocket>,
         broadcast_socket: UdpSocket,
+        direct_stream: Option<Arc<DirectStream>>,
         sigverify_disabled: bool,
         blocktree: &Arc<Blocktree>,
         exit: &Arc<AtomicBool>,
         genesis_blockhash: &Hash,
+        vote_tracker: &Arc<RwLock<VoteTracker>>,
+        transaction_traces: &Arc<TransactionTraces>,
+        transaction_policy: &Arc<dyn TransactionPolicy>,
+        subscriptions: &Arc<RpcSubscriptions>,
     ) -> Self {
         cluster_info.write().unwrap().set_leader(id);
 
-        let (packet_sender, packet_receiver) = channel();
+        let (packet_sender, packet_receiver) =
+            queue_channel("fetch_stage-packets", MAX_QUEUED_PACKET_BATCHES);
+        let fetch_exit = Arc::new(AtomicBool::new(false));
         let fetch_stage = FetchStage::new_with_sender(
             transactions_sockets,
             tpu_via_blobs_sockets,
-            &exit,
+            &fetch_exit,
             &packet_sender,
             &poh_recorder,
         );
-        let (verified_sender, verified_receiver) = channel();
+        let (verified_sender, verified_receiver) = queue_channel(
+            "sigverify_stage-verified_packets",
+            MAX_QUEUED_VERIFIED_BATCHES,
+        );
 
         let sigverify_stage =
             SigVerifyStage::new(packet_receiver, sigverify_disabled, verified_sender.clone());
 
-        let (verified_vote_sender, verified_vote_receiver) = channel();
         let cluster_info_vote_listener = ClusterInfoVoteListener::new(
             &exit,
             cluster_info.clone(),
             sigverify_disabled,
-            verified_vote_sender,
             &poh_recorder,
+            vote_tracker,
+            subscriptions,
         );
 
         let banking_stage = BankingStage::new(
             &cluster_info,
             poh_recorder,
             verified_receiver,
-            verified_vote_receiver,
+            transaction_traces,
+            transaction_policy,
         );
 
         let broadcast_stage = BroadcastStage::new(
             broadcast_socket,
             cluster_info.clone(),
             entry_receiver,
+            direct_stream,
             &exit,
             blocktree,
             genesis_blockhash,
         );
 
         Self {
+            fetch_exit,
             fetch_stage,
             sigverify_stage,
             banking_stage,
@@ -89,12 +108,19 @@ impl Tpu {
             broadcast_stage,
         }
     }
+
+    /// Stop taking in transactions, the stages behind the fetch stage keep working through
+    /// what was already received
+    pub fn close_ingress(&self) {
+        self.fetch_exit.store(true, Ordering::Relaxed);
+    }
 }
 
 impl Service for Tpu {
     type JoinReturnType = ();
 
     fn join(self) -> thread::Result<()> {
+        self.close_ingress();
         let mut results = vec![];
         results.push(self.fetch_stage.join());
         results.push(self.sigverify_stage.join());
@@ -0,0 +1,139 @@
+//! The `transactionTrace` module remembers the opaque trace ids clients attach to transactions
+//! submitted over RPC. The id never goes on-chain; the stages a traced transaction passes
+//! through look it up by signature to report how long it took to reach them.
+
+use hashbrown::HashMap;
+use morgan_interface::signature::Signature;
+use morgan_interface::timing::duration_as_us;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use morgan_helper::logHelper::*;
+
+/// Traces held at once, submissions past this are not traced until old ones expire
+pub const MAX_TRANSACTION_TRACES: usize = 100_000;
+
+/// Traces are forgotten this long after submission, whether or not they were streamed
+pub const MAX_TRACE_AGE: Duration = Duration::from_secs(120);
+
+/// Trace ids longer than this are truncated
+pub const MAX_TRACE_ID_LEN: usize = 64;
+
+#[derive(Debug, Clone)]
+struct Trace {
+    trace_id: String,
+    submitted: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct TransactionTraces {
+    traces: RwLock<HashMap<Signature, Trace>>,
+}
+
+impl TransactionTraces {
+    /// Start tracing `signature` under `trace_id`, returns false if the table is full
+    pub fn insert(&self, signature: Signature, mut trace_id: String) -> bool {
+        if trace_id.len() > MAX_TRACE_ID_LEN {
+            let end = (0..=MAX_TRACE_ID_LEN)
+                .rev()
+                .find(|i| trace_id.is_char_boundary(*i))
+                .unwrap();
+            trace_id.truncate(end);
+        }
+        let mut traces = self.traces.write().unwrap();
+        if traces.len() >= MAX_TRANSACTION_TRACES {
+            traces.retain(|_, trace| trace.submitted.elapsed() < MAX_TRACE_AGE);
+            if traces.len() >= MAX_TRANSACTION_TRACES {
+                inc_new_counter_warn!("transaction_trace-table_full", 1);
+                return false;
+            }
+        }
+        traces.insert(
+            signature,
+            Trace {
+                trace_id,
+                submitted: Instant::now(),
+            },
+        );
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traces.read().unwrap().is_empty()
+    }
+
+    pub fn trace_id(&self, signature: &Signature) -> Option<String> {
+        self.traces
+            .read()
+            .unwrap()
+            .get(signature)
+            .map(|trace| trace.trace_id.clone())
+    }
+
+    /// Report that `signature` reached `stage`, returns its trace id if it is traced
+    pub fn record(&self, signature: &Signature, stage: &str) -> Option<String> {
+        let trace = self.traces.read().unwrap().get(signature).cloned()?;
+        Self::submit(signature, &trace, stage);
+        Some(trace.trace_id)
+    }
+
+    /// Report that `signature` was dropped at `stage` and stop tracing it
+    pub fn drop_transaction(&self, signature: &Signature, stage: &str, reason: &str) {
+        if let Some(trace) = self.traces.write().unwrap().remove(signature) {
+            Self::submit(signature, &trace, stage);
+            println!(
+                "{}",
+                Warn(
+                    format!(
+                        "traced transaction {} ({}) dropped in {}: {}",
+                        signature, trace.trace_id, stage, reason
+                    )
+                    .to_string(),
+                    module_path!().to_string()
+                )
+            );
+        }
+    }
+
+    /// Report that `signature` reached `stage`, its last one, and stop tracing it
+    pub fn finish(&self, signature: &Signature, stage: &str) -> Option<String> {
+        let trace = self.traces.write().unwrap().remove(signature)?;
+        Self::submit(signature, &trace, stage);
+        Some(trace.trace_id)
+    }
+
+    fn submit(signature: &Signature, trace: &Trace, stage: &str) {
+        let elapsed_us = duration_as_us(&trace.submitted.elapsed());
+        datapoint_info!(
+            "transaction-trace",
+            ("trace_id", trace.trace_id.clone(), String),
+            ("signature", signature.to_string(), String),
+            ("stage", stage.to_string(), String),
+            ("elapsed_us", elapsed_us, i64)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_traces() {
+        let traces = TransactionTraces::default();
+        let signature = Signature::new(&[1u8; 64]);
+        assert!(traces.is_empty());
+        assert_eq!(traces.record(&signature, "banking"), None);
+
+        assert!(traces.insert(signature, "a".repeat(MAX_TRACE_ID_LEN + 1)));
+        assert!(!traces.is_empty());
+        let trace_id = "a".repeat(MAX_TRACE_ID_LEN);
+        assert_eq!(traces.trace_id(&signature), Some(trace_id.clone()));
+        assert_eq!(traces.record(&signature, "banking"), Some(trace_id.clone()));
+        assert_eq!(traces.finish(&signature, "blockstream"), Some(trace_id));
+        assert!(traces.is_empty());
+
+        assert!(traces.insert(signature, "b".to_string()));
+        traces.drop_transaction(&signature, "banking", "buffer full");
+        assert_eq!(traces.trace_id(&signature), None);
+    }
+}
@@ -2,40 +2,49 @@
 //! multi-stage transaction validation pipeline in software.
 //!
 //! 1. BlobFetchStage
-//! - Incoming blobs are picked up from the TVU sockets and repair socket.
+//! - Incoming blobs are picked up from the TVU sockets and repair socket, and the blobs the
+//! leader streams straight to this node from the direct stream socket.
 //! 2. RetransmitStage
 //! - Blobs are windowed until a contiguous chunk is available.  This stage also repairs and
 //! retransmits blobs that are in the queue.
 //! 3. ReplayStage
 //! - Transactions in blobs are processed and applied to the bank.
-//! - TODO We need to verify the signatures in the blobs.
+//! - Transaction signatures are verified unless gossip votes show the cluster already confirmed
+//! the slot.
 //! 4. StorageStage
 //! - Generating the keys used to encrypt the ledger and sample it for storage mining.
 
 // use crate::bank_forks::BankForks;
 use crate::treasuryForks::BankForks;
 use crate::fetchSpotStage::BlobFetchStage;
+use crate::blockStream::BlockstreamConfig;
 use crate::blockStreamService::BlockstreamService;
 use crate::blockBufferPool::{Blocktree, CompletedSlotsReceiver};
 use crate::clusterMessage::ClusterInfo;
+use crate::directStream::{DirectStream, DirectStreamService};
+use crate::ClusterVoteMessageListener::VoteTracker;
 use crate::leaderArrangeCache::LeaderScheduleCache;
 use crate::waterClockRecorder::PohRecorder;
 use crate::repeatStage::ReplayStage;
+use crate::replayErrors::ReplayErrors;
 use crate::retransmitStage::RetransmitStage;
 use crate::rpcSubscriptions::RpcSubscriptions;
 use crate::service::Service;
 use crate::storageStage::{StorageStage, StorageState};
+use crate::transactionTrace::TransactionTraces;
 use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil};
 use std::net::UdpSocket;
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver};
 use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 
 pub struct Tvu {
+    fetch_exit: Arc<AtomicBool>, // Stops the fetch stage ahead of the rest of the pipeline
     fetch_stage: BlobFetchStage,
+    direct_stream_service: Option<DirectStreamService>,
     retransmit_stage: RetransmitStage,
     replay_stage: ReplayStage,
     blockstream_service: Option<BlockstreamService>,
@@ -46,6 +55,7 @@ pub struct Sockets {
     pub fetch: Vec<UdpSocket>,
     pub repair: UdpSocket,
     pub retransmit: UdpSocket,
+    pub direct_stream: Option<Arc<DirectStream>>,
 }
 
 impl Tvu {
@@ -53,7 +63,7 @@ impl Tvu {
     /// on the bank state.
     /// # Arguments
     /// * `cluster_info` - The cluster_info state.
-    /// * `sockets` - fetch, repair, and retransmit sockets
+    /// * `sockets` - fetch, repair, retransmit and direct stream sockets
     /// * `blocktree` - the ledger itself
     #[allow(clippy::new_ret_no_self, clippy::too_many_arguments)]
     pub fn new<T>(
@@ -66,7 +76,7 @@ impl Tvu {
         blocktree: Arc<Blocktree>,
         storage_rotate_count: u64,
         storage_state: &StorageState,
-        blockstream: Option<&String>,
+        blockstream: Option<&BlockstreamConfig>,
         ledger_signal_receiver: Receiver<bool>,
         subscriptions: &Arc<RpcSubscriptions>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
@@ -74,6 +84,10 @@ impl Tvu {
         exit: &Arc<AtomicBool>,
         genesis_blockhash: &Hash,
         completed_slots_receiver: CompletedSlotsReceiver,
+        vote_tracker: &Arc<RwLock<VoteTracker>>,
+        transaction_traces: &Arc<TransactionTraces>,
+        replay_errors: &Arc<ReplayErrors>,
+        leader_warmup_ticks: u64,
     ) -> Self
     where
         T: 'static + KeypairUtil + Sync + Send,
@@ -88,6 +102,7 @@ impl Tvu {
             repair: repair_socket,
             fetch: fetch_sockets,
             retransmit: retransmit_socket,
+            direct_stream,
         } = sockets;
 
         let (blob_fetch_sender, blob_fetch_receiver) = channel();
@@ -96,11 +111,15 @@ impl Tvu {
         let mut blob_sockets: Vec<Arc<UdpSocket>> =
             fetch_sockets.into_iter().map(Arc::new).collect();
         blob_sockets.push(repair_socket.clone());
-        let fetch_stage = BlobFetchStage::new_multi_socket(blob_sockets, &blob_fetch_sender, &exit);
+        let fetch_exit = Arc::new(AtomicBool::new(false));
+        let fetch_stage =
+            BlobFetchStage::new_multi_socket(blob_sockets, &blob_fetch_sender, &fetch_exit);
+        let direct_stream_service = direct_stream.map(|direct_stream| {
+            DirectStreamService::new(&direct_stream, blob_fetch_sender.clone(), &fetch_exit)
+        });
 
-        //TODO
-        //the packets coming out of blob_receiver need to be sent to the GPU and verified
-        //then sent to the window, which does the erasure coding reconstruction
+        // the window service verifies the leader signatures of the fetched blobs in batches
+        // on the sigverify path before storing and retransmitting them
         let retransmit_stage = RetransmitStage::new(
             bank_forks.clone(),
             leader_schedule_cache,
@@ -113,6 +132,7 @@ impl Tvu {
             genesis_blockhash,
             completed_slots_receiver,
             *bank_forks.read().unwrap().working_bank().epoch_schedule(),
+            vote_tracker,
         );
 
         let (replay_stage, slot_full_receiver, root_slot_receiver) = ReplayStage::new(
@@ -127,13 +147,17 @@ impl Tvu {
             subscriptions,
             poh_recorder,
             leader_schedule_cache,
+            vote_tracker,
+            replay_errors,
+            leader_warmup_ticks,
         );
 
         let blockstream_service = if blockstream.is_some() {
             let blockstream_service = BlockstreamService::new(
                 slot_full_receiver,
                 blocktree.clone(),
-                blockstream.unwrap().to_string(),
+                blockstream.unwrap().clone(),
+                transaction_traces,
                 &exit,
             );
             Some(blockstream_service)
@@ -154,21 +178,32 @@ impl Tvu {
         );
 
         Tvu {
+            fetch_exit,
             fetch_stage,
+            direct_stream_service,
             retransmit_stage,
             replay_stage,
             blockstream_service,
             storage_stage,
         }
     }
+
+    /// Stop taking in blobs, from the leader as well as repairs
+    pub fn close_ingress(&self) {
+        self.fetch_exit.store(true, Ordering::Relaxed);
+    }
 }
 
 impl Service for Tvu {
     type JoinReturnType = ();
 
     fn join(self) -> thread::Result<()> {
+        self.close_ingress();
         self.retransmit_stage.join()?;
         self.fetch_stage.join()?;
+        if let Some(direct_stream_service) = self.direct_stream_service {
+            direct_stream_service.join()?;
+        }
         self.storage_stage.join()?;
         if self.blockstream_service.is_some() {
             self.blockstream_service.unwrap().join()?;
@@ -185,6 +220,7 @@ pub mod tests {
     use crate::blockBufferPool::get_tmp_ledger_path;
     use crate::clusterMessage::{ClusterInfo, Node};
     use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
+    use crate::repeatStage::DEFAULT_LEADER_WARMUP_TICKS;
     use crate::storageStage::STORAGE_ROTATE_TEST_COUNT;
     use morgan_runtime::bank::Bank;
     use std::sync::atomic::Ordering;
@@ -228,6 +264,7 @@ pub mod tests {
                     repair: target1.sockets.repair,
                     retransmit: target1.sockets.retransmit,
                     fetch: target1.sockets.tvu,
+                    direct_stream: None,
                 }
             },
             blocktree,
@@ -241,6 +278,10 @@ pub mod tests {
             &exit,
             &Hash::default(),
             completed_slots_receiver,
+            &Arc::new(RwLock::new(VoteTracker::default())),
+            &Arc::new(TransactionTraces::default()),
+            &Arc::new(ReplayErrors::default()),
+            DEFAULT_LEADER_WARMUP_TICKS,
         );
         exit.store(true, Ordering::Relaxed);
         tvu.join().unwrap();
@@ -1,17 +1,36 @@
 //! The `bank_forks` module implments BankForks a DAG of checkpointed Banks
 
 use hashbrown::{HashMap, HashSet};
-use morgan_metricbot::inc_new_counter_info;
+use morgan_metricbot::{datapoint_info, inc_new_counter_info};
+use morgan_runtime::accounts_db::PinnedAccounts;
 use morgan_runtime::bank::Bank;
+use morgan_runtime::snapshot::BankSnapshot;
 use morgan_interface::timing;
 use std::ops::Index;
 use std::sync::Arc;
 use std::time::Instant;
 
+/// The accounts of the first bank rooted in an epoch, pinned while it was rooted
+pub struct EpochAccounts {
+    pub epoch: u64,
+    pub slot: u64,
+    pub accounts: PinnedAccounts,
+}
+
 pub struct BankForks {
     banks: HashMap<u64, Arc<Bank>>,
     working_bank: Arc<Bank>,
     root: u64,
+    /// set to take the accounts of the first bank rooted in each epoch
+    capture_epoch_accounts: bool,
+    epoch_accounts: Option<EpochAccounts>,
+    /// rooted slots between two snapshots, set to take snapshots
+    snapshot_interval_slots: Option<u64>,
+    last_snapshot_slot: Option<u64>,
+    /// rooted slots between two incremental snapshots, set to take them between full ones
+    incremental_snapshot_interval_slots: Option<u64>,
+    last_incremental_snapshot_slot: Option<u64>,
+    snapshot: Option<BankSnapshot>,
 }
 
 impl Index<u64> for BankForks {
@@ -30,6 +49,13 @@ impl BankForks {
             banks,
             working_bank,
             root: 0,
+            capture_epoch_accounts: false,
+            epoch_accounts: None,
+            snapshot_interval_slots: None,
+            last_snapshot_slot: None,
+            incremental_snapshot_interval_slots: None,
+            last_incremental_snapshot_slot: None,
+            snapshot: None,
         }
     }
 
@@ -91,6 +117,13 @@ impl BankForks {
             root,
             banks,
             working_bank,
+            capture_epoch_accounts: false,
+            epoch_accounts: None,
+            snapshot_interval_slots: None,
+            last_snapshot_slot: None,
+            incremental_snapshot_interval_slots: None,
+            last_incremental_snapshot_slot: None,
+            snapshot: None,
         }
     }
 
@@ -108,20 +141,43 @@ impl BankForks {
     }
 
     pub fn set_root(&mut self, root: u64) {
+        let prev_root = self.root;
         self.root = root;
         let set_root_start = Instant::now();
         let root_bank = self
             .banks
             .get(&root)
             .expect("root bank didn't exist in bank_forks");
+        if self.capture_epoch_accounts {
+            if let Some(epoch_accounts) = Self::new_epoch_accounts(root_bank, prev_root) {
+                self.epoch_accounts = Some(epoch_accounts);
+            }
+        }
         let root_tx_count = root_bank
             .parents()
             .last()
             .map(|bank| bank.transaction_count())
             .unwrap_or(0);
+        let starts_epoch = Self::starts_epoch(root_bank);
         root_bank.squash();
+        if let Some(snapshot_interval_slots) = self.snapshot_interval_slots {
+            // The first root of an epoch is also snapshotted, the cluster gossips the hash of
+            // its accounts, so a snapshot of it can be checked against them
+            let is_due = starts_epoch
+                || self.last_snapshot_slot.map_or(true, |last_snapshot_slot| {
+                    root >= last_snapshot_slot + snapshot_interval_slots
+                });
+            if is_due {
+                self.snapshot = Some(BankSnapshot::new(root_bank));
+                self.last_snapshot_slot = Some(root);
+                self.last_incremental_snapshot_slot = None;
+            } else if let Some(snapshot) = self.new_incremental_snapshot(root_bank) {
+                self.snapshot = Some(snapshot);
+                self.last_incremental_snapshot_slot = Some(root);
+            }
+        }
         let new_tx_count = root_bank.transaction_count();
-        self.prune_non_root(root);
+        self.prune_dead_forks();
 
         inc_new_counter_info!(
             "bank-forks_set_root_ms",
@@ -137,10 +193,132 @@ impl BankForks {
         self.root
     }
 
-    fn prune_non_root(&mut self, root: u64) {
-        let descendants = self.descendants();
-        self.banks
-            .retain(|slot, _| descendants[&root].contains(slot))
+    /// Take the accounts of the first bank rooted in each epoch from now on, for
+    /// `take_epoch_accounts`
+    pub fn capture_epoch_accounts(&mut self) {
+        self.capture_epoch_accounts = true;
+    }
+
+    /// The accounts of the first bank rooted in the newest epoch, if they weren't taken yet
+    pub fn take_epoch_accounts(&mut self) -> Option<EpochAccounts> {
+        self.epoch_accounts.take()
+    }
+
+    /// Take a snapshot of the root every `snapshot_interval_slots` rooted slots after
+    /// `last_snapshot_slot` from now on, for `take_snapshot`
+    pub fn capture_snapshots(
+        &mut self,
+        snapshot_interval_slots: u64,
+        last_snapshot_slot: Option<u64>,
+    ) {
+        assert!(snapshot_interval_slots > 0);
+        self.snapshot_interval_slots = Some(snapshot_interval_slots);
+        self.last_snapshot_slot = last_snapshot_slot;
+    }
+
+    /// Also take an incremental snapshot of the root every
+    /// `incremental_snapshot_interval_slots` rooted slots after `last_incremental_snapshot_slot`,
+    /// or after the last full snapshot, between the full ones
+    pub fn capture_incremental_snapshots(
+        &mut self,
+        incremental_snapshot_interval_slots: u64,
+        last_incremental_snapshot_slot: Option<u64>,
+    ) {
+        assert!(incremental_snapshot_interval_slots > 0);
+        self.incremental_snapshot_interval_slots = Some(incremental_snapshot_interval_slots);
+        self.last_incremental_snapshot_slot = last_incremental_snapshot_slot;
+    }
+
+    /// An incremental snapshot of `root_bank` against the last full snapshot, if one is due.
+    /// A full snapshot that wasn't taken yet is never replaced, the increments are against it
+    fn new_incremental_snapshot(&self, root_bank: &Bank) -> Option<BankSnapshot> {
+        let interval_slots = self.incremental_snapshot_interval_slots?;
+        let last_snapshot_slot = self.last_snapshot_slot?;
+        let is_full_pending = self
+            .snapshot
+            .as_ref()
+            .map_or(false, |snapshot| snapshot.base_slot().is_none());
+        let last_slot = self
+            .last_incremental_snapshot_slot
+            .unwrap_or(last_snapshot_slot)
+            .max(last_snapshot_slot);
+        if is_full_pending || root_bank.slot() < last_slot + interval_slots {
+            return None;
+        }
+        Some(BankSnapshot::new_incremental(root_bank, last_snapshot_slot))
+    }
+
+    /// The newest snapshot of the root, if it wasn't taken yet
+    pub fn take_snapshot(&mut self) -> Option<BankSnapshot> {
+        self.snapshot.take()
+    }
+
+    /// Whether `bank` is the first bank of its epoch on its fork
+    fn starts_epoch(bank: &Bank) -> bool {
+        let epoch_of = |slot| bank.get_epoch_and_slot_index(slot).0;
+        bank.parent().map_or(false, |parent| {
+            epoch_of(parent.slot()) < epoch_of(bank.slot())
+        })
+    }
+
+    /// The accounts of the first bank of a new epoch that rooting `root_bank` over
+    /// `prev_root` roots.  They're taken before any later bank is rooted, which would show
+    /// through, so every node gets the same accounts for the same slot
+    fn new_epoch_accounts(root_bank: &Bank, prev_root: u64) -> Option<EpochAccounts> {
+        let epoch_of = |slot| root_bank.get_epoch_and_slot_index(slot).0;
+        let prev_epoch = epoch_of(prev_root);
+        let parents = root_bank.parents();
+        let bank = parents
+            .iter()
+            .rev()
+            .map(|bank| bank.as_ref())
+            .chain(Some(root_bank))
+            .filter(|bank| bank.slot() > prev_root)
+            .find(|bank| epoch_of(bank.slot()) > prev_epoch)?;
+        Some(EpochAccounts {
+            epoch: epoch_of(bank.slot()),
+            slot: bank.slot(),
+            accounts: bank.snapshot_accounts(),
+        })
+    }
+
+    /// The bank at the root, which `set_root` prunes once it has descendants, they still hold
+    /// it as a parent
+    pub fn root_bank(&self) -> Option<Arc<Bank>> {
+        if let Some(bank) = self.banks.get(&self.root) {
+            return Some(bank.clone());
+        }
+        self.banks.values().find_map(|bank| {
+            bank.parents()
+                .into_iter()
+                .find(|parent| parent.slot() == self.root)
+        })
+    }
+
+    /// Drop every bank that doesn't descend from the root and purge the accounts of the forks
+    /// that lost out to it, without waiting on whoever still holds their banks.  The root and
+    /// its ancestors go too, their accounts stay.  Returns the slots of the purged forks
+    pub fn prune_dead_forks(&mut self) -> Vec<u64> {
+        let root = self.root;
+        let descendants = self.descendants().remove(&root).unwrap_or_default();
+        let mut dead_forks = vec![];
+        self.banks.retain(|slot, bank| {
+            if descendants.contains(slot) {
+                return true;
+            }
+            // a noop for the ancestors of the root, they're already roots of the accounts
+            if *slot != root {
+                bank.purge_fork();
+                dead_forks.push(*slot);
+            }
+            false
+        });
+        datapoint_info!(
+            "bank-forks_prune",
+            ("live_forks", self.banks.len(), i64),
+            ("dead_forks", dead_forks.len(), i64)
+        );
+        dead_forks
     }
 }
 
@@ -150,6 +328,7 @@ mod tests {
     use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
     use morgan_interface::hash::Hash;
     use morgan_interface::pubkey::Pubkey;
+    use morgan_runtime::epoch_schedule::MINIMUM_SLOT_LENGTH;
 
     #[test]
     fn test_bank_forks() {
@@ -219,4 +398,114 @@ mod tests {
         assert_eq!(bank_forks.active_banks(), vec![1]);
     }
 
+    #[test]
+    fn test_bank_forks_root_bank() {
+        let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let mut bank_forks = BankForks::new(0, bank);
+        assert_eq!(bank_forks.root_bank().unwrap().slot(), 0);
+        let bank1 = Bank::new_from_parent(&bank_forks[0], &Pubkey::default(), 1);
+        bank_forks.insert(bank1);
+        let bank2 = Bank::new_from_parent(&bank_forks[1], &Pubkey::default(), 2);
+        bank_forks.insert(bank2);
+        bank_forks.set_root(1);
+        assert!(bank_forks.get(1).is_none());
+        assert_eq!(bank_forks.root_bank().unwrap().slot(), 1);
+    }
+
+    #[test]
+    fn test_bank_forks_prune_dead_forks() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let mut bank_forks = BankForks::new(0, bank);
+        let bank1 = Bank::new_from_parent(&bank_forks[0], &Pubkey::default(), 1);
+        bank_forks.insert(bank1);
+        let bank2 = Bank::new_from_parent(&bank_forks[1], &Pubkey::default(), 2);
+        bank_forks.insert(bank2);
+
+        // slot 3 forks off 0 and loses out to 1
+        let key = Pubkey::new_rand();
+        let bank3 = Bank::new_from_parent(&bank_forks[0], &Pubkey::default(), 3);
+        bank3.transfer(1, &mint_keypair, &key).unwrap();
+        bank_forks.insert(bank3);
+        let bank3 = bank_forks[3].clone();
+        assert_eq!(bank3.get_balance(&key), 1);
+
+        bank_forks.set_root(1);
+        assert_eq!(bank_forks.active_banks(), vec![2]);
+        assert!(bank_forks.get(3).is_none());
+        assert_eq!(bank_forks.root_bank().unwrap().slot(), 1);
+
+        // the dead fork's accounts are gone even though its bank is still held
+        assert_eq!(bank3.get_balance(&key), 0);
+        assert!(bank_forks.prune_dead_forks().is_empty());
+    }
+
+    #[test]
+    fn test_bank_forks_epoch_accounts() {
+        let GenesisBlockInfo {
+            mut genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        genesis_block.slots_per_epoch = MINIMUM_SLOT_LENGTH as u64;
+        genesis_block.epoch_warmup = false;
+        let slots_per_epoch = genesis_block.slots_per_epoch;
+        let mut bank_forks = BankForks::new(0, Bank::new(&genesis_block));
+        bank_forks.capture_epoch_accounts();
+
+        // root the last slot of epoch 0, then jump the root over the first slot of epoch 1
+        let key = Pubkey::new_rand();
+        for slot in 1..slots_per_epoch + 3 {
+            let bank = Bank::new_from_parent(&bank_forks[slot - 1], &Pubkey::default(), slot);
+            bank.transfer(slot, &mint_keypair, &key).unwrap();
+            bank_forks.insert(bank);
+        }
+        bank_forks.set_root(slots_per_epoch - 1);
+        assert!(bank_forks.take_epoch_accounts().is_none());
+        bank_forks.set_root(slots_per_epoch + 1);
+
+        let epoch_accounts = bank_forks.take_epoch_accounts().unwrap();
+        assert_eq!(epoch_accounts.epoch, 1);
+        assert_eq!(epoch_accounts.slot, slots_per_epoch);
+        // later roots don't show through the accounts
+        bank_forks.set_root(slots_per_epoch + 2);
+        let balance = epoch_accounts
+            .accounts
+            .iter()
+            .find(|(pubkey, _)| *pubkey == key)
+            .map(|(_, account)| account.difs);
+        assert_eq!(balance, Some((1..=slots_per_epoch).sum()));
+        assert!(bank_forks.take_epoch_accounts().is_none());
+    }
+
+    #[test]
+    fn test_bank_forks_epoch_snapshot() {
+        let GenesisBlockInfo {
+            mut genesis_block, ..
+        } = create_genesis_block(10_000);
+        genesis_block.slots_per_epoch = MINIMUM_SLOT_LENGTH as u64;
+        genesis_block.epoch_warmup = false;
+        let slots_per_epoch = genesis_block.slots_per_epoch;
+        let bank0 = Bank::new(&genesis_block);
+        bank0.freeze();
+        let mut bank_forks = BankForks::new(0, bank0);
+        bank_forks.capture_snapshots(slots_per_epoch * 10, Some(0));
+
+        // Only the first root of epoch 1 is snapshotted before the interval is up
+        for slot in 1..slots_per_epoch + 3 {
+            let parent = bank_forks.working_bank();
+            let bank = Bank::new_from_parent(&parent, &Pubkey::default(), slot);
+            bank.freeze();
+            bank_forks.insert(bank);
+            bank_forks.set_root(slot - 1);
+            let snapshot = bank_forks.take_snapshot();
+            assert_eq!(snapshot.is_some(), slot - 1 == slots_per_epoch);
+        }
+    }
+
 }
@@ -8,24 +8,33 @@ use crate::entryInfo::{hash_transactions, Entry};
 use crate::leaderArrangeCache::LeaderScheduleCache;
 use crate::packet;
 use crate::packet::{Packet, Packets};
-use crate::waterClockRecorder::{PohRecorder, PohRecorderError, WorkingBankEntries};
+use crate::waterClockRecorder::{
+    PohRecorder, PohRecorderError, WorkingBankEntries, MAX_RECORD_TRANSACTIONS,
+};
 use crate::waterClockService::PohService;
 use crate::result::{Error, Result};
 use crate::service::Service;
-use crate::signatureVerifyStage::VerifiedPackets;
+use crate::signatureVerifyStage::VerifiedReceiver;
+use crate::transactionPolicy::{self, TransactionPolicy};
+use crate::transactionTrace::TransactionTraces;
 use bincode::deserialize;
+use hashbrown::HashSet;
 use itertools::Itertools;
-use morgan_metricbot::{inc_new_counter_debug, inc_new_counter_info, inc_new_counter_warn};
+use morgan_metricbot::{
+    datapoint_debug, datapoint_info, inc_new_counter_debug, inc_new_counter_info,
+    inc_new_counter_warn,
+};
 use morgan_runtime::accounts_db::ErrorCounters;
 use morgan_runtime::bank::Bank;
 use morgan_runtime::locked_accounts_results::LockedAccountsResults;
 use morgan_interface::poh_config::PohConfig;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::timing::{
-    self, duration_as_us, DEFAULT_TICKS_PER_SLOT, MAX_RECENT_BLOCKHASHES,
-    MAX_TRANSACTION_FORWARDING_DELAY,
+    self, duration_as_us, DEFAULT_TICKS_PER_SLOT, MAX_TRANSACTION_FORWARDING_DELAY,
+    NUM_CONSECUTIVE_LEADER_SLOTS,
 };
 use morgan_interface::transaction::{self, Transaction, TransactionError};
+use std::cmp;
 use std::net::UdpSocket;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, RecvTimeoutError};
@@ -42,6 +51,13 @@ pub type UnprocessedPackets = Vec<PacketsAndOffsets>;
 // number of threads is 1 until mt bank is ready
 pub const NUM_THREADS: u32 = 10;
 
+/// Unprocessed packets a banking thread holds on to, the oldest are dropped past this
+pub const MAX_BUFFERED_PACKETS: usize = 100_000;
+
+/// Upcoming leaders a node that isn't leading forwards its buffered packets to, so they still
+/// land if the first one skips its slots
+pub const NUM_FORWARD_LEADERS: usize = 2;
+
 /// Stores the stage's thread handle and output receiver.
 pub struct BankingStage {
     bank_thread_hdls: Vec<JoinHandle<()>>,
@@ -60,28 +76,31 @@ impl BankingStage {
     pub fn new(
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
-        verified_receiver: Receiver<VerifiedPackets>,
-        verified_vote_receiver: Receiver<VerifiedPackets>,
+        verified_receiver: VerifiedReceiver,
+        transaction_traces: &Arc<TransactionTraces>,
+        transaction_policy: &Arc<dyn TransactionPolicy>,
     ) -> Self {
         Self::new_num_threads(
             cluster_info,
             poh_recorder,
             verified_receiver,
-            verified_vote_receiver,
-            2, // 1 for voting, 1 for banking.
-               // More than 2 threads is slower in testnet testing.
+            transaction_traces,
+            transaction_policy,
+            // Votes are recorded by the ClusterInfoVoteListener.
+            // More than 1 banking thread is slower in testnet testing.
+            1,
         )
     }
 
     fn new_num_threads(
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
-        verified_receiver: Receiver<VerifiedPackets>,
-        verified_vote_receiver: Receiver<VerifiedPackets>,
+        verified_receiver: VerifiedReceiver,
+        transaction_traces: &Arc<TransactionTraces>,
+        transaction_policy: &Arc<dyn TransactionPolicy>,
         num_threads: u32,
     ) -> Self {
         let verified_receiver = Arc::new(Mutex::new(verified_receiver));
-        let verified_vote_receiver = Arc::new(Mutex::new(verified_vote_receiver));
 
         // Single thread to generate entries from many banks.
         // This thread talks to poh_service and broadcasts the entries once they have been recorded.
@@ -91,15 +110,11 @@ impl BankingStage {
         // Many banks that process transactions in parallel.
         let bank_thread_hdls: Vec<JoinHandle<()>> = (0..num_threads)
             .map(|i| {
-                let (verified_receiver, enable_forwarding) = if i < num_threads - 1 {
-                    (verified_receiver.clone(), true)
-                } else {
-                    // Disable forwarding of vote transactions, as votes are gossiped
-                    (verified_vote_receiver.clone(), false)
-                };
-
+                let verified_receiver = verified_receiver.clone();
                 let poh_recorder = poh_recorder.clone();
                 let cluster_info = cluster_info.clone();
+                let transaction_traces = transaction_traces.clone();
+                let transaction_policy = transaction_policy.clone();
                 let exit = exit.clone();
                 let mut recv_start = Instant::now();
                 Builder::new()
@@ -110,8 +125,9 @@ impl BankingStage {
                             &poh_recorder,
                             &cluster_info,
                             &mut recv_start,
-                            enable_forwarding,
                             i,
+                            &transaction_traces,
+                            &*transaction_policy,
                         );
                         exit.store(true, Ordering::Relaxed);
                     })
@@ -148,6 +164,8 @@ impl BankingStage {
         my_pubkey: &Pubkey,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         buffered_packets: &mut Vec<PacketsAndOffsets>,
+        transaction_traces: &TransactionTraces,
+        transaction_policy: &dyn TransactionPolicy,
     ) -> Result<UnprocessedPackets> {
         let mut unprocessed_packets = vec![];
         let mut rebuffered_packets = 0;
@@ -171,6 +189,8 @@ impl BankingStage {
                     &poh_recorder,
                     &msgs,
                     unprocessed_indexes.to_owned(),
+                    transaction_traces,
+                    transaction_policy,
                 )?;
 
             new_tx_count += processed;
@@ -247,22 +267,28 @@ impl BankingStage {
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         buffered_packets: &mut Vec<PacketsAndOffsets>,
-        enable_forwarding: bool,
+        transaction_traces: &TransactionTraces,
+        transaction_policy: &dyn TransactionPolicy,
     ) -> Result<()> {
         let rcluster_info = cluster_info.read().unwrap();
 
-        let (decision, next_leader) = {
+        let (decision, next_leaders) = {
             let poh = poh_recorder.lock().unwrap();
-            let next_leader = poh.next_slot_leader();
-            (
-                Self::consume_or_forward_packets(
-                    next_leader,
-                    poh.bank().is_some(),
-                    poh.would_be_leader(DEFAULT_TICKS_PER_SLOT * 2),
-                    &rcluster_info.id(),
-                ),
-                next_leader,
-            )
+            let decision = Self::consume_or_forward_packets(
+                poh.next_slot_leader(),
+                poh.bank().is_some(),
+                poh.would_be_leader(DEFAULT_TICKS_PER_SLOT * 2),
+                &rcluster_info.id(),
+            );
+            let next_leaders = if decision == BufferedPacketsDecision::Forward {
+                poh.next_slot_leaders(
+                    NUM_FORWARD_LEADERS,
+                    NUM_CONSECUTIVE_LEADER_SLOTS * NUM_FORWARD_LEADERS as u64,
+                )
+            } else {
+                vec![]
+            };
+            (decision, next_leaders)
         };
 
         match decision {
@@ -271,41 +297,39 @@ impl BankingStage {
                     &rcluster_info.id(),
                     poh_recorder,
                     buffered_packets,
+                    transaction_traces,
+                    transaction_policy,
                 )?;
                 buffered_packets.append(&mut unprocessed);
                 Ok(())
             }
             BufferedPacketsDecision::Forward => {
-                if enable_forwarding {
-                    next_leader.map_or(Ok(()), |leader_pubkey| {
-                        rcluster_info
-                            .lookup(&leader_pubkey)
-                            .map_or(Ok(()), |leader| {
-                                let _ = Self::forward_buffered_packets(
-                                    &socket,
-                                    &leader.tpu_via_blobs,
-                                    &buffered_packets,
-                                );
-                                buffered_packets.clear();
-                                Ok(())
-                            })
-                    })
-                } else {
+                let tpu_addrs: Vec<_> = next_leaders
+                    .iter()
+                    .filter_map(|leader_pubkey| rcluster_info.lookup(leader_pubkey))
+                    .map(|leader| leader.tpu_via_blobs)
+                    .collect();
+                // hold on to the packets until a leader to forward them to is known
+                if !tpu_addrs.is_empty() {
+                    for tpu_addr in &tpu_addrs {
+                        let _ = Self::forward_buffered_packets(socket, tpu_addr, buffered_packets);
+                    }
                     buffered_packets.clear();
-                    Ok(())
                 }
+                Ok(())
             }
             _ => Ok(()),
         }
     }
 
     pub fn process_loop(
-        verified_receiver: &Arc<Mutex<Receiver<VerifiedPackets>>>,
+        verified_receiver: &Arc<Mutex<VerifiedReceiver>>,
         poh_recorder: &Arc<Mutex<PohRecorder>>,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         recv_start: &mut Instant,
-        enable_forwarding: bool,
         id: u32,
+        transaction_traces: &TransactionTraces,
+        transaction_policy: &dyn TransactionPolicy,
     ) {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
         let mut buffered_packets = vec![];
@@ -316,7 +340,8 @@ impl BankingStage {
                     poh_recorder,
                     cluster_info,
                     &mut buffered_packets,
-                    enable_forwarding,
+                    transaction_traces,
+                    transaction_policy,
                 )
                 .unwrap_or_else(|_| buffered_packets.clear());
             }
@@ -338,6 +363,8 @@ impl BankingStage {
                 recv_timeout,
                 cluster_info,
                 id,
+                transaction_traces,
+                transaction_policy,
             ) {
                 Err(Error::RecvTimeoutError(RecvTimeoutError::Timeout)) => (),
                 Ok(mut unprocessed_packets) => {
@@ -350,6 +377,17 @@ impl BankingStage {
                         .sum();
                     inc_new_counter_info!("banking_stage-buffered_packets", num);
                     buffered_packets.append(&mut unprocessed_packets);
+                    let (dropped, buffered) = Self::drop_oldest_buffered_packets(
+                        &mut buffered_packets,
+                        MAX_BUFFERED_PACKETS,
+                        transaction_traces,
+                    );
+                    inc_new_counter_info!("banking_stage-dropped_buffered_packets", dropped);
+                    datapoint_info!(
+                        "banking_stage-queue_depth",
+                        ("id", id, i64),
+                        ("buffered_packets", buffered, i64)
+                    );
                 }
                 Err(err) => {
                     debug!("morgan-banking-stage-tx: exit due to {:?}", err);
@@ -359,6 +397,43 @@ impl BankingStage {
         }
     }
 
+    /// Drop whole batches from the front until at most `max_packets` unprocessed packets remain,
+    /// returns the number of packets dropped and the number still buffered
+    fn drop_oldest_buffered_packets(
+        buffered_packets: &mut Vec<PacketsAndOffsets>,
+        max_packets: usize,
+        transaction_traces: &TransactionTraces,
+    ) -> (usize, usize) {
+        let mut buffered: usize = buffered_packets
+            .iter()
+            .map(|(_, unprocessed)| unprocessed.len())
+            .sum();
+        let mut num_batches = 0;
+        let mut dropped = 0;
+        for (_, unprocessed) in buffered_packets.iter() {
+            if buffered <= max_packets {
+                break;
+            }
+            buffered -= unprocessed.len();
+            dropped += unprocessed.len();
+            num_batches += 1;
+        }
+        if !transaction_traces.is_empty() {
+            for (msgs, unprocessed) in &buffered_packets[..num_batches] {
+                let (transactions, _) = Self::transactions_from_packets(msgs, unprocessed);
+                for signature in transactions.iter().filter_map(|tx| tx.signatures.get(0)) {
+                    transaction_traces.drop_transaction(
+                        signature,
+                        "banking_stage",
+                        "packet buffer full",
+                    );
+                }
+            }
+        }
+        buffered_packets.drain(..num_batches);
+        (dropped, buffered)
+    }
+
     pub fn num_threads() -> u32 {
         sys_info::cpu_num().unwrap_or(NUM_THREADS)
     }
@@ -400,9 +475,7 @@ impl BankingStage {
             );
             let hash = hash_transactions(&processed_transactions);
             // record and unlock will unlock all the successful transactions
-            poh.lock()
-                .unwrap()
-                .record(bank.slot(), hash, processed_transactions)?;
+            PohRecorder::record_fairly(poh, bank.slot(), hash, processed_transactions)?;
         }
         Ok(record_locks)
     }
@@ -418,8 +491,11 @@ impl BankingStage {
         // the likelihood of any single thread getting starved and processing old ids.
         // TODO: Banking stage threads should be prioritized to complete faster then this queue
         // expires.
-        let (loaded_accounts, results) =
-            bank.load_and_execute_transactions(txs, lock_results, MAX_RECENT_BLOCKHASHES / 2);
+        let (loaded_accounts, results) = bank.load_and_execute_transactions(
+            txs,
+            lock_results,
+            bank.runtime_params().max_processing_age,
+        );
         let load_execute_time = now.elapsed();
 
         let freeze_lock = bank.freeze_lock();
@@ -449,6 +525,14 @@ impl BankingStage {
             duration_as_us(&commit_time),
             txs.len(),
         );
+        datapoint_debug!(
+            "banking_stage-process_and_record_transactions",
+            ("slot", bank.slot(), i64),
+            ("load_execute_us", duration_as_us(&load_execute_time), i64),
+            ("record_us", duration_as_us(&record_time), i64),
+            ("commit_us", duration_as_us(&commit_time), i64),
+            ("txs_len", txs.len(), i64)
+        );
 
         Ok(())
     }
@@ -459,6 +543,7 @@ impl BankingStage {
         poh: &Arc<Mutex<PohRecorder>>,
         chunk_offset: usize,
     ) -> (Result<()>, Vec<usize>) {
+        let _slot_span = slot_span(bank.slot());
         let now = Instant::now();
         // Once accounts are locked, other threads cannot encode transactions that will modify the
         // same account state
@@ -503,12 +588,14 @@ impl BankingStage {
         transactions: &[Transaction],
         poh: &Arc<Mutex<PohRecorder>>,
     ) -> Result<(usize, Vec<usize>)> {
+        let _slot_span = slot_span(bank.slot());
         let mut chunk_start = 0;
         let mut unprocessed_txs = vec![];
         while chunk_start != transactions.len() {
+            let chunk_max = cmp::min(transactions.len(), chunk_start + MAX_RECORD_TRANSACTIONS);
             let chunk_end = chunk_start
                 + entryInfo::num_will_fit(
-                    &transactions[chunk_start..],
+                    &transactions[chunk_start..chunk_max],
                     packet::BLOB_DATA_SIZE as u64,
                     &Entry::serialized_to_blob_size,
                 );
@@ -624,7 +711,10 @@ impl BankingStage {
         let result = bank.check_transactions(
             transactions,
             &filter,
-            (MAX_RECENT_BLOCKHASHES - MAX_TRANSACTION_FORWARDING_DELAY) / 2,
+            bank.runtime_params()
+                .max_recent_blockhashes
+                .saturating_sub(MAX_TRANSACTION_FORWARDING_DELAY)
+                / 2,
             &mut error_counters,
         );
 
@@ -636,9 +726,17 @@ impl BankingStage {
         poh: &Arc<Mutex<PohRecorder>>,
         msgs: &Packets,
         transaction_indexes: Vec<usize>,
+        transaction_traces: &TransactionTraces,
+        transaction_policy: &dyn TransactionPolicy,
     ) -> Result<(usize, usize, Vec<usize>)> {
         let (transactions, transaction_indexes) =
             Self::transactions_from_packets(msgs, &transaction_indexes);
+        let (transactions, transaction_indexes) = Self::filter_admitted_transactions(
+            transactions,
+            transaction_indexes,
+            transaction_policy,
+            transaction_traces,
+        );
         debug!(
             "bank: {} filtered transactions {}",
             bank.slot(),
@@ -663,9 +761,104 @@ impl BankingStage {
             unprocessed_tx_count.saturating_sub(filtered_unprocessed_tx_indexes.len())
         );
 
+        if !transaction_traces.is_empty() {
+            Self::trace_transactions(
+                bank,
+                &transactions,
+                &transaction_indexes,
+                &unprocessed_tx_indexes,
+                &filtered_unprocessed_tx_indexes,
+                transaction_traces,
+            );
+        }
+
         Ok((processed, tx_len, filtered_unprocessed_tx_indexes))
     }
 
+    /// Report what became of each traced transaction in a batch: committed to the bank,
+    /// buffered for a retry, or dropped because it can no longer be processed
+    fn trace_transactions(
+        bank: &Bank,
+        transactions: &[Transaction],
+        transaction_indexes: &[usize],
+        unprocessed_tx_indexes: &[usize],
+        buffered_packet_indexes: &[usize],
+        transaction_traces: &TransactionTraces,
+    ) {
+        let unprocessed_tx_indexes: HashSet<_> = unprocessed_tx_indexes.iter().collect();
+        let buffered_packet_indexes: HashSet<_> = buffered_packet_indexes.iter().collect();
+        for (i, tx) in transactions.iter().enumerate() {
+            // packets aren't sanitized, a transaction without signatures can't be traced
+            let signature = match tx.signatures.get(0) {
+                Some(signature) => signature,
+                None => continue,
+            };
+            if transaction_traces.trace_id(signature).is_none() {
+                continue;
+            }
+            if unprocessed_tx_indexes.contains(&i) {
+                if buffered_packet_indexes.contains(&transaction_indexes[i]) {
+                    transaction_traces.record(signature, "banking_stage-buffered");
+                } else {
+                    transaction_traces.drop_transaction(
+                        signature,
+                        "banking_stage",
+                        "blockhash too old to retry",
+                    );
+                }
+            } else {
+                match bank.get_signature_status(signature) {
+                    Some(_) => {
+                        transaction_traces.record(signature, "banking_stage-committed");
+                    }
+                    None => transaction_traces.drop_transaction(
+                        signature,
+                        "banking_stage",
+                        "not committed to the bank",
+                    ),
+                }
+            }
+        }
+    }
+
+    // This function drops the transactions the policy refuses, they are neither processed nor
+    // buffered for a retry. Vote transactions are never refused.
+    fn filter_admitted_transactions(
+        transactions: Vec<Transaction>,
+        transaction_indexes: Vec<usize>,
+        transaction_policy: &dyn TransactionPolicy,
+        transaction_traces: &TransactionTraces,
+    ) -> (Vec<Transaction>, Vec<usize>) {
+        let mut refused = 0;
+        let admitted = transactions
+            .into_iter()
+            .zip(transaction_indexes)
+            .filter(|(tx, _)| {
+                if transactionPolicy::is_vote_transaction(tx) {
+                    return true;
+                }
+                match transaction_policy.check(tx) {
+                    Ok(()) => true,
+                    Err(reason) => {
+                        refused += 1;
+                        if !transaction_traces.is_empty() {
+                            if let Some(signature) = tx.signatures.get(0) {
+                                transaction_traces.drop_transaction(
+                                    signature,
+                                    "banking_stage",
+                                    &reason,
+                                );
+                            }
+                        }
+                        false
+                    }
+                }
+            })
+            .unzip();
+        inc_new_counter_info!("banking_stage-refused_by_policy", refused);
+        admitted
+    }
+
     fn filter_unprocessed_packets(
         bank: &Arc<Bank>,
         msgs: &Packets,
@@ -712,12 +905,14 @@ impl BankingStage {
 
     /// Process the incoming packets
     pub fn process_packets(
-        verified_receiver: &Arc<Mutex<Receiver<VerifiedPackets>>>,
+        verified_receiver: &Arc<Mutex<VerifiedReceiver>>,
         poh: &Arc<Mutex<PohRecorder>>,
         recv_start: &mut Instant,
         recv_timeout: Duration,
         cluster_info: &Arc<RwLock<ClusterInfo>>,
         id: u32,
+        transaction_traces: &TransactionTraces,
+        transaction_policy: &dyn TransactionPolicy,
     ) -> Result<UnprocessedPackets> {
         let mms = verified_receiver
             .lock()
@@ -749,7 +944,14 @@ impl BankingStage {
             let bank = bank.unwrap();
 
             let (processed, verified_txs_len, unprocessed_indexes) =
-                Self::process_received_packets(&bank, &poh, &msgs, packet_indexes)?;
+                Self::process_received_packets(
+                    &bank,
+                    &poh,
+                    &msgs,
+                    packet_indexes,
+                    transaction_traces,
+                    transaction_policy,
+                )?;
 
             new_tx_count += processed;
 
@@ -858,13 +1060,16 @@ mod tests {
     use crate::entryInfo::EntrySlice;
     use crate::genesisUtils::{create_genesis_block, GenesisBlockInfo};
     use crate::packet::to_packets;
+    use crate::transactionPolicy::TransactionPolicyConfig;
     use crate::waterClockRecorder::WorkingBank;
     use crate::{get_tmp_ledger_path, tmp_ledger_name};
     use itertools::Itertools;
+    use morgan_interface::hash::Hash;
     use morgan_interface::instruction::InstructionError;
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_transaction;
     use morgan_interface::transaction::TransactionError;
+    use morgan_vote_api::vote_instruction;
     use std::sync::mpsc::channel;
     use std::thread::sleep;
 
@@ -873,7 +1078,6 @@ mod tests {
         let genesis_block = create_genesis_block(2).genesis_block;
         let bank = Arc::new(Bank::new(&genesis_block));
         let (verified_sender, verified_receiver) = channel();
-        let (vote_sender, vote_receiver) = channel();
         let ledger_path = get_tmp_ledger_path!();
         {
             let blocktree = Arc::new(
@@ -886,11 +1090,11 @@ mod tests {
             let banking_stage = BankingStage::new(
                 &cluster_info,
                 &poh_recorder,
-                verified_receiver,
-                vote_receiver,
+                verified_receiver.into(),
+                &Arc::new(TransactionTraces::default()),
+                &TransactionPolicyConfig::default().build(),
             );
             drop(verified_sender);
-            drop(vote_sender);
             exit.store(true, Ordering::Relaxed);
             banking_stage.join().unwrap();
             poh_service.join().unwrap();
@@ -908,7 +1112,6 @@ mod tests {
         let bank = Arc::new(Bank::new(&genesis_block));
         let start_hash = bank.last_blockhash();
         let (verified_sender, verified_receiver) = channel();
-        let (vote_sender, vote_receiver) = channel();
         let ledger_path = get_tmp_ledger_path!();
         {
             let blocktree = Arc::new(
@@ -921,13 +1124,13 @@ mod tests {
             let banking_stage = BankingStage::new(
                 &cluster_info,
                 &poh_recorder,
-                verified_receiver,
-                vote_receiver,
+                verified_receiver.into(),
+                &Arc::new(TransactionTraces::default()),
+                &TransactionPolicyConfig::default().build(),
             );
             trace!("sending bank");
             sleep(Duration::from_millis(600));
             drop(verified_sender);
-            drop(vote_sender);
             exit.store(true, Ordering::Relaxed);
             poh_service.join().unwrap();
             drop(poh_recorder);
@@ -957,7 +1160,6 @@ mod tests {
         let bank = Arc::new(Bank::new(&genesis_block));
         let start_hash = bank.last_blockhash();
         let (verified_sender, verified_receiver) = channel();
-        let (vote_sender, vote_receiver) = channel();
         let ledger_path = get_tmp_ledger_path!();
         {
             let blocktree = Arc::new(
@@ -970,8 +1172,9 @@ mod tests {
             let banking_stage = BankingStage::new(
                 &cluster_info,
                 &poh_recorder,
-                verified_receiver,
-                vote_receiver,
+                verified_receiver.into(),
+                &Arc::new(TransactionTraces::default()),
+                &TransactionPolicyConfig::default().build(),
             );
 
             // fund another account so we can send 2 good transactions in a single batch.
@@ -1013,7 +1216,6 @@ mod tests {
                 .unwrap();
 
             drop(verified_sender);
-            drop(vote_sender);
             exit.store(true, Ordering::Relaxed);
             poh_service.join().unwrap();
             drop(poh_recorder);
@@ -1097,7 +1299,6 @@ mod tests {
             .collect();
         verified_sender.send(packets).unwrap();
 
-        let (vote_sender, vote_receiver) = channel();
         let ledger_path = get_tmp_ledger_path!();
         {
             let entry_receiver = {
@@ -1115,9 +1316,10 @@ mod tests {
                 let _banking_stage = BankingStage::new_num_threads(
                     &cluster_info,
                     &poh_recorder,
-                    verified_receiver,
-                    vote_receiver,
-                    2,
+                    verified_receiver.into(),
+                    &Arc::new(TransactionTraces::default()),
+                    &TransactionPolicyConfig::default().build(),
+                    1,
                 );
 
                 // wait for banking_stage to eat the packets
@@ -1129,7 +1331,6 @@ mod tests {
                 entry_receiver
             };
             drop(verified_sender);
-            drop(vote_sender);
 
             // consume the entire entry_receiver, feed it into a new bank
             // check that the balance is what we expect.
@@ -1638,4 +1839,138 @@ mod tests {
             })
             .collect_vec();
     }
+
+    #[test]
+    fn test_trace_transactions() {
+        let GenesisBlockInfo {
+            genesis_block,
+            mint_keypair,
+            ..
+        } = create_genesis_block(10_000);
+        let bank = Bank::new(&genesis_block);
+        let blockhash = genesis_block.hash();
+        let mut transactions: Vec<_> = (0..3)
+            .map(|_| system_transaction::transfer(&mint_keypair, &Pubkey::new_rand(), 1, blockhash))
+            .collect();
+        bank.process_transaction(&transactions[0]).unwrap();
+
+        let transaction_traces = TransactionTraces::default();
+        for (i, tx) in transactions.iter().enumerate() {
+            transaction_traces.insert(tx.signatures[0], format!("trace-{}", i));
+        }
+        // packets aren't sanitized, so an unsigned transaction can reach the banking stage
+        let mut unsigned = transactions[0].clone();
+        unsigned.signatures.clear();
+        transactions.push(unsigned);
+
+        // the first was committed, the second expired and the third was buffered for a retry
+        BankingStage::trace_transactions(
+            &bank,
+            &transactions,
+            &[0, 1, 2, 3],
+            &[1, 2, 3],
+            &[2],
+            &transaction_traces,
+        );
+        assert_eq!(
+            transaction_traces.trace_id(&transactions[0].signatures[0]),
+            Some("trace-0".to_string())
+        );
+        assert_eq!(
+            transaction_traces.trace_id(&transactions[1].signatures[0]),
+            None
+        );
+        assert_eq!(
+            transaction_traces.trace_id(&transactions[2].signatures[0]),
+            Some("trace-2".to_string())
+        );
+    }
+
+    #[test]
+    fn test_filter_admitted_transactions() {
+        let keypair = Keypair::new();
+        let transactions: Vec<_> = [1, 1_000, 10]
+            .iter()
+            .map(|difs| {
+                system_transaction::transfer(&keypair, &Pubkey::new_rand(), *difs, Hash::default())
+            })
+            .collect();
+        let transaction_traces = TransactionTraces::default();
+        transaction_traces.insert(transactions[1].signatures[0], "trace".to_string());
+
+        let policy = TransactionPolicyConfig {
+            max_transfer_difs: Some(100),
+            ..TransactionPolicyConfig::default()
+        }
+        .build();
+        let (admitted, indexes) = BankingStage::filter_admitted_transactions(
+            transactions.clone(),
+            vec![3, 5, 7],
+            &*policy,
+            &transaction_traces,
+        );
+        assert_eq!(
+            admitted,
+            vec![transactions[0].clone(), transactions[2].clone()]
+        );
+        assert_eq!(indexes, vec![3, 7]);
+        // a refused transaction is no longer traced
+        assert!(transaction_traces.is_empty());
+
+        // votes are admitted even when the vote program isn't allowed, and an unsigned
+        // transaction is refused while others are traced
+        let pubkey = keypair.pubkey();
+        let vote_ix = vote_instruction::vote(&pubkey, &pubkey, &pubkey, vec![]);
+        let vote_tx =
+            Transaction::new_signed_instructions(&[&keypair], vec![vote_ix], Hash::default());
+        let mut unsigned_tx = transactions[0].clone();
+        unsigned_tx.signatures.clear();
+        transaction_traces.insert(transactions[2].signatures[0], "trace".to_string());
+        let policy = TransactionPolicyConfig {
+            allowed_programs: Some(vec![]),
+            ..TransactionPolicyConfig::default()
+        }
+        .build();
+        let (admitted, indexes) = BankingStage::filter_admitted_transactions(
+            vec![vote_tx.clone(), unsigned_tx],
+            vec![0, 1],
+            &*policy,
+            &transaction_traces,
+        );
+        assert_eq!(admitted, vec![vote_tx]);
+        assert_eq!(indexes, vec![0]);
+    }
+
+    #[test]
+    fn test_drop_oldest_buffered_packets() {
+        let mut buffered_packets: Vec<_> = (0..3)
+            .map(|i| {
+                let mut packets = Packets::default();
+                packets.packets.resize(4, Packet::default());
+                packets.packets[0].meta.port = i;
+                (packets, vec![0, 1, 2, 3])
+            })
+            .collect();
+
+        assert_eq!(
+            BankingStage::drop_oldest_buffered_packets(
+                &mut buffered_packets,
+                12,
+                &TransactionTraces::default()
+            ),
+            (0, 12)
+        );
+        assert_eq!(buffered_packets.len(), 3);
+
+        assert_eq!(
+            BankingStage::drop_oldest_buffered_packets(
+                &mut buffered_packets,
+                5,
+                &TransactionTraces::default()
+            ),
+            (8, 4)
+        );
+        assert_eq!(buffered_packets.len(), 1);
+        assert_eq!(buffered_packets[0].0.packets[0].meta.port, 2);
+    }
 }
@@ -1,46 +1,82 @@
 //! The `fullnode` module hosts all the fullnode microservices.
 
 // use crate::bank_forks::BankForks;
+use crate::accountsHashVerifier::AccountsHashVerifier;
+use crate::adminRpcService::AdminRpcService;
 use crate::treasuryForks::BankForks;
-use crate::blockBufferPool::{Blocktree, CompletedSlotsReceiver};
+use crate::blockStream::BlockstreamConfig;
+use crate::blockBufferPool::{Blocktree, BlocktreeConfig, CompletedSlotsReceiver};
 use crate::blockBufferPoolProcessor::{self, BankForksInfo};
 use crate::clusterMessage::{ClusterInfo, Node};
+use crate::ClusterVoteMessageListener::VoteTracker;
 use crate::connectionInfo::ContactInfo;
+use crate::directStream::DirectStream;
 use crate::gossipService::{discover_cluster, GossipService};
 use crate::leaderArrangeCache::LeaderScheduleCache;
+use crate::ledgerArchive::{LedgerArchive, LedgerArchiveConfig, LedgerArchiveService};
+use crate::ledgerCleanupService::LedgerCleanupService;
 use crate::waterClockRecorder::PohRecorder;
 use crate::waterClockService::PohService;
+use crate::repeatStage::DEFAULT_LEADER_WARMUP_TICKS;
 use crate::rpc::JsonRpcConfig;
 use crate::rpcPubSsubService::PubSubService;
 use crate::rpcService::JsonRpcService;
 use crate::rpcSubscriptions::RpcSubscriptions;
 use crate::service::Service;
+use crate::snapshotService::{SnapshotService, DEFAULT_SNAPSHOT_INTERVAL_SLOTS};
+use crate::startupProgress::{StartupPhase, StartupProgress};
 use crate::storageStage::StorageState;
+use crate::transactionPolicy::TransactionPolicyConfig;
 use crate::transactionProcessCentre::Tpu;
+use crate::replayErrors::ReplayErrors;
+use crate::transactionTrace::TransactionTraces;
 use crate::transactionVerifyCentre::{Sockets, Tvu};
 use morgan_metricbot::inc_new_counter_info;
+use morgan_tokenbot::drone::{run_drone_until_exit, Drone};
 use morgan_runtime::bank::Bank;
+use morgan_runtime::execution_pool::{new_execution_pool, ExecutionPoolConfig};
+use morgan_runtime::snapshot::{
+    restore_incremental_snapshot, restore_snapshot, snapshot_archive_path, snapshot_slots,
+};
 use morgan_interface::genesis_block::GenesisBlock;
 use morgan_interface::poh_config::PohConfig;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil};
 use morgan_interface::timing::timestamp;
 use morgan_storage_api::SLOTS_PER_SEGMENT;
+use rayon::ThreadPool;
+use std::io;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Receiver;
+use std::sync::mpsc::{channel, Receiver};
 use std::sync::{Arc, Mutex, RwLock};
-use std::thread::Result;
+use std::thread::{sleep, Builder, JoinHandle, Result};
+use std::time::{Duration, Instant};
 use morgan_helper::logHelper::*;
 
 #[derive(Clone, Debug)]
 pub struct ValidatorConfig {
     pub sigverify_disabled: bool,
     pub voting_disabled: bool,
-    pub blockstream: Option<String>,
+    pub blockstream: Option<BlockstreamConfig>,
     pub storage_rotate_count: u64,
     pub account_paths: Option<String>,
     pub rpc_config: JsonRpcConfig,
+    pub halt_on_accounts_hash_mismatch: bool, // Exit if the cluster disagrees with our accounts
+    pub admin_rpc_port: Option<u16>,          // Serve the admin RPC on this loopback port
+    pub drone_keypair: Option<Arc<Keypair>>,  // Run a drone funded by this keypair in-process
+    pub blocktree_config: BlocktreeConfig,
+    pub ledger_archive: Option<LedgerArchiveConfig>, // Offload rooted slots to object storage
+    pub transaction_policy: TransactionPolicyConfig, // Which transactions banking admits
+    pub leader_warmup_ticks: u64, // Create our leader bank this many ticks before the slot
+    pub snapshot_path: Option<String>, // Save bank snapshots here and restore from the newest
+    pub snapshot_interval_slots: u64, // Rooted slots between two snapshots
+    pub incremental_snapshot_interval_slots: Option<u64>, // Rooted slots between two increments
+    pub accounts_index_memory_budget: Option<usize>, // Accounts index bytes kept in memory
+    pub max_ledger_slots: Option<u64>, // Purge ledger slots this far below the root
+    pub direct_stream_peers: usize, // As leader, stream blobs straight to this many top stakes
+    pub rpc_only: bool,           // Replay and serve RPC, without a tpu, votes or broadcast
+    pub execution_pool: ExecutionPoolConfig, // Threads banks execute transactions on
 }
 impl Default for ValidatorConfig {
     fn default() -> Self {
@@ -55,6 +91,21 @@ impl Default for ValidatorConfig {
             storage_rotate_count: NUM_HASHES_FOR_STORAGE_ROTATE,
             account_paths: None,
             rpc_config: JsonRpcConfig::default(),
+            halt_on_accounts_hash_mismatch: false,
+            admin_rpc_port: None,
+            drone_keypair: None,
+            blocktree_config: BlocktreeConfig::default(),
+            ledger_archive: None,
+            transaction_policy: TransactionPolicyConfig::default(),
+            leader_warmup_ticks: DEFAULT_LEADER_WARMUP_TICKS,
+            snapshot_path: None,
+            snapshot_interval_slots: DEFAULT_SNAPSHOT_INTERVAL_SLOTS,
+            incremental_snapshot_interval_slots: None,
+            accounts_index_memory_budget: None,
+            max_ledger_slots: None,
+            direct_stream_peers: 0,
+            rpc_only: false,
+            execution_pool: ExecutionPoolConfig::default(),
         }
     }
 }
@@ -62,14 +113,23 @@ impl Default for ValidatorConfig {
 pub struct Validator {
     pub id: Pubkey,
     exit: Arc<AtomicBool>,
+    shutdown_request: Arc<AtomicBool>, // Set by the `fullnodeExit` RPC
     rpc_service: Option<JsonRpcService>,
+    admin_rpc_service: Option<AdminRpcService>,
     rpc_pubsub_service: Option<PubSubService>,
+    ledger_archive_service: Option<LedgerArchiveService>,
+    snapshot_service: Option<SnapshotService>,
+    ledger_cleanup_service: Option<LedgerCleanupService>,
     gossip_service: GossipService,
     poh_recorder: Arc<Mutex<PohRecorder>>,
     poh_service: PohService,
-    tpu: Tpu,
+    slot_duration: Duration,
+    blocktree: Arc<Blocktree>,
+    tpu: Option<Tpu>,
     tvu: Tvu,
+    accounts_hash_verifier: AccountsHashVerifier,
     ip_echo_server: morgan_netutil::IpEchoServer,
+    drone: Option<JoinHandle<()>>,
 }
 
 impl Validator {
@@ -97,20 +157,76 @@ impl Validator {
         let bank = Bank::new_with_paths(&genesis_block, None);
         let genesis_blockhash = bank.last_blockhash();
 
+        if config.rpc_only {
+            // Not leader eligible, nothing should send this node transactions
+            node.info.tpu = socketaddr_any!();
+            node.info.tpu_via_blobs = socketaddr_any!();
+        }
+        node.info.wallclock = timestamp();
+        node.info.cluster_id = ContactInfo::compute_cluster_id(&genesis_blockhash);
+        let exit = Arc::new(AtomicBool::new(false));
+        let mut rpc_config = config.rpc_config.clone();
+        let drone = config.drone_keypair.as_ref().map(|drone_keypair| {
+            let (drone_port, drone) =
+                start_embedded_drone(drone_keypair, node.info.drone.port(), &exit);
+            node.info.drone = SocketAddr::new(node.info.rpc.ip(), drone_port);
+            if rpc_config.drone_addr.is_none() {
+                rpc_config.drone_addr = Some(node.info.drone);
+            }
+            drone
+        });
+        if rpc_config.ledger_archive.is_none() {
+            rpc_config.ledger_archive = config.ledger_archive.clone();
+        }
+        if rpc_config.snapshot_path.is_none() {
+            rpc_config.snapshot_path = config.snapshot_path.as_ref().map(Into::into);
+        }
+        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(
+            node.info.clone(),
+            keypair.clone(),
+        )));
+        let shutdown_request = Arc::new(AtomicBool::new(false));
+
+        // The admin rpc comes up before the ledger is replayed so `getHealth` can report how far
+        // startup got, the genesis bank it serves until then is swapped for the replayed forks
+        let startup_progress = Arc::new(StartupProgress::default());
+        let bank_forks = Arc::new(RwLock::new(BankForks::new(bank.slot(), bank)));
+        let admin_rpc_service = config.admin_rpc_port.map(|admin_rpc_port| {
+            AdminRpcService::new(
+                admin_rpc_port,
+                ledger_path,
+                &cluster_info,
+                &bank_forks,
+                &startup_progress,
+                &shutdown_request,
+                &exit,
+            )
+        });
+
         let (
-            bank_forks,
+            processed_bank_forks,
             bank_forks_info,
             blocktree,
             ledger_signal_receiver,
             completed_slots_receiver,
             leader_schedule_cache,
             poh_config,
-        ) = new_banks_from_blocktree(ledger_path, config.account_paths.clone());
+        ) = new_banks_from_blocktree(
+            ledger_path,
+            config.account_paths.clone(),
+            config.accounts_index_memory_budget,
+            config.snapshot_path.clone(),
+            config.blocktree_config,
+            new_execution_pool(&config.execution_pool),
+            Some(&startup_progress),
+        );
+        startup_progress.set_phase(StartupPhase::StartingServices);
+        // an identity set over the admin rpc while the ledger loaded is the one services start with
+        let id = cluster_info.read().unwrap().id();
 
         let leader_schedule_cache = Arc::new(leader_schedule_cache);
-        let exit = Arc::new(AtomicBool::new(false));
         let bank_info = &bank_forks_info[0];
-        let bank = bank_forks[bank_info.bank_slot].clone();
+        let bank = processed_bank_forks[bank_info.bank_slot].clone();
 
         // info!(
         //     "{}",
@@ -129,6 +245,7 @@ impl Validator {
         let blocktree = Arc::new(blocktree);
 
         let poh_config = Arc::new(poh_config);
+        let slot_duration = poh_config.target_tick_duration * bank.ticks_per_slot() as u32;
         let (poh_recorder, entry_receiver) = PohRecorder::new_with_clear_signal(
             bank.tick_height(),
             bank.last_blockhash(),
@@ -175,16 +292,15 @@ impl Validator {
                 module_path!().to_string()
             )
         );
-        let bank_forks = Arc::new(RwLock::new(bank_forks));
-
-        node.info.wallclock = timestamp();
-        let cluster_info = Arc::new(RwLock::new(ClusterInfo::new(
-            node.info.clone(),
-            keypair.clone(),
-        )));
+        *bank_forks.write().unwrap() = processed_bank_forks;
 
         let storage_state = StorageState::new();
 
+        // shared by the rpc service, which registers client trace ids, and the stages that report
+        // on traced transactions
+        let transaction_traces = Arc::new(TransactionTraces::default());
+        // filled in by replay, read by the rpc service
+        let replay_errors = Arc::new(ReplayErrors::default());
         let rpc_service = if node.info.rpc.port() == 0 {
             None
         } else {
@@ -192,12 +308,50 @@ impl Validator {
                 &cluster_info,
                 SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), node.info.rpc.port()),
                 storage_state.clone(),
-                config.rpc_config.clone(),
+                rpc_config,
                 bank_forks.clone(),
-                &exit,
+                Some(blocktree.clone()),
+                &transaction_traces,
+                &replay_errors,
+                &shutdown_request,
             ))
         };
 
+        let ledger_archive = config.ledger_archive.as_ref().map(|ledger_archive| {
+            Arc::new(
+                LedgerArchive::from_config(ledger_archive).expect("failed to open ledger archive"),
+            )
+        });
+        let ledger_archive_service = ledger_archive.as_ref().map(|ledger_archive| {
+            LedgerArchiveService::new(
+                ledger_archive.clone(),
+                blocktree.clone(),
+                bank_forks.clone(),
+                &exit,
+            )
+        });
+
+        let snapshot_service = config.snapshot_path.as_ref().map(|snapshot_path| {
+            SnapshotService::new(
+                snapshot_path.into(),
+                config.snapshot_interval_slots,
+                config.incremental_snapshot_interval_slots,
+                bank_forks.clone(),
+                &exit,
+            )
+        });
+
+        let ledger_cleanup_service = config.max_ledger_slots.map(|max_ledger_slots| {
+            LedgerCleanupService::new(
+                blocktree.clone(),
+                bank_forks.clone(),
+                max_ledger_slots,
+                config.snapshot_path.as_ref().map(Into::into),
+                ledger_archive.clone(),
+                &exit,
+            )
+        });
+
         let ip_echo_server =
             morgan_netutil::ip_echo_server(node.sockets.gossip.local_addr().unwrap().port());
 
@@ -233,6 +387,10 @@ impl Validator {
                 .set_entrypoint(entrypoint_info.clone());
         }
 
+        let direct_stream = node
+            .sockets
+            .direct_stream
+            .map(|socket| Arc::new(DirectStream::new(&id, socket, config.direct_stream_peers)));
         let sockets = Sockets {
             repair: node
                 .sockets
@@ -250,14 +408,17 @@ impl Validator {
                 .iter()
                 .map(|s| s.try_clone().expect("Failed to clone TVU Sockets"))
                 .collect(),
+            direct_stream: direct_stream.clone(),
         };
 
-        let voting_keypair = if config.voting_disabled {
+        let voting_keypair = if config.voting_disabled || config.rpc_only {
             None
         } else {
             Some(voting_keypair)
         };
 
+        // shared by the vote listener, which fills it from gossip, and replay, which reads it
+        let vote_tracker = Arc::new(RwLock::new(VoteTracker::default()));
         let tvu = Tvu::new(
             vote_account,
             voting_keypair,
@@ -276,6 +437,17 @@ impl Validator {
             &exit,
             &genesis_blockhash,
             completed_slots_receiver,
+            &vote_tracker,
+            &transaction_traces,
+            &replay_errors,
+            config.leader_warmup_ticks,
+        );
+
+        let accounts_hash_verifier = AccountsHashVerifier::new(
+            &bank_forks,
+            &cluster_info,
+            config.halt_on_accounts_hash_mismatch,
+            &exit,
         );
 
         if config.sigverify_disabled {
@@ -289,49 +461,213 @@ impl Validator {
             );
         }
 
-        let tpu = Tpu::new(
-            &id,
-            &cluster_info,
-            &poh_recorder,
-            entry_receiver,
-            node.sockets.tpu,
-            node.sockets.tpu_via_blobs,
-            node.sockets.broadcast,
-            config.sigverify_disabled,
-            &blocktree,
-            &exit,
-            &genesis_blockhash,
-        );
+        // Without a tpu to take its entries poh abandons any leader bank, so an rpc only
+        // node never produces a block even if it's scheduled to
+        let tpu = if config.rpc_only {
+            println!(
+                "{}",
+                printLn(
+                    "serving rpc only, not voting or leading".to_string(),
+                    module_path!().to_string()
+                )
+            );
+            None
+        } else {
+            Some(Tpu::new(
+                &id,
+                &cluster_info,
+                &poh_recorder,
+                entry_receiver,
+                node.sockets.tpu,
+                node.sockets.tpu_via_blobs,
+                node.sockets.broadcast,
+                direct_stream,
+                config.sigverify_disabled,
+                &blocktree,
+                &exit,
+                &genesis_blockhash,
+                &vote_tracker,
+                &transaction_traces,
+                &config.transaction_policy.build(),
+                &subscriptions,
+            ))
+        };
 
+        startup_progress.set_phase(StartupPhase::Running);
         inc_new_counter_info!("fullnode-new", 1);
         Self {
             id,
             gossip_service,
             rpc_service,
+            admin_rpc_service,
             rpc_pubsub_service,
+            ledger_archive_service,
+            snapshot_service,
+            ledger_cleanup_service,
             tpu,
             tvu,
+            accounts_hash_verifier,
             exit,
+            shutdown_request,
             poh_service,
             poh_recorder,
+            slot_duration,
+            blocktree,
             ip_echo_server,
+            drone,
         }
     }
 
     // Used for notifying many nodes in parallel to exit
     pub fn exit(&self) {
+        self.shutdown_request.store(true, Ordering::Relaxed);
         self.exit.store(true, Ordering::Relaxed);
     }
 
+    /// Block until a shutdown is requested over RPC or the node exits on its own
+    pub fn wait_for_exit(&self) {
+        while !self.shutdown_request.load(Ordering::Relaxed) && !self.exit.load(Ordering::Relaxed) {
+            sleep(Duration::from_millis(100));
+        }
+    }
+
+    /// Shut down without cutting the ledger off mid-write: stop taking in transactions and
+    /// blobs, let the working bank reach its last tick or abandon it, join every service, and
+    /// flush the ledger
+    pub fn shutdown(self) -> Result<()> {
+        println!(
+            "{}",
+            printLn(
+                format!("shutting down verifier {}", self.id).to_string(),
+                module_path!().to_string()
+            )
+        );
+        if let Some(tpu) = &self.tpu {
+            tpu.close_ingress();
+        }
+        self.tvu.close_ingress();
+        self.settle_working_bank();
+        self.exit();
+
+        let blocktree = self.blocktree.clone();
+        self.join()?;
+        if let Err(e) = blocktree.flush() {
+            println!(
+                "{}",
+                Error(
+                    format!("failed to flush ledger: {:?}", e).to_string(),
+                    module_path!().to_string()
+                )
+            );
+        }
+        Ok(())
+    }
+
+    // A working bank gets a slot's time to reach its last tick, after which PohRecorder clears
+    // it. One that doesn't, or can't because PoH already stopped, is abandoned so that nothing
+    // is recorded into it while the services stop.
+    fn settle_working_bank(&self) {
+        let start = Instant::now();
+        loop {
+            let mut poh_recorder = self.poh_recorder.lock().unwrap();
+            let bank = match poh_recorder.bank() {
+                Some(bank) => bank,
+                None => return,
+            };
+            if self.exit.load(Ordering::Relaxed) || start.elapsed() >= self.slot_duration {
+                println!(
+                    "{}",
+                    Warn(
+                        format!(
+                            "abandoning working bank {} at tick {}",
+                            bank.slot(),
+                            bank.tick_height()
+                        )
+                        .to_string(),
+                        module_path!().to_string()
+                    )
+                );
+                poh_recorder.clear_bank();
+                return;
+            }
+            drop(poh_recorder);
+            sleep(Duration::from_millis(10));
+        }
+    }
+
     pub fn close(self) -> Result<()> {
         self.exit();
         self.join()
     }
 }
 
+/// Run a drone funded by `keypair` on `port`, or any free port if 0, until `exit` is set.
+/// Returns the port it listens on and its thread, it is meant for test clusters.
+fn start_embedded_drone(
+    keypair: &Keypair,
+    port: u16,
+    exit: &Arc<AtomicBool>,
+) -> (u16, JoinHandle<()>) {
+    let keypair = Keypair::from_bytes(&keypair.to_bytes()).unwrap();
+    let exit = exit.clone();
+    let (sender, receiver) = channel();
+    let thread_hdl = Builder::new()
+        .name("morgan-drone".to_string())
+        .spawn(move || {
+            let drone = Arc::new(Mutex::new(Drone::new(keypair, None, None)));
+            run_drone_until_exit(drone, socketaddr!(0, port), Some(sender), &exit);
+        })
+        .unwrap();
+    let port = receiver
+        .recv()
+        .expect("embedded drone failed to start")
+        .port();
+    (port, thread_hdl)
+}
+
+/// The newest snapshot under `snapshot_path` that restores, the newest increment over the
+/// last full one first, then the full ones from the newest down
+fn restore_newest_snapshot(
+    snapshot_path: &str,
+    account_paths: &Option<String>,
+    index_memory_budget: Option<usize>,
+) -> Option<Bank> {
+    match restore_incremental_snapshot(snapshot_path, account_paths.clone(), index_memory_budget) {
+        Ok(bank) => return Some(bank),
+        // Without a manifest there are only full snapshots
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => (),
+        Err(err) => println!(
+            "{}",
+            Warn(
+                format!("failed to restore incremental snapshot: {}", err),
+                module_path!().to_string()
+            )
+        ),
+    }
+    for slot in snapshot_slots(snapshot_path).ok()? {
+        let archive_path = snapshot_archive_path(snapshot_path, slot);
+        match restore_snapshot(&archive_path, account_paths.clone(), index_memory_budget) {
+            Ok(bank) => return Some(bank),
+            Err(err) => println!(
+                "{}",
+                Warn(
+                    format!("failed to restore snapshot {:?}: {}", archive_path, err),
+                    module_path!().to_string()
+                )
+            ),
+        }
+    }
+    None
+}
+
 pub fn new_banks_from_blocktree(
     blocktree_path: &str,
     account_paths: Option<String>,
+    index_memory_budget: Option<usize>,
+    snapshot_path: Option<String>,
+    blocktree_config: BlocktreeConfig,
+    execution_pool: Option<Arc<ThreadPool>>,
+    progress: Option<&StartupProgress>,
 ) -> (
     BankForks,
     Vec<BankForksInfo>,
@@ -345,12 +681,51 @@ pub fn new_banks_from_blocktree(
         GenesisBlock::load(blocktree_path).expect("Expected to successfully open genesis block");
 
     let (blocktree, ledger_signal_receiver, completed_slots_receiver) =
-        Blocktree::open_with_signal(blocktree_path)
+        Blocktree::open_with_config_and_signal(blocktree_path, blocktree_config)
             .expect("Expected to successfully open database ledger");
 
-    let (bank_forks, bank_forks_info, leader_schedule_cache) =
-        blockBufferPoolProcessor::process_blocktree(&genesis_block, &blocktree, account_paths)
-            .expect("process_blocktree failed");
+    let restored_bank = snapshot_path.as_ref().and_then(|snapshot_path| {
+        if let Some(progress) = progress {
+            progress.set_phase(StartupPhase::LoadingSnapshot);
+        }
+        restore_newest_snapshot(snapshot_path, &account_paths, index_memory_budget)
+    });
+    let processed = match restored_bank {
+        Some(mut bank) => {
+            let slot = bank.slot();
+            bank.set_execution_pool(execution_pool.clone());
+            blockBufferPoolProcessor::process_blocktree_from_root(
+                &blocktree,
+                Arc::new(bank),
+                progress,
+            )
+            .map_err(|err| {
+                println!(
+                    "{}",
+                    Warn(
+                        format!(
+                            "snapshot of slot {} doesn't match the ledger, replaying it: {:?}",
+                            slot, err
+                        ),
+                        module_path!().to_string()
+                    )
+                )
+            })
+            .ok()
+        }
+        None => None,
+    };
+    let (bank_forks, bank_forks_info, leader_schedule_cache) = processed.unwrap_or_else(|| {
+        blockBufferPoolProcessor::process_blocktree(
+            &genesis_block,
+            &blocktree,
+            account_paths,
+            index_memory_budget,
+            execution_pool,
+            progress,
+        )
+        .expect("process_blocktree failed")
+    });
 
     (
         bank_forks,
@@ -372,14 +747,32 @@ impl Service for Validator {
         if let Some(rpc_service) = self.rpc_service {
             rpc_service.join()?;
         }
+        if let Some(admin_rpc_service) = self.admin_rpc_service {
+            admin_rpc_service.join()?;
+        }
         if let Some(rpc_pubsub_service) = self.rpc_pubsub_service {
             rpc_pubsub_service.join()?;
         }
+        if let Some(ledger_archive_service) = self.ledger_archive_service {
+            ledger_archive_service.join()?;
+        }
+        if let Some(snapshot_service) = self.snapshot_service {
+            snapshot_service.join()?;
+        }
+        if let Some(ledger_cleanup_service) = self.ledger_cleanup_service {
+            ledger_cleanup_service.join()?;
+        }
 
         self.gossip_service.join()?;
-        self.tpu.join()?;
+        if let Some(tpu) = self.tpu {
+            tpu.join()?;
+        }
         self.tvu.join()?;
+        self.accounts_hash_verifier.join()?;
         self.ip_echo_server.shutdown_now();
+        if let Some(drone) = self.drone {
+            drone.join()?;
+        }
 
         Ok(())
     }
@@ -426,6 +819,18 @@ mod tests {
     use crate::blockBufferPool::create_new_tmp_ledger;
     use crate::genesisUtils::create_genesis_block_with_leader;
     use std::fs::remove_dir_all;
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_embedded_drone_exit() {
+        let exit = Arc::new(AtomicBool::new(false));
+        let (port, drone) = start_embedded_drone(&Keypair::new(), 0, &exit);
+        assert!(TcpListener::bind(socketaddr!(0, port)).is_err());
+        exit.store(true, Ordering::Relaxed);
+        drone.join().unwrap();
+        // The drone's socket is closed once it exits
+        assert!(TcpListener::bind(socketaddr!(0, port)).is_ok());
+    }
 
     #[test]
     fn validator_exit() {
@@ -455,6 +860,49 @@ mod tests {
         remove_dir_all(validator_ledger_path).unwrap();
     }
 
+    #[test]
+    fn validator_rpc_only_exit() {
+        let leader_keypair = Keypair::new();
+        let leader_node = Node::new_localhost_with_pubkey(&leader_keypair.pubkey());
+
+        let validator_keypair = Keypair::new();
+        let validator_node = Node::new_localhost_with_pubkey(&validator_keypair.pubkey());
+        let genesis_block =
+            create_genesis_block_with_leader(10_000, &leader_keypair.pubkey(), 1000).genesis_block;
+        let (validator_ledger_path, _blockhash) = create_new_tmp_ledger!(&genesis_block);
+
+        let voting_keypair = Arc::new(Keypair::new());
+        let storage_keypair = Arc::new(Keypair::new());
+        let mut config = ValidatorConfig::default();
+        config.voting_disabled = true;
+        config.rpc_only = true;
+        let validator = Validator::new(
+            validator_node,
+            &Arc::new(validator_keypair),
+            &validator_ledger_path,
+            &voting_keypair.pubkey(),
+            &voting_keypair,
+            &storage_keypair,
+            Some(&leader_node.info),
+            &config,
+        );
+        assert!(validator.tpu.is_none());
+        validator.close().unwrap();
+        remove_dir_all(validator_ledger_path).unwrap();
+    }
+
+    #[test]
+    fn validator_shutdown() {
+        let (validator, _contact_info, _mint_keypair, ledger_path) = new_validator_for_tests();
+        validator.shutdown().unwrap();
+
+        // The ledger reopens with everything the validator wrote
+        let blocktree = Blocktree::open(&ledger_path).unwrap();
+        assert!(blocktree.meta(0).unwrap().is_some());
+        drop(blocktree);
+        remove_dir_all(ledger_path).unwrap();
+    }
+
     #[test]
     fn validator_parallel_exit() {
         let leader_keypair = Keypair::new();
@@ -31,6 +31,16 @@ impl Poh {
         std::mem::swap(&mut poh, self);
     }
 
+    pub fn hashes_per_tick(&self) -> u64 {
+        self.hashes_per_tick
+    }
+
+    /// Change the number of hashes per tick, taking effect from the next tick on
+    pub fn set_hashes_per_tick(&mut self, hashes_per_tick: u64) {
+        assert!(hashes_per_tick > 1);
+        self.hashes_per_tick = hashes_per_tick;
+    }
+
     pub fn hash(&mut self, max_num_hashes: u64) -> bool {
         let num_hashes = std::cmp::min(self.remaining_hashes - 1, max_num_hashes);
         for _ in 0..num_hashes {
@@ -106,6 +116,20 @@ mod tests {
         true
     }
 
+    #[test]
+    fn test_poh_set_hashes_per_tick() {
+        let mut poh = Poh::new(Hash::default(), Some(4));
+        assert!(!poh.hash(1));
+        poh.set_hashes_per_tick(2);
+        // the tick in progress keeps the old length
+        assert!(!poh.hash(1));
+        assert!(poh.hash(1));
+        assert_eq!(poh.tick().unwrap().num_hashes, 4);
+        assert_eq!(poh.hashes_per_tick(), 2);
+        assert!(poh.hash(1));
+        assert_eq!(poh.tick().unwrap().num_hashes, 2);
+    }
+
     #[test]
     fn test_poh_verify() {
         let zero = Hash::default();
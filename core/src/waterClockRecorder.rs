@@ -22,13 +22,19 @@ use morgan_interface::poh_config::PohConfig;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::timing;
 use morgan_interface::transaction::Transaction;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender, SyncSender};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
 use std::time::Instant;
 use morgan_helper::logHelper::*;
 
 const MAX_LAST_LEADER_GRACE_TICKS_FACTOR: u64 = 2;
 
+/// Most transactions one `record()` call takes, so a single call never holds the recorder long
+/// enough to delay a tick.  Banking splits larger batches into several entries
+pub const MAX_RECORD_TRANSACTIONS: usize = 128;
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum PohRecorderError {
     InvalidCallingObject,
@@ -62,10 +68,12 @@ pub struct PohRecorder {
     leader_schedule_cache: Arc<LeaderScheduleCache>,
     poh_config: Arc<PohConfig>,
     ticks_per_slot: u64,
+    tick_pending: Arc<AtomicBool>, // Raised while the tick producer waits for the lock
 }
 
 impl PohRecorder {
-    fn clear_bank(&mut self) {
+    /// Stop recording into the working bank, if any, whether or not it reached its last tick
+    pub fn clear_bank(&mut self) {
         if let Some(working_bank) = self.working_bank.take() {
             let bank = working_bank.bank;
             let next_leader_slot = self.leader_schedule_cache.next_leader_slot(
@@ -100,12 +108,37 @@ impl PohRecorder {
         self.working_bank.is_some() || close_to_leader_tick
     }
 
+    /// The next slot this node is leader for, if any
+    pub fn next_leader_slot(&self) -> Option<u64> {
+        self.start_leader_at_tick.map(|leader_tick| {
+            leader_tick.saturating_sub(self.max_last_leader_grace_ticks) / self.ticks_per_slot
+        })
+    }
+
     pub fn next_slot_leader(&self) -> Option<Pubkey> {
         let slot =
             leaderArrangeUtils::tick_height_to_slot(self.ticks_per_slot, self.tick_height());
         self.leader_schedule_cache.slot_leader_at(slot + 1, None)
     }
 
+    /// The first `num_leaders` distinct leaders of the slots after the current one, other than
+    /// this node, looking at most `max_slots` ahead and no further than the cached schedules
+    pub fn next_slot_leaders(&self, num_leaders: usize, max_slots: u64) -> Vec<Pubkey> {
+        let slot = leaderArrangeUtils::tick_height_to_slot(self.ticks_per_slot, self.tick_height());
+        let mut leaders = Vec::with_capacity(num_leaders);
+        for next_slot in slot + 1..=slot + max_slots {
+            if leaders.len() == num_leaders {
+                break;
+            }
+            match self.leader_schedule_cache.slot_leader_at(next_slot, None) {
+                Some(leader) if leader == self.id || leaders.contains(&leader) => (),
+                Some(leader) => leaders.push(leader),
+                None => break,
+            }
+        }
+        leaders
+    }
+
     pub fn start_slot(&self) -> u64 {
         self.start_slot
     }
@@ -194,7 +227,12 @@ impl PohRecorder {
             ).to_string();
             println!("{}", printLn(info, module_path!().to_string()));
 
-            poh.reset(blockhash, self.poh_config.hashes_per_tick);
+            // keep any adjustment the poh service made to the tick rate
+            let hashes_per_tick = self
+                .poh_config
+                .hashes_per_tick
+                .map(|_| poh.hashes_per_tick());
+            poh.reset(blockhash, hashes_per_tick);
         }
 
         std::mem::swap(&mut cache, &mut self.tick_cache);
@@ -355,6 +393,10 @@ impl PohRecorder {
         // Entries without transactions are used to track real-time passing in the ledger and
         // cannot be generated by `record()`
         assert!(!transactions.is_empty(), "No transactions provided");
+        assert!(
+            transactions.len() <= MAX_RECORD_TRANSACTIONS,
+            "Too many transactions provided"
+        );
         loop {
             self.flush_cache(false)?;
 
@@ -389,6 +431,48 @@ impl PohRecorder {
         }
     }
 
+    /// The flag the tick producer raises while it waits for the lock, fetched once so raising
+    /// it doesn't take the lock, see `lock_for_tick`
+    pub fn tick_pending(&self) -> Arc<AtomicBool> {
+        self.tick_pending.clone()
+    }
+
+    /// Lock `poh_recorder` to tick, any `record_fairly()` waiting for the lock gives way
+    pub fn lock_for_tick<'a>(
+        poh_recorder: &'a Mutex<PohRecorder>,
+        tick_pending: &AtomicBool,
+    ) -> MutexGuard<'a, PohRecorder> {
+        tick_pending.store(true, Ordering::Release);
+        let poh_recorder = poh_recorder.lock().unwrap();
+        tick_pending.store(false, Ordering::Release);
+        poh_recorder
+    }
+
+    /// `record()` into `poh_recorder`, but let the tick producer go first whenever it is
+    /// waiting for the lock.  The lock makes no fairness promise, so banking threads recording
+    /// back to back could otherwise keep ticks waiting.
+    pub fn record_fairly(
+        poh_recorder: &Mutex<PohRecorder>,
+        bank_slot: u64,
+        mixin: Hash,
+        transactions: Vec<Transaction>,
+    ) -> Result<()> {
+        let mut yields = 0;
+        loop {
+            {
+                let mut poh_recorder = poh_recorder.lock().unwrap();
+                if !poh_recorder.tick_pending.load(Ordering::Acquire) {
+                    if yields > 0 {
+                        inc_new_counter_info!("poh_recorder-record_yields", yields);
+                    }
+                    return poh_recorder.record(bank_slot, mixin, transactions);
+                }
+            }
+            yields += 1;
+            thread::yield_now();
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     pub fn new_with_clear_signal(
         tick_height: u64,
@@ -431,6 +515,7 @@ impl PohRecorder {
                 leader_schedule_cache: leader_schedule_cache.clone(),
                 ticks_per_slot,
                 poh_config: poh_config.clone(),
+                tick_pending: Arc::new(AtomicBool::new(false)),
             },
             receiver,
         )
@@ -474,6 +559,7 @@ mod tests {
     use morgan_interface::hash::hash;
     use morgan_interface::timing::DEFAULT_TICKS_PER_SLOT;
     use std::sync::mpsc::sync_channel;
+    use std::time::Duration;
 
     #[test]
     fn test_poh_recorder_no_zero_tick() {
@@ -1254,6 +1340,46 @@ mod tests {
         Blocktree::destroy(&ledger_path).unwrap();
     }
 
+    #[test]
+    fn test_next_slot_leaders() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree =
+                Blocktree::open(&ledger_path).expect("Expected to be able to open database ledger");
+            let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(2);
+            let bank = Arc::new(Bank::new(&genesis_block));
+            let leader_schedule_cache = Arc::new(LeaderScheduleCache::new_from_bank(&bank));
+            let leader = leader_schedule_cache
+                .slot_leader_at(1, Some(&bank))
+                .unwrap();
+            let blocktree = Arc::new(blocktree);
+            let new_poh_recorder = |id: &Pubkey| {
+                PohRecorder::new(
+                    0,
+                    bank.last_blockhash(),
+                    0,
+                    None,
+                    bank.ticks_per_slot(),
+                    id,
+                    &blocktree,
+                    &leader_schedule_cache,
+                    &Arc::new(PohConfig::default()),
+                )
+                .0
+            };
+
+            // a single node leads every slot, so there's only one leader to find
+            let poh_recorder = new_poh_recorder(&Pubkey::new_rand());
+            assert_eq!(poh_recorder.next_slot_leaders(2, 8), vec![leader]);
+            assert!(poh_recorder.next_slot_leaders(2, 0).is_empty());
+
+            // and the leader doesn't forward to itself
+            let poh_recorder = new_poh_recorder(&leader);
+            assert!(poh_recorder.next_slot_leaders(2, 8).is_empty());
+        }
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
+
     #[test]
     fn test_would_be_leader_soon() {
         let ledger_path = get_tmp_ledger_path!();
@@ -1328,4 +1454,95 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    #[should_panic(expected = "Too many transactions provided")]
+    fn test_poh_recorder_record_too_many_transactions() {
+        let ledger_path = get_tmp_ledger_path!();
+        let blocktree =
+            Blocktree::open(&ledger_path).expect("Expected to be able to open database ledger");
+        let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(2);
+        let bank = Arc::new(Bank::new(&genesis_block));
+        let prev_hash = bank.last_blockhash();
+        let (mut poh_recorder, _entry_receiver) = PohRecorder::new(
+            0,
+            prev_hash,
+            0,
+            Some(4),
+            bank.ticks_per_slot(),
+            &Pubkey::default(),
+            &Arc::new(blocktree),
+            &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+            &Arc::new(PohConfig::default()),
+        );
+        let transactions = vec![test_tx(); MAX_RECORD_TRANSACTIONS + 1];
+        let _ = poh_recorder.record(bank.slot(), hash(b"hello world!"), transactions);
+    }
+
+    #[test]
+    fn test_poh_recorder_tick_jitter_under_record_load() {
+        let ledger_path = get_tmp_ledger_path!();
+        {
+            let blocktree =
+                Blocktree::open(&ledger_path).expect("Expected to be able to open database ledger");
+            let GenesisBlockInfo { genesis_block, .. } = create_genesis_block(2);
+            let bank = Arc::new(Bank::new(&genesis_block));
+            let prev_hash = bank.last_blockhash();
+            let (mut poh_recorder, _entry_receiver) = PohRecorder::new(
+                0,
+                prev_hash,
+                0,
+                Some(4),
+                bank.ticks_per_slot(),
+                &Pubkey::default(),
+                &Arc::new(blocktree),
+                &Arc::new(LeaderScheduleCache::new_from_bank(&bank)),
+                &Arc::new(PohConfig::default()),
+            );
+            poh_recorder.set_working_bank(WorkingBank {
+                bank: bank.clone(),
+                min_tick_height: 0,
+                max_tick_height: std::u64::MAX,
+            });
+            let tick_pending = poh_recorder.tick_pending();
+            let poh_recorder = Arc::new(Mutex::new(poh_recorder));
+            let exit = Arc::new(AtomicBool::new(false));
+
+            let recorders: Vec<_> = (0..4)
+                .map(|_| {
+                    let poh_recorder = poh_recorder.clone();
+                    let exit = exit.clone();
+                    let slot = bank.slot();
+                    thread::spawn(move || {
+                        let tx = test_tx();
+                        while !exit.load(Ordering::Relaxed) {
+                            PohRecorder::record_fairly(
+                                &poh_recorder,
+                                slot,
+                                hash(b"hello world!"),
+                                vec![tx.clone()],
+                            )
+                            .unwrap();
+                        }
+                    })
+                })
+                .collect();
+
+            let mut max_wait = Duration::from_millis(0);
+            for _ in 0..50 {
+                thread::sleep(Duration::from_millis(10));
+                let now = Instant::now();
+                PohRecorder::lock_for_tick(&poh_recorder, &tick_pending).tick();
+                max_wait = max_wait.max(now.elapsed());
+            }
+            exit.store(true, Ordering::Relaxed);
+            for recorder in recorders {
+                recorder.join().unwrap();
+            }
+
+            // Each tick waits for at most the record in progress, not the whole queue of them
+            assert!(max_wait < Duration::from_millis(100), "{:?}", max_wait);
+        }
+        Blocktree::destroy(&ledger_path).unwrap();
+    }
 }
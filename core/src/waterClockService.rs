@@ -4,9 +4,12 @@ use crate::waterClockRecorder::PohRecorder;
 use crate::service::Service;
 use core_affinity;
 use morgan_interface::poh_config::PohConfig;
+use morgan_interface::timing::DEFAULT_NUM_TICKS_PER_SECOND;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, sleep, Builder, JoinHandle};
+use std::time::{Duration, Instant};
+use morgan_helper::logHelper::*;
 
 pub struct PohService {
     tick_producer: JoinHandle<()>,
@@ -20,6 +23,118 @@ pub struct PohService {
 // See benches/poh.rs for some benchmarks that attempt to justify this magic number.
 pub const NUM_HASHES_PER_BATCH: u64 = 1;
 
+// Number of ticks between checks of the tick rate against the wall clock
+pub const DRIFT_CHECK_INTERVAL_TICKS: u64 = DEFAULT_NUM_TICKS_PER_SECOND;
+
+// hashes_per_tick is never lowered below the configured value divided by this
+pub const MAX_HASHES_PER_TICK_REDUCTION: u64 = 2;
+
+// Falling behind by more than this many ticks' worth of time raises an alarm
+pub const MAX_TICK_DRIFT_TICKS: u32 = 4;
+
+/// Compares tick production against the wall clock and lowers hashes_per_tick when hashing
+/// falls behind, e.g. after a VM migration, so the node catches up instead of skipping
+/// slots. It never hashes more than the configured hashes_per_tick, a node hashing at full
+/// speed keeps the cluster's tick rate.
+struct TickDriftMonitor {
+    target_tick_duration: Duration,
+    min_hashes_per_tick: u64,
+    max_hashes_per_tick: u64,
+    hashes_per_tick: u64,
+    start: Instant,
+    num_ticks: u64,
+    window_start: Instant,
+    window_ticks: u64,
+}
+
+/// `Duration::as_secs_f64`, which needs a newer compiler than CI pins
+fn as_secs_f64(d: &Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+impl TickDriftMonitor {
+    fn new(target_tick_duration: Duration, hashes_per_tick: u64, now: Instant) -> Self {
+        Self {
+            target_tick_duration,
+            min_hashes_per_tick: (hashes_per_tick / MAX_HASHES_PER_TICK_REDUCTION).max(2),
+            max_hashes_per_tick: hashes_per_tick,
+            hashes_per_tick,
+            start: now,
+            num_ticks: 0,
+            window_start: now,
+            window_ticks: 0,
+        }
+    }
+
+    /// How far ticks lag behind the wall clock in seconds, negative when ahead of it
+    fn drift(&self, now: Instant) -> f64 {
+        let expected = as_secs_f64(&self.target_tick_duration) * self.num_ticks as f64;
+        as_secs_f64(&(now - self.start)) - expected
+    }
+
+    /// Record a tick produced at `now`. Returns the hashes_per_tick to use from now on
+    /// whenever it changes.
+    fn tick(&mut self, now: Instant) -> Option<u64> {
+        self.num_ticks += 1;
+        self.window_ticks += 1;
+        if self.window_ticks < DRIFT_CHECK_INTERVAL_TICKS {
+            return None;
+        }
+
+        let window_secs = as_secs_f64(&(now - self.window_start));
+        let drift = self.drift(now);
+        let target_secs = as_secs_f64(&self.target_tick_duration);
+        let hashes_per_tick = if window_secs > 0.0 {
+            let hashes_per_sec = (self.window_ticks * self.hashes_per_tick) as f64 / window_secs;
+            // make up the drift over the next window
+            let next_tick_secs = target_secs - drift / DRIFT_CHECK_INTERVAL_TICKS as f64;
+            (hashes_per_sec * next_tick_secs)
+                .max(self.min_hashes_per_tick as f64)
+                .min(self.max_hashes_per_tick as f64) as u64
+        } else {
+            self.max_hashes_per_tick
+        };
+
+        datapoint_info!(
+            "poh_service-tick_drift",
+            ("drift_ms", (drift * 1000.0) as i64, i64),
+            ("hashes_per_tick", hashes_per_tick, i64)
+        );
+        if drift > target_secs * f64::from(MAX_TICK_DRIFT_TICKS) {
+            datapoint_warn!(
+                "poh_service-tick_drift_alarm",
+                ("drift_ms", (drift * 1000.0) as i64, i64),
+                ("hashes_per_tick", hashes_per_tick, i64)
+            );
+            println!(
+                "{}",
+                Warn(
+                    format!(
+                        "poh ticks are {:.0}ms behind the wall clock, hashes_per_tick {}",
+                        drift * 1000.0,
+                        hashes_per_tick
+                    ),
+                    module_path!().to_string()
+                )
+            );
+        }
+        if drift < 0.0 && hashes_per_tick == self.max_hashes_per_tick {
+            // running ahead at full length ticks, there's nothing to make up for later
+            self.start = now;
+            self.num_ticks = 0;
+        }
+        self.window_start = now;
+        self.window_ticks = 0;
+
+        if hashes_per_tick == self.hashes_per_tick {
+            None
+        } else {
+            self.hashes_per_tick = hashes_per_tick;
+            Some(hashes_per_tick)
+        }
+    }
+}
+
 impl PohService {
     pub fn new(
         poh_recorder: Arc<Mutex<PohRecorder>>,
@@ -40,7 +155,7 @@ impl PohService {
                     if let Some(cores) = core_affinity::get_core_ids() {
                         core_affinity::set_for_current(cores[0]);
                     }
-                    Self::tick_producer(poh_recorder, &poh_exit_);
+                    Self::tick_producer(poh_recorder, &poh_config, &poh_exit_);
                 }
                 poh_exit_.store(true, Ordering::Relaxed);
             })
@@ -54,18 +169,34 @@ impl PohService {
         poh_config: &PohConfig,
         poh_exit: &AtomicBool,
     ) {
+        let tick_pending = poh_recorder.lock().unwrap().tick_pending();
         while !poh_exit.load(Ordering::Relaxed) {
             sleep(poh_config.target_tick_duration);
-            poh_recorder.lock().unwrap().tick();
+            PohRecorder::lock_for_tick(&poh_recorder, &tick_pending).tick();
         }
     }
 
-    fn tick_producer(poh_recorder: Arc<Mutex<PohRecorder>>, poh_exit: &AtomicBool) {
-        let poh = poh_recorder.lock().unwrap().poh.clone();
+    fn tick_producer(
+        poh_recorder: Arc<Mutex<PohRecorder>>,
+        poh_config: &PohConfig,
+        poh_exit: &AtomicBool,
+    ) {
+        let (poh, tick_pending) = {
+            let poh_recorder = poh_recorder.lock().unwrap();
+            (poh_recorder.poh.clone(), poh_recorder.tick_pending())
+        };
+        let mut drift_monitor = TickDriftMonitor::new(
+            poh_config.target_tick_duration,
+            poh_config.hashes_per_tick.unwrap(),
+            Instant::now(),
+        );
         loop {
             if poh.lock().unwrap().hash(NUM_HASHES_PER_BATCH) {
                 // Lock PohRecorder only for the final hash...
-                poh_recorder.lock().unwrap().tick();
+                PohRecorder::lock_for_tick(&poh_recorder, &tick_pending).tick();
+                if let Some(hashes_per_tick) = drift_monitor.tick(Instant::now()) {
+                    poh.lock().unwrap().set_hashes_per_tick(hashes_per_tick);
+                }
                 if poh_exit.load(Ordering::Relaxed) {
                     break;
                 }
@@ -94,7 +225,6 @@ mod tests {
     use morgan_runtime::bank::Bank;
     use morgan_interface::hash::hash;
     use morgan_interface::pubkey::Pubkey;
-    use std::time::Duration;
 
     #[test]
     fn test_poh_service() {
@@ -198,4 +328,48 @@ mod tests {
         }
         Blocktree::destroy(&ledger_path).unwrap();
     }
+
+    #[test]
+    fn test_tick_drift_monitor() {
+        let target_tick_duration = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut now = start;
+        let mut monitor = TickDriftMonitor::new(target_tick_duration, 1000, start);
+
+        // on schedule, nothing to adjust
+        for _ in 0..DRIFT_CHECK_INTERVAL_TICKS {
+            now += target_tick_duration;
+            assert_eq!(monitor.tick(now), None);
+        }
+
+        // hashing at 80% speed makes ticks 25% too long and the node falls behind
+        for _ in 0..DRIFT_CHECK_INTERVAL_TICKS - 1 {
+            now += target_tick_duration * 5 / 4;
+            assert_eq!(monitor.tick(now), None);
+        }
+        now += target_tick_duration * 5 / 4;
+        let hashes_per_tick = monitor.tick(now).unwrap();
+        assert!(hashes_per_tick < 800);
+        assert!(monitor.drift(now) > 0.0);
+
+        // a long stall can't push hashes_per_tick below the bound
+        now += Duration::from_secs(10);
+        for _ in 0..DRIFT_CHECK_INTERVAL_TICKS - 1 {
+            monitor.tick(now);
+        }
+        now += target_tick_duration;
+        assert_eq!(
+            monitor.tick(now),
+            Some(1000 / MAX_HASHES_PER_TICK_REDUCTION)
+        );
+
+        // running fast never hashes more than configured, and the lead isn't banked
+        let mut monitor = TickDriftMonitor::new(target_tick_duration, 1000, start);
+        let mut now = start;
+        for _ in 0..DRIFT_CHECK_INTERVAL_TICKS {
+            now += target_tick_duration / 2;
+            assert_eq!(monitor.tick(now), None);
+        }
+        assert_eq!(monitor.num_ticks, 0);
+    }
 }
@@ -0,0 +1,52 @@
+//! The `weightedShuffle` module orders nodes randomly but by weight, heavier nodes tend to come
+//! first.  The order only depends on the weights and the seed, so every node that knows the same
+//! stakes computes the same order, see `ClusterInfo::shuffle_peers_and_index`.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use std::cmp;
+
+/// Indexes of `weights` in random order where each index comes before the others in proportion
+/// to its weight, a zero weight counts as one
+pub fn weighted_shuffle(weights: &[u64], seed: [u8; 32]) -> Vec<usize> {
+    let mut rng = ChaChaRng::from_seed(seed);
+    let total_weight: u64 = weights
+        .iter()
+        .fold(0u64, |acc, weight| acc.saturating_add(cmp::max(*weight, 1)));
+    let mut keys: Vec<_> = weights
+        .iter()
+        .enumerate()
+        .map(|(i, weight)| {
+            let key = (total_weight / cmp::max(*weight, 1))
+                .saturating_mul(rng.gen_range(1, u64::from(u16::max_value())));
+            (key, i)
+        })
+        .collect();
+    keys.sort();
+    keys.into_iter().map(|(_, i)| i).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_shuffle_deterministic() {
+        let weights = vec![50, 10, 0, 10_000, 1];
+        let shuffle = weighted_shuffle(&weights, [7u8; 32]);
+        assert_eq!(shuffle, weighted_shuffle(&weights, [7u8; 32]));
+
+        let mut sorted = shuffle.clone();
+        sorted.sort();
+        assert_eq!(sorted, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_weighted_shuffle_by_weight() {
+        let weights = vec![1, 1, 1, 1_000_000];
+        let heaviest_first = (0..100u8)
+            .filter(|i| weighted_shuffle(&weights, [*i; 32])[0] == 3)
+            .count();
+        assert!(heaviest_first > 90);
+    }
+}
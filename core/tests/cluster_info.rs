@@ -71,10 +71,24 @@ fn run_simulation(stakes: &[u64], fanout: usize) {
     // create some "blobs".
     let blobs: Vec<(_, _)> = (0..100).into_par_iter().map(|i| (i as i32, true)).collect();
 
+    // every node lays out the same tree, the one of a blob with `seed`
+    let seed = [1u8; 32];
+    let (peers, peer_stakes) = cluster_info.sorted_retransmit_peers_and_stakes(Some(&staked_nodes));
+    let (_, shuffled_peers) =
+        ClusterInfo::shuffle_peers_and_index(&leader_info.id, &peers, &peer_stakes, seed);
+
     // pretend to broadcast from leader - cluster_info::create_broadcast_orders
-    let mut broadcast_table = cluster_info.sorted_tvu_peers(Some(&staked_nodes));
-    broadcast_table.truncate(fanout);
-    let orders = ClusterInfo::create_broadcast_orders(false, &blobs, &broadcast_table);
+    // rotate the blobs over layer 1 like their own trees would
+    let layer_1: Vec<_> = shuffled_peers.iter().take(fanout).cloned().collect();
+    let orders = ClusterInfo::create_broadcast_orders(false, &blobs, |(i, _)| {
+        layer_1
+            .iter()
+            .cycle()
+            .skip(*i as usize % layer_1.len())
+            .take(layer_1.len())
+            .cloned()
+            .collect()
+    });
 
     // send blobs to layer 1 nodes
     orders.iter().for_each(|(b, vc)| {
@@ -105,11 +119,12 @@ fn run_simulation(stakes: &[u64], fanout: usize) {
                 );
                 cluster.gossip.set_self(&*id);
                 if !mapped_peers.contains_key(id) {
-                    let (neighbors, children) = compute_retransmit_peers(
-                        Some(&staked_nodes),
-                        &Arc::new(RwLock::new(cluster.clone())),
-                        fanout,
-                    );
+                    let (peers, peer_stakes) =
+                        cluster.sorted_retransmit_peers_and_stakes(Some(&staked_nodes));
+                    let (my_index, shuffled_peers) =
+                        ClusterInfo::shuffle_peers_and_index(id, &peers, &peer_stakes, seed);
+                    let (neighbors, children) =
+                        compute_retransmit_peers(fanout, my_index, &shuffled_peers);
                     let vec_children: Vec<_> = children
                         .iter()
                         .map(|p| {
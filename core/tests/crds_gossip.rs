@@ -79,7 +79,6 @@ fn ring_network_create(num: usize) -> Network {
                 .crds
                 .lookup(&CrdsValueLabel::ContactInfo(start_id))
                 .unwrap()
-                .clone()
         };
         let end = network.get_mut(&keys[(k + 1) % keys.len()]).unwrap();
         end.lock().unwrap().crds.insert(start_info, 0).unwrap();
@@ -292,7 +291,7 @@ fn network_run_pull(
         }
         let total: usize = network_values
             .par_iter()
-            .map(|v| v.lock().unwrap().crds.table.len())
+            .map(|v| v.lock().unwrap().crds.len())
             .sum();
         convergance = total as f64 / ((num * num) as f64);
         if convergance > max_convergance {
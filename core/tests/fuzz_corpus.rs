@@ -0,0 +1,78 @@
+//! Replays the seed corpora of the fuzz targets under `fuzz/`, so the inputs that once
+//! panicked a decoder keep being checked without a nightly toolchain.
+
+use morgan::packet::{deserialize_packets_in_blob, Blob, Meta, Packet, Packets, BLOB_SIZE};
+use morgan::propagationValue::CrdsValue;
+use morgan::signatureVerify;
+use morgan_budget_api::budget_instruction::BudgetInstruction;
+use morgan_exchange_api::exchange_instruction::ExchangeInstruction;
+use morgan_interface::instruction_processor_utils::limited_deserialize;
+use morgan_interface::packet::PACKET_DATA_SIZE;
+use morgan_interface::signature::Signable;
+use morgan_interface::system_instruction::SystemInstruction;
+use std::cmp;
+use std::fs;
+use std::path::PathBuf;
+
+fn corpus(target: &str) -> Vec<Vec<u8>> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("../fuzz/corpus")
+        .join(target);
+    let inputs: Vec<_> = fs::read_dir(&dir)
+        .unwrap_or_else(|err| panic!("no corpus at {:?}: {:?}", dir, err))
+        .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+        .collect();
+    assert!(!inputs.is_empty());
+    inputs
+}
+
+#[test]
+fn test_packet_corpus() {
+    let serialized_meta_size = bincode::serialized_size(&Meta::default()).unwrap() as usize;
+    for data in corpus("packet") {
+        let mut packet = Packet::default();
+        let len = cmp::min(data.len(), PACKET_DATA_SIZE);
+        packet.data[..len].copy_from_slice(&data[..len]);
+        packet.meta.size = len;
+        let _ = signatureVerify::get_packet_offsets(&packet, 0);
+        assert_eq!(
+            signatureVerify::ed25519_verify_cpu(&[Packets::new(vec![packet])]),
+            vec![vec![0u8]]
+        );
+
+        let _ = deserialize_packets_in_blob(
+            &data,
+            serialized_meta_size + PACKET_DATA_SIZE,
+            serialized_meta_size,
+        );
+
+        let mut blob = Blob::default();
+        let len = cmp::min(data.len(), BLOB_SIZE);
+        blob.data[..len].copy_from_slice(&data[..len]);
+        blob.meta.size = len;
+        assert!(!blob.verify());
+    }
+}
+
+#[test]
+fn test_gossip_corpus() {
+    for data in corpus("gossip") {
+        let values: bincode::Result<Vec<CrdsValue>> =
+            bincode::config().limit(BLOB_SIZE as u64).deserialize(&data);
+        let value: bincode::Result<CrdsValue> =
+            bincode::config().limit(BLOB_SIZE as u64).deserialize(&data);
+        for value in values.into_iter().flatten().chain(value) {
+            let _ = (value.label(), value.wallclock());
+            assert!(!value.verify());
+        }
+    }
+}
+
+#[test]
+fn test_instruction_corpus() {
+    for data in corpus("instruction") {
+        let _ = limited_deserialize::<SystemInstruction>(&data);
+        let _ = limited_deserialize::<BudgetInstruction>(&data);
+        let _ = limited_deserialize::<ExchangeInstruction>(&data);
+    }
+}
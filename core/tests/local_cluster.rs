@@ -4,13 +4,19 @@ use crate::morgan::blockBufferPool::Blocktree;
 use hashbrown::HashSet;
 use log::*;
 use morgan::cluster::Cluster;
+use morgan::clusterMessage::FULLNODE_PORT_RANGE;
 use morgan::clusterTests;
 use morgan::gossipService::discover_cluster;
 use morgan::localCluster::{ClusterConfig, LocalCluster};
 use morgan::verifier::ValidatorConfig;
+use morgan_client::thin_client::create_client;
 use morgan_runtime::epoch_schedule::{EpochSchedule, MINIMUM_SLOT_LENGTH};
+use morgan_interface::client::SyncClient;
 use morgan_interface::poh_config::PohConfig;
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::{Keypair, KeypairUtil};
 use morgan_interface::timing;
+use morgan_tokenbot::drone::{request_airdrop_transaction, request_reputation_airdrop_transaction};
 use std::time::Duration;
 use morgan_helper::logHelper::*;
 
@@ -116,9 +122,7 @@ fn test_leader_failure_4() {
 fn test_two_unbalanced_stakes() {
     morgan_logger::setup();
     let mut validator_config = ValidatorConfig::default();
-    let num_ticks_per_second = 100;
     let num_ticks_per_slot = 10;
-    let num_slots_per_epoch = MINIMUM_SLOT_LENGTH as u64;
 
     validator_config.rpc_config.enable_fullnode_exit = true;
     let mut cluster = LocalCluster::new(&ClusterConfig {
@@ -126,16 +130,14 @@ fn test_two_unbalanced_stakes() {
         cluster_difs: 1_000_000,
         validator_config: validator_config.clone(),
         ticks_per_slot: num_ticks_per_slot,
-        slots_per_epoch: num_slots_per_epoch,
-        poh_config: PohConfig::new_sleep(Duration::from_millis(1000 / num_ticks_per_second)),
-        ..ClusterConfig::default()
+        ..ClusterConfig::fast_epochs().with_simulated_time(10)
     });
 
     clusterTests::sleep_n_epochs(
         10.0,
         &cluster.genesis_block.poh_config,
         num_ticks_per_slot,
-        num_slots_per_epoch,
+        cluster.genesis_block.slots_per_epoch,
     );
     cluster.close_preserve_ledgers();
     let leader_pubkey = cluster.entry_point_info.id;
@@ -143,6 +145,37 @@ fn test_two_unbalanced_stakes() {
     clusterTests::verify_ledger_ticks(&leader_ledger, num_ticks_per_slot as usize);
 }
 
+#[test]
+fn test_fast_epochs_with_simulated_time() {
+    morgan_logger::setup();
+    let mut validator_config = ValidatorConfig::default();
+    validator_config.rpc_config.enable_fullnode_exit = true;
+    let config = ClusterConfig {
+        node_stakes: vec![100],
+        cluster_difs: 10_000,
+        validator_config,
+        ..ClusterConfig::fast_epochs().with_simulated_time(10)
+    };
+    let mut cluster = LocalCluster::new(&config);
+    // about 2.5 seconds per epoch, with an epoch to spare for startup
+    clusterTests::sleep_n_epochs(
+        4.0,
+        &cluster.genesis_block.poh_config,
+        config.ticks_per_slot,
+        config.slots_per_epoch,
+    );
+    cluster.close_preserve_ledgers();
+
+    // the lone leader produced every slot into the third epoch
+    let leader_pubkey = cluster.entry_point_info.id;
+    let leader_ledger =
+        Blocktree::open(&cluster.fullnode_infos[&leader_pubkey].ledger_path).unwrap();
+    assert!(leader_ledger
+        .meta(2 * config.slots_per_epoch)
+        .unwrap()
+        .is_some());
+}
+
 #[test]
 #[ignore]
 fn test_forwarding() {
@@ -212,6 +245,73 @@ fn test_listener_startup() {
     assert_eq!(cluster_nodes.len(), 4);
 }
 
+#[test]
+fn test_embedded_drone() {
+    morgan_logger::setup();
+    let config = ClusterConfig {
+        node_stakes: vec![100; 1],
+        cluster_difs: 1_000,
+        embedded_drone: true,
+        ..ClusterConfig::default()
+    };
+    let cluster = LocalCluster::new(&config);
+    let drone_addr = cluster.entry_point_info.valid_drone_addr().unwrap();
+    let (cluster_nodes, _) = discover_cluster(&cluster.entry_point_info.gossip, 1).unwrap();
+    assert_eq!(cluster_nodes[0].drone, drone_addr);
+
+    let client = create_client(
+        cluster.entry_point_info.client_facing_addr(),
+        FULLNODE_PORT_RANGE,
+    );
+    let (blockhash, _fee_calculator) = client.get_recent_blockhash().unwrap();
+    let to = Pubkey::new_rand();
+    let mut transaction = request_airdrop_transaction(&drone_addr, &to, 42, blockhash).unwrap();
+    client
+        .retry_transfer(&cluster.funding_keypair, &mut transaction, 5)
+        .unwrap();
+    assert_eq!(client.poll_get_balance(&to).unwrap(), 42);
+}
+
+#[test]
+fn test_embedded_drone_reputation() {
+    morgan_logger::setup();
+    let config = ClusterConfig {
+        node_stakes: vec![100; 1],
+        cluster_difs: 1_000,
+        embedded_drone: true,
+        ..ClusterConfig::default()
+    };
+    let cluster = LocalCluster::new(&config);
+    let drone_addr = cluster.entry_point_info.valid_drone_addr().unwrap();
+
+    let client = create_client(
+        cluster.entry_point_info.client_facing_addr(),
+        FULLNODE_PORT_RANGE,
+    );
+    let (blockhash, _fee_calculator) = client.get_recent_blockhash().unwrap();
+    let from = Keypair::new();
+    let mut transaction =
+        request_reputation_airdrop_transaction(&drone_addr, &from.pubkey(), 42, blockhash).unwrap();
+    client
+        .retry_transfer(&cluster.funding_keypair, &mut transaction, 5)
+        .unwrap();
+    assert_eq!(
+        client.wait_for_reputation(&from.pubkey(), Some(42)),
+        Some(42)
+    );
+
+    // Fund both sides so the reputation transfer can pay its fee and lands in a live account
+    let to = Pubkey::new_rand();
+    client
+        .transfer(10, &cluster.funding_keypair, &from.pubkey())
+        .unwrap();
+    client.transfer(1, &cluster.funding_keypair, &to).unwrap();
+
+    client.transfer_reputations(40, &from, &to).unwrap();
+    assert_eq!(client.wait_for_reputation(&to, Some(40)), Some(40));
+    assert_eq!(client.get_reputation(&from.pubkey()).unwrap(), 2);
+}
+
 #[test]
 #[ignore]
 fn test_repairman_catchup() {
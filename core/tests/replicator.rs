@@ -12,6 +12,7 @@ use morgan::gossipService::discover_cluster;
 use morgan::localCluster::{ClusterConfig, LocalCluster};
 use morgan::cloner::Replicator;
 use morgan::cloner::ReplicatorRequest;
+use morgan::clusterTests;
 use morgan::storageStage::STORAGE_ROTATE_TEST_COUNT;
 use morgan::streamer::blob_receiver;
 use morgan::verifier::ValidatorConfig;
@@ -306,3 +307,42 @@ fn test_account_setup() {
         );
     });
 }
+
+#[test]
+fn test_replicator_storage_rewards() {
+    morgan_logger::setup();
+    let num_nodes = 1;
+    let num_replicators = 2;
+    let mut validator_config = ValidatorConfig::default();
+    validator_config.storage_rotate_count = STORAGE_ROTATE_TEST_COUNT;
+    let config = ClusterConfig {
+        validator_config,
+        num_replicators,
+        node_stakes: vec![100; num_nodes],
+        cluster_difs: 10_000,
+        ..ClusterConfig::default()
+    };
+    let cluster = LocalCluster::new(&config);
+
+    let _ = discover_cluster(
+        &cluster.entry_point_info.gossip,
+        num_nodes + num_replicators,
+    )
+    .unwrap();
+    let validator_storage_pubkeys: Vec<_> = cluster
+        .fullnode_infos
+        .values()
+        .map(|info| info.storage_keypair.pubkey())
+        .collect();
+    let replicator_storage_pubkeys: Vec<_> = cluster
+        .replicator_infos
+        .values()
+        .map(|info| info.replicator_storage_pubkey)
+        .collect();
+    clusterTests::claim_and_verify_storage_rewards(
+        &cluster.entry_point_info,
+        &cluster.funding_keypair,
+        &validator_storage_pubkeys,
+        &replicator_storage_pubkeys,
+    );
+}
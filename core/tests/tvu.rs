@@ -3,18 +3,21 @@ extern crate morgan;
 
 use log::*;
 use morgan::treasuryStage::create_test_recorder;
-use morgan::blockBufferPool::{create_new_tmp_ledger, Blocktree};
+use morgan::blockBufferPool::{create_new_tmp_ledger, Blocktree, BlocktreeConfig};
 use morgan::clusterMessage::{ClusterInfo, Node};
+use morgan::ClusterVoteMessageListener::VoteTracker;
 use morgan::entryInfo::next_entry_mut;
 use morgan::entryInfo::EntrySlice;
 use morgan::genesisUtils::{create_genesis_block_with_leader, GenesisBlockInfo};
 use morgan::gossipService::GossipService;
 use morgan::packet::index_blobs;
+use morgan::repeatStage::DEFAULT_LEADER_WARMUP_TICKS;
 use morgan::rpcSubscriptions::RpcSubscriptions;
 use morgan::service::Service;
 use morgan::storageStage::StorageState;
 use morgan::storageStage::STORAGE_ROTATE_TEST_COUNT;
 use morgan::streamer;
+use morgan::transactionTrace::TransactionTraces;
 use morgan::transactionVerifyCentre::{Sockets, Tvu};
 use morgan::verifier;
 use morgan_runtime::epoch_schedule::MINIMUM_SLOT_LENGTH;
@@ -40,7 +43,8 @@ fn new_gossip(
 #[test]
 fn test_replay() {
     morgan_logger::setup();
-    let leader = Node::new_localhost();
+    let leader_keypair = Keypair::new();
+    let leader = Node::new_localhost_with_pubkey(&leader_keypair.pubkey());
     let target1_keypair = Keypair::new();
     let target1 = Node::new_localhost_with_pubkey(&target1_keypair.pubkey());
     let target2 = Node::new_localhost();
@@ -95,7 +99,15 @@ fn test_replay() {
         completed_slots_receiver,
         leader_schedule_cache,
         _,
-    ) = verifier::new_banks_from_blocktree(&blocktree_path, None);
+    ) = verifier::new_banks_from_blocktree(
+        &blocktree_path,
+        None,
+        None,
+        None,
+        BlocktreeConfig::default(),
+        None,
+        None,
+    );
     let working_bank = bank_forks.working_bank();
     assert_eq!(
         working_bank.get_balance(&mint_keypair.pubkey()),
@@ -127,6 +139,7 @@ fn test_replay() {
                     repair: target1.sockets.repair,
                     retransmit: target1.sockets.retransmit,
                     fetch: target1.sockets.tvu,
+                    direct_stream: None,
                 }
             },
             blocktree,
@@ -140,6 +153,9 @@ fn test_replay() {
             &exit,
             &morgan_interface::hash::Hash::default(),
             completed_slots_receiver,
+            &Arc::new(RwLock::new(VoteTracker::default())),
+            &Arc::new(TransactionTraces::default()),
+            DEFAULT_LEADER_WARMUP_TICKS,
         );
 
         let mut mint_ref_balance = mint_balance;
@@ -170,9 +186,11 @@ fn test_replay() {
             let blobs = entries.to_shared_blobs();
             index_blobs(&blobs, &leader.info.id, blob_idx, 1, 0);
             blob_idx += blobs.len() as u64;
-            blobs
-                .iter()
-                .for_each(|b| b.write().unwrap().meta.set_addr(&tvu_addr));
+            blobs.iter().for_each(|b| {
+                let mut b = b.write().unwrap();
+                b.sign(&leader_keypair);
+                b.meta.set_addr(&tvu_addr);
+            });
             msgs.extend(blobs.into_iter());
         }
 
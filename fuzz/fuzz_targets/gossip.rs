@@ -0,0 +1,20 @@
+//! Feeds arbitrary bytes to the gossip value decoder, with the same size limit
+//! `ClusterInfo` puts on requests it receives.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use morgan::packet::BLOB_SIZE;
+use morgan::propagationValue::CrdsValue;
+use morgan_interface::signature::Signable;
+
+fuzz_target!(|data: &[u8]| {
+    let values: bincode::Result<Vec<CrdsValue>> =
+        bincode::config().limit(BLOB_SIZE as u64).deserialize(data);
+    let value: bincode::Result<CrdsValue> =
+        bincode::config().limit(BLOB_SIZE as u64).deserialize(data);
+    for value in values.into_iter().flatten().chain(value) {
+        let _ = (value.label(), value.wallclock());
+        value.verify();
+    }
+});
@@ -0,0 +1,16 @@
+//! Feeds arbitrary bytes to the instruction decoders of the system, budget and exchange
+//! programs, which take their data from any transaction.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use morgan_budget_api::budget_instruction::BudgetInstruction;
+use morgan_exchange_api::exchange_instruction::ExchangeInstruction;
+use morgan_interface::instruction_processor_utils::limited_deserialize;
+use morgan_interface::system_instruction::SystemInstruction;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = limited_deserialize::<SystemInstruction>(data);
+    let _ = limited_deserialize::<BudgetInstruction>(data);
+    let _ = limited_deserialize::<ExchangeInstruction>(data);
+});
@@ -0,0 +1,34 @@
+//! Feeds arbitrary bytes to sigverify's transaction offsets, the blob packet decoder and the
+//! blob header accessors, which all see packets straight off the wire.
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use morgan::packet::{deserialize_packets_in_blob, Blob, Meta, Packet, Packets, BLOB_SIZE};
+use morgan::signatureVerify;
+use morgan_interface::packet::PACKET_DATA_SIZE;
+use std::cmp;
+
+fuzz_target!(|data: &[u8]| {
+    let mut packet = Packet::default();
+    let len = cmp::min(data.len(), PACKET_DATA_SIZE);
+    packet.data[..len].copy_from_slice(&data[..len]);
+    packet.meta.size = len;
+    let _ = signatureVerify::get_packet_offsets(&packet, 0);
+    signatureVerify::ed25519_verify_cpu(&[Packets::new(vec![packet])]);
+
+    let serialized_meta_size = bincode::serialized_size(&Meta::default()).unwrap() as usize;
+    let _ = deserialize_packets_in_blob(
+        data,
+        serialized_meta_size + PACKET_DATA_SIZE,
+        serialized_meta_size,
+    );
+
+    let mut blob = Blob::default();
+    let len = cmp::min(data.len(), BLOB_SIZE);
+    blob.data[..len].copy_from_slice(&data[..len]);
+    blob.meta.size = len;
+    let _ = (blob.slot(), blob.index(), blob.id(), blob.is_coding());
+    let _ = &blob.data()[..blob.size()];
+    blob.verify();
+});
@@ -11,6 +11,8 @@ extern crate morgan_token_controller;
 extern crate morgan_config_controller;
 #[macro_use]
 extern crate morgan_exchange_controller;
+#[macro_use]
+extern crate morgan_address_lookup_table_controller;
 
 use clap::{crate_description, crate_name, crate_version, value_t_or_exit, App, Arg};
 use morgan::blockBufferPool::create_new_ledger;
@@ -19,6 +21,7 @@ use morgan_interface::fee_calculator::FeeCalculator;
 use morgan_interface::genesis_block::GenesisBlock;
 use morgan_interface::hash::{hash, Hash};
 use morgan_interface::poh_config::PohConfig;
+use morgan_interface::rent_calculator::RentCalculator;
 use morgan_interface::signature::{read_keypair, KeypairUtil};
 use morgan_interface::system_program;
 use morgan_interface::timing;
@@ -34,6 +37,12 @@ fn main() -> Result<(), Box<dyn error::Error>> {
     let default_bootstrap_leader_difs = &BOOTSTRAP_LEADER_DIFS.to_string();
     let default_difs_per_signature =
         &FeeCalculator::default().difs_per_signature.to_string();
+    let default_target_signatures_per_slot =
+        &FeeCalculator::default().target_signatures_per_slot.to_string();
+    let default_max_difs_per_signature = &u64::max_value().to_string();
+    let default_difs_per_byte_year = &RentCalculator::default().difs_per_byte_year.to_string();
+    let default_rent_exemption_threshold =
+        &RentCalculator::default().exemption_threshold.to_string();
     let default_target_tick_duration =
         &timing::duration_as_ms(&PohConfig::default().target_tick_duration).to_string();
     let default_ticks_per_slot = &timing::DEFAULT_TICKS_PER_SLOT.to_string();
@@ -121,6 +130,52 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 .default_value(default_difs_per_signature)
                 .help("Number of difs the cluster will charge for signature verification"),
         )
+        .arg(
+            Arg::with_name("target_signatures_per_slot")
+                .long("target-signatures-per-slot")
+                .value_name("NUM")
+                .takes_value(true)
+                .default_value(default_target_signatures_per_slot)
+                .help(
+                    "Signatures per slot the fee is adjusted towards each slot, \
+                     0 keeps --difs-per-signature fixed",
+                ),
+        )
+        .arg(
+            Arg::with_name("min_difs_per_signature")
+                .long("min-difs-per-signature")
+                .value_name("DIFS")
+                .takes_value(true)
+                .default_value("0")
+                .help("Lowest fee per signature the adjustment can reach"),
+        )
+        .arg(
+            Arg::with_name("max_difs_per_signature")
+                .long("max-difs-per-signature")
+                .value_name("DIFS")
+                .takes_value(true)
+                .default_value(default_max_difs_per_signature)
+                .help("Highest fee per signature the adjustment can reach"),
+        )
+        .arg(
+            Arg::with_name("difs_per_byte_year")
+                .long("difs-per-byte-year")
+                .value_name("DIFS")
+                .takes_value(true)
+                .default_value(default_difs_per_byte_year)
+                .help(
+                    "Rent a byte of account storage costs a year, \
+                     charged every epoch to the accounts below the rent-exempt balance",
+                ),
+        )
+        .arg(
+            Arg::with_name("rent_exemption_threshold")
+                .long("rent-exemption-threshold")
+                .value_name("YEARS")
+                .takes_value(true)
+                .default_value(default_rent_exemption_threshold)
+                .help("Years of rent an account must hold to be exempt from paying it"),
+        )
         .arg(
             Arg::with_name("target_tick_duration")
                 .long("target-tick-duration")
@@ -219,12 +274,21 @@ fn main() -> Result<(), Box<dyn error::Error>> {
             morgan_token_controller!(),
             morgan_config_controller!(),
             morgan_exchange_controller!(),
+            morgan_address_lookup_table_controller!(),
         ],
     );
     genesis_block.add_storage_controller(&bootstrap_storage_keypair.pubkey());
 
-    genesis_block.fee_calculator.difs_per_signature =
-        value_t_or_exit!(matches, "difs_per_signature", u64);
+    genesis_block.fee_calculator = FeeCalculator::new_congestion_priced(
+        value_t_or_exit!(matches, "difs_per_signature", u64),
+        value_t_or_exit!(matches, "target_signatures_per_slot", u64),
+        value_t_or_exit!(matches, "min_difs_per_signature", u64),
+        value_t_or_exit!(matches, "max_difs_per_signature", u64),
+    );
+    genesis_block.rent_calculator = RentCalculator::new(
+        value_t_or_exit!(matches, "difs_per_byte_year", u64),
+        value_t_or_exit!(matches, "rent_exemption_threshold", f64),
+    );
     genesis_block.ticks_per_slot = value_t_or_exit!(matches, "ticks_per_slot", u64);
     genesis_block.slots_per_epoch = value_t_or_exit!(matches, "slots_per_epoch", u64);
     genesis_block.poh_config.target_tick_duration =
@@ -311,6 +375,10 @@ mod tests {
                 "Exchange11111111111111111111111111111111111",
                 morgan_exchange_api::id(),
             ),
+            (
+                "AddressLookupTab1e1111111111111111111111111",
+                morgan_address_lookup_table_api::id(),
+            ),
         ];
         assert!(ids.iter().all(|(name, id)| *name == id.to_string()));
     }
@@ -329,6 +397,7 @@ mod tests {
             morgan_stake_api::id(),
             morgan_config_api::id(),
             morgan_exchange_api::id(),
+            morgan_address_lookup_table_api::id(),
         ];
         assert!(ids.into_iter().all(move |id| unique.insert(id)));
     }
@@ -1,9 +1,74 @@
 use ansi_term::Color::{Green, Red, Yellow, Blue, Cyan, White};
 use log::*;
 use chrono::prelude::*;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+thread_local! {
+    static SPANS: RefCell<Vec<String>> = RefCell::new(vec![]);
+}
+
+/// Correlation span entered on the current thread; every line formatted by this
+/// module while the guard is alive is tagged with it (e.g. `[slot=5 sig=3xd1..]`),
+/// so a single slot or transaction can be grepped end to end across stages
+pub struct SpanGuard {
+    entered: bool,
+    // spans are tracked per thread, so the guard must be dropped where it was made
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for SpanGuard {
+    fn drop(&mut self) {
+        if self.entered {
+            SPANS.with(|spans| spans.borrow_mut().pop());
+        }
+    }
+}
+
+/// Enter a `key=value` span; re-entering the innermost span is a no-op so that
+/// nested callers of the same slot don't repeat it
+pub fn enter_span<T: Display>(key: &str, value: T) -> SpanGuard {
+    let span = format!("{}={}", key, value);
+    let entered = SPANS.with(|spans| {
+        let mut spans = spans.borrow_mut();
+        if spans.last() == Some(&span) {
+            false
+        } else {
+            spans.push(span);
+            true
+        }
+    });
+    SpanGuard {
+        entered,
+        _not_send: PhantomData,
+    }
+}
+
+pub fn slot_span(slot: u64) -> SpanGuard {
+    enter_span("slot", slot)
+}
+
+pub fn signature_span<T: Display>(signature: T) -> SpanGuard {
+    enter_span("sig", signature)
+}
+
+/// The spans entered on this thread, outermost first, e.g. `slot=5 sig=3xd1..`
+pub fn current_span() -> String {
+    SPANS.with(|spans| spans.borrow().join(" "))
+}
+
+fn with_span(msg: String) -> String {
+    let span = current_span();
+    if span.is_empty() {
+        msg
+    } else {
+        format!("[{}] {}", span, msg)
+    }
+}
 
 pub fn Info(info: String) -> String {
-    return Green.bold().paint(format!("{}", info)).to_string();
+    return Green.bold().paint(with_span(info)).to_string();
 
 }
 
@@ -14,7 +79,7 @@ pub fn printLn(info: String, target: String) -> String {
         Cyan.bold().paint(format!("<")),
         White.bold().paint(format!("{}", local)),
         Green.bold().paint(format!("INFO")),
-        Green.bold().paint(format!("{}", info)),
+        Green.bold().paint(with_span(info)),
         Blue.bold().paint(format!("{}", target)),
         Cyan.bold().paint(format!(">"))
     ).to_string();
@@ -27,7 +92,7 @@ pub fn Error(err: String, target: String) -> String {
         Cyan.bold().paint(format!("<")),
         White.bold().paint(format!("{}", local)),
         Red.bold().paint(format!("ERROR")),
-        Red.bold().paint(format!("{}", err)),
+        Red.bold().paint(with_span(err)),
         Blue.bold().paint(format!("{}", target)),
         Cyan.bold().paint(format!(">"))
     ).to_string();
@@ -41,7 +106,7 @@ pub fn Warn(warn: String, target: String) -> String {
         Cyan.bold().paint(format!("<")),
         White.bold().paint(format!("{}", local)),
         Yellow.bold().paint(format!("WARN")),
-        Yellow.bold().paint(format!("{}", warn)),
+        Yellow.bold().paint(with_span(warn)),
         Blue.bold().paint(format!("{}", target)),
         Cyan.bold().paint(format!(">"))
     ).to_string();
@@ -54,7 +119,7 @@ pub fn Debug(debug: String, target: String) -> String {
         Cyan.bold().paint(format!("<")),
         White.bold().paint(format!("{}", local)),
         Yellow.bold().paint(format!("WARN")),
-        Yellow.bold().paint(format!("{}", debug)),
+        Yellow.bold().paint(with_span(debug)),
         Blue.bold().paint(format!("{}", target)),
         Cyan.bold().paint(format!(">"))
     ).to_string();
@@ -67,8 +132,32 @@ pub fn Trace(trace: String, target: String) -> String {
         Cyan.bold().paint(format!("<")),
         White.bold().paint(format!("{}", local)),
         Yellow.bold().paint(format!("WARN")),
-        Yellow.bold().paint(format!("{}", trace)),
+        Yellow.bold().paint(with_span(trace)),
         Blue.bold().paint(format!("{}", target)),
         Cyan.bold().paint(format!(">"))
     ).to_string();
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spans() {
+        assert_eq!(current_span(), "");
+        {
+            let _slot = slot_span(5);
+            assert_eq!(current_span(), "slot=5");
+            {
+                let _nested = slot_span(5);
+                let _sig = signature_span("abc");
+                assert_eq!(current_span(), "slot=5 sig=abc");
+                assert!(
+                    Warn("msg".to_string(), "target".to_string()).contains("[slot=5 sig=abc] msg")
+                );
+            }
+            assert_eq!(current_span(), "slot=5");
+        }
+        assert_eq!(current_span(), "");
+        assert!(!Warn("msg".to_string(), "target".to_string()).contains("slot="));
+    }
+}
@@ -1,4 +1,5 @@
 use serde_derive::{Deserialize, Serialize};
+use morgan_config_api::config_schema::{config_payload, ConfigSchemaId, UPDATE_MANIFEST_SCHEMA_ID};
 use morgan_config_api::ConfigState;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Signable, Signature};
@@ -40,7 +41,7 @@ impl Signable for SignedUpdateManifest {
 
 impl SignedUpdateManifest {
     pub fn deserialize(account_pubkey: &Pubkey, input: &[u8]) -> Result<Self, Box<error::Error>> {
-        let mut manifest: SignedUpdateManifest = bincode::deserialize(input)?;
+        let mut manifest: SignedUpdateManifest = bincode::deserialize(config_payload(input))?;
         manifest.account_pubkey = *account_pubkey;
         if !manifest.verify() {
             Err(io::Error::new(
@@ -58,4 +59,8 @@ impl ConfigState for SignedUpdateManifest {
         //      bincode::serialized_size(&Self::default()).unwrap()
         256
     }
+
+    fn schema() -> Option<(ConfigSchemaId, u32)> {
+        Some((UPDATE_MANIFEST_SCHEMA_ID, 1))
+    }
 }
@@ -34,12 +34,24 @@ pub trait SyncClient {
     /// transfer completes or produces and error.
     fn transfer(&self, difs: u64, keypair: &Keypair, pubkey: &Pubkey) -> Result<Signature>;
 
+    /// Transfer reputations from `keypair` to `pubkey`, retrying until the
+    /// transfer completes or produces and error.
+    fn transfer_reputations(
+        &self,
+        reputations: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+    ) -> Result<Signature>;
+
     /// Get an account or None if not found.
     fn get_account_data(&self, pubkey: &Pubkey) -> Result<Option<Vec<u8>>>;
 
     /// Get account balance or 0 if not found.
     fn get_balance(&self, pubkey: &Pubkey) -> Result<u64>;
 
+    /// Get account reputations or 0 if not found.
+    fn get_reputation(&self, pubkey: &Pubkey) -> Result<u64>;
+
     /// Get signature status.
     fn get_signature_status(
         &self,
@@ -98,4 +110,13 @@ pub trait AsyncClient {
         pubkey: &Pubkey,
         recent_blockhash: Hash,
     ) -> io::Result<Signature>;
+
+    /// Attempt to transfer reputations from `keypair` to `pubkey`, but don't wait to confirm.
+    fn async_transfer_reputations(
+        &self,
+        reputations: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+        recent_blockhash: Hash,
+    ) -> io::Result<Signature>;
 }
@@ -1,18 +1,81 @@
 use crate::message::Message;
+use std::cmp;
+
+/// Under congestion pricing a slot twice the target raises the next slot's fee by 1/8th and an
+/// empty slot lowers it as much, proportionally in between
+pub const CONGESTION_ADJUSTMENT_DENOMINATOR: u64 = 8;
 
 #[derive(Serialize, Deserialize, Clone, Debug, Default)]
 #[serde(rename_all = "camelCase")]
 pub struct FeeCalculator {
     pub difs_per_signature: u64,
+
+    /// Signatures per slot congestion pricing steers towards, 0 keeps the fee fixed
+    pub target_signatures_per_slot: u64,
+
+    /// Bounds of `difs_per_signature` under congestion pricing
+    pub min_difs_per_signature: u64,
+    pub max_difs_per_signature: u64,
 }
 
 impl FeeCalculator {
     pub fn new(difs_per_signature: u64) -> Self {
         Self {
             difs_per_signature,
+            ..Self::default()
         }
     }
 
+    pub fn new_congestion_priced(
+        difs_per_signature: u64,
+        target_signatures_per_slot: u64,
+        min_difs_per_signature: u64,
+        max_difs_per_signature: u64,
+    ) -> Self {
+        Self {
+            difs_per_signature,
+            target_signatures_per_slot,
+            min_difs_per_signature,
+            max_difs_per_signature,
+        }
+    }
+
+    pub fn is_congestion_priced(&self) -> bool {
+        self.target_signatures_per_slot > 0
+    }
+
+    /// The fee calculator of a child slot, given how many signatures its parent slot
+    /// processed.  It only depends on the parent, so every node derives the same fee.
+    pub fn new_derived(&self, parent_signature_count: u64) -> Self {
+        let mut derived = self.clone();
+        if !self.is_congestion_priced() {
+            return derived;
+        }
+
+        let target = u128::from(self.target_signatures_per_slot);
+        let count = u128::from(parent_signature_count);
+        let difs = u128::from(self.difs_per_signature);
+        // a step of at least 1 dif so a fee of 0 can still rise
+        let step = |gap: u128| {
+            cmp::max(
+                1,
+                difs * gap / target / u128::from(CONGESTION_ADJUSTMENT_DENOMINATOR),
+            )
+        };
+        let difs = if count > target {
+            difs + step(count - target)
+        } else if count < target {
+            difs.saturating_sub(step(target - count))
+        } else {
+            difs
+        };
+
+        let difs = cmp::max(difs, u128::from(self.min_difs_per_signature));
+        let difs = cmp::min(difs, u128::from(self.max_difs_per_signature));
+        derived.difs_per_signature = difs as u64;
+        derived
+    }
+
     pub fn calculate_fee(&self, message: &Message) -> u64 {
         self.difs_per_signature * u64::from(message.header.num_required_signatures)
     }
@@ -46,4 +109,36 @@ mod tests {
         let message = Message::new(vec![ix0, ix1]);
         assert_eq!(FeeCalculator::new(2).calculate_fee(&message), 4);
     }
+
+    #[test]
+    fn test_fee_calculator_new_derived_fixed() {
+        let fee_calculator = FeeCalculator::new(5);
+        assert!(!fee_calculator.is_congestion_priced());
+        assert_eq!(fee_calculator.new_derived(0).difs_per_signature, 5);
+        assert_eq!(fee_calculator.new_derived(1_000_000).difs_per_signature, 5);
+    }
+
+    #[test]
+    fn test_fee_calculator_new_derived_congestion_priced() {
+        let fee_calculator = FeeCalculator::new_congestion_priced(800, 100, 10, 1000);
+        assert!(fee_calculator.is_congestion_priced());
+
+        // on target the fee holds
+        assert_eq!(fee_calculator.new_derived(100).difs_per_signature, 800);
+
+        // a full slot twice the target raises it by an eighth, an empty one lowers it as much
+        assert_eq!(fee_calculator.new_derived(200).difs_per_signature, 900);
+        assert_eq!(fee_calculator.new_derived(0).difs_per_signature, 700);
+        assert_eq!(fee_calculator.new_derived(150).difs_per_signature, 850);
+
+        // within the genesis bounds
+        assert_eq!(fee_calculator.new_derived(10_000).difs_per_signature, 1000);
+        let fee_calculator = FeeCalculator::new_congestion_priced(11, 100, 10, 1000);
+        assert_eq!(fee_calculator.new_derived(0).difs_per_signature, 10);
+
+        // a fee of 0 can still rise
+        let fee_calculator = FeeCalculator::new_congestion_priced(0, 100, 0, 1000);
+        assert_eq!(fee_calculator.new_derived(101).difs_per_signature, 1);
+        assert_eq!(fee_calculator.new_derived(u64::max_value()).difs_per_signature, 1);
+    }
 }
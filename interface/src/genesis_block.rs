@@ -3,8 +3,10 @@
 use crate::account::Account;
 use crate::fee_calculator::FeeCalculator;
 use crate::hash::{hash, Hash};
+use crate::inflation::Inflation;
 use crate::poh_config::PohConfig;
 use crate::pubkey::Pubkey;
+use crate::rent_calculator::RentCalculator;
 use crate::signature::{Keypair, KeypairUtil};
 use crate::system_program;
 use crate::timing::{DEFAULT_SLOTS_PER_EPOCH, DEFAULT_TICKS_PER_SLOT};
@@ -18,7 +20,9 @@ pub struct GenesisBlock {
     pub bootstrap_leader_pubkey: Pubkey,
     pub epoch_warmup: bool,
     pub fee_calculator: FeeCalculator,
+    pub inflation: Inflation,
     pub native_instruction_processors: Vec<(String, Pubkey)>,
+    pub rent_calculator: RentCalculator,
     pub slots_per_epoch: u64,
     pub stakers_slot_offset: u64,
     pub ticks_per_slot: u64,
@@ -52,7 +56,9 @@ impl GenesisBlock {
             bootstrap_leader_pubkey: *bootstrap_leader_pubkey, // TODO: leader_schedule to derive from actual stakes, instead ;)
             epoch_warmup: true,
             fee_calculator: FeeCalculator::default(),
+            inflation: Inflation::default(),
             native_instruction_processors: native_instruction_processors.to_vec(),
+            rent_calculator: RentCalculator::default(),
             slots_per_epoch: DEFAULT_SLOTS_PER_EPOCH,
             stakers_slot_offset: DEFAULT_SLOTS_PER_EPOCH,
             ticks_per_slot: DEFAULT_TICKS_PER_SLOT,
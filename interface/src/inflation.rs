@@ -0,0 +1,69 @@
+//! The `inflation` module holds the schedule of the yearly rate delegated stake earns.
+//! The difs are minted at every epoch boundary and split between the stake accounts and
+//! the vote accounts they're delegated to, see the bank's `rewards`.
+
+/// Yearly rate paid on delegated stake at genesis
+pub const DEFAULT_INITIAL: f64 = 0.15;
+/// Yearly rate the taper never takes the rate below
+pub const DEFAULT_TERMINAL: f64 = 0.015;
+/// How much lower the rate gets every year, until it reaches the terminal rate
+pub const DEFAULT_TAPER: f64 = 0.15;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub struct Inflation {
+    /// Yearly rate paid on delegated stake at genesis
+    pub initial: f64,
+    /// Yearly rate the taper never takes the rate below
+    pub terminal: f64,
+    /// Fraction the rate gets lower by every year, until it reaches the terminal rate
+    pub taper: f64,
+}
+
+impl Default for Inflation {
+    fn default() -> Self {
+        Self {
+            initial: DEFAULT_INITIAL,
+            terminal: DEFAULT_TERMINAL,
+            taper: DEFAULT_TAPER,
+        }
+    }
+}
+
+impl Inflation {
+    /// No rewards at all
+    pub fn new_disabled() -> Self {
+        Self {
+            initial: 0.0,
+            terminal: 0.0,
+            taper: 0.0,
+        }
+    }
+
+    /// The yearly rate paid on delegated stake `year` years after genesis
+    pub fn rate(&self, year: f64) -> f64 {
+        let tapered = self.initial * (1.0 - self.taper).powf(year.max(0.0));
+        tapered.max(self.terminal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflation_rate() {
+        let inflation = Inflation::default();
+        assert_eq!(inflation.rate(0.0), DEFAULT_INITIAL);
+
+        let mut last = inflation.rate(0.0);
+        for year in 1..100 {
+            let rate = inflation.rate(year as f64);
+            assert!(rate <= last);
+            assert!(rate >= DEFAULT_TERMINAL);
+            last = rate;
+        }
+        assert_eq!(last, DEFAULT_TERMINAL);
+
+        assert_eq!(Inflation::new_disabled().rate(10.0), 0.0);
+    }
+}
@@ -56,6 +56,15 @@ pub enum InstructionError {
     /// them returned by the Morgan runtime. A CustomError may be any type that is represented
     /// as or serialized to a u32 integer.
     CustomError(u32),
+
+    /// Program changed the difs of a credit-only account
+    CreditOnlyDifChange,
+
+    /// Program modified the data or program id of a credit-only account
+    CreditOnlyDataModified,
+
+    /// The instruction expected more accounts than it was given
+    NotEnoughAccountKeys,
 }
 
 impl InstructionError {
@@ -96,7 +105,8 @@ pub struct AccountMeta {
     pub pubkey: Pubkey,
     /// True if an Instruciton requires a Transaction signature matching `pubkey`.
     pub is_signer: bool,
-    /// True if the `pubkey` can be loaded as a credit-debit account.
+    /// True if the `pubkey` can be loaded as a credit-debit account. Credit-only accounts are
+    /// read-only, instructions may not modify them.
     pub is_debitable: bool,
 }
 
@@ -1,7 +1,9 @@
 use crate::account::KeyedAccount;
 use crate::instruction::InstructionError;
+use crate::packet::PACKET_DATA_SIZE;
 use crate::pubkey::Pubkey;
 use num_traits::FromPrimitive;
+use serde::de::DeserializeOwned;
 
 // All native programs export a symbol named process()
 pub const ENTRYPOINT: &str = "process";
@@ -51,6 +53,18 @@ macro_rules! morgan_program_id(
     )
 );
 
+/// Deserialize instruction data from an untrusted transaction.  Instruction data never
+/// exceeds a packet, so a forged length prefix can't make the decoder allocate more than that
+pub fn limited_deserialize<T>(data: &[u8]) -> Result<T, InstructionError>
+where
+    T: DeserializeOwned,
+{
+    bincode::config()
+        .limit(PACKET_DATA_SIZE as u64)
+        .deserialize(data)
+        .map_err(|_| InstructionError::InvalidInstructionData)
+}
+
 pub trait DecodeError<E> {
     fn decode_custom_error_to_enum(int: u32) -> Option<E>
     where
@@ -85,4 +99,21 @@ mod tests {
         let option: Option<TestEnum> = TestEnum::decode_custom_error_to_enum(3);
         assert_eq!(option, None);
     }
+
+    #[test]
+    fn test_limited_deserialize() {
+        let data = bincode::serialize(&vec![1u8; 8]).unwrap();
+        assert_eq!(limited_deserialize::<Vec<u8>>(&data), Ok(vec![1u8; 8]));
+        assert_eq!(
+            limited_deserialize::<Vec<u8>>(&data[..4]),
+            Err(InstructionError::InvalidInstructionData)
+        );
+
+        // a length prefix claiming more than a packet is rejected before allocating
+        let data = bincode::serialize(&(u64::max_value())).unwrap();
+        assert_eq!(
+            limited_deserialize::<Vec<u8>>(&data),
+            Err(InstructionError::InvalidInstructionData)
+        );
+    }
 }
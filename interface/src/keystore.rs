@@ -0,0 +1,707 @@
+//! The `keystore` module reads keypairs out of the key file formats `read_keypair` accepts
+//! besides the plain JSON array of keypair bytes:
+//!
+//! * an encrypted keystore, a JSON object holding the keypair sealed with ChaCha20-Poly1305
+//!   under a key derived from a passphrase with scrypt
+//! * a BIP39 seed phrase, whose seed, stretched with an optional passphrase, seeds the keypair;
+//!   its words and checksum are checked so a corrupt phrase can't stand in for another keypair
+//!
+//! Both are unlocked with the passphrase in `KEYPAIR_PASSPHRASE_ENV`, or one prompted for
+//! when stdin is a terminal.
+
+use crate::signature::{Keypair, KeypairUtil};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use ring::{aead, digest, pbkdf2};
+use solana_ed25519_dalek as ed25519_dalek;
+use std::env;
+use std::error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+/// Environment variable holding the passphrase of encrypted keystores and seed phrases
+pub const KEYPAIR_PASSPHRASE_ENV: &str = "MORGAN_KEYPAIR_PASSPHRASE";
+
+const KEYSTORE_VERSION: u8 = 1;
+const KDF_SCRYPT: &str = "scrypt";
+const CIPHER_CHACHA20_POLY1305: &str = "chacha20-poly1305";
+const SALT_LEN: usize = 32;
+const BIP39_ITERATIONS: u32 = 2048;
+/// The BIP39 English wordlist, one word per line in index order
+const BIP39_WORDLIST: &str = include_str!("bip39_english.txt");
+
+/// Bounds on the scrypt parameters of a keystore, which are read from the file, so a crafted
+/// one can't make unlocking allocate or run without limit
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 16;
+/// Bytes scrypt's `n` blocks of `128 * r` bytes may take
+const MAX_SCRYPT_MEMORY: usize = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ScryptParams {
+    /// log2 of the CPU/memory cost
+    pub log_n: u8,
+    /// Block size
+    pub r: u32,
+    /// Parallelization
+    pub p: u32,
+}
+
+impl Default for ScryptParams {
+    /// 32MB and a fraction of a second to unlock
+    fn default() -> Self {
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct KdfParams {
+    name: String,
+    #[serde(flatten)]
+    scrypt: ScryptParams,
+    /// Hex salt
+    salt: String,
+}
+
+/// An encrypted keystore file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Keystore {
+    version: u8,
+    /// Base58 pubkey of the sealed keypair, authenticated along with it
+    pubkey: String,
+    kdf: KdfParams,
+    cipher: String,
+    /// Hex nonce
+    nonce: String,
+    /// Hex keypair bytes sealed along with their tag
+    ciphertext: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyFileFormat {
+    /// JSON array of the keypair bytes
+    Json,
+    /// Encrypted keystore
+    Keystore,
+    /// BIP39 seed phrase
+    SeedPhrase,
+}
+
+impl KeyFileFormat {
+    /// The format of a key file holding `contents`, `None` if it's empty or none of them
+    pub fn of(contents: &str) -> Option<Self> {
+        match contents.trim_start().chars().next() {
+            Some('[') => Some(KeyFileFormat::Json),
+            Some('{') => Some(KeyFileFormat::Keystore),
+            Some(c) if c.is_ascii_lowercase() => Some(KeyFileFormat::SeedPhrase),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum KeystoreError {
+    /// Neither the environment nor a terminal provided a passphrase
+    NoPassphrase,
+    /// Wrong passphrase, or a keystore that was tampered with
+    Unlock,
+    /// A keystore this version can't read
+    Unsupported(String),
+    Malformed(String),
+    /// A seed phrase with unknown words or a bad checksum
+    InvalidSeedPhrase(String),
+    /// A key file in none of the formats
+    UnknownFormat,
+    Io(io::Error),
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeystoreError::NoPassphrase => write!(
+                f,
+                "keypair is locked, set {} or run from a terminal",
+                KEYPAIR_PASSPHRASE_ENV
+            ),
+            KeystoreError::Unlock => write!(f, "wrong passphrase or corrupt keystore"),
+            KeystoreError::Unsupported(what) => write!(f, "unsupported keystore {}", what),
+            KeystoreError::Malformed(what) => write!(f, "malformed keystore: {}", what),
+            KeystoreError::InvalidSeedPhrase(what) => write!(f, "invalid seed phrase: {}", what),
+            KeystoreError::UnknownFormat => write!(f, "unrecognized key file format"),
+            KeystoreError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl error::Error for KeystoreError {}
+
+impl From<io::Error> for KeystoreError {
+    fn from(err: io::Error) -> Self {
+        KeystoreError::Io(err)
+    }
+}
+
+/// Seal `keypair` into a keystore unlocked by `passphrase`, serialized as JSON
+pub fn encrypt_keypair(
+    keypair: &Keypair,
+    passphrase: &str,
+    params: ScryptParams,
+) -> Result<String, KeystoreError> {
+    let mut rng = OsRng::new().map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; 12];
+    rng.fill_bytes(&mut nonce);
+
+    let pubkey = keypair.pubkey().to_string();
+    let key = scrypt(passphrase.as_bytes(), &salt, &params)?;
+    let sealing_key =
+        aead::SealingKey::new(&aead::CHACHA20_POLY1305, &key).map_err(|_| KeystoreError::Unlock)?;
+    let tag_len = aead::CHACHA20_POLY1305.tag_len();
+    let mut in_out = keypair.to_bytes().to_vec();
+    in_out.resize(in_out.len() + tag_len, 0);
+    aead::seal_in_place(
+        &sealing_key,
+        &nonce,
+        pubkey.as_bytes(),
+        &mut in_out,
+        tag_len,
+    )
+    .map_err(|_| KeystoreError::Unlock)?;
+
+    let keystore = Keystore {
+        version: KEYSTORE_VERSION,
+        pubkey,
+        kdf: KdfParams {
+            name: KDF_SCRYPT.to_string(),
+            scrypt: params,
+            salt: hex::encode(salt),
+        },
+        cipher: CIPHER_CHACHA20_POLY1305.to_string(),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(in_out),
+    };
+    serde_json::to_string_pretty(&keystore).map_err(|err| KeystoreError::Malformed(err.to_string()))
+}
+
+/// Open the keystore serialized in `contents` with `passphrase`
+pub fn decrypt_keypair(contents: &str, passphrase: &str) -> Result<Keypair, KeystoreError> {
+    let keystore: Keystore =
+        serde_json::from_str(contents).map_err(|err| KeystoreError::Malformed(err.to_string()))?;
+    if keystore.version != KEYSTORE_VERSION {
+        return Err(KeystoreError::Unsupported(format!(
+            "version {}",
+            keystore.version
+        )));
+    }
+    if keystore.kdf.name != KDF_SCRYPT {
+        return Err(KeystoreError::Unsupported(keystore.kdf.name));
+    }
+    if keystore.cipher != CIPHER_CHACHA20_POLY1305 {
+        return Err(KeystoreError::Unsupported(keystore.cipher));
+    }
+    let from_hex = |what: &str, value: &str| {
+        hex::decode(value).map_err(|_| KeystoreError::Malformed(format!("{} is not hex", what)))
+    };
+    let salt = from_hex("salt", &keystore.kdf.salt)?;
+    let nonce = from_hex("nonce", &keystore.nonce)?;
+    let mut in_out = from_hex("ciphertext", &keystore.ciphertext)?;
+
+    let key = scrypt(passphrase.as_bytes(), &salt, &keystore.kdf.scrypt)?;
+    let opening_key =
+        aead::OpeningKey::new(&aead::CHACHA20_POLY1305, &key).map_err(|_| KeystoreError::Unlock)?;
+    let keypair_bytes = aead::open_in_place(
+        &opening_key,
+        &nonce,
+        keystore.pubkey.as_bytes(),
+        0,
+        &mut in_out,
+    )
+    .map_err(|_| KeystoreError::Unlock)?;
+    Keypair::from_bytes(keypair_bytes).map_err(|err| KeystoreError::Malformed(err.to_string()))
+}
+
+/// Check `words` are a BIP39 mnemonic: 12 to 24 words of the wordlist whose trailing bits
+/// are the checksum of the entropy the others encode
+fn check_mnemonic(words: &[&str]) -> Result<(), KeystoreError> {
+    if words.len() < 12 || words.len() > 24 || words.len() % 3 != 0 {
+        return Err(KeystoreError::InvalidSeedPhrase(format!(
+            "{} words",
+            words.len()
+        )));
+    }
+    let wordlist: Vec<&str> = BIP39_WORDLIST.lines().collect();
+    let mut bits = Vec::with_capacity(words.len() * 11);
+    for (i, word) in words.iter().enumerate() {
+        // the word itself stays out of the error, it's part of a secret
+        let index = wordlist.binary_search(word).map_err(|_| {
+            KeystoreError::InvalidSeedPhrase(format!("word {} isn't a BIP39 word", i + 1))
+        })?;
+        bits.extend((0..11).rev().map(|bit| (index >> bit) & 1 == 1));
+    }
+    let checksum_len = bits.len() / 33;
+    let (entropy_bits, checksum) = bits.split_at(bits.len() - checksum_len);
+    let entropy: Vec<u8> = entropy_bits
+        .chunks(8)
+        .map(|byte| byte.iter().fold(0, |acc, bit| (acc << 1) | *bit as u8))
+        .collect();
+    let hash = digest::digest(&digest::SHA256, &entropy);
+    let expected = (0..checksum_len).map(|i| (hash.as_ref()[0] >> (7 - i)) & 1 == 1);
+    if !checksum.iter().cloned().eq(expected) {
+        return Err(KeystoreError::InvalidSeedPhrase("bad checksum".to_string()));
+    }
+    Ok(())
+}
+
+/// The keypair seeded by the BIP39 seed of `seed_phrase` and `passphrase`, once the phrase
+/// is checked to be a valid mnemonic.  Whitespace between its words is normalized.
+pub fn keypair_from_seed_phrase(
+    seed_phrase: &str,
+    passphrase: &str,
+) -> Result<Keypair, KeystoreError> {
+    let words: Vec<_> = seed_phrase.split_whitespace().collect();
+    check_mnemonic(&words)?;
+    let seed_phrase = words.join(" ");
+    let salt = format!("mnemonic{}", passphrase);
+    let mut seed = [0u8; 64];
+    pbkdf2::derive(
+        &digest::SHA512,
+        BIP39_ITERATIONS,
+        salt.as_bytes(),
+        seed_phrase.as_bytes(),
+        &mut seed,
+    );
+    let secret = ed25519_dalek::SecretKey::from_bytes(&seed[..ed25519_dalek::SECRET_KEY_LENGTH])
+        .expect("seed is long enough");
+    let public = ed25519_dalek::PublicKey::from(&secret);
+    Ok(Keypair { secret, public })
+}
+
+/// The passphrase from `KEYPAIR_PASSPHRASE_ENV`, or else one prompted for with `prompt` if
+/// stdin is a terminal
+pub fn read_passphrase(prompt: &str) -> Result<String, KeystoreError> {
+    if let Ok(passphrase) = env::var(KEYPAIR_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    if !terminal::is_tty() {
+        return Err(KeystoreError::NoPassphrase);
+    }
+    eprint!("{}: ", prompt);
+    io::stderr().flush()?;
+    let _echo_off = terminal::EchoOff::new();
+    let mut passphrase = String::new();
+    io::stdin().lock().read_line(&mut passphrase)?;
+    eprintln!();
+    Ok(passphrase.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// The keypair in a key file holding `contents`, asking `read_passphrase` for the
+/// passphrase of encrypted keystores and seed phrases
+pub fn keypair_from_contents<F>(
+    contents: &str,
+    read_passphrase: F,
+) -> Result<Keypair, Box<error::Error>>
+where
+    F: Fn(&str) -> Result<String, KeystoreError>,
+{
+    match KeyFileFormat::of(contents).ok_or(KeystoreError::UnknownFormat)? {
+        KeyFileFormat::Json => {
+            let bytes: Vec<u8> = serde_json::from_str(contents)?;
+            let keypair = Keypair::from_bytes(&bytes)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+            Ok(keypair)
+        }
+        KeyFileFormat::Keystore => {
+            let passphrase = read_passphrase("Keystore passphrase")?;
+            Ok(decrypt_keypair(contents, &passphrase)?)
+        }
+        KeyFileFormat::SeedPhrase => {
+            let passphrase = read_passphrase("Seed phrase passphrase (empty for none)")?;
+            Ok(keypair_from_seed_phrase(contents, &passphrase)?)
+        }
+    }
+}
+
+/// scrypt as specified by RFC 7914, deriving a 32 byte key
+fn scrypt(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: &ScryptParams,
+) -> Result<[u8; 32], KeystoreError> {
+    let bad_params = || KeystoreError::Malformed("bad scrypt parameters".to_string());
+    if params.log_n == 0
+        || params.log_n > MAX_SCRYPT_LOG_N
+        || params.r == 0
+        || params.r > MAX_SCRYPT_R
+        || params.p == 0
+        || params.p > MAX_SCRYPT_P
+    {
+        return Err(bad_params());
+    }
+    let n = 1usize << params.log_n;
+    let r = params.r as usize;
+    let block_len = 128 * r;
+    if n * block_len > MAX_SCRYPT_MEMORY {
+        return Err(bad_params());
+    }
+    let mut blocks = vec![0u8; params.p as usize * block_len];
+    pbkdf2::derive(&digest::SHA256, 1, salt, passphrase, &mut blocks);
+    for block in blocks.chunks_mut(block_len) {
+        ro_mix(block, n, r);
+    }
+    let mut key = [0u8; 32];
+    pbkdf2::derive(&digest::SHA256, 1, &blocks, passphrase, &mut key);
+    Ok(key)
+}
+
+fn ro_mix(block: &mut [u8], n: usize, r: usize) {
+    let words = 32 * r;
+    let mut x: Vec<u32> = block
+        .chunks(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    let mut scratch = vec![0u32; words];
+    let mut v = vec![0u32; n * words];
+    for i in 0..n {
+        v[i * words..(i + 1) * words].copy_from_slice(&x);
+        block_mix(&mut x, &mut scratch, r);
+    }
+    for _ in 0..n {
+        let j = x[(2 * r - 1) * 16] as usize & (n - 1);
+        for (x, v) in x.iter_mut().zip(&v[j * words..(j + 1) * words]) {
+            *x ^= v;
+        }
+        block_mix(&mut x, &mut scratch, r);
+    }
+    for (bytes, word) in block.chunks_mut(4).zip(x) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+}
+
+fn block_mix(b: &mut [u32], scratch: &mut [u32], r: usize) {
+    let mut x = [0u32; 16];
+    x.copy_from_slice(&b[(2 * r - 1) * 16..]);
+    for i in 0..2 * r {
+        for (x, b) in x.iter_mut().zip(&b[i * 16..(i + 1) * 16]) {
+            *x ^= b;
+        }
+        salsa20_8(&mut x);
+        // even blocks go to the first half of the output, odd ones to the second
+        let out = if i % 2 == 0 { i / 2 } else { r + i / 2 };
+        scratch[out * 16..(out + 1) * 16].copy_from_slice(&x);
+    }
+    b.copy_from_slice(scratch);
+}
+
+fn salsa20_8(b: &mut [u32; 16]) {
+    fn quarter_round(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    }
+    let mut x = *b;
+    for _ in 0..4 {
+        quarter_round(&mut x, 0, 4, 8, 12);
+        quarter_round(&mut x, 5, 9, 13, 1);
+        quarter_round(&mut x, 10, 14, 2, 6);
+        quarter_round(&mut x, 15, 3, 7, 11);
+        quarter_round(&mut x, 0, 1, 2, 3);
+        quarter_round(&mut x, 5, 6, 7, 4);
+        quarter_round(&mut x, 10, 11, 8, 9);
+        quarter_round(&mut x, 15, 12, 13, 14);
+    }
+    for (b, x) in b.iter_mut().zip(&x) {
+        *b = b.wrapping_add(*x);
+    }
+}
+
+#[cfg(unix)]
+mod terminal {
+    use std::mem;
+
+    pub fn is_tty() -> bool {
+        unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+    }
+
+    /// Turns off echoing what's typed on the terminal until dropped
+    pub struct EchoOff(Option<libc::termios>);
+
+    impl EchoOff {
+        pub fn new() -> Self {
+            unsafe {
+                let mut termios: libc::termios = mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                    return EchoOff(None);
+                }
+                let saved = termios;
+                termios.c_lflag &= !libc::ECHO;
+                termios.c_lflag |= libc::ECHONL;
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios);
+                EchoOff(Some(saved))
+            }
+        }
+    }
+
+    impl Drop for EchoOff {
+        fn drop(&mut self) {
+            if let Some(saved) = &self.0 {
+                unsafe {
+                    libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, saved);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod terminal {
+    pub fn is_tty() -> bool {
+        false
+    }
+
+    pub struct EchoOff;
+
+    impl EchoOff {
+        pub fn new() -> Self {
+            EchoOff
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PARAMS: ScryptParams = ScryptParams {
+        log_n: 4,
+        r: 1,
+        p: 1,
+    };
+
+    // The RFC 7914 test vectors are 64 bytes long, the derived key is their first 32
+    fn scrypt_hex(passphrase: &str, salt: &str, log_n: u8, r: u32, p: u32) -> String {
+        let params = ScryptParams { log_n, r, p };
+        hex::encode(scrypt(passphrase.as_bytes(), salt.as_bytes(), &params).unwrap())
+    }
+
+    fn words(hex: &str) -> Vec<u32> {
+        hex::decode(hex)
+            .unwrap()
+            .chunks(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect()
+    }
+
+    // RFC 7914 section 8
+    #[test]
+    fn test_salsa20_8_rfc7914() {
+        let mut b = [0u32; 16];
+        b.copy_from_slice(&words(
+            "7e879a214f3ec9867ca940e641718f26baee555b8c61c1b50df846116dcd3b1d\
+             ee24f319df9b3d8514121e4b5ac5aa3276021d2909c74829edebc68db8b8c25e",
+        ));
+        salsa20_8(&mut b);
+        assert_eq!(
+            b.to_vec(),
+            words(
+                "a41f859c6608cc993b81cacb020cef05044b2181a2fd337dfd7b1c6396682f29\
+                 b4393168e3c9e6bcfe6bc5b7a06d96bae424cc102c91745c24ad673dc7618f81"
+            )
+        );
+    }
+
+    const RFC7914_BLOCK: &str = "\
+        f7ce0b653d2d72a4108cf5abe912ffdd777616dbbb27a70e8204f3ae2d0f6fad\
+        89f68f4811d1e87bcc3bd7400a9ffd29094f0184639574f39ae5a1315217bcd7\
+        894991447213bb226c25b54da86370fbcd984380374666bb8ffcb5bf40c254b0\
+        67d27c51ce4ad5fed829c90b505a571b7f4d1cad6a523cda770e67bceaaf7e89";
+
+    // RFC 7914 section 9
+    #[test]
+    fn test_block_mix_rfc7914() {
+        let mut b = words(RFC7914_BLOCK);
+        let mut scratch = vec![0u32; 32];
+        block_mix(&mut b, &mut scratch, 1);
+        assert_eq!(
+            b,
+            words(
+                "a41f859c6608cc993b81cacb020cef05044b2181a2fd337dfd7b1c6396682f29\
+                 b4393168e3c9e6bcfe6bc5b7a06d96bae424cc102c91745c24ad673dc7618f81\
+                 20edc975323881a80540f64c162dcd3c21077cfe5f8d5fe2b1a4168f953678b7\
+                 7d3b3d803b60e4ab920996e59b4d53b65d2a225877d5edf5842cb9f14eefe425"
+            )
+        );
+    }
+
+    // RFC 7914 section 10
+    #[test]
+    fn test_ro_mix_rfc7914() {
+        let mut block = hex::decode(RFC7914_BLOCK).unwrap();
+        ro_mix(&mut block, 16, 1);
+        assert_eq!(
+            hex::encode(block),
+            "79ccc193629debca047f0b70604bf6b62ce3dd4a9626e355fafc6198e6ea2b46\
+             d58413673b99b029d665c357601fb426a0b2f4bba200ee9f0a43d19b571a9c71\
+             ef1142e65d5a266fddca832ce59faa7cac0b9cf1be2bffca300d01ee387619c4\
+             ae12fd4438f203a0e4e1c47ec314861f4e9087cb33396a6873e8f9d2539a4b8e"
+        );
+    }
+
+    // RFC 7914 section 12
+    #[test]
+    fn test_scrypt_rfc7914() {
+        assert_eq!(
+            scrypt_hex("", "", 4, 1, 1),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442"
+        );
+        assert_eq!(
+            scrypt_hex("password", "NaCl", 10, 8, 16),
+            "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b373162"
+        );
+        assert_eq!(
+            scrypt_hex("pleaseletmein", "SodiumChloride", 14, 8, 1),
+            "7023bdcb3afd7348461c06cd81fd38ebfda8fbba904f8e3ea9b543f6545da1f2"
+        );
+    }
+
+    // The last RFC 7914 vector takes the full MAX_SCRYPT_MEMORY
+    #[test]
+    #[ignore]
+    fn test_scrypt_rfc7914_max_memory() {
+        assert_eq!(
+            scrypt_hex("pleaseletmein", "SodiumChloride", 20, 8, 1),
+            "2101cb9b6a511aaeaddbbe09cf70f881ec568d574a2ffd4dabe5ee9820adaa47"
+        );
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_keypair() {
+        let keypair = Keypair::new();
+        let keystore = encrypt_keypair(&keypair, "hunter2", TEST_PARAMS).unwrap();
+        assert_eq!(KeyFileFormat::of(&keystore), Some(KeyFileFormat::Keystore));
+
+        let decrypted = decrypt_keypair(&keystore, "hunter2").unwrap();
+        assert_eq!(decrypted.to_bytes().to_vec(), keypair.to_bytes().to_vec());
+        match decrypt_keypair(&keystore, "hunter3") {
+            Err(KeystoreError::Unlock) => (),
+            other => panic!("unexpected {:?}", other.map(|keypair| keypair.pubkey())),
+        }
+
+        // the pubkey is authenticated along with the keypair
+        let mut tampered: Keystore = serde_json::from_str(&keystore).unwrap();
+        tampered.pubkey = Keypair::new().pubkey().to_string();
+        let tampered = serde_json::to_string(&tampered).unwrap();
+        assert!(decrypt_keypair(&tampered, "hunter2").is_err());
+    }
+
+    #[test]
+    fn test_keypair_from_seed_phrase() {
+        let seed_phrase =
+            "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        let keypair = keypair_from_seed_phrase(seed_phrase, "TREZOR").unwrap();
+        // first half of the BIP39 test vector's seed
+        assert_eq!(
+            hex::encode(&keypair.secret.as_bytes()[..]),
+            "2e8905819b8723fe2c1d161860e5ee1830318dbf49a83bd451cfb8440c28bd6f"
+        );
+        let spaced = format!("  {}\n", seed_phrase.replace(' ', "\t "));
+        assert_eq!(
+            keypair_from_seed_phrase(&spaced, "TREZOR")
+                .unwrap()
+                .pubkey(),
+            keypair.pubkey()
+        );
+        assert_ne!(
+            keypair_from_seed_phrase(seed_phrase, "").unwrap().pubkey(),
+            keypair.pubkey()
+        );
+    }
+
+    #[test]
+    fn test_keypair_from_contents() {
+        let keypair = Keypair::new();
+        let no_passphrase = |_: &str| Err(KeystoreError::NoPassphrase);
+        let passphrase = |_: &str| Ok("hunter2".to_string());
+
+        let json = serde_json::to_string(&keypair.to_bytes().to_vec()).unwrap();
+        assert_eq!(
+            keypair_from_contents(&json, no_passphrase)
+                .unwrap()
+                .pubkey(),
+            keypair.pubkey()
+        );
+
+        let keystore = encrypt_keypair(&keypair, "hunter2", TEST_PARAMS).unwrap();
+        assert!(keypair_from_contents(&keystore, no_passphrase).is_err());
+        assert_eq!(
+            keypair_from_contents(&keystore, passphrase)
+                .unwrap()
+                .pubkey(),
+            keypair.pubkey()
+        );
+
+        let seed_phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert_eq!(
+            keypair_from_contents(seed_phrase, passphrase)
+                .unwrap()
+                .pubkey(),
+            keypair_from_seed_phrase(seed_phrase, "hunter2")
+                .unwrap()
+                .pubkey()
+        );
+
+        // empty, truncated or corrupt files don't become some other keypair
+        for contents in &[
+            "",
+            " \n",
+            "\u{0}\u{1}",
+            &seed_phrase[..seed_phrase.len() - 6],
+        ] {
+            assert!(keypair_from_contents(contents, passphrase).is_err());
+        }
+    }
+
+    #[test]
+    fn test_check_mnemonic() {
+        let check =
+            |seed_phrase: &str| check_mnemonic(&seed_phrase.split_whitespace().collect::<Vec<_>>());
+        check("letter advice cage absurd amount doctor acoustic avoid letter advice cage above")
+            .unwrap();
+        check(&format!("{}art", "abandon ".repeat(23))).unwrap();
+        check(&format!("{}vote", "zoo ".repeat(23))).unwrap();
+
+        match check(&"abandon ".repeat(12)) {
+            Err(KeystoreError::InvalidSeedPhrase(what)) => assert_eq!(what, "bad checksum"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match check("abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abuot") {
+            Err(KeystoreError::InvalidSeedPhrase(what)) => {
+                assert_eq!(what, "word 12 isn't a BIP39 word")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(check(&"abandon ".repeat(11)).is_err());
+        assert!(check(&"abandon ".repeat(13)).is_err());
+        assert!(check(&"abandon ".repeat(27)).is_err());
+    }
+
+    #[test]
+    fn test_scrypt_bounds() {
+        let scrypt_with = |log_n, r, p| scrypt(b"password", b"salt", &ScryptParams { log_n, r, p });
+        assert!(scrypt_with(0, 1, 1).is_err());
+        assert!(scrypt_with(MAX_SCRYPT_LOG_N + 1, 1, 1).is_err());
+        assert!(scrypt_with(4, 0, 1).is_err());
+        assert!(scrypt_with(4, MAX_SCRYPT_R + 1, 1).is_err());
+        assert!(scrypt_with(4, 1, 0).is_err());
+        assert!(scrypt_with(4, 1, u32::max_value()).is_err());
+        // within each bound, but over the memory bound together
+        assert!(scrypt_with(MAX_SCRYPT_LOG_N, MAX_SCRYPT_R, 1).is_err());
+        assert!(scrypt_with(4, MAX_SCRYPT_R, MAX_SCRYPT_P).is_ok());
+    }
+}
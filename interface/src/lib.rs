@@ -5,14 +5,18 @@ pub mod client;
 pub mod fee_calculator;
 pub mod genesis_block;
 pub mod hash;
+pub mod inflation;
 pub mod instruction;
 pub mod instruction_processor_utils;
+pub mod keystore;
 pub mod loader_instruction;
 pub mod message;
 pub mod native_loader;
+pub mod nonce_state;
 pub mod packet;
 pub mod poh_config;
 pub mod pubkey;
+pub mod rent_calculator;
 pub mod rpc_port;
 pub mod short_vec;
 pub mod signature;
@@ -2,9 +2,23 @@
 
 use crate::hash::Hash;
 use crate::instruction::{AccountMeta, CompiledInstruction, Instruction};
+use crate::packet::PACKET_DATA_SIZE;
 use crate::pubkey::Pubkey;
-use crate::short_vec;
+use crate::short_vec::{self, ShortU16, ShortVec};
+use crate::signature::Signature;
 use itertools::Itertools;
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde::ser::{SerializeTuple, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::mem::size_of;
+
+/// The top bit of the first byte of a serialized message marks a versioned message. Legacy
+/// messages start with `num_required_signatures`, which never has the top bit set.
+pub const MESSAGE_VERSION_PREFIX: u8 = 0x80;
+
+/// The version of messages that carry address table lookups
+pub const MESSAGE_VERSION_1: u8 = 1;
 
 fn position(keys: &[Pubkey], key: &Pubkey) -> u8 {
     keys.iter().position(|k| k == key).unwrap() as u8
@@ -59,6 +73,10 @@ impl InstructionKeys {
 /// payer key is provided, it is always placed first in the list of signed keys. Credit-only signed
 /// accounts are placed last in the set of signed accounts. Credit-only unsigned accounts,
 /// including program ids, are placed last in the set. No duplicates and order is preserved.
+///
+/// A key listed by more than one account meta is signed if any of them is a signer and
+/// credit-debit if any of them is debitable, so merging never drops a lock the instructions
+/// asked for.
 fn get_keys(instructions: &[Instruction], payer: Option<&Pubkey>) -> InstructionKeys {
     let programs: Vec<_> = get_program_ids(instructions)
         .iter()
@@ -68,32 +86,44 @@ fn get_keys(instructions: &[Instruction], payer: Option<&Pubkey>) -> Instruction
             is_debitable: false,
         })
         .collect();
-    let mut keys_and_signed: Vec<_> = instructions
-        .iter()
-        .flat_map(|ix| ix.accounts.iter())
-        .collect();
-    keys_and_signed.extend(&programs);
-    keys_and_signed.sort_by(|x, y| {
-        y.is_signer
-            .cmp(&x.is_signer)
-            .then(y.is_debitable.cmp(&x.is_debitable))
-    });
 
-    let payer_account_meta;
+    let mut account_metas: Vec<AccountMeta> = vec![];
     if let Some(payer) = payer {
-        payer_account_meta = AccountMeta {
+        account_metas.push(AccountMeta {
             pubkey: *payer,
             is_signer: true,
             is_debitable: true,
-        };
-        keys_and_signed.insert(0, &payer_account_meta);
+        });
     }
+    for account_meta in instructions
+        .iter()
+        .flat_map(|ix| ix.accounts.iter())
+        .chain(&programs)
+    {
+        match account_metas
+            .iter_mut()
+            .find(|x| x.pubkey == account_meta.pubkey)
+        {
+            Some(x) => {
+                x.is_signer |= account_meta.is_signer;
+                x.is_debitable |= account_meta.is_debitable;
+            }
+            None => account_metas.push(account_meta.clone()),
+        }
+    }
+    // the payer stays first, the sort is stable so each class keeps the order keys were listed
+    let num_payers = if payer.is_some() { 1 } else { 0 };
+    account_metas[num_payers..].sort_by(|x, y| {
+        y.is_signer
+            .cmp(&x.is_signer)
+            .then(y.is_debitable.cmp(&x.is_debitable))
+    });
 
     let mut signed_keys = vec![];
     let mut unsigned_keys = vec![];
     let mut num_credit_only_signed_accounts = 0;
     let mut num_credit_only_unsigned_accounts = 0;
-    for account_meta in keys_and_signed.into_iter().unique_by(|x| x.pubkey) {
+    for account_meta in account_metas {
         if account_meta.is_signer {
             signed_keys.push(account_meta.pubkey);
             if !account_meta.is_debitable {
@@ -123,6 +153,35 @@ fn get_program_ids(instructions: &[Instruction]) -> Vec<Pubkey> {
         .collect()
 }
 
+/// Why `Message::compile` refused a set of instructions
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MessageCompileError {
+    /// The instructions reference more distinct keys than an instruction can index
+    TooManyAccountKeys { num_keys: usize, max_keys: usize },
+
+    /// The signed transaction would not fit in a packet
+    TooLarge { size: usize, max_size: usize },
+}
+
+impl fmt::Display for MessageCompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MessageCompileError::TooManyAccountKeys { num_keys, max_keys } => write!(
+                f,
+                "message references {} account keys, at most {} are allowed",
+                num_keys, max_keys
+            ),
+            MessageCompileError::TooLarge { size, max_size } => write!(
+                f,
+                "signed transaction would be {} bytes, at most {} fit in a packet",
+                size, max_size
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MessageCompileError {}
+
 #[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
 pub struct MessageHeader {
     /// The number of signatures required for this message to be considered valid. The
@@ -139,13 +198,41 @@ pub struct MessageHeader {
     pub num_credit_only_unsigned_accounts: u8,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+/// A reference to an address lookup table account and the table entries a message loads from it
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq, Eq, Clone)]
+pub struct MessageAddressTableLookup {
+    /// The address lookup table account
+    pub account_key: Pubkey,
+
+    /// Indexes of the table entries to load as credit-debit accounts
+    #[serde(with = "short_vec")]
+    pub writable_indexes: Vec<u8>,
+
+    /// Indexes of the table entries to load as credit-only accounts
+    #[serde(with = "short_vec")]
+    pub readonly_indexes: Vec<u8>,
+}
+
+/// The addresses held by an address lookup table, used to compile a message that loads
+/// accounts by table index
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct AddressLookupTableAccount {
+    pub key: Pubkey,
+    pub addresses: Vec<Pubkey>,
+}
+
+/// A message with an empty `address_table_lookups` is serialized in the legacy layout. Otherwise
+/// it is serialized as a v1 message, prefixed with `MESSAGE_VERSION_PREFIX | MESSAGE_VERSION_1`.
+///
+/// Instructions of a v1 message index into `account_keys`, followed by the writable entries of
+/// every lookup, followed by the credit-only entries of every lookup. Program ids and signers
+/// must be in `account_keys`.
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Message {
     /// The message header, identifying signed and credit-only `account_keys`
     pub header: MessageHeader,
 
     /// All the account keys used by this transaction
-    #[serde(with = "short_vec")]
     pub account_keys: Vec<Pubkey>,
 
     /// The id of a recent ledger entry.
@@ -153,8 +240,142 @@ pub struct Message {
 
     /// Programs that will be executed in sequence and committed in one atomic transaction if all
     /// succeed.
-    #[serde(with = "short_vec")]
     pub instructions: Vec<CompiledInstruction>,
+
+    /// Address lookup tables supplying additional account keys, credit-debit keys first
+    pub address_table_lookups: Vec<MessageAddressTableLookup>,
+}
+
+/// Serialize a borrowed slice with a short_vec length prefix
+struct ShortSlice<'a, T>(&'a [T]);
+
+impl<'a, T: Serialize> Serialize for ShortSlice<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        short_vec::serialize(self.0, serializer)
+    }
+}
+
+/// The field layout used by human-readable formats, which need no version prefix
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Message")]
+struct MessageFields {
+    header: MessageHeader,
+    #[serde(with = "short_vec")]
+    account_keys: Vec<Pubkey>,
+    recent_blockhash: Hash,
+    #[serde(with = "short_vec")]
+    instructions: Vec<CompiledInstruction>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    address_table_lookups: Vec<MessageAddressTableLookup>,
+}
+
+impl Serialize for Message {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return MessageFields {
+                header: self.header.clone(),
+                account_keys: self.account_keys.clone(),
+                recent_blockhash: self.recent_blockhash,
+                instructions: self.instructions.clone(),
+                address_table_lookups: self.address_table_lookups.clone(),
+            }
+            .serialize(serializer);
+        }
+
+        let mut seq = serializer.serialize_tuple(1)?;
+        if self.is_versioned() {
+            seq.serialize_element(&(MESSAGE_VERSION_PREFIX | MESSAGE_VERSION_1))?;
+        }
+        seq.serialize_element(&self.header)?;
+        seq.serialize_element(&ShortSlice(&self.account_keys))?;
+        seq.serialize_element(&self.recent_blockhash)?;
+        seq.serialize_element(&ShortSlice(&self.instructions))?;
+        if self.is_versioned() {
+            seq.serialize_element(&ShortSlice(&self.address_table_lookups))?;
+        }
+        seq.end()
+    }
+}
+
+struct MessageVisitor;
+
+impl MessageVisitor {
+    fn next<'de, A, T>(seq: &mut A, index: usize) -> Result<T, A::Error>
+    where
+        A: SeqAccess<'de>,
+        T: Deserialize<'de>,
+    {
+        seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(index, &MessageVisitor))
+    }
+}
+
+impl<'de> Visitor<'de> for MessageVisitor {
+    type Value = Message;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a legacy or versioned message")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Message, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let first: u8 = Self::next(&mut seq, 0)?;
+        let versioned = first & MESSAGE_VERSION_PREFIX != 0;
+        let header = if versioned {
+            let version = first & !MESSAGE_VERSION_PREFIX;
+            if version != MESSAGE_VERSION_1 {
+                return Err(de::Error::custom(format!(
+                    "unsupported message version {}",
+                    version
+                )));
+            }
+            Self::next(&mut seq, 1)?
+        } else {
+            MessageHeader {
+                num_required_signatures: first,
+                num_credit_only_signed_accounts: Self::next(&mut seq, 1)?,
+                num_credit_only_unsigned_accounts: Self::next(&mut seq, 2)?,
+            }
+        };
+        let ShortVec(account_keys) = Self::next(&mut seq, 3)?;
+        let recent_blockhash = Self::next(&mut seq, 4)?;
+        let ShortVec(instructions) = Self::next(&mut seq, 5)?;
+        let address_table_lookups = if versioned {
+            let ShortVec(address_table_lookups): ShortVec<MessageAddressTableLookup> =
+                Self::next(&mut seq, 6)?;
+            if address_table_lookups.is_empty() {
+                return Err(de::Error::custom("versioned message without lookups"));
+            }
+            address_table_lookups
+        } else {
+            vec![]
+        };
+        Ok(Message {
+            header,
+            account_keys,
+            recent_blockhash,
+            instructions,
+            address_table_lookups,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Message, D::Error> {
+        if deserializer.is_human_readable() {
+            let fields = MessageFields::deserialize(deserializer)?;
+            return Ok(Message {
+                header: fields.header,
+                account_keys: fields.account_keys,
+                recent_blockhash: fields.recent_blockhash,
+                instructions: fields.instructions,
+                address_table_lookups: fields.address_table_lookups,
+            });
+        }
+        deserializer.deserialize_tuple(std::usize::MAX, MessageVisitor)
+    }
 }
 
 impl Message {
@@ -175,6 +396,7 @@ impl Message {
             account_keys,
             recent_blockhash,
             instructions,
+            address_table_lookups: vec![],
         }
     }
 
@@ -202,6 +424,155 @@ impl Message {
         )
     }
 
+    /// Like `new_with_payer`, but refuses instructions that reference more keys than can be
+    /// indexed or whose signed transaction would not fit in a packet, instead of producing a
+    /// message the cluster would reject.
+    pub fn compile(
+        instructions: Vec<Instruction>,
+        payer: Option<&Pubkey>,
+    ) -> Result<Self, MessageCompileError> {
+        let max_keys = std::u8::MAX as usize + 1;
+        let keys = get_keys(&instructions, payer);
+        let num_keys = keys.signed_keys.len() + keys.unsigned_keys.len();
+        if num_keys > max_keys {
+            return Err(MessageCompileError::TooManyAccountKeys { num_keys, max_keys });
+        }
+
+        let message = Self::new_with_payer(instructions, payer);
+        let size = message.serialized_transaction_size();
+        if size > PACKET_DATA_SIZE {
+            return Err(MessageCompileError::TooLarge {
+                size,
+                max_size: PACKET_DATA_SIZE,
+            });
+        }
+        Ok(message)
+    }
+
+    /// The size of a transaction carrying this message and all of its signatures
+    pub fn serialized_transaction_size(&self) -> usize {
+        let num_signatures = self.header.num_required_signatures;
+        let len_prefix = ShortU16(u16::from(num_signatures));
+        let signatures_len = bincode::serialized_size(&len_prefix).unwrap() as usize
+            + usize::from(num_signatures) * size_of::<Signature>();
+        signatures_len + bincode::serialized_size(self).unwrap() as usize
+    }
+
+    /// Compile a message that loads unsigned, non-program accounts from the given address lookup
+    /// tables where possible. Falls back to a legacy message if no table holds any of them.
+    pub fn new_with_address_table_lookups(
+        instructions: Vec<Instruction>,
+        payer: Option<&Pubkey>,
+        address_lookup_tables: &[AddressLookupTableAccount],
+    ) -> Self {
+        let InstructionKeys {
+            mut signed_keys,
+            unsigned_keys,
+            num_credit_only_signed_accounts,
+            num_credit_only_unsigned_accounts,
+        } = get_keys(&instructions, payer);
+        let program_ids = get_program_ids(&instructions);
+        let num_credit_debit_unsigned_accounts =
+            unsigned_keys.len() - num_credit_only_unsigned_accounts as usize;
+
+        let mut static_unsigned_keys = vec![];
+        let mut num_static_credit_only_unsigned_accounts = 0;
+        let mut address_table_lookups: Vec<MessageAddressTableLookup> = vec![];
+        for (i, key) in unsigned_keys.into_iter().enumerate() {
+            let is_debitable = i < num_credit_debit_unsigned_accounts;
+            let table_entry = if program_ids.contains(&key) {
+                None
+            } else {
+                address_lookup_tables.iter().find_map(|table| {
+                    table
+                        .addresses
+                        .iter()
+                        .take(std::u8::MAX as usize + 1)
+                        .position(|address| *address == key)
+                        .map(|index| (table.key, index as u8))
+                })
+            };
+            match table_entry {
+                Some((table_key, index)) => {
+                    let position = address_table_lookups
+                        .iter()
+                        .position(|lookup| lookup.account_key == table_key)
+                        .unwrap_or_else(|| {
+                            address_table_lookups.push(MessageAddressTableLookup {
+                                account_key: table_key,
+                                ..MessageAddressTableLookup::default()
+                            });
+                            address_table_lookups.len() - 1
+                        });
+                    let lookup = &mut address_table_lookups[position];
+                    if is_debitable {
+                        lookup.writable_indexes.push(index);
+                    } else {
+                        lookup.readonly_indexes.push(index);
+                    }
+                }
+                None => {
+                    static_unsigned_keys.push(key);
+                    if !is_debitable {
+                        num_static_credit_only_unsigned_accounts += 1;
+                    }
+                }
+            }
+        }
+
+        let num_required_signatures = signed_keys.len() as u8;
+        signed_keys.extend(&static_unsigned_keys);
+        let mut keys = signed_keys.clone();
+        for lookup in &address_table_lookups {
+            let table = address_lookup_tables
+                .iter()
+                .find(|table| table.key == lookup.account_key)
+                .unwrap();
+            keys.extend(
+                lookup
+                    .writable_indexes
+                    .iter()
+                    .map(|i| table.addresses[*i as usize]),
+            );
+        }
+        for lookup in &address_table_lookups {
+            let table = address_lookup_tables
+                .iter()
+                .find(|table| table.key == lookup.account_key)
+                .unwrap();
+            keys.extend(
+                lookup
+                    .readonly_indexes
+                    .iter()
+                    .map(|i| table.addresses[*i as usize]),
+            );
+        }
+        let instructions = compile_instructions(instructions, &keys);
+        let mut message = Self::new_with_compiled_instructions(
+            num_required_signatures,
+            num_credit_only_signed_accounts,
+            num_static_credit_only_unsigned_accounts,
+            signed_keys,
+            Hash::default(),
+            instructions,
+        );
+        message.address_table_lookups = address_table_lookups;
+        message
+    }
+
+    /// True if this message loads accounts from address lookup tables
+    pub fn is_versioned(&self) -> bool {
+        !self.address_table_lookups.is_empty()
+    }
+
+    /// The number of account keys loaded from address lookup tables
+    pub fn num_lookup_keys(&self) -> usize {
+        self.address_table_lookups
+            .iter()
+            .map(|lookup| lookup.writable_indexes.len() + lookup.readonly_indexes.len())
+            .sum()
+    }
+
     pub fn program_ids(&self) -> Vec<&Pubkey> {
         self.instructions
             .iter()
@@ -224,6 +595,22 @@ impl Message {
                     - self.header.num_credit_only_unsigned_accounts as usize)
     }
 
+    /// True if the account at `i` may be modified by the message's instructions. `i` indexes
+    /// `account_keys` followed by the keys loaded from address lookup tables, writable ones
+    /// first. Credit-only accounts are locked shared with other transactions, so the runtime
+    /// rejects any change to them.
+    pub fn is_writable(&self, i: usize) -> bool {
+        if i < self.account_keys.len() {
+            return self.is_credit_debit(i);
+        }
+        let num_writable_lookup_keys: usize = self
+            .address_table_lookups
+            .iter()
+            .map(|lookup| lookup.writable_indexes.len())
+            .sum();
+        i - self.account_keys.len() < num_writable_lookup_keys
+    }
+
     pub fn get_account_keys_by_lock_type(&self) -> (Vec<&Pubkey>, Vec<&Pubkey>) {
         let mut credit_debit_keys = vec![];
         let mut credit_only_keys = vec![];
@@ -243,6 +630,7 @@ mod tests {
     use super::*;
     use crate::instruction::AccountMeta;
     use crate::signature::{Keypair, KeypairUtil};
+    use crate::transaction::Transaction;
 
     #[test]
     fn test_message_unique_program_ids() {
@@ -533,6 +921,7 @@ mod tests {
             account_keys: vec![key0, key1, key2, key3, key4, key5],
             recent_blockhash: Hash::default(),
             instructions: vec![],
+            address_table_lookups: vec![],
         };
         assert_eq!(message.is_credit_debit(0), true);
         assert_eq!(message.is_credit_debit(1), false);
@@ -568,4 +957,180 @@ mod tests {
             (vec![&id1, &id0], vec![&id3, &id2, &program_id])
         );
     }
+
+    fn create_lookup_table_message() -> (Message, AddressLookupTableAccount) {
+        let program_id = Pubkey::new_rand();
+        let payer = Pubkey::new_rand();
+        let id0 = Pubkey::new_rand();
+        let id1 = Pubkey::new_rand();
+        let id2 = Pubkey::new_rand();
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_rand(),
+            addresses: vec![id2, id1, program_id],
+        };
+        let ix = Instruction::new(
+            program_id,
+            &0,
+            vec![
+                AccountMeta::new(id0, false),
+                AccountMeta::new(id1, false),
+                AccountMeta::new_credit_only(id2, false),
+            ],
+        );
+        let message =
+            Message::new_with_address_table_lookups(vec![ix], Some(&payer), &[table.clone()]);
+        (message, table)
+    }
+
+    #[test]
+    fn test_message_with_address_table_lookups() {
+        let (message, table) = create_lookup_table_message();
+        assert!(message.is_versioned());
+        assert_eq!(message.header.num_required_signatures, 1);
+        assert_eq!(message.header.num_credit_only_unsigned_accounts, 1);
+        // the program id stays in the static keys
+        assert_eq!(message.account_keys.len(), 3);
+        assert_eq!(message.account_keys[2], table.addresses[2]);
+        assert_eq!(
+            message.address_table_lookups,
+            vec![MessageAddressTableLookup {
+                account_key: table.key,
+                writable_indexes: vec![1],
+                readonly_indexes: vec![0],
+            }]
+        );
+        assert_eq!(message.num_lookup_keys(), 2);
+        let writable: Vec<_> = (0..5).map(|i| message.is_writable(i)).collect();
+        assert_eq!(writable, vec![true, true, false, true, false]);
+        assert_eq!(
+            message.instructions[0],
+            CompiledInstruction::new(2, &0, vec![1, 3, 4])
+        );
+    }
+
+    #[test]
+    fn test_message_with_address_table_lookups_no_match() {
+        let program_id = Pubkey::new_rand();
+        let ix = Instruction::new(
+            program_id,
+            &0,
+            vec![AccountMeta::new(Pubkey::new_rand(), false)],
+        );
+        let table = AddressLookupTableAccount {
+            key: Pubkey::new_rand(),
+            addresses: vec![program_id],
+        };
+        let message = Message::new_with_address_table_lookups(vec![ix.clone()], None, &[table]);
+        assert!(!message.is_versioned());
+        assert_eq!(message, Message::new(vec![ix]));
+    }
+
+    #[test]
+    fn test_message_serialize_legacy_layout() {
+        let message = Message::new(vec![Instruction::new(
+            Pubkey::new_rand(),
+            &0,
+            vec![AccountMeta::new(Pubkey::new_rand(), true)],
+        )]);
+        let bytes = bincode::serialize(&message).unwrap();
+        assert_eq!(bytes[0], 1);
+        assert_eq!(bytes[3], 2);
+        assert_eq!(bincode::deserialize::<Message>(&bytes).unwrap(), message);
+    }
+
+    #[test]
+    fn test_message_serialize_versioned() {
+        let (message, _) = create_lookup_table_message();
+        let bytes = bincode::serialize(&message).unwrap();
+        assert_eq!(bytes[0], MESSAGE_VERSION_PREFIX | MESSAGE_VERSION_1);
+        assert_eq!(bincode::deserialize::<Message>(&bytes).unwrap(), message);
+
+        let mut bad_version = bytes;
+        bad_version[0] = MESSAGE_VERSION_PREFIX | 2;
+        assert!(bincode::deserialize::<Message>(&bad_version).is_err());
+
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(serde_json::from_str::<Message>(&json).unwrap(), message);
+    }
+
+    #[test]
+    fn test_message_merges_duplicate_account_metas() {
+        let program_id = Pubkey::new_rand();
+        let id0 = Pubkey::new_rand();
+        let id1 = Pubkey::new_rand();
+        let keys = get_keys(
+            &[
+                Instruction::new(
+                    program_id,
+                    &0,
+                    vec![
+                        AccountMeta::new_credit_only(id0, true),
+                        AccountMeta::new_credit_only(id1, false),
+                    ],
+                ),
+                Instruction::new(
+                    program_id,
+                    &0,
+                    vec![AccountMeta::new(id0, false), AccountMeta::new(id1, false)],
+                ),
+            ],
+            None,
+        );
+        assert_eq!(
+            keys,
+            InstructionKeys::new(vec![id0], vec![id1, program_id], 0, 1)
+        );
+    }
+
+    #[test]
+    fn test_message_compile() {
+        let program_id = Pubkey::new_rand();
+        let payer = Pubkey::new_rand();
+        let id0 = Pubkey::new_rand();
+        let instructions = vec![
+            Instruction::new(program_id, &0, vec![AccountMeta::new(id0, false)]),
+            Instruction::new(program_id, &0, vec![AccountMeta::new(id0, false)]),
+        ];
+        let message = Message::compile(instructions.clone(), Some(&payer)).unwrap();
+        assert_eq!(message, Message::new_with_payer(instructions, Some(&payer)));
+        assert_eq!(message.account_keys, vec![payer, id0, program_id]);
+
+        let transaction = Transaction::new_unsigned(message.clone());
+        assert_eq!(
+            message.serialized_transaction_size(),
+            bincode::serialized_size(&transaction).unwrap() as usize
+        );
+    }
+
+    #[test]
+    fn test_message_compile_too_many_account_keys() {
+        let program_id = Pubkey::new_rand();
+        let accounts: Vec<_> = (0..256)
+            .map(|_| AccountMeta::new(Pubkey::new_rand(), false))
+            .collect();
+        let instructions = vec![Instruction::new(program_id, &0, accounts)];
+        assert_eq!(
+            Message::compile(instructions, None),
+            Err(MessageCompileError::TooManyAccountKeys {
+                num_keys: 257,
+                max_keys: 256,
+            })
+        );
+    }
+
+    #[test]
+    fn test_message_compile_too_large() {
+        let program_id = Pubkey::new_rand();
+        let accounts: Vec<_> = (0..40)
+            .map(|_| AccountMeta::new(Pubkey::new_rand(), false))
+            .collect();
+        let instructions = vec![Instruction::new(program_id, &0, accounts)];
+        match Message::compile(instructions, None) {
+            Err(MessageCompileError::TooLarge { size, max_size }) => {
+                assert!(size > max_size);
+                assert_eq!(max_size, PACKET_DATA_SIZE);
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
 }
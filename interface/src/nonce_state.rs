@@ -0,0 +1,110 @@
+//! The `nonce_state` module holds the state of durable nonce accounts.
+//!
+//! A nonce account is a system account storing a nonce that transactions may use as their
+//! `recent_blockhash` instead of a recent blockhash, so they can be signed offline and
+//! submitted long after `MAX_RECENT_BLOCKHASHES` worth of blockhashes went by.  Such a
+//! transaction starts by advancing the nonce, so it can't be replayed once committed.
+
+use crate::account::Account;
+use crate::account_utils::State;
+use crate::hash::Hash;
+use crate::instruction_processor_utils::limited_deserialize;
+use crate::message::Message;
+use crate::pubkey::Pubkey;
+use crate::system_instruction::SystemInstruction;
+use crate::system_program;
+use bincode::serialized_size;
+
+/// Index of the nonce account in the accounts of nonce instructions
+pub const NONCE_ACCOUNT_INDEX: usize = 1;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NonceState {
+    Uninitialized,
+    /// The authority allowed to use the account and the nonce transactions must carry
+    Initialized(Pubkey, Hash),
+}
+
+impl Default for NonceState {
+    fn default() -> Self {
+        NonceState::Uninitialized
+    }
+}
+
+impl NonceState {
+    /// Bytes of account data a nonce account needs
+    pub fn size() -> usize {
+        serialized_size(&NonceState::Initialized(Pubkey::default(), Hash::default())).unwrap()
+            as usize
+    }
+
+    /// The state of `account` if it is a nonce account
+    pub fn from(account: &Account) -> Option<Self> {
+        if !system_program::check_id(&account.owner) || account.data.len() != Self::size() {
+            return None;
+        }
+        account.state().ok()
+    }
+
+    pub fn to(&self, account: &mut Account) -> Option<()> {
+        account.set_state(self).ok()
+    }
+}
+
+/// The nonce account of `message` if it is a durable nonce transaction, one whose first
+/// instruction advances a nonce account
+pub fn nonce_account_pubkey(message: &Message) -> Option<&Pubkey> {
+    let instruction = message.instructions.first()?;
+    let program_id = message
+        .account_keys
+        .get(instruction.program_ids_index as usize)?;
+    if !system_program::check_id(program_id) {
+        return None;
+    }
+    match limited_deserialize(&instruction.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => {
+            let index = *instruction.accounts.get(NONCE_ACCOUNT_INDEX)?;
+            message.account_keys.get(index as usize)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hash::hash;
+    use crate::system_instruction;
+
+    #[test]
+    fn test_nonce_state_from_to() {
+        let mut account = Account::new(1, 0, NonceState::size(), &system_program::id());
+        assert_eq!(NonceState::from(&account), Some(NonceState::Uninitialized));
+
+        let state = NonceState::Initialized(Pubkey::new_rand(), hash(b"nonce"));
+        state.to(&mut account).unwrap();
+        assert_eq!(NonceState::from(&account), Some(state));
+
+        account.owner = Pubkey::new_rand();
+        assert_eq!(NonceState::from(&account), None);
+    }
+
+    #[test]
+    fn test_nonce_account_pubkey() {
+        let nonce_pubkey = Pubkey::new_rand();
+        let authority = Pubkey::new_rand();
+        let to = Pubkey::new_rand();
+
+        let message = Message::new(vec![
+            system_instruction::advance_nonce_account(&nonce_pubkey, &authority),
+            system_instruction::transfer(&authority, &to, 1),
+        ]);
+        assert_eq!(nonce_account_pubkey(&message), Some(&nonce_pubkey));
+
+        let message = Message::new(vec![
+            system_instruction::transfer(&authority, &to, 1),
+            system_instruction::advance_nonce_account(&nonce_pubkey, &authority),
+        ]);
+        assert_eq!(nonce_account_pubkey(&message), None);
+    }
+}
@@ -0,0 +1,85 @@
+//! The `rent_calculator` module works out the rent accounts pay for the storage they take up.
+//! Accounts holding less than the rent-exempt minimum balance are charged once every epoch,
+//! so abandoned accounts are eventually drained and removed.
+
+/// Bytes every account is charged rent for besides its data, what the accounts store keeps
+/// for it
+pub const ACCOUNT_STORAGE_OVERHEAD: u64 = 128;
+
+/// Years of rent an account must hold to be exempt from paying it
+pub const DEFAULT_EXEMPTION_THRESHOLD: f64 = 2.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct RentCalculator {
+    /// Rent a byte of account storage costs a year, 0 charges no rent
+    pub difs_per_byte_year: u64,
+
+    /// Years of rent an account must hold to be exempt from paying it
+    pub exemption_threshold: f64,
+}
+
+impl Default for RentCalculator {
+    fn default() -> Self {
+        Self {
+            difs_per_byte_year: 0,
+            exemption_threshold: DEFAULT_EXEMPTION_THRESHOLD,
+        }
+    }
+}
+
+impl RentCalculator {
+    pub fn new(difs_per_byte_year: u64, exemption_threshold: f64) -> Self {
+        Self {
+            difs_per_byte_year,
+            exemption_threshold,
+        }
+    }
+
+    /// Rent an account with `data_len` bytes of data owes a year
+    pub fn annual_rent(&self, data_len: usize) -> u64 {
+        self.difs_per_byte_year * (ACCOUNT_STORAGE_OVERHEAD + data_len as u64)
+    }
+
+    /// The balance an account with `data_len` bytes of data must hold to pay no rent
+    pub fn minimum_balance(&self, data_len: usize) -> u64 {
+        (self.annual_rent(data_len) as f64 * self.exemption_threshold) as u64
+    }
+
+    pub fn is_exempt(&self, balance: u64, data_len: usize) -> bool {
+        balance >= self.minimum_balance(data_len)
+    }
+
+    /// Rent owed by an account holding `balance` and `data_len` bytes of data for `years`,
+    /// rounded up to the next dif so small accounts can't hold out forever
+    pub fn due(&self, balance: u64, data_len: usize, years: f64) -> u64 {
+        if self.is_exempt(balance, data_len) {
+            0
+        } else {
+            (self.annual_rent(data_len) as f64 * years).ceil() as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rent_calculator() {
+        let rent_calculator = RentCalculator::default();
+        assert_eq!(rent_calculator.minimum_balance(1_000), 0);
+        assert_eq!(rent_calculator.due(0, 1_000, 1.0), 0);
+
+        let rent_calculator = RentCalculator::new(2, 2.0);
+        assert_eq!(rent_calculator.annual_rent(72), 400);
+        assert_eq!(rent_calculator.minimum_balance(72), 800);
+        assert!(rent_calculator.is_exempt(800, 72));
+        assert!(!rent_calculator.is_exempt(799, 72));
+
+        assert_eq!(rent_calculator.due(800, 72, 1.0), 0);
+        assert_eq!(rent_calculator.due(799, 72, 0.5), 200);
+        // a sliver of a year still costs a dif
+        assert_eq!(rent_calculator.due(799, 72, 0.000_001), 1);
+    }
+}
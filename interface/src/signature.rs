@@ -1,5 +1,6 @@
 //! The `signature` module provides functionality for public, and private keys.
 
+use crate::keystore;
 use crate::pubkey::Pubkey;
 use bs58;
 use generic_array::typenum::U64;
@@ -116,12 +117,11 @@ impl KeypairUtil for Keypair {
     }
 }
 
+/// Read the keypair in the key file at `path`: a JSON array of the keypair bytes, an
+/// encrypted keystore or a seed phrase, see the `keystore` module
 pub fn read_keypair(path: &str) -> Result<Keypair, Box<error::Error>> {
-    let file = File::open(path.to_string())?;
-    let bytes: Vec<u8> = serde_json::from_reader(file)?;
-    let keypair = Keypair::from_bytes(&bytes)
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-    Ok(keypair)
+    let contents = fs::read_to_string(path)?;
+    keystore::keypair_from_contents(&contents, keystore::read_passphrase)
 }
 
 pub fn gen_keypair_file(outfile: &str) -> Result<String, Box<error::Error>> {
@@ -0,0 +1,77 @@
+//! named accounts for synthesized data accounts for bank state, etc.
+//!
+//! this account carries the current epoch and its length in ticks under the Bank's epoch
+//! schedule, for programs that time things in epochs
+//!
+use crate::account::Account;
+use crate::account_utils::State;
+use crate::pubkey::Pubkey;
+use crate::syscall;
+use bincode::serialized_size;
+
+/// "Sysca11EpochTicks11111111111111111111111111"
+///  epoch ticks account pubkey
+const ID: [u8; 32] = [
+    6, 167, 211, 138, 69, 218, 77, 47, 235, 20, 129, 115, 173, 250, 96, 24, 70, 120, 16, 217, 203,
+    37, 229, 40, 247, 95, 90, 205, 16, 0, 0, 0,
+];
+
+pub fn id() -> Pubkey {
+    Pubkey::new(&ID)
+}
+
+pub fn check_id(pubkey: &Pubkey) -> bool {
+    pubkey.as_ref() == ID
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct EpochTicks {
+    pub epoch: u64,
+    /// Ticks in `epoch`, which differ from one epoch to the next while epochs warm up
+    pub ticks: u64,
+}
+
+impl EpochTicks {
+    pub fn from(account: &Account) -> Option<Self> {
+        account.state().ok()
+    }
+    pub fn to(&self, account: &mut Account) -> Option<()> {
+        account.set_state(self).ok()
+    }
+
+    pub fn size_of() -> usize {
+        serialized_size(&EpochTicks::default()).unwrap() as usize
+    }
+}
+
+pub fn create_account(difs: u64, epoch_ticks: &EpochTicks) -> Account {
+    let mut account = Account::new(difs, 0, EpochTicks::size_of(), &syscall::id());
+    epoch_ticks.to(&mut account).unwrap();
+    account
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_epoch_ticks_id() {
+        let ids = [("Sysca11EpochTicks11111111111111111111111111", id())];
+        // to get the bytes above:
+        //        ids.iter().for_each(|(name, _)| {
+        //            dbg!((name, bs58::decode(name).into_vec().unwrap()));
+        //        });
+        assert!(ids.iter().all(|(name, id)| *name == id.to_string()));
+        assert!(check_id(&id()));
+    }
+
+    #[test]
+    fn test_epoch_ticks_create_account() {
+        let epoch_ticks = EpochTicks {
+            epoch: 3,
+            ticks: 8192,
+        };
+        let account = create_account(1, &epoch_ticks);
+        assert_eq!(EpochTicks::from(&account), Some(epoch_ticks));
+    }
+}
@@ -2,6 +2,8 @@
 //!
 use crate::pubkey::Pubkey;
 
+pub mod epoch_ticks;
+pub mod runtime_params;
 pub mod slot_hashes;
 
 /// "Sysca11111111111111111111111111111111111111"
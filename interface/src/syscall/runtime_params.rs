@@ -0,0 +1,197 @@
+//! named accounts for synthesized data accounts for bank state, etc.
+//!
+//! this account carries the schedule of the runtime parameters, the protocol constants
+//! that may change from one epoch to the next without a new release of the code.  Banks
+//! resolve the parameters of their epoch from it at every epoch boundary, and fall back to
+//! the defaults in `timing` when the account doesn't exist.  Until a governance program
+//! owns it, the schedule is among the genesis block's accounts.
+//!
+use crate::account::Account;
+use crate::account_utils::State;
+use crate::pubkey::Pubkey;
+use crate::syscall;
+use crate::timing::{
+    MAX_HASH_AGE_IN_SECONDS, MAX_PROCESSING_AGE, MAX_RECENT_BLOCKHASHES,
+    NUM_BLOCKHASH_CONFIRMATIONS,
+};
+use bincode::serialized_size;
+use std::ops::Deref;
+
+/// "Sysca11RuntimeParams11111111111111111111111"
+///  runtime parameters account pubkey
+const ID: [u8; 32] = [
+    6, 167, 211, 138, 69, 219, 183, 57, 222, 11, 116, 140, 76, 240, 86, 233, 5, 128, 29, 10, 168,
+    221, 186, 165, 210, 128, 81, 243, 255, 0, 0, 0,
+];
+
+pub fn id() -> Pubkey {
+    Pubkey::new(&ID)
+}
+
+pub fn check_id(pubkey: &Pubkey) -> bool {
+    pubkey.as_ref() == ID
+}
+
+pub const MAX_SCHEDULE_ENTRIES: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct RuntimeParams {
+    /// Blockhashes a bank keeps, and so the oldest one a replayed transaction may use
+    pub max_recent_blockhashes: usize,
+    /// The oldest blockhash a leader takes a transaction into its block with
+    pub max_processing_age: usize,
+    /// The ancestors of a bank its `confirmed_last_blockhash` is taken from
+    pub num_blockhash_confirmations: usize,
+}
+
+impl Default for RuntimeParams {
+    fn default() -> Self {
+        Self {
+            max_recent_blockhashes: MAX_RECENT_BLOCKHASHES,
+            max_processing_age: MAX_PROCESSING_AGE,
+            num_blockhash_confirmations: NUM_BLOCKHASH_CONFIRMATIONS,
+        }
+    }
+}
+
+impl RuntimeParams {
+    /// The status cache only remembers signatures for MAX_HASH_AGE_IN_SECONDS roots, any
+    /// more blockhashes risk DuplicateSignature errors
+    pub fn is_valid(&self) -> bool {
+        self.max_recent_blockhashes <= MAX_HASH_AGE_IN_SECONDS
+            && self.max_processing_age <= self.max_recent_blockhashes
+    }
+}
+
+/// The runtime parameters by the epoch they activate in
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct RuntimeParamsSchedule {
+    // non-pub to keep control of size and order
+    inner: Vec<(u64, RuntimeParams)>,
+}
+
+impl RuntimeParamsSchedule {
+    pub fn from(account: &Account) -> Option<Self> {
+        account.state().ok()
+    }
+    pub fn to(&self, account: &mut Account) -> Option<()> {
+        account.set_state(self).ok()
+    }
+
+    pub fn size_of() -> usize {
+        serialized_size(&RuntimeParamsSchedule {
+            inner: vec![(0, RuntimeParams::default()); MAX_SCHEDULE_ENTRIES],
+        })
+        .unwrap() as usize
+    }
+
+    /// Activate `params` from `epoch` on, replacing what was scheduled for the same epoch.
+    /// Fails if `params` are invalid or the schedule is full
+    pub fn add(&mut self, epoch: u64, params: RuntimeParams) -> Option<()> {
+        if !params.is_valid() {
+            return None;
+        }
+        match self.inner.binary_search_by_key(&epoch, |(epoch, _)| *epoch) {
+            Ok(i) => self.inner[i].1 = params,
+            Err(i) => {
+                if self.inner.len() >= MAX_SCHEDULE_ENTRIES {
+                    return None;
+                }
+                self.inner.insert(i, (epoch, params));
+            }
+        }
+        Some(())
+    }
+
+    /// The parameters in effect during `epoch`
+    pub fn resolve(&self, epoch: u64) -> RuntimeParams {
+        self.inner
+            .iter()
+            .rev()
+            .find(|(activation_epoch, params)| *activation_epoch <= epoch && params.is_valid())
+            .map(|(_, params)| *params)
+            .unwrap_or_default()
+    }
+}
+
+impl Deref for RuntimeParamsSchedule {
+    type Target = Vec<(u64, RuntimeParams)>;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+pub fn create_account(difs: u64, schedule: &RuntimeParamsSchedule) -> Account {
+    let mut account = Account::new(difs, 0, RuntimeParamsSchedule::size_of(), &syscall::id());
+    schedule.to(&mut account).unwrap();
+    account
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_params_id() {
+        let ids = [("Sysca11RuntimeParams11111111111111111111111", id())];
+        // to get the bytes above:
+        //        ids.iter().for_each(|(name, _)| {
+        //            dbg!((name, bs58::decode(name).into_vec().unwrap()));
+        //        });
+        assert!(ids.iter().all(|(name, id)| *name == id.to_string()));
+        assert!(check_id(&id()));
+    }
+
+    #[test]
+    fn test_runtime_params_schedule() {
+        let mut schedule = RuntimeParamsSchedule::default();
+        assert_eq!(schedule.resolve(0), RuntimeParams::default());
+
+        let shorter = RuntimeParams {
+            max_recent_blockhashes: 60,
+            max_processing_age: 30,
+            num_blockhash_confirmations: 2,
+        };
+        let longer = RuntimeParams {
+            max_processing_age: 90,
+            ..RuntimeParams::default()
+        };
+        assert_eq!(schedule.add(5, longer), Some(()));
+        assert_eq!(schedule.add(2, shorter), Some(()));
+        assert_eq!(
+            schedule.add(
+                9,
+                RuntimeParams {
+                    max_recent_blockhashes: MAX_HASH_AGE_IN_SECONDS + 1,
+                    ..RuntimeParams::default()
+                }
+            ),
+            None
+        );
+        assert_eq!(schedule.len(), 2);
+
+        assert_eq!(schedule.resolve(1), RuntimeParams::default());
+        assert_eq!(schedule.resolve(2), shorter);
+        assert_eq!(schedule.resolve(4), shorter);
+        assert_eq!(schedule.resolve(5), longer);
+        assert_eq!(schedule.resolve(100), longer);
+
+        let account = create_account(1, &schedule);
+        assert_eq!(RuntimeParamsSchedule::from(&account), Some(schedule));
+    }
+
+    #[test]
+    fn test_runtime_params_schedule_full() {
+        let mut schedule = RuntimeParamsSchedule::default();
+        for epoch in 0..MAX_SCHEDULE_ENTRIES as u64 {
+            assert_eq!(schedule.add(epoch, RuntimeParams::default()), Some(()));
+        }
+        assert_eq!(
+            schedule.add(MAX_SCHEDULE_ENTRIES as u64, RuntimeParams::default()),
+            None
+        );
+        // replacing an entry still fits
+        assert_eq!(schedule.add(0, RuntimeParams::default()), Some(()));
+        create_account(1, &schedule);
+    }
+}
@@ -1,7 +1,9 @@
 use log::*;
 use crate::instruction::{AccountMeta, Instruction};
 use crate::instruction_processor_utils::DecodeError;
+use crate::nonce_state::NonceState;
 use crate::pubkey::Pubkey;
+use crate::syscall::slot_hashes;
 use crate::system_program;
 use num_derive::FromPrimitive;
 use morgan_helper::logHelper::*;
@@ -12,6 +14,10 @@ pub enum SystemError {
     ResultWithNegativeDifs,
     SourceNotSystemAccount,
     ResultWithNegativeReputations,
+    InvalidNonceState,
+    NonceNotExpired,
+    NonceAuthorityMismatch,
+    InvalidSlotHashes,
 }
 
 impl<T> DecodeError<T> for SystemError {
@@ -63,6 +69,28 @@ pub enum SystemInstruction {
     /// * Transaction::keys[0] - source
     /// * Transaction::keys[1] - destination
     TransferReputations { reputations: u64 },
+    /// Initialize a nonce account created with `NonceState::size()` bytes of data, storing
+    /// the newest slot hash as its nonce
+    /// * Transaction::keys[0] - payer
+    /// * Transaction::keys[1] - nonce account
+    /// * Transaction::keys[2] - slot_hashes syscall account
+    /// * authority - the key allowed to advance, withdraw from and reauthorize the account
+    InitializeNonceAccount { authority: Pubkey },
+    /// Replace the nonce with the newest slot hash, a durable nonce transaction starts with
+    /// this instruction, see `nonce_state::nonce_account_pubkey`
+    /// * Transaction::keys[0] - nonce authority
+    /// * Transaction::keys[1] - nonce account
+    /// * Transaction::keys[2] - slot_hashes syscall account
+    AdvanceNonceAccount,
+    /// Withdraw difs from a nonce account
+    /// * Transaction::keys[0] - nonce authority
+    /// * Transaction::keys[1] - nonce account
+    /// * Transaction::keys[2] - destination
+    WithdrawNonceAccount { difs: u64 },
+    /// Hand a nonce account to a new authority
+    /// * Transaction::keys[0] - nonce authority
+    /// * Transaction::keys[1] - nonce account
+    AuthorizeNonceAccount { authority: Pubkey },
 }
 
 pub fn create_account(
@@ -149,9 +177,10 @@ pub fn assign(from_pubkey: &Pubkey, program_id: &Pubkey) -> Instruction {
 }
 
 pub fn transfer(from_pubkey: &Pubkey, to_pubkey: &Pubkey, difs: u64) -> Instruction {
+    // Only credited, so transfers to the same account don't lock each other out
     let account_metas = vec![
         AccountMeta::new(*from_pubkey, true),
-        AccountMeta::new(*to_pubkey, false),
+        AccountMeta::new_credit_only(*to_pubkey, false),
     ];
     Instruction::new(
         system_program::id(),
@@ -172,6 +201,85 @@ pub fn transfer_reputations(from_pubkey: &Pubkey, to_pubkey: &Pubkey, reputation
     )
 }
 
+/// Create a nonce account holding `difs` and hand it to `authority`
+pub fn create_nonce_account(
+    from_pubkey: &Pubkey,
+    nonce_pubkey: &Pubkey,
+    authority: &Pubkey,
+    difs: u64,
+) -> Vec<Instruction> {
+    vec![
+        create_account(
+            from_pubkey,
+            nonce_pubkey,
+            difs,
+            NonceState::size() as u64,
+            &system_program::id(),
+        ),
+        Instruction::new(
+            system_program::id(),
+            &SystemInstruction::InitializeNonceAccount {
+                authority: *authority,
+            },
+            vec![
+                AccountMeta::new(*from_pubkey, true),
+                AccountMeta::new(*nonce_pubkey, false),
+                AccountMeta::new_credit_only(slot_hashes::id(), false),
+            ],
+        ),
+    ]
+}
+
+/// Advance the nonce of `nonce_pubkey`, the first instruction of a durable nonce transaction
+pub fn advance_nonce_account(nonce_pubkey: &Pubkey, authority: &Pubkey) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*nonce_pubkey, false),
+        AccountMeta::new_credit_only(slot_hashes::id(), false),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::AdvanceNonceAccount,
+        account_metas,
+    )
+}
+
+pub fn withdraw_nonce_account(
+    nonce_pubkey: &Pubkey,
+    authority: &Pubkey,
+    to_pubkey: &Pubkey,
+    difs: u64,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*nonce_pubkey, false),
+        AccountMeta::new(*to_pubkey, false),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::WithdrawNonceAccount { difs },
+        account_metas,
+    )
+}
+
+pub fn authorize_nonce_account(
+    nonce_pubkey: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    let account_metas = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*nonce_pubkey, false),
+    ];
+    Instruction::new(
+        system_program::id(),
+        &SystemInstruction::AuthorizeNonceAccount {
+            authority: *new_authority,
+        },
+        account_metas,
+    )
+}
+
 /// Create and sign new SystemInstruction::Transfer transaction to many destinations
 pub fn transfer_many(from_pubkey: &Pubkey, to_difs: &[(Pubkey, u64)]) -> Vec<Instruction> {
     to_difs
@@ -200,4 +308,19 @@ mod tests {
         assert_eq!(get_keys(&instructions[0]), vec![alice_pubkey, bob_pubkey]);
         assert_eq!(get_keys(&instructions[1]), vec![alice_pubkey, carol_pubkey]);
     }
+
+    #[test]
+    fn test_create_nonce_account() {
+        let from_pubkey = Pubkey::new_rand();
+        let nonce_pubkey = Pubkey::new_rand();
+        let authority = Pubkey::new_rand();
+
+        let instructions = create_nonce_account(&from_pubkey, &nonce_pubkey, &authority, 42);
+        assert_eq!(instructions.len(), 2);
+        assert_eq!(get_keys(&instructions[0]), vec![from_pubkey, nonce_pubkey]);
+        assert_eq!(
+            get_keys(&instructions[1]),
+            vec![from_pubkey, nonce_pubkey, slot_hashes::id()]
+        );
+    }
 }
@@ -34,6 +34,20 @@ pub fn create_user_account(
     create_account(from_keypair, to, recent_blockhash, difs, 0, &program_id)
 }
 
+/// Create and sign a transaction creating nonce account `nonce_pubkey` for `authority`
+pub fn create_nonce_account(
+    from_keypair: &Keypair,
+    nonce_pubkey: &Pubkey,
+    authority: &Pubkey,
+    difs: u64,
+    recent_blockhash: Hash,
+) -> Transaction {
+    let from_pubkey = from_keypair.pubkey();
+    let instructions =
+        system_instruction::create_nonce_account(&from_pubkey, nonce_pubkey, authority, difs);
+    Transaction::new_signed_instructions(&[from_keypair], instructions, recent_blockhash)
+}
+
 /// Create and sign new system_instruction::Assign transaction
 pub fn assign(from_keypair: &Keypair, recent_blockhash: Hash, program_id: &Pubkey) -> Transaction {
     let from_pubkey = from_keypair.pubkey();
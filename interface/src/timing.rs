@@ -26,6 +26,14 @@ pub const MAX_HASH_AGE_IN_SECONDS: usize = 120;
 // This must be <= MAX_HASH_AGE_IN_SECONDS, otherwise there's risk for DuplicateSignature errors
 pub const MAX_RECENT_BLOCKHASHES: usize = MAX_HASH_AGE_IN_SECONDS;
 
+/// Leaders only take transactions into a block while their blockhash is at most this old,
+/// leaving the rest of MAX_RECENT_BLOCKHASHES for the block to be replayed
+pub const MAX_PROCESSING_AGE: usize = MAX_RECENT_BLOCKHASHES / 2;
+
+/// The ancestors back a bank takes its confirmed blockhash from, see
+/// `Bank::confirmed_last_blockhash`
+pub const NUM_BLOCKHASH_CONFIRMATIONS: usize = 3;
+
 /// This is maximum time consumed in forwarding a transaction from one node to next, before
 /// it can be processed in the target node
 #[cfg(feature = "cuda")]
@@ -35,6 +43,14 @@ pub const MAX_TRANSACTION_FORWARDING_DELAY: usize = 4;
 #[cfg(not(feature = "cuda"))]
 pub const MAX_TRANSACTION_FORWARDING_DELAY: usize = 12;
 
+const SECONDS_PER_YEAR: f64 = 365.25 * 24.0 * 60.0 * 60.0;
+
+/// Slots in `years` at `tick_duration` a tick and `ticks_per_slot`
+pub fn years_as_slots(years: f64, tick_duration: &Duration, ticks_per_slot: u64) -> f64 {
+    years * SECONDS_PER_YEAR * 1_000_000_000.0
+        / (duration_as_ns(tick_duration) * ticks_per_slot) as f64
+}
+
 pub fn duration_as_ns(d: &Duration) -> u64 {
     d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos())
 }
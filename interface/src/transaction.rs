@@ -51,6 +51,12 @@ pub enum TransactionError {
 
     /// Transaction contains an invalid account reference
     InvalidAccountIndex,
+
+    /// Transaction loads an address lookup table that doesn't exist
+    AddressLookupTableNotFound,
+
+    /// Transaction loads an address beyond the end of an address lookup table
+    InvalidAddressLookupTableIndex,
 }
 
 pub type Result<T> = result::Result<T, TransactionError>;
@@ -219,6 +225,19 @@ impl Transaction {
             .all(|signature| *signature != Signature::default())
     }
 
+    /// Verify each signature against the account key at the same position
+    pub fn verify_signatures(&self) -> bool {
+        let message = self.message();
+        if self.signatures.len() > message.account_keys.len() {
+            return false;
+        }
+        let message_data = self.message_data();
+        self.signatures
+            .iter()
+            .zip(&message.account_keys)
+            .all(|(signature, pubkey)| signature.verify(pubkey.as_ref(), &message_data))
+    }
+
     /// Verify that references in the instructions are valid
     pub fn verify_refs(&self) -> bool {
         let message = self.message();
@@ -227,7 +246,9 @@ impl Transaction {
                 return false;
             }
             for account_index in &instruction.accounts {
-                if (*account_index as usize) >= message.account_keys.len() {
+                if (*account_index as usize)
+                    >= message.account_keys.len() + message.num_lookup_keys()
+                {
                     return false;
                 }
             }
@@ -241,6 +262,7 @@ mod tests {
     use super::*;
     use crate::hash::hash;
     use crate::instruction::AccountMeta;
+    use crate::message::MessageAddressTableLookup;
     use crate::signature::Keypair;
     use crate::system_instruction;
     use bincode::{deserialize, serialize, serialized_size};
@@ -252,6 +274,21 @@ mod tests {
         instruction.program_id(&message.account_keys)
     }
 
+    #[test]
+    fn test_verify_signatures() {
+        let keypair0 = Keypair::new();
+        let keypair1 = Keypair::new();
+        let ix = system_instruction::transfer(&keypair0.pubkey(), &keypair1.pubkey(), 1);
+        let mut tx = Transaction::new_signed_instructions(&[&keypair0], vec![ix], Hash::default());
+        assert!(tx.verify_signatures());
+
+        tx.signatures[0] = keypair1.sign_message(&tx.message_data());
+        assert!(!tx.verify_signatures());
+
+        tx.signatures = vec![Signature::default(); tx.message.account_keys.len() + 1];
+        assert!(!tx.verify_signatures());
+    }
+
     #[test]
     fn test_refs() {
         let key = Keypair::new();
@@ -321,6 +358,29 @@ mod tests {
         assert!(!tx.verify_refs());
     }
 
+    #[test]
+    fn test_refs_address_table_lookups() {
+        let key = Keypair::new();
+        let instructions = vec![CompiledInstruction::new(1, &(), vec![2])];
+        let mut tx = Transaction::new_with_compiled_instructions(
+            &[&key],
+            &[],
+            Hash::default(),
+            vec![Pubkey::default()],
+            instructions,
+        );
+        tx.message.address_table_lookups = vec![MessageAddressTableLookup {
+            account_key: Pubkey::new_rand(),
+            writable_indexes: vec![0],
+            readonly_indexes: vec![],
+        }];
+        assert!(tx.verify_refs());
+
+        // program ids can't be loaded from a lookup table
+        tx.message.instructions[0].program_ids_index = 2;
+        assert!(!tx.verify_refs());
+    }
+
     fn create_sample_transaction() -> Transaction {
         let keypair = Keypair::from_bytes(&[
             48, 83, 2, 1, 1, 48, 5, 6, 3, 43, 101, 112, 4, 34, 4, 32, 255, 101, 36, 24, 124, 23,
@@ -1,7 +1,9 @@
 use clap::{crate_description, crate_name, crate_version, App, Arg, SubCommand};
+use morgan_interface::keystore::{encrypt_keypair, read_passphrase, ScryptParams};
 use morgan_interface::pubkey::write_pubkey;
 use morgan_interface::signature::{gen_keypair_file, read_keypair, KeypairUtil};
 use std::error;
+use std::fs;
 
 fn main() -> Result<(), Box<dyn error::Error>> {
     let matches = App::new(crate_name!())
@@ -46,6 +48,27 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                         .help("Path to generated file"),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("encrypt")
+                .about("Encrypt a keypair file into a passphrase protected keystore")
+                .arg(
+                    Arg::with_name("infile")
+                        .index(1)
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Path to keypair file"),
+                )
+                .arg(
+                    Arg::with_name("outfile")
+                        .short("o")
+                        .long("outfile")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Path to generated keystore, - for stdout"),
+                ),
+        )
         .get_matches();
 
     match matches.subcommand() {
@@ -66,6 +89,21 @@ fn main() -> Result<(), Box<dyn error::Error>> {
                 println!("{}", keypair.pubkey());
             }
         }
+        ("encrypt", Some(encrypt_matches)) => {
+            let keypair = read_keypair(encrypt_matches.value_of("infile").unwrap())?;
+            let passphrase = read_passphrase("New keystore passphrase")?;
+            if read_passphrase("Repeat passphrase")? != passphrase {
+                Err("passphrases don't match")?;
+            }
+            let keystore = encrypt_keypair(&keypair, &passphrase, ScryptParams::default())?;
+
+            let outfile = encrypt_matches.value_of("outfile").unwrap();
+            if outfile == "-" {
+                println!("{}", keystore);
+            } else {
+                fs::write(outfile, keystore)?;
+            }
+        }
         match_tuple => {
             let working_matches = if let (_, Some(new_matches)) = match_tuple {
                 new_matches
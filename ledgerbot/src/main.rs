@@ -1,9 +1,71 @@
-use clap::{crate_description, crate_name, crate_version, App, Arg, SubCommand};
+use clap::{
+    crate_description, crate_name, crate_version, App, Arg, ArgGroup, ArgMatches, SubCommand,
+};
 use morgan::blockBufferPool::Blocktree;
 use morgan::blockBufferPoolProcessor::process_blocktree;
+use morgan::snapshotDownload::{fetch_snapshot, SnapshotTrust};
 use morgan_interface::genesis_block::GenesisBlock;
+use morgan_interface::hash::Hash;
 use std::io::{stdout, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::path::Path;
 use std::process::exit;
+use std::time::Duration;
+
+fn parse_addr(matches: &ArgMatches, name: &str) -> SocketAddr {
+    let value = matches.value_of(name).unwrap();
+    value
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .unwrap_or_else(|| {
+            eprintln!("Failed to parse --{} address {}", name, value);
+            exit(1);
+        })
+}
+
+/// Download a snapshot from a peer, check it and install it under the ledger for the validator
+/// to restore from, refusing it if it doesn't match
+fn download_snapshot(ledger_path: &str, matches: &ArgMatches) {
+    let rpc_addr = parse_addr(matches, "rpc-addr");
+    let slot = matches
+        .value_of("slot")
+        .map(|slot| slot.parse().expect("please pass a number for --slot"));
+    let trust = match matches.value_of("trusted-bank-hash") {
+        Some(hash) => SnapshotTrust::BankHash(hash.parse::<Hash>().unwrap_or_else(|err| {
+            eprintln!("Failed to parse --trusted-bank-hash {}: {:?}", hash, err);
+            exit(1);
+        })),
+        None => SnapshotTrust::Gossip {
+            entrypoint: parse_addr(matches, "entrypoint"),
+            timeout: Duration::from_secs(
+                matches
+                    .value_of("gossip-timeout")
+                    .unwrap()
+                    .parse()
+                    .expect("please pass a number for --gossip-timeout"),
+            ),
+        },
+    };
+    let snapshot_path = match matches.value_of("snapshot-path") {
+        Some(snapshot_path) => Path::new(snapshot_path).to_path_buf(),
+        None => Path::new(ledger_path).join("snapshot"),
+    };
+
+    match fetch_snapshot(&rpc_addr, slot, &trust, &snapshot_path) {
+        Ok(archive_path) => {
+            println!("Installed {:?}", archive_path);
+            println!(
+                "Start the validator with --snapshot-path {:?} to restore from it",
+                snapshot_path
+            );
+        }
+        Err(err) => {
+            eprintln!("Snapshot refused: {}", err);
+            exit(1);
+        }
+    }
+}
 
 fn main() {
     morgan_logger::setup();
@@ -43,10 +105,68 @@ fn main() {
         .subcommand(SubCommand::with_name("print").about("Print the ledger"))
         .subcommand(SubCommand::with_name("json").about("Print the ledger in JSON format"))
         .subcommand(SubCommand::with_name("verify").about("Verify the ledger's PoH"))
+        .subcommand(
+            SubCommand::with_name("download-snapshot")
+                .about("Download a snapshot from a peer, verify it and install it under the ledger")
+                .arg(
+                    Arg::with_name("rpc-addr")
+                        .long("rpc-addr")
+                        .value_name("HOST:PORT")
+                        .takes_value(true)
+                        .required(true)
+                        .help("RPC address of the peer to download the snapshot from"),
+                )
+                .arg(
+                    Arg::with_name("slot")
+                        .long("slot")
+                        .value_name("SLOT")
+                        .takes_value(true)
+                        .help("Download the snapshot of this slot instead of the peer's newest"),
+                )
+                .arg(
+                    Arg::with_name("trusted-bank-hash")
+                        .long("trusted-bank-hash")
+                        .value_name("HASH")
+                        .takes_value(true)
+                        .help("Refuse the snapshot unless its bank has this hash"),
+                )
+                .arg(
+                    Arg::with_name("entrypoint")
+                        .long("entrypoint")
+                        .value_name("HOST:PORT")
+                        .takes_value(true)
+                        .help("Refuse the snapshot unless a stake-weighted majority of the cluster gossiping here attests to its accounts hash. Only snapshots of the first root of an epoch are attested"),
+                )
+                .group(
+                    ArgGroup::with_name("trust")
+                        .args(&["trusted-bank-hash", "entrypoint"])
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("gossip-timeout")
+                        .long("gossip-timeout")
+                        .value_name("SECS")
+                        .takes_value(true)
+                        .default_value("60")
+                        .help("Wait this long for the cluster's attestations"),
+                )
+                .arg(
+                    Arg::with_name("snapshot-path")
+                        .long("snapshot-path")
+                        .value_name("DIR")
+                        .takes_value(true)
+                        .help("Install the snapshot in this directory [default: the ledger's snapshot directory]"),
+                ),
+        )
         .get_matches();
 
     let ledger_path = matches.value_of("ledger").unwrap();
 
+    if let ("download-snapshot", Some(download_matches)) = matches.subcommand() {
+        download_snapshot(ledger_path, download_matches);
+        return;
+    }
+
     let genesis_block = GenesisBlock::load(ledger_path).unwrap_or_else(|err| {
         eprintln!(
             "Failed to open ledger genesis_block at {}: {}",
@@ -111,7 +231,7 @@ fn main() {
             }
             stdout().write_all(b"\n]}\n").expect("close array");
         }
-        ("verify", _) => match process_blocktree(&genesis_block, &blocktree, None) {
+        ("verify", _) => match process_blocktree(&genesis_block, &blocktree, None, None, None, None) {
             Ok((_bank_forks, bank_forks_info, _)) => {
                 println!("{:?}", bank_forks_info);
             }
@@ -28,6 +28,17 @@ fn bad_arguments() {
     assert!(!run_ledgerbot(&["-l", "invalid_ledger", "verify"])
         .status
         .success());
+
+    // A snapshot is only downloaded with something to verify it against
+    assert!(!run_ledgerbot(&[
+        "-l",
+        "invalid_ledger",
+        "download-snapshot",
+        "--rpc-addr",
+        "127.0.0.1:8899"
+    ])
+    .status
+    .success());
 }
 
 #[test]
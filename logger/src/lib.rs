@@ -1,16 +1,81 @@
-//! The `logger` module provides a setup function for `env_logger`. Its only function,
-//! `setup()` may be called multiple times.
+//! The `logger` module configures `env_logger` from `RUST_LOG`. `setup()` may be called
+//! multiple times, and `setup_with_filter()` swaps the filter of a running process.
 
 use env_logger;
-use std::sync::{Once, ONCE_INIT};
+use lazy_static::lazy_static;
+use std::sync::{Once, RwLock, ONCE_INIT};
 
 static INIT: Once = ONCE_INIT;
 
+lazy_static! {
+    static ref LOGGER: RwLock<Option<env_logger::Logger>> = RwLock::new(None);
+}
+
+// The logger registered with `log`, forwards to whichever `env_logger::Logger` is current
+struct LoggerShim {}
+
+impl log::Log for LoggerShim {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        match &*LOGGER.read().unwrap() {
+            Some(logger) => logger.enabled(metadata),
+            None => false,
+        }
+    }
+
+    fn log(&self, record: &log::Record) {
+        if let Some(logger) = &*LOGGER.read().unwrap() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+fn replace_logger(logger: env_logger::Logger) {
+    log::set_max_level(logger.filter());
+    *LOGGER.write().unwrap() = Some(logger);
+    // only the first call registers the shim, later ones just swapped the logger above
+    let _ = log::set_boxed_logger(Box::new(LoggerShim {}));
+}
+
 /// Setup function that is only run once, even if called multiple times.
 pub fn setup() {
     INIT.call_once(|| {
-        env_logger::Builder::from_default_env()
-            .default_format_timestamp_nanos(true)
-            .init();
+        replace_logger(
+            env_logger::Builder::from_default_env()
+                .default_format_timestamp_nanos(true)
+                .build(),
+        );
     });
 }
+
+/// Replace the log filter, in `RUST_LOG` syntax, e.g. `morgan=info,morgan::forkSelection=trace`.
+/// Takes effect immediately for all threads.
+pub fn setup_with_filter(filter: &str) {
+    INIT.call_once(|| ());
+    replace_logger(
+        env_logger::Builder::new()
+            .parse_filters(filter)
+            .default_format_timestamp_nanos(true)
+            .build(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::{log_enabled, Level};
+
+    #[test]
+    fn test_setup_with_filter() {
+        setup();
+        setup_with_filter("error,morgan_logger=trace");
+        assert!(log_enabled!(Level::Trace));
+        assert!(log_enabled!(target: "morgan", Level::Error));
+        assert!(!log_enabled!(target: "morgan", Level::Warn));
+
+        setup_with_filter("warn");
+        assert!(!log_enabled!(Level::Trace));
+        assert!(log_enabled!(target: "morgan", Level::Warn));
+    }
+}
@@ -17,7 +17,7 @@ fullnode_usage() {
   cat <<EOF
 
 Fullnode Usage:
-usage: $0 [--blockstream PATH] [--init-complete-file FILE] [--label LABEL] [--stake DIFS] [--no-voting] [--rpc-port port] [rsync network path to bootstrap leader configuration] [cluster entry point]
+usage: $0 [--blockstream PATH] [--init-complete-file FILE] [--label LABEL] [--stake DIFS] [--no-voting] [--rpc-only] [--rpc-port port] [rsync network path to bootstrap leader configuration] [cluster entry point]
 
 Start a validator or a replicator
 
@@ -27,6 +27,7 @@ Start a validator or a replicator
                               multiple fullnodes in the same workspace
   --stake DIFS          - Number of difs to stake
   --no-voting               - start node without vote signer
+  --rpc-only                - with --no-voting, only replay the ledger and serve RPC
   --rpc-port port           - custom RPC port for this node
   --no-restart              - do not restart the node if it exits
 
@@ -216,6 +217,9 @@ while [[ -n $1 ]]; do
     elif [[ $1 = --no-voting ]]; then
       args+=("$1")
       shift
+    elif [[ $1 = --rpc-only ]]; then
+      args+=("$1")
+      shift
     elif [[ $1 = --no-sigverify ]]; then
       args+=("$1")
       shift
@@ -0,0 +1,79 @@
+#![feature(test)]
+
+extern crate test;
+
+use morgan_runtime::bank::*;
+use morgan_runtime::bench_utils::{
+    create_transfer_transactions, BenchAccounts, LARGE_ACCOUNT_COUNT, STORE_BATCH_SIZE,
+    TRANSFER_BENCH_ITERATIONS,
+};
+use morgan_interface::genesis_block::create_genesis_block;
+use morgan_interface::pubkey::Pubkey;
+use rand::{thread_rng, Rng};
+use test::Bencher;
+
+#[bench]
+fn bench_accounts_db_store_new(bencher: &mut Bencher) {
+    let accounts = BenchAccounts::new(LARGE_ACCOUNT_COUNT, 0);
+    let mut fork = 0;
+    bencher.iter(|| {
+        // A fresh batch of accounts into its own fork, the index grows on every iteration
+        let pubkeys: Vec<_> = (0..STORE_BATCH_SIZE).map(|_| Pubkey::new_rand()).collect();
+        fork += 1;
+        accounts.store(fork, &pubkeys, 0);
+    });
+}
+
+#[bench]
+fn bench_accounts_db_store_existing(bencher: &mut Bencher) {
+    let accounts = BenchAccounts::new(LARGE_ACCOUNT_COUNT, 0);
+    let mut fork = 0;
+    bencher.iter(|| {
+        let start = thread_rng().gen_range(0, LARGE_ACCOUNT_COUNT - STORE_BATCH_SIZE);
+        fork += 1;
+        accounts.store(fork, &accounts.pubkeys[start..start + STORE_BATCH_SIZE], 0);
+    });
+}
+
+#[bench]
+fn bench_accounts_db_load(bencher: &mut Bencher) {
+    let accounts = BenchAccounts::new(LARGE_ACCOUNT_COUNT, 0);
+    bencher.iter(|| {
+        let index = thread_rng().gen_range(0, LARGE_ACCOUNT_COUNT);
+        assert!(accounts.load(0, &accounts.pubkeys[index]).is_some());
+    });
+}
+
+#[bench]
+fn bench_accounts_db_load_missing(bencher: &mut Bencher) {
+    let accounts = BenchAccounts::new(LARGE_ACCOUNT_COUNT, 0);
+    bencher.iter(|| {
+        assert!(accounts.load(0, &Pubkey::new_rand()).is_none());
+    });
+}
+
+#[bench]
+fn bench_accounts_db_hash_internal_state(bencher: &mut Bencher) {
+    let accounts = BenchAccounts::new(LARGE_ACCOUNT_COUNT, 0);
+    bencher.iter(|| {
+        assert!(accounts.hash_internal_state(0).is_some());
+    });
+}
+
+#[bench]
+fn bench_bank_process_transfer_transactions(bencher: &mut Bencher) {
+    let (genesis_block, mint_keypair) = create_genesis_block(1_000_000_000_000);
+    let bank = Bank::new(&genesis_block);
+    let transactions = create_transfer_transactions(
+        &bank,
+        &mint_keypair,
+        STORE_BATCH_SIZE,
+        TRANSFER_BENCH_ITERATIONS,
+    );
+    bencher.iter(|| {
+        // Since bencher runs this multiple times, we need to clear the signatures.
+        bank.clear_signatures();
+        let results = bank.process_transactions(&transactions);
+        assert!(results.iter().all(Result::is_ok));
+    });
+}
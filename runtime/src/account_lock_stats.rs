@@ -0,0 +1,134 @@
+//! The `account_lock_stats` module keeps count of how contended the accounts transactions lock
+//! are.  A transaction that can't lock an account because another one holds it is sent back
+//! to be retried, so a handful of hot accounts can serialize the whole pipeline.  The stats are
+//! shared by a bank and all its descendants, and rank accounts by how long transactions waited
+//! on them.
+
+use hashbrown::HashMap;
+use morgan_interface::pubkey::Pubkey;
+use serde_derive::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::sync::Mutex;
+
+/// Most accounts kept track of, past it only the hottest half is kept
+pub const MAX_TRACKED_ACCOUNTS: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountLockStat {
+    /// Times a transaction locked the account
+    pub locks: u64,
+    /// Times a transaction was turned away because another one held the account
+    pub contended: u64,
+    /// Microseconds transactions waited on the account, from the first one turned away to the
+    /// next one locking it
+    pub wait_us: u64,
+    /// Microseconds the account was held locked
+    pub hold_us: u64,
+}
+
+impl AccountLockStat {
+    fn add(&mut self, other: &AccountLockStat) {
+        self.locks = self.locks.saturating_add(other.locks);
+        self.contended = self.contended.saturating_add(other.contended);
+        self.wait_us = self.wait_us.saturating_add(other.wait_us);
+        self.hold_us = self.hold_us.saturating_add(other.hold_us);
+    }
+
+    fn hotness(&self) -> Reverse<(u64, u64, u64)> {
+        Reverse((self.wait_us, self.contended, self.hold_us))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct AccountLockStats {
+    stats: Mutex<HashMap<Pubkey, AccountLockStat>>,
+}
+
+impl AccountLockStats {
+    /// Add up `stats`, what the locks of a batch of transactions came to
+    pub fn record<I>(&self, stats: I)
+    where
+        I: IntoIterator<Item = (Pubkey, AccountLockStat)>,
+    {
+        let mut all_stats = self.stats.lock().unwrap();
+        for (pubkey, stat) in stats {
+            all_stats.entry(pubkey).or_default().add(&stat);
+        }
+        if all_stats.len() > MAX_TRACKED_ACCOUNTS {
+            let mut ranked: Vec<_> = all_stats.drain().collect();
+            ranked.sort_by_key(|(_, stat)| stat.hotness());
+            ranked.truncate(MAX_TRACKED_ACCOUNTS / 2);
+            all_stats.extend(ranked);
+        }
+    }
+
+    pub fn get(&self, pubkey: &Pubkey) -> Option<AccountLockStat> {
+        self.stats.lock().unwrap().get(pubkey).cloned()
+    }
+
+    /// The `num` accounts transactions waited on the longest, hottest first
+    pub fn hottest(&self, num: usize) -> Vec<(Pubkey, AccountLockStat)> {
+        let mut ranked: Vec<_> = self
+            .stats
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(pubkey, stat)| (*pubkey, *stat))
+            .collect();
+        ranked.sort_by_key(|(_, stat)| stat.hotness());
+        ranked.truncate(num);
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_stat(contended: u64, wait_us: u64, hold_us: u64) -> AccountLockStat {
+        AccountLockStat {
+            locks: 1,
+            contended,
+            wait_us,
+            hold_us,
+        }
+    }
+
+    #[test]
+    fn test_account_lock_stats_hottest() {
+        let stats = AccountLockStats::default();
+        let cold = Pubkey::new_rand();
+        let warm = Pubkey::new_rand();
+        let hot = Pubkey::new_rand();
+        stats.record(vec![
+            (cold, new_stat(0, 0, 5)),
+            (warm, new_stat(1, 10, 1)),
+            (hot, new_stat(1, 10, 1)),
+        ]);
+        stats.record(vec![(hot, new_stat(2, 20, 1))]);
+
+        assert_eq!(
+            stats.get(&hot),
+            Some(AccountLockStat {
+                locks: 2,
+                contended: 3,
+                wait_us: 30,
+                hold_us: 2,
+            })
+        );
+        let hottest: Vec<_> = stats.hottest(2).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(hottest, vec![hot, warm]);
+        assert_eq!(stats.hottest(10).len(), 3);
+    }
+
+    #[test]
+    fn test_account_lock_stats_bounded() {
+        let stats = AccountLockStats::default();
+        let hot = Pubkey::new_rand();
+        stats.record(vec![(hot, new_stat(1, 1, 0))]);
+        stats.record((0..MAX_TRACKED_ACCOUNTS).map(|_| (Pubkey::new_rand(), new_stat(0, 0, 0))));
+        assert_eq!(stats.stats.lock().unwrap().len(), MAX_TRACKED_ACCOUNTS / 2);
+        assert!(stats.get(&hot).is_some());
+    }
+}
@@ -1,37 +1,44 @@
+use crate::account_lock_stats::{AccountLockStat, AccountLockStats};
 use crate::accounts_db::{
-    get_paths_vec, AccountInfo, AccountStorage, AccountsDB, ErrorCounters, InstructionAccounts,
-    InstructionLoaders,
+    expect_index_io, get_paths_vec, AccountInfo, AccountStorage, AccountsDB, ErrorCounters,
+    InstructionLookupKeys, PinnedAccounts, TransactionLoadResult,
 };
+use crate::accounts_delta_hash::AccountsDeltaHash;
 use crate::accounts_index::{AccountsIndex, Fork};
 use crate::append_vec::StoredAccount;
 use crate::message_processor::has_duplicates;
 use bincode::serialize;
 use hashbrown::{HashMap, HashSet};
 use log::*;
+use morgan_address_lookup_table_api::address_lookup_table_state::AddressLookupTableState;
 use morgan_metricbot::inc_new_counter_error;
 use morgan_interface::account::Account;
 use morgan_interface::fee_calculator::FeeCalculator;
 use morgan_interface::hash::{Hash, Hasher};
+use morgan_interface::message::Message;
 use morgan_interface::native_loader;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil};
 use morgan_interface::system_program;
+use morgan_interface::timing::duration_as_us;
 use morgan_interface::transaction::Result;
 use morgan_interface::transaction::{Transaction, TransactionError};
 use std::borrow::Borrow;
 use std::env;
 use std::fs::remove_dir_all;
 use std::iter::once;
-use std::ops::Neg;
+use std::ops::{Neg, RangeInclusive};
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const ACCOUNTSDB_DIR: &str = "accountsdb";
 const NUM_ACCOUNT_DIRS: usize = 4;
 const WAIT_FOR_PARENT_MS: u64 = 5;
+/// Accounts `load_all` loads per hold of the index and storage locks
+const LOAD_ALL_CHUNK_SIZE: usize = 1024;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub enum AccountLockType {
@@ -41,6 +48,54 @@ pub enum AccountLockType {
 
 type AccountLocks = Mutex<HashSet<Pubkey>>;
 
+/// Locks held by the transactions in the pipeline. A credit-debit account is locked by one
+/// transaction at a time, a credit-only account is shared by every transaction reading or
+/// crediting it.
+#[derive(Default)]
+struct PipelineLocks {
+    credit_debit_locks: HashSet<Pubkey>,
+    credit_only_locks: HashMap<Pubkey, usize>,
+    // When each locked account was locked, and when a transaction was first turned away from
+    // each account held since
+    locked_at: HashMap<Pubkey, Instant>,
+    contended_since: HashMap<Pubkey, Instant>,
+    // What the locks came to since they were last added to the `AccountLockStats`
+    lock_stats: HashMap<Pubkey, AccountLockStat>,
+}
+
+impl PipelineLocks {
+    fn lock_stat(&mut self, key: &Pubkey) -> &mut AccountLockStat {
+        self.lock_stats.entry(*key).or_default()
+    }
+
+    fn record_contended(&mut self, key: &Pubkey) {
+        self.contended_since
+            .entry(*key)
+            .or_insert_with(Instant::now);
+        self.lock_stat(key).contended += 1;
+    }
+
+    /// `key` was locked, only the first of the transactions sharing a credit-only account
+    /// starts holding it
+    fn record_locked(&mut self, key: &Pubkey) {
+        let now = Instant::now();
+        self.locked_at.entry(*key).or_insert(now);
+        let waited = self
+            .contended_since
+            .remove(key)
+            .map(|since| duration_as_us(&now.duration_since(since)));
+        let stat = self.lock_stat(key);
+        stat.locks += 1;
+        stat.wait_us += waited.unwrap_or(0);
+    }
+
+    fn record_unlocked(&mut self, key: &Pubkey) {
+        if let Some(locked_at) = self.locked_at.remove(key) {
+            self.lock_stat(key).hold_us += duration_as_us(&locked_at.elapsed());
+        }
+    }
+}
+
 // Locks for accounts that are currently being recorded + committed
 type RecordLocks = (
     // Record Locks for the current bank
@@ -57,11 +112,14 @@ pub struct Accounts {
     pub accounts_db: Arc<AccountsDB>,
 
     /// set of accounts which are currently in the pipeline
-    account_locks: AccountLocks,
+    account_locks: Mutex<PipelineLocks>,
 
     /// set of accounts which are about to record + commit
     record_locks: Mutex<RecordLocks>,
 
+    /// how contended the accounts locked by this bank and its ancestors were
+    lock_stats: Arc<AccountLockStats>,
+
     /// List of persistent stores
     paths: String,
 
@@ -112,16 +170,30 @@ impl Accounts {
     }
 
     pub fn new(in_paths: Option<String>) -> Self {
+        Self::new_with_index_memory_budget(in_paths, None)
+    }
+
+    /// Accounts whose index overflows to disk past `index_memory_budget` bytes, if given
+    pub fn new_with_index_memory_budget(
+        in_paths: Option<String>,
+        index_memory_budget: Option<usize>,
+    ) -> Self {
         let (paths, own_paths) = if in_paths.is_none() {
             (Self::make_default_paths(), true)
         } else {
             (in_paths.unwrap(), false)
         };
-        let accounts_db = Arc::new(AccountsDB::new(&paths));
+        let accounts_db = Arc::new(match index_memory_budget {
+            Some(index_memory_budget) => {
+                AccountsDB::new_with_index_memory_budget(&paths, index_memory_budget)
+            }
+            None => AccountsDB::new(&paths),
+        });
         Accounts {
             accounts_db,
-            account_locks: Mutex::new(HashSet::new()),
+            account_locks: Mutex::new(PipelineLocks::default()),
             record_locks: Mutex::new((Arc::new(Mutex::new(HashSet::new())), vec![])),
+            lock_stats: Arc::new(AccountLockStats::default()),
             paths,
             own_paths,
         }
@@ -151,18 +223,55 @@ impl Accounts {
 
         Accounts {
             accounts_db,
-            account_locks: Mutex::new(HashSet::new()),
+            account_locks: Mutex::new(PipelineLocks::default()),
             record_locks: Mutex::new((Arc::new(Mutex::new(HashSet::new())), parent_record_locks)),
+            lock_stats: parent.lock_stats.clone(),
             paths: parent.paths.clone(),
             own_paths: parent.own_paths,
         }
     }
 
+    /// Resolve the keys a message loads from address lookup tables, credit-debit keys first.
+    /// Tables are append-only, so a lookup resolves the same way for the life of a table.
+    fn load_lookup_keys<F>(
+        message: &Message,
+        load: F,
+    ) -> Result<(InstructionLookupKeys, InstructionLookupKeys)>
+    where
+        F: Fn(&Pubkey) -> Option<Account>,
+    {
+        let mut writable_keys = vec![];
+        let mut readonly_keys = vec![];
+        for lookup in &message.address_table_lookups {
+            let table = load(&lookup.account_key)
+                .filter(|account| morgan_address_lookup_table_api::check_id(&account.owner))
+                .and_then(|account| AddressLookupTableState::table_from(&account))
+                .ok_or(TransactionError::AddressLookupTableNotFound)?;
+            let address = |index: &u8| {
+                table
+                    .addresses
+                    .get(*index as usize)
+                    .cloned()
+                    .ok_or(TransactionError::InvalidAddressLookupTableIndex)
+            };
+            for index in &lookup.writable_indexes {
+                writable_keys.push(address(index)?);
+            }
+            for index in &lookup.readonly_indexes {
+                readonly_keys.push(address(index)?);
+            }
+        }
+        Ok((writable_keys, readonly_keys))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn load_tx_accounts(
+        accounts_db: &AccountsDB,
         storage: &AccountStorage,
         ancestors: &HashMap<Fork, usize>,
         accounts_index: &AccountsIndex<AccountInfo>,
         tx: &Transaction,
+        lookup_keys: &[Pubkey],
         fee: u64,
         error_counters: &mut ErrorCounters,
     ) -> Result<Vec<Account>> {
@@ -171,19 +280,23 @@ impl Accounts {
         if tx.signatures.is_empty() && fee != 0 {
             Err(TransactionError::MissingSignatureForFee)
         } else {
+            let keys: Vec<_> = message.account_keys.iter().chain(lookup_keys).collect();
             // Check for unique account keys
-            if has_duplicates(&message.account_keys) {
+            if has_duplicates(&keys) {
                 error_counters.account_loaded_twice += 1;
                 return Err(TransactionError::AccountLoadedTwice);
             }
 
             // There is no way to predict what program will execute without an error
             // If a fee can pay for execution then the program will be scheduled
+            // Lookup keys follow the program ids of a versioned message, so its program accounts
+            // are kept to line the accounts up with the instruction indexes
             let mut called_accounts: Vec<Account> = vec![];
-            for key in &message.account_keys {
-                if !message.program_ids().contains(&key) {
+            for key in keys {
+                if message.is_versioned() || !message.program_ids().contains(&key) {
                     called_accounts.push(
-                        AccountsDB::load(storage, ancestors, accounts_index, key)
+                        accounts_db
+                            .load(storage, ancestors, accounts_index, key)
                             .map(|(account, _)| account)
                             .unwrap_or_default(),
                     );
@@ -206,6 +319,7 @@ impl Accounts {
     }
 
     fn load_executable_accounts(
+        accounts_db: &AccountsDB,
         storage: &AccountStorage,
         ancestors: &HashMap<Fork, usize>,
         accounts_index: &AccountsIndex<AccountInfo>,
@@ -227,7 +341,8 @@ impl Accounts {
             }
             depth += 1;
 
-            let program = match AccountsDB::load(storage, ancestors, accounts_index, &program_id)
+            let program = match accounts_db
+                .load(storage, ancestors, accounts_index, &program_id)
                 .map(|(account, _)| account)
             {
                 Some(program) => program,
@@ -250,6 +365,7 @@ impl Accounts {
 
     /// For each program_id in the transaction, load its loaders.
     fn load_loaders(
+        accounts_db: &AccountsDB,
         storage: &AccountStorage,
         ancestors: &HashMap<Fork, usize>,
         accounts_index: &AccountsIndex<AccountInfo>,
@@ -267,6 +383,7 @@ impl Accounts {
                 }
                 let program_id = message.account_keys[ix.program_ids_index as usize];
                 Self::load_executable_accounts(
+                    accounts_db,
                     storage,
                     ancestors,
                     accounts_index,
@@ -284,7 +401,7 @@ impl Accounts {
         lock_results: Vec<Result<()>>,
         fee_calculator: &FeeCalculator,
         error_counters: &mut ErrorCounters,
-    ) -> Vec<Result<(InstructionAccounts, InstructionLoaders)>> {
+    ) -> Vec<Result<TransactionLoadResult>> {
         //PERF: hold the lock to scan for the references, but not to clone the accounts
         //TODO: two locks usually leads to deadlocks, should this be one structure?
         let accounts_index = self.accounts_db.accounts_index.read().unwrap();
@@ -294,22 +411,33 @@ impl Accounts {
             .map(|etx| match etx {
                 (tx, Ok(())) => {
                     let fee = fee_calculator.calculate_fee(tx.message());
+                    let (mut lookup_keys, readonly_lookup_keys) =
+                        Self::load_lookup_keys(tx.message(), |key| {
+                            self.accounts_db
+                                .load(&storage, ancestors, &accounts_index, key)
+                                .map(|(account, _)| account)
+                        })?;
+                    lookup_keys.extend(readonly_lookup_keys);
                     let accounts = Self::load_tx_accounts(
+                        &self.accounts_db,
                         &storage,
                         ancestors,
                         &accounts_index,
                         tx,
+                        &lookup_keys,
                         fee,
                         error_counters,
                     )?;
                     let loaders = Self::load_loaders(
+                        &self.accounts_db,
                         &storage,
                         ancestors,
                         &accounts_index,
                         tx,
                         error_counters,
                     )?;
-                    Ok((accounts, loaders))
+                    let credits = vec![0; accounts.len()];
+                    Ok((accounts, loaders, lookup_keys, credits))
                 }
                 (_, Err(e)) => Err(e),
             })
@@ -324,7 +452,7 @@ impl Accounts {
     ) -> Option<(Account, Fork)> {
         self.accounts_db
             .load_slow(ancestors, pubkey)
-            .filter(|(acc, _)| acc.difs != 0)
+            .filter(|(acc, _)| acc.difs != 0 || acc.reputations != 0)
     }
 
     pub fn load_by_program(&self, fork: Fork, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
@@ -354,14 +482,21 @@ impl Accounts {
     }
 
     fn lock_account(
-        (fork_locks, parent_locks): (&mut HashSet<Pubkey>, &mut Vec<Arc<AccountLocks>>),
-        keys: &[Pubkey],
+        (fork_locks, parent_locks): (&mut PipelineLocks, &mut Vec<Arc<AccountLocks>>),
+        credit_debit_keys: &[&Pubkey],
+        credit_only_keys: &[&Pubkey],
         error_counters: &mut ErrorCounters,
     ) -> Result<()> {
         // Copy all the accounts
-        for k in keys {
+        let keys = credit_debit_keys
+            .iter()
+            .map(|k| (*k, true))
+            .chain(credit_only_keys.iter().map(|k| (*k, false)));
+        for (k, is_credit_debit) in keys {
             let is_locked = {
-                if fork_locks.contains(k) {
+                if fork_locks.credit_debit_locks.contains(k)
+                    || (is_credit_debit && fork_locks.credit_only_locks.contains_key(k))
+                {
                     true
                 } else {
                     // Check parent locks. As soon as a set of parent locks is empty,
@@ -389,97 +524,259 @@ impl Accounts {
                 }
             };
             if is_locked {
+                fork_locks.record_contended(k);
                 error_counters.account_in_use += 1;
                 debug!("Account in use: {:?}", k);
                 return Err(TransactionError::AccountInUse);
             }
         }
-        for k in keys {
-            fork_locks.insert(*k);
+        for k in credit_debit_keys {
+            fork_locks.credit_debit_locks.insert(**k);
+            fork_locks.record_locked(k);
+        }
+        for k in credit_only_keys {
+            *fork_locks.credit_only_locks.entry(**k).or_insert(0) += 1;
+            fork_locks.record_locked(k);
         }
         Ok(())
     }
 
-    fn lock_record_account(record_locks: &AccountLocks, keys: &[Pubkey]) {
+    fn lock_record_account(record_locks: &AccountLocks, credit_debit_keys: &[&Pubkey]) {
         let mut fork_locks = record_locks.lock().unwrap();
-        for k in keys {
+        for k in credit_debit_keys {
             // The fork locks should always be a subset of the account locks, so
             // the account locks should prevent record locks from ever touching the
             // same accounts
-            assert!(!fork_locks.contains(k));
-            fork_locks.insert(*k);
+            assert!(!fork_locks.contains(*k));
+            fork_locks.insert(**k);
         }
     }
 
-    fn unlock_account(tx: &Transaction, result: &Result<()>, locks: &mut HashSet<Pubkey>) {
+    fn unlock_account(
+        credit_debit_keys: &[&Pubkey],
+        credit_only_keys: &[&Pubkey],
+        result: &Result<()>,
+        locks: &mut PipelineLocks,
+    ) {
         match result {
-            Err(TransactionError::AccountInUse) => (),
+            Err(TransactionError::AccountInUse)
+            | Err(TransactionError::AddressLookupTableNotFound)
+            | Err(TransactionError::InvalidAddressLookupTableIndex) => (),
             _ => {
-                for k in &tx.message().account_keys {
-                    locks.remove(k);
+                for k in credit_debit_keys {
+                    locks.credit_debit_locks.remove(*k);
+                    locks.record_unlocked(k);
+                }
+                for k in credit_only_keys {
+                    let remaining = locks.credit_only_locks.get_mut(*k).map(|count| {
+                        *count -= 1;
+                        *count
+                    });
+                    if remaining == Some(0) {
+                        locks.credit_only_locks.remove(*k);
+                        locks.record_unlocked(k);
+                    }
                 }
             }
         }
     }
 
-    fn unlock_record_account<I>(tx: &I, locks: &mut HashSet<Pubkey>)
-    where
-        I: Borrow<Transaction>,
-    {
-        for k in &tx.borrow().message().account_keys {
-            locks.remove(k);
+    fn unlock_record_account(credit_debit_keys: &[&Pubkey], locks: &mut HashSet<Pubkey>) {
+        for k in credit_debit_keys {
+            locks.remove(*k);
         }
     }
 
-    fn hash_account(stored_account: &StoredAccount) -> Hash {
-        let mut hasher = Hasher::default();
-        hasher.hash(&serialize(&stored_account.balance).unwrap());
-        hasher.hash(stored_account.data);
-        hasher.result()
+    /// The keys a message loads from address lookup tables, credit-debit keys first, which are
+    /// locked along with its `account_keys`
+    fn load_locked_lookup_keys(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        message: &Message,
+    ) -> Result<InstructionLookupKeys> {
+        Self::load_lookup_keys(message, |key| {
+            self.accounts_db
+                .load_slow(ancestors, key)
+                .map(|(account, _)| account)
+        })
+        .map(|(mut writable_keys, readonly_keys)| {
+            writable_keys.extend(readonly_keys);
+            writable_keys
+        })
+    }
+
+    /// Split a message's `account_keys` and `lookup_keys` into the credit-debit keys a
+    /// transaction locks exclusively and the credit-only keys it shares
+    fn keys_by_lock_type<'a>(
+        message: &'a Message,
+        lookup_keys: &'a [Pubkey],
+    ) -> (Vec<&'a Pubkey>, Vec<&'a Pubkey>) {
+        let mut credit_debit_keys = vec![];
+        let mut credit_only_keys = vec![];
+        for (i, key) in message.account_keys.iter().chain(lookup_keys).enumerate() {
+            if message.is_writable(i) {
+                credit_debit_keys.push(key);
+            } else {
+                credit_only_keys.push(key);
+            }
+        }
+        (credit_debit_keys, credit_only_keys)
     }
 
+    /// Hash of the accounts `fork_id` stored, kept up to date as they are stored
     pub fn hash_internal_state(&self, fork_id: Fork) -> Option<Hash> {
+        self.accounts_db.delta_hash(fork_id)
+    }
+
+    /// Hash the accounts `fork_id` stored from scratch, scanning its storage
+    fn scan_hash_internal_state(&self, fork_id: Fork) -> Option<Hash> {
         let accumulator: Vec<Vec<(Pubkey, u64, Hash)>> = self.accounts_db.scan_account_storage(
             fork_id,
             |stored_account: &StoredAccount, accum: &mut Vec<(Pubkey, u64, Hash)>| {
                 accum.push((
                     stored_account.meta.pubkey,
                     stored_account.meta.write_version,
-                    Self::hash_account(stored_account),
+                    AccountsDeltaHash::hash_stored_account(stored_account),
                 ));
             },
         );
         let mut account_hashes: Vec<_> = accumulator.into_iter().flat_map(|x| x).collect();
         account_hashes.sort_by_key(|s| (s.0, (s.1 as i64).neg()));
         account_hashes.dedup_by_key(|s| s.0);
-        if account_hashes.is_empty() {
-            None
-        } else {
-            let mut hasher = Hasher::default();
-            for (_, _, hash) in account_hashes {
-                hasher.hash(hash.as_ref());
-            }
-            Some(hasher.result())
+        let mut delta_hash = AccountsDeltaHash::default();
+        for (pubkey, _, hash) in account_hashes {
+            delta_hash.store(&pubkey, hash);
+        }
+        delta_hash.result()
+    }
+
+    /// Whether `delta_hash`, the hash kept up to date for `fork_id`, agrees with hashing its
+    /// storage
+    pub fn verify_hash_internal_state(&self, fork_id: Fork, delta_hash: Option<Hash>) -> bool {
+        self.scan_hash_internal_state(fork_id) == delta_hash
+    }
+
+    /// Hash every non-empty account visible from `ancestors`, in pubkey order
+    pub fn hash_accounts(&self, ancestors: &HashMap<Fork, usize>) -> Hash {
+        Self::hash_loaded_accounts(&self.load_all(ancestors))
+    }
+
+    /// Hash `accounts` as `hash_accounts` does, they must be what `load_all` returned
+    pub fn hash_loaded_accounts(accounts: &[(Pubkey, Account)]) -> Hash {
+        let mut hasher = Hasher::default();
+        for (pubkey, account) in accounts {
+            hasher.hash(pubkey.as_ref());
+            hasher.hash(&serialize(account).unwrap());
+        }
+        hasher.result()
+    }
+
+    /// Every non-empty account visible from `ancestors`, in pubkey order.  The index and
+    /// storage locks are taken for `LOAD_ALL_CHUNK_SIZE` accounts at a time, so stores aren't
+    /// held up for the whole scan
+    pub fn load_all(&self, ancestors: &HashMap<Fork, usize>) -> Vec<(Pubkey, Account)> {
+        let pubkeys = expect_index_io(self.accounts_db.accounts_index.read().unwrap().pubkeys());
+        self.load_pubkeys(ancestors, pubkeys)
+    }
+
+    /// Like `load_all`, but only the accounts whose `pubkey_prefix` is in `prefixes`
+    pub fn load_range(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        prefixes: &RangeInclusive<u64>,
+    ) -> Vec<(Pubkey, Account)> {
+        let pubkeys = expect_index_io(
+            self.accounts_db
+                .accounts_index
+                .read()
+                .unwrap()
+                .pubkeys_in_range(prefixes),
+        );
+        self.load_pubkeys(ancestors, pubkeys)
+    }
+
+    /// Like `load_all`, but the accounts are only pinned in storage, to be read later without
+    /// holding up stores, see `PinnedAccounts`
+    pub fn pin_all(&self, ancestors: &HashMap<Fork, usize>) -> PinnedAccounts {
+        self.pin_stored_after(ancestors, None)
+    }
+
+    /// Like `pin_all`, but only the accounts stored in a fork after `fork`, the empty ones
+    /// included
+    pub fn pin_changed(&self, ancestors: &HashMap<Fork, usize>, fork: Fork) -> PinnedAccounts {
+        self.pin_stored_after(ancestors, Some(fork))
+    }
+
+    fn pin_stored_after(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        stored_after: Option<Fork>,
+    ) -> PinnedAccounts {
+        let mut pubkeys =
+            expect_index_io(self.accounts_db.accounts_index.read().unwrap().pubkeys());
+        pubkeys.sort();
+        let mut pinned = PinnedAccounts::default();
+        for chunk in pubkeys.chunks(LOAD_ALL_CHUNK_SIZE) {
+            self.accounts_db
+                .pin_accounts(ancestors, chunk, stored_after, &mut pinned);
+        }
+        pinned
+    }
+
+    /// Hash `accounts` as `hash_accounts` does, they must be what `pin_all` returned
+    pub fn hash_pinned_accounts(accounts: &PinnedAccounts) -> Hash {
+        let mut hasher = Hasher::default();
+        for (pubkey, account) in accounts.iter() {
+            hasher.hash(pubkey.as_ref());
+            hasher.hash(&serialize(&account).unwrap());
+        }
+        hasher.result()
+    }
+
+    fn load_pubkeys(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        mut pubkeys: Vec<Pubkey>,
+    ) -> Vec<(Pubkey, Account)> {
+        pubkeys.sort();
+        let mut accounts = vec![];
+        for chunk in pubkeys.chunks(LOAD_ALL_CHUNK_SIZE) {
+            let accounts_index = self.accounts_db.accounts_index.read().unwrap();
+            let storage = self.accounts_db.storage.read().unwrap();
+            accounts.extend(chunk.iter().filter_map(|pubkey| {
+                self.accounts_db
+                    .load(&storage, ancestors, &accounts_index, pubkey)
+                    .filter(|(account, _)| account.difs != 0 || account.reputations != 0)
+                    .map(|(account, _)| (*pubkey, account))
+            }));
         }
+        accounts
     }
 
     /// This function will prevent multiple threads from modifying the same account state at the
     /// same time
     #[must_use]
-    pub fn lock_accounts<I>(&self, txs: &[I]) -> Vec<Result<()>>
+    pub fn lock_accounts<I>(&self, ancestors: &HashMap<Fork, usize>, txs: &[I]) -> Vec<Result<()>>
     where
         I: Borrow<Transaction>,
     {
+        let lookup_keys: Vec<_> = txs
+            .iter()
+            .map(|tx| self.load_locked_lookup_keys(ancestors, tx.borrow().message()))
+            .collect();
         let (_, ref mut parent_record_locks) = *self.record_locks.lock().unwrap();
         let mut error_counters = ErrorCounters::default();
         let rv = txs
             .iter()
-            .map(|tx| {
-                let message = tx.borrow().message();
+            .zip(lookup_keys)
+            .map(|(tx, lookup_keys)| {
+                let lookup_keys = lookup_keys?;
+                let (credit_debit_keys, credit_only_keys) =
+                    Self::keys_by_lock_type(tx.borrow().message(), &lookup_keys);
                 Self::lock_account(
                     (&mut self.account_locks.lock().unwrap(), parent_record_locks),
-                    &message.account_keys[..(message.account_keys.len()
-                        - message.header.num_credit_only_unsigned_accounts as usize)],
+                    &credit_debit_keys,
+                    &credit_only_keys,
                     &mut error_counters,
                 )
             })
@@ -492,50 +789,125 @@ impl Accounts {
                 100
             );
         }
+        self.flush_lock_stats(&mut self.account_locks.lock().unwrap());
         rv
     }
 
-    pub fn lock_record_accounts<I>(&self, txs: &[I])
+    /// Add what the locks came to since last time to the stats shared with the other banks
+    fn flush_lock_stats(&self, locks: &mut PipelineLocks) {
+        self.lock_stats.record(locks.lock_stats.drain());
+    }
+
+    /// The `num` accounts transactions waited on the longest, see `AccountLockStats`
+    pub fn hottest_accounts(&self, num: usize) -> Vec<(Pubkey, AccountLockStat)> {
+        self.lock_stats.hottest(num)
+    }
+
+    pub fn lock_record_accounts<I>(&self, ancestors: &HashMap<Fork, usize>, txs: &[I])
     where
         I: Borrow<Transaction>,
     {
+        let lookup_keys: Vec<_> = txs
+            .iter()
+            .map(|tx| {
+                self.load_locked_lookup_keys(ancestors, tx.borrow().message())
+                    .unwrap_or_default()
+            })
+            .collect();
         let record_locks = self.record_locks.lock().unwrap();
-        for tx in txs {
-            let message = tx.borrow().message();
-            Self::lock_record_account(
-                &record_locks.0,
-                &message.account_keys[..(message.account_keys.len()
-                    - message.header.num_credit_only_unsigned_accounts as usize)],
-            );
+        for (tx, lookup_keys) in txs.iter().zip(lookup_keys) {
+            let (credit_debit_keys, _) =
+                Self::keys_by_lock_type(tx.borrow().message(), &lookup_keys);
+            Self::lock_record_account(&record_locks.0, &credit_debit_keys);
         }
     }
 
     /// Once accounts are unlocked, new transactions that modify that state can enter the pipeline
-    pub fn unlock_accounts<I>(&self, txs: &[I], results: &[Result<()>])
-    where
+    pub fn unlock_accounts<I>(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        txs: &[I],
+        results: &[Result<()>],
+    ) where
         I: Borrow<Transaction>,
     {
+        let lookup_keys: Vec<_> = txs
+            .iter()
+            .map(|tx| {
+                self.load_locked_lookup_keys(ancestors, tx.borrow().message())
+                    .unwrap_or_default()
+            })
+            .collect();
         let my_locks = &mut self.account_locks.lock().unwrap();
         debug!("bank unlock accounts");
         txs.iter()
             .zip(results.iter())
-            .for_each(|(tx, result)| Self::unlock_account(tx.borrow(), result, my_locks));
+            .zip(lookup_keys)
+            .for_each(|((tx, result), lookup_keys)| {
+                let (credit_debit_keys, credit_only_keys) =
+                    Self::keys_by_lock_type(tx.borrow().message(), &lookup_keys);
+                Self::unlock_account(&credit_debit_keys, &credit_only_keys, result, my_locks)
+            });
+        self.flush_lock_stats(my_locks);
+    }
+
+    /// Add what `credits` credited each account to its latest version in `fork`, a load and a
+    /// store under the pipeline locks so transactions sharing an account don't lose each
+    /// other's credits.  Returns the credited accounts
+    fn commit_credits(
+        &self,
+        fork: Fork,
+        ancestors: &HashMap<Fork, usize>,
+        credits: HashMap<&Pubkey, (u64, &Account)>,
+    ) -> Vec<(Pubkey, Account)> {
+        if credits.is_empty() {
+            return vec![];
+        }
+        let _locks = self.account_locks.lock().unwrap();
+        let accounts: Vec<_> = credits
+            .into_iter()
+            .map(|(key, (credit, credited_account))| {
+                let mut account = self
+                    .accounts_db
+                    .load_slow(ancestors, key)
+                    .map(|(account, _)| account)
+                    .unwrap_or_else(|| {
+                        // the account didn't exist, the transaction created it from nothing
+                        let mut account = credited_account.clone();
+                        account.difs = 0;
+                        account
+                    });
+                account.difs += credit;
+                (*key, account)
+            })
+            .collect();
+        let stores: Vec<_> = accounts
+            .iter()
+            .map(|(key, account)| (key, account))
+            .collect();
+        self.accounts_db.store(fork, &stores);
+        accounts
     }
 
-    pub fn unlock_record_accounts<I>(&self, txs: &[I])
+    pub fn unlock_record_accounts<I>(&self, ancestors: &HashMap<Fork, usize>, txs: &[I])
     where
         I: Borrow<Transaction>,
     {
+        let lookup_keys: Vec<_> = txs
+            .iter()
+            .map(|tx| {
+                self.load_locked_lookup_keys(ancestors, tx.borrow().message())
+                    .unwrap_or_default()
+            })
+            .collect();
         let (ref my_record_locks, _) = *self.record_locks.lock().unwrap();
-        for tx in txs {
-            Self::unlock_record_account(tx, &mut my_record_locks.lock().unwrap())
+        for (tx, lookup_keys) in txs.iter().zip(lookup_keys) {
+            let (credit_debit_keys, _) =
+                Self::keys_by_lock_type(tx.borrow().message(), &lookup_keys);
+            Self::unlock_record_account(&credit_debit_keys, &mut my_record_locks.lock().unwrap())
         }
     }
 
-    pub fn has_accounts(&self, fork: Fork) -> bool {
-        self.accounts_db.has_accounts(fork)
-    }
-
     pub fn load_accounts(
         &self,
         ancestors: &HashMap<Fork, usize>,
@@ -543,7 +915,7 @@ impl Accounts {
         results: Vec<Result<()>>,
         fee_calculator: &FeeCalculator,
         error_counters: &mut ErrorCounters,
-    ) -> Vec<Result<(InstructionAccounts, InstructionLoaders)>> {
+    ) -> Vec<Result<TransactionLoadResult>> {
         self.load_accounts_internal(ancestors, txs, results, fee_calculator, error_counters)
     }
 
@@ -551,11 +923,13 @@ impl Accounts {
     pub fn store_accounts(
         &self,
         fork: Fork,
+        ancestors: &HashMap<Fork, usize>,
         txs: &[Transaction],
         res: &[Result<()>],
-        loaded: &[Result<(InstructionAccounts, InstructionLoaders)>],
-    ) {
+        loaded: &[Result<TransactionLoadResult>],
+    ) -> Vec<(Pubkey, Account)> {
         let mut accounts: Vec<(&Pubkey, &Account)> = vec![];
+        let mut credits: HashMap<&Pubkey, (u64, &Account)> = HashMap::new();
         for (i, raccs) in loaded.iter().enumerate() {
             if res[i].is_err() || raccs.is_err() {
                 continue;
@@ -563,17 +937,34 @@ impl Accounts {
 
             let message = &txs[i].message();
             let acc = raccs.as_ref().unwrap();
-            for (key, account) in message.account_keys.iter().zip(acc.0.iter()) {
-                accounts.push((key, account));
+            let program_ids = message.program_ids();
+            let keys = message.account_keys.iter().chain(&acc.2);
+            for (i, ((key, account), credit)) in keys.zip(acc.0.iter()).zip(&acc.3).enumerate() {
+                // credit-only accounts may be shared with other transactions, only what they
+                // were credited is kept. Program accounts are only loaded by versioned
+                // messages, and never stored back
+                if !message.is_writable(i) {
+                    if *credit > 0 {
+                        credits.entry(key).or_insert((0, account)).0 += credit;
+                    }
+                } else if !message.is_versioned() || !program_ids.contains(&key) {
+                    accounts.push((key, account));
+                }
             }
         }
         self.accounts_db.store(fork, &accounts);
+        self.commit_credits(fork, ancestors, credits)
     }
 
     /// Purge a fork if it is not a root
     /// Root forks cannot be purged
+    /// The storage is reclaimed in the background, see `AccountsDB::purge_fork_async`
     pub fn purge_fork(&self, fork: Fork) {
-        self.accounts_db.purge_fork(fork);
+        AccountsDB::purge_fork_async(&self.accounts_db, fork);
+    }
+    /// Wait for a purged fork to be cleaned up before its slot id is reused
+    pub fn wait_for_purge(&self, fork: Fork) {
+        self.accounts_db.wait_for_purge(fork);
     }
     /// Add a fork to root.  Root forks cannot be purged
     pub fn add_root(&self, fork: Fork) {
@@ -586,9 +977,12 @@ mod tests {
     // TODO: all the bank tests are bank specific, issue: 2194
 
     use super::*;
+    use morgan_address_lookup_table_api::address_lookup_table_state::AddressLookupTable;
     use morgan_interface::account::Account;
+    use morgan_interface::account_utils::State;
     use morgan_interface::hash::Hash;
     use morgan_interface::instruction::CompiledInstruction;
+    use morgan_interface::message::MessageAddressTableLookup;
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::transaction::Transaction;
     use std::thread::{sleep, Builder};
@@ -599,7 +993,7 @@ mod tests {
         ka: &Vec<(Pubkey, Account)>,
         fee_calculator: &FeeCalculator,
         error_counters: &mut ErrorCounters,
-    ) -> Vec<Result<(InstructionAccounts, InstructionLoaders)>> {
+    ) -> Vec<Result<TransactionLoadResult>> {
         let accounts = Accounts::new(None);
         for ka in ka.iter() {
             accounts.store_slow(0, &ka.0, &ka.1);
@@ -620,7 +1014,7 @@ mod tests {
         tx: Transaction,
         ka: &Vec<(Pubkey, Account)>,
         error_counters: &mut ErrorCounters,
-    ) -> Vec<Result<(InstructionAccounts, InstructionLoaders)>> {
+    ) -> Vec<Result<TransactionLoadResult>> {
         let fee_calculator = FeeCalculator::default();
         load_accounts_with_fee(tx, ka, &fee_calculator, error_counters)
     }
@@ -796,7 +1190,7 @@ mod tests {
         assert_eq!(error_counters.account_not_found, 0);
         assert_eq!(loaded_accounts.len(), 1);
         match &loaded_accounts[0] {
-            Ok((a, l)) => {
+            Ok((a, l, _, _)) => {
                 assert_eq!(a.len(), 2);
                 assert_eq!(a[0], accounts[0].1);
                 assert_eq!(l.len(), 1);
@@ -806,6 +1200,244 @@ mod tests {
         }
     }
 
+    fn create_lookup_table_account(addresses: Vec<Pubkey>) -> Account {
+        let mut account = Account::new(
+            1,
+            0,
+            AddressLookupTableState::max_space() as usize,
+            &morgan_address_lookup_table_api::id(),
+        );
+        account
+            .set_state(&AddressLookupTableState::Initialized(AddressLookupTable {
+                authority: None,
+                addresses,
+            }))
+            .unwrap();
+        account
+    }
+
+    fn create_lookup_table_transaction(
+        keypair: &Keypair,
+        table_key: Pubkey,
+        writable_indexes: Vec<u8>,
+        readonly_indexes: Vec<u8>,
+    ) -> Transaction {
+        let num_lookup_keys = writable_indexes.len() + readonly_indexes.len();
+        let instructions = vec![CompiledInstruction::new(
+            1,
+            &(),
+            (0..num_lookup_keys as u8 + 2).filter(|i| *i != 1).collect(),
+        )];
+        let mut tx = Transaction::new_with_compiled_instructions(
+            &[keypair],
+            &[],
+            Hash::default(),
+            vec![native_loader::id()],
+            instructions,
+        );
+        tx.message.address_table_lookups = vec![MessageAddressTableLookup {
+            account_key: table_key,
+            writable_indexes,
+            readonly_indexes,
+        }];
+        tx
+    }
+
+    #[test]
+    fn test_load_accounts_address_table_lookups() {
+        let mut accounts: Vec<(Pubkey, Account)> = Vec::new();
+        let mut error_counters = ErrorCounters::default();
+
+        let keypair = Keypair::new();
+        let key0 = keypair.pubkey();
+        let key1 = Pubkey::new_rand();
+        let key2 = Pubkey::new_rand();
+        let table_key = Pubkey::new_rand();
+
+        accounts.push((key0, Account::new(1, 0, 1, &Pubkey::default())));
+        accounts.push((key1, Account::new(2, 0, 1, &Pubkey::default())));
+        accounts.push((key2, Account::new(3, 0, 1, &Pubkey::default())));
+        accounts.push((table_key, create_lookup_table_account(vec![key2, key1])));
+
+        let tx = create_lookup_table_transaction(&keypair, table_key, vec![1], vec![0]);
+        let loaded_accounts = load_accounts(tx, &accounts, &mut error_counters);
+
+        assert_eq!(loaded_accounts.len(), 1);
+        match &loaded_accounts[0] {
+            Ok((a, l, k, _)) => {
+                assert_eq!(k, &vec![key1, key2]);
+                // the program account keeps its place so lookup accounts line up with indexes
+                assert_eq!(a.len(), 4);
+                assert_eq!(a[0], accounts[0].1);
+                assert_eq!(a[2], accounts[1].1);
+                assert_eq!(a[3], accounts[2].1);
+                assert_eq!(l.len(), 1);
+            }
+            Err(e) => Err(e).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_load_accounts_address_table_lookups_invalid() {
+        let mut accounts: Vec<(Pubkey, Account)> = Vec::new();
+        let mut error_counters = ErrorCounters::default();
+
+        let keypair = Keypair::new();
+        let key1 = Pubkey::new_rand();
+        let table_key = Pubkey::new_rand();
+        let not_a_table_key = Pubkey::new_rand();
+
+        accounts.push((keypair.pubkey(), Account::new(1, 0, 1, &Pubkey::default())));
+        accounts.push((table_key, create_lookup_table_account(vec![key1])));
+        accounts.push((not_a_table_key, Account::new(1, 0, 1, &Pubkey::default())));
+
+        let tx = create_lookup_table_transaction(&keypair, table_key, vec![1], vec![]);
+        let loaded_accounts = load_accounts(tx, &accounts, &mut error_counters);
+        assert_eq!(
+            loaded_accounts[0],
+            Err(TransactionError::InvalidAddressLookupTableIndex)
+        );
+
+        let tx = create_lookup_table_transaction(&keypair, not_a_table_key, vec![0], vec![]);
+        let loaded_accounts = load_accounts(tx, &accounts, &mut error_counters);
+        assert_eq!(
+            loaded_accounts[0],
+            Err(TransactionError::AddressLookupTableNotFound)
+        );
+
+        let tx = create_lookup_table_transaction(&keypair, table_key, vec![0], vec![0]);
+        let loaded_accounts = load_accounts(tx, &accounts, &mut error_counters);
+        assert_eq!(
+            loaded_accounts[0],
+            Err(TransactionError::AccountLoadedTwice)
+        );
+    }
+
+    #[test]
+    fn test_lock_accounts_address_table_lookups() {
+        let accounts = Accounts::new(None);
+        let ancestors = vec![(0, 0)].into_iter().collect();
+
+        let keypair = Keypair::new();
+        let key1 = Pubkey::new_rand();
+        let key2 = Pubkey::new_rand();
+        let table_key = Pubkey::new_rand();
+        accounts.store_slow(
+            0,
+            &table_key,
+            &create_lookup_table_account(vec![key1, key2]),
+        );
+
+        let tx = create_lookup_table_transaction(&keypair, table_key, vec![0], vec![1]);
+        let missing_table_tx =
+            create_lookup_table_transaction(&Keypair::new(), Pubkey::new_rand(), vec![0], vec![]);
+        let txs = vec![tx, missing_table_tx];
+        let results = accounts.lock_accounts(&ancestors, &txs);
+        assert_eq!(
+            results,
+            vec![Ok(()), Err(TransactionError::AddressLookupTableNotFound)]
+        );
+        {
+            let locks = accounts.account_locks.lock().unwrap();
+            assert!(locks.credit_debit_locks.contains(&keypair.pubkey()));
+            assert!(locks.credit_debit_locks.contains(&key1));
+            assert_eq!(locks.credit_debit_locks.len(), 2);
+            assert_eq!(locks.credit_only_locks.get(&key2), Some(&1));
+        }
+
+        // a second writer of the looked up account has to wait
+        let tx2 = create_lookup_table_transaction(&Keypair::new(), table_key, vec![0], vec![]);
+        assert_eq!(
+            accounts.lock_accounts(&ancestors, &[tx2]),
+            vec![Err(TransactionError::AccountInUse)]
+        );
+
+        // readers of the credit-only account share it, writers have to wait
+        let reader_txs = vec![create_lookup_table_transaction(
+            &Keypair::new(),
+            table_key,
+            vec![],
+            vec![1],
+        )];
+        let reader_results = accounts.lock_accounts(&ancestors, &reader_txs);
+        assert_eq!(reader_results, vec![Ok(())]);
+        assert_eq!(
+            accounts
+                .account_locks
+                .lock()
+                .unwrap()
+                .credit_only_locks
+                .get(&key2),
+            Some(&2)
+        );
+        let tx3 = create_lookup_table_transaction(&Keypair::new(), table_key, vec![1], vec![]);
+        assert_eq!(
+            accounts.lock_accounts(&ancestors, &[tx3]),
+            vec![Err(TransactionError::AccountInUse)]
+        );
+
+        accounts.unlock_accounts(&ancestors, &txs, &results);
+        assert_eq!(
+            accounts
+                .account_locks
+                .lock()
+                .unwrap()
+                .credit_only_locks
+                .get(&key2),
+            Some(&1)
+        );
+        accounts.unlock_accounts(&ancestors, &reader_txs, &reader_results);
+        let locks = accounts.account_locks.lock().unwrap();
+        assert!(locks.credit_debit_locks.is_empty());
+        assert!(locks.credit_only_locks.is_empty());
+    }
+
+    #[test]
+    fn test_lock_accounts_stats() {
+        let accounts = Accounts::new(None);
+        let ancestors = vec![(0, 0)].into_iter().collect();
+
+        let key1 = Pubkey::new_rand();
+        let table_key = Pubkey::new_rand();
+        accounts.store_slow(0, &table_key, &create_lookup_table_account(vec![key1]));
+        let txs = vec![create_lookup_table_transaction(
+            &Keypair::new(),
+            table_key,
+            vec![0],
+            vec![],
+        )];
+        let results = accounts.lock_accounts(&ancestors, &txs);
+        assert_eq!(results, vec![Ok(())]);
+
+        // a second writer is turned away until the first one unlocks the account
+        let txs2 = vec![create_lookup_table_transaction(
+            &Keypair::new(),
+            table_key,
+            vec![0],
+            vec![],
+        )];
+        assert_eq!(
+            accounts.lock_accounts(&ancestors, &txs2),
+            vec![Err(TransactionError::AccountInUse)]
+        );
+        sleep(Duration::from_millis(1));
+        accounts.unlock_accounts(&ancestors, &txs, &results);
+        let results2 = accounts.lock_accounts(&ancestors, &txs2);
+        assert_eq!(results2, vec![Ok(())]);
+        accounts.unlock_accounts(&ancestors, &txs2, &results2);
+
+        // children add to the same stats
+        let child = Accounts::new_from_parent(&accounts);
+        let hottest = child.hottest_accounts(1);
+        assert_eq!(hottest.len(), 1);
+        let (key, stat) = hottest[0];
+        assert_eq!(key, key1);
+        assert_eq!(stat.locks, 2);
+        assert_eq!(stat.contended, 1);
+        assert!(stat.wait_us >= 1_000);
+        assert!(stat.hold_us >= 1_000);
+    }
+
     #[test]
     fn test_load_accounts_max_call_depth() {
         let mut accounts: Vec<(Pubkey, Account)> = Vec::new();
@@ -980,7 +1612,7 @@ mod tests {
         assert_eq!(error_counters.account_not_found, 0);
         assert_eq!(loaded_accounts.len(), 1);
         match &loaded_accounts[0] {
-            Ok((a, l)) => {
+            Ok((a, l, _, _)) => {
                 assert_eq!(a.len(), 1);
                 assert_eq!(a[0], accounts[0].1);
                 assert_eq!(l.len(), 2);
@@ -1051,6 +1683,22 @@ mod tests {
         assert_eq!(loaded, vec![]);
     }
 
+    #[test]
+    fn test_load_slow_reputations_without_difs() {
+        let accounts = Accounts::new(None);
+        let ancestors = vec![(0, 0)].into_iter().collect();
+
+        // reputations keep an account without difs alive, data included
+        let pubkey = Pubkey::new_rand();
+        let account = Account::new(0, 42, 1, &Pubkey::default());
+        accounts.store_slow(0, &pubkey, &account);
+        assert_eq!(accounts.load_slow(&ancestors, &pubkey), Some((account, 0)));
+
+        let account = Account::new(0, 0, 1, &Pubkey::default());
+        accounts.store_slow(0, &pubkey, &account);
+        assert_eq!(accounts.load_slow(&ancestors, &pubkey), None);
+    }
+
     #[test]
     fn test_accounts_account_not_found() {
         let accounts = Accounts::new(None);
@@ -1061,6 +1709,7 @@ mod tests {
         let storage = accounts.accounts_db.storage.read().unwrap();
         assert_eq!(
             Accounts::load_executable_accounts(
+                &accounts.accounts_db,
                 &storage,
                 &ancestors,
                 &accounts_index,
@@ -1076,6 +1725,29 @@ mod tests {
     fn test_accounts_empty_hash_internal_state() {
         let accounts = Accounts::new(None);
         assert_eq!(accounts.hash_internal_state(0), None);
+        assert!(accounts.verify_hash_internal_state(0, accounts.hash_internal_state(0)));
+    }
+
+    #[test]
+    fn test_accounts_verify_hash_internal_state() {
+        let accounts = Accounts::new(None);
+        let keys: Vec<_> = (0..10).map(|_| Pubkey::new_rand()).collect();
+        for (i, key) in keys.iter().enumerate() {
+            let account = Account::new(i as u64 + 1, 0, i, &Pubkey::default());
+            accounts.store_slow(0, key, &account);
+        }
+        // the latest version of an account counts, emptied ones hash without their data
+        accounts.store_slow(0, &keys[3], &Account::new(100, 0, 3, &Pubkey::default()));
+        accounts.store_slow(0, &keys[4], &Account::new(0, 0, 4, &Pubkey::default()));
+        let hash = accounts.hash_internal_state(0);
+        assert!(hash.is_some());
+        assert!(accounts.verify_hash_internal_state(0, accounts.hash_internal_state(0)));
+
+        accounts.store_slow(1, &keys[0], &Account::new(7, 0, 0, &Pubkey::default()));
+        assert_ne!(accounts.hash_internal_state(1), hash);
+        assert!(accounts.verify_hash_internal_state(1, accounts.hash_internal_state(1)));
+        assert!(!accounts.verify_hash_internal_state(1, hash));
+        assert_eq!(accounts.hash_internal_state(0), hash);
     }
 
     #[test]
@@ -1115,7 +1787,8 @@ mod tests {
                     &mut child.account_locks.lock().unwrap(),
                     &mut child.record_locks.lock().unwrap().1
                 ),
-                &vec![locked_pubkey],
+                &[&locked_pubkey],
+                &[],
                 &mut ErrorCounters::default()
             ),
             Ok(())
@@ -1127,7 +1800,7 @@ mod tests {
             // Check the lock was successfully obtained
             let child_account_locks = &mut child.account_locks.lock().unwrap();
             let parent_record_locks = child.record_locks.lock().unwrap();
-            assert_eq!(child_account_locks.len(), 1);
+            assert_eq!(child_account_locks.credit_debit_locks.len(), 1);
 
             // Make sure child removed the parent's record locks after the parent had
             // released all its locks
@@ -1135,7 +1808,7 @@ mod tests {
 
             // After all the checks pass, clear the account we just locked from the
             // set of locks
-            child_account_locks.clear();
+            child_account_locks.credit_debit_locks.clear();
         }
 
         // Make sure calling new_from_parent() on the child bank also cleans up the copy of old locked
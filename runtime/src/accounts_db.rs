@@ -18,21 +18,28 @@
 //! tracks the number of commits to the entire data store. So the latest
 //! commit for each fork entry would be indexed.
 
+use crate::accounts_delta_hash::AccountsDeltaHash;
 use crate::accounts_index::{AccountsIndex, Fork};
 use crate::append_vec::{AppendVec, StorageMeta, StoredAccount};
+use crate::read_only_accounts_cache::ReadOnlyAccountsCache;
 use hashbrown::{HashMap, HashSet};
 use log::*;
-use rand::{thread_rng, Rng};
-use rayon::prelude::*;
 use morgan_interface::account::Account;
+use morgan_interface::hash::Hash;
 use morgan_interface::pubkey::Pubkey;
+use rand::{thread_rng, Rng};
+use rayon::prelude::*;
 use std::fs::{create_dir_all, remove_dir_all};
+use std::io;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, RwLock};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak};
+use std::thread::Builder;
 
 const ACCOUNT_DATA_FILE_SIZE: u64 = 64 * 1024 * 1024;
 const ACCOUNT_DATA_FILE: &str = "data";
+const ACCOUNTS_INDEX_DIR: &str = "index";
 
 #[derive(Debug, Default)]
 pub struct ErrorCounters {
@@ -50,7 +57,7 @@ pub struct ErrorCounters {
     pub missing_signature_for_fee: usize,
 }
 
-#[derive(Default, Clone)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct AccountInfo {
     /// index identifying the append storage
     id: AppendVecId,
@@ -67,6 +74,20 @@ type AppendVecId = usize;
 pub type AccountStorage = HashMap<usize, Arc<AccountStorageEntry>>;
 pub type InstructionAccounts = Vec<Account>;
 pub type InstructionLoaders = Vec<Vec<(Pubkey, Account)>>;
+/// Account keys loaded from address lookup tables, following the message's `account_keys`
+pub type InstructionLookupKeys = Vec<Pubkey>;
+/// Difs the transaction credited each of its credit-only accounts, following its accounts
+pub type InstructionCredits = Vec<u64>;
+pub type TransactionLoadResult = (
+    InstructionAccounts,
+    InstructionLoaders,
+    InstructionLookupKeys,
+    InstructionCredits,
+);
+
+/// A dead fork along with the storage entries detached from it, waiting to be
+/// reclaimed by the background cleanup thread
+type PurgeRequest = (Fork, Vec<Arc<AccountStorageEntry>>);
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum AccountStorageStatus {
@@ -89,6 +110,10 @@ pub struct AccountStorageEntry {
     /// status corresponding to the storage, lets us know that
     ///  the append_vec, once maxed out, then emptied, can be reclaimed
     count_and_status: RwLock<(usize, AccountStorageStatus)>,
+
+    /// number of `PinnedAccounts` reading from the storage, it isn't reclaimed while pinned.
+    ///  Only changed under the `count_and_status` lock
+    pin_count: AtomicUsize,
 }
 
 impl AccountStorageEntry {
@@ -104,6 +129,7 @@ impl AccountStorageEntry {
             fork_id,
             accounts,
             count_and_status: RwLock::new((0, AccountStorageStatus::StorageAvailable)),
+            pin_count: AtomicUsize::new(0),
         }
     }
 
@@ -112,7 +138,7 @@ impl AccountStorageEntry {
 
         let count = count_and_status.0;
 
-        if status == AccountStorageStatus::StorageFull && count == 0 {
+        if status == AccountStorageStatus::StorageFull && count == 0 && !self.is_pinned() {
             // this case arises when the append_vec is full (store_ptrs fails),
             //  but all accounts have already been removed from the storage
             //
@@ -145,7 +171,7 @@ impl AccountStorageEntry {
         let mut count_and_status = self.count_and_status.write().unwrap();
         let (count, mut status) = *count_and_status;
 
-        if count == 1 && status == AccountStorageStatus::StorageFull {
+        if count == 1 && status == AccountStorageStatus::StorageFull && !self.is_pinned() {
             // this case arises when we remove the last account from the
             //  storage, but we've learned from previous write attempts that
             //  the storage is full
@@ -163,6 +189,78 @@ impl AccountStorageEntry {
 
         *count_and_status = (count - 1, status);
     }
+
+    fn is_pinned(&self) -> bool {
+        self.pin_count.load(Ordering::Relaxed) > 0
+    }
+
+    fn pin(&self) {
+        let _count_and_status = self.count_and_status.write().unwrap();
+        self.pin_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Release a pin, reclaiming the storage if every account was removed from it while it
+    /// was pinned
+    fn unpin(&self) {
+        let mut count_and_status = self.count_and_status.write().unwrap();
+        self.pin_count.fetch_sub(1, Ordering::Relaxed);
+        let (count, status) = *count_and_status;
+        if count == 0 && status == AccountStorageStatus::StorageFull && !self.is_pinned() {
+            self.accounts.reset();
+            *count_and_status = (count, AccountStorageStatus::StorageAvailable);
+        }
+    }
+}
+
+/// Accounts located in storage as of when they were pinned.  Their storage isn't reset and
+/// reused while pinned, so they can be read later without the index and storage locks, and
+/// read as they were when pinned whatever was stored or rooted since.
+#[derive(Default)]
+pub struct PinnedAccounts {
+    /// pubkey, storage and offset of each account, in pubkey order
+    locations: Vec<(Pubkey, AppendVecId, usize)>,
+
+    stores: HashMap<AppendVecId, Arc<AccountStorageEntry>>,
+}
+
+impl PinnedAccounts {
+    /// Number of accounts pinned, including the empty ones `iter` skips
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Read the non-empty accounts, in pubkey order
+    pub fn iter(&self) -> impl Iterator<Item = (Pubkey, Account)> + '_ {
+        self.iter_all()
+            .filter(|(_, account)| account.difs != 0 || account.reputations != 0)
+    }
+
+    /// Read the accounts, the empty ones too, in pubkey order
+    pub fn iter_all(&self) -> impl Iterator<Item = (Pubkey, Account)> + '_ {
+        self.locations
+            .iter()
+            .filter_map(move |(pubkey, id, offset)| {
+                let (stored_account, _) = self.stores[id].accounts.get_account(*offset)?;
+                Some((*pubkey, stored_account.clone_account()))
+            })
+    }
+
+    /// Read all the non-empty accounts, in pubkey order
+    pub fn load(&self) -> Vec<(Pubkey, Account)> {
+        self.iter().collect()
+    }
+}
+
+impl Drop for PinnedAccounts {
+    fn drop(&mut self) {
+        for store in self.stores.values() {
+            store.unpin();
+        }
+    }
 }
 
 // This structure handles the load/store of the accounts
@@ -185,6 +283,27 @@ pub struct AccountsDB {
 
     /// Starting file size of appendvecs
     file_size: u64,
+
+    /// queue feeding the background cleanup thread, spawned on first use
+    purge_sender: Mutex<Option<Sender<PurgeRequest>>>,
+
+    /// number of purges per fork still waiting on the cleanup thread
+    pending_purges: Mutex<HashMap<Fork, usize>>,
+
+    /// signaled every time the cleanup thread finishes a fork
+    purge_done: Condvar,
+
+    /// copies of recently loaded accounts
+    read_only_cache: ReadOnlyAccountsCache,
+
+    /// hash of the accounts every fork from the root up stored, kept up to date by `store`
+    delta_hashes: RwLock<HashMap<Fork, AccountsDeltaHash>>,
+}
+
+/// The accounts index only fails on reading or writing the keys it overflowed to disk, a
+/// failed read of the account storage itself is as fatal
+pub(crate) fn expect_index_io<T>(result: io::Result<T>) -> T {
+    result.unwrap_or_else(|err| panic!("accounts index overflow I/O failed: {}", err))
 }
 
 pub fn get_paths_vec(paths: &str) -> Vec<String> {
@@ -201,6 +320,11 @@ impl AccountsDB {
             write_version: AtomicUsize::new(0),
             paths,
             file_size,
+            purge_sender: Mutex::new(None),
+            pending_purges: Mutex::new(HashMap::new()),
+            purge_done: Condvar::new(),
+            read_only_cache: ReadOnlyAccountsCache::default(),
+            delta_hashes: RwLock::new(HashMap::new()),
         }
     }
 
@@ -208,6 +332,18 @@ impl AccountsDB {
         Self::new_with_file_size(paths, ACCOUNT_DATA_FILE_SIZE)
     }
 
+    /// An `AccountsDB` whose index keeps about `index_memory_budget` bytes in memory and
+    /// overflows the rest to disk under the first of `paths`
+    pub fn new_with_index_memory_budget(paths: &str, index_memory_budget: usize) -> Self {
+        let db = Self::new(paths);
+        let overflow_path = Path::new(&db.paths[0]).join(ACCOUNTS_INDEX_DIR);
+        *db.accounts_index.write().unwrap() = expect_index_io(AccountsIndex::new_with_overflow(
+            overflow_path,
+            index_memory_budget,
+        ));
+        db
+    }
+
     fn new_storage_entry(&self, fork_id: Fork, path: &str) -> AccountStorageEntry {
         AccountStorageEntry::new(
             path,
@@ -226,6 +362,15 @@ impl AccountsDB {
         false
     }
 
+    /// Hash of the accounts `fork` stored, None if it stored none
+    pub fn delta_hash(&self, fork: Fork) -> Option<Hash> {
+        self.delta_hashes
+            .read()
+            .unwrap()
+            .get(&fork)
+            .and_then(AccountsDeltaHash::result)
+    }
+
     /// Scan a specific fork through all the account storage in parallel with sequential read
     // PERF: Sequentially read each storage entry in parallel
     pub fn scan_account_storage<F, B>(&self, fork_id: Fork, scan_func: F) -> Vec<B>
@@ -256,17 +401,49 @@ impl AccountsDB {
     }
 
     pub fn load(
+        &self,
         storage: &AccountStorage,
         ancestors: &HashMap<Fork, usize>,
         accounts_index: &AccountsIndex<AccountInfo>,
         pubkey: &Pubkey,
     ) -> Option<(Account, Fork)> {
-        let (info, fork) = accounts_index.get(pubkey, ancestors)?;
+        let (info, fork) = expect_index_io(accounts_index.get(pubkey, ancestors))?;
+        let store = storage.get(&info.id)?;
+        if let Some(account) = self.read_only_cache.load(pubkey, info.id, info.offset) {
+            return Some((account, fork));
+        }
         //TODO: thread this as a ref
-        storage
-            .get(&info.id)
-            .and_then(|store| Some(store.accounts.get_account(info.offset)?.0.clone_account()))
-            .map(|account| (account, fork))
+        let account = store.accounts.get_account(info.offset)?.0.clone_account();
+        self.read_only_cache
+            .store(pubkey, info.id, info.offset, &account);
+        Some((account, fork))
+    }
+
+    /// Pin the version of each of `pubkeys` visible from `ancestors` to `pinned`, only if it
+    /// was stored in a fork after `stored_after` when given
+    pub fn pin_accounts(
+        &self,
+        ancestors: &HashMap<Fork, usize>,
+        pubkeys: &[Pubkey],
+        stored_after: Option<Fork>,
+        pinned: &mut PinnedAccounts,
+    ) {
+        // the accounts are pinned under the index lock, before a store can reclaim them
+        let accounts_index = self.accounts_index.read().unwrap();
+        let storage = self.storage.read().unwrap();
+        for pubkey in pubkeys {
+            let info = match expect_index_io(accounts_index.get(pubkey, ancestors)) {
+                Some((info, fork)) if stored_after.map_or(true, |after| fork > after) => info,
+                _ => continue,
+            };
+            if let Some(store) = storage.get(&info.id) {
+                pinned.stores.entry(info.id).or_insert_with(|| {
+                    store.pin();
+                    store.clone()
+                });
+                pinned.locations.push((*pubkey, info.id, info.offset));
+            }
+        }
     }
 
     pub fn load_slow(
@@ -276,7 +453,7 @@ impl AccountsDB {
     ) -> Option<(Account, Fork)> {
         let accounts_index = self.accounts_index.read().unwrap();
         let storage = self.storage.read().unwrap();
-        Self::load(&storage, ancestors, &accounts_index, pubkey)
+        self.load(&storage, ancestors, &accounts_index, pubkey)
     }
 
     fn fork_storage(&self, fork_id: Fork) -> Arc<AccountStorageEntry> {
@@ -314,6 +491,105 @@ impl AccountsDB {
                 trace!("PURGING {} {}", v.fork_id, fork);
                 v.fork_id != fork
             });
+            self.delta_hashes.write().unwrap().remove(&fork);
+        }
+    }
+
+    /// Purge a fork without paying for it on the caller's thread. The fork's
+    /// storage is detached right away so a new bank reusing the slot id can
+    /// never write into it, while unmapping the storage and removing the
+    /// fork's stale index entries happens on the background cleanup thread.
+    pub fn purge_fork_async(db: &Arc<Self>, fork: Fork) {
+        //add_root should be called first
+        if db.accounts_index.read().unwrap().is_root(fork) {
+            return;
+        }
+        let detached: Vec<_> = {
+            let mut storage = db.storage.write().unwrap();
+            let ids: Vec<_> = storage
+                .values()
+                .filter(|store| store.fork_id == fork)
+                .map(|store| store.id)
+                .collect();
+            ids.iter().filter_map(|id| storage.remove(id)).collect()
+        };
+        db.delta_hashes.write().unwrap().remove(&fork);
+        trace!("PURGING {} async, {} stores", fork, detached.len());
+        if detached.is_empty() {
+            return;
+        }
+
+        *db.pending_purges.lock().unwrap().entry(fork).or_insert(0) += 1;
+        let mut purge_sender = db.purge_sender.lock().unwrap();
+        if purge_sender.is_none() {
+            let (sender, receiver) = channel();
+            let weak_db = Arc::downgrade(db);
+            Builder::new()
+                .name("morgan-accounts-cleanup".to_string())
+                .spawn(move || Self::cleanup_purged_forks(&weak_db, &receiver))
+                .unwrap();
+            *purge_sender = Some(sender);
+        }
+        purge_sender
+            .as_ref()
+            .unwrap()
+            .send((fork, detached))
+            .expect("accounts cleanup thread exited");
+    }
+
+    fn cleanup_purged_forks(weak_db: &Weak<Self>, receiver: &Receiver<PurgeRequest>) {
+        while let Ok((fork, stores)) = receiver.recv() {
+            match weak_db.upgrade() {
+                Some(db) => db.cleanup_purged_fork(fork, stores),
+                None => break,
+            }
+        }
+    }
+
+    fn cleanup_purged_fork(&self, fork: Fork, stores: Vec<Arc<AccountStorageEntry>>) {
+        let dead_ids: HashSet<AppendVecId> = stores.iter().map(|store| store.id).collect();
+        let pubkeys: HashSet<Pubkey> = stores
+            .iter()
+            .flat_map(|store| {
+                store
+                    .accounts
+                    .accounts(0)
+                    .into_iter()
+                    .map(|stored_account| stored_account.meta.pubkey)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        {
+            // only entries pointing at the detached stores are removed, newer
+            // entries written by a bank that reused this fork are kept
+            let mut index = self.accounts_index.write().unwrap();
+            for pubkey in pubkeys.iter() {
+                expect_index_io(
+                    index.remove_fork_entries(pubkey, fork, |info| dead_ids.contains(&info.id)),
+                );
+            }
+        }
+        // the last references to the stores go away here, unmapping them
+        drop(stores);
+        trace!("PURGED {} {} accounts", fork, pubkeys.len());
+
+        {
+            let mut pending_purges = self.pending_purges.lock().unwrap();
+            let count = pending_purges.get_mut(&fork).unwrap();
+            *count -= 1;
+            if *count == 0 {
+                pending_purges.remove(&fork);
+            }
+        }
+        self.purge_done.notify_all();
+    }
+
+    /// Block until the cleanup thread is done with `fork`, a noop if the fork
+    /// isn't queued for purging
+    pub fn wait_for_purge(&self, fork: Fork) {
+        let mut pending_purges = self.pending_purges.lock().unwrap();
+        while pending_purges.contains_key(&fork) {
+            pending_purges = self.purge_done.wait(pending_purges).unwrap();
         }
     }
 
@@ -322,7 +598,8 @@ impl AccountsDB {
             .iter()
             .map(|(pubkey, account)| {
                 let write_version = self.write_version.fetch_add(1, Ordering::Relaxed) as u64;
-                let data_len = if account.difs == 0 {
+                // an account without difs lives on as long as it holds reputations
+                let data_len = if account.difs == 0 && account.reputations == 0 {
                     0
                 } else {
                     account.data.len() as u64
@@ -360,11 +637,18 @@ impl AccountsDB {
         infos: Vec<AccountInfo>,
         accounts: &[(&Pubkey, &Account)],
     ) -> Vec<(Fork, AccountInfo)> {
+        // keys on disk are read under the shared lock, so the write lock isn't held over I/O
+        expect_index_io(
+            self.accounts_index
+                .read()
+                .unwrap()
+                .prefetch(accounts.iter().map(|(pubkey, _)| *pubkey)),
+        );
         let mut index = self.accounts_index.write().unwrap();
         let mut reclaims = vec![];
         for (i, info) in infos.into_iter().enumerate() {
             let key = &accounts[i].0;
-            reclaims.extend(index.insert(fork_id, key, info).into_iter())
+            reclaims.extend(expect_index_io(index.insert(fork_id, key, info)).into_iter())
         }
         reclaims
     }
@@ -408,8 +692,21 @@ impl AccountsDB {
 
     /// Store the account update.
     pub fn store(&self, fork_id: Fork, accounts: &[(&Pubkey, &Account)]) {
+        self.read_only_cache
+            .invalidate(accounts.iter().map(|(pubkey, _)| *pubkey));
+        let hashes: Vec<_> = accounts
+            .iter()
+            .map(|(pubkey, account)| AccountsDeltaHash::hash_account(pubkey, account))
+            .collect();
         let infos = self.store_accounts(fork_id, accounts);
         let reclaims = self.update_index(fork_id, infos, accounts);
+        {
+            let mut delta_hashes = self.delta_hashes.write().unwrap();
+            let delta_hash = delta_hashes.entry(fork_id).or_default();
+            for ((pubkey, _), hash) in accounts.iter().zip(hashes) {
+                delta_hash.store(pubkey, hash);
+            }
+        }
         trace!("reclaim: {}", reclaims.len());
         let mut dead_forks = self.remove_dead_accounts(reclaims);
         trace!("dead_forks: {}", dead_forks.len());
@@ -421,7 +718,15 @@ impl AccountsDB {
     }
 
     pub fn add_root(&self, fork: Fork) {
-        self.accounts_index.write().unwrap().add_root(fork)
+        {
+            let mut accounts_index = self.accounts_index.write().unwrap();
+            accounts_index.add_root(fork);
+            accounts_index.report_metrics();
+        }
+        // the forks below the root are frozen, nothing hashes them again and their banks
+        // verify against the hash they took when frozen
+        self.delta_hashes.write().unwrap().retain(|f, _| *f >= fork);
+        self.read_only_cache.report_metrics();
     }
 }
 
@@ -429,8 +734,8 @@ impl AccountsDB {
 mod tests {
     // TODO: all the bank tests are bank specific, issue: 2194
     use super::*;
-    use rand::{thread_rng, Rng};
     use morgan_interface::account::Account;
+    use rand::{thread_rng, Rng};
 
     fn cleanup_paths(paths: &str) {
         let paths = get_paths_vec(&paths);
@@ -746,6 +1051,25 @@ mod tests {
         check_accounts(&accounts, &pubkeys, 0);
     }
 
+    #[test]
+    fn test_account_many_index_overflow() {
+        let paths = get_tmp_accounts_path!();
+        let accounts = AccountsDB::new_with_index_memory_budget(&paths.paths, 4 * 1024);
+        let mut pubkeys: Vec<Pubkey> = vec![];
+        create_account(&accounts, &mut pubkeys, 0, 100, 0, 0);
+        check_accounts(&accounts, &pubkeys, 0);
+        assert_eq!(
+            accounts
+                .accounts_index
+                .read()
+                .unwrap()
+                .pubkeys()
+                .unwrap()
+                .len(),
+            100
+        );
+    }
+
     #[test]
     fn test_account_update() {
         let paths = get_tmp_accounts_path!();
@@ -851,6 +1175,58 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_pinned_accounts() {
+        let paths = get_tmp_accounts_path!();
+        let accounts = AccountsDB::new(&paths.paths);
+        let pubkey1 = Pubkey::new_rand();
+        let account1 = Account::new(1, 0, ACCOUNT_DATA_FILE_SIZE as usize / 2, &pubkey1);
+        accounts.store(0, &[(&pubkey1, &account1)]);
+        let pubkey2 = Pubkey::new_rand();
+        let account2 = Account::new(1, 0, ACCOUNT_DATA_FILE_SIZE as usize / 2, &pubkey2);
+        accounts.store(0, &[(&pubkey2, &account2)]);
+
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        let mut pinned = PinnedAccounts::default();
+        accounts.pin_accounts(
+            &ancestors,
+            &[pubkey1, Pubkey::new_rand()],
+            None,
+            &mut pinned,
+        );
+        assert_eq!(pinned.len(), 1);
+
+        // the full storage holding account1 isn't reused once account1 is stored over
+        let mut new_account1 = account1.clone();
+        new_account1.difs = 2;
+        accounts.store(0, &[(&pubkey1, &new_account1)]);
+        {
+            let stores = accounts.storage.read().unwrap();
+            assert_eq!(stores.len(), 3);
+            assert_eq!(stores[&0].count(), 0);
+            assert_eq!(stores[&0].status(), AccountStorageStatus::StorageFull);
+        }
+        assert_eq!(pinned.load(), vec![(pubkey1, account1.clone())]);
+        assert_eq!(
+            accounts.load_slow(&ancestors, &pubkey1).unwrap().0,
+            new_account1
+        );
+
+        // and is reclaimed once unpinned
+        drop(pinned);
+        {
+            let stores = accounts.storage.read().unwrap();
+            assert_eq!(stores[&0].status(), AccountStorageStatus::StorageAvailable);
+        }
+
+        // only the accounts stored after the given fork are pinned
+        accounts.store(1, &[(&pubkey1, &account1)]);
+        let ancestors = vec![(0, 1), (1, 0)].into_iter().collect();
+        let mut pinned = PinnedAccounts::default();
+        accounts.pin_accounts(&ancestors, &[pubkey1, pubkey2], Some(0), &mut pinned);
+        assert_eq!(pinned.load(), vec![(pubkey1, account1)]);
+    }
+
     #[test]
     fn test_purge_fork_not_root() {
         let paths = get_tmp_accounts_path!();
@@ -858,9 +1234,9 @@ mod tests {
         let mut pubkeys: Vec<Pubkey> = vec![];
         create_account(&accounts, &mut pubkeys, 0, 1, 0, 0);
         let ancestors = vec![(0, 0)].into_iter().collect();
-        assert!(accounts.load_slow(&ancestors, &pubkeys[0]).is_some());;
+        assert!(accounts.load_slow(&ancestors, &pubkeys[0]).is_some());
         accounts.purge_fork(0);
-        assert!(accounts.load_slow(&ancestors, &pubkeys[0]).is_none());;
+        assert!(accounts.load_slow(&ancestors, &pubkeys[0]).is_none());
     }
 
     #[test]
@@ -875,6 +1251,89 @@ mod tests {
         assert!(accounts.load_slow(&ancestors, &pubkeys[0]).is_some());
     }
 
+    #[test]
+    fn test_purge_fork_async_not_root() {
+        let paths = get_tmp_accounts_path!();
+        let db = Arc::new(AccountsDB::new(&paths.paths));
+        let mut pubkeys: Vec<Pubkey> = vec![];
+        create_account(&db, &mut pubkeys, 1, 10, 0, 0);
+        let ancestors = vec![(1, 1)].into_iter().collect();
+        assert!(db.load_slow(&ancestors, &pubkeys[0]).is_some());
+
+        AccountsDB::purge_fork_async(&db, 1);
+        // storage is detached before purge_fork_async returns
+        assert!(!db.has_accounts(1));
+        assert!(db.load_slow(&ancestors, &pubkeys[0]).is_none());
+
+        db.wait_for_purge(1);
+        let index = db.accounts_index.read().unwrap();
+        assert!(pubkeys
+            .iter()
+            .all(|pubkey| index.get(pubkey, &ancestors).unwrap().is_none()));
+    }
+
+    #[test]
+    fn test_purge_fork_async_after_root() {
+        let paths = get_tmp_accounts_path!();
+        let db = Arc::new(AccountsDB::new(&paths.paths));
+        let mut pubkeys: Vec<Pubkey> = vec![];
+        create_account(&db, &mut pubkeys, 0, 1, 0, 0);
+        db.add_root(0);
+        AccountsDB::purge_fork_async(&db, 0);
+        db.wait_for_purge(0);
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        assert!(db.load_slow(&ancestors, &pubkeys[0]).is_some());
+    }
+
+    #[test]
+    fn test_purge_fork_async_reuse_fork() {
+        let paths = get_tmp_accounts_path!();
+        let db = Arc::new(AccountsDB::new(&paths.paths));
+        let key0 = Pubkey::new_rand();
+        let key1 = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, 0, &key0);
+        db.store(0, &[(&key0, &account0), (&key1, &account0)]);
+        db.add_root(0);
+
+        // a dead fork 1 overwrote both keys
+        let account1 = Account::new(2, 0, 0, &key0);
+        db.store(1, &[(&key0, &account1), (&key1, &account1)]);
+        AccountsDB::purge_fork_async(&db, 1);
+
+        // a new fork 1 only writes key0, possibly before cleanup ran
+        let account2 = Account::new(3, 0, 0, &key0);
+        db.store(1, &[(&key0, &account2)]);
+        db.wait_for_purge(1);
+
+        let ancestors = vec![(0, 0), (1, 1)].into_iter().collect();
+        assert_eq!(db.load_slow(&ancestors, &key0), Some((account2, 1)));
+        assert_eq!(db.load_slow(&ancestors, &key1), Some((account0, 0)));
+    }
+
+    #[test]
+    fn test_purge_fork_async_many_forks() {
+        let paths = get_tmp_accounts_path!();
+        let db = Arc::new(AccountsDB::new(&paths.paths));
+        let key = Pubkey::new_rand();
+        let account0 = Account::new(1, 0, 0, &key);
+        db.store(0, &[(&key, &account0)]);
+        db.add_root(0);
+
+        for round in 0..10 {
+            for fork in 1..5 {
+                let account = Account::new(round * 10 + fork, 0, 0, &key);
+                db.store(fork, &[(&key, &account)]);
+                AccountsDB::purge_fork_async(&db, fork);
+            }
+        }
+        for fork in 1..5 {
+            db.wait_for_purge(fork);
+            let ancestors = vec![(0, 0), (fork, 1)].into_iter().collect();
+            assert_eq!(db.load_slow(&ancestors, &key), Some((account0.clone(), 0)));
+        }
+        assert_eq!(db.storage.read().unwrap().len(), 1);
+    }
+
     #[test]
     fn test_lazy_gc_fork() {
         //This test is pedantic
@@ -893,6 +1352,7 @@ mod tests {
             .unwrap()
             .get(&pubkey, &ancestors)
             .unwrap()
+            .unwrap()
             .0
             .clone();
         //fork 0 is behind root, but it is not root, therefore it is purged
@@ -912,5 +1372,4 @@ mod tests {
         let ancestors = vec![(1, 1)].into_iter().collect();
         assert_eq!(accounts.load_slow(&ancestors, &pubkey), Some((account, 1)));
     }
-
 }
@@ -0,0 +1,108 @@
+//! The `accounts_delta_hash` module keeps the hash of the latest version of every account a
+//! fork stored as they are stored, so freezing a bank doesn't have to scan the fork's storage.
+//! The result hashes those account hashes in pubkey order, which makes it independent of the
+//! order accounts were stored in.
+
+use crate::append_vec::{AccountBalance, StoredAccount};
+use bincode::serialize;
+use hashbrown::HashMap;
+use morgan_interface::account::Account;
+use morgan_interface::hash::{hashv, Hash, Hasher};
+use morgan_interface::pubkey::Pubkey;
+
+#[derive(Debug, Default)]
+pub struct AccountsDeltaHash {
+    /// hash of the latest version of every account the fork stored
+    hashes: HashMap<Pubkey, Hash>,
+}
+
+impl AccountsDeltaHash {
+    fn hash(pubkey: &Pubkey, balance: &AccountBalance, data: &[u8]) -> Hash {
+        hashv(&[pubkey.as_ref(), &serialize(balance).unwrap(), data])
+    }
+
+    /// Hash of `account` as the accounts store keeps it, without data once it holds no difs
+    pub fn hash_account(pubkey: &Pubkey, account: &Account) -> Hash {
+        let balance = AccountBalance {
+            difs: account.difs,
+            owner: account.owner,
+            executable: account.executable,
+            reputations: account.reputations,
+        };
+        let data: &[u8] = if account.difs == 0 && account.reputations == 0 {
+            &[]
+        } else {
+            &account.data
+        };
+        Self::hash(pubkey, &balance, data)
+    }
+
+    pub fn hash_stored_account(stored_account: &StoredAccount) -> Hash {
+        Self::hash(
+            &stored_account.meta.pubkey,
+            stored_account.balance,
+            stored_account.data,
+        )
+    }
+
+    /// Take `hash` as the latest version of `pubkey`, replacing the one stored before
+    pub fn store(&mut self, pubkey: &Pubkey, hash: Hash) {
+        self.hashes.insert(*pubkey, hash);
+    }
+
+    /// The hash of the accounts stored so far, None before any was
+    pub fn result(&self) -> Option<Hash> {
+        if self.hashes.is_empty() {
+            return None;
+        }
+        let mut hashes: Vec<_> = self.hashes.iter().collect();
+        hashes.sort_unstable_by_key(|(pubkey, _)| *pubkey);
+        let mut hasher = Hasher::default();
+        for (pubkey, hash) in hashes {
+            hasher.hashv(&[pubkey.as_ref(), hash.as_ref()]);
+        }
+        Some(hasher.result())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accounts_delta_hash() {
+        let keys: Vec<_> = (0..3).map(|_| Pubkey::new_rand()).collect();
+        let accounts: Vec<_> = (1..4)
+            .map(|difs| Account::new(difs, 0, 1, &Pubkey::default()))
+            .collect();
+        let hashes: Vec<_> = keys
+            .iter()
+            .zip(&accounts)
+            .map(|(key, account)| AccountsDeltaHash::hash_account(key, account))
+            .collect();
+
+        let mut forward = AccountsDeltaHash::default();
+        assert_eq!(forward.result(), None);
+        for (key, hash) in keys.iter().zip(&hashes) {
+            forward.store(key, *hash);
+        }
+        let mut backward = AccountsDeltaHash::default();
+        for (key, hash) in keys.iter().zip(&hashes).rev() {
+            backward.store(key, *hash);
+        }
+        assert!(forward.result().is_some());
+        assert_eq!(forward.result(), backward.result());
+
+        // a replaced version no longer counts
+        backward.store(&keys[0], hashes[2]);
+        assert_ne!(forward.result(), backward.result());
+        backward.store(&keys[0], hashes[0]);
+        assert_eq!(forward.result(), backward.result());
+
+        // the same account under another key hashes differently
+        assert_ne!(
+            AccountsDeltaHash::hash_account(&keys[0], &accounts[0]),
+            AccountsDeltaHash::hash_account(&keys[1], &accounts[0])
+        );
+    }
+}
@@ -1,22 +1,170 @@
+//! The `accounts_index` module maps each pubkey to where its account lives on every fork.
+//!
+//! The index is in memory by default.  Built with `new_with_overflow` it keeps about a
+//! memory budget worth of recently used keys in memory and moves the rest to bucket files,
+//! see `accounts_index_buckets`, so a node with modest RAM can follow a large account set.
+//! Keys read back from disk move into memory on the next write to the index, so reads only
+//! need a shared lock on it.
+
+use crate::accounts_index_buckets::IndexBuckets;
 use hashbrown::{HashMap, HashSet};
 use log::*;
 use morgan_interface::pubkey::Pubkey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::mem::{self, size_of};
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 pub type Fork = u64;
 
+/// Eviction frees this fraction of the memory budget at once, so stores over the budget
+/// don't each rewrite buckets for a single key
+const EVICTION_BATCH_DIVISOR: usize = 4;
+
+/// Keys `get` read from disk wait at most this many at once to move back into memory
+const MAX_PENDING_PROMOTIONS: usize = 4096;
+
+/// The first 8 bytes of `pubkey` as a big-endian integer, which orders pubkeys the way
+/// `pubkeys_in_range` partitions them
+pub fn pubkey_prefix(pubkey: &Pubkey) -> u64 {
+    let mut prefix = [0u8; 8];
+    prefix.copy_from_slice(&pubkey.as_ref()[..8]);
+    u64::from_be_bytes(prefix)
+}
+
+struct IndexEntry<T> {
+    forks: Vec<(Fork, T)>,
+    /// set on every use, cleared when the entry survives an eviction pass
+    hit: AtomicBool,
+}
+
+impl<T> IndexEntry<T> {
+    fn new(forks: Vec<(Fork, T)>) -> Self {
+        Self {
+            forks,
+            hit: AtomicBool::new(true),
+        }
+    }
+
+    fn size(&self) -> usize {
+        size_of::<Pubkey>() + size_of::<Self>() + self.forks.len() * size_of::<(Fork, T)>()
+    }
+}
+
 #[derive(Default)]
 pub struct AccountsIndex<T> {
-    account_maps: HashMap<Pubkey, Vec<(Fork, T)>>,
+    /// ordered by pubkey, and so by `pubkey_prefix`, for `pubkeys_in_range`
+    account_maps: BTreeMap<Pubkey, IndexEntry<T>>,
     roots: HashSet<Fork>,
     //This value that needs to be stored to recover the index from AppendVec
     pub last_root: Fork,
+    /// keys evicted from `account_maps` to keep it under `memory_budget`
+    overflow: Option<IndexBuckets<T>>,
+    memory_budget: usize,
+    /// estimated bytes held by `account_maps`
+    size: usize,
+    /// keys read from disk, moved into `account_maps` by the next write to the index
+    promotions: Mutex<HashMap<Pubkey, Vec<(Fork, T)>>>,
 }
 
-impl<T: Clone> AccountsIndex<T> {
+impl<T: Clone + Serialize + DeserializeOwned> AccountsIndex<T> {
+    /// An index holding about `memory_budget` bytes of entries in memory, the least recently
+    /// used keys overflow to bucket files under `overflow_path`
+    pub fn new_with_overflow<P: AsRef<Path>>(
+        overflow_path: P,
+        memory_budget: usize,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            account_maps: BTreeMap::new(),
+            roots: HashSet::new(),
+            last_root: 0,
+            overflow: Some(IndexBuckets::new(overflow_path)?),
+            memory_budget,
+            size: 0,
+            promotions: Mutex::new(HashMap::new()),
+        })
+    }
+
     /// Get an account
     /// The latest account that appears in `ancestors` or `roots` is returned.
-    pub fn get(&self, pubkey: &Pubkey, ancestors: &HashMap<Fork, usize>) -> Option<(&T, Fork)> {
-        let list = self.account_maps.get(pubkey)?;
+    pub fn get(
+        &self,
+        pubkey: &Pubkey,
+        ancestors: &HashMap<Fork, usize>,
+    ) -> io::Result<Option<(T, Fork)>> {
+        if let Some(entry) = self.account_maps.get(pubkey) {
+            entry.hit.store(true, Ordering::Relaxed);
+            return Ok(self.latest(&entry.forks, ancestors));
+        }
+        let overflow = match &self.overflow {
+            Some(overflow) => overflow,
+            None => return Ok(None),
+        };
+        if let Some(forks) = self.promotions.lock().unwrap().get(pubkey) {
+            return Ok(self.latest(forks, ancestors));
+        }
+        let forks = match overflow.get(pubkey)? {
+            Some(forks) => forks,
+            None => return Ok(None),
+        };
+        let latest = self.latest(&forks, ancestors);
+        let mut promotions = self.promotions.lock().unwrap();
+        if promotions.len() < MAX_PENDING_PROMOTIONS {
+            promotions.insert(*pubkey, forks);
+        }
+        Ok(latest)
+    }
+
+    /// Read those of `pubkeys` that are on disk ahead of writing them, the write then moves
+    /// them into memory without holding the index over disk reads
+    pub fn prefetch<'a, I>(&self, pubkeys: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a Pubkey>,
+    {
+        let overflow = match &self.overflow {
+            Some(overflow) => overflow,
+            None => return Ok(()),
+        };
+        for pubkey in pubkeys {
+            if self.account_maps.contains_key(pubkey)
+                || self.promotions.lock().unwrap().contains_key(pubkey)
+            {
+                continue;
+            }
+            if let Some(forks) = overflow.get(pubkey)? {
+                self.promotions.lock().unwrap().insert(*pubkey, forks);
+            }
+        }
+        Ok(())
+    }
+
+    /// Move the keys read from disk since the last write into memory.  Only writes change
+    /// what's on disk, so what was read is still current.
+    fn promote(&mut self) -> io::Result<()> {
+        let promotions = mem::replace(self.promotions.get_mut().unwrap(), HashMap::new());
+        if promotions.is_empty() {
+            return Ok(());
+        }
+        inc_new_counter_info!("accounts_index-promoted", promotions.len());
+        let pubkeys: Vec<_> = promotions.keys().cloned().collect();
+        // into memory first, a failed removal from disk must not lose the entries
+        for (pubkey, forks) in promotions {
+            self.put_forks(pubkey, forks);
+        }
+        if let Some(overflow) = &self.overflow {
+            for pubkey in &pubkeys {
+                overflow.remove(pubkey)?;
+            }
+        }
+        self.evict_over_budget()
+    }
+
+    fn latest(&self, list: &[(Fork, T)], ancestors: &HashMap<Fork, usize>) -> Option<(T, Fork)> {
         let mut max = 0;
         let mut rv = None;
         for e in list.iter().rev() {
@@ -26,18 +174,20 @@ impl<T: Clone> AccountsIndex<T> {
                 max = e.0;
             }
         }
-        rv
+        rv.map(|(info, fork)| (info.clone(), fork))
     }
 
     /// Insert a new fork.
     /// @retval - The return value contains any squashed accounts that can freed from storage.
-    pub fn insert(&mut self, fork: Fork, pubkey: &Pubkey, account_info: T) -> Vec<(Fork, T)> {
+    pub fn insert(
+        &mut self,
+        fork: Fork,
+        pubkey: &Pubkey,
+        account_info: T,
+    ) -> io::Result<Vec<(Fork, T)>> {
+        self.promote()?;
         let mut rv = vec![];
-        let mut fork_vec: Vec<(Fork, T)> = vec![];
-        {
-            let entry = self.account_maps.entry(*pubkey).or_insert(vec![]);
-            std::mem::swap(entry, &mut fork_vec);
-        };
+        let mut fork_vec = self.take_forks(pubkey)?;
 
         // filter out old entries
         rv.extend(fork_vec.iter().filter(|(f, _)| *f == fork).cloned());
@@ -53,12 +203,115 @@ impl<T: Clone> AccountsIndex<T> {
                 .cloned(),
         );
         fork_vec.retain(|(fork, _)| !self.is_purged(*fork));
-        {
-            let entry = self.account_maps.entry(*pubkey).or_insert(vec![]);
-            std::mem::swap(entry, &mut fork_vec);
+        self.put_forks(*pubkey, fork_vec);
+        self.evict_over_budget()?;
+        Ok(rv)
+    }
+
+    /// Remove the entries of `pubkey`, from memory or from disk
+    fn take_forks(&mut self, pubkey: &Pubkey) -> io::Result<Vec<(Fork, T)>> {
+        if let Some(entry) = self.account_maps.remove(pubkey) {
+            self.size -= entry.size();
+            return Ok(entry.forks);
+        }
+        match &self.overflow {
+            Some(overflow) => Ok(overflow.take(pubkey)?.unwrap_or_default()),
+            None => Ok(vec![]),
+        }
+    }
+
+    fn put_forks(&mut self, pubkey: Pubkey, forks: Vec<(Fork, T)>) {
+        let entry = IndexEntry::new(forks);
+        self.size += entry.size();
+        self.account_maps.insert(pubkey, entry);
+    }
+
+    /// Move keys that weren't used since the last pass to disk until the entries in memory
+    /// are well under the memory budget, a noop without overflow
+    fn evict_over_budget(&mut self) -> io::Result<()> {
+        let overflow = match self.overflow.as_ref() {
+            Some(overflow) => overflow,
+            None => return Ok(()),
         };
-        rv
+        if self.size <= self.memory_budget {
+            return Ok(());
+        }
+
+        let target = self.memory_budget - self.memory_budget / EVICTION_BATCH_DIVISOR;
+        let mut size = self.size;
+        let mut evicted = HashSet::new();
+        // the first pass gives used entries a second chance, the second one evicts them too
+        for _ in 0..2 {
+            for (pubkey, entry) in self.account_maps.iter() {
+                if size <= target {
+                    break;
+                }
+                if !evicted.contains(pubkey) && !entry.hit.swap(false, Ordering::Relaxed) {
+                    size -= entry.size();
+                    evicted.insert(*pubkey);
+                }
+            }
+        }
+
+        inc_new_counter_info!("accounts_index-evicted", evicted.len());
+        let account_maps = &mut self.account_maps;
+        let entries: Vec<_> = evicted
+            .into_iter()
+            .filter_map(|pubkey| {
+                account_maps
+                    .remove(&pubkey)
+                    .map(|entry| (pubkey, entry.forks))
+            })
+            .collect();
+        if let Err(err) = overflow.store(&entries) {
+            // kept in memory rather than lost
+            for (pubkey, forks) in entries {
+                self.put_forks(pubkey, forks);
+            }
+            return Err(err);
+        }
+        self.size = size;
+        Ok(())
+    }
+
+    pub fn report_metrics(&self) {
+        let on_disk = self.overflow.as_ref().map_or(0, IndexBuckets::len);
+        datapoint_info!(
+            "accounts_db-accounts_index",
+            ("in_memory", self.account_maps.len(), i64),
+            ("in_memory_size", self.size, i64),
+            ("on_disk", on_disk, i64)
+        );
     }
+
+    /// Every pubkey with an entry on some fork
+    pub fn pubkeys(&self) -> io::Result<Vec<Pubkey>> {
+        let mut pubkeys: Vec<_> = self.account_maps.keys().cloned().collect();
+        if let Some(overflow) = &self.overflow {
+            pubkeys.extend(overflow.pubkeys()?);
+        }
+        Ok(pubkeys)
+    }
+
+    /// Every pubkey with an entry on some fork whose `pubkey_prefix` is in `prefixes`.  Only
+    /// the bucket files holding such keys are read
+    pub fn pubkeys_in_range(&self, prefixes: &RangeInclusive<u64>) -> io::Result<Vec<Pubkey>> {
+        let mut pubkeys = vec![];
+        if !prefixes.is_empty() {
+            let bound = |prefix: u64, rest: u8| {
+                let mut bytes = [rest; 32];
+                bytes[..8].copy_from_slice(&prefix.to_be_bytes());
+                Pubkey::new(&bytes)
+            };
+            let keys = bound(*prefixes.start(), 0)..=bound(*prefixes.end(), std::u8::MAX);
+            pubkeys.extend(self.account_maps.range(keys).map(|(pubkey, _)| *pubkey));
+        }
+        if let Some(overflow) = &self.overflow {
+            pubkeys.extend(overflow.pubkeys_in_range(prefixes)?);
+        }
+        Ok(pubkeys)
+    }
+
     pub fn is_purged(&self, fork: Fork) -> bool {
         fork < self.last_root
     }
@@ -73,6 +326,43 @@ impl<T: Clone> AccountsIndex<T> {
         self.last_root = fork;
         self.roots.insert(fork);
     }
+    /// Remove the entries `pubkey` has in `fork` and that match `is_dead`
+    pub fn remove_fork_entries<F>(
+        &mut self,
+        pubkey: &Pubkey,
+        fork: Fork,
+        is_dead: F,
+    ) -> io::Result<()>
+    where
+        F: Fn(&T) -> bool,
+    {
+        self.promote()?;
+        let is_empty = match self.account_maps.get_mut(pubkey) {
+            Some(entry) => {
+                self.size -= entry.size();
+                entry.forks.retain(|(f, info)| *f != fork || !is_dead(info));
+                self.size += entry.size();
+                entry.forks.is_empty()
+            }
+            None => {
+                if let Some(overflow) = self.overflow.as_ref() {
+                    if let Some(mut fork_vec) = overflow.take(pubkey)? {
+                        fork_vec.retain(|(f, info)| *f != fork || !is_dead(info));
+                        if !fork_vec.is_empty() {
+                            overflow.store(&[(*pubkey, fork_vec)])?;
+                        }
+                    }
+                }
+                false
+            }
+        };
+        if is_empty {
+            if let Some(entry) = self.account_maps.remove(pubkey) {
+                self.size -= entry.size();
+            }
+        }
+        Ok(())
+    }
     /// Remove the fork when the storage for the fork is freed
     /// Accounts no longer reference this fork.
     pub fn cleanup_dead_fork(&mut self, fork: Fork) {
@@ -90,40 +380,43 @@ mod tests {
         let key = Keypair::new();
         let index = AccountsIndex::<bool>::default();
         let ancestors = HashMap::new();
-        assert_eq!(index.get(&key.pubkey(), &ancestors), None);
+        assert_eq!(index.get(&key.pubkey(), &ancestors).unwrap(), None);
     }
 
     #[test]
     fn test_insert_no_ancestors() {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
 
         let ancestors = HashMap::new();
-        assert_eq!(index.get(&key.pubkey(), &ancestors), None);
+        assert_eq!(index.get(&key.pubkey(), &ancestors).unwrap(), None);
     }
 
     #[test]
     fn test_insert_wrong_ancestors() {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
 
         let ancestors = vec![(1, 1)].into_iter().collect();
-        assert_eq!(index.get(&key.pubkey(), &ancestors), None);
+        assert_eq!(index.get(&key.pubkey(), &ancestors).unwrap(), None);
     }
 
     #[test]
     fn test_insert_with_ancestors() {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
 
         let ancestors = vec![(0, 0)].into_iter().collect();
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&true, 0)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((true, 0))
+        );
     }
 
     #[test]
@@ -138,12 +431,15 @@ mod tests {
     fn test_insert_with_root() {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
 
         let ancestors = vec![].into_iter().collect();
         index.add_root(0);
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&true, 0)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((true, 0))
+        );
     }
 
     #[test]
@@ -197,13 +493,19 @@ mod tests {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
         let ancestors = vec![(0, 0)].into_iter().collect();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&true, 0)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((true, 0))
+        );
 
-        let gc = index.insert(0, &key.pubkey(), false);
+        let gc = index.insert(0, &key.pubkey(), false).unwrap();
         assert_eq!(gc, vec![(0, true)]);
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&false, 0)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((false, 0))
+        );
     }
 
     #[test]
@@ -211,25 +513,170 @@ mod tests {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
         let ancestors = vec![(0, 0)].into_iter().collect();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
-        let gc = index.insert(1, &key.pubkey(), false);
+        let gc = index.insert(1, &key.pubkey(), false).unwrap();
         assert!(gc.is_empty());
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&true, 0)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((true, 0))
+        );
         let ancestors = vec![(1, 0)].into_iter().collect();
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&false, 1)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((false, 1))
+        );
     }
 
     #[test]
     fn test_update_gc_purged_fork() {
         let key = Keypair::new();
         let mut index = AccountsIndex::<bool>::default();
-        let gc = index.insert(0, &key.pubkey(), true);
+        let gc = index.insert(0, &key.pubkey(), true).unwrap();
         assert!(gc.is_empty());
         index.add_root(1);
-        let gc = index.insert(1, &key.pubkey(), false);
+        let gc = index.insert(1, &key.pubkey(), false).unwrap();
         assert_eq!(gc, vec![(0, true)]);
         let ancestors = vec![].into_iter().collect();
-        assert_eq!(index.get(&key.pubkey(), &ancestors), Some((&false, 1)));
+        assert_eq!(
+            index.get(&key.pubkey(), &ancestors).unwrap(),
+            Some((false, 1))
+        );
+    }
+
+    fn tmp_overflow_path(name: &str) -> std::path::PathBuf {
+        let out_dir = std::env::var("OUT_DIR").unwrap_or_else(|_| "target".to_string());
+        std::path::PathBuf::from(out_dir).join(name)
+    }
+
+    #[test]
+    fn test_overflow_under_budget() {
+        let path = tmp_overflow_path("test_overflow_under_budget");
+        let budget = 10 * size_of::<IndexEntry<bool>>();
+        let mut index = AccountsIndex::<bool>::new_with_overflow(&path, budget).unwrap();
+        let keys: Vec<_> = (0..100).map(|_| Keypair::new().pubkey()).collect();
+        for key in &keys {
+            assert!(index.insert(0, key, true).unwrap().is_empty());
+            assert!(index.size <= budget);
+        }
+        assert!(index.overflow.as_ref().unwrap().len() > 90);
+
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        assert!(keys
+            .iter()
+            .all(|key| index.get(key, &ancestors).unwrap() == Some((true, 0))));
+        let mut pubkeys = index.pubkeys().unwrap();
+        pubkeys.sort();
+        let mut expected = keys.clone();
+        expected.sort();
+        assert_eq!(pubkeys, expected);
+        let _ignored = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_pubkeys_in_range() {
+        let path = tmp_overflow_path("test_pubkeys_in_range");
+        let budget = 10 * size_of::<IndexEntry<bool>>();
+        let mut index = AccountsIndex::<bool>::new_with_overflow(&path, budget).unwrap();
+        let keys: Vec<_> = (0..100).map(|_| Keypair::new().pubkey()).collect();
+        for key in &keys {
+            index.insert(0, key, true).unwrap();
+        }
+
+        // the ranges split the keys, whether in memory or on disk, between them
+        let middle = u64::max_value() / 3;
+        let mut low = index.pubkeys_in_range(&(0..=middle)).unwrap();
+        let mut high = index
+            .pubkeys_in_range(&(middle + 1..=u64::max_value()))
+            .unwrap();
+        assert!(low.iter().all(|key| pubkey_prefix(key) <= middle));
+        assert!(high.iter().all(|key| pubkey_prefix(key) > middle));
+        low.append(&mut high);
+        low.sort();
+        let mut expected = keys.clone();
+        expected.sort();
+        assert_eq!(low, expected);
+        let _ignored = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_pubkeys_in_range_bounds() {
+        let mut index = AccountsIndex::<bool>::default();
+        let key = |prefix: u64, rest: u8| {
+            let mut bytes = [rest; 32];
+            bytes[..8].copy_from_slice(&prefix.to_be_bytes());
+            Pubkey::new(&bytes)
+        };
+        let keys = [key(1, 0xff), key(2, 0), key(2, 0xff), key(3, 0)];
+        for key in &keys {
+            index.insert(0, key, true).unwrap();
+        }
+
+        // both ends of a range take every key with their prefix
+        assert_eq!(
+            index.pubkeys_in_range(&(2..=2)).unwrap(),
+            keys[1..3].to_vec()
+        );
+        assert_eq!(
+            index.pubkeys_in_range(&(0..=2)).unwrap(),
+            keys[..3].to_vec()
+        );
+        assert_eq!(
+            index.pubkeys_in_range(&(3..=u64::max_value())).unwrap(),
+            keys[3..].to_vec()
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = 3..=2;
+        assert!(index.pubkeys_in_range(&empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_overflow_update_cold_key() {
+        let path = tmp_overflow_path("test_overflow_update_cold_key");
+        let mut index = AccountsIndex::<bool>::new_with_overflow(&path, 0).unwrap();
+        let key = Keypair::new().pubkey();
+        assert!(index.insert(0, &key, true).unwrap().is_empty());
+        assert!(index.account_maps.is_empty());
+
+        // the old entry comes back from disk to be reclaimed
+        assert_eq!(index.insert(0, &key, false).unwrap(), vec![(0, true)]);
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        assert_eq!(index.get(&key, &ancestors).unwrap(), Some((false, 0)));
+
+        index.remove_fork_entries(&key, 0, |info| !info).unwrap();
+        assert_eq!(index.get(&key, &ancestors).unwrap(), None);
+        assert!(index.pubkeys().unwrap().is_empty());
+        let _ignored = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_overflow_promote() {
+        let path = tmp_overflow_path("test_overflow_promote");
+        let mut index = AccountsIndex::<bool>::new_with_overflow(&path, 0).unwrap();
+        let key = Keypair::new().pubkey();
+        assert!(index.insert(0, &key, true).unwrap().is_empty());
+        assert!(index.account_maps.is_empty());
+
+        // a key read from disk moves into memory on the next write
+        index.memory_budget = 1024 * 1024;
+        let ancestors = vec![(0, 0)].into_iter().collect();
+        assert_eq!(index.get(&key, &ancestors).unwrap(), Some((true, 0)));
+        assert!(index.account_maps.is_empty());
+        let other_key = Keypair::new().pubkey();
+        assert!(index.insert(0, &other_key, true).unwrap().is_empty());
+        assert!(index.account_maps.contains_key(&key));
+        assert_eq!(index.overflow.as_ref().unwrap().len(), 0);
+        assert_eq!(index.get(&key, &ancestors).unwrap(), Some((true, 0)));
+
+        // so does a key read ahead of writing it
+        index.memory_budget = 0;
+        assert_eq!(index.insert(0, &other_key, false).unwrap(), vec![(0, true)]);
+        assert!(index.account_maps.is_empty());
+        index.memory_budget = 1024 * 1024;
+        index.prefetch(&[key]).unwrap();
+        assert!(index.insert(1, &key, false).unwrap().is_empty());
+        assert!(index.account_maps.contains_key(&key));
+        assert_eq!(index.overflow.as_ref().unwrap().len(), 1);
+        let _ignored = std::fs::remove_dir_all(&path);
     }
 }
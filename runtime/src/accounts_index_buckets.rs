@@ -0,0 +1,426 @@
+//! The `accounts_index_buckets` module keeps the cold keys of an `AccountsIndex` on disk.
+//!
+//! Keys are spread over bucket files by their first byte.  A bucket file is a log of records,
+//! each a pubkey and the length of its entries ahead of the entries, so a lookup skips over
+//! the other keys' entries instead of deserializing them.  Stores append to the log and
+//! removals are only noted in memory, the newest record of a key wins, until a bucket holding
+//! mostly stale records is compacted.  Every bucket keeps a bloom filter of its keys in memory
+//! so looking up a key that isn't on disk, like every newly created account, doesn't touch the
+//! file, and has its own lock so buckets are read and written independently.
+//! Nothing here outlives the process, the index is rebuilt from storage on restart.
+
+use crate::accounts_index::{pubkey_prefix, Fork};
+use crate::bloom::Bloom;
+use bincode::{deserialize, serialize};
+use hashbrown::{HashMap, HashSet};
+use morgan_interface::pubkey::Pubkey;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+const NUM_BUCKETS: usize = 256;
+const BLOOM_FALSE_RATE: f64 = 0.01;
+const BLOOM_MAX_BITS: usize = 8 * 1024 * 1024;
+/// Keys a bucket's bloom filter is sized for at least, it's resized past that
+const BLOOM_MIN_KEYS: usize = 64;
+/// A bucket is compacted once it holds this many records and more than twice its live keys
+const COMPACTION_MIN_RECORDS: usize = 64;
+
+const PUBKEY_SIZE: usize = size_of::<Pubkey>();
+const RECORD_HEADER_SIZE: usize = PUBKEY_SIZE + size_of::<u32>();
+
+#[derive(Default)]
+struct BucketMeta {
+    /// records in the file, stale ones included
+    records: usize,
+    /// keys whose newest record is live
+    live: usize,
+    /// keys removed since their newest record was written
+    removed: HashSet<Pubkey>,
+    filter: Option<Bloom<Pubkey>>,
+    /// keys `filter` was sized for
+    filter_capacity: usize,
+}
+
+impl BucketMeta {
+    fn may_contain(&self, pubkey: &Pubkey) -> bool {
+        !self.removed.contains(pubkey)
+            && self
+                .filter
+                .as_ref()
+                .map(|filter| filter.contains(pubkey))
+                .unwrap_or(false)
+    }
+}
+
+pub struct IndexBuckets<T> {
+    path: PathBuf,
+    buckets: Vec<RwLock<BucketMeta>>,
+    len: AtomicUsize,
+    _phantom: PhantomData<T>,
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+/// Visit the header of every record of the bucket file at `path` in order, with the offset
+/// and length of the record's entries
+fn scan_records<F>(path: &Path, mut visit: F) -> io::Result<()>
+where
+    F: FnMut(Pubkey, u64, usize),
+{
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = [0u8; RECORD_HEADER_SIZE];
+    let mut offset = 0;
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => (),
+            Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        let pubkey = Pubkey::new(&header[..PUBKEY_SIZE]);
+        let mut len = [0u8; size_of::<u32>()];
+        len.copy_from_slice(&header[PUBKEY_SIZE..]);
+        let len = u32::from_le_bytes(len) as usize;
+        offset += RECORD_HEADER_SIZE as u64;
+        visit(pubkey, offset, len);
+        // skipped through the reader's buffer, seeking would drop it for every record
+        let skipped = io::copy(&mut reader.by_ref().take(len as u64), &mut io::sink())?;
+        if skipped != len as u64 {
+            return Err(invalid_data("truncated accounts index bucket"));
+        }
+        offset += len as u64;
+    }
+}
+
+fn read_at(path: &Path, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut data = vec![0; len];
+    file.read_exact(&mut data)?;
+    Ok(data)
+}
+
+fn write_record<W: Write>(writer: &mut W, pubkey: &Pubkey, entries: &[u8]) -> io::Result<()> {
+    writer.write_all(pubkey.as_ref())?;
+    writer.write_all(&(entries.len() as u32).to_le_bytes())?;
+    writer.write_all(entries)
+}
+
+impl<T: Clone + Serialize + DeserializeOwned> IndexBuckets<T> {
+    /// Empty buckets under `path`, whatever a previous process left there is removed
+    pub fn new<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let _ignored = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path)?;
+        Ok(Self {
+            path,
+            buckets: (0..NUM_BUCKETS)
+                .map(|_| RwLock::new(BucketMeta::default()))
+                .collect(),
+            len: AtomicUsize::new(0),
+            _phantom: PhantomData,
+        })
+    }
+
+    /// Number of keys on disk
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// The entries of `pubkey` if it is on disk
+    pub fn get(&self, pubkey: &Pubkey) -> io::Result<Option<Vec<(Fork, T)>>> {
+        let index = Self::bucket_index(pubkey);
+        let meta = self.buckets[index].read().unwrap();
+        if !meta.may_contain(pubkey) {
+            return Ok(None);
+        }
+        self.read_entries(index, pubkey)
+    }
+
+    /// Remove `pubkey` from disk, returning its entries
+    pub fn take(&self, pubkey: &Pubkey) -> io::Result<Option<Vec<(Fork, T)>>> {
+        let index = Self::bucket_index(pubkey);
+        let mut meta = self.buckets[index].write().unwrap();
+        if !meta.may_contain(pubkey) {
+            return Ok(None);
+        }
+        let entries = self.read_entries(index, pubkey)?;
+        if entries.is_some() {
+            self.remove_live(index, &mut meta, pubkey)?;
+        }
+        Ok(entries)
+    }
+
+    /// Remove `pubkey`, which `get` just found on disk, without reading it again
+    pub fn remove(&self, pubkey: &Pubkey) -> io::Result<()> {
+        let index = Self::bucket_index(pubkey);
+        let mut meta = self.buckets[index].write().unwrap();
+        if !meta.may_contain(pubkey) {
+            return Ok(());
+        }
+        self.remove_live(index, &mut meta, pubkey)
+    }
+
+    /// Write `entries`, of keys that aren't on disk, appending to each bucket they touch once
+    pub fn store(&self, entries: &[(Pubkey, Vec<(Fork, T)>)]) -> io::Result<()> {
+        let mut by_bucket: HashMap<usize, Vec<_>> = HashMap::new();
+        for (pubkey, forks) in entries {
+            by_bucket
+                .entry(Self::bucket_index(pubkey))
+                .or_insert_with(Vec::new)
+                .push((pubkey, forks));
+        }
+        for (index, entries) in by_bucket {
+            let mut meta = self.buckets[index].write().unwrap();
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(self.bucket_path(index))?;
+            let mut writer = BufWriter::new(file);
+            for (pubkey, forks) in &entries {
+                let data = serialize(*forks).map_err(invalid_data)?;
+                write_record(&mut writer, pubkey, &data)?;
+            }
+            writer.flush()?;
+
+            meta.records += entries.len();
+            meta.live += entries.len();
+            self.len.fetch_add(entries.len(), Ordering::Relaxed);
+            for (pubkey, _) in &entries {
+                meta.removed.remove(*pubkey);
+            }
+            if meta.live > meta.filter_capacity {
+                self.rebuild_filter(index, &mut meta)?;
+            } else {
+                let filter = meta.filter.as_mut().unwrap();
+                entries.iter().for_each(|(pubkey, _)| filter.add(*pubkey));
+            }
+        }
+        Ok(())
+    }
+
+    /// Every pubkey on disk
+    pub fn pubkeys(&self) -> io::Result<Vec<Pubkey>> {
+        let mut pubkeys = vec![];
+        for index in 0..NUM_BUCKETS {
+            let meta = self.buckets[index].read().unwrap();
+            if meta.live > 0 {
+                pubkeys.extend(self.live_records(index, &meta)?.keys());
+            }
+        }
+        Ok(pubkeys)
+    }
+
+    /// The keys on disk whose `pubkey_prefix` is in `prefixes`, read from the buckets that
+    /// can hold them only
+    pub fn pubkeys_in_range(&self, prefixes: &RangeInclusive<u64>) -> io::Result<Vec<Pubkey>> {
+        // with a bucket per first byte, the buckets of a prefix range are a range too
+        let first = (prefixes.start() >> 56) as usize;
+        let last = (prefixes.end() >> 56) as usize;
+        let mut pubkeys = vec![];
+        for index in first..=last {
+            let meta = self.buckets[index].read().unwrap();
+            if meta.live > 0 {
+                pubkeys.extend(
+                    self.live_records(index, &meta)?
+                        .keys()
+                        .filter(|pubkey| prefixes.contains(&pubkey_prefix(pubkey))),
+                );
+            }
+        }
+        Ok(pubkeys)
+    }
+
+    fn bucket_index(pubkey: &Pubkey) -> usize {
+        pubkey.as_ref()[0] as usize % NUM_BUCKETS
+    }
+
+    fn bucket_path(&self, index: usize) -> PathBuf {
+        self.path.join(format!("bucket-{}", index))
+    }
+
+    /// The entries of the newest record of `pubkey`, the only ones deserialized
+    fn read_entries(&self, index: usize, pubkey: &Pubkey) -> io::Result<Option<Vec<(Fork, T)>>> {
+        let path = self.bucket_path(index);
+        let mut newest = None;
+        scan_records(&path, |key, offset, len| {
+            if key == *pubkey {
+                newest = Some((offset, len));
+            }
+        })?;
+        match newest {
+            Some((offset, len)) => {
+                let data = read_at(&path, offset, len)?;
+                deserialize(&data).map(Some).map_err(invalid_data)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Offset and length of the newest record of every live key of the bucket
+    fn live_records(
+        &self,
+        index: usize,
+        meta: &BucketMeta,
+    ) -> io::Result<HashMap<Pubkey, (u64, usize)>> {
+        let mut records = HashMap::new();
+        scan_records(&self.bucket_path(index), |pubkey, offset, len| {
+            records.insert(pubkey, (offset, len));
+        })?;
+        records.retain(|pubkey, _| !meta.removed.contains(pubkey));
+        Ok(records)
+    }
+
+    fn remove_live(&self, index: usize, meta: &mut BucketMeta, pubkey: &Pubkey) -> io::Result<()> {
+        meta.removed.insert(*pubkey);
+        meta.live -= 1;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        if meta.live == 0 {
+            let _ignored = fs::remove_file(self.bucket_path(index));
+            *meta = BucketMeta::default();
+        } else if meta.records >= COMPACTION_MIN_RECORDS && meta.records > 2 * meta.live {
+            self.compact(index, meta)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrite the bucket with only the newest record of each live key
+    fn compact(&self, index: usize, meta: &mut BucketMeta) -> io::Result<()> {
+        let path = self.bucket_path(index);
+        let records = self.live_records(index, meta)?;
+        let tmp_path = path.with_extension("tmp");
+        let mut reader = File::open(&path)?;
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        let mut data = vec![];
+        for (pubkey, (offset, len)) in &records {
+            data.resize(*len, 0);
+            reader.seek(SeekFrom::Start(*offset))?;
+            reader.read_exact(&mut data)?;
+            write_record(&mut writer, pubkey, &data)?;
+        }
+        writer.flush()?;
+        fs::rename(&tmp_path, &path)?;
+
+        meta.records = records.len();
+        meta.removed.clear();
+        self.rebuild_filter(index, meta)
+    }
+
+    /// Size the bucket's bloom filter for twice its live keys and add them
+    fn rebuild_filter(&self, index: usize, meta: &mut BucketMeta) -> io::Result<()> {
+        let records = self.live_records(index, meta)?;
+        let capacity = (2 * records.len()).max(BLOOM_MIN_KEYS);
+        let mut filter = Bloom::random(capacity, BLOOM_FALSE_RATE, BLOOM_MAX_BITS);
+        records.keys().for_each(|pubkey| filter.add(pubkey));
+        meta.filter = Some(filter);
+        meta.filter_capacity = capacity;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tmp_buckets_path(name: &str) -> PathBuf {
+        let out_dir = std::env::var("OUT_DIR").unwrap_or_else(|_| "target".to_string());
+        PathBuf::from(out_dir).join(name)
+    }
+
+    #[test]
+    fn test_index_buckets_store_get_take() {
+        let path = tmp_buckets_path("test_index_buckets_store_get_take");
+        let buckets = IndexBuckets::<bool>::new(&path).unwrap();
+        let pubkeys: Vec<_> = (0..100).map(|_| Pubkey::new_rand()).collect();
+        assert!(buckets.get(&pubkeys[0]).unwrap().is_none());
+
+        let entries: Vec<_> = pubkeys
+            .iter()
+            .map(|pubkey| (*pubkey, vec![(1, true)]))
+            .collect();
+        buckets.store(&entries).unwrap();
+        assert_eq!(buckets.len(), 100);
+        assert_eq!(buckets.get(&pubkeys[0]).unwrap(), Some(vec![(1, true)]));
+        assert!(buckets.get(&Pubkey::new_rand()).unwrap().is_none());
+
+        let mut on_disk = buckets.pubkeys().unwrap();
+        on_disk.sort();
+        let mut expected = pubkeys.clone();
+        expected.sort();
+        assert_eq!(on_disk, expected);
+
+        for pubkey in &pubkeys {
+            assert_eq!(buckets.take(pubkey).unwrap(), Some(vec![(1, true)]));
+            assert!(buckets.get(pubkey).unwrap().is_none());
+        }
+        assert_eq!(buckets.len(), 0);
+        assert!(buckets.pubkeys().unwrap().is_empty());
+        let _ignored = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_index_buckets_newest_record_wins() {
+        let path = tmp_buckets_path("test_index_buckets_newest_record_wins");
+        let buckets = IndexBuckets::<u64>::new(&path).unwrap();
+        // one bucket, so its records pile up until it is compacted
+        let pubkeys: Vec<_> = (0..4)
+            .map(|i| {
+                let mut bytes = [0u8; 32];
+                bytes[1] = i;
+                Pubkey::new(&bytes)
+            })
+            .collect();
+        let entries: Vec<_> = pubkeys
+            .iter()
+            .map(|pubkey| (*pubkey, vec![(0, 0)]))
+            .collect();
+        buckets.store(&entries).unwrap();
+
+        for round in 1..=COMPACTION_MIN_RECORDS as u64 {
+            let entries = buckets.take(&pubkeys[0]).unwrap().unwrap();
+            assert_eq!(entries, vec![(0, round - 1)]);
+            buckets.store(&[(pubkeys[0], vec![(0, round)])]).unwrap();
+        }
+        assert!(buckets.buckets[0].read().unwrap().records < COMPACTION_MIN_RECORDS);
+        assert_eq!(
+            buckets.get(&pubkeys[0]).unwrap(),
+            Some(vec![(0, COMPACTION_MIN_RECORDS as u64)])
+        );
+
+        buckets.remove(&pubkeys[1]).unwrap();
+        assert!(buckets.get(&pubkeys[1]).unwrap().is_none());
+        assert_eq!(buckets.get(&pubkeys[2]).unwrap(), Some(vec![(0, 0)]));
+        assert_eq!(buckets.len(), 3);
+        assert_eq!(buckets.pubkeys().unwrap().len(), 3);
+        let _ignored = fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_index_buckets_corrupt_file() {
+        let path = tmp_buckets_path("test_index_buckets_corrupt_file");
+        let buckets = IndexBuckets::<bool>::new(&path).unwrap();
+        let pubkey = Pubkey::new_rand();
+        buckets.store(&[(pubkey, vec![(1, true)])]).unwrap();
+
+        let bucket_path = buckets.bucket_path(IndexBuckets::<bool>::bucket_index(&pubkey));
+        let len = fs::metadata(&bucket_path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&bucket_path)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+        assert!(buckets.get(&pubkey).is_err());
+        assert!(buckets.take(&pubkey).is_err());
+        let _ignored = fs::remove_dir_all(&path);
+    }
+}
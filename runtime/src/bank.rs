@@ -2,14 +2,16 @@
 //! programs. It offers a high-level API that signs transactions
 //! on behalf of the caller, and a low-level API for when they have
 //! already been signed and verified.
+use crate::account_lock_stats::AccountLockStat;
 use crate::accounts::{AccountLockType, Accounts};
-use crate::accounts_db::{ErrorCounters, InstructionAccounts, InstructionLoaders};
+use crate::accounts_db::{ErrorCounters, PinnedAccounts, TransactionLoadResult};
 use crate::accounts_index::Fork;
 use crate::blockhash_queue::BlockhashQueue;
 use crate::epoch_schedule::EpochSchedule;
 use crate::locked_accounts_results::LockedAccountsResults;
 use crate::message_processor::{MessageProcessor, ProcessInstruction};
-use crate::stakes::Stakes;
+use crate::rewards;
+use crate::stakes::{Stakes, VoteAccounts};
 use crate::status_cache::StatusCache;
 use bincode::serialize;
 use hashbrown::HashMap;
@@ -21,21 +23,57 @@ use morgan_interface::account::Account;
 use morgan_interface::fee_calculator::FeeCalculator;
 use morgan_interface::genesis_block::GenesisBlock;
 use morgan_interface::hash::{extend_and_hash, Hash};
+use morgan_interface::inflation::Inflation;
 use morgan_interface::native_loader;
+use morgan_interface::nonce_state::{self, NonceState};
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::rent_calculator::RentCalculator;
 use morgan_interface::signature::{Keypair, Signature};
+use morgan_interface::syscall;
+use morgan_interface::syscall::epoch_ticks::{self, EpochTicks};
+use morgan_interface::syscall::runtime_params::{self, RuntimeParams, RuntimeParamsSchedule};
 use morgan_interface::syscall::slot_hashes::{self, SlotHashes};
 use morgan_interface::system_transaction;
-use morgan_interface::timing::{duration_as_ms, duration_as_us, MAX_RECENT_BLOCKHASHES};
+use morgan_interface::timing::{
+    duration_as_ms, duration_as_us, years_as_slots, MAX_RECENT_BLOCKHASHES,
+};
 use morgan_interface::transaction::{Result, Transaction, TransactionError};
+use rayon::prelude::*;
+use rayon::ThreadPool;
 use std::borrow::Borrow;
 use std::cmp;
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::{Arc, RwLock, RwLockReadGuard};
 use std::time::Instant;
 use morgan_helper::logHelper::*;
 
-type BankStatusCache = StatusCache<Result<()>>;
+pub(crate) type BankStatusCache = StatusCache<Result<()>>;
+
+/// The state of a bank besides its accounts and signature statuses, which a snapshot saves
+/// as it is, see `snapshot`
+#[derive(Serialize, Deserialize)]
+pub(crate) struct BankFields {
+    pub slot: u64,
+    pub bank_height: u64,
+    pub hash: Hash,
+    pub parent_hash: Hash,
+    pub transaction_count: u64,
+    pub signature_count: u64,
+    pub tick_height: u64,
+    pub max_tick_height: u64,
+    pub ticks_per_slot: u64,
+    pub collector_id: Pubkey,
+    pub fee_calculator: FeeCalculator,
+    pub inflation: Inflation,
+    pub slots_per_year: f64,
+    pub rent_calculator: RentCalculator,
+    pub epoch_schedule: EpochSchedule,
+    pub blockhash_queue: BlockhashQueue,
+    pub stakes: Stakes,
+    pub epoch_stakes: BTreeMap<u64, Stakes>,
+}
 
 /// Manager for the state of all accounts and programs after processing its entries.
 #[derive(Default)]
@@ -61,9 +99,17 @@ pub struct Bank {
     /// Hash of this Bank's parent's state
     parent_hash: Hash,
 
+    /// Hash of the accounts stored in this slot, slot hashes included, taken once frozen.
+    /// The accounts store drops its own once the slot is rooted
+    frozen_accounts_delta_hash: RwLock<Option<Hash>>,
+
     /// The number of transactions processed without error
     transaction_count: AtomicUsize, // TODO: Use AtomicU64 if/when available
 
+    /// The number of signatures of the fee paying transactions in this slot, which derives
+    /// the fee of the next slot under congestion pricing
+    signature_count: AtomicUsize, // TODO: Use AtomicU64 if/when available
+
     /// Bank tick height
     tick_height: AtomicUsize, // TODO: Use AtomicU64 if/when available
 
@@ -85,15 +131,28 @@ pub struct Bank {
     /// An object to calculate transaction fees.
     pub fee_calculator: FeeCalculator,
 
+    /// The yearly rate paid on delegated stake, initialized from genesis
+    inflation: Inflation,
+
+    /// Slots in a year at the genesis tick rate, the credits a voter earns in a year
+    slots_per_year: f64,
+
+    /// The rent accounts below the exempt balance pay every epoch, initialized from genesis
+    rent_calculator: RentCalculator,
+
     /// initialized from genesis
     epoch_schedule: EpochSchedule,
 
+    /// protocol constants of this bank's epoch, resolved from the runtime parameters
+    /// account at every epoch boundary
+    runtime_params: RuntimeParams,
+
     /// cache of vote_account and stake_account state for this fork
     stakes: RwLock<Stakes>,
 
     /// staked nodes on epoch boundaries, saved off when a bank.slot() is at
     ///   a leader schedule calculation boundary
-    epoch_stakes: HashMap<u64, Stakes>,
+    epoch_stakes: BTreeMap<u64, Stakes>,
 
     /// A boolean reflecting whether any entries were recorded into the PoH
     /// stream for the slot == self.slot
@@ -101,6 +160,10 @@ pub struct Bank {
 
     /// The Message processor
     message_processor: MessageProcessor,
+
+    /// Executes the transactions of a batch side by side, None executes them on the calling
+    /// thread.  Inherited from the parent, see `execution_pool`
+    execution_pool: Option<Arc<ThreadPool>>,
 }
 
 impl Default for BlockhashQueue {
@@ -115,9 +178,21 @@ impl Bank {
     }
 
     pub fn new_with_paths(genesis_block: &GenesisBlock, paths: Option<String>) -> Self {
+        Self::new_with_index_memory_budget(genesis_block, paths, None)
+    }
+
+    /// A bank whose accounts index overflows to disk past `index_memory_budget` bytes, if given
+    pub fn new_with_index_memory_budget(
+        genesis_block: &GenesisBlock,
+        paths: Option<String>,
+        index_memory_budget: Option<usize>,
+    ) -> Self {
         let mut bank = Self::default();
         bank.ancestors.insert(bank.slot(), 0);
-        bank.accounts = Arc::new(Accounts::new(paths));
+        bank.accounts = Arc::new(Accounts::new_with_index_memory_budget(
+            paths,
+            index_memory_budget,
+        ));
         bank.process_genesis_block(genesis_block);
         // genesis needs stakes for all epochs up to the epoch implied by
         //  slot = 0 and genesis configuration
@@ -139,7 +214,9 @@ impl Bank {
         bank.blockhash_queue = RwLock::new(parent.blockhash_queue.read().unwrap().clone());
         bank.status_cache = parent.status_cache.clone();
         bank.bank_height = parent.bank_height + 1;
-        bank.fee_calculator = parent.fee_calculator.clone();
+        bank.fee_calculator = parent
+            .fee_calculator
+            .new_derived(parent.signature_count());
 
         bank.transaction_count
             .store(parent.transaction_count() as usize, Ordering::Relaxed);
@@ -149,6 +226,9 @@ impl Bank {
             .store(parent.tick_height.load(Ordering::SeqCst), Ordering::SeqCst);
         bank.ticks_per_slot = parent.ticks_per_slot;
         bank.epoch_schedule = parent.epoch_schedule;
+        bank.inflation = parent.inflation;
+        bank.slots_per_year = parent.slots_per_year;
+        bank.rent_calculator = parent.rent_calculator;
 
         bank.slot = slot;
         bank.max_tick_height = (bank.slot + 1) * bank.ticks_per_slot - 1;
@@ -164,6 +244,10 @@ impl Bank {
         bank.collector_id = *collector_id;
 
         bank.accounts = Arc::new(Accounts::new_from_parent(&parent.accounts));
+        bank.message_processor.share_program_cache(&parent.message_processor);
+        bank.execution_pool = parent.execution_pool.clone();
+        // a dead bank for this slot may still be getting cleaned up
+        bank.accounts.wait_for_purge(slot);
 
         bank.epoch_stakes = {
             let mut epoch_stakes = parent.epoch_stakes.clone();
@@ -181,13 +265,112 @@ impl Bank {
             bank.ancestors.insert(p.slot(), i + 1);
         });
 
+        bank.collect_rent_since(parent.slot());
+        let (epoch, _) = bank.get_epoch_and_slot_index(slot);
+        let (parent_epoch, _) = parent.get_epoch_and_slot_index(parent.slot());
+        if epoch != parent_epoch {
+            bank.pay_epoch_rewards();
+            bank.update_runtime_params();
+            bank.update_epoch_ticks();
+        } else {
+            bank.runtime_params = parent.runtime_params;
+        }
+
         bank
     }
 
+    /// Create a frozen root bank from the state saved in a snapshot, see `snapshot`
+    pub(crate) fn new_from_snapshot(
+        fields: BankFields,
+        accounts: Accounts,
+        status_cache: BankStatusCache,
+    ) -> Self {
+        let mut bank = Self::default();
+        bank.accounts = Arc::new(accounts);
+        bank.status_cache = Arc::new(RwLock::new(status_cache));
+        bank.blockhash_queue = RwLock::new(fields.blockhash_queue);
+        bank.ancestors.insert(fields.slot, 0);
+        bank.hash = RwLock::new(fields.hash);
+        bank.parent_hash = fields.parent_hash;
+        bank.transaction_count = AtomicUsize::new(fields.transaction_count as usize);
+        bank.signature_count = AtomicUsize::new(fields.signature_count as usize);
+        bank.tick_height = AtomicUsize::new(fields.tick_height as usize);
+        bank.max_tick_height = fields.max_tick_height;
+        bank.ticks_per_slot = fields.ticks_per_slot;
+        bank.slot = fields.slot;
+        bank.bank_height = fields.bank_height;
+        bank.collector_id = fields.collector_id;
+        bank.fee_calculator = fields.fee_calculator;
+        bank.inflation = fields.inflation;
+        bank.slots_per_year = fields.slots_per_year;
+        bank.rent_calculator = fields.rent_calculator;
+        bank.epoch_schedule = fields.epoch_schedule;
+        bank.stakes = RwLock::new(fields.stakes);
+        bank.epoch_stakes = fields.epoch_stakes;
+        bank.update_runtime_params();
+        bank
+    }
+
+    /// The state a snapshot of this bank saves besides its accounts and signature statuses
+    pub(crate) fn snapshot_fields(&self) -> BankFields {
+        BankFields {
+            slot: self.slot,
+            bank_height: self.bank_height,
+            hash: self.hash(),
+            parent_hash: self.parent_hash,
+            transaction_count: self.transaction_count(),
+            signature_count: self.signature_count(),
+            tick_height: self.tick_height(),
+            max_tick_height: self.max_tick_height,
+            ticks_per_slot: self.ticks_per_slot,
+            collector_id: self.collector_id,
+            fee_calculator: self.fee_calculator.clone(),
+            inflation: self.inflation,
+            slots_per_year: self.slots_per_year,
+            rent_calculator: self.rent_calculator,
+            epoch_schedule: self.epoch_schedule,
+            blockhash_queue: self.blockhash_queue.read().unwrap().clone(),
+            stakes: self.stakes.read().unwrap().clone(),
+            epoch_stakes: self.epoch_stakes.clone(),
+        }
+    }
+
+    /// The signature statuses a bank restored from a snapshot of this one can see
+    pub(crate) fn snapshot_status_cache(&self) -> BankStatusCache {
+        self.status_cache
+            .read()
+            .unwrap()
+            .rooted_copy(&self.ancestors)
+    }
+
+    /// Every account this bank can see, pinned in storage as they are now, so they can be read
+    /// after later roots would show through the bank
+    pub fn snapshot_accounts(&self) -> PinnedAccounts {
+        self.accounts.pin_all(&self.ancestors)
+    }
+
+    /// Like `snapshot_accounts`, but only the accounts changed after `slot`, which must be
+    /// rooted, and the ones emptied since are included
+    pub fn snapshot_changed_accounts(&self, slot: u64) -> PinnedAccounts {
+        self.accounts.pin_changed(&self.ancestors, slot)
+    }
+
+    /// What `hash_accounts` returned for the bank `accounts` were pinned from by
+    /// `snapshot_accounts`, without holding on to the bank
+    pub fn hash_snapshot_accounts(accounts: &PinnedAccounts) -> Hash {
+        Accounts::hash_pinned_accounts(accounts)
+    }
+
     pub fn collector_id(&self) -> Pubkey {
         self.collector_id
     }
 
+    /// Execute transaction batches on `execution_pool`, as will the banks descending from this
+    /// one
+    pub fn set_execution_pool(&mut self, execution_pool: Option<Arc<ThreadPool>>) {
+        self.execution_pool = execution_pool;
+    }
+
     pub fn slot(&self) -> u64 {
         self.slot
     }
@@ -216,6 +399,19 @@ impl Bank {
         self.store(&slot_hashes::id(), &account);
     }
 
+    /// Publish the length of this bank's epoch, see `epoch_ticks`
+    fn update_epoch_ticks(&self) {
+        let (epoch, _) = self.get_epoch_and_slot_index(self.slot);
+        let epoch_ticks = EpochTicks {
+            epoch,
+            ticks: self.get_ticks_in_epoch(epoch),
+        };
+        self.store(
+            &epoch_ticks::id(),
+            &epoch_ticks::create_account(1, &epoch_ticks),
+        );
+    }
+
     fn set_hash(&self) -> bool {
         let mut hash = self.hash.write().unwrap();
 
@@ -231,6 +427,8 @@ impl Bank {
     pub fn freeze(&self) {
         if self.set_hash() {
             self.update_slot_hashes();
+            *self.frozen_accounts_delta_hash.write().unwrap() =
+                self.accounts.hash_internal_state(self.slot());
         }
     }
 
@@ -238,6 +436,144 @@ impl Bank {
         &self.epoch_schedule
     }
 
+    pub fn inflation(&self) -> Inflation {
+        self.inflation
+    }
+
+    /// Mint the stakes and their voters the rewards of the credits earned since they were
+    /// last paid, at the inflation rate of this bank's year, see `rewards`
+    fn pay_epoch_rewards(&self) {
+        if self.slots_per_year <= 0.0 {
+            return;
+        }
+        let rate = self.inflation.rate(self.slot as f64 / self.slots_per_year);
+        let rewards = rewards::calculate_epoch_rewards(
+            &self.stakes.read().unwrap(),
+            rate,
+            self.slots_per_year,
+        );
+        for (pubkey, account) in &rewards.accounts {
+            self.store(pubkey, account);
+        }
+        datapoint_info!(
+            "bank-epoch_rewards",
+            ("slot", self.slot as i64, i64),
+            ("voters_rewards", rewards.voters_rewards as i64, i64),
+            ("stakers_rewards", rewards.stakers_rewards as i64, i64),
+            ("accounts", rewards.accounts.len() as i64, i64)
+        );
+    }
+
+    pub fn rent_calculator(&self) -> RentCalculator {
+        self.rent_calculator
+    }
+
+    /// The `pubkey_prefix`es the slot at `slot_index` of an epoch of `slots_in_epoch` slots
+    /// collects rent from, the epoch's slots split the prefixes into even ranges in order
+    fn rent_partition(slots_in_epoch: u64, slot_index: u64) -> RangeInclusive<u64> {
+        let start = |index: u64| ((u128::from(index) << 64) / u128::from(slots_in_epoch)) as u64;
+        let end = if slot_index + 1 == slots_in_epoch {
+            u64::max_value()
+        } else {
+            start(slot_index + 1) - 1
+        };
+        start(slot_index)..=end
+    }
+
+    /// Collect the rent of the slots after `parent_slot` up to this bank's.  Each slot of an
+    /// epoch charges the accounts of its own range of pubkeys, see `rent_partition`, so every
+    /// account pays an epoch's rent once an epoch without any one bank scanning them all
+    fn collect_rent_since(&self, parent_slot: u64) {
+        if self.rent_calculator.difs_per_byte_year == 0 || self.slots_per_year <= 0.0 {
+            return;
+        }
+        let (parent_epoch, parent_index) = self.get_epoch_and_slot_index(parent_slot);
+        let (epoch, index) = self.get_epoch_and_slot_index(self.slot);
+        for rent_epoch in parent_epoch..=epoch {
+            let slots_in_epoch = self.get_slots_in_epoch(rent_epoch);
+            let first = if rent_epoch == parent_epoch {
+                parent_index + 1
+            } else {
+                0
+            };
+            let last = if rent_epoch == epoch {
+                index
+            } else {
+                slots_in_epoch - 1
+            };
+            if first <= last {
+                let prefixes = *Self::rent_partition(slots_in_epoch, first).start()
+                    ..=*Self::rent_partition(slots_in_epoch, last).end();
+                self.collect_rent(rent_epoch, &prefixes);
+            }
+        }
+    }
+
+    /// Charge the accounts below the rent-exempt balance whose `pubkey_prefix` is in
+    /// `prefixes` the rent of `epoch`, and pay it to this bank's collector.  Accounts that
+    /// can't pay are drained and so removed.  Programs, syscall accounts, and vote and stake
+    /// accounts pay no rent, the cluster can't have its stake drained away.
+    fn collect_rent(&self, epoch: u64, prefixes: &RangeInclusive<u64>) {
+        let years = self.epoch_schedule.get_slots_in_epoch(epoch) as f64 / self.slots_per_year;
+
+        let mut collected = 0;
+        let mut removed = 0;
+        for (pubkey, mut account) in self.accounts.load_range(&self.ancestors, prefixes) {
+            if account.executable || syscall::check_id(&account.owner) || Stakes::is_stake(&account)
+            {
+                continue;
+            }
+            let due = self
+                .rent_calculator
+                .due(account.difs, account.data.len(), years);
+            let due = cmp::min(due, account.difs);
+            if due == 0 {
+                continue;
+            }
+            account.difs -= due;
+            if account.difs == 0 {
+                removed += 1;
+            }
+            collected += due;
+            self.store(&pubkey, &account);
+        }
+        if collected > 0 {
+            self.deposit(&self.collector_id, collected);
+        }
+        datapoint_info!(
+            "bank-rent",
+            ("slot", self.slot as i64, i64),
+            ("collected", collected as i64, i64),
+            ("removed_accounts", removed as i64, i64)
+        );
+    }
+
+    /// The protocol constants in effect for this bank's epoch
+    pub fn runtime_params(&self) -> RuntimeParams {
+        self.runtime_params
+    }
+
+    /// Resolve the runtime parameters of this bank's epoch, the defaults unless the
+    /// runtime parameters account schedules others
+    fn update_runtime_params(&mut self) {
+        let (epoch, _) = self.get_epoch_and_slot_index(self.slot);
+        self.runtime_params = self
+            .get_account(&runtime_params::id())
+            .and_then(|account| RuntimeParamsSchedule::from(&account))
+            .map(|schedule| schedule.resolve(epoch))
+            .unwrap_or_default();
+        self.blockhash_queue
+            .write()
+            .unwrap()
+            .set_max_age(self.runtime_params.max_recent_blockhashes);
+    }
+
+    /// Purge the accounts of this bank's fork right away instead of once the bank drops, for
+    /// a fork that can never be rooted.  For root forks this is a noop
+    pub fn purge_fork(&self) {
+        self.accounts.purge_fork(self.slot());
+    }
+
     /// squash the parent's state up into this Bank,
     ///   this Bank becomes a root
     pub fn squash(&self) {
@@ -275,6 +611,13 @@ impl Bank {
         // Bootstrap leader collects fees until `new_from_parent` is called.
         self.collector_id = genesis_block.bootstrap_leader_pubkey;
         self.fee_calculator = genesis_block.fee_calculator.clone();
+        self.inflation = genesis_block.inflation;
+        self.slots_per_year = years_as_slots(
+            1.0,
+            &genesis_block.poh_config.target_tick_duration,
+            genesis_block.ticks_per_slot,
+        );
+        self.rent_calculator = genesis_block.rent_calculator;
 
         for (pubkey, account) in genesis_block.accounts.iter() {
             self.store(pubkey, account);
@@ -296,6 +639,8 @@ impl Bank {
             genesis_block.stakers_slot_offset,
             genesis_block.epoch_warmup,
         );
+        self.update_runtime_params();
+        self.update_epoch_ticks();
 
         // Add native programs mandatory for the MessageProcessor to function
         self.register_native_instruction_processor(
@@ -328,15 +673,33 @@ impl Bank {
         self.blockhash_queue.read().unwrap().last_hash()
     }
 
-    /// Return a confirmed blockhash with NUM_BLOCKHASH_CONFIRMATIONS
-    pub fn confirmed_last_blockhash(&self) -> Hash {
-        const NUM_BLOCKHASH_CONFIRMATIONS: usize = 3;
+    /// Return true if transactions using `blockhash` are no older than `max_age` blockhashes
+    pub fn check_blockhash_age(&self, blockhash: &Hash, max_age: usize) -> bool {
+        self.blockhash_queue
+            .read()
+            .unwrap()
+            .check_hash_age(*blockhash, max_age)
+    }
+
+    /// The last slot a transaction using `blockhash` is accepted in when hashes are accepted
+    /// up to `max_age` old, assuming no slots are skipped in between
+    pub fn get_blockhash_last_valid_slot(&self, blockhash: &Hash, max_age: usize) -> Option<u64> {
+        let age = self.blockhash_queue.read().unwrap().get_hash_age(blockhash)?;
+        let remaining = (max_age as u64).checked_sub(age)?;
+        Some(self.slot + remaining)
+    }
 
+    /// Return a confirmed blockhash with the `num_blockhash_confirmations` of the runtime
+    /// parameters
+    pub fn confirmed_last_blockhash(&self) -> Hash {
         let parents = self.parents();
         if parents.is_empty() {
             self.last_blockhash()
         } else {
-            let index = cmp::min(NUM_BLOCKHASH_CONFIRMATIONS, parents.len() - 1);
+            let index = cmp::min(
+                self.runtime_params.num_blockhash_confirmations,
+                parents.len() - 1,
+            );
             parents[index].last_blockhash()
         }
     }
@@ -379,7 +742,7 @@ impl Bank {
         slots_and_stakes.sort_by(|a, b| b.0.cmp(&a.0));
 
         let max_slot = self.slot();
-        let min_slot = max_slot.saturating_sub(MAX_RECENT_BLOCKHASHES as u64);
+        let min_slot = max_slot.saturating_sub(self.runtime_params.max_recent_blockhashes as u64);
 
         let mut total_stake = 0;
         for (slot, stake) in slots_and_stakes.iter() {
@@ -443,7 +806,7 @@ impl Bank {
         }
         // TODO: put this assert back in
         // assert!(!self.is_frozen());
-        let results = self.accounts.lock_accounts(txs);
+        let results = self.accounts.lock_accounts(&self.ancestors, txs);
         LockedAccountsResults::new(results, &self, txs, AccountLockType::AccountLock)
     }
 
@@ -455,12 +818,14 @@ impl Bank {
             locked_accounts_results.needs_unlock = false;
             match locked_accounts_results.lock_type() {
                 AccountLockType::AccountLock => self.accounts.unlock_accounts(
+                    &self.ancestors,
                     locked_accounts_results.transactions(),
                     locked_accounts_results.locked_accounts_results(),
                 ),
-                AccountLockType::RecordLock => self
-                    .accounts
-                    .unlock_record_accounts(locked_accounts_results.transactions()),
+                AccountLockType::RecordLock => self.accounts.unlock_record_accounts(
+                    &self.ancestors,
+                    locked_accounts_results.transactions(),
+                ),
             }
         }
     }
@@ -472,12 +837,12 @@ impl Bank {
     where
         I: std::borrow::Borrow<Transaction>,
     {
-        self.accounts.lock_record_accounts(txs);
+        self.accounts.lock_record_accounts(&self.ancestors, txs);
         LockedAccountsResults::new(vec![], &self, txs, AccountLockType::RecordLock)
     }
 
     pub fn unlock_record_accounts(&self, txs: &[Transaction]) {
-        self.accounts.unlock_record_accounts(txs)
+        self.accounts.unlock_record_accounts(&self.ancestors, txs)
     }
 
     fn load_accounts(
@@ -485,7 +850,7 @@ impl Bank {
         txs: &[Transaction],
         results: Vec<Result<()>>,
         error_counters: &mut ErrorCounters,
-    ) -> Vec<Result<(InstructionAccounts, InstructionLoaders)>> {
+    ) -> Vec<Result<TransactionLoadResult>> {
         self.accounts.load_accounts(
             &self.ancestors,
             txs,
@@ -525,6 +890,7 @@ impl Bank {
             .map(|(tx, lock_res)| {
                 if lock_res.is_ok()
                     && !hash_queue.check_hash_age(tx.message().recent_blockhash, max_age)
+                    && !self.check_durable_nonce(tx)
                 {
                     error_counters.reserve_blockhash += 1;
                     Err(TransactionError::BlockhashNotFound)
@@ -534,6 +900,43 @@ impl Bank {
             })
             .collect()
     }
+    /// Whether `tx` carries the nonce of the nonce account its first instruction advances,
+    /// which stands in for a recent blockhash
+    fn check_durable_nonce(&self, tx: &Transaction) -> bool {
+        match nonce_state::nonce_account_pubkey(tx.message())
+            .and_then(|nonce_pubkey| self.get_account(nonce_pubkey))
+            .and_then(|nonce_account| NonceState::from(&nonce_account))
+        {
+            Some(NonceState::Initialized(_, nonce)) => nonce == tx.message().recent_blockhash,
+            _ => false,
+        }
+    }
+    /// Advance the nonce of a durable nonce transaction whose instructions failed, and so
+    /// didn't store the nonce its first instruction advanced
+    fn advance_durable_nonce(&self, tx: &Transaction) {
+        let nonce_pubkey = match nonce_state::nonce_account_pubkey(tx.message()) {
+            Some(nonce_pubkey) => nonce_pubkey,
+            None => return,
+        };
+        let mut nonce_account = match self.get_account(nonce_pubkey) {
+            Some(nonce_account) => nonce_account,
+            None => return,
+        };
+        let next_nonce = self
+            .get_account(&slot_hashes::id())
+            .and_then(|account| SlotHashes::from(&account))
+            .and_then(|slot_hashes| slot_hashes.first().map(|(_, hash)| *hash));
+        if let (Some(NonceState::Initialized(authority, nonce)), Some(next_nonce)) =
+            (NonceState::from(&nonce_account), next_nonce)
+        {
+            if nonce == tx.message().recent_blockhash && nonce != next_nonce {
+                NonceState::Initialized(authority, next_nonce)
+                    .to(&mut nonce_account)
+                    .unwrap();
+                self.store(nonce_pubkey, &nonce_account);
+            }
+        }
+    }
     fn check_signatures(
         &self,
         txs: &[Transaction],
@@ -642,10 +1045,8 @@ impl Bank {
         txs: &[Transaction],
         lock_results: &LockedAccountsResults<Transaction>,
         max_age: usize,
-    ) -> (
-        Vec<Result<(InstructionAccounts, InstructionLoaders)>>,
-        Vec<Result<()>>,
-    ) {
+    ) -> (Vec<Result<TransactionLoadResult>>, Vec<Result<()>>) {
+        let _slot_span = slot_span(self.slot());
         debug!("processing transactions: {}", txs.len());
         let mut error_counters = ErrorCounters::default();
         let now = Instant::now();
@@ -659,17 +1060,33 @@ impl Bank {
         let tick_height = self.tick_height();
         let load_elapsed = now.elapsed();
         let now = Instant::now();
-        let executed: Vec<Result<()>> =
-            loaded_accounts
+        let execute = |(accs, tx): (&mut Result<TransactionLoadResult>, &Transaction)| match accs {
+            Err(e) => Err(e.clone()),
+            Ok((ref mut accounts, ref mut loaders, ref lookup_keys, ref mut credits)) => {
+                self.message_processor.process_message(
+                    tx.message(),
+                    lookup_keys,
+                    loaders,
+                    accounts,
+                    credits,
+                    tick_height,
+                )
+            }
+        };
+        let executed: Vec<Result<()>> = match &self.execution_pool {
+            Some(execution_pool) => execution_pool.install(|| {
+                loaded_accounts
+                    .par_iter_mut()
+                    .zip(txs.par_iter())
+                    .map(execute)
+                    .collect()
+            }),
+            None => loaded_accounts
                 .iter_mut()
                 .zip(txs.iter())
-                .map(|(accs, tx)| match accs {
-                    Err(e) => Err(e.clone()),
-                    Ok((ref mut accounts, ref mut loaders)) => self
-                        .message_processor
-                        .process_message(tx.message(), loaders, accounts, tick_height),
-                })
-                .collect();
+                .map(execute)
+                .collect(),
+        };
 
         let execution_elapsed = now.elapsed();
 
@@ -685,8 +1102,15 @@ impl Bank {
             if r.is_ok() {
                 tx_count += 1;
             } else {
-                if err_count == 0 {
-                    debug!("tx error: {:?} {:?}", r, tx);
+                if err_count == 0 && log_enabled!(Level::Debug) {
+                    let _signature_span = signature_span(tx.signatures[0]);
+                    println!(
+                        "{}",
+                        Debug(
+                            format!("tx error: {:?} {:?}", r, tx),
+                            module_path!().to_string()
+                        )
+                    );
                 }
                 err_count += 1;
             }
@@ -727,6 +1151,9 @@ impl Bank {
                         // necessary to withdraw from account[0] here because previous
                         // work of doing so (in accounts.load()) is ignored by store()
                         self.withdraw(&message.account_keys[0], fee)?;
+                        // the nonce has to move with the fee, or the transaction could be
+                        // replayed and charged again
+                        self.advance_durable_nonce(tx);
                         fees += fee;
                         Ok(())
                     }
@@ -745,9 +1172,10 @@ impl Bank {
     pub fn commit_transactions(
         &self,
         txs: &[Transaction],
-        loaded_accounts: &[Result<(InstructionAccounts, InstructionLoaders)>],
+        loaded_accounts: &[Result<TransactionLoadResult>],
         executed: &[Result<()>],
     ) -> Vec<Result<()>> {
+        let _slot_span = slot_span(self.slot());
         if self.is_frozen() {
             // warn!("{}", Warn(format!("=========== FIXME: commit_transactions() working on a frozen bank! ================").to_string()));
             println!("{}",Warn(format!("=========== FIXME: commit_transactions() working on a frozen bank! ================").to_string().to_string(),module_path!().to_string()));
@@ -758,13 +1186,27 @@ impl Bank {
             self.is_delta.store(true, Ordering::Relaxed);
         }
 
+        // every committed transaction pays its fee, whether its instructions succeed or not
+        let signature_count = executed
+            .iter()
+            .zip(txs.iter())
+            .filter(|(res, _)| Self::can_commit(res))
+            .map(|(_, tx)| usize::from(tx.message().header.num_required_signatures))
+            .sum();
+        self.increment_signature_count(signature_count);
+
         // TODO: put this assert back in
         // assert!(!self.is_frozen());
         let now = Instant::now();
-        self.accounts
-            .store_accounts(self.slot(), txs, executed, loaded_accounts);
+        let credited = self.accounts.store_accounts(
+            self.slot(),
+            &self.ancestors,
+            txs,
+            executed,
+            loaded_accounts,
+        );
 
-        self.store_stakes(txs, executed, loaded_accounts);
+        self.store_stakes(txs, executed, loaded_accounts, &credited);
 
         // once committed there is no way to unroll
         let write_elapsed = now.elapsed();
@@ -794,7 +1236,11 @@ impl Bank {
     #[must_use]
     pub fn process_transactions(&self, txs: &[Transaction]) -> Vec<Result<()>> {
         let lock_results = self.lock_accounts(txs);
-        self.load_execute_and_commit_transactions(txs, &lock_results, MAX_RECENT_BLOCKHASHES)
+        self.load_execute_and_commit_transactions(
+            txs,
+            &lock_results,
+            self.runtime_params.max_recent_blockhashes,
+        )
     }
 
     /// Create, sign, and process a Transaction from `keypair` to `to` of
@@ -895,6 +1341,14 @@ impl Bank {
             .fetch_add(tx_count, Ordering::Relaxed);
     }
 
+    pub fn signature_count(&self) -> u64 {
+        self.signature_count.load(Ordering::Relaxed) as u64
+    }
+    fn increment_signature_count(&self, signature_count: usize) {
+        self.signature_count
+            .fetch_add(signature_count, Ordering::Relaxed);
+    }
+
     pub fn get_signature_confirmation_status(
         &self,
         signature: &Signature,
@@ -915,16 +1369,40 @@ impl Bank {
     /// Hash the `accounts` HashMap. This represents a validator's interpretation
     ///  of the delta of the ledger since the last vote and up to now
     fn hash_internal_state(&self) -> Hash {
+        // The accounts store keeps the hash of the accounts stored in this slot up to date
+        // as they're stored, so there's nothing to scan here
+        let accounts_delta_hash = self.accounts.hash_internal_state(self.slot());
+
         // If there are no accounts, return the same hash as we did before
         // checkpointing.
-        if !self.accounts.has_accounts(self.slot()) {
+        if accounts_delta_hash.is_none() {
             return self.parent_hash;
         }
-
-        let accounts_delta_hash = self.accounts.hash_internal_state(self.slot());
         extend_and_hash(&self.parent_hash, &serialize(&accounts_delta_hash).unwrap())
     }
 
+    /// Whether the hash of the accounts stored in this slot, kept up to date as they were
+    /// stored, agrees with hashing them all again
+    pub fn verify_hash_internal_state(&self) -> bool {
+        let frozen_accounts_delta_hash = *self.frozen_accounts_delta_hash.read().unwrap();
+        let accounts_delta_hash =
+            frozen_accounts_delta_hash.or_else(|| self.accounts.hash_internal_state(self.slot()));
+        self.accounts
+            .verify_hash_internal_state(self.slot(), accounts_delta_hash)
+    }
+
+    /// Hash of the full account state as seen by this bank, unlike `hash()` which only
+    /// covers the accounts changed in this slot
+    pub fn hash_accounts(&self) -> Hash {
+        self.accounts.hash_accounts(&self.ancestors)
+    }
+
+    /// The `num` accounts transactions of this bank and its ancestors waited on the longest to
+    /// lock, hottest first
+    pub fn hottest_accounts(&self, num: usize) -> Vec<(Pubkey, AccountLockStat)> {
+        self.accounts.hottest_accounts(num)
+    }
+
     /// Return the number of ticks per slot
     pub fn ticks_per_slot(&self) -> u64 {
         self.ticks_per_slot
@@ -948,6 +1426,11 @@ impl Bank {
         self.epoch_schedule.get_slots_in_epoch(epoch)
     }
 
+    /// Return the number of ticks in the given epoch
+    pub fn get_ticks_in_epoch(&self, epoch: u64) -> u64 {
+        self.ticks_per_slot * self.get_slots_in_epoch(epoch)
+    }
+
     /// returns the epoch for which this bank's stakers_slot_offset and slot would
     ///  need to cache stakers
     pub fn get_stakers_epoch(&self, slot: u64) -> u64 {
@@ -959,7 +1442,8 @@ impl Bank {
         &self,
         txs: &[Transaction],
         res: &[Result<()>],
-        loaded: &[Result<(InstructionAccounts, InstructionLoaders)>],
+        loaded: &[Result<TransactionLoadResult>],
+        credited: &[(Pubkey, Account)],
     ) {
         for (i, raccs) in loaded.iter().enumerate() {
             if res[i].is_err() || raccs.is_err() {
@@ -969,29 +1453,48 @@ impl Bank {
             let message = &txs[i].message();
             let acc = raccs.as_ref().unwrap();
 
-            for (pubkey, account) in message
+            // credit-only accounts are stored with their credits added, below
+            for (_, (pubkey, account)) in message
                 .account_keys
                 .iter()
+                .chain(&acc.2)
                 .zip(acc.0.iter())
-                .filter(|(_, account)| Stakes::is_stake(account))
+                .enumerate()
+                .filter(|(i, (_, account))| message.is_writable(*i) && Stakes::is_stake(account))
             {
                 self.stakes.write().unwrap().store(pubkey, account);
             }
         }
+        for (pubkey, account) in credited {
+            if Stakes::is_stake(account) {
+                self.stakes.write().unwrap().store(pubkey, account);
+            }
+        }
     }
 
     /// current vote accounts for this bank along with the stake
-    ///   attributed to each account
-    pub fn vote_accounts(&self) -> HashMap<Pubkey, (u64, Account)> {
-        self.stakes.read().unwrap().vote_accounts().clone()
+    ///   attributed to each account, in pubkey order
+    pub fn vote_accounts(&self) -> BTreeMap<Pubkey, (u64, Account)> {
+        self.stakes
+            .read()
+            .unwrap()
+            .vote_accounts()
+            .iter()
+            .map(|(pubkey, (stake, account))| (*pubkey, (*stake, Account::clone(account))))
+            .collect()
     }
 
     /// vote accounts for the specific epoch along with the stake
-    ///   attributed to each account
-    pub fn epoch_vote_accounts(&self, epoch: u64) -> Option<&HashMap<Pubkey, (u64, Account)>> {
+    ///   attributed to each account, in pubkey order
+    pub fn epoch_vote_accounts(&self, epoch: u64) -> Option<&VoteAccounts> {
         self.epoch_stakes.get(&epoch).map(Stakes::vote_accounts)
     }
 
+    /// stake accounts as they were when the stakes for the specific epoch were saved off
+    pub fn epoch_stake_accounts(&self, epoch: u64) -> Option<&BTreeMap<Pubkey, Arc<Account>>> {
+        self.epoch_stakes.get(&epoch).map(Stakes::stake_accounts)
+    }
+
     /// given a slot, return the epoch and offset into the epoch this slot falls
     /// e.g. with a fixed number for slots_per_epoch, the calculation is simply:
     ///
@@ -1023,7 +1526,7 @@ impl Bank {
 impl Drop for Bank {
     fn drop(&mut self) {
         // For root forks this is a noop
-        self.accounts.purge_fork(self.slot());
+        self.purge_fork();
     }
 }
 
@@ -1031,12 +1534,15 @@ impl Drop for Bank {
 mod tests {
     use super::*;
     use crate::epoch_schedule::MINIMUM_SLOT_LENGTH;
+    use crate::execution_pool;
     use crate::genesis_utils::{
         create_genesis_block_with_leader, GenesisBlockInfo, BOOTSTRAP_LEADER_DIFS,
     };
     use morgan_interface::genesis_block::create_genesis_block;
     use morgan_interface::hash;
-    use morgan_interface::instruction::InstructionError;
+    use morgan_address_lookup_table_api::address_lookup_table_instruction;
+    use morgan_interface::instruction::{AccountMeta, InstructionError};
+    use morgan_interface::message::{AddressLookupTableAccount, Message};
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_instruction;
     use morgan_interface::system_transaction;
@@ -1383,18 +1889,112 @@ mod tests {
         let txs = vec![tx0, tx1, tx2];
         let results = bank.process_transactions(&txs);
 
-        // If multiple transactions attempt to deposit into the same account, only the first will
-        // succeed, even though such atomic adds are safe. A System Transfer `To` account should be
-        // given credit-only handling
-
+        // A System Transfer `To` account is credit-only, so every deposit into it succeeds
+        // and they all add up
         assert_eq!(results[0], Ok(()));
-        assert_eq!(results[1], Err(TransactionError::AccountInUse));
-        assert_eq!(results[2], Err(TransactionError::AccountInUse));
+        assert_eq!(results[1], Ok(()));
+        assert_eq!(results[2], Ok(()));
+        assert_eq!(bank.get_balance(&recipient), 3);
+        assert_eq!(bank.get_balance(&payer0.pubkey()), 2);
+        assert_eq!(bank.get_balance(&payer1.pubkey()), 2);
 
-        // After credit-only account handling is implemented, the following checks should pass instead:
-        // assert_eq!(results[0], Ok(()));
-        // assert_eq!(results[1], Ok(()));
-        // assert_eq!(results[2], Ok(()));
+        // and once credited the account can be spent from again
+        let recipient = Keypair::new();
+        bank.transfer(2, &mint_keypair, &recipient.pubkey())
+            .unwrap();
+        bank.transfer(1, &recipient, &payer0.pubkey()).unwrap();
+        assert_eq!(bank.get_balance(&recipient.pubkey()), 1);
+        assert_eq!(bank.get_balance(&payer0.pubkey()), 3);
+    }
+
+    #[test]
+    fn test_credit_only_credits_committed() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10);
+        let bank = Bank::new(&genesis_block);
+        let payer = Keypair::new();
+        let recipient = Pubkey::new_rand();
+        bank.transfer(3, &mint_keypair, &payer.pubkey()).unwrap();
+        let txs0 = vec![system_transaction::transfer(
+            &mint_keypair,
+            &recipient,
+            1,
+            genesis_block.hash(),
+        )];
+        let txs1 = vec![system_transaction::transfer(
+            &payer,
+            &recipient,
+            2,
+            genesis_block.hash(),
+        )];
+        let lock_results0 = bank.lock_accounts(&txs0);
+        let lock_results1 = bank.lock_accounts(&txs1);
+
+        // each transaction's credit lands as it commits, while the other still shares the
+        // account
+        assert_eq!(
+            bank.load_execute_and_commit_transactions(
+                &txs0,
+                &lock_results0,
+                MAX_RECENT_BLOCKHASHES
+            ),
+            vec![Ok(())]
+        );
+        assert_eq!(bank.get_balance(&recipient), 1);
+        assert_eq!(
+            bank.load_execute_and_commit_transactions(
+                &txs1,
+                &lock_results1,
+                MAX_RECENT_BLOCKHASHES
+            ),
+            vec![Ok(())]
+        );
+        assert_eq!(bank.get_balance(&recipient), 3);
+
+        drop(lock_results0);
+        drop(lock_results1);
+        assert_eq!(bank.get_balance(&recipient), 3);
+    }
+
+    #[test]
+    fn test_credit_only_accounts() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10);
+        let bank = Bank::new(&genesis_block);
+        let payer0 = Keypair::new();
+        let payer1 = Keypair::new();
+        let shared = Pubkey::new_rand();
+        bank.transfer(3, &mint_keypair, &payer0.pubkey()).unwrap();
+        bank.transfer(3, &mint_keypair, &payer1.pubkey()).unwrap();
+
+        // Transactions that only read the same account don't lock each other out
+        let transfer_reading_shared = |payer: &Keypair| {
+            let mut instruction =
+                system_instruction::transfer(&payer.pubkey(), &Pubkey::new_rand(), 1);
+            instruction
+                .accounts
+                .push(AccountMeta::new_credit_only(shared, false));
+            Transaction::new_signed_instructions(&[payer], vec![instruction], genesis_block.hash())
+        };
+        let txs = vec![
+            transfer_reading_shared(&payer0),
+            transfer_reading_shared(&payer1),
+        ];
+        assert_eq!(bank.process_transactions(&txs), vec![Ok(()), Ok(())]);
+
+        // and can credit it, but not take from it
+        let mut instruction = system_instruction::transfer(&payer1.pubkey(), &shared, 1);
+        instruction.accounts[0].is_debitable = false;
+        let message = Message::new_with_payer(vec![instruction], Some(&payer0.pubkey()));
+        let tx = Transaction::new(&[&payer0, &payer1], message, genesis_block.hash());
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::CreditOnlyDifChange
+            ))
+        );
+        assert_eq!(bank.get_balance(&shared), 0);
+        bank.transfer(1, &payer0, &shared).unwrap();
+        assert_eq!(bank.get_balance(&shared), 1);
     }
 
     #[test]
@@ -1543,12 +2143,192 @@ mod tests {
         assert_ne!(bank0.hash_internal_state(), initial_state);
         bank1.transfer(1_000, &mint_keypair, &pubkey).unwrap();
         assert_eq!(bank0.hash_internal_state(), bank1.hash_internal_state());
+        assert!(bank0.verify_hash_internal_state());
 
         // Checkpointing should not change its state
         let bank2 = new_from_parent(&Arc::new(bank1));
         assert_eq!(bank0.hash_internal_state(), bank2.hash_internal_state());
     }
 
+    #[test]
+    fn test_bank_verify_hash_internal_state_after_root() {
+        let (genesis_block, mint_keypair) = create_genesis_block(2_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        bank0
+            .transfer(1_000, &mint_keypair, &Pubkey::new_rand())
+            .unwrap();
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        bank1
+            .transfer(500, &mint_keypair, &Pubkey::new_rand())
+            .unwrap();
+        let bank2 = new_from_parent(&bank1);
+        bank2.squash();
+
+        // the store dropped the delta hashes below the root, the banks froze with theirs
+        assert_eq!(bank0.accounts.hash_internal_state(bank0.slot()), None);
+        assert!(bank0.verify_hash_internal_state());
+        assert!(bank1.verify_hash_internal_state());
+        assert!(bank2.verify_hash_internal_state());
+    }
+
+    #[test]
+    fn test_bank_execution_pool() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank0 = Bank::new(&genesis_block);
+        let mut bank1 = Bank::new(&genesis_block);
+        bank1.set_execution_pool(execution_pool::new_execution_pool(
+            &execution_pool::ExecutionPoolConfig {
+                num_threads: 4,
+                pin_threads: false,
+            },
+        ));
+
+        let payers: Vec<_> = (0..8).map(|_| Keypair::new()).collect();
+        let mut txs: Vec<_> = payers
+            .iter()
+            .map(|payer| {
+                system_transaction::transfer(
+                    &payer,
+                    &Pubkey::new_rand(),
+                    10,
+                    bank0.last_blockhash(),
+                )
+            })
+            .collect();
+        // one that can't pay
+        txs.push(system_transaction::transfer(
+            &Keypair::new(),
+            &Pubkey::new_rand(),
+            10,
+            bank0.last_blockhash(),
+        ));
+        for bank in &[&bank0, &bank1] {
+            for payer in &payers {
+                bank.transfer(100, &mint_keypair, &payer.pubkey()).unwrap();
+            }
+        }
+
+        // batches execute the same on the pool as on the calling thread
+        let results = bank0.process_transactions(&txs);
+        assert_eq!(bank1.process_transactions(&txs), results);
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 8);
+        assert_eq!(bank0.hash_internal_state(), bank1.hash_internal_state());
+
+        // children execute on their parent's pool
+        let bank2 = new_from_parent(&Arc::new(bank1));
+        assert!(bank2.execution_pool.is_some());
+    }
+
+    #[test]
+    fn test_bank_hash_accounts() {
+        let (genesis_block, mint_keypair) = create_genesis_block(2_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bank1 = Arc::new(Bank::new(&genesis_block));
+        let initial_hash = bank0.hash_accounts();
+        assert_eq!(bank1.hash_accounts(), initial_hash);
+
+        let pubkey = Pubkey::new_rand();
+        bank0.transfer(1_000, &mint_keypair, &pubkey).unwrap();
+        assert_ne!(bank0.hash_accounts(), initial_hash);
+        bank1.transfer(1_000, &mint_keypair, &pubkey).unwrap();
+        assert_eq!(bank0.hash_accounts(), bank1.hash_accounts());
+
+        // children see their parents' accounts as well as their own
+        let child0 = new_from_parent(&bank0);
+        let child1 = new_from_parent(&bank1);
+        assert_eq!(child0.hash_accounts(), child1.hash_accounts());
+        child0.transfer(1, &mint_keypair, &pubkey).unwrap();
+        assert_ne!(child0.hash_accounts(), child1.hash_accounts());
+    }
+
+    #[test]
+    fn test_bank_hash_independent_of_processing_order() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let vote_keypairs: Vec<_> = (0..8).map(|_| Keypair::new()).collect();
+
+        // replays the same slots with the vote accounts created in `order`
+        let replay = |order: &[usize]| {
+            let bank0 = Arc::new(Bank::new(&genesis_block));
+            let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+            for i in order {
+                let instructions = vote_instruction::create_account(
+                    &mint_keypair.pubkey(),
+                    &vote_keypairs[*i].pubkey(),
+                    &mint_keypair.pubkey(),
+                    0,
+                    10,
+                );
+                let transaction = Transaction::new_signed_instructions(
+                    &[&mint_keypair],
+                    instructions,
+                    bank1.last_blockhash(),
+                );
+                bank1.process_transaction(&transaction).unwrap();
+            }
+            bank1.freeze();
+
+            // the first bank of the next stakers epoch snapshots the stakes
+            let mut slot = 2;
+            while bank1.get_stakers_epoch(slot) == bank1.get_stakers_epoch(1) {
+                slot += 1;
+            }
+            let bank2 = Bank::new_from_parent(&bank1, &Pubkey::default(), slot);
+            let epoch_vote_pubkeys: Vec<_> = bank2
+                .epoch_vote_accounts(bank2.get_stakers_epoch(slot))
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect();
+            let vote_pubkeys: Vec<_> = bank2.vote_accounts().keys().cloned().collect();
+            assert_eq!(epoch_vote_pubkeys, vote_pubkeys);
+            bank2.freeze();
+            (bank1.hash(), bank2.hash(), bank2.hash_accounts(), vote_pubkeys)
+        };
+
+        let forward: Vec<_> = (0..vote_keypairs.len()).collect();
+        let reverse: Vec<_> = forward.iter().rev().cloned().collect();
+        let expected = replay(&forward);
+        assert_eq!(replay(&forward), expected);
+        assert_eq!(replay(&reverse), expected);
+    }
+
+    #[test]
+    fn test_bank_snapshot_fields_epoch_stakes() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let vote_keypair = Keypair::new();
+        let instructions = vote_instruction::create_account(
+            &mint_keypair.pubkey(),
+            &vote_keypair.pubkey(),
+            &mint_keypair.pubkey(),
+            0,
+            10,
+        );
+        let transaction = Transaction::new_signed_instructions(
+            &[&mint_keypair],
+            instructions,
+            bank0.last_blockhash(),
+        );
+        bank0.process_transaction(&transaction).unwrap();
+
+        let mut slot = 1;
+        while bank0.get_stakers_epoch(slot) == bank0.get_stakers_epoch(0) {
+            slot += 1;
+        }
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), slot);
+        let epoch = bank1.get_stakers_epoch(slot);
+        assert!(bank1
+            .epoch_vote_accounts(epoch)
+            .unwrap()
+            .contains_key(&vote_keypair.pubkey()));
+
+        // the stakes saved off at epoch boundaries can't be rebuilt from the accounts of a
+        // later slot, so they travel with a snapshot
+        let bytes = bincode::serialize(&bank1.snapshot_fields()).unwrap();
+        let fields: BankFields = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(bank1.epoch_stakes, fields.epoch_stakes);
+    }
+
     #[test]
     fn test_hash_internal_state_genesis() {
         let bank0 = Bank::new(&create_genesis_block(10).0);
@@ -1655,6 +2435,88 @@ mod tests {
         assert_eq!(parent.get_balance(&key1.pubkey()), 1);
     }
 
+    #[test]
+    fn test_bank_reuse_slot_of_dropped_bank() {
+        let (genesis_block, mint_keypair) = create_genesis_block(500);
+        let parent = Arc::new(Bank::new(&genesis_block));
+        let key1 = Keypair::new();
+        parent.transfer(1, &mint_keypair, &key1.pubkey()).unwrap();
+
+        // a dead fork at slot 1 spends from key1
+        let bank = Bank::new_from_parent(&parent, &Pubkey::default(), 1);
+        bank.transfer(1, &key1, &Pubkey::new_rand()).unwrap();
+        assert_eq!(bank.get_balance(&key1.pubkey()), 0);
+        drop(bank);
+
+        // the replacement bank for slot 1 only sees the parent's state
+        let bank = Bank::new_from_parent(&parent, &Pubkey::default(), 1);
+        assert_eq!(bank.get_balance(&key1.pubkey()), 1);
+        bank.transfer(2, &mint_keypair, &key1.pubkey()).unwrap();
+        assert_eq!(bank.get_balance(&key1.pubkey()), 3);
+    }
+
+    #[test]
+    fn test_bank_address_table_lookups() {
+        let (genesis_block, mint_keypair) = create_genesis_block(500);
+        let mut bank = Bank::new(&genesis_block);
+        bank.add_instruction_processor(
+            morgan_address_lookup_table_api::id(),
+            address_lookup_table_instruction::process_instruction,
+        );
+        let table_pubkey = Pubkey::new_rand();
+        let key1 = Pubkey::new_rand();
+
+        let mut instructions = address_lookup_table_instruction::create_lookup_table(
+            &mint_keypair.pubkey(),
+            &table_pubkey,
+            &mint_keypair.pubkey(),
+            1,
+        );
+        instructions.push(address_lookup_table_instruction::extend_lookup_table(
+            &table_pubkey,
+            &mint_keypair.pubkey(),
+            vec![key1],
+        ));
+        let tx = Transaction::new_signed_instructions(
+            &[&mint_keypair],
+            instructions,
+            bank.last_blockhash(),
+        );
+        bank.process_transaction(&tx).unwrap();
+
+        let table = AddressLookupTableAccount {
+            key: table_pubkey,
+            addresses: vec![key1],
+        };
+        let message = Message::new_with_address_table_lookups(
+            vec![system_instruction::transfer(&mint_keypair.pubkey(), &key1, 10)],
+            None,
+            &[table],
+        );
+        assert!(message.is_versioned());
+        assert!(!message.account_keys.contains(&key1));
+        let tx = Transaction::new(&[&mint_keypair], message, bank.last_blockhash());
+        bank.process_transaction(&tx).unwrap();
+        assert_eq!(bank.get_balance(&key1), 10);
+        assert_eq!(bank.get_balance(&mint_keypair.pubkey()), 489);
+
+        // a message referencing a missing table is rejected before it runs
+        let message = Message::new_with_address_table_lookups(
+            vec![system_instruction::transfer(&mint_keypair.pubkey(), &key1, 10)],
+            None,
+            &[AddressLookupTableAccount {
+                key: Pubkey::new_rand(),
+                addresses: vec![key1],
+            }],
+        );
+        let tx = Transaction::new(&[&mint_keypair], message, bank.last_blockhash());
+        assert_eq!(
+            bank.process_transaction(&tx),
+            Err(TransactionError::AddressLookupTableNotFound)
+        );
+        assert_eq!(bank.get_balance(&key1), 10);
+    }
+
     #[test]
     fn test_bank_epoch_vote_accounts() {
         let leader_pubkey = Pubkey::new_rand();
@@ -1718,6 +2580,217 @@ mod tests {
         assert!(child.epoch_vote_accounts(i).is_some());
     }
 
+    #[test]
+    fn test_bank_pay_epoch_rewards() {
+        let leader_pubkey = Pubkey::new_rand();
+        let GenesisBlockInfo {
+            mut genesis_block,
+            voting_keypair,
+            ..
+        } = create_genesis_block_with_leader(1, &leader_pubkey, 1_000_000_000_000);
+        genesis_block.slots_per_epoch = MINIMUM_SLOT_LENGTH as u64;
+        genesis_block.epoch_warmup = false;
+
+        // the bootstrap leader earned some credits before its stake was last paid
+        let vote_pubkey = voting_keypair.pubkey();
+        for (pubkey, account) in genesis_block.accounts.iter_mut() {
+            if *pubkey == vote_pubkey {
+                let mut vote_state = VoteState::deserialize(&account.data).unwrap();
+                for slot in 0..42 {
+                    vote_state.process_slot_vote_unchecked(slot);
+                }
+                vote_state.serialize(&mut account.data).unwrap();
+            }
+        }
+        let (stake_pubkey, _) = *genesis_block
+            .accounts
+            .iter()
+            .find(|(_, account)| morgan_stake_api::check_id(&account.owner))
+            .unwrap();
+
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let stake_difs = bank0.get_balance(&stake_pubkey);
+        let vote_difs = bank0.get_balance(&vote_pubkey);
+
+        // nothing is paid within an epoch
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &leader_pubkey, 1));
+        assert_eq!(bank1.get_balance(&stake_pubkey), stake_difs);
+
+        // the first bank of the next epoch mints the stake its rewards, the voter takes no
+        //  commission
+        let bank2 = Arc::new(Bank::new_from_parent(
+            &bank1,
+            &leader_pubkey,
+            genesis_block.slots_per_epoch,
+        ));
+        assert!(bank2.get_balance(&stake_pubkey) > stake_difs);
+        assert_eq!(bank2.get_balance(&vote_pubkey), vote_difs);
+        assert_eq!(
+            bank2.stakes.read().unwrap().stake_accounts()[&stake_pubkey].difs,
+            bank2.get_balance(&stake_pubkey)
+        );
+
+        // and the credits are only paid once
+        let stake_difs = bank2.get_balance(&stake_pubkey);
+        let bank3 =
+            Bank::new_from_parent(&bank2, &leader_pubkey, genesis_block.slots_per_epoch * 2);
+        assert_eq!(bank3.get_balance(&stake_pubkey), stake_difs);
+
+        // no inflation, no rewards
+        genesis_block.inflation = Inflation::new_disabled();
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bank1 = Bank::new_from_parent(&bank0, &leader_pubkey, genesis_block.slots_per_epoch);
+        assert_eq!(
+            bank1.get_balance(&stake_pubkey),
+            bank0.get_balance(&stake_pubkey)
+        );
+    }
+
+    #[test]
+    fn test_rent_partition() {
+        let slots_in_epoch = 3;
+        assert_eq!(*Bank::rent_partition(slots_in_epoch, 0).start(), 0);
+        assert_eq!(
+            *Bank::rent_partition(slots_in_epoch, slots_in_epoch - 1).end(),
+            u64::max_value()
+        );
+        for index in 1..slots_in_epoch {
+            assert_eq!(
+                *Bank::rent_partition(slots_in_epoch, index - 1).end() + 1,
+                *Bank::rent_partition(slots_in_epoch, index).start()
+            );
+        }
+        assert_eq!(Bank::rent_partition(1, 0), 0..=u64::max_value());
+    }
+
+    #[test]
+    fn test_bank_collect_rent() {
+        let (mut genesis_block, mint_keypair) = create_genesis_block(1_000_000);
+        genesis_block.slots_per_epoch = MINIMUM_SLOT_LENGTH as u64;
+        genesis_block.epoch_warmup = false;
+        genesis_block.rent_calculator = RentCalculator::new(1, 2.0);
+        let slots_per_epoch = genesis_block.slots_per_epoch;
+
+        // every account in the range slot 5 collects from
+        let partition_start = *Bank::rent_partition(slots_per_epoch, 5).start();
+        let pubkey_in_partition = |offset: u64| {
+            let mut pubkey = [0u8; 32];
+            pubkey[..8].copy_from_slice(&(partition_start + offset).to_be_bytes());
+            Pubkey::new(&pubkey)
+        };
+        let poor_pubkey = pubkey_in_partition(0);
+        let dust_pubkey = pubkey_in_partition(1);
+        let program_pubkey = pubkey_in_partition(2);
+        let vote_pubkey = pubkey_in_partition(3);
+        let mut program_account = Account::new(1, 0, 0, &native_loader::id());
+        program_account.executable = true;
+        genesis_block.accounts.extend(vec![
+            (poor_pubkey, Account::new(100, 0, 0, &Pubkey::default())),
+            (dust_pubkey, Account::new(1, 0, 0, &Pubkey::default())),
+            (program_pubkey, program_account),
+            (vote_pubkey, Account::new(1, 0, 0, &morgan_vote_api::id())),
+        ]);
+        let collector_pubkey = Pubkey::new_rand();
+
+        // slot 1 only collects from its own range
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &collector_pubkey, 1));
+        assert_eq!(bank1.get_balance(&poor_pubkey), 100);
+        assert_eq!(bank1.get_balance(&collector_pubkey), 0);
+
+        // skipping ahead to slot 5 collects the ranges of the slots in between too: the
+        //  accounts below the exempt balance pay the epoch's rent, rounded up to a dif, to
+        //  the collector
+        let bank5 = Arc::new(Bank::new_from_parent(&bank1, &collector_pubkey, 5));
+        let years = slots_per_epoch as f64 / bank5.slots_per_year;
+        let due = bank5.rent_calculator().due(100, 0, years);
+        assert!(due > 0);
+        assert_eq!(bank5.get_balance(&poor_pubkey), 100 - due);
+        assert_eq!(bank5.get_account(&dust_pubkey), None);
+        assert_eq!(bank5.get_balance(&program_pubkey), 1);
+        assert_eq!(bank5.get_balance(&vote_pubkey), 1);
+        assert_eq!(bank5.get_balance(&mint_keypair.pubkey()), 1_000_000);
+        assert_eq!(bank5.get_balance(&collector_pubkey), due + 1);
+
+        // once an epoch
+        let bank6 = Arc::new(Bank::new_from_parent(&bank5, &collector_pubkey, 6));
+        assert_eq!(bank6.get_balance(&poor_pubkey), 100 - due);
+        let bank7 = Bank::new_from_parent(&bank6, &collector_pubkey, slots_per_epoch + 5);
+        assert_eq!(bank7.get_balance(&poor_pubkey), 100 - 2 * due);
+    }
+
+    #[test]
+    fn test_bank_runtime_params() {
+        let (mut genesis_block, _) = create_genesis_block(500);
+        genesis_block.slots_per_epoch = MINIMUM_SLOT_LENGTH as u64;
+        genesis_block.epoch_warmup = false;
+
+        let params = RuntimeParams {
+            max_recent_blockhashes: 16,
+            max_processing_age: 8,
+            num_blockhash_confirmations: 1,
+        };
+        let mut schedule = RuntimeParamsSchedule::default();
+        schedule.add(1, params).unwrap();
+        genesis_block.accounts.push((
+            runtime_params::id(),
+            runtime_params::create_account(1, &schedule),
+        ));
+
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        assert_eq!(bank0.runtime_params(), RuntimeParams::default());
+
+        // still epoch 0
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        assert_eq!(bank1.runtime_params(), RuntimeParams::default());
+
+        // the first bank of epoch 1 picks up the scheduled parameters, and its children
+        //  inherit them
+        let bank2 = Arc::new(Bank::new_from_parent(
+            &bank1,
+            &Pubkey::default(),
+            genesis_block.slots_per_epoch,
+        ));
+        assert_eq!(bank2.runtime_params(), params);
+        let bank3 = Bank::new_from_parent(&bank2, &Pubkey::default(), bank2.slot() + 1);
+        assert_eq!(bank3.runtime_params(), params);
+
+        // hashes older than the scheduled max_recent_blockhashes are dropped
+        let blockhash = bank3.last_blockhash();
+        let mut blockhash_queue = bank3.blockhash_queue.write().unwrap();
+        for i in 0..=params.max_recent_blockhashes {
+            blockhash_queue.register_hash(&hash::hash(&i.to_le_bytes()));
+        }
+        assert!(!blockhash_queue.check_hash(blockhash));
+    }
+
+    #[test]
+    fn test_bank_epoch_ticks() {
+        let (mut genesis_block, _) = create_genesis_block(500);
+        genesis_block.epoch_warmup = true;
+        let epoch_ticks =
+            |bank: &Bank| EpochTicks::from(&bank.get_account(&epoch_ticks::id()).unwrap()).unwrap();
+
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        assert_eq!(
+            epoch_ticks(&bank0),
+            EpochTicks {
+                epoch: 0,
+                ticks: bank0.ticks_per_slot() * bank0.get_slots_in_epoch(0),
+            }
+        );
+
+        // warming up, epoch 1 runs twice as long as epoch 0
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), bank0.get_slots_in_epoch(0));
+        assert_eq!(
+            epoch_ticks(&bank1),
+            EpochTicks {
+                epoch: 1,
+                ticks: 2 * epoch_ticks(&bank0).ticks,
+            }
+        );
+    }
+
     #[test]
     fn test_zero_signatures() {
         morgan_logger::setup();
@@ -1740,6 +2813,113 @@ mod tests {
         assert_eq!(bank.get_balance(&key.pubkey()), 0);
     }
 
+    fn get_nonce(bank: &Bank, nonce_pubkey: &Pubkey) -> Option<Hash> {
+        match NonceState::from(&bank.get_account(nonce_pubkey)?)? {
+            NonceState::Initialized(_, nonce) => Some(nonce),
+            NonceState::Uninitialized => None,
+        }
+    }
+
+    #[test]
+    fn test_durable_nonce_transaction() {
+        let (genesis_block, mint_keypair) = create_genesis_block(500);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        let nonce_pubkey = Pubkey::new_rand();
+        let to_pubkey = Pubkey::new_rand();
+
+        let tx = system_transaction::create_nonce_account(
+            &mint_keypair,
+            &nonce_pubkey,
+            &mint_keypair.pubkey(),
+            10,
+            bank1.last_blockhash(),
+        );
+        assert_eq!(bank1.process_transaction(&tx), Ok(()));
+        let nonce = get_nonce(&bank1, &nonce_pubkey).unwrap();
+        assert!(!bank1.check_blockhash_age(&nonce, MAX_RECENT_BLOCKHASHES));
+
+        // signed offline with the nonce in place of a recent blockhash
+        let durable_tx = Transaction::new_signed_instructions(
+            &[&mint_keypair],
+            vec![
+                system_instruction::advance_nonce_account(&nonce_pubkey, &mint_keypair.pubkey()),
+                system_instruction::transfer(&mint_keypair.pubkey(), &to_pubkey, 1),
+            ],
+            nonce,
+        );
+        // without advancing the nonce first the nonce is just an unknown blockhash
+        let tx = Transaction::new_signed_instructions(
+            &[&mint_keypair],
+            vec![system_instruction::transfer(
+                &mint_keypair.pubkey(),
+                &to_pubkey,
+                1,
+            )],
+            nonce,
+        );
+
+        let bank2 = new_from_parent(&bank1);
+        assert_eq!(
+            bank2.process_transaction(&tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+        assert_eq!(bank2.process_transaction(&durable_tx), Ok(()));
+        assert_eq!(bank2.get_balance(&to_pubkey), 1);
+        assert_ne!(get_nonce(&bank2, &nonce_pubkey), Some(nonce));
+
+        // the nonce moved on, so the transaction can't be replayed
+        let bank3 = new_from_parent(&Arc::new(bank2));
+        assert_eq!(
+            bank3.process_transaction(&durable_tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+        assert_eq!(bank3.get_balance(&to_pubkey), 1);
+    }
+
+    #[test]
+    fn test_durable_nonce_transaction_failed() {
+        let (mut genesis_block, mint_keypair) = create_genesis_block(500);
+        genesis_block.fee_calculator.difs_per_signature = 2;
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        let nonce_pubkey = Pubkey::new_rand();
+        let to_pubkey = Pubkey::new_rand();
+
+        let tx = system_transaction::create_nonce_account(
+            &mint_keypair,
+            &nonce_pubkey,
+            &mint_keypair.pubkey(),
+            10,
+            bank1.last_blockhash(),
+        );
+        assert_eq!(bank1.process_transaction(&tx), Ok(()));
+        let nonce = get_nonce(&bank1, &nonce_pubkey).unwrap();
+
+        // the transfer fails after the nonce was advanced
+        let durable_tx = Transaction::new_signed_instructions(
+            &[&mint_keypair],
+            vec![
+                system_instruction::advance_nonce_account(&nonce_pubkey, &mint_keypair.pubkey()),
+                system_instruction::transfer(&mint_keypair.pubkey(), &to_pubkey, 1_000),
+            ],
+            nonce,
+        );
+        let bank2 = new_from_parent(&bank1);
+        let balance = bank2.get_balance(&mint_keypair.pubkey());
+        assert!(bank2.process_transaction(&durable_tx).is_err());
+        assert_eq!(bank2.get_balance(&mint_keypair.pubkey()), balance - 2);
+        assert_ne!(get_nonce(&bank2, &nonce_pubkey), Some(nonce));
+
+        // so it can't be replayed to charge the fee again
+        let bank3 = new_from_parent(&Arc::new(bank2));
+        assert_eq!(
+            bank3.process_transaction(&durable_tx),
+            Err(TransactionError::BlockhashNotFound)
+        );
+        assert_eq!(bank3.get_balance(&mint_keypair.pubkey()), balance - 2);
+    }
+
     #[test]
     fn test_bank_get_slots_in_epoch() {
         let (genesis_block, _) = create_genesis_block(500);
@@ -1843,6 +3023,38 @@ mod tests {
         assert_eq!(bank.is_votable(), true);
     }
 
+    #[test]
+    fn test_get_blockhash_last_valid_slot() {
+        let (genesis_block, _mint_keypair) = create_genesis_block(500);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let genesis_hash = genesis_block.hash();
+        assert_eq!(
+            bank0.get_blockhash_last_valid_slot(&genesis_hash, 10),
+            Some(10)
+        );
+        assert_eq!(
+            bank0.get_blockhash_last_valid_slot(&Hash::default(), 10),
+            None
+        );
+
+        // finishing the slot registers a newer hash
+        for i in 0..genesis_block.ticks_per_slot - 1 {
+            bank0.register_tick(&hash::hash(format!("hello world {}", i).as_bytes()));
+        }
+        assert_eq!(
+            bank0.get_blockhash_last_valid_slot(&genesis_hash, 10),
+            Some(9)
+        );
+        assert_eq!(bank0.get_blockhash_last_valid_slot(&genesis_hash, 0), None);
+
+        // a child bank agrees on when the hash expires
+        let bank1 = new_from_parent(&bank0);
+        assert_eq!(
+            bank1.get_blockhash_last_valid_slot(&genesis_hash, 10),
+            Some(10)
+        );
+    }
+
     #[test]
     fn test_bank_inherit_tx_count() {
         let (genesis_block, mint_keypair) = create_genesis_block(500);
@@ -1894,6 +3106,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_bank_congestion_priced_fee_calculator() {
+        let (mut genesis_block, mint_keypair) = create_genesis_block(500);
+        genesis_block.fee_calculator = FeeCalculator::new_congestion_priced(8, 1, 1, 100);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        bank0.transfer(10, &mint_keypair, &Pubkey::new_rand()).unwrap();
+        bank0.transfer(10, &mint_keypair, &Pubkey::new_rand()).unwrap();
+        assert_eq!(bank0.signature_count(), 2);
+
+        // twice the target raises the fee of the next slot
+        let bank1 = Arc::new(new_from_parent(&bank0));
+        assert_eq!(bank1.signature_count(), 0);
+        assert_eq!(bank1.fee_calculator.difs_per_signature, 9);
+
+        // an empty slot lowers it again
+        let bank2 = new_from_parent(&bank1);
+        assert_eq!(bank2.fee_calculator.difs_per_signature, 8);
+    }
+
+    #[test]
+    fn test_bank_signature_count_only_committed() {
+        let (genesis_block, mint_keypair) = create_genesis_block(500);
+        let bank = Bank::new(&genesis_block);
+        let txs = vec![system_transaction::transfer(
+            &mint_keypair,
+            &Pubkey::new_rand(),
+            10,
+            genesis_block.hash(),
+        )];
+        let lock_results = bank.lock_accounts(&txs);
+        let (loaded_accounts, executed) =
+            bank.load_and_execute_transactions(&txs, &lock_results, MAX_RECENT_BLOCKHASHES);
+        // executed but not committed, e.g. a leader that ran out of slot
+        assert_eq!(bank.signature_count(), 0);
+
+        let _ = bank.commit_transactions(&txs, &loaded_accounts, &executed);
+        assert_eq!(bank.signature_count(), 1);
+    }
+
     #[test]
     fn test_bank_vote_accounts() {
         let GenesisBlockInfo {
@@ -68,6 +68,19 @@ impl AsyncClient for BankClient {
             system_instruction::transfer(&keypair.pubkey(), pubkey, difs);
         self.async_send_instruction(keypair, transfer_instruction, recent_blockhash)
     }
+
+    /// Transfer `reputations` from `keypair` to `pubkey`
+    fn async_transfer_reputations(
+        &self,
+        reputations: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+        recent_blockhash: Hash,
+    ) -> io::Result<Signature> {
+        let transfer_instruction =
+            system_instruction::transfer_reputations(&keypair.pubkey(), pubkey, reputations);
+        self.async_send_instruction(keypair, transfer_instruction, recent_blockhash)
+    }
 }
 
 impl SyncClient for BankClient {
@@ -91,6 +104,18 @@ impl SyncClient for BankClient {
         self.send_instruction(keypair, transfer_instruction)
     }
 
+    /// Transfer `reputations` from `keypair` to `pubkey`
+    fn transfer_reputations(
+        &self,
+        reputations: u64,
+        keypair: &Keypair,
+        pubkey: &Pubkey,
+    ) -> Result<Signature> {
+        let transfer_instruction =
+            system_instruction::transfer_reputations(&keypair.pubkey(), pubkey, reputations);
+        self.send_instruction(keypair, transfer_instruction)
+    }
+
     fn get_account_data(&self, pubkey: &Pubkey) -> Result<Option<Vec<u8>>> {
         Ok(self.bank.get_account(pubkey).map(|account| account.data))
     }
@@ -99,6 +124,10 @@ impl SyncClient for BankClient {
         Ok(self.bank.get_balance(pubkey))
     }
 
+    fn get_reputation(&self, pubkey: &Pubkey) -> Result<u64> {
+        Ok(self.bank.get_reputation(pubkey))
+    }
+
     fn get_signature_status(
         &self,
         signature: &Signature,
@@ -240,4 +269,35 @@ mod tests {
         bank_client.send_message(&doe_keypairs, message).unwrap();
         assert_eq!(bank_client.get_balance(&bob_pubkey).unwrap(), 42);
     }
+
+    #[test]
+    fn test_bank_client_transfer_reputations() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank_client = BankClient::new(Bank::new(&genesis_block));
+
+        // reputations are only minted when an account is created with them
+        let alice_keypair = Keypair::new();
+        let alice_pubkey = alice_keypair.pubkey();
+        let instruction = system_instruction::create_user_account_with_reputation(
+            &mint_keypair.pubkey(),
+            &alice_pubkey,
+            100,
+        );
+        bank_client
+            .send_instruction(&mint_keypair, instruction)
+            .unwrap();
+        assert_eq!(bank_client.get_reputation(&alice_pubkey).unwrap(), 100);
+
+        let bob_pubkey = Pubkey::new_rand();
+        bank_client
+            .transfer_reputations(42, &alice_keypair, &bob_pubkey)
+            .unwrap();
+        assert_eq!(bank_client.get_reputation(&alice_pubkey).unwrap(), 58);
+        assert_eq!(bank_client.get_reputation(&bob_pubkey).unwrap(), 42);
+
+        assert!(bank_client
+            .transfer_reputations(59, &alice_keypair, &bob_pubkey)
+            .is_err());
+        assert_eq!(bank_client.get_reputation(&alice_pubkey).unwrap(), 58);
+    }
 }
@@ -0,0 +1,82 @@
+//! Fixtures for the runtime benches, built with the `bench` feature.  They reach into the
+//! accounts store, which isn't otherwise public, so store, load and hash can be measured on
+//! their own as well as through a `Bank`.
+
+use crate::accounts::Accounts;
+use crate::bank::Bank;
+use hashbrown::HashMap;
+use morgan_interface::account::Account;
+use morgan_interface::hash::Hash;
+use morgan_interface::pubkey::Pubkey;
+use morgan_interface::signature::{Keypair, KeypairUtil};
+use morgan_interface::system_transaction;
+use morgan_interface::transaction::Transaction;
+
+/// Accounts in a mainnet-sized store
+pub const LARGE_ACCOUNT_COUNT: usize = 100_000;
+
+/// Accounts stored per call, about what a banking thread commits at once
+pub const STORE_BATCH_SIZE: usize = 1_024;
+
+/// An accounts store seeded with `count` accounts in fork 0
+pub struct BenchAccounts {
+    accounts: Accounts,
+    pub pubkeys: Vec<Pubkey>,
+}
+
+impl BenchAccounts {
+    pub fn new(count: usize, data_len: usize) -> Self {
+        let accounts = Accounts::new(None);
+        let pubkeys: Vec<_> = (0..count).map(|_| Pubkey::new_rand()).collect();
+        let bench_accounts = Self { accounts, pubkeys };
+        for chunk in bench_accounts.pubkeys.chunks(STORE_BATCH_SIZE) {
+            bench_accounts.store(0, chunk, data_len);
+        }
+        bench_accounts
+    }
+
+    /// Store a new version of each of `pubkeys` in `fork`
+    pub fn store(&self, fork: u64, pubkeys: &[Pubkey], data_len: usize) {
+        let account = Account::new(1, 0, data_len, &Pubkey::default());
+        let batch: Vec<_> = pubkeys.iter().map(|pubkey| (pubkey, &account)).collect();
+        self.accounts.accounts_db.store(fork, &batch);
+    }
+
+    /// Load `pubkey` as seen from `fork`, whose ancestors are all the forks below it
+    pub fn load(&self, fork: u64, pubkey: &Pubkey) -> Option<Account> {
+        let ancestors: HashMap<_, _> = (0..=fork).map(|fork| (fork, 0)).collect();
+        self.accounts
+            .load_slow(&ancestors, pubkey)
+            .map(|(account, _)| account)
+    }
+
+    pub fn hash_internal_state(&self, fork: u64) -> Option<Hash> {
+        self.accounts.hash_internal_state(fork)
+    }
+}
+
+/// Iterations of a transfer bench the transfer accounts are funded for, more than the
+/// bencher runs
+pub const TRANSFER_BENCH_ITERATIONS: u64 = 100_000;
+
+/// `count` signed transfers out of distinct accounts funded from `mint_keypair`, so none of them
+/// contend for an account lock.  Each account holds enough to pay for its transfer and fee
+/// `iterations` times over.
+pub fn create_transfer_transactions(
+    bank: &Bank,
+    mint_keypair: &Keypair,
+    count: usize,
+    iterations: u64,
+) -> Vec<Transaction> {
+    let blockhash = bank.last_blockhash();
+    (0..count)
+        .map(|_| {
+            let keypair = Keypair::new();
+            let tx = system_transaction::transfer(&keypair, &Pubkey::new_rand(), 1, blockhash);
+            let fee = bank.fee_calculator.calculate_fee(tx.message());
+            bank.transfer((1 + fee) * iterations, mint_keypair, &keypair.pubkey())
+                .expect("mint too small to fund the transfers");
+            tx
+        })
+        .collect()
+}
@@ -2,14 +2,14 @@ use hashbrown::HashMap;
 use morgan_interface::hash::Hash;
 use morgan_interface::timing::timestamp;
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
 struct HashAge {
     timestamp: u64,
     hash_height: u64,
 }
 
 /// Low memory overhead, so can be cloned for every checkpoint
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct BlockhashQueue {
     /// updated whenever an hash is registered
     hash_height: u64,
@@ -33,6 +33,11 @@ impl BlockhashQueue {
         }
     }
 
+    /// Keep hashes up to `max_age` from now on, older ones go as new hashes are registered
+    pub fn set_max_age(&mut self, max_age: usize) {
+        self.max_age = max_age;
+    }
+
     #[allow(dead_code)]
     pub fn hash_height(&self) -> u64 {
         self.hash_height
@@ -51,6 +56,13 @@ impl BlockhashQueue {
             _ => false,
         }
     }
+    /// How many hashes were registered after `hash`, if it's still in the queue
+    pub fn get_hash_age(&self, hash: &Hash) -> Option<u64> {
+        self.ages
+            .get(hash)
+            .map(|age| self.hash_height - age.hash_height)
+    }
+
     /// check if hash is valid
     #[cfg(test)]
     pub fn check_hash(&self, hash: Hash) -> bool {
@@ -121,6 +133,16 @@ mod tests {
         assert_eq!(hash_queue.hash_height(), 1);
     }
     #[test]
+    fn test_get_hash_age() {
+        let mut hash_queue = BlockhashQueue::new(100);
+        let hash0 = hash(&serialize(&0).unwrap());
+        assert_eq!(hash_queue.get_hash_age(&hash0), None);
+        hash_queue.register_hash(&hash0);
+        assert_eq!(hash_queue.get_hash_age(&hash0), Some(0));
+        hash_queue.register_hash(&hash(&serialize(&1).unwrap()));
+        assert_eq!(hash_queue.get_hash_age(&hash0), Some(1));
+    }
+    #[test]
     fn test_reject_old_last_hash() {
         let mut hash_queue = BlockhashQueue::new(100);
         let last_hash = hash(&serialize(&0).unwrap());
@@ -0,0 +1,57 @@
+//! The `execution_pool` module builds the thread pool a bank executes the transactions of a
+//! batch on, so execution can use more or fewer threads than the stages handing it batches.
+//! Transactions of a batch never write the same account, so they run side by side.
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ExecutionPoolConfig {
+    /// Threads executing a batch, 0 executes it on the thread that hands it over
+    pub num_threads: usize,
+
+    /// Pin every execution thread to a core of its own, wrapping around if there are fewer
+    pub pin_threads: bool,
+}
+
+/// The pool `config` asks for, None to execute on the calling thread
+pub fn new_execution_pool(config: &ExecutionPoolConfig) -> Option<Arc<ThreadPool>> {
+    if config.num_threads == 0 {
+        return None;
+    }
+    let core_ids = if config.pin_threads {
+        core_affinity::get_core_ids().unwrap_or_default()
+    } else {
+        vec![]
+    };
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(config.num_threads)
+        .thread_name(|i| format!("morgan-execute-{}", i))
+        .start_handler(move |i| {
+            if !core_ids.is_empty() {
+                core_affinity::set_for_current(core_ids[i % core_ids.len()]);
+            }
+        })
+        .build()
+        .expect("new execution pool");
+    Some(Arc::new(pool))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_execution_pool() {
+        assert!(new_execution_pool(&ExecutionPoolConfig::default()).is_none());
+
+        let pool = new_execution_pool(&ExecutionPoolConfig {
+            num_threads: 2,
+            pin_threads: true,
+        })
+        .unwrap();
+        assert_eq!(pool.current_num_threads(), 2);
+        let name = pool.install(|| std::thread::current().name().map(str::to_string));
+        assert!(name.unwrap().starts_with("morgan-execute-"));
+    }
+}
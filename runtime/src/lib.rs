@@ -1,17 +1,26 @@
+pub mod account_lock_stats;
 mod accounts;
 pub mod accounts_db;
+mod accounts_delta_hash;
 mod accounts_index;
+mod accounts_index_buckets;
 pub mod append_vec;
 pub mod bank;
 pub mod bank_client;
+#[cfg(feature = "bench")]
+pub mod bench_utils;
 mod blockhash_queue;
 pub mod bloom;
 pub mod epoch_schedule;
+pub mod execution_pool;
 pub mod genesis_utils;
 pub mod loader_utils;
 pub mod locked_accounts_results;
 pub mod message_processor;
 mod native_loader;
+pub mod read_only_accounts_cache;
+pub mod rewards;
+pub mod snapshot;
 pub mod stakes;
 mod status_cache;
 mod system_instruction_processor;
@@ -1,21 +1,14 @@
-use crate::native_loader;
+use crate::native_loader::{self, NativeProgramCache};
 use crate::system_instruction_processor;
 use morgan_interface::account::{create_keyed_accounts, Account, KeyedAccount};
 use morgan_interface::instruction::{CompiledInstruction, InstructionError};
-use morgan_interface::instruction_processor_utils;
 use morgan_interface::message::Message;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::system_program;
 use morgan_interface::transaction::TransactionError;
-use std::collections::HashMap;
-use std::sync::RwLock;
+use std::sync::Arc;
 use log::*;
 
-#[cfg(unix)]
-use libloading::os::unix::*;
-#[cfg(windows)]
-use libloading::os::windows::*;
-
 /// Return true if the slice has any duplicate elements
 pub fn has_duplicates<T: PartialEq>(xs: &[T]) -> bool {
     // Note: This is an O(n^2) algorithm, but requires no heap allocations. The benchmark
@@ -55,6 +48,7 @@ fn get_subset_unchecked_mut<'a, T>(
 }
 
 fn verify_instruction(
+    is_writable: bool,
     program_id: &Pubkey,
     pre_program_id: &Pubkey,
     pre_difs: u64,
@@ -63,6 +57,20 @@ fn verify_instruction(
 ) -> Result<(), InstructionError> {
     // Verify the transaction
 
+    // Credit-only accounts are shared with other transactions in the pipeline, no program may
+    // change them, only the system program may credit them so transfers into the same account
+    // don't lock each other out
+    if !is_writable {
+        if pre_difs > account.difs
+            || (pre_difs < account.difs && !system_program::check_id(&program_id))
+        {
+            return Err(InstructionError::CreditOnlyDifChange);
+        }
+        if *pre_program_id != account.owner || pre_data != &account.data[..] {
+            return Err(InstructionError::CreditOnlyDataModified);
+        }
+    }
+
     // Make sure that program_id is still the same or this was just assigned by the system program
     if *pre_program_id != account.owner && !system_program::check_id(&program_id) {
         return Err(InstructionError::ModifiedProgramId);
@@ -84,11 +92,9 @@ fn verify_instruction(
 pub type ProcessInstruction =
     fn(&Pubkey, &mut [KeyedAccount], &[u8], u64) -> Result<(), InstructionError>;
 
-pub type SymbolCache = RwLock<HashMap<Vec<u8>, Symbol<instruction_processor_utils::Entrypoint>>>;
-
 pub struct MessageProcessor {
     instruction_processors: Vec<(Pubkey, ProcessInstruction)>,
-    symbol_cache: SymbolCache,
+    program_cache: Arc<NativeProgramCache>,
 }
 
 impl Default for MessageProcessor {
@@ -100,7 +106,7 @@ impl Default for MessageProcessor {
 
         Self {
             instruction_processors,
-            symbol_cache: RwLock::new(HashMap::new()),
+            program_cache: Arc::new(NativeProgramCache::default()),
         }
     }
 }
@@ -116,11 +122,17 @@ impl MessageProcessor {
             .push((program_id, process_instruction));
     }
 
+    /// Load native programs through `parent`'s cache instead of reloading them per bank
+    pub fn share_program_cache(&mut self, parent: &MessageProcessor) {
+        self.program_cache = parent.program_cache.clone();
+    }
+
     /// Process an instruction
     /// This method calls the instruction's program entrypoint method
     fn process_instruction(
         &self,
         message: &Message,
+        lookup_keys: &[Pubkey],
         instruction: &CompiledInstruction,
         executable_accounts: &mut [(Pubkey, Account)],
         program_accounts: &mut [&mut Account],
@@ -133,7 +145,10 @@ impl MessageProcessor {
             .iter()
             .map(|&index| {
                 let index = index as usize;
-                let key = &message.account_keys[index];
+                let key = message
+                    .account_keys
+                    .get(index)
+                    .unwrap_or_else(|| &lookup_keys[index - message.account_keys.len()]);
                 (key, index < message.header.num_required_signatures as usize)
             })
             .zip(program_accounts.iter_mut())
@@ -157,7 +172,7 @@ impl MessageProcessor {
             &mut keyed_accounts,
             &instruction.data,
             tick_height,
-            &self.symbol_cache,
+            &self.program_cache,
         )
     }
 
@@ -168,9 +183,11 @@ impl MessageProcessor {
     fn execute_instruction(
         &self,
         message: &Message,
+        lookup_keys: &[Pubkey],
         instruction: &CompiledInstruction,
         executable_accounts: &mut [(Pubkey, Account)],
         program_accounts: &mut [&mut Account],
+        credits: &mut [u64],
         tick_height: u64,
     ) -> Result<(), InstructionError> {
         let program_id = instruction.program_id(&message.account_keys);
@@ -184,6 +201,7 @@ impl MessageProcessor {
 
         self.process_instruction(
             message,
+            lookup_keys,
             instruction,
             executable_accounts,
             program_accounts,
@@ -191,16 +209,24 @@ impl MessageProcessor {
         )?;
 
         // Verify the instruction
-        for ((pre_program_id, pre_difs, pre_data), post_account) in
-            pre_data.iter().zip(program_accounts.iter())
+        for ((index, (pre_program_id, pre_difs, pre_data)), post_account) in instruction
+            .accounts
+            .iter()
+            .zip(pre_data.iter())
+            .zip(program_accounts.iter())
         {
+            let is_writable = message.is_writable(*index as usize);
             verify_instruction(
+                is_writable,
                 &program_id,
                 pre_program_id,
                 *pre_difs,
                 pre_data,
                 post_account,
             )?;
+            if !is_writable {
+                credits[*index as usize] += post_account.difs - pre_difs;
+            }
         }
         // The total sum of all the difs in all the accounts cannot change.
         let post_total: u64 = program_accounts.iter().map(|a| a.difs).sum();
@@ -213,11 +239,15 @@ impl MessageProcessor {
     /// Process a message.
     /// This method calls each instruction in the message over the set of loaded Accounts
     /// The accounts are committed back to the bank only if every instruction succeeds
+    /// `lookup_keys` are the keys loaded from the message's address lookup tables, `credits`
+    /// takes what the message credited each of its credit-only accounts
     pub fn process_message(
         &self,
         message: &Message,
+        lookup_keys: &[Pubkey],
         loaders: &mut [Vec<(Pubkey, Account)>],
         accounts: &mut [Account],
+        credits: &mut [u64],
         tick_height: u64,
     ) -> Result<(), TransactionError> {
         for (instruction_index, instruction) in message.instructions.iter().enumerate() {
@@ -232,9 +262,11 @@ impl MessageProcessor {
             // executable account is not passed in as part of the accounts slice
             self.execute_instruction(
                 message,
+                lookup_keys,
                 instruction,
                 executable_accounts,
                 &mut program_accounts,
+                credits,
                 tick_height,
             )
             .map_err(|err| TransactionError::InstructionError(instruction_index as u8, err))?;
@@ -288,7 +320,7 @@ mod tests {
             pre: &Pubkey,
             post: &Pubkey,
         ) -> Result<(), InstructionError> {
-            verify_instruction(&ix, &pre, 0, &[], &Account::new(0, 0, 0, post))
+            verify_instruction(true, &ix, &pre, 0, &[], &Account::new(0, 0, 0, post))
         }
 
         let system_program_id = system_program::id();
@@ -312,7 +344,7 @@ mod tests {
         fn change_data(program_id: &Pubkey) -> Result<(), InstructionError> {
             let alice_program_id = Pubkey::new_rand();
             let account = Account::new(0, 0, 0, &alice_program_id);
-            verify_instruction(&program_id, &alice_program_id, 0, &[42], &account)
+            verify_instruction(true, &program_id, &alice_program_id, 0, &[42], &account)
         }
 
         let system_program_id = system_program::id();
@@ -329,4 +361,48 @@ mod tests {
             "malicious Mallory should not be able to change the account data"
         );
     }
+
+    #[test]
+    fn test_verify_instruction_credit_only() {
+        let alice_program_id = Pubkey::new_rand();
+        let account = Account::new(1, 0, 1, &alice_program_id);
+        let verify_credit_only = |program_id: &Pubkey, pre_difs: u64, pre_data: &[u8]| {
+            verify_instruction(
+                false,
+                program_id,
+                &alice_program_id,
+                pre_difs,
+                pre_data,
+                &account,
+            )
+        };
+        let system_program_id = system_program::id();
+
+        assert_eq!(verify_credit_only(&alice_program_id, 1, &[0]), Ok(()));
+        assert_eq!(
+            verify_credit_only(&system_program_id, 0, &[0]),
+            Ok(()),
+            "the system program may credit a credit-only account"
+        );
+        assert_eq!(
+            verify_credit_only(&alice_program_id, 0, &[0]),
+            Err(InstructionError::CreditOnlyDifChange),
+            "no other program may credit a credit-only account, not even its owner"
+        );
+        assert_eq!(
+            verify_credit_only(&alice_program_id, 2, &[0]),
+            Err(InstructionError::CreditOnlyDifChange),
+            "not even the owner may take difs from a credit-only account"
+        );
+        assert_eq!(
+            verify_credit_only(&alice_program_id, 1, &[42]),
+            Err(InstructionError::CreditOnlyDataModified),
+            "the owner may not change the data of a credit-only account"
+        );
+        assert_eq!(
+            verify_credit_only(&system_program_id, 1, &[42]),
+            Err(InstructionError::CreditOnlyDataModified),
+            "the system program may not change a credit-only account either"
+        );
+    }
 }
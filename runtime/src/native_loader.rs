@@ -1,12 +1,13 @@
 //! Native loader
-use crate::message_processor::SymbolCache;
 use bincode::deserialize;
+use hashbrown::HashMap;
 #[cfg(unix)]
 use libloading::os::unix::*;
 #[cfg(windows)]
 use libloading::os::windows::*;
 use log::*;
 use morgan_interface::account::KeyedAccount;
+use morgan_interface::hash::{hash, Hash};
 use morgan_interface::instruction::InstructionError;
 use morgan_interface::instruction_processor_utils;
 use morgan_interface::loader_instruction::LoaderInstruction;
@@ -14,6 +15,7 @@ use morgan_interface::pubkey::Pubkey;
 use std::env;
 use std::path::PathBuf;
 use std::str;
+use std::sync::{Arc, Mutex};
 use morgan_helper::logHelper::*;
 
 /// Dynamic link library prefixes
@@ -56,71 +58,156 @@ fn create_path(name: &str) -> PathBuf {
     }
 }
 
+/// Native programs kept loaded before the least recently used one is dropped
+pub const MAX_CACHED_NATIVE_PROGRAMS: usize = 64;
+
+pub struct NativeProgram {
+    // declared first so it's dropped before the library it points into
+    entrypoint: Symbol<instruction_processor_utils::Entrypoint>,
+    _library: Library,
+}
+
+/// Programs loaded on first use and keyed by the hash of their program account's data, so
+/// rewriting the account loads the new program instead of running a stale one. Callers
+/// hold an `Arc` for the length of a call, a program evicted while it runs is only dropped
+/// once the call returns.
+pub struct ProgramCache<T> {
+    max_programs: usize,
+    programs: Mutex<CachedPrograms<T>>,
+}
+
+struct CachedPrograms<T> {
+    /// each program with the clock value it was last used at
+    programs: HashMap<Hash, (Arc<T>, u64)>,
+    clock: u64,
+}
+
+pub type NativeProgramCache = ProgramCache<NativeProgram>;
+
+impl<T> Default for ProgramCache<T> {
+    fn default() -> Self {
+        Self::new(MAX_CACHED_NATIVE_PROGRAMS)
+    }
+}
+
+impl<T> ProgramCache<T> {
+    pub fn new(max_programs: usize) -> Self {
+        Self {
+            max_programs,
+            programs: Mutex::new(CachedPrograms {
+                programs: HashMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// The program for `program_data`, calling `load` if it isn't cached
+    pub fn get_or_load<F>(&self, program_data: &[u8], load: F) -> Result<Arc<T>, InstructionError>
+    where
+        F: FnOnce() -> Result<T, InstructionError>,
+    {
+        let key = hash(program_data);
+        {
+            let mut cached = self.programs.lock().unwrap();
+            let CachedPrograms { programs, clock } = &mut *cached;
+            *clock += 1;
+            if let Some((program, last_used)) = programs.get_mut(&key) {
+                *last_used = *clock;
+                return Ok(program.clone());
+            }
+        }
+
+        // load without the lock held, other programs keep running meanwhile
+        inc_new_counter_info!("native_loader-program_cache_miss", 1);
+        let program = Arc::new(load()?);
+        let mut cached = self.programs.lock().unwrap();
+        let CachedPrograms { programs, clock } = &mut *cached;
+        programs.insert(key, (program.clone(), *clock));
+        if programs.len() > self.max_programs {
+            let lru = programs
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| *key)
+                .unwrap();
+            programs.remove(&lru);
+            inc_new_counter_info!("native_loader-program_cache_evicted", 1);
+        }
+        Ok(program)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.programs.lock().unwrap().programs.len()
+    }
+}
+
+fn load_program(name_vec: &[u8]) -> Result<NativeProgram, InstructionError> {
+    let name = match str::from_utf8(name_vec) {
+        Ok(v) => v,
+        Err(e) => {
+            // warn!("Invalid UTF-8 sequence: {}", e);
+            println!("{}",Warn(format!("Invalid UTF-8 sequence: {}", e).to_string(),module_path!().to_string()));
+            return Err(InstructionError::GenericError);
+        }
+    };
+    trace!("Load native {:?}", name);
+    let path = create_path(&name);
+    // TODO linux tls bug can cause crash on dlclose(), workaround by never unloading.
+    // Evicting a program drops its handle but its code stays mapped until then.
+    match Library::open(Some(&path), libc::RTLD_NODELETE | libc::RTLD_NOW) {
+        Ok(library) => unsafe {
+            let entrypoint: Symbol<instruction_processor_utils::Entrypoint> =
+                match library.get(instruction_processor_utils::ENTRYPOINT.as_bytes()) {
+                    Ok(s) => s,
+                    Err(e) => {
+                        // warn!(
+                        //     "{:?}: Unable to find {:?} in program",
+                        //     e,
+                        //     instruction_processor_utils::ENTRYPOINT
+                        // );
+                        println!(
+                            "{}",
+                            Warn(format!("{:?}: Unable to find {:?} in program",
+                                e,
+                                instruction_processor_utils::ENTRYPOINT).to_string(),
+                            module_path!().to_string())
+                        );
+
+                        return Err(InstructionError::GenericError);
+                    }
+                };
+            Ok(NativeProgram {
+                entrypoint,
+                _library: library,
+            })
+        },
+        Err(e) => {
+            // warn!("Unable to load: {:?}", e);
+            println!(
+                "{}",
+                Warn(format!("Unable to load: {:?}", e).to_string(),
+                module_path!().to_string())
+            );
+            Err(InstructionError::GenericError)
+        }
+    }
+}
+
 pub fn entrypoint(
     program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     ix_data: &[u8],
     tick_height: u64,
-    symbol_cache: &SymbolCache,
+    program_cache: &NativeProgramCache,
 ) -> Result<(), InstructionError> {
     if keyed_accounts[0].account.executable {
         // dispatch it
         let (names, params) = keyed_accounts.split_at_mut(1);
         let name_vec = &names[0].account.data;
-        if let Some(entrypoint) = symbol_cache.read().unwrap().get(name_vec) {
-            unsafe {
-                return entrypoint(program_id, params, ix_data, tick_height);
-            }
-        }
-        let name = match str::from_utf8(name_vec) {
-            Ok(v) => v,
-            Err(e) => {
-                // warn!("Invalid UTF-8 sequence: {}", e);
-                println!("{}",Warn(format!("Invalid UTF-8 sequence: {}", e).to_string(),module_path!().to_string()));
-                return Err(InstructionError::GenericError);
-            }
-        };
-        trace!("Call native {:?}", name);
-        let path = create_path(&name);
-        // TODO linux tls bug can cause crash on dlclose(), workaround by never unloading
-        match Library::open(Some(&path), libc::RTLD_NODELETE | libc::RTLD_NOW) {
-            Ok(library) => unsafe {
-                let entrypoint: Symbol<instruction_processor_utils::Entrypoint> =
-                    match library.get(instruction_processor_utils::ENTRYPOINT.as_bytes()) {
-                        Ok(s) => s,
-                        Err(e) => {
-                            // warn!(
-                            //     "{:?}: Unable to find {:?} in program",
-                            //     e,
-                            //     instruction_processor_utils::ENTRYPOINT
-                            // );
-                            println!(
-                                "{}",
-                                Warn(format!("{:?}: Unable to find {:?} in program",
-                                    e,
-                                    instruction_processor_utils::ENTRYPOINT).to_string(),
-                                module_path!().to_string())
-                            );
-
-                            return Err(InstructionError::GenericError);
-                        }
-                    };
-                let ret = entrypoint(program_id, params, ix_data, tick_height);
-                symbol_cache
-                    .write()
-                    .unwrap()
-                    .insert(name_vec.to_vec(), entrypoint);
-                return ret;
-            },
-            Err(e) => {
-                // warn!("Unable to load: {:?}", e);
-                println!(
-                    "{}",
-                    Warn(format!("Unable to load: {:?}", e).to_string(),
-                    module_path!().to_string())
-                );
-                return Err(InstructionError::GenericError);
-            }
+        let program = program_cache.get_or_load(name_vec, || load_program(name_vec))?;
+        trace!("Call native {:?}", program_id);
+        unsafe {
+            return (program.entrypoint)(program_id, params, ix_data, tick_height);
         }
     } else if let Ok(instruction) = deserialize(ix_data) {
         if keyed_accounts[0].signer_key().is_none() {
@@ -176,3 +263,48 @@ pub fn entrypoint(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_cache_load_once() {
+        let cache = ProgramCache::new(2);
+        assert_eq!(*cache.get_or_load(b"a", || Ok(1)).unwrap(), 1);
+        assert_eq!(
+            *cache
+                .get_or_load(b"a", || panic!("cached program reloaded"))
+                .unwrap(),
+            1
+        );
+        assert_eq!(cache.len(), 1);
+
+        // new account data is a new program
+        assert_eq!(*cache.get_or_load(b"b", || Ok(2)).unwrap(), 2);
+        assert_eq!(cache.len(), 2);
+
+        // failed loads aren't cached
+        assert_eq!(
+            cache.get_or_load(b"c", || Err(InstructionError::GenericError)),
+            Err(InstructionError::GenericError)
+        );
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_program_cache_evict_lru() {
+        let cache = ProgramCache::new(2);
+        cache.get_or_load(b"a", || Ok(1)).unwrap();
+        let running = cache.get_or_load(b"b", || Ok(2)).unwrap();
+        cache.get_or_load(b"a", || Ok(1)).unwrap();
+
+        // "b" is least recently used
+        cache.get_or_load(b"c", || Ok(3)).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(*cache.get_or_load(b"a", || Ok(0)).unwrap(), 1);
+        assert_eq!(*cache.get_or_load(b"b", || Ok(0)).unwrap(), 0);
+        // an evicted program stays usable by callers still holding it
+        assert_eq!(*running, 2);
+    }
+}
@@ -0,0 +1,202 @@
+//! The `read_only_accounts_cache` keeps copies of recently loaded accounts so hot accounts
+//! (sysvars, fee payers, popular programs) skip the storage mmap on every load.
+//!
+//! Entries are keyed by pubkey and remember where in storage the cached copy was read
+//! from. A load only hits when the index still points at that same location, so a cached
+//! copy can never be returned for a fork that sees a different version of the account.
+//! Storage is only reset once nothing in it is indexed and every store invalidates the
+//! pubkeys it writes, so a location is never reused under a stale cached copy.
+
+use hashbrown::HashMap;
+use log::*;
+use morgan_interface::account::Account;
+use morgan_interface::pubkey::Pubkey;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::RwLock;
+
+/// Bytes of account data the cache holds before it starts evicting
+pub const DEFAULT_MAX_CACHE_SIZE: usize = 64 * 1024 * 1024;
+
+struct CachedAccount {
+    id: usize,
+    offset: usize,
+    account: Account,
+    /// set on every hit, cleared when the entry survives an eviction pass
+    hit: AtomicBool,
+}
+
+impl CachedAccount {
+    fn size(&self) -> usize {
+        size_of::<Pubkey>() + size_of::<CachedAccount>() + self.account.data.len()
+    }
+}
+
+pub struct ReadOnlyAccountsCache {
+    max_size: usize,
+    cache: RwLock<HashMap<Pubkey, CachedAccount>>,
+    size: AtomicUsize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl Default for ReadOnlyAccountsCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CACHE_SIZE)
+    }
+}
+
+impl ReadOnlyAccountsCache {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size,
+            cache: RwLock::new(HashMap::new()),
+            size: AtomicUsize::new(0),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// The cached copy of `pubkey` if it was read from storage entry `id` at `offset`
+    pub fn load(&self, pubkey: &Pubkey, id: usize, offset: usize) -> Option<Account> {
+        let cache = self.cache.read().unwrap();
+        match cache.get(pubkey) {
+            Some(entry) if entry.id == id && entry.offset == offset => {
+                entry.hit.store(true, Ordering::Relaxed);
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(entry.account.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, pubkey: &Pubkey, id: usize, offset: usize, account: &Account) {
+        let entry = CachedAccount {
+            id,
+            offset,
+            account: account.clone(),
+            hit: AtomicBool::new(false),
+        };
+        if entry.size() > self.max_size {
+            return;
+        }
+        let mut cache = self.cache.write().unwrap();
+        self.size.fetch_add(entry.size(), Ordering::Relaxed);
+        if let Some(old) = cache.insert(*pubkey, entry) {
+            self.size.fetch_sub(old.size(), Ordering::Relaxed);
+        }
+        if self.size.load(Ordering::Relaxed) > self.max_size {
+            self.evict(&mut cache, pubkey);
+        }
+    }
+
+    /// Drop the cached copies of accounts that were just written
+    pub fn invalidate<'a>(&self, pubkeys: impl Iterator<Item = &'a Pubkey>) {
+        let mut cache = self.cache.write().unwrap();
+        for pubkey in pubkeys {
+            if let Some(old) = cache.remove(pubkey) {
+                self.size.fetch_sub(old.size(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Keep `newest` and the entries hit since the last pass, or only `newest` if even
+    /// those don't fit
+    fn evict(&self, cache: &mut HashMap<Pubkey, CachedAccount>, newest: &Pubkey) {
+        let evicted = cache.len();
+        cache.retain(|pubkey, entry| entry.hit.swap(false, Ordering::Relaxed) || pubkey == newest);
+        let mut size: usize = cache.values().map(CachedAccount::size).sum();
+        if size > self.max_size {
+            cache.retain(|pubkey, _| pubkey == newest);
+            size = cache.values().map(CachedAccount::size).sum();
+        }
+        self.size.store(size, Ordering::Relaxed);
+        inc_new_counter_info!("accounts_db-read_only_cache_evicted", evicted - cache.len());
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    /// Report the hit rate since the last report
+    pub fn report_metrics(&self) {
+        let hits = self.hits.swap(0, Ordering::Relaxed);
+        let misses = self.misses.swap(0, Ordering::Relaxed);
+        let hit_rate_percent = if hits + misses == 0 {
+            0
+        } else {
+            hits * 100 / (hits + misses)
+        };
+        datapoint_info!(
+            "accounts_db-read_only_cache",
+            ("hits", hits, i64),
+            ("misses", misses, i64),
+            ("hit_rate_percent", hit_rate_percent, i64),
+            ("entries", self.len(), i64),
+            ("size", self.size(), i64)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_only_accounts_cache_load_store() {
+        let cache = ReadOnlyAccountsCache::default();
+        let pubkey = Pubkey::new_rand();
+        let account = Account::new(1, 0, 8, &Pubkey::default());
+        assert_eq!(cache.load(&pubkey, 0, 0), None);
+
+        cache.store(&pubkey, 0, 0, &account);
+        assert_eq!(cache.load(&pubkey, 0, 0), Some(account));
+        // a different storage location is a different version of the account
+        assert_eq!(cache.load(&pubkey, 0, 128), None);
+        assert_eq!(cache.load(&pubkey, 1, 0), None);
+        assert_eq!(cache.hits.load(Ordering::Relaxed), 1);
+        assert_eq!(cache.misses.load(Ordering::Relaxed), 3);
+
+        cache.invalidate([pubkey].iter());
+        assert_eq!(cache.load(&pubkey, 0, 0), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.size(), 0);
+    }
+
+    #[test]
+    fn test_read_only_accounts_cache_evict() {
+        let account = Account::new(1, 0, 1024, &Pubkey::default());
+        let entry_size = size_of::<Pubkey>() + size_of::<CachedAccount>() + 1024;
+        let cache = ReadOnlyAccountsCache::new(entry_size * 3);
+        let pubkeys: Vec<_> = (0..4).map(|_| Pubkey::new_rand()).collect();
+        for pubkey in &pubkeys[..3] {
+            cache.store(pubkey, 0, 0, &account);
+        }
+        assert_eq!(cache.len(), 3);
+        assert!(cache.load(&pubkeys[0], 0, 0).is_some());
+
+        // only the new entry and the one hit since the last pass survive
+        cache.store(&pubkeys[3], 0, 0, &account);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size(), entry_size * 2);
+        assert!(cache.load(&pubkeys[0], 0, 0).is_some());
+        assert!(cache.load(&pubkeys[3], 0, 0).is_some());
+        assert!(cache.load(&pubkeys[1], 0, 0).is_none());
+
+        // accounts bigger than the whole cache are never kept
+        let cache = ReadOnlyAccountsCache::new(entry_size - 1);
+        cache.store(&pubkeys[0], 0, 0, &account);
+        assert!(cache.is_empty());
+    }
+}
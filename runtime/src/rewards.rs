@@ -0,0 +1,149 @@
+//! The `rewards` module works out what the stakers and voters of the cluster earned in an
+//! epoch.  At every epoch boundary the bank mints every active stake the inflation rate
+//! for the credits its voter earned since the stake was last paid, and splits that with
+//! the voter by its commission, see `stake_state::redeem_rewards`.
+
+use crate::stakes::Stakes;
+use morgan_interface::account::Account;
+use morgan_interface::pubkey::Pubkey;
+use morgan_stake_api::stake_state::{self, StakeState};
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+/// The stake and vote accounts with their rewards paid, and the difs minted to pay them
+#[derive(Debug, Default, PartialEq)]
+pub struct EpochRewards {
+    pub accounts: Vec<(Pubkey, Account)>,
+    pub voters_rewards: u64,
+    pub stakers_rewards: u64,
+}
+
+impl EpochRewards {
+    pub fn total(&self) -> u64 {
+        self.voters_rewards + self.stakers_rewards
+    }
+}
+
+/// Pay the stakes in `stakes` at `rate` a year, for voters that earn `credits_per_year`
+/// credits voting on every slot.  Stakes and voters are walked in pubkey order, so every
+/// node mints the same rewards
+pub fn calculate_epoch_rewards(stakes: &Stakes, rate: f64, credits_per_year: f64) -> EpochRewards {
+    let mut rewards = EpochRewards::default();
+    let mut vote_accounts: BTreeMap<Pubkey, Account> = BTreeMap::new();
+
+    for (stake_pubkey, stake_account) in stakes.stake_accounts() {
+        let voter_pubkey = match StakeState::voter_pubkey_from(stake_account) {
+            Some(voter_pubkey) => voter_pubkey,
+            None => continue,
+        };
+        let vote_account = match vote_accounts.entry(voter_pubkey) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => match stakes.vote_accounts().get(&voter_pubkey) {
+                Some((_, vote_account)) => entry.insert(Account::clone(vote_account)),
+                None => continue,
+            },
+        };
+
+        let mut stake_account = Account::clone(stake_account);
+        if let Some((voters_reward, stakers_reward)) = stake_state::redeem_rewards(
+            &mut stake_account,
+            &voter_pubkey,
+            vote_account,
+            rate,
+            credits_per_year,
+        ) {
+            rewards.voters_rewards += voters_reward;
+            rewards.stakers_rewards += stakers_reward;
+            rewards.accounts.push((*stake_pubkey, stake_account));
+        }
+    }
+
+    rewards.accounts.extend(
+        vote_accounts
+            .into_iter()
+            .filter(|(voter_pubkey, vote_account)| {
+                stakes.vote_accounts()[voter_pubkey].1.difs != vote_account.difs
+            }),
+    );
+    rewards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morgan_interface::account_utils::State;
+    use morgan_vote_api::vote_state::{self, VoteState};
+
+    const CREDITS_PER_YEAR: f64 = 1_000.0;
+
+    fn create_voter(stakes: &mut Stakes, credits: u64, commission: u32) -> (Pubkey, VoteState) {
+        let vote_pubkey = Pubkey::new_rand();
+        let mut vote_account =
+            vote_state::create_account(&vote_pubkey, &Pubkey::new_rand(), commission, 1);
+        let mut vote_state: VoteState = vote_account.state().unwrap();
+        let mut slot = 0;
+        while vote_state.credits() < credits {
+            vote_state.process_slot_vote_unchecked(slot);
+            slot += 1;
+        }
+        vote_account.set_state(&vote_state).unwrap();
+        stakes.store(&vote_pubkey, &vote_account);
+        (vote_pubkey, vote_state)
+    }
+
+    #[test]
+    fn test_calculate_epoch_rewards() {
+        let mut stakes = Stakes::default();
+        let (busy_pubkey, busy_state) = create_voter(&mut stakes, 10, std::u32::MAX / 2);
+        let (_idle_pubkey, idle_state) = create_voter(&mut stakes, 0, 0);
+
+        // stakes earn for the credits since they delegated
+        let busy_stake_pubkey = Pubkey::new_rand();
+        stakes.store(
+            &busy_stake_pubkey,
+            &stake_state::create_delegate_stake_account(
+                &busy_pubkey,
+                &VoteState::default(),
+                10_500,
+            ),
+        );
+        let late_stake_pubkey = Pubkey::new_rand();
+        stakes.store(
+            &late_stake_pubkey,
+            &stake_state::create_delegate_stake_account(&busy_pubkey, &busy_state, 10_500),
+        );
+        let idle_stake_pubkey = Pubkey::new_rand();
+        stakes.store(
+            &idle_stake_pubkey,
+            &stake_state::create_delegate_stake_account(&busy_pubkey, &idle_state, 10_500),
+        );
+
+        // 10_500 * 0.1 * 10 credits / 1_000 credits a year, split in half and rounded down
+        let rewards = calculate_epoch_rewards(&stakes, 0.1, CREDITS_PER_YEAR);
+        assert_eq!(rewards.stakers_rewards, 10);
+        assert_eq!(rewards.voters_rewards, 10);
+        assert_eq!(rewards.total(), 20);
+
+        let accounts: BTreeMap<_, _> = rewards.accounts.into_iter().collect();
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[&busy_stake_pubkey].difs, 10_505);
+        assert_eq!(accounts[&idle_stake_pubkey].difs, 10_505);
+        assert_eq!(accounts[&busy_pubkey].difs, 1 + 10);
+        assert!(!accounts.contains_key(&late_stake_pubkey));
+        assert_eq!(
+            StakeState::from(&accounts[&busy_stake_pubkey]),
+            Some(StakeState::Delegate {
+                voter_pubkey: busy_pubkey,
+                credits_observed: busy_state.credits(),
+                stake: 10_500,
+                deactivated: stake_state::STAKE_ACTIVE,
+            })
+        );
+
+        // no inflation, no rewards
+        assert_eq!(
+            calculate_epoch_rewards(&stakes, 0.0, CREDITS_PER_YEAR),
+            EpochRewards::default()
+        );
+    }
+}
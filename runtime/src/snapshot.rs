@@ -0,0 +1,764 @@
+//! The `snapshot` module saves a frozen bank to a compressed archive and restores a bank from
+//! one, so a validator can start from a recent root instead of replaying the whole ledger.
+//! An archive is a gzip stream of bincode records: a header, the bank's fields, its rooted
+//! signature statuses and then every non-empty account it can see.
+//!
+//! An incremental archive is laid out the same, but only holds the accounts changed since the
+//! newest full archive, emptied ones included, and is restored on top of it.  A manifest
+//! lists the increments taken against the full archive, each chained to the one before by
+//! hash, and is checked along with the archives it names before they're restored.
+
+use crate::accounts::Accounts;
+use crate::accounts_db::PinnedAccounts;
+use crate::bank::{Bank, BankFields, BankStatusCache};
+use bincode::{deserialize_from, serialize_into};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use hashbrown::HashMap;
+use morgan_interface::account::Account;
+use morgan_interface::hash::{hashv, Hash, Hasher};
+use morgan_interface::pubkey::Pubkey;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the archive layout changes, older archives are refused
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+const SNAPSHOT_PREFIX: &str = "snapshot-";
+const INCREMENTAL_SNAPSHOT_PREFIX: &str = "incremental-snapshot-";
+const SNAPSHOT_EXTENSION: &str = ".bin.gz";
+const SNAPSHOT_MANIFEST_FILE: &str = "incremental-snapshots.manifest";
+
+/// Bytes read at once while hashing an archive
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Accounts stored into the accounts db at once while restoring
+const RESTORE_BATCH_SIZE: usize = 1024;
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotHeader {
+    version: u32,
+    slot: u64,
+    accounts_hash: Hash,
+    num_accounts: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IncrementalSnapshotHeader {
+    version: u32,
+    slot: u64,
+    /// slot of the full snapshot the accounts changed since
+    base_slot: u64,
+    /// hash of the archived accounts, the empty ones included
+    accounts_hash: Hash,
+    num_accounts: u64,
+}
+
+/// The increments taken against the newest full snapshot, oldest first
+#[derive(Serialize, Deserialize)]
+struct SnapshotManifest {
+    version: u32,
+    full_slot: u64,
+    full_archive_hash: Hash,
+    increments: Vec<ManifestEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ManifestEntry {
+    slot: u64,
+    archive_hash: Hash,
+    /// hash of the previous entry's chain hash, or of nothing for the full snapshot, with this
+    /// entry's slot and archive hash
+    chain_hash: Hash,
+}
+
+fn chain_hash(prev_chain_hash: &Hash, slot: u64, archive_hash: &Hash) -> Hash {
+    hashv(&[
+        prev_chain_hash.as_ref(),
+        &slot.to_le_bytes(),
+        archive_hash.as_ref(),
+    ])
+}
+
+impl SnapshotManifest {
+    fn new(full_slot: u64, full_archive_hash: Hash) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            full_slot,
+            full_archive_hash,
+            increments: vec![],
+        }
+    }
+
+    fn full_chain_hash(&self) -> Hash {
+        chain_hash(&Hash::default(), self.full_slot, &self.full_archive_hash)
+    }
+
+    fn push(&mut self, slot: u64, archive_hash: Hash) {
+        let prev_chain_hash = self
+            .increments
+            .last()
+            .map_or_else(|| self.full_chain_hash(), |entry| entry.chain_hash);
+        self.increments.push(ManifestEntry {
+            slot,
+            archive_hash,
+            chain_hash: chain_hash(&prev_chain_hash, slot, &archive_hash),
+        });
+    }
+
+    /// Check that every entry chains to the one before, in slot order
+    fn verify(&self) -> io::Result<()> {
+        let mut prev_chain_hash = self.full_chain_hash();
+        let mut prev_slot = self.full_slot;
+        for entry in &self.increments {
+            if entry.slot <= prev_slot
+                || entry.chain_hash != chain_hash(&prev_chain_hash, entry.slot, &entry.archive_hash)
+            {
+                return Err(invalid_data(format!(
+                    "snapshot manifest chain is broken at slot {}",
+                    entry.slot
+                )));
+            }
+            prev_chain_hash = entry.chain_hash;
+            prev_slot = entry.slot;
+        }
+        Ok(())
+    }
+
+    fn load<P: AsRef<Path>>(snapshot_path: P) -> io::Result<Self> {
+        let file = BufReader::new(File::open(
+            snapshot_path.as_ref().join(SNAPSHOT_MANIFEST_FILE),
+        )?);
+        let manifest: Self = deserialize_from(file).map_err(invalid_data)?;
+        check_version(manifest.version)?;
+        Ok(manifest)
+    }
+
+    /// Written aside and renamed into place, like the archives
+    fn save<P: AsRef<Path>>(&self, snapshot_path: P) -> io::Result<()> {
+        let manifest_path = snapshot_path.as_ref().join(SNAPSHOT_MANIFEST_FILE);
+        let tmp_path = manifest_path.with_extension("tmp");
+        let mut file = BufWriter::new(File::create(&tmp_path)?);
+        write_record(&mut file, self)?;
+        file.flush()?;
+        fs::rename(&tmp_path, &manifest_path)
+    }
+}
+
+fn invalid_data<E: ToString>(err: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+fn write_record<T: serde::Serialize, W: Write>(writer: &mut W, record: &T) -> io::Result<()> {
+    serialize_into(writer, record).map_err(invalid_data)
+}
+
+fn read_record<T, R: Read>(reader: &mut R) -> io::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    deserialize_from(reader).map_err(invalid_data)
+}
+
+fn check_version(version: u32) -> io::Result<()> {
+    if version != SNAPSHOT_VERSION {
+        return Err(invalid_data(format!(
+            "snapshot version {} is not supported, expected {}",
+            version, SNAPSHOT_VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Hash of the bytes of the archive at `archive_path`
+fn hash_archive<P: AsRef<Path>>(archive_path: P) -> io::Result<Hash> {
+    let mut file = File::open(archive_path)?;
+    let mut hasher = Hasher::default();
+    let mut buf = vec![0; HASH_CHUNK_SIZE];
+    loop {
+        let len = file.read(&mut buf)?;
+        if len == 0 {
+            return Ok(hasher.result());
+        }
+        hasher.hash(&buf[..len]);
+    }
+}
+
+fn check_archive_hash<P: AsRef<Path>>(archive_path: P, expected: &Hash) -> io::Result<()> {
+    let hash = hash_archive(&archive_path)?;
+    if hash != *expected {
+        return Err(invalid_data(format!(
+            "archive {:?} hashes to {}, the snapshot manifest has {}",
+            archive_path.as_ref(),
+            hash,
+            expected
+        )));
+    }
+    Ok(())
+}
+
+/// Path of the archive of `slot` under `snapshot_path`
+pub fn snapshot_archive_path<P: AsRef<Path>>(snapshot_path: P, slot: u64) -> PathBuf {
+    snapshot_path
+        .as_ref()
+        .join(format!("{}{}{}", SNAPSHOT_PREFIX, slot, SNAPSHOT_EXTENSION))
+}
+
+/// Path of the incremental archive of `slot` under `snapshot_path`
+pub fn incremental_snapshot_archive_path<P: AsRef<Path>>(snapshot_path: P, slot: u64) -> PathBuf {
+    snapshot_path.as_ref().join(format!(
+        "{}{}{}",
+        INCREMENTAL_SNAPSHOT_PREFIX, slot, SNAPSHOT_EXTENSION
+    ))
+}
+
+/// Slots of the archives under `snapshot_path`, newest first
+pub fn snapshot_slots<P: AsRef<Path>>(snapshot_path: P) -> io::Result<Vec<u64>> {
+    archive_slots(snapshot_path, SNAPSHOT_PREFIX)
+}
+
+/// Slots of the incremental archives under `snapshot_path`, newest first
+pub fn incremental_snapshot_slots<P: AsRef<Path>>(snapshot_path: P) -> io::Result<Vec<u64>> {
+    archive_slots(snapshot_path, INCREMENTAL_SNAPSHOT_PREFIX)
+}
+
+fn archive_slots<P: AsRef<Path>>(snapshot_path: P, prefix: &str) -> io::Result<Vec<u64>> {
+    let mut slots: Vec<u64> = fs::read_dir(snapshot_path)?
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name();
+            let file_name = file_name.to_str()?;
+            if !file_name.starts_with(prefix) {
+                return None;
+            }
+            file_name[prefix.len()..]
+                .trim_end_matches(SNAPSHOT_EXTENSION)
+                .parse()
+                .ok()
+        })
+        .collect();
+    slots.sort_by(|a, b| b.cmp(a));
+    Ok(slots)
+}
+
+/// What an archive holds of a bank, taken while the bank is the newest root.  Once later
+/// banks are rooted their accounts and statuses show through the bank's, so the state is
+/// taken first and archived at leisure.  Taking it is cheap, the accounts are only pinned in
+/// storage and are read when archived.
+pub struct BankSnapshot {
+    fields: BankFields,
+    status_cache: BankStatusCache,
+    accounts: PinnedAccounts,
+    /// slot of the full snapshot an incremental snapshot is taken against
+    base_slot: Option<u64>,
+}
+
+impl BankSnapshot {
+    /// Take the state of `bank`, which must be frozen and not yet have a rooted descendant
+    pub fn new(bank: &Bank) -> Self {
+        assert!(bank.is_frozen());
+        Self {
+            fields: bank.snapshot_fields(),
+            status_cache: bank.snapshot_status_cache(),
+            accounts: bank.snapshot_accounts(),
+            base_slot: None,
+        }
+    }
+
+    /// Like `new`, but only take the accounts changed since the full snapshot of `base_slot`,
+    /// the newest one saved, for an incremental archive
+    pub fn new_incremental(bank: &Bank, base_slot: u64) -> Self {
+        assert!(bank.is_frozen());
+        assert!(base_slot < bank.slot());
+        Self {
+            fields: bank.snapshot_fields(),
+            status_cache: bank.snapshot_status_cache(),
+            accounts: bank.snapshot_changed_accounts(base_slot),
+            base_slot: Some(base_slot),
+        }
+    }
+
+    pub fn slot(&self) -> u64 {
+        self.fields.slot
+    }
+
+    /// Slot of the full snapshot this one is an increment over, None if it's a full one
+    pub fn base_slot(&self) -> Option<u64> {
+        self.base_slot
+    }
+
+    /// Save to an archive under `snapshot_path` and return the archive's path.  The archive
+    /// is written aside and renamed into place, so a crash never leaves a partial archive
+    /// behind under the final name.  A full archive starts a new manifest, an incremental one
+    /// is added to the manifest of its full archive.
+    pub fn save<P: AsRef<Path>>(&self, snapshot_path: P) -> io::Result<PathBuf> {
+        fs::create_dir_all(&snapshot_path)?;
+        let archive_path = match self.base_slot {
+            None => snapshot_archive_path(&snapshot_path, self.slot()),
+            Some(_) => incremental_snapshot_archive_path(&snapshot_path, self.slot()),
+        };
+        let tmp_path = archive_path.with_extension("tmp");
+
+        let mut manifest = None;
+        let file = BufWriter::new(File::create(&tmp_path)?);
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        let accounts = match self.base_slot {
+            None => {
+                let accounts = self.accounts.load();
+                let header = SnapshotHeader {
+                    version: SNAPSHOT_VERSION,
+                    slot: self.slot(),
+                    accounts_hash: Accounts::hash_loaded_accounts(&accounts),
+                    num_accounts: accounts.len() as u64,
+                };
+                write_record(&mut encoder, &header)?;
+                accounts
+            }
+            Some(base_slot) => {
+                let base_manifest = SnapshotManifest::load(&snapshot_path)?;
+                if base_manifest.full_slot != base_slot {
+                    return Err(invalid_data(format!(
+                        "incremental snapshot of slot {} is against slot {}, the newest full \
+                         snapshot is of slot {}",
+                        self.slot(),
+                        base_slot,
+                        base_manifest.full_slot
+                    )));
+                }
+                manifest = Some(base_manifest);
+                let accounts: Vec<_> = self.accounts.iter_all().collect();
+                let header = IncrementalSnapshotHeader {
+                    version: SNAPSHOT_VERSION,
+                    slot: self.slot(),
+                    base_slot,
+                    accounts_hash: Accounts::hash_loaded_accounts(&accounts),
+                    num_accounts: accounts.len() as u64,
+                };
+                write_record(&mut encoder, &header)?;
+                accounts
+            }
+        };
+        write_record(&mut encoder, &self.fields)?;
+        write_record(&mut encoder, &self.status_cache)?;
+        for account in &accounts {
+            write_record(&mut encoder, account)?;
+        }
+        encoder.finish()?.flush()?;
+        fs::rename(&tmp_path, &archive_path)?;
+
+        let archive_hash = hash_archive(&archive_path)?;
+        let manifest = match manifest {
+            Some(mut manifest) => {
+                manifest.push(self.slot(), archive_hash);
+                manifest
+            }
+            None => SnapshotManifest::new(self.slot(), archive_hash),
+        };
+        manifest.save(&snapshot_path)?;
+        Ok(archive_path)
+    }
+}
+
+/// Save `bank`, which must be frozen and the newest root, to an archive under
+/// `snapshot_path` and return the archive's path, see `BankSnapshot::save`
+pub fn save_snapshot<P: AsRef<Path>>(bank: &Bank, snapshot_path: P) -> io::Result<PathBuf> {
+    BankSnapshot::new(bank).save(snapshot_path)
+}
+
+type ArchiveReader = GzDecoder<BufReader<File>>;
+
+fn open_archive<P: AsRef<Path>>(archive_path: P) -> io::Result<ArchiveReader> {
+    Ok(GzDecoder::new(BufReader::new(File::open(archive_path)?)))
+}
+
+/// Read the bank fields and statuses that follow the header of an archive of `slot`
+fn read_bank_state(
+    decoder: &mut ArchiveReader,
+    slot: u64,
+) -> io::Result<(BankFields, BankStatusCache)> {
+    let fields: BankFields = read_record(decoder)?;
+    if fields.slot != slot {
+        return Err(invalid_data(format!(
+            "snapshot of slot {} holds a bank of slot {}",
+            slot, fields.slot
+        )));
+    }
+    let status_cache: BankStatusCache = read_record(decoder)?;
+    Ok((fields, status_cache))
+}
+
+fn store_accounts(accounts: &Accounts, slot: u64, batch: &[(Pubkey, Account)]) {
+    let batch: Vec<_> = batch
+        .iter()
+        .map(|(pubkey, account)| (pubkey, account))
+        .collect();
+    accounts.accounts_db.store(slot, &batch);
+}
+
+/// Store the next `num_accounts` accounts of an archive into `accounts` at `slot`
+fn restore_accounts(
+    decoder: &mut ArchiveReader,
+    num_accounts: u64,
+    accounts: &Accounts,
+    slot: u64,
+) -> io::Result<()> {
+    let mut remaining = num_accounts;
+    while remaining > 0 {
+        let batch_size = remaining.min(RESTORE_BATCH_SIZE as u64);
+        let batch = (0..batch_size)
+            .map(|_| read_record(decoder))
+            .collect::<io::Result<Vec<(Pubkey, Account)>>>()?;
+        store_accounts(accounts, slot, &batch);
+        remaining -= batch_size;
+    }
+    Ok(())
+}
+
+/// Restore the bank saved in the archive at `archive_path`, storing its accounts under
+/// `account_paths` with an index overflowing to disk past `index_memory_budget` bytes, if
+/// given.  The restored bank is a frozen root without parents.
+pub fn restore_snapshot<P: AsRef<Path>>(
+    archive_path: P,
+    account_paths: Option<String>,
+    index_memory_budget: Option<usize>,
+) -> io::Result<Bank> {
+    let mut decoder = open_archive(archive_path)?;
+    let header: SnapshotHeader = read_record(&mut decoder)?;
+    check_version(header.version)?;
+    let (fields, status_cache) = read_bank_state(&mut decoder, header.slot)?;
+
+    let accounts = Accounts::new_with_index_memory_budget(account_paths, index_memory_budget);
+    restore_accounts(&mut decoder, header.num_accounts, &accounts, header.slot)?;
+    accounts.add_root(header.slot);
+
+    let bank = Bank::new_from_snapshot(fields, accounts, status_cache);
+    let accounts_hash = bank.hash_accounts();
+    if accounts_hash != header.accounts_hash {
+        return Err(invalid_data(format!(
+            "restored accounts hash {} does not match the snapshot's {}",
+            accounts_hash, header.accounts_hash
+        )));
+    }
+    Ok(bank)
+}
+
+/// Move the archive at `archive_path`, fetched from elsewhere and checked by the caller, under
+/// `snapshot_path` as the newest full snapshot and return its new path.  It starts a new
+/// manifest, so the increments over an older full snapshot are no longer restored.
+pub fn install_snapshot<P: AsRef<Path>, Q: AsRef<Path>>(
+    archive_path: P,
+    snapshot_path: Q,
+) -> io::Result<PathBuf> {
+    let header: SnapshotHeader = read_record(&mut open_archive(&archive_path)?)?;
+    check_version(header.version)?;
+    fs::create_dir_all(&snapshot_path)?;
+    let installed_path = snapshot_archive_path(&snapshot_path, header.slot);
+    fs::rename(&archive_path, &installed_path)?;
+    SnapshotManifest::new(header.slot, hash_archive(&installed_path)?).save(&snapshot_path)?;
+    Ok(installed_path)
+}
+
+/// Restore the bank of the newest incremental archive under `snapshot_path` listed in its
+/// manifest, on top of the full archive it was taken against, or of the full archive alone
+/// if none of its increments are left.  The manifest's chain and the hashes of both archives
+/// are checked first, see `restore_snapshot` for the rest.
+pub fn restore_incremental_snapshot<P: AsRef<Path>>(
+    snapshot_path: P,
+    account_paths: Option<String>,
+    index_memory_budget: Option<usize>,
+) -> io::Result<Bank> {
+    let manifest = SnapshotManifest::load(&snapshot_path)?;
+    manifest.verify()?;
+    let full_archive_path = snapshot_archive_path(&snapshot_path, manifest.full_slot);
+    check_archive_hash(&full_archive_path, &manifest.full_archive_hash)?;
+    let increment = match manifest
+        .increments
+        .iter()
+        .rev()
+        .find(|entry| incremental_snapshot_archive_path(&snapshot_path, entry.slot).exists())
+    {
+        Some(increment) => increment,
+        None => return restore_snapshot(full_archive_path, account_paths, index_memory_budget),
+    };
+    let archive_path = incremental_snapshot_archive_path(&snapshot_path, increment.slot);
+    check_archive_hash(&archive_path, &increment.archive_hash)?;
+
+    let mut full_decoder = open_archive(full_archive_path)?;
+    let full_header: SnapshotHeader = read_record(&mut full_decoder)?;
+    check_version(full_header.version)?;
+    if full_header.slot != manifest.full_slot {
+        return Err(invalid_data(format!(
+            "snapshot manifest lists slot {}, its full snapshot is of slot {}",
+            manifest.full_slot, full_header.slot
+        )));
+    }
+    // Only the full snapshot's accounts are needed, its bank is superseded by the increment's
+    read_bank_state(&mut full_decoder, full_header.slot)?;
+    let accounts = Accounts::new_with_index_memory_budget(account_paths, index_memory_budget);
+    restore_accounts(
+        &mut full_decoder,
+        full_header.num_accounts,
+        &accounts,
+        full_header.slot,
+    )?;
+    accounts.add_root(full_header.slot);
+    let mut ancestors = HashMap::new();
+    ancestors.insert(full_header.slot, 0);
+    let accounts_hash = accounts.hash_accounts(&ancestors);
+    if accounts_hash != full_header.accounts_hash {
+        return Err(invalid_data(format!(
+            "restored accounts hash {} does not match the full snapshot's {}",
+            accounts_hash, full_header.accounts_hash
+        )));
+    }
+
+    let mut decoder = open_archive(archive_path)?;
+    let header: IncrementalSnapshotHeader = read_record(&mut decoder)?;
+    check_version(header.version)?;
+    if header.slot != increment.slot || header.base_slot != full_header.slot {
+        return Err(invalid_data(format!(
+            "incremental snapshot of slot {} against slot {} is listed as slot {} against \
+             slot {}",
+            header.slot, header.base_slot, increment.slot, full_header.slot
+        )));
+    }
+    let (fields, status_cache) = read_bank_state(&mut decoder, header.slot)?;
+    // The increment's accounts are few, so they're hashed whole before they shadow the full
+    // snapshot's
+    let changed = (0..header.num_accounts)
+        .map(|_| read_record(&mut decoder))
+        .collect::<io::Result<Vec<(Pubkey, Account)>>>()?;
+    let accounts_hash = Accounts::hash_loaded_accounts(&changed);
+    if accounts_hash != header.accounts_hash {
+        return Err(invalid_data(format!(
+            "incremental snapshot accounts hash {} does not match its header's {}",
+            accounts_hash, header.accounts_hash
+        )));
+    }
+    for batch in changed.chunks(RESTORE_BATCH_SIZE) {
+        store_accounts(&accounts, header.slot, batch);
+    }
+    accounts.add_root(header.slot);
+
+    Ok(Bank::new_from_snapshot(fields, accounts, status_cache))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morgan_interface::genesis_block::create_genesis_block;
+    use morgan_interface::hash::hash;
+    use morgan_interface::signature::{Keypair, KeypairUtil};
+    use morgan_interface::system_transaction;
+    use morgan_interface::transaction::TransactionError;
+    use std::sync::Arc;
+
+    fn tmp_snapshot_path(name: &str) -> PathBuf {
+        let out_dir = std::env::var("OUT_DIR").unwrap_or_else(|_| "target".to_string());
+        let path = Path::new(&out_dir).join("snapshot").join(name);
+        let _ignored = fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn test_save_and_restore_snapshot() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let key1 = Keypair::new();
+        bank0
+            .transfer(1_000, &mint_keypair, &key1.pubkey())
+            .unwrap();
+        bank0.freeze();
+
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let key2 = Pubkey::new_rand();
+        let tx = system_transaction::transfer(&key1, &key2, 100, bank1.last_blockhash());
+        assert_eq!(bank1.process_transaction(&tx), Ok(()));
+        bank1.squash();
+        bank1.freeze();
+
+        let snapshot_path = tmp_snapshot_path("test_save_and_restore_snapshot");
+        let archive_path = save_snapshot(&bank1, &snapshot_path).unwrap();
+        assert_eq!(archive_path, snapshot_archive_path(&snapshot_path, 1));
+        assert_eq!(snapshot_slots(&snapshot_path).unwrap(), vec![1]);
+
+        let bank = restore_snapshot(&archive_path, None, None).unwrap();
+        assert!(bank.is_frozen());
+        assert_eq!(bank.slot(), 1);
+        assert_eq!(bank.hash(), bank1.hash());
+        assert_eq!(bank.hash_accounts(), bank1.hash_accounts());
+        assert_eq!(bank.last_blockhash(), bank1.last_blockhash());
+        assert_eq!(bank.transaction_count(), bank1.transaction_count());
+        assert_eq!(bank.get_balance(&key1.pubkey()), 900);
+        assert_eq!(bank.get_balance(&key2), 100);
+        assert_eq!(bank.get_signature_status(&tx.signatures[0]), Some(Ok(())));
+
+        // The restored bank goes on like the one it was saved from
+        let bank2 = Bank::new_from_parent(&Arc::new(bank), &Pubkey::default(), 2);
+        assert_eq!(
+            bank2.process_transaction(&tx),
+            Err(TransactionError::DuplicateSignature)
+        );
+        bank2.transfer(1, &mint_keypair, &key2).unwrap();
+        assert_eq!(bank2.get_balance(&key2), 101);
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+
+    #[test]
+    fn test_save_and_restore_incremental_snapshot() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        let key1 = Keypair::new();
+        bank0
+            .transfer(1_000, &mint_keypair, &key1.pubkey())
+            .unwrap();
+        bank0.freeze();
+        let snapshot_path = tmp_snapshot_path("test_save_and_restore_incremental_snapshot");
+        save_snapshot(&bank0, &snapshot_path).unwrap();
+
+        // key1 is emptied, the increment must still carry it to shadow the full snapshot's
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        let key2 = Pubkey::new_rand();
+        bank1.transfer(1_000, &key1, &key2).unwrap();
+        bank1.squash();
+        bank1.freeze();
+        let archive_path = BankSnapshot::new_incremental(&bank1, 0)
+            .save(&snapshot_path)
+            .unwrap();
+        assert_eq!(
+            archive_path,
+            incremental_snapshot_archive_path(&snapshot_path, 1)
+        );
+
+        let bank2 = Bank::new_from_parent(&bank1, &Pubkey::default(), 2);
+        let key3 = Pubkey::new_rand();
+        bank2.transfer(5, &mint_keypair, &key3).unwrap();
+        bank2.squash();
+        bank2.freeze();
+        BankSnapshot::new_incremental(&bank2, 0)
+            .save(&snapshot_path)
+            .unwrap();
+        assert_eq!(snapshot_slots(&snapshot_path).unwrap(), vec![0]);
+        assert_eq!(
+            incremental_snapshot_slots(&snapshot_path).unwrap(),
+            vec![2, 1]
+        );
+
+        // The newest increment is restored
+        let bank = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+        assert_eq!(bank.slot(), 2);
+        assert_eq!(bank.hash(), bank2.hash());
+        assert_eq!(bank.hash_accounts(), bank2.hash_accounts());
+        assert_eq!(bank.get_balance(&key1.pubkey()), 0);
+        assert_eq!(bank.get_balance(&key2), 1_000);
+        assert_eq!(bank.get_balance(&key3), 5);
+
+        // Falling back to the older increment once the newest is gone
+        fs::remove_file(incremental_snapshot_archive_path(&snapshot_path, 2)).unwrap();
+        let bank = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+        assert_eq!(bank.slot(), 1);
+        assert_eq!(bank.hash_accounts(), bank1.hash_accounts());
+        assert_eq!(bank.get_balance(&key1.pubkey()), 0);
+        assert_eq!(bank.get_balance(&key3), 0);
+
+        // And to the full snapshot once there are none
+        fs::remove_file(incremental_snapshot_archive_path(&snapshot_path, 1)).unwrap();
+        let bank = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+        assert_eq!(bank.slot(), 0);
+        assert_eq!(bank.get_balance(&key1.pubkey()), 1_000);
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+
+    #[test]
+    fn test_incremental_snapshot_checks() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        bank0.freeze();
+        let bank1 = Arc::new(Bank::new_from_parent(&bank0, &Pubkey::default(), 1));
+        bank1.freeze();
+        let bank2 = Bank::new_from_parent(&bank1, &Pubkey::default(), 2);
+        bank2
+            .transfer(5, &mint_keypair, &Pubkey::new_rand())
+            .unwrap();
+        bank2.squash();
+        bank2.freeze();
+        let snapshot_path = tmp_snapshot_path("test_incremental_snapshot_checks");
+
+        // No manifest, no full snapshot to take an increment against
+        assert!(BankSnapshot::new_incremental(&bank2, 0)
+            .save(&snapshot_path)
+            .is_err());
+        assert!(restore_incremental_snapshot(&snapshot_path, None, None).is_err());
+
+        // Only against the newest full snapshot
+        save_snapshot(&bank0, &snapshot_path).unwrap();
+        save_snapshot(&bank1, &snapshot_path).unwrap();
+        assert!(BankSnapshot::new_incremental(&bank2, 0)
+            .save(&snapshot_path)
+            .is_err());
+        BankSnapshot::new_incremental(&bank2, 1)
+            .save(&snapshot_path)
+            .unwrap();
+        assert!(restore_incremental_snapshot(&snapshot_path, None, None).is_ok());
+
+        // A tampered archive is refused
+        let archive_path = incremental_snapshot_archive_path(&snapshot_path, 2);
+        let mut archive = fs::read(&archive_path).unwrap();
+        let last = archive.len() - 1;
+        archive[last] ^= 1;
+        fs::write(&archive_path, &archive).unwrap();
+        assert!(restore_incremental_snapshot(&snapshot_path, None, None).is_err());
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+    }
+
+    #[test]
+    fn test_install_snapshot() {
+        let (genesis_block, mint_keypair) = create_genesis_block(10_000);
+        let bank0 = Arc::new(Bank::new(&genesis_block));
+        bank0.freeze();
+        let snapshot_path = tmp_snapshot_path("test_install_snapshot");
+        save_snapshot(&bank0, &snapshot_path).unwrap();
+        let bank1 = Bank::new_from_parent(&bank0, &Pubkey::default(), 1);
+        let key = Pubkey::new_rand();
+        bank1.transfer(5, &mint_keypair, &key).unwrap();
+        bank1.squash();
+        bank1.freeze();
+        BankSnapshot::new_incremental(&bank1, 0)
+            .save(&snapshot_path)
+            .unwrap();
+
+        let download_path = tmp_snapshot_path("test_install_snapshot_download");
+        let bank2 = Bank::new_from_parent(&Arc::new(bank1), &Pubkey::default(), 2);
+        bank2.squash();
+        bank2.freeze();
+        let archive_path = save_snapshot(&bank2, &download_path).unwrap();
+
+        // The installed snapshot is restored over the increment of the one before
+        let installed_path = install_snapshot(&archive_path, &snapshot_path).unwrap();
+        assert_eq!(installed_path, snapshot_archive_path(&snapshot_path, 2));
+        assert!(!archive_path.exists());
+        let bank = restore_incremental_snapshot(&snapshot_path, None, None).unwrap();
+        assert_eq!(bank.slot(), 2);
+        assert_eq!(bank.get_balance(&key), 5);
+        let _ignored = fs::remove_dir_all(&snapshot_path);
+        let _ignored = fs::remove_dir_all(&download_path);
+    }
+
+    #[test]
+    fn test_snapshot_manifest_verify() {
+        let mut manifest = SnapshotManifest::new(1, hash(&[1]));
+        manifest.push(2, hash(&[2]));
+        manifest.push(3, hash(&[3]));
+        assert!(manifest.verify().is_ok());
+
+        // Swapping an archive breaks the chain
+        manifest.increments[0].archive_hash = hash(&[4]);
+        assert!(manifest.verify().is_err());
+        manifest.increments[0].archive_hash = hash(&[2]);
+
+        // As does dropping an entry from the middle
+        manifest.increments.remove(0);
+        assert!(manifest.verify().is_err());
+    }
+}
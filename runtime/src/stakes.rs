@@ -1,17 +1,26 @@
 //! Stakes serve as a cache of stake and vote accounts to derive
 //! node stakes
-use hashbrown::HashMap;
+//!
+//! Every bank starts from a clone of its parent's stakes, so the maps are shared between
+//! clones and only copied by the first store that changes them.  Accounts are kept behind
+//! their own `Arc`, so that copy only clones pointers, not every vote account's state.
 use morgan_interface::account::Account;
 use morgan_interface::pubkey::Pubkey;
 use morgan_stake_api::stake_state::StakeState;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
-#[derive(Default, Clone)]
+/// vote accounts and the stake delegated to each
+pub type VoteAccounts = BTreeMap<Pubkey, (u64, Arc<Account>)>;
+
+#[derive(Default, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Stakes {
-    /// vote accounts
-    vote_accounts: HashMap<Pubkey, (u64, Account)>,
+    /// vote accounts, ordered so every node walks them the same way
+    vote_accounts: Arc<VoteAccounts>,
 
     /// stake_accounts
-    stake_accounts: HashMap<Pubkey, Account>,
+    stake_accounts: Arc<BTreeMap<Pubkey, Arc<Account>>>,
 }
 
 impl Stakes {
@@ -33,7 +42,9 @@ impl Stakes {
     pub fn store(&mut self, pubkey: &Pubkey, account: &Account) {
         if morgan_vote_api::check_id(&account.owner) {
             if account.difs == 0 {
-                self.vote_accounts.remove(pubkey);
+                if self.vote_accounts.contains_key(pubkey) {
+                    Arc::make_mut(&mut self.vote_accounts).remove(pubkey);
+                }
             } else {
                 // update the stake of this entry
                 let stake = self
@@ -41,7 +52,8 @@ impl Stakes {
                     .get(pubkey)
                     .map_or_else(|| self.calculate_stake(pubkey), |v| v.0);
 
-                self.vote_accounts.insert(*pubkey, (stake, account.clone()));
+                Arc::make_mut(&mut self.vote_accounts)
+                    .insert(*pubkey, (stake, Arc::new(account.clone())));
             }
         } else if morgan_stake_api::check_id(&account.owner) {
             //  old_stake is stake difs and voter_pubkey from the pre-store() version
@@ -53,30 +65,43 @@ impl Stakes {
             let stake = StakeState::voter_pubkey_from(account)
                 .map(|voter_pubkey| (account.difs, voter_pubkey));
 
-            // if adjustments need to be made...
+            // if adjustments need to be made, to voters that are known...
             if stake != old_stake {
-                if let Some((old_stake, old_voter_pubkey)) = old_stake {
-                    self.vote_accounts
-                        .entry(old_voter_pubkey)
-                        .and_modify(|e| e.0 -= old_stake);
-                }
-                if let Some((stake, voter_pubkey)) = stake {
-                    self.vote_accounts
-                        .entry(voter_pubkey)
-                        .and_modify(|e| e.0 += stake);
+                let is_voter = |stake: &Option<(u64, Pubkey)>| {
+                    stake.map_or(false, |(_, voter_pubkey)| {
+                        self.vote_accounts.contains_key(&voter_pubkey)
+                    })
+                };
+                if is_voter(&old_stake) || is_voter(&stake) {
+                    let vote_accounts = Arc::make_mut(&mut self.vote_accounts);
+                    if let Some((old_stake, old_voter_pubkey)) = old_stake {
+                        vote_accounts
+                            .entry(old_voter_pubkey)
+                            .and_modify(|e| e.0 -= old_stake);
+                    }
+                    if let Some((stake, voter_pubkey)) = stake {
+                        vote_accounts
+                            .entry(voter_pubkey)
+                            .and_modify(|e| e.0 += stake);
+                    }
                 }
             }
 
             if account.difs == 0 {
-                self.stake_accounts.remove(pubkey);
+                if self.stake_accounts.contains_key(pubkey) {
+                    Arc::make_mut(&mut self.stake_accounts).remove(pubkey);
+                }
             } else {
-                self.stake_accounts.insert(*pubkey, account.clone());
+                Arc::make_mut(&mut self.stake_accounts).insert(*pubkey, Arc::new(account.clone()));
             }
         }
     }
-    pub fn vote_accounts(&self) -> &HashMap<Pubkey, (u64, Account)> {
+    pub fn vote_accounts(&self) -> &VoteAccounts {
         &self.vote_accounts
     }
+    pub fn stake_accounts(&self) -> &BTreeMap<Pubkey, Arc<Account>> {
+        &self.stake_accounts
+    }
 }
 
 #[cfg(test)]
@@ -252,4 +277,86 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_stakes_vote_accounts_ordered() {
+        let nodes: Vec<_> = (0..8).map(|i| create_staked_node_accounts(i + 1)).collect();
+
+        let mut forward = Stakes::default();
+        for ((vote_pubkey, vote_account), (stake_pubkey, stake_account)) in &nodes {
+            forward.store(vote_pubkey, vote_account);
+            forward.store(stake_pubkey, stake_account);
+        }
+        let mut reverse = Stakes::default();
+        for ((vote_pubkey, vote_account), (stake_pubkey, stake_account)) in nodes.iter().rev() {
+            reverse.store(stake_pubkey, stake_account);
+            reverse.store(vote_pubkey, vote_account);
+        }
+
+        let mut vote_pubkeys: Vec<_> = nodes.iter().map(|((pubkey, _), _)| *pubkey).collect();
+        vote_pubkeys.sort();
+        let forward: Vec<_> = forward
+            .vote_accounts()
+            .iter()
+            .map(|(pubkey, (stake, _))| (*pubkey, *stake))
+            .collect();
+        let reverse: Vec<_> = reverse
+            .vote_accounts()
+            .iter()
+            .map(|(pubkey, (stake, _))| (*pubkey, *stake))
+            .collect();
+        assert_eq!(
+            forward.iter().map(|(pubkey, _)| *pubkey).collect::<Vec<_>>(),
+            vote_pubkeys
+        );
+        assert_eq!(forward, reverse);
+    }
+
+    #[test]
+    fn test_stakes_copy_on_write() {
+        let mut stakes = Stakes::default();
+        let ((vote_pubkey, vote_account), (stake_pubkey, mut stake_account)) =
+            create_staked_node_accounts(10);
+        stakes.store(&vote_pubkey, &vote_account);
+        stakes.store(&stake_pubkey, &stake_account);
+
+        let mut child = stakes.clone();
+        assert!(Arc::ptr_eq(&stakes.vote_accounts, &child.vote_accounts));
+        assert!(Arc::ptr_eq(&stakes.stake_accounts, &child.stake_accounts));
+
+        // neither a vote nor a stake account, nothing is copied
+        let account = Account::new(1, 0, 0, &Pubkey::default());
+        child.store(&Pubkey::new_rand(), &account);
+        assert!(Arc::ptr_eq(&stakes.vote_accounts, &child.vote_accounts));
+
+        stake_account.difs = 42;
+        child.store(&stake_pubkey, &stake_account);
+        assert!(!Arc::ptr_eq(&stakes.vote_accounts, &child.vote_accounts));
+        assert_eq!(stakes.vote_accounts().get(&vote_pubkey).unwrap().0, 10);
+        assert_eq!(child.vote_accounts().get(&vote_pubkey).unwrap().0, 42);
+        // the copy shares the accounts it didn't change
+        assert!(Arc::ptr_eq(
+            &stakes.vote_accounts()[&vote_pubkey].1,
+            &child.vote_accounts()[&vote_pubkey].1
+        ));
+
+        // a stake delegated to an unknown voter leaves the vote accounts alone
+        let mut grandchild = child.clone();
+        let (other_stake_pubkey, other_stake_account) =
+            create_stake_account(10, &Pubkey::new_rand());
+        grandchild.store(&other_stake_pubkey, &other_stake_account);
+        assert!(Arc::ptr_eq(&child.vote_accounts, &grandchild.vote_accounts));
+    }
+
+    #[test]
+    fn test_stakes_serialize() {
+        let mut stakes = Stakes::default();
+        let ((vote_pubkey, vote_account), (stake_pubkey, stake_account)) =
+            create_staked_node_accounts(10);
+        stakes.store(&vote_pubkey, &vote_account);
+        stakes.store(&stake_pubkey, &stake_account);
+
+        let bytes = bincode::serialize(&stakes).unwrap();
+        let deserialized: Stakes = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(deserialized, stakes);
+    }
 }
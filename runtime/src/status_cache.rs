@@ -14,6 +14,7 @@ type SignatureSlice = [u8; CACHED_SIGNATURE_SIZE];
 type SignatureMap<T> = HashMap<SignatureSlice, ForkStatus<T>>;
 type StatusMap<T> = HashMap<Hash, (ForkId, usize, SignatureMap<T>)>;
 
+#[derive(Serialize, Deserialize)]
 pub struct StatusCache<T: Clone> {
     /// all signatures seen during a hash period
     cache: StatusMap<T>,
@@ -96,6 +97,41 @@ impl<T: Clone> StatusCache<T> {
         sig_forks.push((fork, res));
     }
 
+    /// A copy holding only the statuses of `ancestors` and of the roots, all of them rooted.
+    /// Statuses of other forks would be wrong for a bank restored at the newest ancestor, whose
+    /// descendants may reuse those forks' slots.
+    pub fn rooted_copy(&self, ancestors: &HashMap<ForkId, usize>) -> Self {
+        let is_rooted = |fork: &ForkId| ancestors.contains_key(fork) || self.roots.contains(fork);
+        let cache = self
+            .cache
+            .iter()
+            .filter_map(|(blockhash, (max_fork, index, sig_map))| {
+                let sig_map: SignatureMap<T> = sig_map
+                    .iter()
+                    .filter_map(|(sig_slice, forks)| {
+                        let forks: ForkStatus<T> = forks
+                            .iter()
+                            .filter(|(fork, _)| is_rooted(fork))
+                            .cloned()
+                            .collect();
+                        if forks.is_empty() {
+                            None
+                        } else {
+                            Some((*sig_slice, forks))
+                        }
+                    })
+                    .collect();
+                if sig_map.is_empty() {
+                    None
+                } else {
+                    Some((*blockhash, (*max_fork, *index, sig_map)))
+                }
+            })
+            .collect();
+        let roots = self.roots.iter().chain(ancestors.keys()).cloned().collect();
+        Self { cache, roots }
+    }
+
     /// Clear for testing
     pub fn clear_signatures(&mut self) {
         for v in self.cache.values_mut() {
@@ -265,4 +301,27 @@ mod tests {
         sig_slice.clone_from_slice(&sig.as_ref()[*index..*index + CACHED_SIGNATURE_SIZE]);
         assert!(sig_map.get(&sig_slice).is_some());
     }
+
+    #[test]
+    fn test_rooted_copy() {
+        let sig = Signature::default();
+        let mut status_cache = BankStatusCache::default();
+        let blockhash = hash(Hash::default().as_ref());
+        status_cache.insert(&blockhash, &sig, 0, ());
+        status_cache.insert(&blockhash, &sig, 1, ());
+        status_cache.insert(&blockhash, &sig, 2, ());
+        status_cache.add_root(0);
+
+        // fork 2 is not an ancestor of fork 1, a restored fork 2 may be a different block
+        let ancestors = vec![(1, 0)].into_iter().collect();
+        let rooted_copy = status_cache.rooted_copy(&ancestors);
+        let forks: Vec<_> = rooted_copy.cache[&blockhash]
+            .2
+            .values()
+            .flatten()
+            .map(|(fork, _)| *fork)
+            .collect();
+        assert_eq!(forks, vec![0, 1]);
+        assert!(rooted_copy.roots.contains(&1));
+    }
 }
@@ -1,12 +1,18 @@
 use log::*;
 use morgan_interface::account::KeyedAccount;
+use morgan_interface::hash::Hash;
 use morgan_interface::instruction::InstructionError;
+use morgan_interface::instruction_processor_utils::limited_deserialize;
+use morgan_interface::nonce_state::{NonceState, NONCE_ACCOUNT_INDEX};
 use morgan_interface::pubkey::Pubkey;
+use morgan_interface::syscall::slot_hashes::{self, SlotHashes};
 use morgan_interface::system_instruction::{SystemError, SystemInstruction};
 use morgan_interface::system_program;
 
 const FROM_ACCOUNT_INDEX: usize = 0;
 const TO_ACCOUNT_INDEX: usize = 1;
+const NONCE_SLOT_HASHES_INDEX: usize = 2;
+const NONCE_WITHDRAW_TO_INDEX: usize = 2;
 
 fn create_system_account(
     keyed_accounts: &mut [KeyedAccount],
@@ -120,15 +126,125 @@ fn transfer_reputations(
     Ok(())
 }
 
+/// The newest slot hash, the nonce a nonce account moves to when initialized or advanced
+fn newest_slot_hash(slot_hashes_account: &KeyedAccount) -> Result<Hash, SystemError> {
+    if !slot_hashes::check_id(slot_hashes_account.unsigned_key()) {
+        debug!("Nonce: invalid slot_hashes account");
+        Err(SystemError::InvalidSlotHashes)?;
+    }
+    SlotHashes::from(&slot_hashes_account.account)
+        .and_then(|slot_hashes| slot_hashes.first().map(|(_, hash)| *hash))
+        .ok_or(SystemError::InvalidSlotHashes)
+}
+
+/// The nonce of the account at `NONCE_ACCOUNT_INDEX`, checking the signer at
+/// `FROM_ACCOUNT_INDEX` is its authority
+fn authorized_nonce(keyed_accounts: &[KeyedAccount]) -> Result<Hash, SystemError> {
+    match NonceState::from(&keyed_accounts[NONCE_ACCOUNT_INDEX].account) {
+        Some(NonceState::Initialized(authority, nonce)) => {
+            if keyed_accounts[FROM_ACCOUNT_INDEX].signer_key() != Some(&authority) {
+                debug!("Nonce: account[from] isn't the nonce authority");
+                Err(SystemError::NonceAuthorityMismatch)?;
+            }
+            Ok(nonce)
+        }
+        _ => {
+            debug!("Nonce: account isn't an initialized nonce account");
+            Err(SystemError::InvalidNonceState)
+        }
+    }
+}
+
+fn set_nonce_state(
+    keyed_accounts: &mut [KeyedAccount],
+    state: &NonceState,
+) -> Result<(), SystemError> {
+    state
+        .to(&mut keyed_accounts[NONCE_ACCOUNT_INDEX].account)
+        .ok_or(SystemError::InvalidNonceState)
+}
+
+fn initialize_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    authority: &Pubkey,
+) -> Result<(), SystemError> {
+    if NonceState::from(&keyed_accounts[NONCE_ACCOUNT_INDEX].account)
+        != Some(NonceState::Uninitialized)
+    {
+        debug!("InitializeNonceAccount: account isn't an uninitialized nonce account");
+        Err(SystemError::InvalidNonceState)?;
+    }
+    let nonce = newest_slot_hash(&keyed_accounts[NONCE_SLOT_HASHES_INDEX])?;
+    set_nonce_state(keyed_accounts, &NonceState::Initialized(*authority, nonce))
+}
+
+fn advance_nonce_account(keyed_accounts: &mut [KeyedAccount]) -> Result<(), SystemError> {
+    let nonce = authorized_nonce(keyed_accounts)?;
+    let next_nonce = newest_slot_hash(&keyed_accounts[NONCE_SLOT_HASHES_INDEX])?;
+    if next_nonce == nonce {
+        debug!("AdvanceNonceAccount: no new slot hash since the last advance");
+        Err(SystemError::NonceNotExpired)?;
+    }
+    let authority = *keyed_accounts[FROM_ACCOUNT_INDEX].unsigned_key();
+    set_nonce_state(
+        keyed_accounts,
+        &NonceState::Initialized(authority, next_nonce),
+    )
+}
+
+fn withdraw_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    difs: u64,
+) -> Result<(), SystemError> {
+    authorized_nonce(keyed_accounts)?;
+    if difs > keyed_accounts[NONCE_ACCOUNT_INDEX].account.difs {
+        debug!(
+            "WithdrawNonceAccount: insufficient difs ({}, need {})",
+            keyed_accounts[NONCE_ACCOUNT_INDEX].account.difs, difs
+        );
+        Err(SystemError::ResultWithNegativeDifs)?;
+    }
+    keyed_accounts[NONCE_ACCOUNT_INDEX].account.difs -= difs;
+    keyed_accounts[NONCE_WITHDRAW_TO_INDEX].account.difs += difs;
+    Ok(())
+}
+
+fn authorize_nonce_account(
+    keyed_accounts: &mut [KeyedAccount],
+    authority: &Pubkey,
+) -> Result<(), SystemError> {
+    let nonce = authorized_nonce(keyed_accounts)?;
+    set_nonce_state(keyed_accounts, &NonceState::Initialized(*authority, nonce))
+}
+
+/// The number of accounts `instruction` indexes into
+fn num_accounts_required(instruction: &SystemInstruction) -> usize {
+    match instruction {
+        SystemInstruction::Assign { .. } => 1,
+        SystemInstruction::CreateAccount { .. }
+        | SystemInstruction::CreateAccountWithReputation { .. }
+        | SystemInstruction::Transfer { .. }
+        | SystemInstruction::TransferReputations { .. }
+        | SystemInstruction::AuthorizeNonceAccount { .. } => 2,
+        SystemInstruction::InitializeNonceAccount { .. }
+        | SystemInstruction::AdvanceNonceAccount
+        | SystemInstruction::WithdrawNonceAccount { .. } => 3,
+    }
+}
+
 pub fn process_instruction(
     _program_id: &Pubkey,
     keyed_accounts: &mut [KeyedAccount],
     data: &[u8],
     _tick_height: u64,
 ) -> Result<(), InstructionError> {
-    if let Ok(instruction) = bincode::deserialize(data) {
+    if let Ok(instruction) = limited_deserialize(data) {
         trace!("process_instruction: {:?}", instruction);
         trace!("keyed_accounts: {:?}", keyed_accounts);
+        if keyed_accounts.len() < num_accounts_required(&instruction) {
+            debug!("not enough accounts for {:?}", instruction);
+            Err(InstructionError::NotEnoughAccountKeys)?;
+        }
         // All system instructions require that accounts_keys[0] be a signer
         if keyed_accounts[FROM_ACCOUNT_INDEX].signer_key().is_none() {
             debug!("account[from] is unsigned");
@@ -155,6 +271,16 @@ pub fn process_instruction(
             }
             SystemInstruction::Transfer { difs } => transfer_difs(keyed_accounts, difs),
             SystemInstruction::TransferReputations { reputations } => transfer_reputations(keyed_accounts, reputations),
+            SystemInstruction::InitializeNonceAccount { authority } => {
+                initialize_nonce_account(keyed_accounts, &authority)
+            }
+            SystemInstruction::AdvanceNonceAccount => advance_nonce_account(keyed_accounts),
+            SystemInstruction::WithdrawNonceAccount { difs } => {
+                withdraw_nonce_account(keyed_accounts, difs)
+            }
+            SystemInstruction::AuthorizeNonceAccount { authority } => {
+                authorize_nonce_account(keyed_accounts, &authority)
+            }
         }
         .map_err(|e| InstructionError::CustomError(e as u32))
     } else {
@@ -172,6 +298,7 @@ mod tests {
     use morgan_interface::account::Account;
     use morgan_interface::client::SyncClient;
     use morgan_interface::genesis_block::create_genesis_block;
+    use morgan_interface::hash::hash;
     use morgan_interface::instruction::{AccountMeta, Instruction, InstructionError};
     use morgan_interface::signature::{Keypair, KeypairUtil};
     use morgan_interface::system_program;
@@ -415,6 +542,113 @@ mod tests {
         assert_eq!(to_account.difs, 1);
     }
 
+    fn create_slot_hashes_account(hash: Hash) -> Account {
+        let mut account = slot_hashes::create_account(1);
+        let mut slot_hashes = SlotHashes::from(&account).unwrap();
+        slot_hashes.add(0, hash);
+        slot_hashes.to(&mut account).unwrap();
+        account
+    }
+
+    #[test]
+    fn test_advance_nonce_account() {
+        let authority = Pubkey::new_rand();
+        let mut authority_account = Account::new(1, 0, 0, &system_program::id());
+        let nonce = Pubkey::new_rand();
+        let mut nonce_account = Account::new(100, 0, NonceState::size(), &system_program::id());
+        let mut slot_hashes_account = create_slot_hashes_account(hash(b"slot 0"));
+        let slot_hashes_id = slot_hashes::id();
+
+        let mut keyed_accounts = [
+            KeyedAccount::new(&authority, true, &mut authority_account),
+            KeyedAccount::new(&nonce, false, &mut nonce_account),
+            KeyedAccount::new(&slot_hashes_id, false, &mut slot_hashes_account),
+        ];
+        assert_eq!(
+            advance_nonce_account(&mut keyed_accounts),
+            Err(SystemError::InvalidNonceState)
+        );
+        initialize_nonce_account(&mut keyed_accounts, &authority).unwrap();
+        assert_eq!(
+            initialize_nonce_account(&mut keyed_accounts, &authority),
+            Err(SystemError::InvalidNonceState)
+        );
+
+        // the nonce only moves once there is a new slot hash
+        assert_eq!(
+            advance_nonce_account(&mut keyed_accounts),
+            Err(SystemError::NonceNotExpired)
+        );
+        *keyed_accounts[NONCE_SLOT_HASHES_INDEX].account =
+            create_slot_hashes_account(hash(b"slot 1"));
+        advance_nonce_account(&mut keyed_accounts).unwrap();
+        assert_eq!(
+            NonceState::from(&keyed_accounts[NONCE_ACCOUNT_INDEX].account),
+            Some(NonceState::Initialized(authority, hash(b"slot 1")))
+        );
+    }
+
+    #[test]
+    fn test_nonce_instruction_not_enough_accounts() {
+        let authority = Pubkey::new_rand();
+        let mut authority_account = Account::new(1, 0, 0, &system_program::id());
+        let nonce = Pubkey::new_rand();
+        let mut nonce_account = Account::new(100, 0, NonceState::size(), &system_program::id());
+        let mut keyed_accounts = [
+            KeyedAccount::new(&authority, true, &mut authority_account),
+            KeyedAccount::new(&nonce, false, &mut nonce_account),
+        ];
+        let data = serialize(&SystemInstruction::AdvanceNonceAccount).unwrap();
+        assert_eq!(
+            process_instruction(&Pubkey::default(), &mut keyed_accounts, &data, 0),
+            Err(InstructionError::NotEnoughAccountKeys)
+        );
+        assert_eq!(
+            process_instruction(&Pubkey::default(), &mut [], &data, 0),
+            Err(InstructionError::NotEnoughAccountKeys)
+        );
+    }
+
+    #[test]
+    fn test_nonce_account_authority() {
+        let authority = Pubkey::new_rand();
+        let mut authority_account = Account::new(1, 0, 0, &system_program::id());
+        let nonce = Pubkey::new_rand();
+        let mut nonce_account = Account::new(100, 0, NonceState::size(), &system_program::id());
+        NonceState::Initialized(authority, hash(b"slot 0"))
+            .to(&mut nonce_account)
+            .unwrap();
+        let mallory = Pubkey::new_rand();
+        let mut mallory_account = Account::new(1, 0, 0, &system_program::id());
+
+        let mut keyed_accounts = [
+            KeyedAccount::new(&mallory, true, &mut mallory_account),
+            KeyedAccount::new(&nonce, false, &mut nonce_account),
+        ];
+        assert_eq!(
+            authorize_nonce_account(&mut keyed_accounts, &mallory),
+            Err(SystemError::NonceAuthorityMismatch)
+        );
+
+        let mut keyed_accounts = [
+            KeyedAccount::new(&authority, true, &mut authority_account),
+            KeyedAccount::new(&nonce, false, &mut nonce_account),
+            KeyedAccount::new(&mallory, false, &mut mallory_account),
+        ];
+        withdraw_nonce_account(&mut keyed_accounts, 40).unwrap();
+        assert_eq!(
+            withdraw_nonce_account(&mut keyed_accounts, 61),
+            Err(SystemError::ResultWithNegativeDifs)
+        );
+        authorize_nonce_account(&mut keyed_accounts, &mallory).unwrap();
+        assert_eq!(
+            withdraw_nonce_account(&mut keyed_accounts, 1),
+            Err(SystemError::NonceAuthorityMismatch)
+        );
+        assert_eq!(nonce_account.difs, 60);
+        assert_eq!(mallory_account.difs, 41);
+    }
+
     #[test]
     fn test_system_unsigned_transaction() {
         let (genesis_block, alice_keypair) = create_genesis_block(100);
@@ -1,11 +1,28 @@
 use clap::{crate_description, crate_name, crate_version, App, Arg};
-use morgan_tokenbot::drone::{run_drone, Drone, DRONE_PORT};
+use morgan_client::rpc_client::RpcClient;
+use morgan_tokenbot::drone::{
+    run_drone, Drone, RateLimits, RecipientLimits, SponsorLimits, DRONE_PORT,
+    FEE_CALCULATOR_MAX_AGE,
+};
+use morgan_tokenbot::drone_store::DroneStore;
 use morgan_tokenbot::socketaddr;
+use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::read_keypair;
+use morgan_interface::timing::timestamp;
+use std::cmp;
 use std::error;
 use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
+use morgan_helper::logHelper::*;
+
+// Upper bound on how late a time slice is rolled over
+const COMPACT_INTERVAL: Duration = Duration::from_secs(5);
+/// How often the cluster's fee calculator is fetched to price sponsored messages, well within
+/// `FEE_CALCULATOR_MAX_AGE`
+const FEE_CALCULATOR_INTERVAL: Duration = Duration::from_secs(10);
 
 fn main() -> Result<(), Box<error::Error>> {
     morgan_logger::setup();
@@ -36,6 +53,130 @@ fn main() -> Result<(), Box<error::Error>> {
                 .takes_value(true)
                 .help("Request limit for time slice"),
         )
+        .arg(
+            Arg::with_name("state_file")
+                .long("state-file")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("File in which to keep request accounting across restarts"),
+        )
+        .arg(
+            Arg::with_name("recipient_cap")
+                .long("recipient-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Lifetime limit of difs granted to a single recipient without reputation"),
+        )
+        .arg(
+            Arg::with_name("recipient_reputation_cap")
+                .long("recipient-reputation-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Lifetime limit of reputations granted to a single recipient"),
+        )
+        .arg(
+            Arg::with_name("reputation_weight")
+                .long("reputation-weight")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Difs added to a recipient's lifetime limit per unit of reputation it holds"),
+        )
+        .arg(
+            Arg::with_name("reputation_half_life")
+                .long("reputation-half-life")
+                .value_name("SECS")
+                .takes_value(true)
+                .help("Time for a recipient's reputation to decay by half"),
+        )
+        .arg(
+            Arg::with_name("reputation_min_age")
+                .long("reputation-min-age")
+                .value_name("SECS")
+                .takes_value(true)
+                .help("Age a recipient needs before its reputation raises its lifetime limit"),
+        )
+        .arg(
+            Arg::with_name("new_recipient_cap")
+                .long("new-recipient-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Limit of recipients never seen before that are served per time slice"),
+        )
+        .arg(
+            Arg::with_name("new_recipient_ip_cap")
+                .long("new-recipient-ip-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Limit of recipients never seen before that are served per time slice to a single IP"),
+        )
+        .arg(
+            Arg::with_name("max_recipients")
+                .long("max-recipients")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Limit of recipients the drone keeps accounts of"),
+        )
+        .arg(
+            Arg::with_name("recipient_retention")
+                .long("recipient-retention")
+                .value_name("SECS")
+                .takes_value(true)
+                .help("Time without a grant after which a recipient is forgotten"),
+        )
+        .arg(
+            Arg::with_name("rate_limit_window")
+                .long("rate-limit-window")
+                .value_name("SECS")
+                .takes_value(true)
+                .help("Sliding window over which to limit requests per IP and per pubkey"),
+        )
+        .arg(
+            Arg::with_name("ip_request_cap")
+                .long("ip-request-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Limit of requests granted to a single IP per rate limit window"),
+        )
+        .arg(
+            Arg::with_name("pubkey_request_cap")
+                .long("pubkey-request-cap")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Limit of requests granted to a single pubkey per rate limit window"),
+        )
+        .arg(
+            Arg::with_name("sponsor_program")
+                .long("sponsor-program")
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .multiple(true)
+                .requires("json_rpc_url")
+                .help("Sponsor the fees of messages that only invoke these programs"),
+        )
+        .arg(
+            Arg::with_name("sponsor_max_fee")
+                .long("sponsor-max-fee")
+                .value_name("NUM")
+                .takes_value(true)
+                .requires("sponsor_program")
+                .help("Largest fee sponsored for a single message"),
+        )
+        .arg(
+            Arg::with_name("sponsor_max_signatures")
+                .long("sponsor-max-signatures")
+                .value_name("NUM")
+                .takes_value(true)
+                .requires("sponsor_program")
+                .help("Signatures a sponsored message may require, the drone's included, up to 255"),
+        )
+        .arg(
+            Arg::with_name("json_rpc_url")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .requires("sponsor_program")
+                .help("JSON RPC URL of the cluster, whose current fees price sponsored messages"),
+        )
         .get_matches();
 
     let mint_keypair =
@@ -54,21 +195,119 @@ fn main() -> Result<(), Box<error::Error>> {
         request_cap = None;
     }
 
+    let mut limits = RecipientLimits::default();
+    let parse_arg = |name: &str| {
+        matches.value_of(name).map(|value| {
+            value
+                .parse::<u64>()
+                .unwrap_or_else(|_| panic!("failed to parse {}", name))
+        })
+    };
+    if let Some(cap) = parse_arg("recipient_cap") {
+        limits.recipient_cap = cap;
+    }
+    if let Some(cap) = parse_arg("recipient_reputation_cap") {
+        limits.recipient_reputation_cap = cap;
+    }
+    if let Some(weight) = parse_arg("reputation_weight") {
+        limits.reputation_weight = weight;
+    }
+    if let Some(secs) = parse_arg("reputation_half_life") {
+        limits.reputation_half_life = Duration::new(secs, 0);
+    }
+    if let Some(secs) = parse_arg("reputation_min_age") {
+        limits.reputation_min_age = Duration::new(secs, 0);
+    }
+    if let Some(cap) = parse_arg("new_recipient_cap") {
+        limits.new_recipient_cap = cap;
+    }
+    if let Some(cap) = parse_arg("new_recipient_ip_cap") {
+        limits.new_recipient_ip_cap = cap;
+    }
+    if let Some(cap) = parse_arg("max_recipients") {
+        limits.max_recipients = cap as usize;
+    }
+    if let Some(secs) = parse_arg("recipient_retention") {
+        limits.recipient_retention = Duration::new(secs, 0);
+    }
+
+    let mut rate_limits = RateLimits::default();
+    if let Some(secs) = parse_arg("rate_limit_window") {
+        rate_limits.window = Duration::new(secs, 0);
+    }
+    if let Some(cap) = parse_arg("ip_request_cap") {
+        rate_limits.ip_request_cap = cap;
+    }
+    if let Some(cap) = parse_arg("pubkey_request_cap") {
+        rate_limits.pubkey_request_cap = cap;
+    }
+
+    let sponsor_limits = matches.values_of("sponsor_program").map(|program_ids| {
+        let mut sponsor_limits = SponsorLimits {
+            program_allowlist: program_ids
+                .map(|program_id| {
+                    program_id
+                        .parse::<Pubkey>()
+                        .expect("failed to parse program id")
+                })
+                .collect(),
+            ..SponsorLimits::default()
+        };
+        if let Some(fee) = parse_arg("sponsor_max_fee") {
+            sponsor_limits.max_fee = fee;
+        }
+        if let Some(signatures) = matches.value_of("sponsor_max_signatures") {
+            sponsor_limits.max_signatures = signatures
+                .parse()
+                .expect("failed to parse sponsor_max_signatures, at most 255");
+        }
+        sponsor_limits
+    });
+
     let drone_addr = socketaddr!(0, DRONE_PORT);
 
-    let drone = Arc::new(Mutex::new(Drone::new(
-        mint_keypair,
-        time_slice,
-        request_cap,
-    )));
+    let mut drone = Drone::new(mint_keypair, time_slice, request_cap);
+    drone.set_recipient_limits(limits);
+    drone.set_rate_limits(rate_limits);
+    if let Some(sponsor_limits) = sponsor_limits {
+        drone.set_sponsor_limits(sponsor_limits);
+    }
+    if let Some(path) = matches.value_of("state_file") {
+        drone.persist_to(DroneStore::new(PathBuf::from(path)))?;
+    }
+    let drone = Arc::new(Mutex::new(drone));
 
     let drone1 = drone.clone();
     thread::spawn(move || loop {
         let time = drone1.lock().unwrap().time_slice;
-        thread::sleep(time);
-        drone1.lock().unwrap().clear_request_count();
+        thread::sleep(cmp::min(time, COMPACT_INTERVAL));
+        drone1.lock().unwrap().compact(timestamp());
     });
 
+    if let Some(json_rpc_url) = matches.value_of("json_rpc_url") {
+        let rpc_client = RpcClient::new(json_rpc_url.to_string());
+        let drone = drone.clone();
+        thread::spawn(move || loop {
+            match rpc_client.get_recent_blockhash() {
+                Ok((_, fee_calculator)) => drone
+                    .lock()
+                    .unwrap()
+                    .set_fee_calculator(fee_calculator, timestamp()),
+                Err(err) => println!(
+                    "{}",
+                    Warn(
+                        format!(
+                            "failed to fetch the cluster's fees, sponsorship stops after {}ms: {:?}",
+                            FEE_CALCULATOR_MAX_AGE, err
+                        ),
+                        module_path!().to_string()
+                    )
+                ),
+            }
+            thread::sleep(FEE_CALCULATOR_INTERVAL);
+        });
+    }
+
     run_drone(drone, drone_addr, None);
     Ok(())
 }
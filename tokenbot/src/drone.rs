@@ -2,24 +2,42 @@
 //! which is the custodian of any remaining difs in a mint.
 //! The Morgan Drone builds and send airdrop transactions,
 //! checking requests against a request cap for a given time time_slice
-//! and (to come) an IP rate limit.
+//! and against per-IP and per-pubkey request caps over a sliding window.
+//! To keep the drone from being farmed with fresh keys, each recipient has a
+//! lifetime allowance, raised by the reputation it holds once it is old enough, and
+//! only so many recipients the drone has never seen are served per time slice, in
+//! total and from any one IP.  Recipients the drone hasn't granted anything for a
+//! retention period are forgotten.
+//! Accounting can be persisted with a `DroneStore` so it survives restarts.
+//! A drone can also sponsor fees: it co-signs a message a client built as its fee payer, so
+//! accounts without difs can send their first transactions.  Only messages that invoke
+//! allowlisted programs and leave the drone's account alone are sponsored, within a fee cap.
 
 use bincode::{deserialize, serialize};
 use byteorder::{ByteOrder, LittleEndian};
+use crate::drone_store::{DroneLedger, DroneStore, RecipientRecord};
 use bytes::{Bytes, BytesMut};
 use log::*;
+use morgan_client::retry_policy::RetryPolicy;
 use serde_derive::{Deserialize, Serialize};
 use morgan_metricbot::datapoint_info;
+use morgan_interface::fee_calculator::FeeCalculator;
 use morgan_interface::hash::Hash;
 use morgan_interface::message::Message;
 use morgan_interface::packet::PACKET_DATA_SIZE;
 use morgan_interface::pubkey::Pubkey;
 use morgan_interface::signature::{Keypair, KeypairUtil};
 use morgan_interface::system_instruction;
+use morgan_interface::timing::timestamp;
 use morgan_interface::transaction::Transaction;
+use std::collections::{HashMap, VecDeque};
+use std::error;
+use std::fmt;
+use std::hash::Hash as StdHash;
 use std::io;
 use std::io::{Error, ErrorKind};
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex};
 use std::thread;
@@ -27,6 +45,7 @@ use std::time::Duration;
 use tokio;
 use tokio::net::TcpListener;
 use tokio::prelude::{Future, Read, Sink, Stream, Write};
+use tokio::runtime::Runtime;
 use tokio_codec::{BytesCodec, Decoder};
 use morgan_helper::logHelper::*;
 
@@ -44,8 +63,249 @@ macro_rules! socketaddr {
 pub const TIME_SLICE: u64 = 60;
 pub const REQUEST_CAP: u64 = 100_000_000_000_000;
 pub const DRONE_PORT: u16 = 11100;
+/// Length of the sliding window the per-IP and per-pubkey request caps apply to, in seconds
+pub const RATE_LIMIT_WINDOW: u64 = 60 * 60;
+/// Requests granted to a single IP per rate limit window
+pub const IP_REQUEST_CAP: u64 = 1_000;
+/// Requests granted to a single pubkey per rate limit window
+pub const PUBKEY_REQUEST_CAP: u64 = 100;
+/// Difs a single recipient may be granted over its lifetime, before reputation
+pub const RECIPIENT_CAP: u64 = REQUEST_CAP;
+/// Reputations a single recipient may be granted over its lifetime
+pub const RECIPIENT_REPUTATION_CAP: u64 = REQUEST_CAP;
+/// Recipients the drone has never seen that it serves per time slice
+pub const NEW_RECIPIENT_CAP: u64 = 1_000;
+/// Recipients the drone has never seen that it serves per time slice to a single IP
+pub const NEW_RECIPIENT_IP_CAP: u64 = 10;
+/// Time for a recipient's reputation standing to decay by half, in seconds
+pub const REPUTATION_HALF_LIFE: u64 = 7 * 24 * 60 * 60;
+/// Age a recipient needs before its reputation standing raises its allowance, in seconds
+pub const REPUTATION_MIN_AGE: u64 = 24 * 60 * 60;
+/// Recipients the drone keeps accounts of at once
+pub const MAX_RECIPIENTS: usize = 1_000_000;
+/// Time without a grant after which a recipient is forgotten, in seconds
+pub const RECIPIENT_RETENTION: u64 = 90 * 24 * 60 * 60;
+/// Least time between two saves of the ledger on a grant, in ms; compaction saves the rest
+const LEDGER_SAVE_INTERVAL_MS: u64 = 1_000;
+/// The transaction length a reply carrying a serialized `DroneError` starts with
+const ERROR_REPLY_LENGTH: u16 = 0;
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Per-recipient limits, see `Drone::set_recipient_limits`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecipientLimits {
+    /// Lifetime dif allowance of a recipient without reputation
+    pub recipient_cap: u64,
+
+    /// Lifetime reputation allowance of a recipient
+    pub recipient_reputation_cap: u64,
+
+    /// Extra difs of lifetime allowance per unit of reputation standing
+    pub reputation_weight: u64,
+
+    /// Time for reputation standing to decay by half
+    pub reputation_half_life: Duration,
+
+    /// Age a recipient needs before its reputation standing counts, so standing can't be
+    /// farmed with fresh recipients
+    pub reputation_min_age: Duration,
+
+    /// New recipients served per time slice
+    pub new_recipient_cap: u64,
+
+    /// New recipients served per time slice to a single IP
+    pub new_recipient_ip_cap: u64,
+
+    /// Recipients kept at once, new ones are refused while the table is full
+    pub max_recipients: usize,
+
+    /// Time without a grant after which a recipient is forgotten
+    pub recipient_retention: Duration,
+}
+
+impl Default for RecipientLimits {
+    fn default() -> Self {
+        RecipientLimits {
+            recipient_cap: RECIPIENT_CAP,
+            recipient_reputation_cap: RECIPIENT_REPUTATION_CAP,
+            reputation_weight: 0,
+            reputation_half_life: Duration::new(REPUTATION_HALF_LIFE, 0),
+            reputation_min_age: Duration::new(REPUTATION_MIN_AGE, 0),
+            new_recipient_cap: NEW_RECIPIENT_CAP,
+            new_recipient_ip_cap: NEW_RECIPIENT_IP_CAP,
+            max_recipients: MAX_RECIPIENTS,
+            recipient_retention: Duration::new(RECIPIENT_RETENTION, 0),
+        }
+    }
+}
+
+/// Signatures a sponsored message may require by default, the drone's and the client's
+pub const MAX_SPONSORED_SIGNATURES: u8 = 2;
+
+/// Age in ms after which the cluster's fee calculator no longer prices sponsored messages,
+/// see `Drone::set_fee_calculator`
+pub const FEE_CALCULATOR_MAX_AGE: u64 = 60_000;
+
+/// Limits on the messages the drone pays fees for, see `Drone::set_sponsor_limits`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SponsorLimits {
+    /// Programs a sponsored message may invoke
+    pub program_allowlist: Vec<Pubkey>,
+
+    /// Signatures a sponsored message may require, the drone's included
+    pub max_signatures: u8,
+
+    /// Largest fee the drone pays for a single message, priced by the cluster's current fee
+    /// calculator
+    pub max_fee: u64,
+}
+
+impl Default for SponsorLimits {
+    fn default() -> Self {
+        SponsorLimits {
+            program_allowlist: vec![],
+            max_signatures: MAX_SPONSORED_SIGNATURES,
+            max_fee: 0,
+        }
+    }
+}
+
+/// Per-IP and per-pubkey request limits, see `Drone::set_rate_limits`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimits {
+    /// Sliding window requests are counted over
+    pub window: Duration,
+
+    /// Requests granted to a single IP per window
+    pub ip_request_cap: u64,
+
+    /// Requests granted to a single pubkey per window
+    pub pubkey_request_cap: u64,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            window: Duration::new(RATE_LIMIT_WINDOW, 0),
+            ip_request_cap: IP_REQUEST_CAP,
+            pubkey_request_cap: PUBKEY_REQUEST_CAP,
+        }
+    }
+}
+
+/// A request the drone turned down for exceeding a rate limit, carried by the `io::Error`
+/// `process_drone_request` returns and sent back to the client
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum DroneError {
+    /// The IP was granted `cap` requests within the window already
+    IpRateLimited {
+        ip: IpAddr,
+        cap: u64,
+        retry_after: Duration,
+    },
+
+    /// The pubkey was granted `cap` requests within the window already
+    PubkeyRateLimited {
+        pubkey: Pubkey,
+        cap: u64,
+        retry_after: Duration,
+    },
+}
+
+impl fmt::Display for DroneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DroneError::IpRateLimited {
+                ip,
+                cap,
+                retry_after,
+            } => write!(
+                f,
+                "ip rate limit reached; ip: {} cap: {} retry after: {}s",
+                ip,
+                cap,
+                retry_after.as_secs()
+            ),
+            DroneError::PubkeyRateLimited {
+                pubkey,
+                cap,
+                retry_after,
+            } => write!(
+                f,
+                "pubkey rate limit reached; pubkey: {} cap: {} retry after: {}s",
+                pubkey,
+                cap,
+                retry_after.as_secs()
+            ),
+        }
+    }
+}
+
+impl error::Error for DroneError {}
+
+impl From<DroneError> for io::Error {
+    fn from(err: DroneError) -> Self {
+        Error::new(ErrorKind::Other, err)
+    }
+}
+
+/// How long until another request fits under `cap` in the window of `window_ms` ending at
+/// `now`, or `None` if one fits already; `times` are the requests granted so far, oldest first
+fn retry_after(
+    times: Option<&VecDeque<u64>>,
+    cap: u64,
+    window_ms: u64,
+    now: u64,
+) -> Option<Duration> {
+    let recent: Vec<u64> = times
+        .map(|times| {
+            times
+                .iter()
+                .filter(|time| now.saturating_sub(**time) < window_ms)
+                .cloned()
+                .collect()
+        })
+        .unwrap_or_default();
+    if (recent.len() as u64) < cap {
+        return None;
+    }
+    // the request that has to fall out of the window before there is room for another
+    let expiring = recent.len() - cap as usize;
+    let retry_after_ms = match recent.get(expiring) {
+        Some(time) => (time + window_ms).saturating_sub(now),
+        None => window_ms,
+    };
+    Some(Duration::from_millis(retry_after_ms))
+}
+
+/// Forget the requests in `cache` that fell out of the window of `window_ms` ending at `now`
+fn expire_requests<K: StdHash + Eq>(
+    cache: &mut HashMap<K, VecDeque<u64>>,
+    window_ms: u64,
+    now: u64,
+) {
+    for times in cache.values_mut() {
+        while times
+            .front()
+            .map(|time| now.saturating_sub(*time) >= window_ms)
+            .unwrap_or(false)
+        {
+            times.pop_front();
+        }
+    }
+    cache.retain(|_, times| !times.is_empty());
+}
+
+/// What `reputation` standing has decayed to `elapsed_ms` after it was last updated
+pub fn decayed_reputation(reputation: u64, elapsed_ms: u64, half_life: Duration) -> u64 {
+    let half_life_ms = half_life.as_millis() as u64;
+    if half_life_ms == 0 {
+        return 0;
+    }
+    let half_lives = elapsed_ms as f64 / half_life_ms as f64;
+    (reputation as f64 * 0.5f64.powf(half_lives)) as u64
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum DroneRequest {
     GetAirdrop {
         difs: u64,
@@ -57,14 +317,40 @@ pub enum DroneRequest {
         to: Pubkey,
         blockhash: Hash,
     },
+    /// Co-sign `message` as its fee payer, the drone's pubkey first in its account keys
+    SponsorFee { message: Message },
+}
+
+impl DroneRequest {
+    /// The pubkey the request is for, the first signer after the drone for a sponsored fee
+    pub fn to(&self) -> Pubkey {
+        match self {
+            DroneRequest::GetAirdrop { to, .. } | DroneRequest::GetReputation { to, .. } => *to,
+            DroneRequest::SponsorFee { message } => {
+                message.account_keys.get(1).cloned().unwrap_or_default()
+            }
+        }
+    }
 }
 
 pub struct Drone {
     mint_keypair: Keypair,
-    ip_cache: Vec<IpAddr>,
+    ip_cache: HashMap<IpAddr, VecDeque<u64>>,
+    pubkey_cache: HashMap<Pubkey, VecDeque<u64>>,
+    rate_limits: RateLimits,
     pub time_slice: Duration,
     request_cap: u64,
     pub request_current: u64,
+    window_start: u64,
+    limits: RecipientLimits,
+    pub new_recipients_current: u64,
+    new_recipients_by_ip: HashMap<IpAddr, u64>,
+    recipients: HashMap<Pubkey, RecipientRecord>,
+    store: Option<DroneStore>,
+    ledger_saved: u64,
+    sponsor_limits: Option<SponsorLimits>,
+    /// The cluster's fee calculator and when it was fetched
+    fee_calculator: Option<(FeeCalculator, u64)>,
 }
 
 impl Drone {
@@ -83,34 +369,347 @@ impl Drone {
         };
         Drone {
             mint_keypair,
-            ip_cache: Vec::new(),
+            ip_cache: HashMap::new(),
+            pubkey_cache: HashMap::new(),
+            rate_limits: RateLimits::default(),
             time_slice,
             request_cap,
             request_current: 0,
+            window_start: timestamp(),
+            limits: RecipientLimits::default(),
+            new_recipients_current: 0,
+            new_recipients_by_ip: HashMap::new(),
+            recipients: HashMap::new(),
+            store: None,
+            ledger_saved: 0,
+            sponsor_limits: None,
+            fee_calculator: None,
+        }
+    }
+
+    pub fn set_recipient_limits(&mut self, limits: RecipientLimits) {
+        self.limits = limits;
+    }
+
+    pub fn set_rate_limits(&mut self, rate_limits: RateLimits) {
+        self.rate_limits = rate_limits;
+    }
+
+    /// Sponsor the fees of messages within `sponsor_limits`, the drone sponsors none until set
+    pub fn set_sponsor_limits(&mut self, sponsor_limits: SponsorLimits) {
+        self.sponsor_limits = Some(sponsor_limits);
+    }
+
+    /// Price sponsored messages with `fee_calculator`, the one the cluster charges fees with as
+    /// of `now`.  Fees follow congestion, so it must be refreshed within
+    /// `FEE_CALCULATOR_MAX_AGE`, or nothing is sponsored
+    pub fn set_fee_calculator(&mut self, fee_calculator: FeeCalculator, now: u64) {
+        self.fee_calculator = Some((fee_calculator, now));
+    }
+
+    /// Resume from the accounting saved in `store`, and save to it from now on
+    pub fn persist_to(&mut self, store: DroneStore) -> io::Result<()> {
+        let ledger = store.load()?;
+        if ledger.window_start != 0 {
+            self.window_start = ledger.window_start;
+            self.request_current = ledger.request_current;
+            self.ip_cache = ledger.ip_cache;
+            self.pubkey_cache = ledger.pubkey_cache;
+            self.new_recipients_current = ledger.new_recipients_current;
+            self.new_recipients_by_ip = ledger.new_recipients_by_ip;
+            self.recipients = ledger.recipients;
+        }
+        self.store = Some(store);
+        self.compact(timestamp());
+        Ok(())
+    }
+
+    /// Save the ledger if it wasn't saved within `LEDGER_SAVE_INTERVAL_MS` of `now`, so a burst
+    /// of grants doesn't rewrite it for each one
+    fn save_if_due(&mut self, now: u64) {
+        if now.saturating_sub(self.ledger_saved) >= LEDGER_SAVE_INTERVAL_MS {
+            self.save();
         }
     }
 
+    fn save(&mut self) {
+        self.ledger_saved = timestamp();
+        if let Some(store) = &self.store {
+            let ledger = DroneLedger {
+                window_start: self.window_start,
+                request_current: self.request_current,
+                ip_cache: self.ip_cache.clone(),
+                pubkey_cache: self.pubkey_cache.clone(),
+                new_recipients_current: self.new_recipients_current,
+                new_recipients_by_ip: self.new_recipients_by_ip.clone(),
+                recipients: self.recipients.clone(),
+            };
+            if let Err(err) = store.save(&ledger) {
+                println!(
+                    "{}",
+                    Warn(
+                        format!("failed to save drone ledger: {:?}", err),
+                        module_path!().to_string()
+                    )
+                );
+            }
+        }
+    }
+
+    /// Start a new time slice once the current one has run out, forget requests that fell
+    /// out of the rate limit window and recipients past retention, and save the ledger;
+    /// `now` is in ms since the epoch
+    pub fn compact(&mut self, now: u64) {
+        let time_slice_ms = self.time_slice.as_millis() as u64;
+        if now.saturating_sub(self.window_start) >= time_slice_ms {
+            self.clear_request_count();
+            self.new_recipients_current = 0;
+            self.new_recipients_by_ip.clear();
+            self.window_start = now;
+        }
+        let window_ms = self.rate_limits.window.as_millis() as u64;
+        expire_requests(&mut self.ip_cache, window_ms, now);
+        expire_requests(&mut self.pubkey_cache, window_ms, now);
+        let retention_ms = self.limits.recipient_retention.as_millis() as u64;
+        self.recipients
+            .retain(|_, record| now.saturating_sub(record.reputation_updated) < retention_ms);
+        self.save();
+    }
+
     pub fn check_request_limit(&mut self, request_amount: u64) -> bool {
         (self.request_current + request_amount) <= self.request_cap
     }
 
+    /// Fail if `ip` or `to` were granted their cap of requests within the rate limit
+    /// window ending at `now`, in ms since the epoch
+    pub fn check_rate_limits(&self, ip: IpAddr, to: &Pubkey, now: u64) -> Result<(), DroneError> {
+        let window_ms = self.rate_limits.window.as_millis() as u64;
+        let cap = self.rate_limits.ip_request_cap;
+        if let Some(retry_after) = retry_after(self.ip_cache.get(&ip), cap, window_ms, now) {
+            datapoint_info!("drone-ip-rate-limited", ("count", 1, i64));
+            return Err(DroneError::IpRateLimited {
+                ip,
+                cap,
+                retry_after,
+            });
+        }
+        let cap = self.rate_limits.pubkey_request_cap;
+        if let Some(retry_after) = retry_after(self.pubkey_cache.get(to), cap, window_ms, now) {
+            datapoint_info!("drone-pubkey-rate-limited", ("count", 1, i64));
+            return Err(DroneError::PubkeyRateLimited {
+                pubkey: *to,
+                cap,
+                retry_after,
+            });
+        }
+        Ok(())
+    }
+
+    /// Count a request granted at `now` against the rate limits of `ip` and `to`
+    pub fn record_request(&mut self, ip: IpAddr, to: &Pubkey, now: u64) {
+        self.ip_cache.entry(ip).or_default().push_back(now);
+        self.pubkey_cache.entry(*to).or_default().push_back(now);
+    }
+
+    /// The record of `to` once `difs` and `reputations` are charged to its lifetime allowance,
+    /// failing if either allowance would be exceeded, or if `to` is new and the new recipient
+    /// caps for the time slice have been reached or the recipient table is full
+    fn check_recipient(
+        &self,
+        ip: IpAddr,
+        to: &Pubkey,
+        difs: u64,
+        reputations: u64,
+        now: u64,
+    ) -> Result<RecipientRecord, io::Error> {
+        let record = match self.recipients.get(to) {
+            Some(record) => record.clone(),
+            None => {
+                if self.new_recipients_current >= self.limits.new_recipient_cap {
+                    datapoint_info!("drone-new-recipient-throttled", ("count", 1, i64));
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "new recipient limit reached; current: {} cap: {}",
+                            self.new_recipients_current, self.limits.new_recipient_cap
+                        ),
+                    ));
+                }
+                let ip_current = self.new_recipients_by_ip.get(&ip).cloned().unwrap_or(0);
+                if ip_current >= self.limits.new_recipient_ip_cap {
+                    datapoint_info!("drone-new-recipient-ip-throttled", ("count", 1, i64));
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "new recipient limit reached for {}; current: {} cap: {}",
+                            ip, ip_current, self.limits.new_recipient_ip_cap
+                        ),
+                    ));
+                }
+                if self.recipients.len() >= self.limits.max_recipients {
+                    datapoint_info!("drone-recipients-full", ("count", 1, i64));
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        format!("recipient table full; cap: {}", self.limits.max_recipients),
+                    ));
+                }
+                RecipientRecord {
+                    first_seen: now,
+                    ..RecipientRecord::default()
+                }
+            }
+        };
+
+        let reputation = decayed_reputation(
+            record.reputation,
+            now.saturating_sub(record.reputation_updated),
+            self.limits.reputation_half_life,
+        );
+        let min_age_ms = self.limits.reputation_min_age.as_millis() as u64;
+        let standing = if now.saturating_sub(record.first_seen) >= min_age_ms {
+            reputation
+        } else {
+            0
+        };
+        let difs_allowance = self
+            .limits
+            .recipient_cap
+            .saturating_add(standing.saturating_mul(self.limits.reputation_weight));
+        if record.difs.saturating_add(difs) > difs_allowance {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "recipient limit reached; req: {} granted: {} allowance: {}",
+                    difs, record.difs, difs_allowance
+                ),
+            ));
+        }
+        if record.reputations.saturating_add(reputations) > self.limits.recipient_reputation_cap {
+            return Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "recipient reputation limit reached; req: {} granted: {} cap: {}",
+                    reputations, record.reputations, self.limits.recipient_reputation_cap
+                ),
+            ));
+        }
+
+        Ok(RecipientRecord {
+            difs: record.difs + difs,
+            reputations: record.reputations + reputations,
+            reputation: reputation.saturating_add(reputations),
+            reputation_updated: now,
+            first_seen: record.first_seen,
+        })
+    }
+
+    /// Charge `difs` and `reputations` to the lifetime allowance of `to`, requested from `ip`,
+    /// failing without charging anything if `check_recipient` does
+    pub fn charge_recipient(
+        &mut self,
+        ip: IpAddr,
+        to: &Pubkey,
+        difs: u64,
+        reputations: u64,
+    ) -> Result<(), io::Error> {
+        let record = self.check_recipient(ip, to, difs, reputations, timestamp())?;
+        self.commit_recipient(ip, to, record);
+        Ok(())
+    }
+
+    /// Store the `record` `check_recipient` returned for `to`, counting `to` against the new
+    /// recipient caps if it wasn't known
+    fn commit_recipient(&mut self, ip: IpAddr, to: &Pubkey, record: RecipientRecord) {
+        if !self.recipients.contains_key(to) {
+            self.new_recipients_current += 1;
+            *self.new_recipients_by_ip.entry(ip).or_insert(0) += 1;
+        }
+        self.recipients.insert(*to, record);
+    }
+
     pub fn clear_request_count(&mut self) {
         self.request_current = 0;
     }
 
     pub fn add_ip_to_cache(&mut self, ip: IpAddr) {
-        self.ip_cache.push(ip);
+        self.ip_cache.entry(ip).or_default().push_back(timestamp());
     }
 
     pub fn clear_ip_cache(&mut self) {
         self.ip_cache.clear();
     }
 
+    /// The fee the drone pays for `message` at `now`, failing unless the message is within the
+    /// sponsor limits: the drone pays its fee and nothing else, and only allowlisted programs run
+    pub fn check_sponsored_message(&self, message: &Message, now: u64) -> Result<u64, io::Error> {
+        let reject = |reason: String| {
+            datapoint_info!("drone-fee-sponsorship-rejected", ("count", 1, i64));
+            Err(Error::new(
+                ErrorKind::Other,
+                format!("fee sponsorship rejected; {}", reason),
+            ))
+        };
+        let limits = match &self.sponsor_limits {
+            Some(limits) => limits,
+            None => return reject("drone doesn't sponsor fees".to_string()),
+        };
+        let fee_calculator = match &self.fee_calculator {
+            Some((fee_calculator, fetched))
+                if now.saturating_sub(*fetched) <= FEE_CALCULATOR_MAX_AGE =>
+            {
+                fee_calculator
+            }
+            _ => return reject("cluster fee rate unknown".to_string()),
+        };
+        let drone_pubkey = self.mint_keypair.pubkey();
+        if message.account_keys.first() != Some(&drone_pubkey) {
+            return reject("drone isn't the fee payer".to_string());
+        }
+        let num_signatures = message.header.num_required_signatures;
+        if num_signatures < 2 || num_signatures > limits.max_signatures {
+            return reject(format!(
+                "signatures: {} cap: {}",
+                num_signatures, limits.max_signatures
+            ));
+        }
+        if !message.address_table_lookups.is_empty() {
+            return reject("address table lookups".to_string());
+        }
+        if message.account_keys[1..].contains(&drone_pubkey) {
+            return reject("drone account repeated".to_string());
+        }
+        for instruction in &message.instructions {
+            let program_id = match message
+                .account_keys
+                .get(instruction.program_ids_index as usize)
+            {
+                Some(program_id) => program_id,
+                None => return reject("program id out of bounds".to_string()),
+            };
+            if !limits.program_allowlist.contains(program_id) {
+                return reject(format!("program {} not allowed", program_id));
+            }
+            // the drone's signature authorizes the fee only
+            if instruction.accounts.contains(&0) {
+                return reject("instruction uses the drone account".to_string());
+            }
+        }
+        let fee = fee_calculator.calculate_fee(message);
+        if fee > limits.max_fee {
+            return reject(format!("fee: {} cap: {}", fee, limits.max_fee));
+        }
+        Ok(fee)
+    }
+
+    /// Build the transaction `req` asks for on behalf of `ip`, charging the recipient only once
+    /// the transaction is built
     pub fn build_airdrop_transaction(
         &mut self,
         req: DroneRequest,
+        ip: IpAddr,
     ) -> Result<Transaction, io::Error> {
         trace!("build_airdrop_transaction: {:?}", req);
+        let now = timestamp();
         match req {
             DroneRequest::GetAirdrop {
                 difs,
@@ -118,11 +717,11 @@ impl Drone {
                 blockhash,
             } => {
                 if self.check_request_limit(difs) {
-                    self.request_current += difs;
+                    let record = self.check_recipient(ip, &to, difs, 0, now)?;
                     datapoint_info!(
                         "drone-airdrop",
                         ("request_amount", difs, i64),
-                        ("request_current", self.request_current, i64)
+                        ("request_current", self.request_current + difs, i64)
                     );
                     // info!("{}", Info(format!("Requesting airdrop of {} to {:?}", difs, to).to_string()));
                     println!("{}",
@@ -137,7 +736,10 @@ impl Drone {
                         difs,
                     );
                     let message = Message::new(vec![create_instruction]);
-                    Ok(Transaction::new(&[&self.mint_keypair], message, blockhash))
+                    let tx = Transaction::new(&[&self.mint_keypair], message, blockhash);
+                    self.commit_recipient(ip, &to, record);
+                    self.request_current += difs;
+                    Ok(tx)
                 } else {
                     Err(Error::new(
                         ErrorKind::Other,
@@ -154,11 +756,11 @@ impl Drone {
                 blockhash,
             } => {
                 if self.check_request_limit(reputations) {
-                    self.request_current += reputations;
+                    let record = self.check_recipient(ip, &to, 0, reputations, now)?;
                     datapoint_info!(
-                        "drone-airdrop",
+                        "drone-reputation-airdrop",
                         ("request_amount", reputations, i64),
-                        ("request_current", self.request_current, i64)
+                        ("request_current", self.request_current + reputations, i64)
                     );
                     // info!("{}", Info(format!("Requesting reputation airdrop of {} to {:?}", reputations, to).to_string()));
                     println!("{}",
@@ -173,7 +775,10 @@ impl Drone {
                         reputations,
                     );
                     let message = Message::new(vec![create_instruction]);
-                    Ok(Transaction::new(&[&self.mint_keypair], message, blockhash))
+                    let tx = Transaction::new(&[&self.mint_keypair], message, blockhash);
+                    self.commit_recipient(ip, &to, record);
+                    self.request_current += reputations;
+                    Ok(tx)
                 } else {
                     Err(Error::new(
                         ErrorKind::Other,
@@ -184,9 +789,47 @@ impl Drone {
                     ))
                 }
             }
+            DroneRequest::SponsorFee { message } => {
+                let fee = self.check_sponsored_message(&message, now)?;
+                if self.check_request_limit(fee) {
+                    let to = message.account_keys[1];
+                    let record = self.check_recipient(ip, &to, fee, 0, now)?;
+                    datapoint_info!(
+                        "drone-fee-sponsorship",
+                        ("request_amount", fee, i64),
+                        ("request_current", self.request_current + fee, i64)
+                    );
+                    println!("{}",
+                        printLn(
+                            format!("Sponsoring fee of {} for {:?}", fee, to).to_string(),
+                            module_path!().to_string()
+                        )
+                    );
+                    let blockhash = message.recent_blockhash;
+                    let mut tx = Transaction::new_unsigned(message);
+                    tx.partial_sign(&[&self.mint_keypair], blockhash);
+                    self.commit_recipient(ip, &to, record);
+                    self.request_current += fee;
+                    Ok(tx)
+                } else {
+                    Err(Error::new(
+                        ErrorKind::Other,
+                        format!(
+                            "token limit reached; req: {} current: {} cap: {}",
+                            fee, self.request_current, self.request_cap
+                        ),
+                    ))
+                }
+            }
         }
     }
-    pub fn process_drone_request(&mut self, bytes: &BytesMut) -> Result<Bytes, io::Error> {
+    /// Build the transaction `bytes` ask for on behalf of `ip`, failing with a `DroneError`
+    /// if `ip` or the recipient are over their rate limits
+    pub fn process_drone_request(
+        &mut self,
+        bytes: &BytesMut,
+        ip: IpAddr,
+    ) -> Result<Bytes, io::Error> {
         let req: DroneRequest = deserialize(bytes).or_else(|err| {
             Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -201,9 +844,15 @@ impl Drone {
                 module_path!().to_string()
             )
         );
-        let res = self.build_airdrop_transaction(req);
+        let now = timestamp();
+        let to = req.to();
+        let res = self
+            .check_rate_limits(ip, &to, now)
+            .map_err(io::Error::from)
+            .and_then(|_| self.build_airdrop_transaction(req, ip));
         match res {
             Ok(tx) => {
+                self.record_request(ip, &to, now);
                 let response_vec = bincode::serialize(&tx).or_else(|err| {
                     Err(io::Error::new(
                         io::ErrorKind::Other,
@@ -216,6 +865,7 @@ impl Drone {
                 response_vec_with_length.extend_from_slice(&response_vec);
 
                 let response_bytes = Bytes::from(response_vec_with_length);
+                self.save_if_due(now);
                 // info!("{}", Info(format!("Airdrop transaction granted").to_string()));
                 println!("{}",
                     printLn(
@@ -241,6 +891,7 @@ impl Drone {
 
 impl Drop for Drone {
     fn drop(&mut self) {
+        self.save();
         morgan_metricbot::flush();
     }
 }
@@ -263,71 +914,32 @@ pub fn request_airdrop_transaction(
             module_path!().to_string()
         )
     );
-    // TODO: make this async tokio client
-    let mut stream = TcpStream::connect_timeout(drone_addr, Duration::new(3, 0))?;
-    stream.set_read_timeout(Some(Duration::new(10, 0)))?;
     let req = DroneRequest::GetAirdrop {
         difs,
         blockhash,
         to: *id,
     };
-    let req = serialize(&req).expect("serialize drone request");
-    stream.write_all(&req)?;
-
-    // Read length of transaction
-    let mut buffer = [0; 2];
-    stream.read_exact(&mut buffer).or_else(|err| {
-        // info!(
-        //     "{}",
-        //     Info(format!("request_airdrop_transaction: buffer length read_exact error: {:?}",
-        //     err).to_string())
-        // );
-        println!("{}",
-            printLn(
-                format!("request_airdrop_transaction: buffer length read_exact error: {:?}",
-                    err).to_string(),
-                module_path!().to_string()
-            )
-        );
-        Err(Error::new(ErrorKind::Other, "Airdrop failed"))
-    })?;
-    let transaction_length = LittleEndian::read_u16(&buffer) as usize;
-    if transaction_length >= PACKET_DATA_SIZE {
-        Err(Error::new(
-            ErrorKind::Other,
-            format!(
-                "request_airdrop_transaction: invalid transaction_length from drone: {}",
-                transaction_length
-            ),
-        ))?;
-    }
+    request_drone_transaction(drone_addr, &req, "request_airdrop_transaction")
+}
 
-    // Read the transaction
-    let mut buffer = Vec::new();
-    buffer.resize(transaction_length, 0);
-    stream.read_exact(&mut buffer).or_else(|err| {
-        // info!(
-        //     "{}",
-        //     Info(format!("request_airdrop_transaction: buffer read_exact error: {:?}",
-        //     err).to_string())
-        // );
-        println!("{}",
-            printLn(
-                format!("request_airdrop_transaction: buffer read_exact error: {:?}",
-                    err).to_string(),
-                module_path!().to_string()
+/// Request an airdrop transaction, retrying failed requests as allowed by `policy`
+pub fn request_airdrop_transaction_with_retry(
+    drone_addr: &SocketAddr,
+    id: &Pubkey,
+    difs: u64,
+    blockhash: Hash,
+    policy: &RetryPolicy,
+) -> Result<Transaction, Error> {
+    policy
+        .retry_if(is_retryable, || {
+            request_airdrop_transaction(drone_addr, id, difs, blockhash)
+        })
+        .map_err(|err| {
+            Error::new(
+                ErrorKind::Other,
+                format!("request_airdrop_transaction to {} {}", drone_addr, err),
             )
-        );
-        Err(Error::new(ErrorKind::Other, "Airdrop failed"))
-    })?;
-
-    let transaction: Transaction = deserialize(&buffer).or_else(|err| {
-        Err(Error::new(
-            ErrorKind::Other,
-            format!("request_airdrop_transaction deserialize failure: {:?}", err),
-        ))
-    })?;
-    Ok(transaction)
+        })
 }
 
 pub fn request_reputation_airdrop_transaction(
@@ -348,15 +960,57 @@ pub fn request_reputation_airdrop_transaction(
             module_path!().to_string()
         )
     );
-    // TODO: make this async tokio client
-    let mut stream = TcpStream::connect_timeout(drone_addr, Duration::new(3, 0))?;
-    stream.set_read_timeout(Some(Duration::new(10, 0)))?;
     let req = DroneRequest::GetReputation {
         reputations,
         blockhash,
         to: *id,
     };
-    let req = serialize(&req).expect("serialize drone request");
+    request_drone_transaction(drone_addr, &req, "request_reputation_airdrop_transaction")
+}
+
+/// Request a reputation airdrop transaction, retrying failed requests as allowed by `policy`
+pub fn request_reputation_airdrop_transaction_with_retry(
+    drone_addr: &SocketAddr,
+    id: &Pubkey,
+    reputations: u64,
+    blockhash: Hash,
+    policy: &RetryPolicy,
+) -> Result<Transaction, Error> {
+    policy
+        .retry_if(is_retryable, || {
+            request_reputation_airdrop_transaction(drone_addr, id, reputations, blockhash)
+        })
+        .map_err(|err| {
+            Error::new(
+                ErrorKind::Other,
+                format!("request_reputation_airdrop_transaction to {} {}", drone_addr, err),
+            )
+        })
+}
+
+/// Have the drone at `drone_addr` pay the fee of `message`, its pubkey the first account key.
+/// The transaction comes back signed by the drone only, the other signers still have to sign it
+pub fn request_fee_sponsorship(
+    drone_addr: &SocketAddr,
+    message: &Message,
+) -> Result<Transaction, Error> {
+    let req = DroneRequest::SponsorFee {
+        message: message.clone(),
+    };
+    request_drone_transaction(drone_addr, &req, "request_fee_sponsorship")
+}
+
+/// Send `req` to the drone at `drone_addr` and read back the length-prefixed transaction it
+/// grants. `caller` names the request in log and error messages.
+fn request_drone_transaction(
+    drone_addr: &SocketAddr,
+    req: &DroneRequest,
+    caller: &str,
+) -> Result<Transaction, Error> {
+    // TODO: make this async tokio client
+    let mut stream = TcpStream::connect_timeout(drone_addr, Duration::new(3, 0))?;
+    stream.set_read_timeout(Some(Duration::new(10, 0)))?;
+    let req = serialize(req).expect("serialize drone request");
     stream.write_all(&req)?;
 
     // Read length of transaction
@@ -364,25 +1018,29 @@ pub fn request_reputation_airdrop_transaction(
     stream.read_exact(&mut buffer).or_else(|err| {
         // info!(
         //     "{}",
-        //     Info(format!("request_reputation_airdrop_transaction: buffer length read_exact error: {:?}",
-        //     err).to_string())
+        //     Info(format!("{}: buffer length read_exact error: {:?}",
+        //     caller, err).to_string())
         // );
         println!("{}",
             printLn(
-                format!("request_reputation_airdrop_transaction: buffer length read_exact error: {:?}",
-                    err).to_string(),
+                format!("{}: buffer length read_exact error: {:?}",
+                    caller, err).to_string(),
                 module_path!().to_string()
             )
         );
         Err(Error::new(ErrorKind::Other, "Airdrop failed"))
     })?;
-    let transaction_length = LittleEndian::read_u16(&buffer) as usize;
+    let transaction_length = LittleEndian::read_u16(&buffer);
+    if transaction_length == ERROR_REPLY_LENGTH {
+        Err(read_drone_error(&mut stream, caller)?)?;
+    }
+    let transaction_length = transaction_length as usize;
     if transaction_length >= PACKET_DATA_SIZE {
         Err(Error::new(
             ErrorKind::Other,
             format!(
-                "request_reputation_airdrop_transaction: invalid transaction_length from drone: {}",
-                transaction_length
+                "{}: invalid transaction_length from drone: {}",
+                caller, transaction_length
             ),
         ))?;
     }
@@ -393,13 +1051,13 @@ pub fn request_reputation_airdrop_transaction(
     stream.read_exact(&mut buffer).or_else(|err| {
         // info!(
         //     "{}",
-        //     Info(format!("request_reputation_airdrop_transaction: buffer read_exact error: {:?}",
-        //     err).to_string())
+        //     Info(format!("{}: buffer read_exact error: {:?}",
+        //     caller, err).to_string())
         // );
         println!("{}",
             printLn(
-                format!("request_reputation_airdrop_transaction: buffer read_exact error: {:?}",
-                    err).to_string(),
+                format!("{}: buffer read_exact error: {:?}",
+                    caller, err).to_string(),
                 module_path!().to_string()
             )
         );
@@ -409,12 +1067,58 @@ pub fn request_reputation_airdrop_transaction(
     let transaction: Transaction = deserialize(&buffer).or_else(|err| {
         Err(Error::new(
             ErrorKind::Other,
-            format!("request_reputation_airdrop_transaction deserialize failure: {:?}", err),
+            format!("{} deserialize failure: {:?}", caller, err),
         ))
     })?;
     Ok(transaction)
 }
 
+/// Read the `DroneError` that follows `ERROR_REPLY_LENGTH` in a reply
+fn read_drone_error(stream: &mut TcpStream, caller: &str) -> Result<DroneError, Error> {
+    let read_err = |err| {
+        Error::new(
+            ErrorKind::Other,
+            format!("{}: drone error read failure: {:?}", caller, err),
+        )
+    };
+    let mut buffer = [0; 2];
+    stream.read_exact(&mut buffer).map_err(read_err)?;
+    let mut buffer = vec![0; LittleEndian::read_u16(&buffer) as usize];
+    stream.read_exact(&mut buffer).map_err(read_err)?;
+    deserialize(&buffer).map_err(|err| {
+        Error::new(
+            ErrorKind::Other,
+            format!("{}: drone error deserialize failure: {:?}", caller, err),
+        )
+    })
+}
+
+/// Whether a failed drone request is worth retrying, a rate limit won't lift before the
+/// retry policy gives up
+fn is_retryable(err: &Error) -> bool {
+    err.get_ref()
+        .and_then(|err| err.downcast_ref::<DroneError>())
+        .is_none()
+}
+
+/// The reply to a request `process_drone_request` failed with `err`: a `DroneError`
+/// serialized after `ERROR_REPLY_LENGTH`, or nothing for any other error
+fn error_reply(err: &io::Error) -> Bytes {
+    let drone_error = match err
+        .get_ref()
+        .and_then(|err| err.downcast_ref::<DroneError>())
+    {
+        Some(drone_error) => drone_error,
+        None => return Bytes::new(),
+    };
+    let error_vec = serialize(drone_error).expect("serialize drone error");
+    let mut reply = vec![0; 4];
+    LittleEndian::write_u16(&mut reply[..2], ERROR_REPLY_LENGTH);
+    LittleEndian::write_u16(&mut reply[2..], error_vec.len() as u16);
+    reply.extend_from_slice(&error_vec);
+    Bytes::from(reply)
+}
+
 // For integration tests. Listens on random open port and reports port to Sender.
 pub fn run_local_drone(
     mint_keypair: Keypair,
@@ -437,6 +1141,29 @@ pub fn run_drone(
     drone_addr: SocketAddr,
     send_addr: Option<Sender<SocketAddr>>,
 ) {
+    tokio::run(bind_drone(drone, drone_addr, send_addr));
+}
+
+/// Like `run_drone`, but returns once `exit` is set, closing the drone's socket
+pub fn run_drone_until_exit(
+    drone: Arc<Mutex<Drone>>,
+    drone_addr: SocketAddr,
+    send_addr: Option<Sender<SocketAddr>>,
+    exit: &AtomicBool,
+) {
+    let mut runtime = Runtime::new().unwrap();
+    runtime.spawn(bind_drone(drone, drone_addr, send_addr));
+    while !exit.load(Ordering::Relaxed) {
+        thread::sleep(Duration::from_millis(100));
+    }
+    runtime.shutdown_now().wait().unwrap();
+}
+
+fn bind_drone(
+    drone: Arc<Mutex<Drone>>,
+    drone_addr: SocketAddr,
+    send_addr: Option<Sender<SocketAddr>>,
+) -> impl Future<Item = (), Error = ()> {
     let socket = TcpListener::bind(&drone_addr).unwrap();
     if send_addr.is_some() {
         send_addr
@@ -451,16 +1178,21 @@ pub fn run_drone(
             module_path!().to_string()
         )
     );
-    let done = socket
+    socket
         .incoming()
         .map_err(|e| debug!("failed to accept socket; error = {:?}", e))
         .for_each(move |socket| {
+            // peers without an address share a single rate limit
+            let ip = socket
+                .peer_addr()
+                .map(|peer_addr| peer_addr.ip())
+                .unwrap_or_else(|_| IpAddr::V4(Ipv4Addr::UNSPECIFIED));
             let drone2 = drone.clone();
             let framed = BytesCodec::new().framed(socket);
             let (writer, reader) = framed.split();
 
             let processor = reader.and_then(move |bytes| {
-                match drone2.lock().unwrap().process_drone_request(&bytes) {
+                match drone2.lock().unwrap().process_drone_request(&bytes, ip) {
                     Ok(response_bytes) => {
                         trace!("Airdrop response_bytes: {:?}", response_bytes.to_vec());
                         Ok(response_bytes)
@@ -473,7 +1205,7 @@ pub fn run_drone(
                                 module_path!().to_string()
                             )
                         );
-                        Ok(Bytes::from(&b""[..]))
+                        Ok(error_reply(&e))
                     }
                 }
             });
@@ -486,15 +1218,16 @@ pub fn run_drone(
                 }))
                 .then(|_| Ok(()));
             tokio::spawn(server)
-        });
-    tokio::run(done);
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use bytes::BufMut;
+    use morgan_interface::instruction::{AccountMeta, Instruction};
     use morgan_interface::system_instruction::SystemInstruction;
+    use morgan_interface::system_program;
     use std::time::Duration;
 
     #[test]
@@ -524,7 +1257,7 @@ mod tests {
         assert_eq!(drone.ip_cache.len(), 0);
         drone.add_ip_to_cache(ip);
         assert_eq!(drone.ip_cache.len(), 1);
-        assert!(drone.ip_cache.contains(&ip));
+        assert!(drone.ip_cache.contains_key(&ip));
     }
 
     #[test]
@@ -540,6 +1273,305 @@ mod tests {
         assert!(drone.ip_cache.is_empty());
     }
 
+    #[test]
+    fn test_compact() {
+        let keypair = Keypair::new();
+        let mut drone = Drone::new(keypair, Some(1), None);
+        let now = drone.window_start;
+        drone.request_current = 256;
+        let ip = "127.0.0.1".parse().expect("create IpAddr from string");
+        drone.ip_cache.insert(ip, vec![now, now + 1000].into());
+
+        drone.compact(now + 999);
+        assert_eq!(drone.request_current, 256);
+        drone.compact(now + 1000);
+        assert_eq!(drone.request_current, 0);
+        assert_eq!(drone.window_start, now + 1000);
+        assert!(drone.ip_cache.contains_key(&ip));
+
+        drone.compact(now + RATE_LIMIT_WINDOW * 1000);
+        assert_eq!(drone.ip_cache[&ip].len(), 1);
+        drone.compact(now + RATE_LIMIT_WINDOW * 1000 + 1000);
+        assert!(drone.ip_cache.is_empty());
+    }
+
+    #[test]
+    fn test_check_rate_limits() {
+        let mut drone = Drone::new(Keypair::new(), None, None);
+        drone.set_rate_limits(RateLimits {
+            window: Duration::new(10, 0),
+            ip_request_cap: 3,
+            pubkey_request_cap: 2,
+        });
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+        let to = Pubkey::new_rand();
+        let now = 1_000_000;
+
+        drone.record_request(ip, &to, now);
+        drone.check_rate_limits(ip, &to, now).unwrap();
+        drone.record_request(ip, &to, now + 1_000);
+        assert_eq!(
+            drone.check_rate_limits(ip, &to, now + 2_000),
+            Err(DroneError::PubkeyRateLimited {
+                pubkey: to,
+                cap: 2,
+                retry_after: Duration::from_millis(8_000),
+            })
+        );
+
+        // other recipients at the same IP are served up to the IP's cap
+        let other = Pubkey::new_rand();
+        drone.check_rate_limits(ip, &other, now + 2_000).unwrap();
+        drone.record_request(ip, &other, now + 2_000);
+        assert_eq!(
+            drone.check_rate_limits(ip, &Pubkey::new_rand(), now + 2_000),
+            Err(DroneError::IpRateLimited {
+                ip,
+                cap: 3,
+                retry_after: Duration::from_millis(8_000),
+            })
+        );
+        drone
+            .check_rate_limits("127.0.0.2".parse().unwrap(), &other, now + 2_000)
+            .unwrap();
+
+        // the window slides, the oldest request no longer counts
+        drone.check_rate_limits(ip, &to, now + 10_000).unwrap();
+    }
+
+    #[test]
+    fn test_process_drone_request_rate_limited() {
+        let mut drone = Drone::new(Keypair::new(), None, None);
+        drone.set_rate_limits(RateLimits {
+            pubkey_request_cap: 1,
+            ..RateLimits::default()
+        });
+        let req = DroneRequest::GetAirdrop {
+            difs: 1,
+            to: Pubkey::new_rand(),
+            blockhash: Hash::default(),
+        };
+        let req = serialize(&req).unwrap();
+        let mut bytes = BytesMut::with_capacity(req.len());
+        bytes.put(&req[..]);
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+
+        drone.process_drone_request(&bytes, ip).unwrap();
+        let err = drone.process_drone_request(&bytes, ip).unwrap_err();
+        match err
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<DroneError>())
+        {
+            Some(DroneError::PubkeyRateLimited { cap, .. }) => assert_eq!(*cap, 1),
+            _ => panic!("unexpected error {:?}", err),
+        }
+        assert_eq!(drone.request_current, 1);
+    }
+
+    #[test]
+    fn test_charge_recipient_lifetime_cap() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let mut drone = Drone::new(Keypair::new(), None, None);
+        drone.set_recipient_limits(RecipientLimits {
+            recipient_cap: 10,
+            recipient_reputation_cap: 2,
+            ..RecipientLimits::default()
+        });
+        let to = Pubkey::new_rand();
+        drone.charge_recipient(ip, &to, 6, 0).unwrap();
+        assert!(drone.charge_recipient(ip, &to, 5, 0).is_err());
+        drone.charge_recipient(ip, &to, 4, 0).unwrap();
+        assert!(drone.charge_recipient(ip, &to, 1, 0).is_err());
+
+        // the cap is per recipient and outlasts the time slice
+        drone.compact(drone.window_start + drone.time_slice.as_millis() as u64);
+        assert!(drone.charge_recipient(ip, &to, 1, 0).is_err());
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 10, 0)
+            .unwrap();
+
+        drone.charge_recipient(ip, &to, 0, 2).unwrap();
+        assert!(drone.charge_recipient(ip, &to, 0, 1).is_err());
+        assert_eq!(drone.recipients[&to].difs, 10);
+        assert_eq!(drone.recipients[&to].reputations, 2);
+    }
+
+    #[test]
+    fn test_charge_recipient_reputation_weight() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let mut drone = Drone::new(Keypair::new(), None, None);
+        drone.set_recipient_limits(RecipientLimits {
+            recipient_cap: 10,
+            reputation_weight: 5,
+            reputation_min_age: Duration::new(0, 0),
+            ..RecipientLimits::default()
+        });
+        let to = Pubkey::new_rand();
+        drone.charge_recipient(ip, &to, 10, 0).unwrap();
+        assert!(drone.charge_recipient(ip, &to, 1, 0).is_err());
+        drone.charge_recipient(ip, &to, 0, 2).unwrap();
+        drone.charge_recipient(ip, &to, 10, 0).unwrap();
+        assert!(drone.charge_recipient(ip, &to, 1, 0).is_err());
+
+        // once the standing has decayed the allowance is back to the cap, which the
+        // recipient is already over
+        drone.recipients.get_mut(&to).unwrap().reputation_updated = 0;
+        let err = drone.charge_recipient(ip, &to, 0, 0).unwrap_err();
+        assert!(err.to_string().contains("allowance: 10"));
+    }
+
+    #[test]
+    fn test_charge_recipient_reputation_min_age() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let mut drone = Drone::new(Keypair::new(), None, None);
+        drone.set_recipient_limits(RecipientLimits {
+            recipient_cap: 10,
+            reputation_weight: 5,
+            reputation_min_age: Duration::new(60, 0),
+            ..RecipientLimits::default()
+        });
+        let to = Pubkey::new_rand();
+        drone.charge_recipient(ip, &to, 0, 2).unwrap();
+        drone.charge_recipient(ip, &to, 10, 0).unwrap();
+
+        // a fresh recipient's standing doesn't raise its allowance yet
+        assert!(drone.charge_recipient(ip, &to, 1, 0).is_err());
+        let record = drone.recipients.get_mut(&to).unwrap();
+        record.first_seen = record.first_seen.saturating_sub(60_000);
+        drone.charge_recipient(ip, &to, 10, 0).unwrap();
+    }
+
+    #[test]
+    fn test_decayed_reputation() {
+        let half_life = Duration::new(10, 0);
+        assert_eq!(decayed_reputation(100, 0, half_life), 100);
+        assert_eq!(decayed_reputation(100, 10_000, half_life), 50);
+        assert_eq!(decayed_reputation(100, 20_000, half_life), 25);
+        assert_eq!(decayed_reputation(100, 10_000_000, half_life), 0);
+        assert_eq!(decayed_reputation(100, 0, Duration::new(0, 0)), 0);
+    }
+
+    #[test]
+    fn test_new_recipient_throttle() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let mut drone = Drone::new(Keypair::new(), Some(1), None);
+        drone.set_recipient_limits(RecipientLimits {
+            new_recipient_cap: 2,
+            ..RecipientLimits::default()
+        });
+        let first = Pubkey::new_rand();
+        drone.charge_recipient(ip, &first, 1, 0).unwrap();
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+        assert_eq!(drone.new_recipients_current, 2);
+        assert!(drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .is_err());
+
+        // recipients already seen are still served
+        drone.charge_recipient(ip, &first, 1, 0).unwrap();
+
+        let now = drone.window_start;
+        drone.compact(now + 1000);
+        assert_eq!(drone.new_recipients_current, 0);
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_new_recipient_ip_throttle() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let other_ip = "127.0.0.2".parse().unwrap();
+        let mut drone = Drone::new(Keypair::new(), Some(1), None);
+        drone.set_recipient_limits(RecipientLimits {
+            new_recipient_ip_cap: 2,
+            ..RecipientLimits::default()
+        });
+        let first = Pubkey::new_rand();
+        drone.charge_recipient(ip, &first, 1, 0).unwrap();
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+        assert!(drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .is_err());
+
+        // the cap is per IP, and recipients already seen are still served
+        drone
+            .charge_recipient(other_ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+        drone.charge_recipient(ip, &first, 1, 0).unwrap();
+
+        let now = drone.window_start;
+        drone.compact(now + 1000);
+        assert!(drone.new_recipients_by_ip.is_empty());
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_recipient_retention() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let mut drone = Drone::new(Keypair::new(), None, None);
+        drone.set_recipient_limits(RecipientLimits {
+            max_recipients: 2,
+            recipient_retention: Duration::new(10, 0),
+            ..RecipientLimits::default()
+        });
+        let first = Pubkey::new_rand();
+        drone.charge_recipient(ip, &first, 1, 0).unwrap();
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+        assert!(drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .is_err());
+
+        // recipients without a grant for the retention period are forgotten
+        drone.recipients.get_mut(&first).unwrap().reputation_updated = 0;
+        drone.compact(timestamp());
+        assert_eq!(drone.recipients.len(), 1);
+        assert!(!drone.recipients.contains_key(&first));
+        drone
+            .charge_recipient(ip, &Pubkey::new_rand(), 1, 0)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_drone_persist_to() {
+        let path =
+            std::env::temp_dir().join(format!("test_drone_persist_to-{}", Pubkey::new_rand()));
+        let mut drone = Drone::new(Keypair::new(), None, Some(100));
+        drone.persist_to(DroneStore::new(path.clone())).unwrap();
+        let request = DroneRequest::GetAirdrop {
+            difs: 60,
+            to: Pubkey::new_rand(),
+            blockhash: Hash::default(),
+        };
+        let request = serialize(&request).unwrap();
+        let mut bytes = BytesMut::with_capacity(request.len());
+        bytes.put(&request[..]);
+        let ip = "127.0.0.1".parse().unwrap();
+        drone.process_drone_request(&bytes, ip).unwrap();
+
+        // grants right after a save are written out later, at the latest when the drone stops
+        let saved = DroneStore::new(path.clone()).load().unwrap();
+        assert_eq!(saved.request_current, 0);
+        drop(drone);
+
+        // a restarted drone picks up the same slice
+        let mut drone = Drone::new(Keypair::new(), None, Some(100));
+        drone.persist_to(DroneStore::new(path.clone())).unwrap();
+        assert_eq!(drone.request_current, 60);
+        assert_eq!(drone.recipients.len(), 1);
+        assert_eq!(drone.ip_cache[&ip].len(), 1);
+        assert!(drone.process_drone_request(&bytes, ip).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_drone_default_init() {
         let keypair = Keypair::new();
@@ -552,6 +1584,7 @@ mod tests {
 
     #[test]
     fn test_drone_build_airdrop_transaction() {
+        let ip = "127.0.0.1".parse().unwrap();
         let to = Pubkey::new_rand();
         let blockhash = Hash::default();
         let request = DroneRequest::GetAirdrop {
@@ -564,7 +1597,9 @@ mod tests {
         let mint_pubkey = mint.pubkey();
         let mut drone = Drone::new(mint, None, None);
 
-        let tx = drone.build_airdrop_transaction(request).unwrap();
+        let tx = drone
+            .build_airdrop_transaction(request.clone(), ip)
+            .unwrap();
         let message = tx.message();
 
         assert_eq!(tx.signatures.len(), 1);
@@ -588,12 +1623,13 @@ mod tests {
 
         let mint = Keypair::new();
         drone = Drone::new(mint, None, Some(1));
-        let tx = drone.build_airdrop_transaction(request);
+        let tx = drone.build_airdrop_transaction(request, ip);
         assert!(tx.is_err());
     }
 
     #[test]
     fn test_drone_build_reputation_airdrop_transaction() {
+        let ip = "127.0.0.1".parse().unwrap();
         let to = Pubkey::new_rand();
         let blockhash = Hash::default();
         let request = DroneRequest::GetReputation {
@@ -606,7 +1642,9 @@ mod tests {
         let mint_pubkey = mint.pubkey();
         let mut drone = Drone::new(mint, None, None);
 
-        let tx = drone.build_airdrop_transaction(request).unwrap();
+        let tx = drone
+            .build_airdrop_transaction(request.clone(), ip)
+            .unwrap();
         let message = tx.message();
 
         assert_eq!(tx.signatures.len(), 1);
@@ -629,10 +1667,160 @@ mod tests {
 
         let mint = Keypair::new();
         drone = Drone::new(mint, None, Some(1));
-        let tx = drone.build_airdrop_transaction(request);
+        let tx = drone.build_airdrop_transaction(request, ip);
         assert!(tx.is_err());
     }
 
+    #[test]
+    fn test_request_airdrop_transaction_with_retry() {
+        let drone_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let policy = RetryPolicy::new(Duration::from_millis(0), 2, Duration::from_secs(10));
+        let err = request_airdrop_transaction_with_retry(
+            &drone_addr,
+            &Pubkey::new_rand(),
+            1,
+            Hash::default(),
+            &policy,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("failed after 2 attempts"));
+    }
+
+    #[test]
+    fn test_request_airdrop_transaction_rate_limited() {
+        let to = Pubkey::new_rand();
+        let drone_error = DroneError::PubkeyRateLimited {
+            pubkey: to,
+            cap: 1,
+            retry_after: Duration::from_secs(60),
+        };
+        // answers every request with the reply the drone sends a rate limited one
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let drone_addr = listener.local_addr().unwrap();
+        let reply = error_reply(&drone_error.clone().into());
+        thread::spawn(move || {
+            for mut stream in listener.incoming().filter_map(|stream| stream.ok()) {
+                stream.write_all(&reply).unwrap();
+            }
+        });
+
+        let err = request_airdrop_transaction(&drone_addr, &to, 1, Hash::default()).unwrap_err();
+        assert_eq!(
+            err.get_ref()
+                .and_then(|err| err.downcast_ref::<DroneError>()),
+            Some(&drone_error)
+        );
+
+        // a rate limited request isn't retried
+        let policy = RetryPolicy::new(Duration::from_millis(0), 3, Duration::from_secs(10));
+        let err =
+            request_airdrop_transaction_with_retry(&drone_addr, &to, 1, Hash::default(), &policy)
+                .unwrap_err();
+        assert!(err.to_string().contains("failed after 1 attempts"));
+    }
+
+    #[test]
+    fn test_request_reputation_airdrop_transaction_with_retry() {
+        let drone_addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let policy = RetryPolicy::new(Duration::from_millis(0), 2, Duration::from_secs(10));
+        let err = request_reputation_airdrop_transaction_with_retry(
+            &drone_addr,
+            &Pubkey::new_rand(),
+            1,
+            Hash::default(),
+            &policy,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("failed after 2 attempts"));
+    }
+
+    #[test]
+    fn test_drone_sponsor_fee() {
+        let ip = "127.0.0.1".parse().unwrap();
+        let mint = Keypair::new();
+        let mint_pubkey = mint.pubkey();
+        let client = Keypair::new();
+        let blockhash = Hash::new(client.pubkey().as_ref());
+        let new_message = |instruction, payer: &Pubkey| {
+            let mut message = Message::new_with_payer(vec![instruction], Some(payer));
+            message.recent_blockhash = blockhash;
+            message
+        };
+        let message = new_message(
+            system_instruction::assign(&client.pubkey(), &Pubkey::new_rand()),
+            &mint_pubkey,
+        );
+        let now = timestamp();
+
+        // no fees sponsored until enabled
+        let mut drone = Drone::new(mint, None, None);
+        assert!(drone.check_sponsored_message(&message, now).is_err());
+
+        // nor without the rate the cluster charges, every fee would count as free
+        drone.set_sponsor_limits(SponsorLimits {
+            program_allowlist: vec![system_program::id()],
+            max_fee: 10,
+            ..SponsorLimits::default()
+        });
+        assert!(drone.check_sponsored_message(&message, now).is_err());
+
+        drone.set_fee_calculator(FeeCalculator::new(5), now);
+        assert_eq!(drone.check_sponsored_message(&message, now).unwrap(), 10);
+        let request = DroneRequest::SponsorFee {
+            message: message.clone(),
+        };
+        assert_eq!(request.to(), client.pubkey());
+        let mut tx = drone.build_airdrop_transaction(request, ip).unwrap();
+        assert_eq!(tx.message(), &message);
+        assert_eq!(drone.request_current, 10);
+        assert!(!tx.is_signed());
+        tx.partial_sign(&[&client], blockhash);
+        assert!(tx.is_signed());
+        assert!(tx.verify_signatures());
+
+        // the drone pays the fee only, for allowlisted programs
+        let message = new_message(
+            system_instruction::transfer(&client.pubkey(), &mint_pubkey, 1),
+            &mint_pubkey,
+        );
+        assert!(drone.check_sponsored_message(&message, now).is_err());
+        let instruction = Instruction::new(
+            Pubkey::new_rand(),
+            &0u8,
+            vec![AccountMeta::new(client.pubkey(), true)],
+        );
+        let message = new_message(instruction, &mint_pubkey);
+        assert!(drone.check_sponsored_message(&message, now).is_err());
+        let message = new_message(
+            system_instruction::assign(&client.pubkey(), &Pubkey::new_rand()),
+            &client.pubkey(),
+        );
+        assert!(drone.check_sponsored_message(&message, now).is_err());
+
+        // within the fee cap at the rate the cluster charges now
+        let message = new_message(
+            system_instruction::assign(&client.pubkey(), &Pubkey::new_rand()),
+            &mint_pubkey,
+        );
+        drone.set_fee_calculator(FeeCalculator::new(6), now);
+        assert!(drone.check_sponsored_message(&message, now).is_err());
+
+        // and only while that rate is recent
+        drone.set_fee_calculator(FeeCalculator::new(5), now);
+        assert!(drone
+            .check_sponsored_message(&message, now + FEE_CALCULATOR_MAX_AGE)
+            .is_ok());
+        assert!(drone
+            .check_sponsored_message(&message, now + FEE_CALCULATOR_MAX_AGE + 1)
+            .is_err());
+    }
+
     #[test]
     fn test_process_drone_request() {
         let to = Pubkey::new_rand();
@@ -657,14 +1845,15 @@ mod tests {
         LittleEndian::write_u16(&mut expected_vec_with_length, expected_bytes.len() as u16);
         expected_vec_with_length.extend_from_slice(&expected_bytes);
 
+        let ip = "127.0.0.1".parse().unwrap();
         let mut drone = Drone::new(keypair, None, None);
-        let response = drone.process_drone_request(&bytes);
+        let response = drone.process_drone_request(&bytes, ip);
         let response_vec = response.unwrap().to_vec();
         assert_eq!(expected_vec_with_length, response_vec);
 
         let mut bad_bytes = BytesMut::with_capacity(9);
         bad_bytes.put("bad bytes");
-        assert!(drone.process_drone_request(&bad_bytes).is_err());
+        assert!(drone.process_drone_request(&bad_bytes, ip).is_err());
     }
 
     #[test]
@@ -691,13 +1880,14 @@ mod tests {
         LittleEndian::write_u16(&mut expected_vec_with_length, expected_bytes.len() as u16);
         expected_vec_with_length.extend_from_slice(&expected_bytes);
 
+        let ip = "127.0.0.1".parse().unwrap();
         let mut drone = Drone::new(keypair, None, None);
-        let response = drone.process_drone_request(&bytes);
+        let response = drone.process_drone_request(&bytes, ip);
         let response_vec = response.unwrap().to_vec();
         assert_eq!(expected_vec_with_length, response_vec);
 
         let mut bad_bytes = BytesMut::with_capacity(9);
         bad_bytes.put("bad bytes");
-        assert!(drone.process_drone_request(&bad_bytes).is_err());
+        assert!(drone.process_drone_request(&bad_bytes, ip).is_err());
     }
 }
@@ -0,0 +1,380 @@
+//! The `drone_store` module keeps the drone's request accounting on disk so a
+//! restart doesn't hand out a fresh request cap.
+//!
+//! The ledger is saved behind a magic number and a layout version.  Ledgers saved before
+//! the layout was versioned are migrated when they are loaded.
+
+use bincode::{deserialize, serialize, serialized_size};
+use morgan_interface::pubkey::Pubkey;
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+/// Starts every versioned ledger, an unversioned one starts with its `window_start` instead
+const LEDGER_MAGIC: &[u8; 8] = b"MGNDRONE";
+
+/// The layout of `DroneLedger` that `DroneStore::save` writes
+pub const LEDGER_VERSION: u32 = 1;
+
+/// Everything the drone needs to resume accounting where it left off
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct DroneLedger {
+    /// Start of the current time slice, in ms since the epoch
+    pub window_start: u64,
+
+    /// Difs and reputations granted since `window_start`
+    pub request_current: u64,
+
+    /// Requests granted to each IP within the rate limit window, in ms since the epoch
+    pub ip_cache: HashMap<IpAddr, VecDeque<u64>>,
+
+    /// Requests granted to each pubkey within the rate limit window, in ms since the epoch
+    pub pubkey_cache: HashMap<Pubkey, VecDeque<u64>>,
+
+    /// Recipients seen for the first time since `window_start`
+    pub new_recipients_current: u64,
+
+    /// Recipients each IP introduced since `window_start`
+    pub new_recipients_by_ip: HashMap<IpAddr, u64>,
+
+    /// What each recipient has been granted over its lifetime
+    pub recipients: HashMap<Pubkey, RecipientRecord>,
+}
+
+/// The drone's account of a single recipient, kept for as long as the ledger is
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct RecipientRecord {
+    /// Difs granted over the recipient's lifetime
+    pub difs: u64,
+
+    /// Reputations granted over the recipient's lifetime
+    pub reputations: u64,
+
+    /// Reputation standing, which decays from `reputation_updated` on and raises the
+    /// recipient's dif allowance
+    pub reputation: u64,
+
+    /// When `reputation` was last brought up to date, which every grant does, in ms since
+    /// the epoch
+    pub reputation_updated: u64,
+
+    /// When the recipient was first granted anything, in ms since the epoch
+    pub first_seen: u64,
+}
+
+/// The ledger as saved before it was versioned, without per-recipient accounting
+#[derive(Serialize, Deserialize)]
+struct UnversionedLedger {
+    window_start: u64,
+    request_current: u64,
+    ip_cache: HashMap<IpAddr, u64>,
+}
+
+/// The ledger as saved before it was versioned, with per-recipient accounting
+#[derive(Serialize, Deserialize)]
+struct UnversionedRecipientsLedger {
+    window_start: u64,
+    request_current: u64,
+    ip_cache: HashMap<IpAddr, u64>,
+    new_recipients_current: u64,
+    recipients: HashMap<Pubkey, UnversionedRecipientRecord>,
+}
+
+/// The ledger as saved before it was versioned, with per-IP and per-pubkey request windows
+#[derive(Serialize, Deserialize)]
+struct UnversionedRateLimitedLedger {
+    window_start: u64,
+    request_current: u64,
+    ip_cache: HashMap<IpAddr, VecDeque<u64>>,
+    pubkey_cache: HashMap<Pubkey, VecDeque<u64>>,
+    new_recipients_current: u64,
+    recipients: HashMap<Pubkey, UnversionedRecipientRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct UnversionedRecipientRecord {
+    difs: u64,
+    reputations: u64,
+    reputation: u64,
+    reputation_updated: u64,
+}
+
+// The unversioned ledgers kept when each IP was last seen, that becomes its one request
+fn migrate_ip_cache(ip_cache: HashMap<IpAddr, u64>) -> HashMap<IpAddr, VecDeque<u64>> {
+    ip_cache
+        .into_iter()
+        .map(|(ip, last_seen)| (ip, vec![last_seen].into()))
+        .collect()
+}
+
+impl From<UnversionedLedger> for DroneLedger {
+    fn from(ledger: UnversionedLedger) -> Self {
+        DroneLedger {
+            window_start: ledger.window_start,
+            request_current: ledger.request_current,
+            ip_cache: migrate_ip_cache(ledger.ip_cache),
+            ..DroneLedger::default()
+        }
+    }
+}
+
+fn migrate_recipients(
+    recipients: HashMap<Pubkey, UnversionedRecipientRecord>,
+) -> HashMap<Pubkey, RecipientRecord> {
+    recipients
+        .into_iter()
+        .map(|(pubkey, record)| {
+            let record = RecipientRecord {
+                difs: record.difs,
+                reputations: record.reputations,
+                reputation: record.reputation,
+                reputation_updated: record.reputation_updated,
+                // the last grant is the earliest time the ledger vouches for
+                first_seen: record.reputation_updated,
+            };
+            (pubkey, record)
+        })
+        .collect()
+}
+
+impl From<UnversionedRecipientsLedger> for DroneLedger {
+    fn from(ledger: UnversionedRecipientsLedger) -> Self {
+        DroneLedger {
+            window_start: ledger.window_start,
+            request_current: ledger.request_current,
+            ip_cache: migrate_ip_cache(ledger.ip_cache),
+            new_recipients_current: ledger.new_recipients_current,
+            recipients: migrate_recipients(ledger.recipients),
+            ..DroneLedger::default()
+        }
+    }
+}
+
+impl From<UnversionedRateLimitedLedger> for DroneLedger {
+    fn from(ledger: UnversionedRateLimitedLedger) -> Self {
+        DroneLedger {
+            window_start: ledger.window_start,
+            request_current: ledger.request_current,
+            ip_cache: ledger.ip_cache,
+            pubkey_cache: ledger.pubkey_cache,
+            new_recipients_current: ledger.new_recipients_current,
+            recipients: migrate_recipients(ledger.recipients),
+            ..DroneLedger::default()
+        }
+    }
+}
+
+/// Deserialize `bytes` as a `T` only if they hold exactly one, the unversioned layouts
+/// can't be told apart otherwise
+fn deserialize_exact<T: serde::Serialize + DeserializeOwned>(bytes: &[u8]) -> Option<T> {
+    let value: T = deserialize(bytes).ok()?;
+    if serialized_size(&value).ok()? == bytes.len() as u64 {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+fn decode_ledger(bytes: &[u8]) -> Result<DroneLedger, String> {
+    if !bytes.starts_with(LEDGER_MAGIC) {
+        return deserialize_exact::<UnversionedRateLimitedLedger>(bytes)
+            .map(DroneLedger::from)
+            .or_else(|| {
+                deserialize_exact::<UnversionedRecipientsLedger>(bytes).map(DroneLedger::from)
+            })
+            .or_else(|| deserialize_exact::<UnversionedLedger>(bytes).map(DroneLedger::from))
+            .ok_or_else(|| "unrecognized unversioned layout".to_string());
+    }
+    let bytes = &bytes[LEDGER_MAGIC.len()..];
+    let version: u32 = deserialize(bytes).map_err(|err| format!("{:?}", err))?;
+    match version {
+        LEDGER_VERSION => deserialize(&bytes[4..]).map_err(|err| format!("{:?}", err)),
+        version => Err(format!("unsupported version {}", version)),
+    }
+}
+
+pub struct DroneStore {
+    path: PathBuf,
+}
+
+impl DroneStore {
+    pub fn new(path: PathBuf) -> Self {
+        DroneStore { path }
+    }
+
+    /// Read the saved ledger, or an empty one if nothing has been saved yet
+    pub fn load(&self) -> io::Result<DroneLedger> {
+        match fs::read(&self.path) {
+            Ok(bytes) => decode_ledger(&bytes).map_err(|err| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("deserialize drone ledger {:?}: {}", self.path, err),
+                )
+            }),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(DroneLedger::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn save(&self, ledger: &DroneLedger) -> io::Result<()> {
+        let serialize_err = |err| {
+            Error::new(
+                ErrorKind::Other,
+                format!("serialize drone ledger: {:?}", err),
+            )
+        };
+        let mut bytes = LEDGER_MAGIC.to_vec();
+        bytes.extend(serialize(&LEDGER_VERSION).map_err(serialize_err)?);
+        bytes.extend(serialize(ledger).map_err(serialize_err)?);
+        // write then rename so a crash mid-save can't leave a truncated ledger
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    fn tmp_store_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("{}-{}", name, Pubkey::new_rand()))
+    }
+
+    #[test]
+    fn test_drone_store_save_load() {
+        let path = tmp_store_path("test_drone_store_save_load");
+        let store = DroneStore::new(path.clone());
+        assert_eq!(store.load().unwrap(), DroneLedger::default());
+
+        let mut ledger = DroneLedger {
+            window_start: 1_000,
+            request_current: 42,
+            ip_cache: HashMap::new(),
+            pubkey_cache: HashMap::new(),
+            new_recipients_current: 1,
+            new_recipients_by_ip: HashMap::new(),
+            recipients: HashMap::new(),
+        };
+        let to = Pubkey::new_rand();
+        ledger
+            .ip_cache
+            .insert("127.0.0.1".parse().unwrap(), vec![1_200, 1_500].into());
+        ledger.pubkey_cache.insert(to, vec![1_500].into());
+        ledger.recipients.insert(
+            to,
+            RecipientRecord {
+                difs: 42,
+                reputations: 0,
+                reputation: 0,
+                reputation_updated: 1_500,
+                first_seen: 1_200,
+            },
+        );
+        store.save(&ledger).unwrap();
+        assert_eq!(DroneStore::new(path.clone()).load().unwrap(), ledger);
+
+        fs::write(&path, b"bad bytes").unwrap();
+        assert!(store.load().is_err());
+
+        let mut bytes = LEDGER_MAGIC.to_vec();
+        bytes.extend(serialize(&(LEDGER_VERSION + 1)).unwrap());
+        bytes.extend(serialize(&ledger).unwrap());
+        fs::write(&path, bytes).unwrap();
+        assert!(store.load().is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_drone_store_migrate_unversioned() {
+        let path = tmp_store_path("test_drone_store_migrate_unversioned");
+        let store = DroneStore::new(path.clone());
+        let ip: IpAddr = "127.0.0.1".parse().unwrap();
+
+        let mut ip_cache = HashMap::new();
+        ip_cache.insert(ip, 1_500);
+        let unversioned = UnversionedLedger {
+            window_start: 1_000,
+            request_current: 42,
+            ip_cache: ip_cache.clone(),
+        };
+        fs::write(&path, serialize(&unversioned).unwrap()).unwrap();
+        let ledger = store.load().unwrap();
+        assert_eq!(ledger.window_start, 1_000);
+        assert_eq!(ledger.request_current, 42);
+        assert_eq!(ledger.ip_cache[&ip], VecDeque::from(vec![1_500]));
+        assert!(ledger.recipients.is_empty());
+
+        let to = Pubkey::new_rand();
+        let mut recipients = HashMap::new();
+        recipients.insert(
+            to,
+            UnversionedRecipientRecord {
+                difs: 42,
+                reputations: 1,
+                reputation: 1,
+                reputation_updated: 1_500,
+            },
+        );
+        let unversioned = UnversionedRecipientsLedger {
+            window_start: 1_000,
+            request_current: 42,
+            ip_cache,
+            new_recipients_current: 1,
+            recipients,
+        };
+        fs::write(&path, serialize(&unversioned).unwrap()).unwrap();
+        let ledger = store.load().unwrap();
+        assert_eq!(ledger.new_recipients_current, 1);
+        assert_eq!(
+            ledger.recipients[&to],
+            RecipientRecord {
+                difs: 42,
+                reputations: 1,
+                reputation: 1,
+                reputation_updated: 1_500,
+                first_seen: 1_500,
+            }
+        );
+
+        let mut ip_cache = HashMap::new();
+        ip_cache.insert(ip, VecDeque::from(vec![1_500]));
+        let mut pubkey_cache = HashMap::new();
+        pubkey_cache.insert(to, VecDeque::from(vec![1_500]));
+        let mut recipients = HashMap::new();
+        recipients.insert(
+            to,
+            UnversionedRecipientRecord {
+                difs: 42,
+                reputations: 1,
+                reputation: 1,
+                reputation_updated: 1_500,
+            },
+        );
+        let unversioned = UnversionedRateLimitedLedger {
+            window_start: 1_000,
+            request_current: 42,
+            ip_cache,
+            pubkey_cache: pubkey_cache.clone(),
+            new_recipients_current: 1,
+            recipients,
+        };
+        fs::write(&path, serialize(&unversioned).unwrap()).unwrap();
+        let ledger = store.load().unwrap();
+        assert_eq!(ledger.ip_cache[&ip], VecDeque::from(vec![1_500]));
+        assert_eq!(ledger.pubkey_cache, pubkey_cache);
+        assert_eq!(ledger.recipients[&to].first_seen, 1_500);
+
+        // saved back in the versioned layout
+        store.save(&ledger).unwrap();
+        assert!(fs::read(&path).unwrap().starts_with(LEDGER_MAGIC));
+        assert_eq!(store.load().unwrap(), ledger);
+        fs::remove_file(&path).unwrap();
+    }
+}
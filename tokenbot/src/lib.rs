@@ -1,2 +1,3 @@
 pub mod drone;
 pub mod drone_mock;
+pub mod drone_store;
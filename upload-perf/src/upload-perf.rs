@@ -6,7 +6,7 @@ use std::collections::HashMap;
 use std::env;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
-use std::process::Command;
+use std::process::{exit, Command};
 
 fn get_last_metrics(metric: &str, db: &str, name: &str, branch: &str) -> Result<String, String> {
     let query = format!(
@@ -52,6 +52,11 @@ fn main() {
     let mut results = HashMap::new();
 
     let db = env::var("INFLUX_DATABASE").unwrap_or_else(|_| "scratch".to_string());
+    // Fail when a median grows by more than this percentage over the baseline
+    let regression_percent: Option<i64> = env::var("BENCH_REGRESSION_PERCENT")
+        .ok()
+        .map(|percent| percent.parse().expect("BENCH_REGRESSION_PERCENT"));
+    let mut regressions = vec![];
 
     for line in BufReader::new(file).lines() {
         if let Ok(v) = serde_json::from_str(&line.unwrap()) {
@@ -106,6 +111,12 @@ fn main() {
                 values.1,
                 values.3.parse::<i32>().unwrap_or_default(),
             );
+            if let (Some(percent), Ok(last_median)) = (regression_percent, values.2.parse::<i64>())
+            {
+                if last_median > 0 && values.0 * 100 > last_median * (100 + percent) {
+                    regressions.push((entry, values.0, last_median));
+                }
+            }
         }
     } else {
         println!("No previous results found for {} branch", branch);
@@ -116,4 +127,15 @@ fn main() {
         }
     }
     morgan_metricbot::flush();
+
+    if !regressions.is_empty() {
+        println!(
+            "Benches regressed by more than {}%:",
+            regression_percent.unwrap()
+        );
+        for (entry, median, last_median) in regressions {
+            println!("{}, {:#10?}, {:#10?}", entry, median, last_median);
+        }
+        exit(1);
+    }
 }
@@ -2,16 +2,18 @@ use clap::{crate_description, crate_name, crate_version, App, Arg};
 use log::*;
 use morgan::clusterMessage::{Node, FULLNODE_PORT_RANGE};
 use morgan::connectionInfo::ContactInfo;
+use morgan::ledgerArchive::LedgerArchiveConfig;
 use morgan::localVoteSignerService::LocalVoteSignerService;
-use morgan::service::Service;
 use morgan::socketaddr;
 use morgan::verifier::{Validator, ValidatorConfig};
 use morgan_netutil::parse_port_range;
 use morgan_interface::signature::{read_keypair, Keypair, KeypairUtil};
+use std::env;
 use std::fs::File;
 use std::net::SocketAddr;
 use std::process::exit;
 use std::sync::Arc;
+use std::time::Duration;
 use morgan_helper::logHelper::*;
 
 fn port_range_validator(port_range: String) -> Result<(), String> {
@@ -35,8 +37,8 @@ fn main() {
             Arg::with_name("blockstream")
                 .long("blockstream")
                 .takes_value(true)
-                .value_name("UNIX DOMAIN SOCKET")
-                .help("Open blockstream at this unix domain socket location")
+                .value_name("UNIX DOMAIN SOCKET | tcp://HOST:PORT | exec:COMMAND")
+                .help("Stream entries to this unix domain socket location, TCP server, or the stdin of this shell command")
         )
         .arg(
             Arg::with_name("identity")
@@ -98,6 +100,13 @@ fn main() {
                 .takes_value(false)
                 .help("Launch node without voting"),
         )
+        .arg(
+            Arg::with_name("rpc_only")
+                .long("rpc-only")
+                .takes_value(false)
+                .requires("no_voting")
+                .help("Only replay the ledger and serve RPC, without a TPU or broadcast, and never lead"),
+        )
         .arg(
             Arg::with_name("no_sigverify")
                 .short("v")
@@ -105,6 +114,13 @@ fn main() {
                 .takes_value(false)
                 .help("Run without signature verification"),
         )
+        .arg(
+            Arg::with_name("gpu_sigverify_min_batch_size")
+                .long("gpu-sigverify-min-batch-size")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Verify batches with fewer packets than this on the CPU even when a GPU is available"),
+        )
         .arg(
             Arg::with_name("rpc_port")
                 .long("rpc-port")
@@ -118,6 +134,12 @@ fn main() {
                 .takes_value(false)
                 .help("Enable the JSON RPC 'fullnodeExit' API.  Only enable in a debug environment"),
         )
+        .arg(
+            Arg::with_name("enable_rpc_debug")
+                .long("enable-rpc-debug")
+                .takes_value(false)
+                .help("Enable the JSON RPC 'getSlotEntries' and 'getForkReplayErrors' APIs.  Only enable in a debug environment"),
+        )
         .arg(
             Arg::with_name("rpc_drone_address")
                 .long("rpc-drone-address")
@@ -125,6 +147,166 @@ fn main() {
                 .takes_value(true)
                 .help("Enable the JSON RPC 'requestAirdrop' API with this drone address."),
         )
+        .arg(
+            Arg::with_name("halt_on_accounts_hash_mismatch")
+                .long("halt-on-accounts-hash-mismatch")
+                .takes_value(false)
+                .help("Exit when the accounts hash gossiped by a stake majority differs from ours"),
+        )
+        .arg(
+            Arg::with_name("embedded_drone_keypair")
+                .long("embedded-drone-keypair")
+                .value_name("PATH")
+                .takes_value(true)
+                .help("Run a drone funded by this keypair inside the validator, on the RPC port + 2 if --rpc-port is given. Meant for test clusters"),
+        )
+        .arg(
+            Arg::with_name("admin_rpc_port")
+                .long("admin-rpc-port")
+                .value_name("PORT")
+                .takes_value(true)
+                .help("Serve the admin RPC on this port of the loopback interface, e.g. to change the log filter with 'setLogFilter', dump per-socket receive and kernel drop counts with 'getSocketStats', check how far along startup and how far behind the cluster the node is with 'getHealth', switch to another identity keypair with 'setIdentity', list the accounts transactions contend for most with 'getHottestAccounts' or stop the node with 'exit'. Requests must carry the token in the ledger directory's admin-rpc.token as an 'Authorization: Bearer' header, and 'setIdentity' is refused once the node starts its services"),
+        )
+        .arg(
+            Arg::with_name("rpc_max_response_bytes")
+                .long("rpc-max-response-bytes")
+                .value_name("BYTES")
+                .takes_value(true)
+                .help("Return an error instead of any JSON RPC response larger than this"),
+        )
+        .arg(
+            Arg::with_name("ledger_write_flush_interval_ms")
+                .long("ledger-write-flush-interval-ms")
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+                .help("Aggregate received blobs per slot and write them to the ledger at most this often. 0 writes every blob as it arrives"),
+        )
+        .arg(
+            Arg::with_name("ledger_max_pending_blobs")
+                .long("ledger-max-pending-blobs")
+                .value_name("COUNT")
+                .takes_value(true)
+                .help("Write a slot's aggregated blobs to the ledger once this many are pending"),
+        )
+        .arg(
+            Arg::with_name("ledger_sync_wal")
+                .long("ledger-sync-wal")
+                .takes_value(false)
+                .help("Sync the ledger write-ahead log to disk on every write"),
+        )
+        .arg(
+            Arg::with_name("ledger_wal_bytes_per_sync")
+                .long("ledger-wal-bytes-per-sync")
+                .value_name("BYTES")
+                .takes_value(true)
+                .help("Incrementally sync ledger files to disk every this many bytes written"),
+        )
+        .arg(
+            Arg::with_name("ledger_recovery")
+                .long("ledger-recovery")
+                .takes_value(false)
+                .help("Truncate ledger slots left inconsistent by a crash instead of failing to start"),
+        )
+        .arg(
+            Arg::with_name("ledger_archive_endpoint")
+                .long("ledger-archive-endpoint")
+                .value_name("URL")
+                .takes_value(true)
+                .requires("ledger_archive_bucket")
+                .help("Upload rooted slots to this S3-compatible endpoint and serve them from there over RPC. Credentials are read from AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY"),
+        )
+        .arg(
+            Arg::with_name("ledger_archive_bucket")
+                .long("ledger-archive-bucket")
+                .value_name("BUCKET")
+                .takes_value(true)
+                .requires("ledger_archive_endpoint")
+                .help("Bucket to archive the ledger into"),
+        )
+        .arg(
+            Arg::with_name("ledger_archive_region")
+                .long("ledger-archive-region")
+                .value_name("REGION")
+                .takes_value(true)
+                .default_value("us-east-1")
+                .help("Region the ledger archive requests are signed for"),
+        )
+        .arg(
+            Arg::with_name("ledger_archive_prefix")
+                .long("ledger-archive-prefix")
+                .value_name("PREFIX")
+                .takes_value(true)
+                .default_value("ledger")
+                .help("Prefix of the ledger archive object keys"),
+        )
+        .arg(
+            Arg::with_name("ledger_archive_segment_slots")
+                .long("ledger-archive-segment-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .help("Archive the ledger in objects of this many slots"),
+        )
+        .arg(
+            Arg::with_name("allowed_program")
+                .long("allowed-program")
+                .value_name("PUBKEY")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .help("Only admit transactions that call these programs, may be repeated"),
+        )
+        .arg(
+            Arg::with_name("max_transfer_difs")
+                .long("max-transfer-difs")
+                .value_name("DIFS")
+                .takes_value(true)
+                .help("Refuse transactions that transfer more than this in one instruction"),
+        )
+        .arg(
+            Arg::with_name("leader_warmup_ticks")
+                .long("leader-warmup-ticks")
+                .value_name("TICKS")
+                .takes_value(true)
+                .help("Create the bank of our leader slot this many ticks before the slot starts. 0 creates it when the slot starts"),
+        )
+        .arg(
+            Arg::with_name("snapshot_path")
+                .long("snapshot-path")
+                .value_name("DIR")
+                .takes_value(true)
+                .help("Save bank snapshots in this directory and restore from the newest one at startup"),
+        )
+        .arg(
+            Arg::with_name("snapshot_interval_slots")
+                .long("snapshot-interval-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .requires("snapshot_path")
+                .help("Snapshot the root bank every time the root moves this many slots"),
+        )
+        .arg(
+            Arg::with_name("incremental_snapshot_interval_slots")
+                .long("incremental-snapshot-interval-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .requires("snapshot_path")
+                .help("Between full snapshots, snapshot the accounts changed since the last full one every time the root moves this many slots"),
+        )
+        .arg(
+            Arg::with_name("max_ledger_slots")
+                .long("max-ledger-slots")
+                .value_name("SLOTS")
+                .takes_value(true)
+                .requires("snapshot_path")
+                .help("Purge ledger slots more than this many slots below the root, keeping those the newest snapshot replays"),
+        )
+        .arg(
+            Arg::with_name("direct_stream_peers")
+                .long("direct-stream-peers")
+                .value_name("COUNT")
+                .takes_value(true)
+                .help("As leader, stream blobs straight to this many of the highest staked validators ahead of broadcast"),
+        )
         .arg(
             Arg::with_name("signer")
                 .short("s")
@@ -141,6 +323,27 @@ fn main() {
                 .takes_value(true)
                 .help("Comma separated persistent accounts location"),
         )
+        .arg(
+            Arg::with_name("accounts_index_memory_mb")
+                .long("accounts-index-memory-mb")
+                .value_name("MEGABYTES")
+                .takes_value(true)
+                .help("Keep about this much of the accounts index in memory and the rest on disk"),
+        )
+        .arg(
+            Arg::with_name("execution_threads")
+                .long("execution-threads")
+                .value_name("NUM")
+                .takes_value(true)
+                .help("Execute the transactions of a batch on this many threads of their own instead of the banking and replay threads"),
+        )
+        .arg(
+            Arg::with_name("pin_execution_threads")
+                .long("pin-execution-threads")
+                .requires("execution_threads")
+                .takes_value(false)
+                .help("Pin every execution thread to a core of its own"),
+        )
         .arg(
             clap::Arg::with_name("gossip_port")
                 .long("gossip-port")
@@ -194,15 +397,143 @@ fn main() {
     let ledger_path = matches.value_of("ledger").unwrap();
 
     validator_config.sigverify_disabled = matches.is_present("no_sigverify");
+    if let Some(min_batch_size) = matches.value_of("gpu_sigverify_min_batch_size") {
+        let min_batch_size = min_batch_size
+            .parse()
+            .expect("failed to parse gpu-sigverify-min-batch-size");
+        morgan::signatureVerify::set_gpu_min_batch_size(min_batch_size);
+    }
 
     validator_config.voting_disabled = matches.is_present("no_voting");
+    validator_config.rpc_only = matches.is_present("rpc_only");
+    validator_config.halt_on_accounts_hash_mismatch =
+        matches.is_present("halt_on_accounts_hash_mismatch");
+    if let Some(drone_keypair) = matches.value_of("embedded_drone_keypair") {
+        let drone_keypair = read_keypair(drone_keypair).unwrap_or_else(|err| {
+            eprintln!("{}: Unable to open keypair file: {}", err, drone_keypair);
+            exit(1);
+        });
+        validator_config.drone_keypair = Some(Arc::new(drone_keypair));
+    }
+    validator_config.admin_rpc_port = matches
+        .value_of("admin_rpc_port")
+        .map(|port| port.parse().expect("failed to parse admin-rpc-port"));
 
     if matches.is_present("enable_rpc_exit") {
         validator_config.rpc_config.enable_fullnode_exit = true;
     }
+    if matches.is_present("enable_rpc_debug") {
+        validator_config.rpc_config.enable_debug_rpc = true;
+    }
     validator_config.rpc_config.drone_addr = matches.value_of("rpc_drone_address").map(|address| {
         morgan_netutil::parse_host_port(address).expect("failed to parse drone address")
     });
+    if let Some(max_response_bytes) = matches.value_of("rpc_max_response_bytes") {
+        validator_config.rpc_config.max_response_bytes = max_response_bytes
+            .parse()
+            .expect("failed to parse rpc-max-response-bytes");
+    }
+
+    if let Some(interval_ms) = matches.value_of("ledger_write_flush_interval_ms") {
+        validator_config.blocktree_config.write_flush_interval = Duration::from_millis(
+            interval_ms
+                .parse()
+                .expect("failed to parse ledger-write-flush-interval-ms"),
+        );
+    }
+    if let Some(max_pending_blobs) = matches.value_of("ledger_max_pending_blobs") {
+        validator_config.blocktree_config.max_pending_blobs = max_pending_blobs
+            .parse()
+            .expect("failed to parse ledger-max-pending-blobs");
+    }
+    validator_config.blocktree_config.wal.sync = matches.is_present("ledger_sync_wal");
+    if let Some(bytes_per_sync) = matches.value_of("ledger_wal_bytes_per_sync") {
+        validator_config.blocktree_config.wal.bytes_per_sync = bytes_per_sync
+            .parse()
+            .expect("failed to parse ledger-wal-bytes-per-sync");
+    }
+    validator_config.blocktree_config.recovery_mode = matches.is_present("ledger_recovery");
+    if let Some(endpoint) = matches.value_of("ledger_archive_endpoint") {
+        if !cfg!(feature = "ledger-archive") {
+            eprintln!("--ledger-archive-endpoint requires the ledger-archive feature");
+            exit(1);
+        }
+        let value_of = |name: &str| matches.value_of(name).unwrap().to_string();
+        let mut ledger_archive = LedgerArchiveConfig {
+            endpoint: endpoint.to_string(),
+            bucket: value_of("ledger_archive_bucket"),
+            region: value_of("ledger_archive_region"),
+            prefix: value_of("ledger_archive_prefix"),
+            access_key: env::var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+            secret_key: env::var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
+            ..LedgerArchiveConfig::default()
+        };
+        if let Some(segment_slots) = matches.value_of("ledger_archive_segment_slots") {
+            ledger_archive.segment_slots = segment_slots
+                .parse()
+                .expect("failed to parse ledger-archive-segment-slots");
+            assert!(
+                ledger_archive.segment_slots > 0,
+                "ledger-archive-segment-slots must be positive"
+            );
+        }
+        validator_config.ledger_archive = Some(ledger_archive);
+    }
+
+    if let Some(programs) = matches.values_of("allowed_program") {
+        validator_config.transaction_policy.allowed_programs = Some(
+            programs
+                .map(|program| program.parse().expect("failed to parse allowed-program"))
+                .collect(),
+        );
+    }
+    if let Some(max_transfer_difs) = matches.value_of("max_transfer_difs") {
+        validator_config.transaction_policy.max_transfer_difs = Some(
+            max_transfer_difs
+                .parse()
+                .expect("failed to parse max-transfer-difs"),
+        );
+    }
+    if let Some(leader_warmup_ticks) = matches.value_of("leader_warmup_ticks") {
+        validator_config.leader_warmup_ticks = leader_warmup_ticks
+            .parse()
+            .expect("failed to parse leader-warmup-ticks");
+    }
+    validator_config.snapshot_path = matches.value_of("snapshot_path").map(ToString::to_string);
+    if let Some(snapshot_interval_slots) = matches.value_of("snapshot_interval_slots") {
+        validator_config.snapshot_interval_slots = snapshot_interval_slots
+            .parse()
+            .expect("failed to parse snapshot-interval-slots");
+        assert!(
+            validator_config.snapshot_interval_slots > 0,
+            "snapshot-interval-slots must be positive"
+        );
+    }
+    if let Some(incremental_snapshot_interval_slots) =
+        matches.value_of("incremental_snapshot_interval_slots")
+    {
+        let incremental_snapshot_interval_slots: u64 = incremental_snapshot_interval_slots
+            .parse()
+            .expect("failed to parse incremental-snapshot-interval-slots");
+        assert!(
+            incremental_snapshot_interval_slots > 0,
+            "incremental-snapshot-interval-slots must be positive"
+        );
+        validator_config.incremental_snapshot_interval_slots =
+            Some(incremental_snapshot_interval_slots);
+    }
+    if let Some(max_ledger_slots) = matches.value_of("max_ledger_slots") {
+        validator_config.max_ledger_slots = Some(
+            max_ledger_slots
+                .parse()
+                .expect("failed to parse max-ledger-slots"),
+        );
+    }
+    if let Some(direct_stream_peers) = matches.value_of("direct_stream_peers") {
+        validator_config.direct_stream_peers = direct_stream_peers
+            .parse()
+            .expect("failed to parse direct-stream-peers");
+    }
 
     let dynamic_port_range = parse_port_range(matches.value_of("dynamic_port_range").unwrap())
         .expect("invalid dynamic_port_range");
@@ -221,6 +552,19 @@ fn main() {
     } else {
         validator_config.account_paths = None;
     }
+    if let Some(accounts_index_memory_mb) = matches.value_of("accounts_index_memory_mb") {
+        let accounts_index_memory_mb: usize = accounts_index_memory_mb
+            .parse()
+            .expect("failed to parse accounts-index-memory-mb");
+        validator_config.accounts_index_memory_budget =
+            Some(accounts_index_memory_mb * 1024 * 1024);
+    }
+    if let Some(execution_threads) = matches.value_of("execution_threads") {
+        validator_config.execution_pool.num_threads = execution_threads
+            .parse()
+            .expect("failed to parse execution-threads");
+    }
+    validator_config.execution_pool.pin_threads = matches.is_present("pin_execution_threads");
     let cluster_entrypoint = matches.value_of("entrypoint").map(|entrypoint| {
         let entrypoint_addr = morgan_netutil::parse_host_port(entrypoint)
             .expect("failed to parse entrypoint address");
@@ -239,7 +583,12 @@ fn main() {
         (Some(signer_service), signer_addr)
     };
     let init_complete_file = matches.value_of("init_complete_file");
-    validator_config.blockstream = matches.value_of("blockstream").map(ToString::to_string);
+    validator_config.blockstream = matches.value_of("blockstream").map(|blockstream| {
+        blockstream.parse().unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            exit(1);
+        })
+    });
 
     let keypair = Arc::new(keypair);
     let mut node = Node::new_with_external_ip(&keypair.pubkey(), &gossip_addr, dynamic_port_range);
@@ -251,6 +600,9 @@ fn main() {
         }
         node.info.rpc = SocketAddr::new(gossip_addr.ip(), port_number);
         node.info.rpc_pubsub = SocketAddr::new(gossip_addr.ip(), port_number + 1);
+        if validator_config.drone_keypair.is_some() {
+            node.info.drone = SocketAddr::new(gossip_addr.ip(), port_number + 2);
+        }
     };
 
     let validator = Validator::new(
@@ -274,7 +626,8 @@ fn main() {
             module_path!().to_string()
         )
     );
-    validator.join().expect("Verifier exit");
+    validator.wait_for_exit();
+    validator.shutdown().expect("Verifier exit");
     // info!("{}", Info(format!("Validator exiting..").to_string()));
     println!("{}",
         printLn(
@@ -51,9 +51,7 @@ pub enum WalletCommand {
     CreateVoteAccount(Pubkey, Pubkey, u32, u64),
     ShowVoteAccount(Pubkey),
     CreateStakeAccount(Pubkey, u64),
-    CreateMiningPoolAccount(Pubkey, u64),
     DelegateStake(Keypair, Pubkey),
-    RedeemVoteCredits(Pubkey, Pubkey, Pubkey),
     ShowStakeAccount(Pubkey),
     CreateStorageMiningPoolAccount(Pubkey, u64),
     CreateReplicatorStorageAccount(Pubkey),
@@ -227,15 +225,6 @@ pub fn parse_command(
                 difs,
             ))
         }
-        ("create-mining-pool-account", Some(matches)) => {
-            let mining_pool_account_pubkey =
-                pubkey_of(matches, "mining_pool_account_pubkey").unwrap();
-            let difs = matches.value_of("difs").unwrap().parse()?;
-            Ok(WalletCommand::CreateMiningPoolAccount(
-                mining_pool_account_pubkey,
-                difs,
-            ))
-        }
         ("delegate-stake", Some(matches)) => {
             let staking_account_keypair =
                 keypair_of(matches, "staking_account_keypair_file").unwrap();
@@ -245,17 +234,6 @@ pub fn parse_command(
                 voting_account_pubkey,
             ))
         }
-        ("redeem-vote-credits", Some(matches)) => {
-            let mining_pool_account_pubkey =
-                pubkey_of(matches, "mining_pool_account_pubkey").unwrap();
-            let staking_account_pubkey = pubkey_of(matches, "staking_account_pubkey").unwrap();
-            let voting_account_pubkey = pubkey_of(matches, "voting_account_pubkey").unwrap();
-            Ok(WalletCommand::RedeemVoteCredits(
-                mining_pool_account_pubkey,
-                staking_account_pubkey,
-                voting_account_pubkey,
-            ))
-        }
         ("show-stake-account", Some(matches)) => {
             let staking_account_pubkey = pubkey_of(matches, "staking_account_pubkey").unwrap();
             Ok(WalletCommand::ShowStakeAccount(staking_account_pubkey))
@@ -549,23 +527,6 @@ fn process_create_stake_account(
     Ok(signature_str.to_string())
 }
 
-fn process_create_mining_pool_account(
-    rpc_client: &RpcClient,
-    config: &WalletConfig,
-    mining_pool_account_pubkey: &Pubkey,
-    difs: u64,
-) -> ProcessResult {
-    let (recent_blockhash, _fee_calculator) = rpc_client.get_recent_blockhash()?;
-    let ixs = stake_instruction::create_mining_pool_account(
-        &config.keypair.pubkey(),
-        mining_pool_account_pubkey,
-        difs,
-    );
-    let mut tx = Transaction::new_signed_instructions(&[&config.keypair], ixs, recent_blockhash);
-    let signature_str = rpc_client.send_and_confirm_transaction(&mut tx, &[&config.keypair])?;
-    Ok(signature_str.to_string())
-}
-
 fn process_delegate_stake(
     rpc_client: &RpcClient,
     config: &WalletConfig,
@@ -588,44 +549,27 @@ fn process_delegate_stake(
     Ok(signature_str.to_string())
 }
 
-fn process_redeem_vote_credits(
-    rpc_client: &RpcClient,
-    config: &WalletConfig,
-    mining_pool_account_pubkey: &Pubkey,
-    staking_account_pubkey: &Pubkey,
-    voting_account_pubkey: &Pubkey,
-) -> ProcessResult {
-    let (recent_blockhash, _fee_calculator) = rpc_client.get_recent_blockhash()?;
-    let ixs = vec![stake_instruction::redeem_vote_credits(
-        &config.keypair.pubkey(),
-        mining_pool_account_pubkey,
-        staking_account_pubkey,
-        voting_account_pubkey,
-    )];
-    let mut tx = Transaction::new_signed_instructions(&[&config.keypair], ixs, recent_blockhash);
-    let signature_str = rpc_client.send_and_confirm_transaction(&mut tx, &[&config.keypair])?;
-    Ok(signature_str.to_string())
-}
-
 fn process_show_stake_account(
     rpc_client: &RpcClient,
     _config: &WalletConfig,
     staking_account_pubkey: &Pubkey,
 ) -> ProcessResult {
-    use morgan_stake_api::stake_state::StakeState;
+    use morgan_stake_api::stake_state::{StakeState, STAKE_ACTIVE};
     let stake_account = rpc_client.get_account(staking_account_pubkey)?;
     match stake_account.state() {
         Ok(StakeState::Delegate {
             voter_pubkey,
             credits_observed,
+            stake,
+            deactivated,
         }) => {
             println!("account difs: {}", stake_account.difs);
             println!("voter pubkey: {}", voter_pubkey);
             println!("credits observed: {}", credits_observed);
-            Ok("".to_string())
-        }
-        Ok(StakeState::MiningPool) => {
-            println!("account difs: {}", stake_account.difs);
+            println!("delegated stake: {}", stake);
+            if deactivated != STAKE_ACTIVE {
+                println!("deactivated at tick height: {}", deactivated);
+            }
             Ok("".to_string())
         }
         _ => Err(WalletError::RpcRequestError(
@@ -881,7 +825,7 @@ fn process_pay(
 
 fn process_cancel(rpc_client: &RpcClient, config: &WalletConfig, pubkey: &Pubkey) -> ProcessResult {
     let (blockhash, _fee_calculator) = rpc_client.get_recent_blockhash()?;
-    let ix = budget_instruction::apply_signature(
+    let ix = budget_instruction::cancel(
         &config.keypair.pubkey(),
         pubkey,
         &config.keypair.pubkey(),
@@ -1014,15 +958,6 @@ pub fn process_command(config: &WalletConfig) -> ProcessResult {
             process_create_stake_account(&rpc_client, config, &staking_account_pubkey, *difs)
         }
 
-        WalletCommand::CreateMiningPoolAccount(mining_pool_account_pubkey, difs) => {
-            process_create_mining_pool_account(
-                &rpc_client,
-                config,
-                &mining_pool_account_pubkey,
-                *difs,
-            )
-        }
-
         WalletCommand::DelegateStake(staking_account_keypair, voting_account_pubkey) => {
             process_delegate_stake(
                 &rpc_client,
@@ -1032,18 +967,6 @@ pub fn process_command(config: &WalletConfig) -> ProcessResult {
             )
         }
 
-        WalletCommand::RedeemVoteCredits(
-            mining_pool_account_pubkey,
-            staking_account_pubkey,
-            voting_account_pubkey,
-        ) => process_redeem_vote_credits(
-            &rpc_client,
-            config,
-            &mining_pool_account_pubkey,
-            &staking_account_pubkey,
-            &voting_account_pubkey,
-        ),
-
         WalletCommand::ShowStakeAccount(staking_account_pubkey) => {
             process_show_stake_account(&rpc_client, config, &staking_account_pubkey)
         }
@@ -1370,27 +1293,6 @@ pub fn app<'ab, 'v>(name: &str, about: &'ab str, version: &'v str) -> App<'ab, '
                         .help("Vote account pubkey"),
                 )
         )
-        .subcommand(
-            SubCommand::with_name("create-mining-pool-account")
-                .about("Create staking mining pool account")
-                .arg(
-                    Arg::with_name("mining_pool_account_pubkey")
-                        .index(1)
-                        .value_name("PUBKEY")
-                        .takes_value(true)
-                        .required(true)
-                        .validator(is_pubkey)
-                        .help("Staking mining pool account address to fund"),
-                )
-                .arg(
-                    Arg::with_name("difs")
-                        .index(2)
-                        .value_name("NUM")
-                        .takes_value(true)
-                        .required(true)
-                        .help("The number of difs to assign to the mining pool account"),
-                ),
-        )
        .subcommand(
             SubCommand::with_name("create-stake-account")
                 .about("Create staking account")
@@ -1433,37 +1335,6 @@ pub fn app<'ab, 'v>(name: &str, about: &'ab str, version: &'v str) -> App<'ab, '
                         .help("The voting account to which to delegate the stake."),
                 ),
         )
-        .subcommand(
-            SubCommand::with_name("redeem-vote-credits")
-                .about("Redeem credits in the staking account")
-                .arg(
-                    Arg::with_name("mining_pool_account_pubkey")
-                        .index(1)
-                        .value_name("MINING POOL PUBKEY")
-                        .takes_value(true)
-                        .required(true)
-                        .validator(is_pubkey)
-                        .help("Mining pool account to redeem credits from"),
-                )
-                .arg(
-                    Arg::with_name("staking_account_pubkey")
-                        .index(2)
-                        .value_name("STAKING ACCOUNT PUBKEY")
-                        .takes_value(true)
-                        .required(true)
-                        .validator(is_pubkey)
-                        .help("Staking account address to redeem credits for"),
-                )
-                .arg(
-                    Arg::with_name("voting_account_pubkey")
-                        .index(3)
-                        .value_name("PUBKEY")
-                        .takes_value(true)
-                        .required(true)
-                        .validator(is_pubkey)
-                        .help("The voting account to which the stake was previously delegated."),
-                ),
-        )
         .subcommand(
             SubCommand::with_name("show-stake-account")
                 .about("Show the contents of a stake account")